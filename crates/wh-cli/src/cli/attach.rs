@@ -0,0 +1,182 @@
+//! Attach Command Implementation
+//!
+//! `rift attach <link>` watches another daemon's session from this machine.
+//! The host streams its status and peers over a control stream, and they are
+//! shown in the usual dashboard, read-only. Headless builds print a line
+//! whenever the numbers change.
+
+use anyhow::{bail, Context, Result};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_util::compat::FuturesAsyncReadCompatExt;
+use wh_core::control::ControlMessage;
+use wh_core::{receive_secrets, send_secrets, PeerNetwork, RiftConfig};
+use wh_daemon::attach::{SessionView, ViewerUpdate};
+use wh_daemon::DaemonEvent;
+
+use crate::tui::{self, format_bytes};
+
+/// Attempts at dialing the host while discovery catches up
+const MAX_DIAL_RETRIES: u32 = 20;
+
+/// Run the attach command
+pub async fn run(link: String, no_tui: bool) -> Result<()> {
+    let config = RiftConfig::load_or_default(&RiftConfig::default_config_path())?;
+    let limits = config.history.clone();
+    let mut network = PeerNetwork::new(config).await?;
+    let mut events = network.take_event_receiver();
+    network.start_listening().await?;
+
+    let mut retry_count = 0;
+    let peer_id = loop {
+        let _ = network.poll_once().await;
+        match network.connect(&link).await {
+            Ok(peer_id) => break peer_id,
+            Err(_) if retry_count < MAX_DIAL_RETRIES => {
+                retry_count += 1;
+                tokio::time::sleep(Duration::from_millis(250)).await;
+            }
+            Err(e) => {
+                network.shutdown().await;
+                return Err(e.into());
+            }
+        }
+    };
+
+    let mut control = network.stream_control();
+    let open = control.open_stream(peer_id, network.protocols().control.clone());
+    tokio::pin!(open);
+    let stream = loop {
+        tokio::select! {
+            _ = network.poll_once() => {}
+            Some(_) = events.recv() => {}
+            result = &mut open => break result.with_context(|| format!("failed to open a control stream to {}", peer_id))?,
+        }
+    };
+
+    let mut stream = stream.compat();
+    send_secrets(&mut stream, &ControlMessage::Attach).await?;
+    let (update_tx, mut updates) = mpsc::channel(16);
+    let reader_task = tokio::spawn(async move {
+        while let Ok(update) = receive_secrets::<ViewerUpdate, _>(&mut stream).await {
+            if update_tx.send(update).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let first = loop {
+        tokio::select! {
+            _ = network.poll_once() => {}
+            Some(_) = events.recv() => {}
+            update = updates.recv() => break update,
+        }
+    };
+    let view = match first.map(SessionView::from_update) {
+        Some(Ok(view)) => view,
+        Some(Err(reason)) => {
+            reader_task.abort();
+            network.shutdown().await;
+            bail!("{} refused: {}", peer_id, reason);
+        }
+        None => {
+            reader_task.abort();
+            network.shutdown().await;
+            bail!("{} closed the stream without showing its session", peer_id);
+        }
+    };
+
+    let outcome = if no_tui {
+        watch_headless(&mut network, &mut events, &mut updates, view).await
+    } else {
+        let ports = if view.snapshot.shared_ports.is_empty() {
+            view.snapshot.port.into_iter().collect()
+        } else {
+            view.snapshot.shared_ports.clone()
+        };
+        let (event_tx, event_rx) = mpsc::channel(100);
+        let (command_tx, _commands) = mpsc::channel(8);
+        for event in view.events_since(None) {
+            let _ = event_tx.send(event).await;
+        }
+
+        let dashboard = tui::run_attach_tui(ports, link, &limits, event_rx, command_tx);
+        tokio::pin!(dashboard);
+        let mut previous = view;
+        let mut ended = None;
+        loop {
+            tokio::select! {
+                result = &mut dashboard => {
+                    result?;
+                    break;
+                }
+                _ = network.poll_once() => {}
+                Some(_) = events.recv() => {}
+                update = updates.recv(), if ended.is_none() => match update.map(SessionView::from_update) {
+                    // Never wait on the dashboard here: it only drains while this loop runs
+                    Some(Ok(view)) => {
+                        for event in view.events_since(Some(&previous)) {
+                            let _ = event_tx.try_send(event);
+                        }
+                        previous = view;
+                    }
+                    // The dashboard closes itself on `Shutdown`
+                    Some(Err(reason)) => {
+                        ended = Some(reason);
+                        let _ = event_tx.try_send(DaemonEvent::Shutdown);
+                    }
+                    None => {
+                        ended = Some("Host ended the session".to_string());
+                        let _ = event_tx.try_send(DaemonEvent::Shutdown);
+                    }
+                },
+            }
+        }
+        ended.unwrap_or_else(|| "Detached".to_string())
+    };
+
+    reader_task.abort();
+    network.shutdown().await;
+    println!("{}", outcome);
+    Ok(())
+}
+
+/// Print a line whenever the watched session changes, until it ends or Ctrl-C
+async fn watch_headless(
+    network: &mut PeerNetwork,
+    events: &mut mpsc::Receiver<wh_core::NetworkEvent>,
+    updates: &mut mpsc::Receiver<ViewerUpdate>,
+    view: SessionView,
+) -> String {
+    println!("\n👀 Watching {} (read-only). Press Ctrl-C to leave.\n", view.link);
+    let mut last_line = String::new();
+    let mut view = view;
+    loop {
+        let snapshot = &view.snapshot;
+        let line = format!(
+            "{} · {} peer(s) · {} connection(s) · ↑ {} ↓ {}",
+            snapshot.mode,
+            view.peers.len(),
+            snapshot.active_connections,
+            format_bytes(snapshot.session_bytes_sent),
+            format_bytes(snapshot.session_bytes_received),
+        );
+        if line != last_line {
+            println!("{}", line);
+            last_line = line;
+        }
+
+        view = loop {
+            tokio::select! {
+                _ = network.poll_once() => {}
+                Some(_) = events.recv() => {}
+                update = updates.recv() => match update.map(SessionView::from_update) {
+                    Some(Ok(view)) => break view,
+                    Some(Err(reason)) => return reason,
+                    None => return "Host ended the session".to_string(),
+                },
+                _ = tokio::signal::ctrl_c() => return "Detached".to_string(),
+            }
+        };
+    }
+}
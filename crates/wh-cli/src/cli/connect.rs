@@ -1,24 +1,66 @@
 //! Connect Command Implementation
 
-use anyhow::Result;
-use wh_core::{RiftConfig, PeerId, secrets::{EnvVault, SecretsRequest}};
+use anyhow::{bail, Result};
+use wh_core::{ConfigOverrides, Keystore, Multiaddr, NetworkEvent, PeerCapabilities, RiftConfig, RiftError, PeerId, SharedService, network::PeerIdentity, secrets::{DeliveredSecret, EnvVault}};
+use wh_daemon::secrets_queue::{fetch_secrets, write_env_file};
 use wh_daemon::{DaemonCommand, DaemonServer};
+use std::net::SocketAddr;
 use std::path::PathBuf;
-use tracing::{info, error};
+use std::time::Duration;
+use tracing::{info, error, warn};
 
+use super::dry_run;
 use crate::tui;
 
+/// How long `--list`, `--probe` and service lookups wait for the peer to be reached and answer
+const LIST_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Attempts at dialing the peer while discovery catches up (`--list`, `--probe`, service lookups)
+const MAX_DIAL_RETRIES: u32 = 20;
+
+/// Options of the connect command
+#[derive(Debug, Clone, Default)]
+pub struct ConnectOptions {
+    /// Local port to listen on (defaults to the remote port)
+    pub local_port: Option<u16>,
+    /// Remote ports to forward, each with its local port, instead of the port of the link
+    pub ports: Vec<(u16, u16)>,
+    /// Request secrets from the peer
+    pub request_secrets: bool,
+    /// Save received secrets to this file
+    pub save_secrets: Option<PathBuf>,
+    /// Bind to 0.0.0.0 instead of 127.0.0.1
+    pub public: bool,
+    /// Use a throwaway identity for this session
+    pub ephemeral: bool,
+    /// Stripe each connection across this many streams
+    pub stripe: Option<u8>,
+    /// Override where the secrets key is kept
+    pub keystore: Option<Keystore>,
+    /// Log HTTP requests made through the tunnel
+    pub http_log: bool,
+    /// Skip the startup banner
+    pub quiet: bool,
+    /// Check the setup and print what would happen instead of connecting
+    pub dry_run: bool,
+    /// Fail unless the tunnel is up within this many seconds
+    pub wait_ready: Option<u64>,
+    /// List the services the peer shares instead of connecting
+    pub list: bool,
+    /// Show the optional features the peer supports instead of connecting
+    pub probe: bool,
+    /// Addresses of the peer to dial first
+    pub addrs: Vec<Multiaddr>,
+    /// Connect to the service shared under this name instead of a port
+    pub service: Option<String>,
+}
+
 /// Run the connect command
-pub async fn run(
-    link: String,
-    local_port: Option<u16>,
-    request_secrets: bool,
-    save_secrets: Option<PathBuf>,
-    public: bool,
-    no_tui: bool,
-) -> Result<()> {
-    // Ensure link has the rift:// prefix
-    let link = if link.starts_with("rift://") {
+pub async fn run(link: String, options: ConnectOptions, no_tui: bool) -> Result<()> {
+    let ConnectOptions { local_port, ports, request_secrets, save_secrets, public, ephemeral, stripe, keystore, http_log, quiet, dry_run, wait_ready, list, probe, addrs, service } = options;
+
+    // Ensure link has the rift:// prefix (multiaddrs are turned into links below)
+    let link = if link.starts_with("rift://") || link.starts_with('/') {
         link
     } else if link.starts_with("pk://") {
         // Handle old pk:// prefix for backwards compatibility
@@ -27,28 +69,91 @@ pub async fn run(
         format!("rift://{}", link)
     };
 
-    // Extract port from link if present (format: pk://PEER_ID/PORT)
-    let (peer_link, port) = if let Some(idx) = link.rfind('/') {
+    // Set the query (addresses, link ID) aside (format: rift://PEER_ID/PORT?addrs=...&link=ID)
+    let (link, query) = PeerIdentity::split_link_query(&link);
+    let link_suffix = query.map(|query| format!("?{}", query)).unwrap_or_default();
+
+    // Extract port from link if present (format: pk://PEER_ID/PORT); a complete
+    // multiaddr ends in a protocol value, not in a port
+    let (peer_link, port) = if link.parse::<Multiaddr>().is_ok() {
+        (link.to_string(), 3000)
+    } else if let Some(idx) = link.rfind('/') {
         let port_str = &link[idx + 1..];
         if let Ok(p) = port_str.parse::<u16>() {
-            (link[..idx].to_string(), p)
+            (format!("{}{}", &link[..idx], link_suffix), p)
         } else {
             // No port in link, use default 3000
-            (link.clone(), 3000)
+            (format!("{}{}", link, link_suffix), 3000)
         }
     } else {
-        (link.clone(), 3000)
+        (format!("{}{}", link, link_suffix), 3000)
     };
 
-    let local_port = local_port.unwrap_or(port);
+    // A multiaddr (/ip4/.../p2p/<PEER_ID>[/PORT]) is dialed directly
+    let peer_link = match peer_link.parse::<Multiaddr>() {
+        Ok(addr) => PeerIdentity::link_from_multiaddr(&addr)?,
+        Err(_) if peer_link.starts_with('/') => bail!("Invalid multiaddr: {}", peer_link),
+        Err(_) => peer_link,
+    };
+    let peer_link = PeerIdentity::with_link_addresses(&peer_link, &addrs)?;
 
+    // Create daemon
+    let config_path = RiftConfig::default_config_path();
+    let file_config = RiftConfig::load_or_default(&config_path)?;
+    let mut config = file_config.clone().with_ephemeral_identity(ephemeral);
+    if ephemeral {
+        info!("Using an ephemeral identity for this session");
+    }
+    if let Some(streams) = stripe {
+        config = config.with_striping(streams);
+    }
+    if let Some(keystore) = keystore {
+        config = config.with_keystore(keystore);
+    }
+    if http_log {
+        config = config.with_http_log(true);
+    }
+    // With `-p`, the first forward takes the place of the link's port and the rest share its connection
+    let (port, local_port, forwards) = match ports.split_first() {
+        Some((&(remote, local), rest)) => (remote, Some(local), rest.to_vec()),
+        None => (port, local_port, Vec::new()),
+    };
     let bind_addr = if public { "0.0.0.0" } else { "127.0.0.1" };
-
+    if dry_run {
+        let ip = bind_addr.parse()?;
+        let tunnels: Vec<_> = std::iter::once((port, local_port.unwrap_or(port)))
+            .chain(forwards.iter().copied())
+            .map(|(remote, local)| (remote, SocketAddr::new(ip, local)))
+            .collect();
+        return dry_run::connect(&peer_link, &tunnels, request_secrets, save_secrets.as_deref(), &config, &config_path);
+    }
+    // A DNS host (rift://peers.example.dev/3000) becomes the peer it lists
+    let peer_link = if wh_core::network::is_dns_link(&peer_link) {
+        let resolved = wh_core::resolve_link(&peer_link, config.dns_resolver.as_deref()).await?;
+        info!("Resolved {} to {}", peer_link, resolved);
+        resolved
+    } else {
+        peer_link
+    };
+    if list {
+        return list_services(&peer_link, config, quiet).await;
+    }
+    if probe {
+        return probe_peer(&peer_link, config, quiet).await;
+    }
+    // A named service is looked up for its port, which the local port defaults to
+    let port = match &service {
+        Some(name) => resolve_service(&peer_link, name, config.clone()).await?,
+        None => port,
+    };
+    let local_port = local_port.unwrap_or(port);
     info!("Connecting to {} port {} (local: {}:{})", peer_link, port, bind_addr, local_port);
-
-    // Create daemon
-    let config = RiftConfig::default();
+    let access_log = config.http_log.then(|| config.state_dir().join(wh_daemon::http_log::ACCESS_LOG_FILE));
+    let secrets_config = config.clone();
+    let history_limits = config.history.clone();
+    let overrides = ConfigOverrides::between(&file_config, &config)?;
     let mut daemon = DaemonServer::new(config).await?;
+    daemon.watch_config(config_path, overrides);
 
     // Get handles
     let command_tx = daemon.command_sender();
@@ -57,6 +162,15 @@ pub async fn run(
     // Start the daemon
     daemon.start().await?;
 
+    // Subscribed before the connect command, so the outcome cannot be missed
+    let ready = match wait_ready {
+        Some(secs) => {
+            let peer_id = PeerIdentity::parse_rift_link(&peer_link)?;
+            Some(daemon.wait_until_tunnel_ready(peer_id, Duration::from_secs(secs)))
+        }
+        None => None,
+    };
+
     // Create connect session
     command_tx
         .send(DaemonCommand::Connect {
@@ -64,28 +178,75 @@ pub async fn run(
             port,
             local_port: Some(local_port),
             bind_addr: bind_addr.to_string(),
+            service,
+            forwards: forwards.clone(),
         })
         .await?;
 
     // Request secrets if flag is set
-    if request_secrets {
-        if let Err(e) = request_secrets_from_peer(&peer_link, &save_secrets).await {
-            error!("Failed to request secrets: {}", e);
-            eprintln!("⚠️  Failed to retrieve secrets: {}", e);
+    if request_secrets
+        && let Err(e) = request_secrets_from_peer(&peer_link, &save_secrets, secrets_config).await
+    {
+        let unreachable = matches!(e.downcast_ref::<RiftError>(), Some(RiftError::ConnectionFailed { .. }));
+        match &save_secrets {
+            // The daemon asks again when the host next connects, in this session or a later one
+            Some(path) if unreachable && !ephemeral => {
+                let save_path = std::path::absolute(path)?;
+                let peer_id = PeerIdentity::parse_rift_link(&peer_link)?;
+                eprintln!("⏳ {}; the request is queued and the secrets go to {} once it connects", e, save_path.display());
+                command_tx
+                    .send(DaemonCommand::QueueSecretsRequest { peer_id: peer_id.to_string(), save_path })
+                    .await?;
+            }
+            None if unreachable && !ephemeral => {
+                eprintln!("⚠️  {}; pass --save-secrets FILE to queue the request until it connects", e);
+            }
+            _ => {
+                error!("Failed to request secrets: {}", e);
+                eprintln!("⚠️  Failed to retrieve secrets: {}", e);
+            }
         }
     }
 
-    println!("\n╔══════════════════════════════════════════════════════════════╗");
-    println!("║                     🔗 Rift Connect                          ║");
-    println!("╠══════════════════════════════════════════════════════════════╣");
-    println!("║ Connecting to: {}  ║", format!("{:<42}", peer_link));
-    println!("║ Remote port: {}                                              ║", port);
-    println!("║ Local bind:  {}:{}                                      ║", bind_addr, local_port);
-    println!("║                                                              ║");
-    println!("║ Access the tunnel at: http://{}:{}                   ║", bind_addr, local_port);
-    println!("╚══════════════════════════════════════════════════════════════╝\n");
+    if !quiet {
+        println!("\n╔══════════════════════════════════════════════════════════════╗");
+        println!("║                     🔗 Rift Connect                          ║");
+        println!("╠══════════════════════════════════════════════════════════════╣");
+        println!("║ Connecting to: {:<42}  ║", peer_link);
+        println!("║ Remote port: {}                                              ║", port);
+        println!("║ Local bind:  {}:{}                                      ║", bind_addr, local_port);
+        println!("║                                                              ║");
+        println!("║ Access the tunnel at: http://{}:{}                   ║", bind_addr, local_port);
+        for (remote, local) in &forwards {
+            println!("║ Also forwarding:  {}:{} -> remote port {}             ║", bind_addr, local, remote);
+        }
+        println!("╚══════════════════════════════════════════════════════════════╝\n");
+        if let Some(path) = &access_log {
+            println!("📝 Logging HTTP requests to {}\n", path.display());
+        }
+    }
 
-    if no_tui {
+    if let Some(ready) = ready {
+        // The daemon has to run for the tunnel to come up
+        let running = tokio::spawn(async move { daemon.run().await });
+        let local = match ready.await {
+            Ok(port) => port,
+            Err(e) => {
+                let _ = command_tx.send(DaemonCommand::Shutdown).await;
+                bail!("Tunnel not ready: {}", e);
+            }
+        };
+        if quiet {
+            println!("{}:{}", bind_addr, local);
+        } else {
+            println!("✅ Tunnel ready on {}:{}", bind_addr, local);
+        }
+        if no_tui {
+            running.await??;
+        } else {
+            tui::run_connect_tui(peer_link, port, local_port, &history_limits, event_rx, command_tx).await?;
+        }
+    } else if no_tui {
         // Simple mode - just run the daemon
         daemon.run().await?;
     } else {
@@ -97,96 +258,269 @@ pub async fn run(
         });
         
         // Run TUI in foreground (receives events from daemon)
-        tui::run_connect_tui(peer_link, port, local_port, event_rx, command_tx).await?;
+        tui::run_connect_tui(peer_link, port, local_port, &history_limits, event_rx, command_tx).await?;
     }
 
     Ok(())
 }
 
+/// Ask a peer which services it shares and print a link to each
+async fn list_services(peer_link: &str, config: RiftConfig, quiet: bool) -> Result<()> {
+    let (peer_id, services) = fetch_services(peer_link, config).await?;
+    if quiet {
+        for service in &services {
+            println!("rift://{}/{}", peer_id, service.port);
+        }
+        return Ok(());
+    }
+    if services.is_empty() {
+        println!("rift://{} shares nothing (or does not list its services)", peer_id);
+        return Ok(());
+    }
+    println!("\n📋 Services shared by rift://{}\n", peer_id);
+    for service in &services {
+        println!("    {:<32} rift://{}/{}", service.to_string(), peer_id, service.port);
+    }
+    println!();
+    Ok(())
+}
+
+/// The port a peer shares under a service name
+async fn resolve_service(peer_link: &str, name: &str, config: RiftConfig) -> Result<u16> {
+    let (peer_id, services) = fetch_services(peer_link, config).await?;
+    if let Some(service) = services.iter().find(|service| service.name.as_deref() == Some(name)) {
+        info!("Service {} of {} is port {}", name, peer_id, service.port);
+        return Ok(service.port);
+    }
+    let names: Vec<&str> = services.iter().filter_map(|service| service.name.as_deref()).collect();
+    if names.is_empty() {
+        bail!("rift://{} shares no service named {}", peer_id, name);
+    }
+    bail!("rift://{} shares no service named {} (it shares: {})", peer_id, name, names.join(", "));
+}
+
+/// Reach a peer and print the optional features it announces
+async fn probe_peer(peer_link: &str, config: RiftConfig, quiet: bool) -> Result<()> {
+    let ours = PeerCapabilities::for_config(&config);
+    let mut network = wh_core::PeerNetwork::new(config).await?;
+    network.start_listening().await?;
+    let mut events = network.take_event_receiver();
+
+    let deadline = tokio::time::sleep(LIST_TIMEOUT);
+    tokio::pin!(deadline);
+    let peer_id = dial_peer(&mut network, peer_link).await?;
+    // Identify runs on every new connection, so the answer is on its way
+    let agent_version = loop {
+        tokio::select! {
+            _ = network.poll_once() => {}
+            Some(event) = events.recv() => {
+                if let NetworkEvent::PeerIdentified { peer_id: identified, agent_version } = event
+                    && identified == peer_id
+                {
+                    break agent_version;
+                }
+            }
+            _ = &mut deadline => {
+                network.shutdown().await;
+                bail!("rift://{} did not identify itself within {}s", peer_id, LIST_TIMEOUT.as_secs());
+            }
+        }
+    };
+    network.shutdown().await;
+
+    let Some(capabilities) = PeerCapabilities::from_agent_version(&agent_version) else {
+        if quiet {
+            println!("unknown");
+        } else {
+            println!("rift://{} does not announce its features (an older Rift release?)", peer_id);
+        }
+        return Ok(());
+    };
+    if quiet {
+        println!("{}", capabilities.agent_tokens());
+        return Ok(());
+    }
+    let release = agent_version.split(' ').next().unwrap_or_default();
+    println!("\n🔎 rift://{} runs {}\n", peer_id, release);
+    let yes_no = |on: bool| if on { "yes" } else { "no" };
+    println!("    {:<16} {}", "Secrets", yes_no(capabilities.secrets));
+    println!("    {:<16} {}", "File transfer", yes_no(capabilities.file_transfer));
+    println!("    {:<16} {}", "HTTP logging", yes_no(capabilities.http));
+    println!("    {:<16} {}", "Compression", yes_no(capabilities.compression));
+    println!("    {:<16} {}", "Tunnel protocol", capabilities.max_protocol);
+    if capabilities.max_protocol != ours.max_protocol {
+        println!("\n⚠️  This node speaks tunnel protocol {}; tunnels to the peer will not work", ours.max_protocol);
+    }
+    println!();
+    Ok(())
+}
+
+/// Dial a peer, retrying while discovery catches up
+async fn dial_peer(network: &mut wh_core::PeerNetwork, peer_link: &str) -> Result<PeerId> {
+    let mut retry_count = 0;
+    loop {
+        let _ = network.poll_once().await;
+        match network.connect(peer_link).await {
+            Ok(peer_id) => return Ok(peer_id),
+            Err(_) if retry_count < MAX_DIAL_RETRIES => {
+                retry_count += 1;
+                tokio::time::sleep(Duration::from_millis(250)).await;
+            }
+            Err(e) => {
+                network.shutdown().await;
+                return Err(e.into());
+            }
+        }
+    }
+}
+
+/// Reach a peer and ask which services it shares
+async fn fetch_services(peer_link: &str, config: RiftConfig) -> Result<(PeerId, Vec<SharedService>)> {
+    let mut network = wh_core::PeerNetwork::new(config).await?;
+    network.start_listening().await?;
+    let mut events = network.take_event_receiver();
+
+    let deadline = tokio::time::sleep(LIST_TIMEOUT);
+    tokio::pin!(deadline);
+    let peer_id = dial_peer(&mut network, peer_link).await?;
+
+    // The query runs in its own task so the network keeps being polled meanwhile
+    let mut control = network.stream_control();
+    let protocols = network.protocols().clone();
+    let mut query = tokio::spawn(async move { wh_core::query_services(&mut control, &protocols, peer_id).await });
+    let result = loop {
+        tokio::select! {
+            _ = network.poll_once() => {}
+            Some(_) = events.recv() => {}
+            result = &mut query => break result?,
+            _ = &mut deadline => {
+                query.abort();
+                break Err(wh_core::RiftError::ConnectionFailed {
+                    peer_id: peer_id.to_string(),
+                    reason: format!("no answer within {}s", LIST_TIMEOUT.as_secs()),
+                });
+            }
+        }
+    };
+    network.shutdown().await;
+
+    let services = result.map_err(|e| anyhow::anyhow!("Cannot list services of {}: {}", peer_id, e))?;
+    Ok((peer_id, services))
+}
+
 /// Request secrets from a peer
 async fn request_secrets_from_peer(
     peer_link: &str,
     save_path: &Option<PathBuf>,
+    config: RiftConfig,
 ) -> Result<()> {
-    use wh_core::{network::{PeerIdentity, SECRETS_PROTOCOL}, send_secrets, receive_secrets};
     use tokio_util::compat::FuturesAsyncReadCompatExt;
     
     info!("Requesting secrets from peer");
     
-    // Create a temporary network just for secrets request
-    let config = RiftConfig::default();
-    let mut network = wh_core::PeerNetwork::new(config).await?;
-    
+    // Create our vault to get our public key (fresh keys if we must stay unlinkable)
+    let vault = if config.ephemeral_identity {
+        EnvVault::new()
+    } else {
+        let mut vault = EnvVault::with_keypair(EnvVault::load_identity_for(&config)?);
+        // Local values for secrets the host keeps to itself; the file is optional
+        let local = std::path::Path::new(".env.rift.tmp");
+        match std::fs::metadata(local) {
+            Ok(_) => vault
+                .load_env_file(local)
+                .map_err(|e| anyhow::anyhow!("Cannot read {}: {}", local.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => bail!("Cannot read {}: {}", local.display(), e),
+        }
+        vault
+    };
+
+    let spool = config.spool();
     // Parse peer ID from link
     let peer_id: PeerId = PeerIdentity::parse_rift_link(peer_link)?;
-    
-    // Connect to peer
+
+    // What this host sent last time, so it only sends changes (never kept for ephemeral identities)
+    let store = (!config.ephemeral_identity).then(|| config.open_state_store()).transpose()?;
+    let host = peer_id.to_string();
+    let previous = store.as_ref().and_then(|store| vault.load_received(store.as_ref(), &host));
+
+    let mut network = wh_core::PeerNetwork::new(config).await?;
+    let mut events = network.take_event_receiver();
+    network.start_listening().await?;
+    let unreachable = |reason: String| RiftError::ConnectionFailed { peer_id: host.clone(), reason };
+
+    // Connect to peer; the network is polled until the stream opens or the host proves unreachable
     network.connect(peer_link).await?;
-    info!("Connected to peer for secrets request");
-    
-    // Create our vault to get our public key
-    let vault = EnvVault::from_file(".env.rift.tmp")
-        .unwrap_or_else(|_| {
-            // If no file exists, create a new vault with identity
-            let keypair = EnvVault::load_or_create_identity()
-                .expect("Failed to load identity");
-            EnvVault::with_keypair(keypair)
-        });
-    
-    // Open a secrets stream
     let mut control = network.stream_control();
-    let stream = control
-        .open_stream(peer_id, SECRETS_PROTOCOL)
-        .await
-        .map_err(|e| anyhow::anyhow!("Failed to open secrets stream: {:?}", e))?;
-    
+    let open = control.open_stream(peer_id, network.protocols().secrets.clone());
+    tokio::pin!(open);
+    let deadline = tokio::time::sleep(LIST_TIMEOUT);
+    tokio::pin!(deadline);
+    let stream = loop {
+        tokio::select! {
+            _ = network.poll_once() => {}
+            Some(_) = events.recv() => {}
+            result = &mut open => break result.map_err(|e| unreachable(format!("cannot open a secrets stream: {:?}", e)))?,
+            _ = &mut deadline => return Err(unreachable(format!("not reachable within {}s", LIST_TIMEOUT.as_secs())).into()),
+        }
+    };
     info!("Opened secrets stream");
-    
-    // Split the stream
-    let stream = stream.compat();
-    let (mut read, mut write) = tokio::io::split(stream);
-    
-    // Send our public key in a SecretsRequest
-    let request = SecretsRequest {
-        public_key: vault.public_key().to_vec(),
+
+    // Send our public key and what we hold, then decrypt the answer, filling in the unchanged secrets
+    let exchange = fetch_secrets(stream.compat(), &vault, previous.as_ref());
+    tokio::pin!(exchange);
+    let (response, received) = loop {
+        tokio::select! {
+            _ = network.poll_once() => {}
+            Some(_) = events.recv() => {}
+            result = &mut exchange => break result.map_err(|e| anyhow::anyhow!("Secrets exchange failed: {}", e))?,
+        }
     };
-    
-    send_secrets(&mut write, &request).await
-        .map_err(|e| anyhow::anyhow!("Failed to send secrets request: {}", e))?;
-    
-    info!("Sent secrets request");
-    
-    // Receive the encrypted secrets response
-    let response = receive_secrets(&mut read).await
-        .map_err(|e| anyhow::anyhow!("Failed to receive secrets response: {}", e))?;
-    
+    network.shutdown().await;
     info!("Received secrets response");
-    
-    // Decrypt the secrets
-    let secrets = vault.decrypt_from_peer(&response)
-        .map_err(|e| anyhow::anyhow!("Failed to decrypt secrets: {}", e))?;
+
+    if let Some(store) = &store
+        && let Err(e) = vault.save_received(store.as_ref(), &host, &received)
+    {
+        warn!("Failed to keep the received secrets for next time: {}", e);
+    }
+    let delivered = &received.secrets;
+    let mut keys: Vec<&String> = delivered.keys().collect();
+    keys.sort();
     
     println!("\n🔐 Successfully received and decrypted shared secrets!");
     println!("\n╔══════════════════════════════════════════════════════════════╗");
     println!("║                      Shared Secrets                          ║");
     println!("╠══════════════════════════════════════════════════════════════╣");
     
-    for (key, value) in &secrets {
-        println!("║ {:<28} = {:<30} ║", key, value);
+    let mut missing = Vec::new();
+    for key in keys {
+        let secret = &delivered[key];
+        let value = vault.resolve(secret);
+        let shown = match (secret, &value) {
+            (DeliveredSecret::Masked { masked }, _) => format!("{} (masked)", masked),
+            (DeliveredSecret::Reference { .. }, Some(value)) => format!("{} (yours)", value),
+            (DeliveredSecret::Reference { reference }, None) => {
+                missing.push(reference.as_str());
+                "(set it locally)".to_string()
+            }
+            (DeliveredSecret::Value(value), _) => value.clone(),
+        };
+        println!("║ {:<28} = {:<30} ║", key, shown);
     }
     
     println!("╚══════════════════════════════════════════════════════════════╝\n");
+    if !response.unchanged.is_empty() {
+        println!("   {} of them unchanged since the last request\n", response.unchanged.len());
+    }
+    if !missing.is_empty() {
+        println!("⚠️  The host keeps {} to itself; set them in your environment or .env.rift.tmp\n", missing.join(", "));
+    }
     
     // Save to file if requested
     if let Some(path) = save_path {
-        let mut content = String::new();
-        for (key, value) in &secrets {
-            content.push_str(&format!("{}={}\n", key, value));
-        }
-        
-        std::fs::write(path, content)
+        write_env_file(&spool, &vault, &received, path)
             .map_err(|e| anyhow::anyhow!("Failed to write secrets to file: {}", e))?;
-        
         println!("✅ Secrets saved to: {}", path.display());
     }
     
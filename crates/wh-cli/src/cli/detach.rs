@@ -0,0 +1,45 @@
+//! Detached Sessions
+//!
+//! `--detach` re-runs the current command line in the background with
+//! `--quiet`, waits for it to print its link and hands that back, so the
+//! foreground process can exit while the session keeps running.
+
+use anyhow::{bail, Context, Result};
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+
+/// Start this command again in the background and return the first line it prints
+///
+/// Also returns the process ID of the background session.
+pub fn spawn() -> Result<(String, u32)> {
+    let exe = std::env::current_exe().context("cannot locate the rift executable")?;
+    let mut args: Vec<_> = std::env::args_os().skip(1).filter(|arg| arg != "--detach").collect();
+    if !args.iter().any(|arg| arg == "--quiet" || arg == "-q") {
+        args.push("--quiet".into());
+    }
+
+    let mut command = Command::new(exe);
+    command
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null());
+    // Own process group, so Ctrl-C in this terminal does not stop the session
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+
+    let mut child = command.spawn().context("failed to start the background session")?;
+    let stdout = child.stdout.take().context("background session has no stdout")?;
+    let mut line = String::new();
+    BufReader::new(stdout).read_line(&mut line)?;
+
+    let line = line.trim().to_string();
+    if line.is_empty() {
+        let status = child.wait()?;
+        bail!("background session exited before it was ready ({})", status);
+    }
+    Ok((line, child.id()))
+}
@@ -0,0 +1,69 @@
+//! Discover Command Implementation
+
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::time::Duration;
+use wh_core::{NetworkEvent, PeerNetwork, RiftConfig};
+
+/// Run the discover command
+pub async fn run(watch: bool, timeout_secs: u64) -> Result<()> {
+    let config = RiftConfig::load_or_default(&RiftConfig::default_config_path())?;
+    let mut network = PeerNetwork::new(config).await?;
+    let mut events = network.take_event_receiver();
+    network.start_listening().await?;
+
+    if watch {
+        println!("\n🔍 Watching the local network for Rift peers (Ctrl-C to stop)\n");
+    } else {
+        println!("\n🔍 Looking for Rift peers on the local network ({}s)...\n", timeout_secs);
+    }
+
+    let mut found: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let deadline = tokio::time::sleep(Duration::from_secs(timeout_secs));
+    tokio::pin!(deadline);
+
+    loop {
+        tokio::select! {
+            _ = network.poll_once() => {}
+            Some(event) = events.recv() => match event {
+                NetworkEvent::PeerDiscovered { peer_id, addresses } => {
+                    let addresses: Vec<String> = addresses.iter().map(|a| a.to_string()).collect();
+                    if watch {
+                        let verb = if found.contains_key(&peer_id.to_string()) { "~" } else { "+" };
+                        println!("{} rift://{}", verb, peer_id);
+                        for addr in &addresses {
+                            println!("    {}", addr);
+                        }
+                    }
+                    found.insert(peer_id.to_string(), addresses);
+                }
+                NetworkEvent::PeerExpired { peer_id } => {
+                    if watch {
+                        println!("- rift://{}", peer_id);
+                    }
+                    found.remove(&peer_id.to_string());
+                }
+                _ => {}
+            },
+            _ = &mut deadline, if !watch => break,
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+
+    network.shutdown().await;
+
+    if !watch {
+        if found.is_empty() {
+            println!("No peers found.\n");
+        }
+        for (peer_id, addresses) in &found {
+            println!("rift://{}", peer_id);
+            for addr in addresses {
+                println!("    {}", addr);
+            }
+        }
+        println!();
+    }
+
+    Ok(())
+}
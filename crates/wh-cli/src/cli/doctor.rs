@@ -0,0 +1,93 @@
+//! Doctor Command Implementation
+//!
+//! Joins the network for a while and reports how reachable this machine is:
+//! the NAT type and the evidence behind it.
+
+use anyhow::Result;
+use std::collections::HashSet;
+use std::time::Duration;
+use wh_core::{NatType, NetworkEvent, PeerNetwork, RiftConfig};
+
+/// Run the doctor command
+pub async fn run(timeout_secs: u64) -> Result<()> {
+    let config = RiftConfig::load_or_default(&RiftConfig::default_config_path())?;
+    let mut network = PeerNetwork::new(config).await?;
+    let mut events = network.take_event_receiver();
+    let mut listening = network.start_listening().await?;
+
+    println!("\n🩺 Checking connectivity ({}s, Ctrl-C to stop early)...\n", timeout_secs);
+
+    let mut peers = HashSet::new();
+    let deadline = tokio::time::sleep(Duration::from_secs(timeout_secs));
+    tokio::pin!(deadline);
+
+    loop {
+        tokio::select! {
+            _ = network.poll_once() => {}
+            Some(event) = events.recv() => match event {
+                NetworkEvent::Listening { address } => listening.push(address),
+                NetworkEvent::PeerConnected { peer_id, .. } => {
+                    peers.insert(peer_id);
+                }
+                NetworkEvent::NatTypeChanged { nat_type } => println!("  NAT looks {}", nat_type),
+                NetworkEvent::RelayReservationAccepted { relay_peer_id, renewal: false } => {
+                    println!("  Relay {} holds a slot for us", relay_peer_id);
+                }
+                NetworkEvent::RelayReservationFailed { relay_peer_id, reason } => {
+                    println!("  Relay {} gave no slot: {}", relay_peer_id, reason);
+                }
+                NetworkEvent::RelayReservationLost { relay_peer_id, reason } => {
+                    println!("  Relay {} dropped our slot: {}", relay_peer_id, reason);
+                }
+                _ => {}
+            },
+            _ = &mut deadline => break,
+            _ = tokio::signal::ctrl_c() => break,
+        }
+        // AutoNAT has the final say; stop once it and the observations agree on something
+        let report = network.nat_report();
+        if report.reachable.is_some() && report.nat_type != NatType::Unknown {
+            break;
+        }
+    }
+
+    let report = network.nat_report();
+    let reservations = network.relay_reservations();
+    let discovery = network.discovery_mechanisms();
+    network.shutdown().await;
+
+    listening.sort();
+    listening.dedup();
+    println!();
+    println!("Listening:   {}", listening.first().map(ToString::to_string).unwrap_or_else(|| "-".to_string()));
+    for address in listening.iter().skip(1) {
+        println!("             {}", address);
+    }
+    println!("Peers seen:  {}", peers.len());
+    if discovery.is_empty() {
+        println!("Discovery:   none (peers need a full link to reach us)");
+    } else {
+        println!("Discovery:   {}", discovery.join(", "));
+    }
+    println!("Observed at: {}", report.observed.first().map(ToString::to_string).unwrap_or_else(|| "-".to_string()));
+    for address in report.observed.iter().skip(1) {
+        println!("             {}", address);
+    }
+    let autonat = match report.reachable {
+        Some(true) => "reachable from the internet",
+        Some(false) => "not reachable from the internet",
+        None => "no verdict",
+    };
+    println!("AutoNAT:     {}", autonat);
+    println!("Hole punch:  {} succeeded, {} failed", report.hole_punches_succeeded, report.hole_punches_failed);
+    match reservations.first() {
+        Some(relay) => println!("Relay slots: {}", relay),
+        None => println!("Relay slots: none (peers that cannot dial us have no way in)"),
+    }
+    for relay in reservations.iter().skip(1) {
+        println!("             {}", relay);
+    }
+    println!("\nNAT type:    {} — {}\n", report.nat_type, report.nat_type.advice());
+
+    Ok(())
+}
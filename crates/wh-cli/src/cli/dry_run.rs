@@ -0,0 +1,361 @@
+//! Dry Run
+//!
+//! Checks what `share` and `connect` need (config, identity, ports, secrets,
+//! link) and prints what they would do, without starting the daemon or
+//! touching the network. Exits with an error if any check fails.
+
+use anyhow::{bail, Result};
+use std::net::{SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::path::Path;
+use std::time::Duration;
+use wh_core::network::{is_dns_link, listen_addresses, load_swarm_key, PeerIdentity};
+use wh_core::{EnvVault, ExitRequest, PreflightAction, PreflightConfig, Redaction, RiftConfig};
+use wh_daemon::audit::{AuditLog, ChainStatus};
+use wh_daemon::preflight;
+
+/// How long to wait for the shared service to accept a probe connection
+const PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Outcome of a single check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Ok,
+    Warn,
+    Fail,
+}
+
+/// Checks and planned actions, printed together at the end
+#[derive(Debug, Default)]
+struct Report {
+    checks: Vec<(Status, &'static str, String)>,
+    plan: Vec<(&'static str, String)>,
+}
+
+impl Report {
+    fn check(&mut self, status: Status, what: &'static str, detail: impl Into<String>) {
+        self.checks.push((status, what, detail.into()));
+    }
+
+    fn plan(&mut self, what: &'static str, detail: impl Into<String>) {
+        self.plan.push((what, detail.into()));
+    }
+
+    /// Print the report; fails if any check failed
+    fn finish(self, title: &str) -> Result<()> {
+        println!("\n{} (dry run)\n", title);
+        for (status, what, detail) in &self.checks {
+            let mark = match status {
+                Status::Ok => "✓",
+                Status::Warn => "!",
+                Status::Fail => "✗",
+            };
+            println!("  {} {:<12} {}", mark, what, detail);
+        }
+        println!("\nWould:");
+        for (what, detail) in &self.plan {
+            println!("  {:<12} {}", what, detail);
+        }
+        println!();
+
+        let failed = self.checks.iter().filter(|(status, _, _)| *status == Status::Fail).count();
+        if failed > 0 {
+            bail!("dry run found {} problem(s)", failed);
+        }
+        Ok(())
+    }
+
+    /// Checks shared by share and connect: config file, identity, swarm key
+    fn node(&mut self, config: &RiftConfig, config_path: &Path) {
+        if config_path.exists() {
+            self.check(Status::Ok, "Config", config_path.display().to_string());
+        } else {
+            self.check(Status::Ok, "Config", format!("defaults ({} not found)", config_path.display()));
+        }
+
+        if config.ephemeral_identity {
+            self.check(Status::Ok, "Identity", "ephemeral, generated for this session");
+        } else if config.identity_path.exists() {
+            match PeerIdentity::load(&config.identity_path) {
+                Ok(identity) => self.check(Status::Ok, "Identity", identity.peer_id_string()),
+                Err(e) => self.check(Status::Fail, "Identity", format!("{}: {}", config.identity_path.display(), e)),
+            }
+        } else {
+            self.check(Status::Warn, "Identity", format!("none yet, would generate {}", config.identity_path.display()));
+        }
+
+        match config.open_state_store() {
+            Ok(store) => {
+                self.check(Status::Ok, "State", format!("{} backend", config.state.backend));
+                let audit_log = AuditLog::new(store);
+                match audit_log.verify() {
+                    Ok(ChainStatus::Intact(0)) => {}
+                    Ok(ChainStatus::Intact(records)) => self.check(Status::Ok, "Audit log", format!("{} record(s), hash chain intact", records)),
+                    Ok(ChainStatus::Broken(index)) => self.check(
+                        Status::Fail,
+                        "Audit log",
+                        format!("hash chain broken at record {} of {}", index + 1, audit_log.location()),
+                    ),
+                    Err(e) => self.check(Status::Warn, "Audit log", e.to_string()),
+                }
+            }
+            Err(e) => self.check(Status::Fail, "State", e.to_string()),
+        }
+
+        match listen_addresses(config) {
+            Ok(addrs) if !config.listen_addrs.is_empty() => {
+                let list: Vec<String> = addrs.iter().map(ToString::to_string).collect();
+                self.check(Status::Ok, "Listen on", list.join(", "));
+            }
+            Ok(_) => {}
+            Err(e) => self.check(Status::Fail, "Listen on", e.to_string()),
+        }
+
+        match config.swarm_key() {
+            Some(path) => match load_swarm_key(&path) {
+                Ok(_) => self.check(Status::Ok, "Swarm key", format!("private network ({})", path.display())),
+                Err(e) => self.check(Status::Fail, "Swarm key", format!("{}: {}", path.display(), e)),
+            },
+            None => self.plan("Network", network_summary(config)),
+        }
+    }
+
+    /// Check that a local TCP port can be bound
+    fn tcp_port(&mut self, what: &'static str, addr: SocketAddr) {
+        match TcpListener::bind(addr) {
+            Ok(_) => self.check(Status::Ok, what, format!("tcp {} is free", addr)),
+            Err(e) => self.check(Status::Fail, what, format!("cannot bind tcp {}: {}", addr, e)),
+        }
+    }
+
+    /// Check that a local UDP port can be bound (0 is always free)
+    fn udp_port(&mut self, what: &'static str, port: u16) {
+        if port == 0 {
+            self.check(Status::Ok, what, "udp, any free port");
+            return;
+        }
+        match UdpSocket::bind(("0.0.0.0", port)) {
+            Ok(_) => self.check(Status::Ok, what, format!("udp {} is free", port)),
+            Err(e) => self.check(Status::Fail, what, format!("cannot bind udp {}: {}", port, e)),
+        }
+    }
+}
+
+/// Dry run of `rift share`
+pub fn share(
+    ports: &[u16],
+    forwards: &[(String, u16)],
+    secrets: Option<&Path>,
+    auto_approve: bool,
+    link_page: Option<u16>,
+    config: &RiftConfig,
+    config_path: &Path,
+) -> Result<()> {
+    let mut report = Report::default();
+    report.node(config, config_path);
+
+    // Ports of the node itself
+    report.udp_port("Listen", config.listen_port);
+    if config.websocket.enabled {
+        report.tcp_port("WebSocket", SocketAddr::from(([0, 0, 0, 0], config.websocket.port)));
+    }
+    if config.webrtc.enabled {
+        report.udp_port("WebRTC", config.webrtc.port);
+    }
+    if let Some(page_port) = link_page.filter(|page_port| *page_port != 0) {
+        report.tcp_port("Link page", SocketAddr::from(([127, 0, 0, 1], page_port)));
+    }
+
+    // The shared services (with their names) and their SNI routes
+    let mut targets: Vec<(String, u16)> = ports
+        .iter()
+        .map(|port| {
+            let mut names: Vec<&str> = config
+                .service_names
+                .iter()
+                .filter(|(_, named)| *named == port)
+                .map(|(name, _)| name.as_str())
+                .collect();
+            names.sort();
+            (names.join(", "), *port)
+        })
+        .collect();
+    let mut routes: Vec<_> = config.sni_routes.iter().map(|(name, port)| (name.clone(), *port)).collect();
+    routes.sort();
+    targets.extend(routes);
+    for (server_name, target_port) in &targets {
+        let addr = SocketAddr::from(([127, 0, 0, 1], *target_port));
+        let route = if server_name.is_empty() { String::new() } else { format!(" ({})", server_name) };
+        match TcpStream::connect_timeout(&addr, PROBE_TIMEOUT) {
+            Ok(_) => report.check(Status::Ok, "Target", format!("localhost:{}{} is accepting connections", target_port, route)),
+            Err(_) => report.check(Status::Warn, "Target", format!("nothing listening on localhost:{}{} yet", target_port, route)),
+        }
+    }
+    // Probed above already; only other daemons sharing the ports are left to check
+    let conflicts = PreflightConfig {
+        listener: PreflightAction::Off,
+        ..config.preflight.clone()
+    };
+    let store = config.open_state_store().ok();
+    for (action, issue) in preflight::check_share(ports, &conflicts, store.as_deref()) {
+        let status = if action == PreflightAction::Fail { Status::Fail } else { Status::Warn };
+        report.check(status, "Conflict", issue.to_string());
+    }
+    // Other machines are not probed: that would touch the network
+    let policy = config.share_target_policy();
+    for (host, port) in forwards {
+        let target = ExitRequest { host: host.clone(), port: *port };
+        if policy.allows(&target) {
+            report.check(Status::Ok, "Target", format!("{} is allowed by share_targets", target));
+        } else {
+            report.check(Status::Fail, "Target", format!("{} is not in share_targets", target));
+        }
+    }
+
+    if let Some(path) = secrets {
+        let mut vault = EnvVault::new();
+        match vault.load_env_file(path) {
+            Ok(()) => {
+                let held_back = vault.secrets().keys().filter(|key| config.secrets_redaction.get(*key).is_some_and(|r| *r != Redaction::Full)).count();
+                let redacted = match held_back {
+                    0 => String::new(),
+                    n => format!(", {} masked or by reference", n),
+                };
+                report.check(Status::Ok, "Secrets", format!("{} ({} variables{})", path.display(), vault.len(), redacted))
+            }
+            Err(e) => report.check(Status::Fail, "Secrets", format!("{}: {}", path.display(), e)),
+        }
+    }
+
+    for port in ports {
+        report.plan("Share", format!("localhost:{}", port));
+    }
+    for (host, port) in forwards {
+        report.plan("Share", ExitRequest { host: host.clone(), port: *port }.to_string());
+    }
+    for (server_name, target_port) in targets.iter().skip(ports.len()) {
+        report.plan("Route", format!("TLS {} -> localhost:{}", server_name, target_port));
+    }
+    let protocols: Vec<String> = config.protocol_policy.allow.iter().map(ToString::to_string).collect();
+    let overrides = match config.protocol_policy.peers.len() {
+        0 => String::new(),
+        n => format!(" ({} peer(s) with their own list)", n),
+    };
+    report.plan("Offer", format!("{}{}", protocols.join(", "), overrides));
+    report.plan("Approve", approval_summary(auto_approve, config));
+    if config.links.require {
+        let expiry = match config.links.default_ttl_secs {
+            0 => "no expiry".to_string(),
+            secs => format!("expires after {}s", secs),
+        };
+        report.plan("Links", format!("issue a revocable link ID ({})", expiry));
+    }
+    report.plan("Rate limit", match config.rate_limit.max_bytes_per_sec {
+        0 => "none".to_string(),
+        rate => format!("{} bytes/s per {:?}", rate, config.rate_limit.scope).to_lowercase(),
+    });
+    if config.max_request_body_bytes > 0 {
+        report.plan("Uploads", format!("refuse HTTP bodies over {} bytes", config.max_request_body_bytes));
+    }
+    let limits = &config.resource_limits;
+    if limits.max_tasks > 0 || limits.max_buffered_bytes > 0 || limits.max_rss_bytes > 0 {
+        report.plan(
+            "Limits",
+            format!("tasks {}, buffers {} bytes, memory {} bytes (0 = off)", limits.max_tasks, limits.max_buffered_bytes, limits.max_rss_bytes),
+        );
+    }
+
+    report.finish("🔑 Rift Share")
+}
+
+/// Dry run of `rift connect`
+pub fn connect(
+    peer_link: &str,
+    tunnels: &[(u16, SocketAddr)],
+    request_secrets: bool,
+    save_secrets: Option<&Path>,
+    config: &RiftConfig,
+    config_path: &Path,
+) -> Result<()> {
+    let mut report = Report::default();
+    report.node(config, config_path);
+
+    match PeerIdentity::parse_rift_link(peer_link) {
+        Ok(peer_id) => {
+            let addresses = PeerIdentity::link_addresses(peer_link).len();
+            let link_id = PeerIdentity::link_id(peer_link)
+                .map(|id| format!(", link ID {}", id))
+                .unwrap_or_default();
+            report.check(Status::Ok, "Link", format!("peer {}, {} embedded address(es){}", peer_id, addresses, link_id));
+        }
+        Err(_) if is_dns_link(peer_link) => {
+            let resolver = config.dns_resolver.as_deref().unwrap_or("the system resolver");
+            report.check(Status::Ok, "Link", format!("DNS name, looked up via {} when connecting", resolver));
+        }
+        Err(e) => report.check(Status::Fail, "Link", format!("{}: {}", peer_link, e)),
+    }
+
+    for (_, local_addr) in tunnels {
+        report.tcp_port("Local port", *local_addr);
+    }
+
+    if let Some(path) = save_secrets {
+        let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        if dir.is_dir() {
+            report.check(Status::Ok, "Save to", path.display().to_string());
+        } else {
+            report.check(Status::Fail, "Save to", format!("{}: directory does not exist", dir.display()));
+        }
+    }
+
+    for (port, local_addr) in tunnels {
+        report.plan("Tunnel", format!("{} -> remote port {}", local_addr, port));
+    }
+    if config.striping.streams > 1 {
+        report.plan("Stripe", format!("{} streams per connection", config.striping.streams));
+    }
+    if request_secrets {
+        report.plan("Secrets", "request the peer's shared secrets");
+    }
+    if config.http_log {
+        report.plan("HTTP log", config.state_dir().join(wh_daemon::http_log::ACCESS_LOG_FILE).display().to_string());
+    }
+    let retry = &config.dial_retry;
+    report.plan("Dial", format!("{} attempt(s), backoff {}-{}ms", retry.attempts, retry.initial_backoff_ms, retry.max_backoff_ms));
+    report.plan("Reconnect", if config.auto_reconnect.enabled { "re-dial the host if the connection drops" } else { "off" });
+
+    report.finish("🔗 Rift Connect")
+}
+
+/// How the node reaches peers outside a private network
+fn network_summary(config: &RiftConfig) -> String {
+    let mut parts = vec!["public network".to_string()];
+    if config.enable_mdns {
+        parts.push("mDNS".to_string());
+    }
+    if config.enable_relay {
+        parts.push("relays".to_string());
+    }
+    if let Some(server) = &config.rendezvous_server {
+        parts.push(format!("rendezvous {}", server));
+    }
+    parts.join(", ")
+}
+
+/// Who gets a tunnel without being prompted
+fn approval_summary(auto_approve: bool, config: &RiftConfig) -> String {
+    let access = &config.peer_access;
+    let mut summary = if auto_approve {
+        "every peer automatically (insecure)".to_string()
+    } else if access.allow.is_empty() {
+        "after a prompt for each peer".to_string()
+    } else {
+        format!("only {} allowlisted peer(s)", access.allow.len())
+    };
+    if !access.block.is_empty() {
+        summary.push_str(&format!(", {} blocked", access.block.len()));
+    }
+    if config.reject_ephemeral_peers {
+        summary.push_str(", ephemeral peers refused");
+    }
+    summary
+}
@@ -0,0 +1,301 @@
+//! Exit Command Implementation
+//!
+//! `rift exit serve` lets named peers open connections from this machine to
+//! destinations on an allowlist (localhost by default); `rift exit connect`
+//! offers such a host as a local SOCKS5 proxy. Every request the host sees
+//! is written to the audit log.
+
+use anyhow::{bail, Context, Result};
+use futures::StreamExt;
+use std::io::Write;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::compat::FuturesAsyncReadCompatExt;
+use wh_core::{
+    open_exit_stream, receive_secrets, send_secrets, ExitPolicy, ExitRequest, ExitResponse, PeerId, PeerNetwork, Protocols,
+    RiftConfig, RiftProtocol, Stream,
+};
+use wh_daemon::audit::AuditLog;
+
+use super::ExitAction;
+
+/// Attempts at dialing the host while discovery catches up
+const MAX_DIAL_RETRIES: u32 = 20;
+
+/// How long the host waits for a destination to accept
+const DESTINATION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// SOCKS5 reply codes
+const SOCKS_SUCCEEDED: u8 = 0x00;
+const SOCKS_GENERAL_FAILURE: u8 = 0x01;
+const SOCKS_NOT_ALLOWED: u8 = 0x02;
+const SOCKS_COMMAND_NOT_SUPPORTED: u8 = 0x07;
+const SOCKS_ADDRESS_NOT_SUPPORTED: u8 = 0x08;
+
+/// Run the exit command
+pub async fn run(action: ExitAction) -> Result<()> {
+    match action {
+        ExitAction::Serve { peers, allow, yes } => serve(peers, allow, yes).await,
+        ExitAction::Connect { link, socks_port } => connect(link, socks_port).await,
+    }
+}
+
+async fn serve(peers: Vec<String>, allow: Vec<String>, yes: bool) -> Result<()> {
+    let peers = peers
+        .iter()
+        .map(|peer| peer.parse::<PeerId>().with_context(|| format!("invalid peer ID '{}'", peer)))
+        .collect::<Result<Vec<_>>>()?;
+    let mut exit_policy = ExitPolicy::localhost();
+    for rule in &allow {
+        exit_policy = exit_policy.with_rule(rule)?;
+    }
+    let destinations = exit_policy.describe().join(", ");
+
+    println!("\n⚠️  Exit mode lets these peers open connections FROM THIS MACHINE:\n");
+    for peer in &peers {
+        println!("   {}", peer);
+    }
+    println!("\n   Allowed destinations:");
+    for destination in exit_policy.describe() {
+        println!("   - {}", destination);
+    }
+    println!("\n   They can reach anything listening there, including services bound to");
+    println!("   localhost only. Only continue for peers you trust with this machine.\n");
+    if !yes && !confirm("Type 'yes' to enable exit mode: ")? {
+        bail!("exit mode not enabled");
+    }
+
+    let config = RiftConfig::load_or_default(&RiftConfig::default_config_path())?;
+    let state = config.open_state_store()?;
+    let config = config.with_state_store(state.clone());
+    let protocol_policy = config.protocol_policy.clone();
+    let audit = AuditLog::new(state);
+    let mut network = PeerNetwork::new(config).await?;
+    let mut events = network.take_event_receiver();
+    let mut incoming = network.take_incoming_exit_streams();
+    network.start_listening().await?;
+
+    for peer in &peers {
+        audit.record(&peer.to_string(), "exit_enabled", &destinations);
+    }
+    println!("\n🚪 Exit mode on. Peers connect with: rift exit connect {}", network.rift_link());
+    println!("   Requests are recorded in {}. Press Ctrl+C to stop.\n", audit.location());
+
+    loop {
+        tokio::select! {
+            _ = network.poll_once() => {}
+            Some(_) = events.recv() => {}
+            Some((peer_id, stream)) = incoming.next() => {
+                if !peers.contains(&peer_id) || !protocol_policy.is_allowed(&peer_id, RiftProtocol::Exit) {
+                    audit.record(&peer_id.to_string(), "exit_rejected", "peer not allowed to use exit mode");
+                    continue;
+                }
+                tokio::spawn(serve_request(peer_id, stream, exit_policy.clone(), audit.clone()));
+            }
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+
+    for peer in &peers {
+        audit.record(&peer.to_string(), "exit_disabled", "host stopped exit mode");
+    }
+    network.shutdown().await;
+    println!("\n🚪 Exit mode off");
+    Ok(())
+}
+
+/// Check one exit request and relay it if allowed
+async fn serve_request(peer_id: PeerId, stream: Stream, policy: ExitPolicy, audit: AuditLog) {
+    let peer = peer_id.to_string();
+    let mut stream = stream.compat();
+    let request: ExitRequest = match receive_secrets(&mut stream).await {
+        Ok(request) => request,
+        Err(e) => {
+            audit.record(&peer, "exit_rejected", &format!("malformed request: {}", e));
+            return;
+        }
+    };
+    let destination = request.to_string();
+
+    if !policy.allows(&request) {
+        audit.record(&peer, "exit_denied", &destination);
+        println!("⛔ {} denied {}", peer_id, destination);
+        let reason = format!("{} is not an allowed destination", destination);
+        let _ = send_secrets(&mut stream, &ExitResponse::Denied { reason }).await;
+        return;
+    }
+
+    let connect = TcpStream::connect((request.host.as_str(), request.port));
+    let mut target = match tokio::time::timeout(DESTINATION_TIMEOUT, connect).await {
+        Ok(Ok(target)) => target,
+        Ok(Err(e)) => {
+            let reason = format!("failed to connect to {}: {}", destination, e);
+            audit.record(&peer, "exit_failed", &reason);
+            let _ = send_secrets(&mut stream, &ExitResponse::Failed { reason }).await;
+            return;
+        }
+        Err(_) => {
+            let reason = format!("timed out connecting to {}", destination);
+            audit.record(&peer, "exit_failed", &reason);
+            let _ = send_secrets(&mut stream, &ExitResponse::Failed { reason }).await;
+            return;
+        }
+    };
+
+    audit.record(&peer, "exit_opened", &destination);
+    println!("🚪 {} -> {}", peer_id, destination);
+    if send_secrets(&mut stream, &ExitResponse::Connected).await.is_ok() {
+        let _ = tokio::io::copy_bidirectional(&mut stream, &mut target).await;
+    }
+}
+
+async fn connect(link: String, socks_port: u16) -> Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", socks_port))
+        .await
+        .with_context(|| format!("failed to listen on 127.0.0.1:{}", socks_port))?;
+    let config = RiftConfig::load_or_default(&RiftConfig::default_config_path())?;
+    let mut network = PeerNetwork::new(config).await?;
+    let mut events = network.take_event_receiver();
+    network.start_listening().await?;
+
+    let mut retry_count = 0;
+    let peer_id = loop {
+        let _ = network.poll_once().await;
+        match network.connect(&link).await {
+            Ok(peer_id) => break peer_id,
+            Err(_) if retry_count < MAX_DIAL_RETRIES => {
+                retry_count += 1;
+                tokio::time::sleep(Duration::from_millis(250)).await;
+            }
+            Err(e) => {
+                network.shutdown().await;
+                return Err(e.into());
+            }
+        }
+    };
+
+    println!("\n🚪 SOCKS5 proxy on 127.0.0.1:{}, exiting through {}", socks_port, peer_id);
+    println!("   e.g. curl --socks5-hostname 127.0.0.1:{} http://localhost:3000", socks_port);
+    println!("   The host decides which destinations are reachable. Press Ctrl+C to stop.\n");
+
+    let control = network.stream_control();
+    let protocols = network.protocols().clone();
+    loop {
+        tokio::select! {
+            _ = network.poll_once() => {}
+            Some(_) = events.recv() => {}
+            accepted = listener.accept() => {
+                let (client, _) = accepted?;
+                let mut control = control.clone();
+                let protocols = protocols.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = proxy_socks_client(client, &mut control, &protocols, peer_id).await {
+                        eprintln!("⚠️  {}", e);
+                    }
+                });
+            }
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+
+    network.shutdown().await;
+    Ok(())
+}
+
+/// Serve one SOCKS5 client through the exit host
+async fn proxy_socks_client(
+    mut client: TcpStream,
+    control: &mut wh_core::libp2p_stream::Control,
+    protocols: &Protocols,
+    peer_id: PeerId,
+) -> Result<()> {
+    let request = match socks_handshake(&mut client).await? {
+        Ok(request) => request,
+        Err(code) => {
+            socks_reply(&mut client, code).await?;
+            return Ok(());
+        }
+    };
+
+    let (response, mut stream) = match open_exit_stream(control, protocols, peer_id, &request).await {
+        Ok(opened) => opened,
+        Err(e) => {
+            socks_reply(&mut client, SOCKS_GENERAL_FAILURE).await?;
+            return Err(e.into());
+        }
+    };
+    match response {
+        ExitResponse::Connected => {
+            socks_reply(&mut client, SOCKS_SUCCEEDED).await?;
+            tokio::io::copy_bidirectional(&mut client, &mut stream).await?;
+            Ok(())
+        }
+        ExitResponse::Denied { reason } => {
+            socks_reply(&mut client, SOCKS_NOT_ALLOWED).await?;
+            bail!("{}", reason)
+        }
+        ExitResponse::Failed { reason } => {
+            socks_reply(&mut client, SOCKS_GENERAL_FAILURE).await?;
+            bail!("{}", reason)
+        }
+    }
+}
+
+/// Read a SOCKS5 greeting and CONNECT request (no authentication)
+///
+/// Returns the destination, or the reply code to refuse the request with.
+async fn socks_handshake(client: &mut TcpStream) -> Result<std::result::Result<ExitRequest, u8>> {
+    let mut header = [0u8; 2];
+    client.read_exact(&mut header).await?;
+    if header[0] != 5 {
+        bail!("not a SOCKS5 client");
+    }
+    let mut methods = vec![0u8; header[1] as usize];
+    client.read_exact(&mut methods).await?;
+    if !methods.contains(&0) {
+        client.write_all(&[5, 0xff]).await?;
+        bail!("SOCKS client requires authentication");
+    }
+    client.write_all(&[5, 0]).await?;
+
+    let mut request = [0u8; 4];
+    client.read_exact(&mut request).await?;
+    let host = match request[3] {
+        1 => {
+            let mut ip = [0u8; 4];
+            client.read_exact(&mut ip).await?;
+            std::net::Ipv4Addr::from(ip).to_string()
+        }
+        3 => {
+            let len = client.read_u8().await? as usize;
+            let mut name = vec![0u8; len];
+            client.read_exact(&mut name).await?;
+            String::from_utf8(name).context("SOCKS host name is not UTF-8")?
+        }
+        4 => {
+            let mut ip = [0u8; 16];
+            client.read_exact(&mut ip).await?;
+            std::net::Ipv6Addr::from(ip).to_string()
+        }
+        _ => return Ok(Err(SOCKS_ADDRESS_NOT_SUPPORTED)),
+    };
+    let port = client.read_u16().await?;
+
+    if request[1] != 1 {
+        return Ok(Err(SOCKS_COMMAND_NOT_SUPPORTED));
+    }
+    Ok(Ok(ExitRequest { host, port }))
+}
+
+async fn socks_reply(client: &mut TcpStream, code: u8) -> std::io::Result<()> {
+    client.write_all(&[5, code, 0, 1, 0, 0, 0, 0, 0, 0]).await
+}
+
+fn confirm(prompt: &str) -> Result<bool> {
+    print!("{}", prompt);
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(answer.trim().eq_ignore_ascii_case("yes"))
+}
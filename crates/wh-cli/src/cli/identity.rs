@@ -0,0 +1,39 @@
+//! Identity Command Implementation
+//!
+//! `rift identity genkey --swarm` creates the pre-shared key of a private
+//! network. Every node of the network needs a copy of the same file.
+
+use anyhow::{bail, Result};
+use std::path::PathBuf;
+use wh_core::{generate_swarm_key, save_swarm_key, RiftConfig, SWARM_KEY_FILE};
+
+use super::IdentityAction;
+
+/// Run the identity command
+pub async fn run(action: IdentityAction) -> Result<()> {
+    match action {
+        IdentityAction::Genkey { swarm, output, force } => genkey(swarm, output, force),
+    }
+}
+
+fn genkey(swarm: bool, output: Option<PathBuf>, force: bool) -> Result<()> {
+    if !swarm {
+        bail!("specify the kind of key to generate: --swarm");
+    }
+    let config = RiftConfig::load_or_default(&RiftConfig::default_config_path())?;
+    let path = output.unwrap_or_else(|| config.state_dir().join(SWARM_KEY_FILE));
+    if path.exists() && !force {
+        bail!("{} already exists (use --force to replace it)", path.display());
+    }
+
+    let key = generate_swarm_key();
+    save_swarm_key(&path, &key)?;
+
+    println!("\n🔐 Swarm key written to {}", path.display());
+    println!("   Fingerprint: {}\n", key.fingerprint());
+    println!("   Copy it to every node of the private network and add to config.toml:");
+    println!("   [network]");
+    println!("   psk_path = {:?}\n", path.display().to_string());
+    println!("   Nodes with the key only talk to each other, over TCP.");
+    Ok(())
+}
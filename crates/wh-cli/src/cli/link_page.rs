@@ -0,0 +1,96 @@
+//! Link Page
+//!
+//! Serves a tiny local web page showing the Rift link as text and as a QR
+//! code, for opening on a second screen or taking a screenshot to send.
+
+use anyhow::Result;
+use std::net::SocketAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tracing::debug;
+
+/// Bind the link page on 127.0.0.1:`port` (0 = any free port) and serve it in the background
+///
+/// Returns the address actually bound.
+pub async fn serve(link: String, port: u16) -> Result<SocketAddr> {
+    let listener = TcpListener::bind(("127.0.0.1", port)).await?;
+    let addr = listener.local_addr()?;
+    let page = render(&link)?;
+
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            let page = page.clone();
+            tokio::spawn(async move {
+                // The request itself does not matter; every path gets the page
+                let mut buf = [0u8; 1024];
+                let _ = socket.read(&mut buf).await;
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{}",
+                    page.len(),
+                    page
+                );
+                if let Err(e) = socket.write_all(response.as_bytes()).await {
+                    debug!("Link page write failed: {}", e);
+                }
+            });
+        }
+    });
+
+    Ok(addr)
+}
+
+/// Render the HTML page for a link
+fn render(link: &str) -> Result<String> {
+    let qr = qr_code(link)?;
+    let link = escape_html(link);
+
+    Ok(format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>Rift link</title>
+<style>
+body {{ font-family: system-ui, sans-serif; display: flex; flex-direction: column; align-items: center; margin-top: 3em; }}
+code {{ font-size: 1.1em; padding: 0.6em 1em; background: #f2f2f2; border-radius: 6px; word-break: break-all; }}
+button {{ margin-top: 1em; font-size: 1em; padding: 0.4em 1.2em; }}
+</style>
+</head>
+<body>
+<h1>🔑 Rift</h1>
+{qr}
+<p><code id="link">{link}</code></p>
+<button onclick="navigator.clipboard.writeText(document.getElementById('link').textContent)">Copy link</button>
+<p>Connect with <code>rift connect {link}</code></p>
+</body>
+</html>
+"#
+    ))
+}
+
+/// The link as an SVG QR code
+#[cfg(feature = "qr")]
+fn qr_code(link: &str) -> Result<String> {
+    use qrcode::render::svg;
+    use qrcode::QrCode;
+
+    Ok(QrCode::new(link.as_bytes())?
+        .render::<svg::Color>()
+        .min_dimensions(320, 320)
+        .quiet_zone(true)
+        .build())
+}
+
+/// Builds without the `qr` feature show the link as text only
+#[cfg(not(feature = "qr"))]
+fn qr_code(_link: &str) -> Result<String> {
+    Ok(String::new())
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
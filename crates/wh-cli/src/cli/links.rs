@@ -0,0 +1,70 @@
+//! Links Command Implementation
+
+use anyhow::{bail, Result};
+use wh_core::{network::PeerIdentity, RiftConfig};
+use wh_daemon::audit::AuditLog;
+use wh_daemon::links::{LinkStatus, LinkStore, LINKS_FILE};
+use wh_daemon::tokens::unix_now;
+
+use super::LinksAction;
+
+/// Run the links command
+pub async fn run(action: LinksAction) -> Result<()> {
+    let config = RiftConfig::load_or_default(&RiftConfig::default_config_path())?;
+    let mut links = LinkStore::load(config.state_dir().join(LINKS_FILE));
+
+    match action {
+        LinksAction::Create { port, ttl, label } => {
+            let identity = PeerIdentity::load_or_generate(&config.identity_path)?;
+            let link = links.issue(ttl.unwrap_or(config.links.default_ttl_secs), label);
+            println!("{}/{}{}", identity.to_rift_link(), port, PeerIdentity::link_query(&[], Some(&link.id)));
+            if !config.links.require {
+                eprintln!("Note: links.require is off, so peers can still connect without a link ID");
+            }
+        }
+        LinksAction::List => {
+            if links.is_empty() {
+                println!("No links yet. Create one with `rift links create <PORT>`.");
+                return Ok(());
+            }
+            let now = unix_now();
+            println!("\n🔗 Links\n");
+            for link in links.list() {
+                let status = link.status(now);
+                let expiry = match (status, link.expires_at) {
+                    (LinkStatus::Valid, Some(at)) => format!("expires in {}", format_secs(at - now)),
+                    (LinkStatus::Valid, None) => "no expiry".to_string(),
+                    _ => String::new(),
+                };
+                println!(
+                    "{}  {:<8} created {} ago  {:<18} {}",
+                    link.id,
+                    status,
+                    format_secs(now.saturating_sub(link.created_at)),
+                    expiry,
+                    link.label.as_deref().unwrap_or(""),
+                );
+            }
+            println!();
+        }
+        LinksAction::Revoke { id } => {
+            if !links.revoke(&id) {
+                bail!("unknown link: {}", id);
+            }
+            AuditLog::new(config.open_state_store()?).record("local", "link_revoked", &id);
+            println!("Revoked {}", id);
+        }
+    }
+
+    Ok(())
+}
+
+/// Format a span of seconds in its largest whole unit
+pub(super) fn format_secs(secs: u64) -> String {
+    match secs {
+        0..60 => format!("{}s", secs),
+        60..3600 => format!("{}m", secs / 60),
+        3600..86400 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86400),
+    }
+}
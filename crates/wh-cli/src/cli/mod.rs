@@ -2,12 +2,41 @@
 //!
 //! Defines the command-line interface using clap.
 
+pub mod attach;
 pub mod connect;
+pub mod detach;
+pub mod discover;
+pub mod doctor;
+pub mod dry_run;
+pub mod exit;
+pub mod identity;
 pub mod info;
+pub mod link_page;
+pub mod links;
+pub mod peers;
+pub mod ping;
+pub mod relay;
+pub mod relays;
 pub mod share;
+pub mod status;
+#[cfg(feature = "term")]
+pub mod term;
+
+/// Stand-in for builds without terminal sharing
+#[cfg(not(feature = "term"))]
+pub mod term {
+    use anyhow::{bail, Result};
+
+    use super::TermAction;
+
+    pub async fn run(_action: TermAction) -> Result<()> {
+        bail!("this build of rift has no terminal sharing")
+    }
+}
 
 use clap::{Parser, Subcommand};
 use std::path::PathBuf;
+use wh_core::Keystore;
 
 /// Rift - Local-First P2P Tunneling Tool
 ///
@@ -26,21 +55,45 @@ pub struct Cli {
     #[arg(long, global = true)]
     pub no_tui: bool,
 
+    /// No banners or TUI; `share` prints only its link on stdout
+    #[arg(short, long, global = true)]
+    pub quiet: bool,
+
+    /// Where to keep the secrets key: "keyring" (default) or "file" (default without keyring support)
+    #[arg(long, global = true, value_name = "KEYSTORE")]
+    pub keystore: Option<Keystore>,
+
+    /// Keep config, identity, peer book and state in DIR (default: $XDG_CONFIG_HOME/rift or the platform's config dir)
+    #[arg(long, global = true, value_name = "DIR", env = "RIFT_CONFIG_DIR")]
+    pub config_dir: Option<PathBuf>,
+
+    /// Ask the daemon at HOST:PORT (its `remote_control.listen`) instead of this machine
+    #[arg(long, global = true, value_name = "HOST:PORT", env = "RIFT_REMOTE")]
+    pub remote: Option<String>,
+
+    /// Bearer token for --remote (default: the `control_token` file in the state dir)
+    #[arg(long, global = true, value_name = "TOKEN", env = "RIFT_CONTROL_TOKEN", hide_env_values = true, requires = "remote")]
+    pub token: Option<String>,
+
     #[command(subcommand)]
     pub command: Commands,
 }
 
 #[derive(Subcommand, Debug)]
 pub enum Commands {
-    /// Share a local port with peers
+    /// Share local ports with peers
     ///
     /// Starts listening for peer connections and forwards traffic
-    /// to the specified local port.
+    /// to the specified local ports, all over the same session.
     #[command(visible_alias = "s")]
     Share {
-        /// The local port to share
-        #[arg(value_name = "PORT")]
-        port: u16,
+        /// The local ports to share (e.g. `3000 8080 5432`)
+        #[arg(value_name = "PORT", required_unless_present = "targets", num_args = 1..)]
+        ports: Vec<u16>,
+
+        /// Share PORT of another machine through this one (repeatable; must be in `share_targets`)
+        #[arg(long = "target", value_name = "HOST:PORT", value_parser = parse_share_target)]
+        targets: Vec<(String, u16)>,
 
         /// Path to .env file containing secrets to share
         #[arg(short, long, value_name = "FILE")]
@@ -49,6 +102,46 @@ pub enum Commands {
         /// Automatically approve all incoming connections (insecure)
         #[arg(long)]
         auto_approve: bool,
+
+        /// Hand out a guest link valid for DURATION (e.g. 1h); guests are cut off when it expires
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+        guest: Option<u64>,
+
+        /// TLS passthrough: route a server name to another local port (repeatable)
+        #[arg(long = "sni", value_name = "HOST=PORT", value_parser = parse_sni_route)]
+        sni_routes: Vec<(String, u16)>,
+
+        /// Name the shared ports, in order, so peers can connect by name (repeatable)
+        #[arg(long = "name", value_name = "NAME")]
+        names: Vec<String>,
+
+        /// Serve a local web page with the link and a QR code (optionally on PORT)
+        #[arg(long, value_name = "PORT", num_args = 0..=1, default_missing_value = "0")]
+        link_page: Option<u16>,
+
+        /// Cap each connection at RATE bytes per second, both directions together (e.g. 5M)
+        #[arg(long, value_name = "RATE", value_parser = parse_rate)]
+        max_rate: Option<u64>,
+
+        /// Apply --max-rate to all connections of a peer together
+        #[arg(long, requires = "max_rate")]
+        per_peer: bool,
+
+        /// Keep sharing in the background; prints the link and returns
+        #[arg(long)]
+        detach: bool,
+
+        /// Quitting the TUI keeps the session running instead of stopping it
+        #[arg(long)]
+        keep_alive_on_exit: bool,
+
+        /// Put this node's addresses in the link so peers can dial it without discovery
+        #[arg(long)]
+        addrs: bool,
+
+        /// Check config, identity, ports and secrets, print what would happen and exit
+        #[arg(long)]
+        dry_run: bool,
     },
 
     /// Connect to a shared port
@@ -56,14 +149,26 @@ pub enum Commands {
     /// Connects to a peer's shared port and creates a local proxy.
     #[command(visible_alias = "c")]
     Connect {
-        /// The Rift link (rift://<PEER_ID>) or peer ID to connect to
+        /// The Rift link (rift://<PEER_ID>), peer ID, or multiaddr ending in /p2p/<PEER_ID> to connect to
         #[arg(value_name = "LINK")]
         link: String,
 
+        /// Connect to the service the peer shared under this name (`rift share --name`)
+        #[arg(value_name = "SERVICE", conflicts_with_all = ["list", "probe", "dry_run"])]
+        service: Option<String>,
+
+        /// Dial the peer at this address first, skipping discovery (repeatable)
+        #[arg(long = "addr", value_name = "MULTIADDR")]
+        addrs: Vec<wh_core::Multiaddr>,
+
         /// Local port to listen on (defaults to the remote port)
         #[arg(short, long, value_name = "PORT")]
         local_port: Option<u16>,
 
+        /// Forward this remote port, on LOCAL if given, instead of the port of the link (repeatable)
+        #[arg(short = 'p', long = "port", value_name = "REMOTE[:LOCAL]", value_parser = parse_forward, conflicts_with_all = ["service", "local_port", "list", "probe"])]
+        ports: Vec<(u16, u16)>,
+
         /// Bind to 0.0.0.0 instead of 127.0.0.1 (allows external connections)
         #[arg(long)]
         public: bool,
@@ -75,6 +180,34 @@ pub enum Commands {
         /// Save received secrets to a file (requires --request-secrets)
         #[arg(long, value_name = "FILE", requires = "request_secrets")]
         save_secrets: Option<PathBuf>,
+
+        /// Use a throwaway identity for this session (never saved)
+        #[arg(long)]
+        ephemeral: bool,
+
+        /// Stripe each connection across N parallel streams (bulk transfers)
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(u8).range(1..=16))]
+        stripe: Option<u8>,
+
+        /// Log HTTP requests (method, path, status, duration) to the TUI and access.log
+        #[arg(long)]
+        http_log: bool,
+
+        /// Exit non-zero unless the tunnel is up within DURATION (e.g. 30s); prints the local address once it is
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+        wait_ready: Option<u64>,
+
+        /// List the services the peer shares and exit instead of connecting
+        #[arg(long, conflicts_with_all = ["local_port", "request_secrets", "wait_ready", "dry_run"])]
+        list: bool,
+
+        /// Show which optional features the peer supports and exit instead of connecting
+        #[arg(long, conflicts_with_all = ["list", "local_port", "request_secrets", "wait_ready", "dry_run"])]
+        probe: bool,
+
+        /// Check config, identity, link and local port, print what would happen and exit
+        #[arg(long)]
+        dry_run: bool,
     },
 
     /// Show node information
@@ -82,4 +215,405 @@ pub enum Commands {
     /// Displays the local peer ID and Rift link.
     #[command(visible_alias = "i")]
     Info,
+
+    /// Find Rift peers on the local network
+    ///
+    /// Listens for mDNS announcements and lists the peers found.
+    #[command(visible_alias = "d")]
+    Discover {
+        /// Keep running and print peers as they appear, change or leave
+        #[arg(short, long)]
+        watch: bool,
+
+        /// Seconds to listen before printing results (ignored with --watch)
+        #[arg(short, long, value_name = "SECS", default_value_t = 5)]
+        timeout: u64,
+    },
+
+    /// List services shared by other Rift nodes
+    ///
+    /// Connects to peers found on the local network and collects the
+    /// services they announce, so links need not be passed around by hand.
+    #[command(visible_alias = "p")]
+    Peers {
+        #[command(subcommand)]
+        action: Option<PeersAction>,
+
+        /// Keep running and print services as they appear or go away
+        #[arg(short, long)]
+        watch: bool,
+
+        /// Seconds to listen before printing results (ignored with --watch)
+        #[arg(short, long, value_name = "SECS", default_value_t = 10)]
+        timeout: u64,
+    },
+
+    /// Check how reachable this machine is
+    ///
+    /// Joins the network and classifies the NAT in front of it (open, cone,
+    /// symmetric or UDP blocked) from what peers observe.
+    Doctor {
+        /// Seconds to collect evidence before reporting
+        #[arg(short, long, value_name = "SECS", default_value_t = 20)]
+        timeout: u64,
+    },
+
+    /// Show running sessions
+    ///
+    /// Displays traffic and tunnel setup latency (p50/p95) of the
+    /// share and connect sessions running on this machine, or of the
+    /// daemon given with --remote.
+    Status {
+        /// Close one tunnelled connection by its `#ID` first (needs --remote)
+        #[arg(long, value_name = "ID")]
+        close: Option<u64>,
+    },
+
+    /// Run a relay server for your team
+    ///
+    /// Relays circuits for peers that cannot reach each other directly and
+    /// serves as their rendezvous point. Run it on an always-reachable machine.
+    Relay {
+        /// UDP port to listen on (default from config: 4001)
+        #[arg(short, long, value_name = "PORT")]
+        port: Option<u16>,
+
+        /// Also accept WebSocket clients on this TCP port, for networks that only allow HTTP(S)
+        #[arg(long, value_name = "PORT")]
+        websocket_port: Option<u16>,
+
+        /// Publicly reachable address of this machine, e.g. /ip4/203.0.113.7/udp/4001/quic-v1 (repeatable)
+        #[arg(long = "external-addr", value_name = "MULTIADDR")]
+        external_addrs: Vec<String>,
+    },
+
+    /// Manage the relays remembered from earlier sessions
+    ///
+    /// Known relays are tried before the public bootstrap nodes, pinned
+    /// ones first, then by latency.
+    Relays {
+        #[command(subcommand)]
+        action: RelaysAction,
+    },
+
+    /// Manage revocable links
+    ///
+    /// With `links.require` set in the config, peers need a link ID handed
+    /// out by `rift share` or `links create`; revoking an ID locks its holders out.
+    Links {
+        #[command(subcommand)]
+        action: LinksAction,
+    },
+
+    /// Check end-to-end connectivity to a peer
+    ///
+    /// Sends payloads over the echo protocol, proving that Rift streams
+    /// reach the peer and not just that a connection exists.
+    Ping {
+        /// Rift link of the peer (rift://...)
+        link: String,
+
+        /// Number of pings to send
+        #[arg(short, long, default_value_t = 4)]
+        count: u32,
+
+        /// Payload size in bytes
+        #[arg(short, long, value_name = "BYTES", default_value_t = 64)]
+        size: usize,
+
+        /// Milliseconds to wait between pings
+        #[arg(long, value_name = "MS", default_value_t = 1000)]
+        interval_ms: u64,
+    },
+
+    /// Watch another daemon's dashboard read-only
+    ///
+    /// The host must list this node's peer ID in `viewers`. Stats, peers and
+    /// connections are streamed over the control protocol; nothing you press
+    /// changes the host's session.
+    Attach {
+        /// Rift link of the daemon to watch (rift://...)
+        link: String,
+    },
+
+    /// Share a terminal with approved viewers
+    ///
+    /// Runs your shell (or a command) in a pseudo-terminal and streams it to
+    /// peers running `rift term view`. Viewers are read-only by default.
+    Term {
+        #[command(subcommand)]
+        action: TermAction,
+    },
+
+    /// Let a trusted peer connect through this machine
+    ///
+    /// Exit mode forwards connections to destinations the peer picks, limited
+    /// to localhost unless more are allowed. The peer uses it as a SOCKS5 proxy.
+    Exit {
+        #[command(subcommand)]
+        action: ExitAction,
+    },
+
+    /// Generate keys
+    ///
+    /// `genkey --swarm` creates the pre-shared key of a private network:
+    /// only nodes holding it can connect to each other.
+    Identity {
+        #[command(subcommand)]
+        action: IdentityAction,
+    },
+}
+
+/// Actions of the relays command
+#[derive(Subcommand, Debug)]
+pub enum RelaysAction {
+    /// List known relays, best first
+    #[command(visible_alias = "ls")]
+    List,
+
+    /// Show which configured relay holds our reservation and how each probed
+    Status,
+
+    /// Always prefer a relay (adds it if unknown)
+    Pin {
+        /// Relay address ending in /p2p/<PEER_ID>
+        #[arg(value_name = "MULTIADDR")]
+        address: String,
+    },
+
+    /// Remove a relay from the list
+    Forget {
+        /// Relay address as shown by `rift relays list`
+        #[arg(value_name = "MULTIADDR")]
+        address: String,
+    },
+}
+
+/// Actions of the peers command
+#[derive(Subcommand, Debug)]
+pub enum PeersAction {
+    /// Review or edit which secrets a peer receives
+    ///
+    /// A peer that asked before gets the same keys again; keys added to the
+    /// secrets file since are held back until granted here or in the prompt.
+    Secrets {
+        /// Peer ID (all peers with a scope if omitted)
+        #[arg(value_name = "PEER")]
+        peer: Option<String>,
+
+        /// Share this key with the peer from now on
+        #[arg(long, value_name = "KEY")]
+        add: Vec<String>,
+
+        /// Stop sharing this key with the peer
+        #[arg(long, value_name = "KEY")]
+        remove: Vec<String>,
+
+        /// Forget the scope; the next request is treated as the first
+        #[arg(long, conflicts_with_all = ["add", "remove"])]
+        forget: bool,
+    },
+
+    /// Give a peer a nickname shown instead of its ID
+    ///
+    /// Running sessions pick the name up when the peer next connects.
+    Rename {
+        /// Peer ID, or its current nickname
+        #[arg(value_name = "PEER")]
+        peer: String,
+
+        /// New nickname (removes the nickname if omitted)
+        #[arg(value_name = "NAME")]
+        name: Option<String>,
+    },
+}
+
+/// Actions of the links command
+#[derive(Subcommand, Debug)]
+pub enum LinksAction {
+    /// Create a link to a shared port
+    Create {
+        /// The shared port the link points at
+        #[arg(value_name = "PORT")]
+        port: u16,
+
+        /// Seconds until the link expires (default from config: links.default_ttl_secs)
+        #[arg(long, value_name = "SECS")]
+        ttl: Option<u64>,
+
+        /// Note on who the link is for, shown by `rift links list`
+        #[arg(long, value_name = "TEXT")]
+        label: Option<String>,
+    },
+
+    /// List created links, oldest first
+    #[command(visible_alias = "ls")]
+    List,
+
+    /// Stop accepting a link; peers holding it are refused from their next connection
+    Revoke {
+        /// Link ID as shown by `rift links list`
+        #[arg(value_name = "ID")]
+        id: String,
+    },
+}
+
+/// Actions of the term command
+#[derive(Subcommand, Debug)]
+pub enum TermAction {
+    /// Share your shell, or the command after `--`
+    Share {
+        /// Let viewers type into the terminal
+        #[arg(long)]
+        interactive: bool,
+
+        /// Admit this peer without prompting (repeatable)
+        #[arg(long, value_name = "PEER_ID")]
+        allow: Vec<String>,
+
+        /// Admit every viewer without prompting (insecure)
+        #[arg(long)]
+        auto_approve: bool,
+
+        /// Command to run instead of $SHELL
+        #[arg(last = true, value_name = "COMMAND")]
+        command: Vec<String>,
+    },
+
+    /// Watch a shared terminal
+    View {
+        /// Rift link of the host (rift://...)
+        link: String,
+    },
+}
+
+/// Actions of the exit command
+#[derive(Subcommand, Debug)]
+pub enum ExitAction {
+    /// Forward connections for the given peers
+    Serve {
+        /// Peer allowed to use this machine as an exit (repeatable)
+        #[arg(long = "peer", value_name = "PEER_ID", required = true)]
+        peers: Vec<String>,
+
+        /// Also allow HOST (any port) or HOST:PORT besides localhost (repeatable)
+        #[arg(long, value_name = "DEST")]
+        allow: Vec<String>,
+
+        /// Skip the confirmation prompt
+        #[arg(long)]
+        yes: bool,
+    },
+
+    /// Use a peer's exit through a local SOCKS5 proxy
+    Connect {
+        /// Rift link of the exit host (rift://...)
+        link: String,
+
+        /// Local port of the SOCKS5 proxy
+        #[arg(long, default_value_t = 1080)]
+        socks_port: u16,
+    },
+}
+
+/// Actions of the identity command
+#[derive(Subcommand, Debug)]
+pub enum IdentityAction {
+    /// Generate a new key
+    Genkey {
+        /// Generate a private network swarm key
+        #[arg(long)]
+        swarm: bool,
+
+        /// Where to write the key (default: swarm.key next to the identity)
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+
+        /// Replace an existing key file
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+/// Parse a byte rate like `500K`, `5M` or `1G` (binary multiples, optional `B`)
+fn parse_rate(s: &str) -> Result<u64, String> {
+    let upper = s.trim().to_ascii_uppercase();
+    let digits = upper.trim_end_matches('B');
+    let (number, multiplier) = match digits.chars().last() {
+        Some('K') => (&digits[..digits.len() - 1], 1024),
+        Some('M') => (&digits[..digits.len() - 1], 1024 * 1024),
+        Some('G') => (&digits[..digits.len() - 1], 1024 * 1024 * 1024),
+        _ => (digits, 1),
+    };
+    let rate = number
+        .trim()
+        .parse::<u64>()
+        .map_err(|_| format!("invalid rate '{}', expected e.g. 500K or 5M", s))?;
+    match rate.checked_mul(multiplier) {
+        Some(0) => Err("rate must be above zero".to_string()),
+        Some(rate) => Ok(rate),
+        None => Err(format!("rate '{}' is too large", s)),
+    }
+}
+
+/// Parse a duration like `90s`, `30m`, `1h` or `2d` into seconds (plain numbers are seconds)
+fn parse_duration(s: &str) -> Result<u64, String> {
+    let trimmed = s.trim().to_ascii_lowercase();
+    let (number, multiplier) = match trimmed.chars().last() {
+        Some('s') => (&trimmed[..trimmed.len() - 1], 1),
+        Some('m') => (&trimmed[..trimmed.len() - 1], 60),
+        Some('h') => (&trimmed[..trimmed.len() - 1], 3600),
+        Some('d') => (&trimmed[..trimmed.len() - 1], 86400),
+        _ => (trimmed.as_str(), 1),
+    };
+    let secs = number
+        .trim()
+        .parse::<u64>()
+        .map_err(|_| format!("invalid duration '{}', expected e.g. 30m or 1h", s))?;
+    match secs.checked_mul(multiplier) {
+        Some(0) => Err("duration must be above zero".to_string()),
+        Some(secs) => Ok(secs),
+        None => Err(format!("duration '{}' is too large", s)),
+    }
+}
+
+/// Parse a `HOST=PORT` SNI route
+fn parse_share_target(s: &str) -> Result<(String, u16), String> {
+    let (host, port) = s
+        .rsplit_once(':')
+        .ok_or_else(|| format!("expected HOST:PORT, got '{}'", s))?;
+    let host = host.strip_prefix('[').and_then(|host| host.strip_suffix(']')).unwrap_or(host);
+    if host.is_empty() || host.contains(':') && !s.starts_with('[') {
+        return Err(format!("expected HOST:PORT (IPv6 hosts in brackets), got '{}'", s));
+    }
+    let port = port
+        .parse::<u16>()
+        .ok()
+        .filter(|port| *port != 0)
+        .ok_or_else(|| format!("invalid port '{}'", port))?;
+    Ok((host.to_string(), port))
+}
+
+/// Parse a `REMOTE[:LOCAL]` port forward (the local port defaults to the remote one)
+fn parse_forward(s: &str) -> Result<(u16, u16), String> {
+    let port = |port: &str| {
+        port.parse::<u16>()
+            .ok()
+            .filter(|port| *port != 0)
+            .ok_or_else(|| format!("invalid port '{}'", port))
+    };
+    match s.split_once(':') {
+        Some((remote, local)) => Ok((port(remote)?, port(local)?)),
+        None => port(s).map(|remote| (remote, remote)),
+    }
+}
+
+fn parse_sni_route(s: &str) -> Result<(String, u16), String> {
+    let (host, port) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected HOST=PORT, got '{}'", s))?;
+    let port = port
+        .parse::<u16>()
+        .map_err(|e| format!("invalid port '{}': {}", port, e))?;
+    Ok((host.to_string(), port))
 }
@@ -0,0 +1,150 @@
+//! Peers Command Implementation
+//!
+//! Lists what other Rift nodes share, from the announcements they publish on
+//! the presence topic, edits the secrets each peer receives and names peers.
+
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeMap;
+use std::time::Duration;
+use wh_core::{NetworkEvent, PeerBook, PeerId, PeerNetwork, PeerRtt, RiftConfig, SharedService};
+use wh_daemon::secret_scopes::{SecretScopes, SECRET_SCOPES_FILE};
+
+use super::PeersAction;
+
+/// Run the peers command
+pub async fn run(watch: bool, timeout_secs: u64) -> Result<()> {
+    let config = RiftConfig::load_or_default(&RiftConfig::default_config_path())?;
+    let mut network = PeerNetwork::new(config).await?;
+    let mut events = network.take_event_receiver();
+    network.watch_presence()?;
+    network.start_listening().await?;
+
+    if watch {
+        println!("\n📡 Watching for shared services (Ctrl-C to stop)\n");
+    } else {
+        println!("\n📡 Collecting shared services ({}s)...\n", timeout_secs);
+    }
+
+    let mut available: BTreeMap<PeerId, Vec<SharedService>> = BTreeMap::new();
+    let deadline = tokio::time::sleep(Duration::from_secs(timeout_secs));
+    tokio::pin!(deadline);
+
+    loop {
+        tokio::select! {
+            _ = network.poll_once() => {}
+            Some(event) = events.recv() => match event {
+                NetworkEvent::ServicesAnnounced { peer_id, services } => {
+                    if services.is_empty() {
+                        if available.remove(&peer_id).is_some() && watch {
+                            println!("- rift://{}", peer_id);
+                        }
+                        continue;
+                    }
+                    if watch && available.get(&peer_id) != Some(&services) {
+                        let nickname = network.peer_nickname(&peer_id);
+                        print_peer(&peer_id, nickname.as_deref(), &services, network.peer_rtt(&peer_id));
+                    }
+                    available.insert(peer_id, services);
+                }
+                NetworkEvent::PeerDisconnected { peer_id } => {
+                    let removed = available.remove(&peer_id).is_some();
+                    if removed && watch {
+                        println!("- rift://{}", peer_id);
+                    }
+                }
+                _ => {}
+            },
+            _ = &mut deadline, if !watch => break,
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+
+    if !watch {
+        if available.is_empty() {
+            println!("No shared services found.\n");
+        }
+        let nicknames = network.peer_nicknames();
+        for (peer_id, services) in &available {
+            print_peer(peer_id, nicknames.get(peer_id).map(String::as_str), services, network.peer_rtt(peer_id));
+        }
+        println!();
+    }
+
+    network.shutdown().await;
+
+    Ok(())
+}
+
+/// Run a peers subcommand
+pub async fn run_action(action: PeersAction) -> Result<()> {
+    let config = RiftConfig::load_or_default(&RiftConfig::default_config_path())?;
+    let mut scopes = SecretScopes::load(config.state_dir().join(SECRET_SCOPES_FILE));
+
+    match action {
+        PeersAction::Rename { peer, name } => {
+            let mut book = PeerBook::open(config.open_state_store()?);
+            let peer_id = match book.find_nickname(&peer) {
+                Some(peer_id) => peer_id,
+                None => peer.parse::<PeerId>().with_context(|| format!("'{}' is neither a peer ID nor a nickname", peer))?,
+            };
+            book.set_nickname(&peer_id, name.clone())?;
+            match name {
+                Some(name) => println!("{} is now {}", peer_id, name),
+                None => println!("Removed the nickname of {}", peer_id),
+            }
+        }
+        PeersAction::Secrets { peer: None, .. } => {
+            if scopes.is_empty() {
+                println!("No peer has received secrets yet.");
+                return Ok(());
+            }
+            println!("\n🔐 Secrets scopes\n");
+            for scope in scopes.list() {
+                println!("{}", scope.peer_id);
+                println!("    {}", format_keys(&scope.keys));
+            }
+            println!();
+        }
+        PeersAction::Secrets { peer: Some(peer), add, remove, forget } => {
+            let peer_id = peer.parse::<PeerId>().with_context(|| format!("invalid peer ID '{}'", peer))?.to_string();
+            if forget {
+                if !scopes.forget(&peer_id) {
+                    bail!("{} has no secrets scope", peer_id);
+                }
+                println!("Forgot the secrets scope of {}", peer_id);
+                return Ok(());
+            }
+            if !add.is_empty() {
+                scopes.grant(&peer_id, add);
+            }
+            if !remove.is_empty() && !scopes.revoke(&peer_id, &remove) {
+                bail!("{} has no secrets scope", peer_id);
+            }
+            match scopes.get(&peer_id) {
+                Some(scope) => println!("{}\n    {}", peer_id, format_keys(&scope.keys)),
+                None => println!("{} has not received secrets; its first request gets every key.", peer_id),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn format_keys(keys: &std::collections::BTreeSet<String>) -> String {
+    if keys.is_empty() {
+        "(no keys)".to_string()
+    } else {
+        keys.iter().cloned().collect::<Vec<_>>().join(", ")
+    }
+}
+
+fn print_peer(peer_id: &PeerId, nickname: Option<&str>, services: &[SharedService], rtt: Option<PeerRtt>) {
+    let name = nickname.map(|nickname| format!("  {}", nickname)).unwrap_or_default();
+    match rtt {
+        Some(rtt) => println!("rift://{}{}  ({}ms, last {}ms)", peer_id, name, rtt.average.as_millis(), rtt.last.as_millis()),
+        None => println!("rift://{}{}", peer_id, name),
+    }
+    for service in services {
+        println!("    {}", service);
+    }
+}
@@ -0,0 +1,102 @@
+//! Ping Command Implementation
+//!
+//! Checks that Rift streams reach a peer end to end by sending payloads over
+//! the echo protocol and timing the round trip.
+
+use anyhow::{bail, Result};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use wh_core::{echo_round_trip, PeerNetwork, RiftConfig};
+
+/// Attempts at dialing the peer while discovery catches up
+const MAX_DIAL_RETRIES: u32 = 20;
+
+/// Run the ping command
+pub async fn run(link: String, count: u32, size: usize, interval_ms: u64) -> Result<()> {
+    let config = RiftConfig::load_or_default(&RiftConfig::default_config_path())?;
+    let mut network = PeerNetwork::new(config).await?;
+    let mut events = network.take_event_receiver();
+    network.start_listening().await?;
+
+    let mut retry_count = 0;
+    let peer_id = loop {
+        let _ = network.poll_once().await;
+        match network.connect(&link).await {
+            Ok(peer_id) => break peer_id,
+            Err(_) if retry_count < MAX_DIAL_RETRIES => {
+                retry_count += 1;
+                tokio::time::sleep(Duration::from_millis(250)).await;
+            }
+            Err(e) => {
+                network.shutdown().await;
+                return Err(e.into());
+            }
+        }
+    };
+
+    println!("\n📡 PING rift://{} ({} bytes)\n", peer_id, size);
+
+    // Pings run in their own task so the network keeps being polled meanwhile
+    let (result_tx, mut results) = mpsc::channel(count.max(1) as usize);
+    let mut control = network.stream_control();
+    let protocols = network.protocols().clone();
+    let pinger = tokio::spawn(async move {
+        for seq in 1..=count {
+            if seq > 1 {
+                tokio::time::sleep(Duration::from_millis(interval_ms)).await;
+            }
+            let result = echo_round_trip(&mut control, &protocols, peer_id, size).await;
+            if result_tx.send((seq, result)).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut rtts = Vec::new();
+    let mut sent = 0;
+    loop {
+        tokio::select! {
+            _ = network.poll_once() => {}
+            Some(_) = events.recv() => {}
+            result = results.recv() => match result {
+                Some((seq, Ok(rtt))) => {
+                    sent += 1;
+                    println!("{} bytes from {}: seq={} time={:.2}ms", size, peer_id, seq, ms(rtt));
+                    rtts.push(rtt);
+                }
+                Some((seq, Err(e))) => {
+                    sent += 1;
+                    println!("seq={} failed: {}", seq, e);
+                }
+                None => break,
+            },
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+
+    pinger.abort();
+    network.shutdown().await;
+
+    let received = rtts.len();
+    let loss = if sent == 0 {
+        0.0
+    } else {
+        100.0 * (sent - received) as f64 / sent as f64
+    };
+    println!("\n--- rift://{} ping statistics ---", peer_id);
+    println!("{} sent, {} received, {:.0}% loss", sent, received, loss);
+    if let (Some(min), Some(max)) = (rtts.iter().min(), rtts.iter().max()) {
+        let avg = rtts.iter().sum::<Duration>() / received as u32;
+        println!("rtt min/avg/max = {:.2}/{:.2}/{:.2} ms", ms(*min), ms(avg), ms(*max));
+    }
+    println!();
+
+    if sent > 0 && received == 0 {
+        bail!("no echo replies from {}", peer_id);
+    }
+    Ok(())
+}
+
+fn ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
@@ -0,0 +1,53 @@
+//! Relay Command Implementation
+
+use anyhow::Result;
+use wh_core::{RelayServer, RelayServerEvent, RiftConfig};
+
+/// Run the relay command
+pub async fn run(port: Option<u16>, websocket_port: Option<u16>, external_addrs: Vec<String>) -> Result<()> {
+    let mut config = RiftConfig::load_or_default(&RiftConfig::default_config_path())?;
+    if let Some(port) = port {
+        config.relay_server.listen_port = port;
+    }
+    if let Some(port) = websocket_port {
+        config.relay_server.websocket_port = port;
+    }
+    config.relay_server.external_addrs.extend(external_addrs);
+
+    let mut relay = RelayServer::new(&config).await?;
+    relay.start_listening()?;
+    let peer_id = *relay.peer_id();
+
+    println!("\n📡 Rift relay {}\n", peer_id);
+    let external = relay.external_addresses();
+    if external.is_empty() {
+        println!("No --external-addr given; announcing the addresses clients observe.\n");
+    } else {
+        println!("Add to config.toml on each team machine:\n");
+        let addrs: Vec<String> = external.iter().map(|addr| format!("\"{}/p2p/{}\"", addr, peer_id)).collect();
+        println!("relays = [{}]", addrs.join(", "));
+        if config.relay_server.rendezvous {
+            println!("rendezvous_server = {}", addrs[0]);
+        }
+        println!();
+    }
+
+    loop {
+        tokio::select! {
+            event = relay.next_event() => match event {
+                RelayServerEvent::Listening { address } => println!("Listening on {}/p2p/{}", address, peer_id),
+                RelayServerEvent::ReservationAccepted { peer_id, renewed: false } => println!("+ reservation {}", peer_id),
+                RelayServerEvent::ReservationAccepted { renewed: true, .. } => {}
+                RelayServerEvent::ReservationDenied { peer_id } => println!("! reservation denied {}", peer_id),
+                RelayServerEvent::ReservationExpired { peer_id } => println!("- reservation {}", peer_id),
+                RelayServerEvent::CircuitOpened { src, dst } => println!("+ circuit {} -> {}", src, dst),
+                RelayServerEvent::CircuitDenied { src, dst } => println!("! circuit denied {} -> {}", src, dst),
+                RelayServerEvent::CircuitClosed { src, dst } => println!("- circuit {} -> {}", src, dst),
+                RelayServerEvent::PeerRegistered { peer_id, namespace } => println!("+ registered {} in '{}'", peer_id, namespace),
+            },
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,67 @@
+//! Relays Command Implementation
+
+use anyhow::{bail, Result};
+use std::time::{SystemTime, UNIX_EPOCH};
+use wh_core::{KnownRelays, Multiaddr, RelayAvailability, RelayProbeReport, RiftConfig, KNOWN_RELAYS_FILE, RELAY_PROBES_FILE};
+
+use super::RelaysAction;
+
+/// Run the relays command
+pub async fn run(action: RelaysAction) -> Result<()> {
+    let config = RiftConfig::load_or_default(&RiftConfig::default_config_path())?;
+    let mut relays = KnownRelays::load(config.state_dir().join(KNOWN_RELAYS_FILE));
+
+    match action {
+        RelaysAction::List => {
+            if relays.is_empty() {
+                println!("No known relays. Relays are remembered after they carry a session.");
+                return Ok(());
+            }
+            println!("\n📡 Known relays (best first)\n");
+            for relay in relays.list() {
+                let latency = relay
+                    .latency_ms
+                    .map(|ms| format!("{}ms", ms))
+                    .unwrap_or_else(|| "-".to_string());
+                let pin = if relay.pinned { "📌" } else { "  " };
+                println!("{} {:>7}  {:>4} uses  {}", pin, latency, relay.successes, relay.address);
+            }
+            println!();
+        }
+        RelaysAction::Status => {
+            let Some(report) = RelayProbeReport::load(&config.state_dir().join(RELAY_PROBES_FILE))? else {
+                println!("No relay probes yet. They run during a session with two or more relays configured.");
+                return Ok(());
+            };
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+            println!("\n📡 Configured relays (probed {}s ago)\n", now.saturating_sub(report.decided_at));
+            for probe in &report.probes {
+                let rtt = probe.rtt_ms.map(|ms| format!("{}ms", ms)).unwrap_or_else(|| "-".to_string());
+                let availability = match &probe.availability {
+                    RelayAvailability::Pending => "no answer".to_string(),
+                    RelayAvailability::Accepted => "accepted".to_string(),
+                    RelayAvailability::Refused { reason } => format!("refused: {}", reason),
+                };
+                let mark = if report.chosen.as_ref() == Some(&probe.address) { "★" } else { " " };
+                println!("{} {:>7}  {}  ({})", mark, rtt, probe.address, availability);
+            }
+            match &report.chosen {
+                Some(chosen) => println!("\nOur reservation is on {}\n", chosen),
+                None => println!("\nNo configured relay took a reservation\n"),
+            }
+        }
+        RelaysAction::Pin { address } => {
+            let addr: Multiaddr = address.parse()?;
+            relays.pin(&addr)?;
+            println!("📌 Pinned {}", addr);
+        }
+        RelaysAction::Forget { address } => {
+            if !relays.forget(&address) {
+                bail!("unknown relay: {}", address);
+            }
+            println!("Forgot {}", address);
+        }
+    }
+
+    Ok(())
+}
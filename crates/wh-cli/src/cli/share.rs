@@ -1,20 +1,152 @@
 //! Share Command Implementation
 
-use anyhow::Result;
-use wh_core::RiftConfig;
+use anyhow::{bail, Result};
+use wh_core::{network::PeerIdentity, ConfigOverrides, ExitRequest, Keystore, PreflightAction, RateLimitScope, RiftConfig};
+use wh_daemon::links::{LinkStore, LINKS_FILE};
+use wh_daemon::preflight;
 use wh_daemon::{DaemonCommand, DaemonServer};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tracing::{info, error};
 
+use super::{detach, dry_run, link_page, links};
 use crate::tui;
 
+/// Options of the share command
+#[derive(Debug, Clone, Default)]
+pub struct ShareOptions {
+    /// Path to a .env file with secrets to share
+    pub secrets: Option<PathBuf>,
+    /// Approve every incoming connection
+    pub auto_approve: bool,
+    /// Seconds a guest link stays valid; only its holders may connect
+    pub guest: Option<u64>,
+    /// TLS passthrough routes: server name -> local port
+    pub sni_routes: Vec<(String, u16)>,
+    /// Ports of other machines shared through this one, as (host, port)
+    pub targets: Vec<(String, u16)>,
+    /// Names of the shared ports, in the order the ports were given
+    pub names: Vec<String>,
+    /// Bandwidth cap in bytes per second (overrides the config file)
+    pub max_rate: Option<u64>,
+    /// Apply `max_rate` per peer instead of per connection
+    pub per_peer: bool,
+    /// Override where the secrets key is kept
+    pub keystore: Option<Keystore>,
+    /// Serve the link page on this local port (0 = any free port)
+    pub link_page: Option<u16>,
+    /// Print only the link on stdout
+    pub quiet: bool,
+    /// Keep sharing in the background and return once the link is printed
+    pub detach: bool,
+    /// Quitting the dashboard leaves the session running instead of stopping it
+    pub keep_alive_on_exit: bool,
+    /// Embed this node's addresses in the link
+    pub addrs: bool,
+    /// Check the setup and print what would happen instead of sharing
+    pub dry_run: bool,
+}
+
+/// Check the shared local ports: warn about problems, fail on those the config says to
+fn run_preflight(ports: &[u16], config: &RiftConfig, config_path: &Path) -> Result<()> {
+    let store = config.open_state_store().ok();
+    let mut failed = None;
+    for (action, issue) in preflight::check_share(ports, &config.preflight, store.as_deref()) {
+        if action == PreflightAction::Fail {
+            error!("Preflight: {}", issue);
+            failed.get_or_insert(issue);
+        } else {
+            eprintln!("⚠️  {}", issue);
+        }
+    }
+    match failed {
+        Some(issue) => bail!(
+            "{} (set {} = \"warn\" in {} to share anyway)",
+            issue,
+            issue.setting(),
+            config_path.display()
+        ),
+        None => Ok(()),
+    }
+}
+
 /// Run the share command
-pub async fn run(port: u16, secrets: Option<PathBuf>, auto_approve: bool, no_tui: bool) -> Result<()> {
-    info!("Sharing port {} (secrets: {:?}, auto_approve: {})", port, secrets, auto_approve);
+pub async fn run(ports: Vec<u16>, options: ShareOptions, no_tui: bool) -> Result<()> {
+    let ShareOptions { secrets, auto_approve, guest, sni_routes, targets, names, max_rate, per_peer, keystore, link_page, quiet, detach, keep_alive_on_exit, addrs, dry_run } = options;
+    // Local ports first, then the targets' ports: names, links and the dashboard follow this order
+    let local_ports = ports;
+    let ports: Vec<u16> = local_ports.iter().copied().chain(targets.iter().map(|(_, port)| *port)).collect();
+    // Peers reach a target by its port, so it must not be shared already
+    for (i, (host, port)) in targets.iter().enumerate() {
+        if ports[..local_ports.len() + i].contains(port) {
+            bail!("{}:{} uses port {}, which is already shared", host, port, port);
+        }
+    }
+    if names.len() > ports.len() {
+        bail!("{} names for {} port(s): each --name belongs to the port in the same position", names.len(), ports.len());
+    }
+    let config_path = RiftConfig::default_config_path();
+    let mut config = RiftConfig::load_or_default(&config_path)?;
+    let file_config = config.clone();
+    // Before detaching, so the problems show up in this terminal
+    if !dry_run {
+        run_preflight(&local_ports, &config, &config_path)?;
+    }
+    if detach && !dry_run {
+        let (link, pid) = detach::spawn()?;
+        println!("{}", link);
+        if !quiet {
+            eprintln!("Sharing {} in the background (pid {})", describe_shares(&local_ports, &targets), pid);
+        }
+        return Ok(());
+    }
+    info!("Sharing port(s) {:?} (secrets: {:?}, auto_approve: {})", ports, secrets, auto_approve);
 
     // Create daemon
-    let config = RiftConfig::default();
+    for (server_name, route_port) in sni_routes {
+        info!("Routing TLS server name {} to localhost:{}", server_name, route_port);
+        config = config.with_sni_route(server_name, route_port);
+    }
+    for (name, port) in names.iter().zip(&ports) {
+        info!("Sharing localhost:{} as {}", port, name);
+        config = config.with_service_name(name.clone(), *port);
+    }
+    if let Some(max_rate) = max_rate {
+        let scope = if per_peer { RateLimitScope::Peer } else { RateLimitScope::Connection };
+        info!("Limiting tunnels to {} bytes/s per {:?}", max_rate, scope);
+        config = config.with_max_rate(max_rate, scope);
+    }
+    if let Some(keystore) = keystore {
+        config = config.with_keystore(keystore);
+    }
+    if dry_run {
+        return dry_run::share(&local_ports, &targets, secrets.as_deref(), auto_approve, link_page, &config, &config_path);
+    }
+    let policy = config.share_target_policy();
+    for (host, port) in &targets {
+        let target = ExitRequest { host: host.clone(), port: *port };
+        if !policy.allows(&target) {
+            bail!("{} is not an allowed target; add \"{}\" to share_targets in {}", target, target, config_path.display());
+        }
+    }
+    let history_limits = config.history.clone();
+    // A fresh revocable link for this session, saved before the daemon loads the links
+    let mut links = LinkStore::load(config.state_dir().join(LINKS_FILE));
+    let link_id = match guest {
+        Some(ttl) => {
+            let issued = links.issue_guest(ttl);
+            info!("Issued guest link {} for {}s", issued.id, ttl);
+            Some(issued.id)
+        }
+        None if config.links.require => {
+            let issued = links.issue(config.links.default_ttl_secs, None);
+            info!("Issued link {}", issued.id);
+            Some(issued.id)
+        }
+        None => None,
+    };
+    let overrides = ConfigOverrides::between(&file_config, &config)?;
     let mut daemon = DaemonServer::new(config).await?;
+    daemon.watch_config(config_path, overrides);
 
     // Get handles
     let command_tx = daemon.command_sender();
@@ -26,56 +158,161 @@ pub async fn run(port: u16, secrets: Option<PathBuf>, auto_approve: bool, no_tui
     // Create share session
     command_tx
         .send(DaemonCommand::Share {
-            port,
+            ports: local_ports.clone(),
             secrets_path: secrets,
             auto_approve,
+            require_link: guest.is_some(),
+            targets: targets.clone(),
         })
         .await?;
 
     // Get the link
-    let link = daemon.rift_link().await;
+    let base_link = daemon.rift_link().await;
+    let addresses = if addrs { daemon.link_addresses().await } else { Vec::new() };
+    let link_suffix = PeerIdentity::link_query(&addresses, link_id.as_deref());
+    let link = format!("{}{}", base_link, link_suffix);
     
-    // Try to copy link to clipboard (non-fatal if it fails)
-    match arboard::Clipboard::new() {
-        Ok(mut clipboard) => {
-            if let Err(e) = clipboard.set_text(&link) {
-                // Silently ignore clipboard errors (headless environments)
-                info!("Failed to copy to clipboard: {}", e);
-            } else {
-                println!("📋 Link copied to clipboard!");
-            }
+    if quiet {
+        // The links alone on stdout, one per port, for scripts
+        for port in &ports {
+            println!("{}/{}{}", base_link, port, link_suffix);
         }
-        Err(e) => {
-            // Silently ignore if clipboard isn't available
-            info!("Clipboard not available: {}", e);
+    }
+
+    copy_to_clipboard(&link, quiet);
+
+    if !quiet {
+        println!("\n╔══════════════════════════════════════════════════════════════╗");
+        println!("║                      🔑 Rift Share                           ║");
+        println!("╠══════════════════════════════════════════════════════════════╣");
+        if !local_ports.is_empty() {
+            println!("║ {:<60} ║", format!("Sharing: localhost:{}", format_ports(&local_ports)));
+        }
+        for (host, port) in &targets {
+            println!("║ {:<60} ║", format!("Sharing: {} (through this machine)", ExitRequest { host: host.clone(), port: *port }));
+        }
+        println!("║                                                              ║");
+        println!("║ Share this link with peers:                                  ║");
+        println!("║ {:<54}  ║", link);
+        if ports.len() > 1 {
+            println!("║ {:<60} ║", "Peers pick a port with rift://<PEER_ID>/<PORT>");
+        }
+        if let Some(name) = names.first() {
+            println!("║ {:<60} ║", format!("Peers can connect by name: rift connect <LINK> {}", name));
+        }
+        println!("║                                                              ║");
+        if let Some(ttl) = guest {
+            println!("║ {:<60} ║", format!("Guest link: access ends in {}", links::format_secs(ttl)));
+        }
+        println!("║ Waiting for connections...                                   ║");
+        println!("╚══════════════════════════════════════════════════════════════╝\n");
+    }
+
+    if let Some(page_port) = link_page {
+        match link_page::serve(format!("{}/{}{}", base_link, ports[0], link_suffix), page_port).await {
+            Ok(addr) if quiet => eprintln!("🔗 Link page: http://{}", addr),
+            Ok(addr) => println!("🔗 Link page: http://{}\n", addr),
+            Err(e) => error!("Failed to start link page: {}", e),
         }
     }
-    
-    println!("\n╔══════════════════════════════════════════════════════════════╗");
-    println!("║                      🔑 Rift Share                           ║");
-    println!("╠══════════════════════════════════════════════════════════════╣");
-    println!("║ Sharing: localhost:{}                                       ║", port);
-    println!("║                                                              ║");
-    println!("║ Share this link with peers:                                  ║");
-    println!("║ {}  ║", format!("{:<54}", link));
-    println!("║                                                              ║");
-    println!("║ Waiting for connections...                                   ║");
-    println!("╚══════════════════════════════════════════════════════════════╝\n");
 
     if no_tui {
         // Simple mode - just run the daemon
         daemon.run().await?;
     } else {
         // Spawn daemon to run in background (processes network events)
-        tokio::spawn(async move {
+        let handle = tokio::spawn(async move {
             if let Err(e) = daemon.run().await {
                 error!("Daemon error: {}", e);
             }
         });
         
         // Run TUI in foreground (receives events from daemon)
-        tui::run_share_tui(port, link, event_rx, command_tx).await?;
+        let exit = tui::run_share_tui(ports.clone(), link, &history_limits, keep_alive_on_exit, event_rx, command_tx.clone()).await?;
+        if exit == tui::ExitAction::KeepAlive {
+            keep_alive(&describe_shares(&local_ports, &targets), handle, command_tx).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Keep the daemon serving after the dashboard closed, until Ctrl-C or SIGTERM
+///
+/// Closing the terminal does not end the session either: SIGHUP is ignored from
+/// here on, so a demo survives the window it was started from.
+async fn keep_alive(
+    shares: &str,
+    mut handle: tokio::task::JoinHandle<()>,
+    command_tx: tokio::sync::mpsc::Sender<DaemonCommand>,
+) -> Result<()> {
+    let pid = std::process::id();
+    eprintln!("Still sharing {} without the dashboard (pid {})", shares, pid);
+    eprintln!("Stop it with Ctrl-C or `kill {}`", pid);
+
+    #[cfg(unix)]
+    let (mut hangup, mut terminate) = {
+        use tokio::signal::unix::{signal, SignalKind};
+        (signal(SignalKind::hangup())?, signal(SignalKind::terminate())?)
+    };
+    loop {
+        #[cfg(unix)]
+        let stop = tokio::select! {
+            _ = &mut handle => return Ok(()),
+            _ = tokio::signal::ctrl_c() => true,
+            _ = terminate.recv() => true,
+            _ = hangup.recv() => false,
+        };
+        #[cfg(not(unix))]
+        let stop = tokio::select! {
+            _ = &mut handle => return Ok(()),
+            _ = tokio::signal::ctrl_c() => true,
+        };
+        if stop {
+            break;
+        }
+        info!("Terminal closed; still sharing {}", shares);
     }
 
+    let _ = command_tx.send(DaemonCommand::Shutdown).await;
+    let _ = handle.await;
     Ok(())
 }
+
+/// Shared ports for messages (`3000, 8080`)
+fn format_ports(ports: &[u16]) -> String {
+    ports.iter().map(u16::to_string).collect::<Vec<_>>().join(", ")
+}
+
+/// What a share serves, for messages (`localhost:3000, 8080 and 192.168.1.50:8443`)
+fn describe_shares(local_ports: &[u16], targets: &[(String, u16)]) -> String {
+    let mut shares: Vec<String> = Vec::new();
+    if !local_ports.is_empty() {
+        shares.push(format!("localhost:{}", format_ports(local_ports)));
+    }
+    shares.extend(targets.iter().map(|(host, port)| ExitRequest { host: host.clone(), port: *port }.to_string()));
+    shares.join(" and ")
+}
+
+/// Try to copy the link to the clipboard (non-fatal if it fails)
+#[cfg(feature = "clipboard")]
+fn copy_to_clipboard(link: &str, quiet: bool) {
+    match arboard::Clipboard::new() {
+        Ok(mut clipboard) => {
+            if let Err(e) = clipboard.set_text(link) {
+                // Silently ignore clipboard errors (headless environments)
+                info!("Failed to copy to clipboard: {}", e);
+            } else if !quiet {
+                println!("📋 Link copied to clipboard!");
+            }
+        }
+        Err(e) => {
+            // Silently ignore if clipboard isn't available
+            info!("Clipboard not available: {}", e);
+        }
+    }
+}
+
+/// Builds without clipboard support only print the link
+#[cfg(not(feature = "clipboard"))]
+fn copy_to_clipboard(_link: &str, _quiet: bool) {}
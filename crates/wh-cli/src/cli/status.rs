@@ -0,0 +1,128 @@
+//! Status Command Implementation
+
+use anyhow::{bail, Context, Result};
+use wh_core::{stats::LatencySummary, RiftConfig};
+use wh_daemon::remote::{self, RemoteCommand, RemoteResponse, CONTROL_TOKEN_FILE};
+use wh_daemon::status::StatusSnapshot;
+
+use crate::tui::{format_bytes, format_port_stats, format_traffic_classes};
+
+/// Run the status command
+pub async fn run() -> Result<()> {
+    let config = RiftConfig::load_or_default(&RiftConfig::default_config_path())?;
+    let snapshots = StatusSnapshot::read_all(config.open_state_store()?.as_ref());
+
+    if snapshots.is_empty() {
+        println!("\nNo running Rift sessions.\n");
+        return Ok(());
+    }
+
+    println!("\n🔑 Rift Status\n");
+    for snapshot in snapshots {
+        print_snapshot(&snapshot);
+    }
+
+    Ok(())
+}
+
+/// Run the status command against a daemon's remote control API
+///
+/// With `close`, ask the daemon to close that connection instead of listing its status.
+pub async fn run_remote(addr: &str, token: Option<String>, close: Option<u64>) -> Result<()> {
+    let token = match token {
+        Some(token) => token,
+        None => {
+            let config = RiftConfig::load_or_default(&RiftConfig::default_config_path())?;
+            let path = config.state_dir().join(CONTROL_TOKEN_FILE);
+            std::fs::read_to_string(&path)
+                .map(|token| token.trim().to_string())
+                .with_context(|| format!("no --token given and {} is unreadable", path.display()))?
+        }
+    };
+
+    if let Some(connection_id) = close {
+        return match remote::request(addr, &token, RemoteCommand::CloseConnection { connection_id }).await? {
+            RemoteResponse::Ok => {
+                println!("Asked {} to close connection #{}", addr, connection_id);
+                Ok(())
+            }
+            RemoteResponse::Error { message } => bail!("{}: {}", addr, message),
+            RemoteResponse::Status { .. } => bail!("{}: unexpected answer", addr),
+        };
+    }
+
+    match remote::request(addr, &token, RemoteCommand::Status).await? {
+        RemoteResponse::Status { status } => {
+            println!("\n🔑 Rift Status ({})\n", addr);
+            print_snapshot(&status);
+            Ok(())
+        }
+        RemoteResponse::Error { message } => bail!("{}: {}", addr, message),
+        RemoteResponse::Ok => bail!("{}: unexpected answer", addr),
+    }
+}
+
+fn print_snapshot(snapshot: &StatusSnapshot) {
+    {
+        let port = snapshot
+            .port
+            .map(|p| format!(" :{}", p))
+            .unwrap_or_default();
+        println!("{}{} (pid {})", snapshot.mode, port, snapshot.pid);
+        println!("  Connections: {} ({} tasks)", snapshot.active_connections, snapshot.live_tasks);
+        println!(
+            "  Traffic:     {} sent / {} received",
+            format_bytes(snapshot.bytes_sent),
+            format_bytes(snapshot.bytes_received)
+        );
+        println!(
+            "  Session:     {} sent / {} received",
+            format_bytes(snapshot.session_bytes_sent),
+            format_bytes(snapshot.session_bytes_received)
+        );
+        if !snapshot.traffic_classes.is_empty() {
+            println!("  Traffic mix: {}", format_traffic_classes(&snapshot.traffic_classes));
+        }
+        if snapshot.shared_ports.len() > 1 {
+            let ports: Vec<String> = snapshot.shared_ports.iter().map(u16::to_string).collect();
+            println!("  Shared ports: {}", ports.join(", "));
+            println!("  Per port:    {}", format_port_stats(&snapshot.ports));
+        }
+        for peer in &snapshot.peer_traffic {
+            println!(
+                "  Peer {}…  {} open / {} total  {} / {}  {}/s",
+                peer.peer_id.get(..16).unwrap_or(&peer.peer_id),
+                peer.open,
+                peer.connections,
+                format_bytes(peer.bytes_sent),
+                format_bytes(peer.bytes_received),
+                format_bytes(peer.rate_sent + peer.rate_received),
+            );
+        }
+        println!("  Stream open: {}", format_latency(&snapshot.stream_open));
+        println!("  Target dial: {}", format_latency(&snapshot.target_connect));
+        for conn in &snapshot.connections {
+            println!(
+                "    #{} {}… {} :{} {}  {} / {} ({}/s)  up {}s, idle {}s",
+                conn.id,
+                conn.peer_id.get(..16).unwrap_or(&conn.peer_id),
+                conn.path,
+                conn.port,
+                conn.class.map(|class| class.to_string()).unwrap_or_else(|| "-".to_string()),
+                format_bytes(conn.bytes_sent),
+                format_bytes(conn.bytes_received),
+                format_bytes(conn.rate_sent + conn.rate_received),
+                snapshot.updated_at.saturating_sub(conn.started_at),
+                snapshot.updated_at.saturating_sub(conn.last_activity),
+            );
+        }
+        println!();
+    }
+}
+
+fn format_latency(summary: &LatencySummary) -> String {
+    match (summary.p50_ms, summary.p95_ms) {
+        (Some(p50), Some(p95)) => format!("p50 ≤{}ms  p95 ≤{}ms  ({} samples)", p50, p95, summary.count),
+        _ => "no samples".to_string(),
+    }
+}
@@ -0,0 +1,401 @@
+//! Term Command Implementation
+//!
+//! `rift term share` runs a shell in a pseudo-terminal and streams it to
+//! approved viewers; `rift term view` watches one. Viewers are read-only
+//! unless the host passes `--interactive`.
+
+use anyhow::{bail, Context, Result};
+use crossterm::terminal;
+use futures::StreamExt;
+use portable_pty::{native_pty_system, CommandBuilder, PtySize};
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{broadcast, mpsc};
+use tokio_util::compat::FuturesAsyncReadCompatExt;
+use wh_core::term::{Scrollback, TermMessage};
+use wh_core::{receive_secrets, send_secrets, PeerId, PeerNetwork, RiftConfig, RiftProtocol, Stream};
+
+use super::TermAction;
+
+/// Attempts at dialing the host while discovery catches up
+const MAX_DIAL_RETRIES: u32 = 20;
+
+/// Ctrl-] leaves a viewer session
+const DETACH_KEY: u8 = 0x1d;
+
+/// Ctrl-C also leaves a read-only viewer session
+const CTRL_C: u8 = 0x03;
+
+/// How often the host terminal is checked for size changes
+const RESIZE_POLL: Duration = Duration::from_millis(500);
+
+/// Run the term command
+pub async fn run(action: TermAction) -> Result<()> {
+    match action {
+        TermAction::Share {
+            interactive,
+            allow,
+            auto_approve,
+            command,
+        } => share(command, interactive, allow, auto_approve).await,
+        TermAction::View { link } => view(link).await,
+    }
+}
+
+async fn share(command: Vec<String>, interactive: bool, allow: Vec<String>, auto_approve: bool) -> Result<()> {
+    let allowed = allow
+        .iter()
+        .map(|peer| peer.parse::<PeerId>().with_context(|| format!("invalid peer ID '{}'", peer)))
+        .collect::<Result<Vec<_>>>()?;
+    let config = RiftConfig::load_or_default(&RiftConfig::default_config_path())?;
+    let policy = config.protocol_policy.clone();
+    let mut network = PeerNetwork::new(config).await?;
+    let mut events = network.take_event_receiver();
+    let mut incoming = network.take_incoming_term_streams();
+    network.start_listening().await?;
+
+    let mut size = terminal::size().unwrap_or((80, 24));
+    let pty = native_pty_system()
+        .openpty(pty_size(size))
+        .context("failed to open a pseudo-terminal")?;
+    let mut program = match command.split_first() {
+        Some((program, args)) => {
+            let mut program = CommandBuilder::new(program);
+            program.args(args);
+            program
+        }
+        None => CommandBuilder::new_default_prog(),
+    };
+    program.cwd(std::env::current_dir()?);
+    let mut child = pty
+        .slave
+        .spawn_command(program)
+        .context("failed to start the shared program")?;
+    drop(pty.slave);
+    let mut pty_writer = pty.master.take_writer()?;
+    let mut pty_output = spawn_reader(pty.master.try_clone_reader()?);
+
+    println!(
+        "\n📺 Sharing terminal ({})",
+        if interactive { "viewers can type" } else { "read-only" }
+    );
+    println!("   Watch with: rift term view {}", network.rift_link());
+    println!("   Exit the shell to stop sharing.\n");
+
+    let raw_mode = RawMode::enable()?;
+    let mut stdin = spawn_reader(std::io::stdin());
+    let mut stdout = std::io::stdout();
+    let (output_tx, _) = broadcast::channel(256);
+    let (input_tx, mut viewer_input) = mpsc::channel(64);
+    let (left_tx, mut left) = mpsc::unbounded_channel();
+    let mut scrollback = Scrollback::default();
+    let mut pending: VecDeque<(PeerId, Stream)> = VecDeque::new();
+    let mut resize_check = tokio::time::interval(RESIZE_POLL);
+
+    loop {
+        tokio::select! {
+            _ = network.poll_once() => {}
+            Some(_) = events.recv() => {}
+            Some((peer_id, stream)) = incoming.next() => {
+                if !policy.is_allowed(&peer_id, RiftProtocol::Term) {
+                    notice(&format!("Rejected {} (term is not in protocol_policy)", peer_id));
+                } else if auto_approve || allowed.contains(&peer_id) {
+                    notice(&format!("{} is watching", peer_id));
+                    start_viewer(peer_id, stream, size, interactive, &scrollback, &output_tx, &input_tx, &left_tx);
+                } else {
+                    if pending.is_empty() {
+                        prompt(peer_id, interactive);
+                    }
+                    pending.push_back((peer_id, stream));
+                }
+            }
+            data = pty_output.recv() => match data {
+                Some(data) => {
+                    stdout.write_all(&data)?;
+                    stdout.flush()?;
+                    scrollback.push(&data);
+                    for message in TermMessage::output(&data) {
+                        let _ = output_tx.send(message);
+                    }
+                }
+                None => break,
+            },
+            Some(keys) = stdin.recv() => {
+                // While a viewer waits for approval, the next keypress answers the prompt
+                if let Some((peer_id, stream)) = pending.pop_front() {
+                    if matches!(keys.first(), Some(b'y' | b'Y')) {
+                        notice(&format!("{} is watching", peer_id));
+                        start_viewer(peer_id, stream, size, interactive, &scrollback, &output_tx, &input_tx, &left_tx);
+                    } else {
+                        notice(&format!("Denied {}", peer_id));
+                    }
+                    if let Some((next, _)) = pending.front() {
+                        prompt(*next, interactive);
+                    }
+                } else {
+                    pty_writer.write_all(&keys)?;
+                }
+            }
+            Some(keys) = viewer_input.recv() => {
+                pty_writer.write_all(&keys)?;
+            }
+            Some(peer_id) = left.recv() => {
+                notice(&format!("{} stopped watching", peer_id));
+            }
+            _ = resize_check.tick() => {
+                if let Ok(current) = terminal::size()
+                    && current != size
+                {
+                    size = current;
+                    let _ = pty.master.resize(pty_size(size));
+                    let _ = output_tx.send(TermMessage::Resize { cols: size.0, rows: size.1 });
+                }
+            }
+        }
+    }
+
+    let code = child.wait().map(|status| status.exit_code()).unwrap_or(1);
+    let _ = output_tx.send(TermMessage::Exit { code });
+
+    // Keep the swarm running briefly so viewers receive the last output
+    let flush = tokio::time::sleep(Duration::from_millis(500));
+    tokio::pin!(flush);
+    loop {
+        tokio::select! {
+            _ = network.poll_once() => {}
+            _ = &mut flush => break,
+        }
+    }
+
+    drop(raw_mode);
+    network.shutdown().await;
+    println!("\n📺 Terminal sharing ended (exit code {})", code);
+    Ok(())
+}
+
+/// Stream the session to an approved viewer
+#[allow(clippy::too_many_arguments)]
+fn start_viewer(
+    peer_id: PeerId,
+    stream: Stream,
+    (cols, rows): (u16, u16),
+    interactive: bool,
+    scrollback: &Scrollback,
+    output_tx: &broadcast::Sender<TermMessage>,
+    input_tx: &mpsc::Sender<Vec<u8>>,
+    left_tx: &mpsc::UnboundedSender<PeerId>,
+) {
+    // Subscribe and snapshot together so the viewer sees no gap or repeat
+    let mut output = output_tx.subscribe();
+    let backlog = scrollback.contents();
+    let input = interactive.then(|| input_tx.clone());
+    let left_tx = left_tx.clone();
+
+    tokio::spawn(async move {
+        let (mut reader, mut writer) = tokio::io::split(stream.compat());
+        let from_viewer = async {
+            while let Ok(message) = receive_secrets::<TermMessage, _>(&mut reader).await {
+                if let (TermMessage::Input { data }, Some(input)) = (message, &input)
+                    && input.send(data).await.is_err()
+                {
+                    break;
+                }
+            }
+        };
+        let to_viewer = async {
+            send_secrets(&mut writer, &TermMessage::Hello { cols, rows, interactive }).await?;
+            for message in TermMessage::output(&backlog) {
+                send_secrets(&mut writer, &message).await?;
+            }
+            loop {
+                match output.recv().await {
+                    Ok(message) => {
+                        let exit = matches!(message, TermMessage::Exit { .. });
+                        send_secrets(&mut writer, &message).await?;
+                        if exit {
+                            let _ = writer.shutdown().await;
+                            break;
+                        }
+                    }
+                    // A slow viewer skips output rather than stalling the host
+                    Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+            Ok::<_, wh_core::RiftError>(())
+        };
+        tokio::select! {
+            _ = from_viewer => {}
+            _ = to_viewer => {}
+        }
+        let _ = left_tx.send(peer_id);
+    });
+}
+
+async fn view(link: String) -> Result<()> {
+    let config = RiftConfig::load_or_default(&RiftConfig::default_config_path())?;
+    let mut network = PeerNetwork::new(config).await?;
+    let mut events = network.take_event_receiver();
+    network.start_listening().await?;
+
+    let mut retry_count = 0;
+    let peer_id = loop {
+        let _ = network.poll_once().await;
+        match network.connect(&link).await {
+            Ok(peer_id) => break peer_id,
+            Err(_) if retry_count < MAX_DIAL_RETRIES => {
+                retry_count += 1;
+                tokio::time::sleep(Duration::from_millis(250)).await;
+            }
+            Err(e) => {
+                network.shutdown().await;
+                return Err(e.into());
+            }
+        }
+    };
+
+    let mut control = network.stream_control();
+    let open = control.open_stream(peer_id, network.protocols().term.clone());
+    tokio::pin!(open);
+    let stream = loop {
+        tokio::select! {
+            _ = network.poll_once() => {}
+            Some(_) = events.recv() => {}
+            result = &mut open => break result.with_context(|| format!("failed to open a terminal stream to {}", peer_id))?,
+        }
+    };
+
+    let (mut reader, mut writer) = tokio::io::split(stream.compat());
+    let (message_tx, mut messages) = mpsc::channel(64);
+    let reader_task = tokio::spawn(async move {
+        while let Ok(message) = receive_secrets::<TermMessage, _>(&mut reader).await {
+            if message_tx.send(message).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    println!("\n📺 Waiting for {} to approve...", peer_id);
+    let interactive = loop {
+        tokio::select! {
+            _ = network.poll_once() => {}
+            Some(_) = events.recv() => {}
+            message = messages.recv() => match message {
+                Some(TermMessage::Hello { cols, rows, interactive }) => {
+                    println!(
+                        "   Watching {} ({}x{}, {}). Press Ctrl-] to leave.\n",
+                        peer_id,
+                        cols,
+                        rows,
+                        if interactive { "interactive" } else { "read-only" }
+                    );
+                    break interactive;
+                }
+                Some(_) => {}
+                None => {
+                    reader_task.abort();
+                    network.shutdown().await;
+                    bail!("{} declined", peer_id);
+                }
+            },
+            _ = tokio::signal::ctrl_c() => {
+                reader_task.abort();
+                network.shutdown().await;
+                return Ok(());
+            }
+        }
+    };
+
+    let raw_mode = RawMode::enable()?;
+    let mut stdin = spawn_reader(std::io::stdin());
+    let mut stdout = std::io::stdout();
+    let outcome = loop {
+        tokio::select! {
+            _ = network.poll_once() => {}
+            Some(_) = events.recv() => {}
+            message = messages.recv() => match message {
+                Some(TermMessage::Output { data }) => {
+                    stdout.write_all(&data)?;
+                    stdout.flush()?;
+                }
+                Some(TermMessage::Exit { code }) => break format!("Shared program exited (code {})", code),
+                Some(_) => {}
+                None => break "Host ended the session".to_string(),
+            },
+            Some(keys) = stdin.recv() => {
+                if keys.contains(&DETACH_KEY) || (!interactive && keys.contains(&CTRL_C)) {
+                    break "Detached".to_string();
+                }
+                if interactive {
+                    send_secrets(&mut writer, &TermMessage::Input { data: keys }).await?;
+                }
+            }
+        }
+    };
+
+    drop(raw_mode);
+    reader_task.abort();
+    network.shutdown().await;
+    println!("\n📺 {}", outcome);
+    Ok(())
+}
+
+/// Keeps the local terminal in raw mode until dropped
+struct RawMode;
+
+impl RawMode {
+    fn enable() -> Result<Self> {
+        terminal::enable_raw_mode()?;
+        Ok(Self)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Forward bytes from a blocking reader on its own thread
+fn spawn_reader(mut reader: impl Read + Send + 'static) -> mpsc::Receiver<Vec<u8>> {
+    let (tx, rx) = mpsc::channel(64);
+    std::thread::spawn(move || {
+        let mut buf = [0u8; 4096];
+        loop {
+            match reader.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    if tx.blocking_send(buf[..n].to_vec()).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+    rx
+}
+
+fn pty_size((cols, rows): (u16, u16)) -> PtySize {
+    PtySize {
+        rows,
+        cols,
+        pixel_width: 0,
+        pixel_height: 0,
+    }
+}
+
+/// Print a line between shared output while in raw mode
+fn notice(message: &str) {
+    print!("\r\n[rift] {}\r\n", message);
+    let _ = std::io::stdout().flush();
+}
+
+fn prompt(peer_id: PeerId, interactive: bool) {
+    notice(&format!(
+        "{} wants to watch{}. Allow? [y/N]",
+        peer_id,
+        if interactive { " and type" } else { "" }
+    ));
+}
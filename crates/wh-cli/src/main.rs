@@ -18,30 +18,132 @@ async fn main() -> Result<()> {
 
     // Only setup logging when TUI is NOT active (to prevent log output corrupting TUI)
     // When TUI is active, logs go through the TUI's event log panel instead
-    if cli.no_tui {
+    // Quiet mode implies no TUI and keeps stdout for the link alone
+    // Builds without the TUI always run headless
+    let no_tui = cli.no_tui || cli.quiet || !tui::AVAILABLE;
+    if no_tui {
         let filter = if cli.verbose {
             EnvFilter::new("debug")
+        } else if cli.quiet {
+            EnvFilter::new("warn")
         } else {
             EnvFilter::new("info")
         };
 
-        tracing_subscriber::registry()
-            .with(fmt::layer().with_target(false))
-            .with(filter)
-            .init();
+        if cli.quiet {
+            tracing_subscriber::registry()
+                .with(fmt::layer().with_target(false).with_writer(std::io::stderr))
+                .with(filter)
+                .init();
+        } else {
+            tracing_subscriber::registry()
+                .with(fmt::layer().with_target(false))
+                .with(filter)
+                .init();
+        }
+    }
+
+    // Before any configuration is loaded, so every path follows it
+    if let Some(dir) = cli.config_dir {
+        wh_core::RiftConfig::override_config_dir(dir)?;
+    }
+
+    // Only status is answered over the remote control API
+    if let Some(remote) = cli.remote {
+        return match cli.command {
+            Commands::Status { close } => cli::status::run_remote(&remote, cli.token, close).await,
+            _ => anyhow::bail!("--remote only works with `rift status`"),
+        };
     }
 
     // Execute command
     match cli.command {
-        Commands::Share { port, secrets, auto_approve } => {
-            cli::share::run(port, secrets, auto_approve, cli.no_tui).await?;
+        Commands::Share { ports, targets, secrets, auto_approve, guest, sni_routes, names, link_page, max_rate, per_peer, detach, keep_alive_on_exit, addrs, dry_run } => {
+            let options = cli::share::ShareOptions {
+                secrets,
+                auto_approve,
+                guest,
+                sni_routes,
+                targets,
+                names,
+                max_rate,
+                per_peer,
+                keystore: cli.keystore,
+                link_page,
+                quiet: cli.quiet,
+                detach,
+                keep_alive_on_exit,
+                addrs,
+                dry_run,
+            };
+            cli::share::run(ports, options, no_tui).await?;
         }
-        Commands::Connect { link, local_port, request_secrets, save_secrets, public } => {
-            cli::connect::run(link, local_port, request_secrets, save_secrets, public, cli.no_tui).await?;
+        Commands::Connect { link, service, addrs, local_port, ports, request_secrets, save_secrets, public, ephemeral, stripe, http_log, wait_ready, list, probe, dry_run } => {
+            let options = cli::connect::ConnectOptions {
+                local_port,
+                ports,
+                request_secrets,
+                save_secrets,
+                public,
+                ephemeral,
+                stripe,
+                keystore: cli.keystore,
+                http_log,
+                quiet: cli.quiet,
+                dry_run,
+                wait_ready,
+                list,
+                probe,
+                addrs,
+                service,
+            };
+            cli::connect::run(link, options, no_tui).await?;
         }
         Commands::Info => {
             cli::info::run().await?;
         }
+        Commands::Discover { watch, timeout } => {
+            cli::discover::run(watch, timeout).await?;
+        }
+        Commands::Peers { action: Some(action), .. } => {
+            cli::peers::run_action(action).await?;
+        }
+        Commands::Peers { action: None, watch, timeout } => {
+            cli::peers::run(watch, timeout).await?;
+        }
+        Commands::Doctor { timeout } => {
+            cli::doctor::run(timeout).await?;
+        }
+        Commands::Status { close } => {
+            if close.is_some() {
+                anyhow::bail!("--close needs --remote: connections are closed through the daemon's remote control API");
+            }
+            cli::status::run().await?;
+        }
+        Commands::Relay { port, websocket_port, external_addrs } => {
+            cli::relay::run(port, websocket_port, external_addrs).await?;
+        }
+        Commands::Relays { action } => {
+            cli::relays::run(action).await?;
+        }
+        Commands::Links { action } => {
+            cli::links::run(action).await?;
+        }
+        Commands::Ping { link, count, size, interval_ms } => {
+            cli::ping::run(link, count, size, interval_ms).await?;
+        }
+        Commands::Attach { link } => {
+            cli::attach::run(link, no_tui).await?;
+        }
+        Commands::Term { action } => {
+            cli::term::run(action).await?;
+        }
+        Commands::Exit { action } => {
+            cli::exit::run(action).await?;
+        }
+        Commands::Identity { action } => {
+            cli::identity::run(action).await?;
+        }
     }
 
     Ok(())
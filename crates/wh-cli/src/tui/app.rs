@@ -7,13 +7,15 @@ use crossterm::{
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use futures::StreamExt;
-use wh_daemon::{DaemonCommand, DaemonEvent};
+use wh_core::{control::ServiceHealth, stats::TrafficBreakdown, History, HistoryLimits, PeerCapabilities};
+use wh_daemon::{connections::PortStats, http_log::InspectedExchange, DaemonCommand, DaemonEvent};
 use ratatui::{prelude::*, Terminal};
+use std::collections::HashMap;
 use std::io;
 use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 
-use super::ui;
+use super::{format_bytes, ui, ExitAction};
 
 /// Application mode
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -27,12 +29,24 @@ pub enum AppMode {
 pub struct ConnectionEntry {
     pub peer_id: String,
     pub connected_at: Instant,
-    #[allow(dead_code)]
     pub bytes_sent: u64,
-    #[allow(dead_code)]
     pub bytes_received: u64,
+    /// Bytes per second both ways, over the daemon's last sample
+    pub rate: u64,
     #[allow(dead_code)]
     pub active: bool,
+    /// Rolling average ping round trip, once known
+    pub rtt_ms: Option<u64>,
+}
+
+/// A returning peer's secrets request with newly added keys (share mode)
+#[derive(Debug, Clone)]
+pub struct SecretsPrompt {
+    pub peer_id: String,
+    /// Keys the peer received before, shared by default
+    pub keys: Vec<String>,
+    /// Keys added since, shared only if accepted
+    pub new_keys: Vec<String>,
 }
 
 /// Application state
@@ -43,6 +57,9 @@ pub struct App {
     /// Local port (share mode) or remote port (connect mode)
     pub port: u16,
 
+    /// Every shared port (share mode); `port` is the first
+    pub shared_ports: Vec<u16>,
+
     /// Local port for connect mode
     #[allow(dead_code)]
     pub local_port: u16,
@@ -51,7 +68,7 @@ pub struct App {
     pub link: String,
 
     /// Active connections
-    pub connections: Vec<ConnectionEntry>,
+    pub connections: History<ConnectionEntry>,
 
     /// Total bytes sent
     pub bytes_sent: u64,
@@ -59,12 +76,36 @@ pub struct App {
     /// Total bytes received
     pub bytes_received: u64,
 
+    /// Bytes sent since the session started or the counters were reset
+    pub session_bytes_sent: u64,
+
+    /// Bytes received since the session started or the counters were reset
+    pub session_bytes_received: u64,
+
+    /// Show lifetime totals instead of the session counters
+    pub show_lifetime: bool,
+
+    /// Connections and bytes per kind of traffic
+    pub traffic_classes: TrafficBreakdown,
+
+    /// Connections and bytes per port
+    pub port_stats: Vec<PortStats>,
+
     /// Log messages
-    pub logs: Vec<String>,
+    pub logs: History<String>,
 
     /// Should quit
     pub should_quit: bool,
 
+    /// What happens to the session once the dashboard quits
+    pub exit_action: ExitAction,
+
+    /// Quit keeps the session running without asking (share mode)
+    pub keep_alive_on_exit: bool,
+
+    /// Asking whether quitting should stop the session or keep it running
+    pub confirm_quit: bool,
+
     /// Status message
     pub status: String,
 
@@ -75,67 +116,158 @@ pub struct App {
     pub show_help: bool,
 
     /// Traffic history for graph (bytes per second)
-    pub traffic_history: Vec<u64>,
+    pub traffic_history: History<u64>,
 
     /// Last stats update time
     last_stats_update: Instant,
 
     /// Pending connection approval request
     pub pending_approval: Option<String>,
+
+    /// Pending secrets request with new keys
+    pub pending_secrets: Option<SecretsPrompt>,
+
+    /// Identity claims presented by peers (peer_id -> claims, signature valid)
+    pub attestations: HashMap<String, (String, bool)>,
+
+    /// Optional features peers announced (peer_id -> capabilities)
+    pub capabilities: HashMap<String, PeerCapabilities>,
+
+    /// Nicknames the user gave peers (peer_id -> nickname)
+    pub nicknames: HashMap<String, String>,
+
+    /// Captured HTTP exchanges, newest last
+    pub inspected: History<InspectedExchange>,
+
+    /// Show the request inspector instead of the traffic graph
+    pub show_inspector: bool,
+
+    /// Selected inspector entry, counted from the newest
+    pub inspector_selected: usize,
+
+    /// Health of the shared service, once known
+    pub service_health: Option<ServiceHealth>,
+
+    /// Local connections are held while the host's service is down (connect mode)
+    pub listener_paused: bool,
+
+    /// The connect command is still looking for the host (connect mode); `x` cancels it
+    pub connecting: bool,
+
+    /// Watching another daemon with `rift attach`: keys that act on the session are ignored
+    pub read_only: bool,
 }
 
 impl App {
     /// Create a new app for share mode
-    pub fn new_share(port: u16, link: String) -> Self {
+    pub fn new_share(ports: Vec<u16>, link: String, limits: &HistoryLimits) -> Self {
+        let port = ports.first().copied().unwrap_or_default();
         Self {
             mode: AppMode::Share,
             port,
+            shared_ports: ports,
             local_port: port,
             link,
-            connections: Vec::new(),
+            connections: History::new(limits.connections),
             bytes_sent: 0,
             bytes_received: 0,
-            logs: vec!["Rift started...".to_string()],
+            session_bytes_sent: 0,
+            session_bytes_received: 0,
+            show_lifetime: false,
+            traffic_classes: TrafficBreakdown::default(),
+            port_stats: Vec::new(),
+            logs: Self::initial_logs(limits.log_lines),
             should_quit: false,
+            exit_action: ExitAction::Stop,
+            keep_alive_on_exit: false,
+            confirm_quit: false,
             status: "Waiting for connections".to_string(),
             secrets_count: 0,
             show_help: false,
-            traffic_history: vec![0; 120], // More data points for smoother graph
+            traffic_history: History::filled(limits.traffic_samples, 0),
             last_stats_update: Instant::now(),
             pending_approval: None,
+            pending_secrets: None,
+            attestations: HashMap::new(),
+            capabilities: HashMap::new(),
+            nicknames: HashMap::new(),
+            inspected: History::new(limits.inspected_requests),
+            show_inspector: false,
+            inspector_selected: 0,
+            service_health: None,
+            listener_paused: false,
+            connecting: false,
+            read_only: false,
         }
     }
 
     /// Create a new app for connect mode
-    pub fn new_connect(peer_link: String, remote_port: u16, local_port: u16) -> Self {
+    pub fn new_connect(peer_link: String, remote_port: u16, local_port: u16, limits: &HistoryLimits) -> Self {
         Self {
             mode: AppMode::Connect,
             port: remote_port,
+            shared_ports: Vec::new(),
             local_port,
             link: peer_link,
-            connections: Vec::new(),
+            connections: History::new(limits.connections),
             bytes_sent: 0,
             bytes_received: 0,
-            logs: vec!["Rift started...".to_string()],
+            session_bytes_sent: 0,
+            session_bytes_received: 0,
+            show_lifetime: false,
+            traffic_classes: TrafficBreakdown::default(),
+            port_stats: Vec::new(),
+            logs: Self::initial_logs(limits.log_lines),
             should_quit: false,
+            exit_action: ExitAction::Stop,
+            keep_alive_on_exit: false,
+            confirm_quit: false,
             status: "Connecting...".to_string(),
             secrets_count: 0,
             show_help: false,
-            traffic_history: vec![0; 120], // More data points for smoother graph
+            traffic_history: History::filled(limits.traffic_samples, 0),
             last_stats_update: Instant::now(),
             pending_approval: None,
+            pending_secrets: None,
+            attestations: HashMap::new(),
+            capabilities: HashMap::new(),
+            nicknames: HashMap::new(),
+            inspected: History::new(limits.inspected_requests),
+            show_inspector: false,
+            inspector_selected: 0,
+            service_health: None,
+            listener_paused: false,
+            connecting: true,
+            read_only: false,
         }
     }
 
+    fn initial_logs(capacity: usize) -> History<String> {
+        let mut logs = History::new(capacity);
+        logs.push("Rift started...".to_string());
+        logs
+    }
+
     /// Add a log message
     pub fn log(&mut self, msg: impl Into<String>) {
         let msg = msg.into();
         self.logs.push(format!("[{}] {}", chrono_lite(), msg));
+    }
+
+    /// A peer's nickname, or the start of its ID
+    pub fn peer_label(&self, peer_id: &str) -> String {
+        match self.nicknames.get(peer_id) {
+            Some(nickname) => nickname.clone(),
+            None => peer_id[..peer_id.len().min(16)].to_string(),
+        }
+    }
 
-        // Keep only last 100 logs
-        if self.logs.len() > 100 {
-            self.logs.remove(0);
+    /// The shared ports (share mode) or the remote port, comma separated
+    pub fn ports_label(&self) -> String {
+        if self.shared_ports.is_empty() {
+            return self.port.to_string();
         }
+        self.shared_ports.iter().map(u16::to_string).collect::<Vec<_>>().join(", ")
     }
 
     /// Handle a daemon event
@@ -150,19 +282,24 @@ impl App {
             DaemonEvent::Listening { address } => {
                 self.log(format!("Listening on {}", address));
             }
-            DaemonEvent::PeerConnected { peer_id } => {
-                self.log(format!("Peer connected: {}", &peer_id[..16]));
+            DaemonEvent::PeerConnected { peer_id, nickname } => {
+                if let Some(nickname) = nickname {
+                    self.nicknames.insert(peer_id.clone(), nickname);
+                }
+                self.log(format!("Peer connected: {}", self.peer_label(&peer_id)));
                 self.connections.push(ConnectionEntry {
                     peer_id: peer_id.clone(),
                     connected_at: Instant::now(),
                     bytes_sent: 0,
                     bytes_received: 0,
+                    rate: 0,
                     active: true,
+                    rtt_ms: None,
                 });
                 self.status = format!("{} peer(s) connected", self.connections.len());
             }
             DaemonEvent::PeerDisconnected { peer_id } => {
-                self.log(format!("Peer disconnected: {}", &peer_id[..16]));
+                self.log(format!("Peer disconnected: {}", self.peer_label(&peer_id)));
                 self.connections.retain(|c| c.peer_id != peer_id);
                 if self.connections.is_empty() {
                     self.status = "Waiting for connections".to_string();
@@ -170,24 +307,232 @@ impl App {
                     self.status = format!("{} peer(s) connected", self.connections.len());
                 }
             }
+            DaemonEvent::PeerNamed { peer_id, nickname } => {
+                self.nicknames.insert(peer_id, nickname);
+            }
+            DaemonEvent::GuestExpired { peer_id } => {
+                self.log(format!("Guest access of {} expired - disconnected", self.peer_label(&peer_id)));
+            }
+            DaemonEvent::PeerList { peers } => {
+                if peers.is_empty() {
+                    self.log("No connected peers");
+                }
+                for peer in peers {
+                    let rtt = peer.rtt_ms.map(|ms| format!(", {}ms", ms)).unwrap_or_default();
+                    let capabilities = match &peer.capabilities {
+                        Some(capabilities) => format!(", supports {}", capabilities),
+                        None => ", features unknown".to_string(),
+                    };
+                    self.log(format!(
+                        "Peer {} {} {} for {}s, {} address(es){}{}",
+                        self.peer_label(&peer.peer_id),
+                        peer.direction,
+                        peer.path,
+                        peer.connected_secs,
+                        peer.addresses.len(),
+                        rtt,
+                        capabilities,
+                    ));
+                }
+            }
+            DaemonEvent::PeerRtt { peer_id, average_ms, .. } => {
+                if let Some(conn) = self.connections.iter_mut().find(|c| c.peer_id == peer_id) {
+                    conn.rtt_ms = Some(average_ms);
+                }
+            }
+            DaemonEvent::PeerDiscovered { peer_id, addresses } => {
+                self.log(format!("Discovered {} on LAN ({} address(es))", self.peer_label(&peer_id), addresses.len()));
+            }
+            DaemonEvent::PeerAttested { peer_id, claims, valid } => {
+                if valid {
+                    self.log(format!("{} claims to be {}", self.peer_label(&peer_id), claims));
+                } else {
+                    self.log(format!("Failed attestation from {}: bad signature", self.peer_label(&peer_id)));
+                }
+                self.attestations.insert(peer_id, (claims, valid));
+            }
+            DaemonEvent::PeerCapabilities { peer_id, capabilities } => {
+                self.log(format!("{} supports {}", self.peer_label(&peer_id), capabilities));
+                self.capabilities.insert(peer_id, capabilities);
+            }
+            DaemonEvent::DialRetrying { peer_id, attempt, max_attempts, discovery } => {
+                let via = if discovery.is_empty() {
+                    String::new()
+                } else {
+                    format!(", discovering via {}", discovery.join(", "))
+                };
+                self.log(format!(
+                    "{} not reachable yet, retrying ({}/{}{}) — x to cancel",
+                    self.peer_label(&peer_id),
+                    attempt,
+                    max_attempts,
+                    via
+                ));
+            }
+            DaemonEvent::ConnectCancelled { peer_id, attempts } => {
+                self.connecting = false;
+                self.status = "Cancelled".to_string();
+                self.log(format!("Stopped connecting to {} after {} attempt(s)", self.peer_label(&peer_id), attempts));
+            }
+            DaemonEvent::UsingRelay { peer_id, relay, rtt_ms } => {
+                let cost = rtt_ms.map(|ms| format!(", {}ms round trips", ms)).unwrap_or_default();
+                self.log(format!("Hole punch to {} failed, going through relay {}{}", self.peer_label(&peer_id), relay, cost));
+            }
+            DaemonEvent::PathUpgraded { peer_id, relayed_ms, direct_ms } => {
+                let latency = match relayed_ms {
+                    Some(relayed_ms) => format!("latency dropped from {} ms to {} ms", relayed_ms, direct_ms),
+                    None => format!("latency {} ms", direct_ms),
+                };
+                self.log(format!("{} upgraded to direct — {}", self.peer_label(&peer_id), latency));
+                if let Some(conn) = self.connections.iter_mut().find(|c| c.peer_id == peer_id) {
+                    conn.rtt_ms = Some(direct_ms);
+                }
+            }
+            DaemonEvent::RelayReserved { relay_peer_id } => {
+                self.log(format!("Relay {} holds a slot for us", self.peer_label(&relay_peer_id)));
+            }
+            DaemonEvent::RelayReservationEnded { relay_peer_id, reason, lost } => {
+                let what = if lost { "Lost the reservation on" } else { "No reservation on" };
+                self.log(format!("{} relay {}: {}", what, self.peer_label(&relay_peer_id), reason));
+            }
+            DaemonEvent::RelaySelected { relay, rtt_ms } => {
+                let rtt = rtt_ms.map(|ms| format!(" ({} ms)", ms)).unwrap_or_default();
+                self.log(format!("Keeping our relay slot on {}{}", relay, rtt));
+            }
+            DaemonEvent::NatDetected { nat_type } => {
+                self.log(format!("NAT: {} ({})", nat_type, nat_type.advice()));
+            }
             DaemonEvent::TunnelEstablished { peer_id, port } => {
-                self.log(format!("Tunnel established with {} on port {}", &peer_id[..16], port));
+                self.connecting = false;
+                self.log(format!("Tunnel established with {} on port {}", self.peer_label(&peer_id), port));
+            }
+            DaemonEvent::TunnelFailed { peer_id, reason } => {
+                self.connecting = false;
+                self.log(format!("Tunnel to {} failed: {}", self.peer_label(&peer_id), reason));
+            }
+            DaemonEvent::WokeFromSleep { asleep_secs, peers } => {
+                self.log(format!("Woke after {}s asleep: renewed listeners, checking {} peer(s)", asleep_secs, peers));
+            }
+            DaemonEvent::PeerLostInSleep { peer_id } => {
+                self.log(format!("Connection to {} did not survive sleep", self.peer_label(&peer_id)));
+            }
+            DaemonEvent::TunnelReconnected { peer_id, attempts, downtime_ms } => {
+                self.log(format!(
+                    "Reconnected to {} after {:.1}s ({} attempt(s))",
+                    self.peer_label(&peer_id),
+                    downtime_ms as f64 / 1000.0,
+                    attempts
+                ));
             }
-            DaemonEvent::TunnelConnection { connection_id } => {
-                self.log(format!("New tunnel connection #{}", connection_id));
+            DaemonEvent::ConnectionOpened { connection_id, peer_id, port } => {
+                self.log(format!("Connection #{} with {} on port {} opened", connection_id, self.peer_label(&peer_id), port));
+            }
+            DaemonEvent::ConnectionClosed { connection_id, bytes_sent, bytes_received, duration_ms, .. } => {
+                self.log(format!(
+                    "Connection #{} closed after {:.1}s (↑{} ↓{})",
+                    connection_id,
+                    duration_ms as f64 / 1000.0,
+                    format_bytes(bytes_sent),
+                    format_bytes(bytes_received),
+                ));
             }
             DaemonEvent::IncomingConnectionRequest { peer_id } => {
-                self.log(format!("Connection request from {}", &peer_id[..16]));
+                self.log(format!("Connection request from {}", self.peer_label(&peer_id)));
                 self.pending_approval = Some(peer_id);
             }
+            DaemonEvent::StreamRejected { peer_id, protocol } => {
+                self.log(format!("Rejected {} stream from {} (policy)", protocol, self.peer_label(&peer_id)));
+            }
+            DaemonEvent::PeerBlocked { peer_id } => {
+                self.log(format!("Refused {} (peer access list)", self.peer_label(&peer_id)));
+            }
+            DaemonEvent::SessionResumed { peer_id } => {
+                self.log(format!("Session resumed by {} (approval skipped)", self.peer_label(&peer_id)));
+                if self.pending_approval.as_deref() == Some(peer_id.as_str()) {
+                    self.pending_approval = None;
+                }
+            }
+            DaemonEvent::ViewerAttached { peer_id } => {
+                self.log(format!("{} is watching this dashboard (read-only)", self.peer_label(&peer_id)));
+            }
+            DaemonEvent::ReconnectApproved { peer_id } => {
+                self.log(format!("{} reconnected (approved within grace window)", self.peer_label(&peer_id)));
+                if self.pending_approval.as_deref() == Some(peer_id.as_str()) {
+                    self.pending_approval = None;
+                }
+            }
+            DaemonEvent::ConnectionMigrated { peer_id, replayed_bytes } => {
+                self.log(format!("Stream to {} dropped; reopened and replayed {} bytes", self.peer_label(&peer_id), replayed_bytes));
+            }
+            DaemonEvent::ConnectionTerminated { connection_id, peer_id, port } => {
+                self.log(format!("Closed connection #{} from {} on port {}", connection_id, self.peer_label(&peer_id), port));
+            }
+            DaemonEvent::ConnectionTimedOut { connection_id, peer_id, port, idle_secs } => {
+                self.log(format!(
+                    "Connection #{} from {} on port {} timed out ({}s idle)",
+                    connection_id,
+                    self.peer_label(&peer_id),
+                    port,
+                    idle_secs
+                ));
+            }
+            DaemonEvent::HttpRequest { exchange } => {
+                self.log(exchange.to_string());
+            }
+            DaemonEvent::HttpInspected { record } => {
+                self.inspected.push(record);
+                // Keep the same entry selected as new ones arrive
+                if self.inspector_selected > 0 {
+                    self.inspector_selected = (self.inspector_selected + 1).min(self.inspected.len() - 1);
+                }
+            }
             DaemonEvent::SecretsReceived { count } => {
                 self.secrets_count = count;
                 self.log(format!("Received {} secrets", count));
             }
+            DaemonEvent::SecretsRequestQueued { peer_id, save_path } => {
+                self.log(format!("{} is away; secrets will be written to {} once it connects", self.peer_label(&peer_id), save_path));
+            }
+            DaemonEvent::QueuedSecretsDelivered { peer_id, count, save_path } => {
+                self.secrets_count = count;
+                self.log(format!("{} is back: {} secrets written to {}", self.peer_label(&peer_id), count, save_path));
+            }
+            DaemonEvent::QueuedSecretsFailed { peer_id, reason } => {
+                self.log(format!("Queued secrets request to {} failed: {}", self.peer_label(&peer_id), reason));
+            }
+            DaemonEvent::SecretsApprovalRequest { peer_id, keys, new_keys } => {
+                self.log(format!("{} asks for secrets again; {} new since last time", self.peer_label(&peer_id), new_keys.len()));
+                self.pending_secrets = Some(SecretsPrompt { peer_id, keys, new_keys });
+            }
+            DaemonEvent::SecretsShared { peer_id, count, withheld } => {
+                if self.pending_secrets.as_ref().is_some_and(|prompt| prompt.peer_id == peer_id) {
+                    self.pending_secrets = None;
+                }
+                if withheld.is_empty() {
+                    self.log(format!("Sent {} secret(s) to {}", count, self.peer_label(&peer_id)));
+                } else {
+                    self.log(format!("Sent {} secret(s) to {}, withheld {}", count, self.peer_label(&peer_id), withheld.join(", ")));
+                }
+            }
+            DaemonEvent::KvChanged { key, value, peer_id } => match value {
+                Some(value) => self.log(format!(
+                    "{} set {} = {}",
+                    self.peer_label(&peer_id),
+                    key,
+                    String::from_utf8_lossy(&value)
+                )),
+                None => self.log(format!("{} deleted {}", self.peer_label(&peer_id), key)),
+            },
             DaemonEvent::StatsUpdate {
                 bytes_sent,
                 bytes_received,
+                session_bytes_sent,
+                session_bytes_received,
                 active_connections: _,
+                live_tasks: _,
+                traffic_classes,
+                ports,
+                peers,
             } => {
                 // Calculate bytes/sec since last update
                 let elapsed = self.last_stats_update.elapsed().as_secs_f64();
@@ -195,24 +540,96 @@ impl App {
                     let bytes_delta = (bytes_sent + bytes_received).saturating_sub(self.bytes_sent + self.bytes_received);
                     let bytes_per_sec = (bytes_delta as f64 / elapsed) as u64;
                     
-                    // Add to history and shift
-                    self.traffic_history.remove(0);
+                    // Oldest sample falls off the front
                     self.traffic_history.push(bytes_per_sec);
                 }
                 
                 self.bytes_sent = bytes_sent;
                 self.bytes_received = bytes_received;
+                self.session_bytes_sent = session_bytes_sent;
+                self.session_bytes_received = session_bytes_received;
+                self.traffic_classes = traffic_classes;
+                self.port_stats = ports;
+                for peer in peers {
+                    if let Some(conn) = self.connections.iter_mut().find(|c| c.peer_id == peer.peer_id) {
+                        conn.bytes_sent = peer.bytes_sent;
+                        conn.bytes_received = peer.bytes_received;
+                        conn.rate = peer.rate_sent + peer.rate_received;
+                    }
+                }
                 self.last_stats_update = Instant::now();
             }
+            DaemonEvent::ConfigReloaded { applied, requires_restart } => {
+                if !applied.is_empty() {
+                    self.log(format!("Config reloaded: applied {}", applied.join(", ")));
+                }
+                if !requires_restart.is_empty() {
+                    self.log(format!("Config changes need a restart: {}", requires_restart.join(", ")));
+                }
+            }
+            DaemonEvent::ServiceHealth { health, paused } => {
+                if paused {
+                    self.log(format!("Warning: service {} - local connections paused", health));
+                } else {
+                    self.log(format!("Service {}", health));
+                }
+                self.service_health = Some(health);
+                self.listener_paused = paused;
+            }
+            DaemonEvent::ResourceWarning { message } => {
+                self.log(format!("Warning: {}", message));
+            }
             DaemonEvent::Error { message } => {
                 self.log(format!("Error: {}", message));
             }
             DaemonEvent::Shutdown => {
                 self.should_quit = true;
+                self.exit_action = ExitAction::Stop;
+            }
+        }
+    }
+
+    /// Counters shown in the stats panel: session or lifetime (sent, received)
+    pub fn displayed_bytes(&self) -> (u64, u64) {
+        if self.show_lifetime {
+            (self.bytes_sent, self.bytes_received)
+        } else {
+            (self.session_bytes_sent, self.session_bytes_received)
+        }
+    }
+
+    /// Inspector entry currently selected
+    pub fn selected_exchange(&self) -> Option<&InspectedExchange> {
+        self.inspected.iter().rev().nth(self.inspector_selected)
+    }
+
+    /// Ask the daemon to send the selected request again
+    fn replay_selected(&mut self) -> Option<DaemonCommand> {
+        let record = self.selected_exchange()?;
+        let summary = format!("{} {}", record.exchange.method, record.exchange.path);
+        match record.request.as_ref().and_then(|request| request.to_bytes()) {
+            Some(request) => {
+                self.log(format!("Replaying {}", summary));
+                Some(DaemonCommand::ReplayRequest { request })
+            }
+            None => {
+                self.log(format!("Cannot replay {}: request body was not captured", summary));
+                None
             }
         }
     }
 
+    /// Leave the dashboard, stopping the daemon unless the session is kept alive
+    fn quit(&mut self, action: ExitAction) -> Option<DaemonCommand> {
+        self.confirm_quit = false;
+        self.should_quit = true;
+        self.exit_action = action;
+        match action {
+            ExitAction::Stop => Some(DaemonCommand::Shutdown),
+            ExitAction::KeepAlive => None,
+        }
+    }
+
     /// Handle keyboard input
     pub fn handle_key(&mut self, key: KeyCode) -> Option<DaemonCommand> {
         // If there's a pending approval, handle y/n first
@@ -234,16 +651,99 @@ impl App {
             }
         }
 
+        if let Some(prompt) = &self.pending_secrets {
+            let peer_id = prompt.peer_id.clone();
+            let command = match key {
+                KeyCode::Char('y') | KeyCode::Char('Y') => DaemonCommand::ApproveSecrets { peer_id, include_new: false },
+                KeyCode::Char('a') | KeyCode::Char('A') => DaemonCommand::ApproveSecrets { peer_id, include_new: true },
+                KeyCode::Char('n') | KeyCode::Char('N') => {
+                    self.log("Secrets request denied");
+                    DaemonCommand::DenySecrets { peer_id }
+                }
+                _ => return None,
+            };
+            self.pending_secrets = None;
+            return Some(command);
+        }
+
+        if self.confirm_quit {
+            return match key {
+                KeyCode::Char('s') | KeyCode::Char('S') => self.quit(ExitAction::Stop),
+                KeyCode::Char('k') | KeyCode::Char('K') => self.quit(ExitAction::KeepAlive),
+                KeyCode::Char('c') | KeyCode::Char('C') | KeyCode::Esc => {
+                    self.confirm_quit = false;
+                    None
+                }
+                _ => None,
+            };
+        }
+
+        // A viewer can look around but not touch the host's session
+        if self.read_only {
+            return match key {
+                KeyCode::Char('q') | KeyCode::Esc => self.quit(ExitAction::Stop),
+                KeyCode::Char('h') => {
+                    self.show_help = !self.show_help;
+                    None
+                }
+                KeyCode::Char('l') => {
+                    self.show_lifetime = !self.show_lifetime;
+                    None
+                }
+                _ => None,
+            };
+        }
+
         // Normal key handling
         match key {
-            KeyCode::Char('q') | KeyCode::Esc => {
-                self.should_quit = true;
-                Some(DaemonCommand::Shutdown)
-            }
+            KeyCode::Char('q') | KeyCode::Esc => match self.mode {
+                AppMode::Share if self.keep_alive_on_exit => self.quit(ExitAction::KeepAlive),
+                // Peers are using the share: ask before cutting them off
+                AppMode::Share if !self.connections.is_empty() => {
+                    self.confirm_quit = true;
+                    None
+                }
+                _ => self.quit(ExitAction::Stop),
+            },
             KeyCode::Char('h') => {
                 self.show_help = !self.show_help;
                 None
             }
+            KeyCode::Char('r') => {
+                self.log("Revoking all session tokens");
+                Some(DaemonCommand::RevokeSessionTokens { peer_id: None })
+            }
+            KeyCode::Char('p') => Some(DaemonCommand::ListPeers),
+            KeyCode::Char('z') => {
+                // Zero locally too, so the panel does not wait for the next update
+                self.session_bytes_sent = 0;
+                self.session_bytes_received = 0;
+                self.log("Session counters reset");
+                Some(DaemonCommand::ResetStats)
+            }
+            KeyCode::Char('l') => {
+                self.show_lifetime = !self.show_lifetime;
+                None
+            }
+            KeyCode::Char('x') if self.connecting => {
+                self.connecting = false;
+                Some(DaemonCommand::CancelConnect)
+            }
+            KeyCode::Char('i') if self.mode == AppMode::Connect => {
+                self.show_inspector = !self.show_inspector;
+                None
+            }
+            KeyCode::Up if self.show_inspector => {
+                self.inspector_selected = self.inspector_selected.saturating_sub(1);
+                None
+            }
+            KeyCode::Down if self.show_inspector => {
+                if self.inspector_selected + 1 < self.inspected.len() {
+                    self.inspector_selected += 1;
+                }
+                None
+            }
+            KeyCode::Enter if self.show_inspector => self.replay_selected(),
             _ => None,
         }
     }
@@ -262,13 +762,18 @@ fn chrono_lite() -> String {
 }
 
 /// Run the TUI for share mode
+///
+/// Returns whether the session should keep running after the dashboard closed.
 pub async fn run_share_tui(
-    port: u16,
+    ports: Vec<u16>,
     link: String,
+    limits: &HistoryLimits,
+    keep_alive_on_exit: bool,
     event_rx: mpsc::Receiver<DaemonEvent>,
     command_tx: mpsc::Sender<DaemonCommand>,
-) -> Result<()> {
-    let app = App::new_share(port, link);
+) -> Result<ExitAction> {
+    let mut app = App::new_share(ports, link, limits);
+    app.keep_alive_on_exit = keep_alive_on_exit;
     run_tui(app, event_rx, command_tx).await
 }
 
@@ -277,11 +782,30 @@ pub async fn run_connect_tui(
     peer_link: String,
     remote_port: u16,
     local_port: u16,
+    limits: &HistoryLimits,
     event_rx: mpsc::Receiver<DaemonEvent>,
     command_tx: mpsc::Sender<DaemonCommand>,
 ) -> Result<()> {
-    let app = App::new_connect(peer_link, remote_port, local_port);
-    run_tui(app, event_rx, command_tx).await
+    let app = App::new_connect(peer_link, remote_port, local_port, limits);
+    run_tui(app, event_rx, command_tx).await?;
+    Ok(())
+}
+
+/// Run the TUI for `rift attach`, showing another daemon's session read-only
+///
+/// `ports` are the host's shared ports (or the port it connects to).
+pub async fn run_attach_tui(
+    ports: Vec<u16>,
+    link: String,
+    limits: &HistoryLimits,
+    event_rx: mpsc::Receiver<DaemonEvent>,
+    command_tx: mpsc::Sender<DaemonCommand>,
+) -> Result<()> {
+    let mut app = App::new_share(ports, link, limits);
+    app.read_only = true;
+    app.status = "Attached (read-only)".to_string();
+    run_tui(app, event_rx, command_tx).await?;
+    Ok(())
 }
 
 /// Main TUI run loop
@@ -289,7 +813,7 @@ async fn run_tui(
     mut app: App,
     mut event_rx: mpsc::Receiver<DaemonEvent>,
     command_tx: mpsc::Sender<DaemonCommand>,
-) -> Result<()> {
+) -> Result<ExitAction> {
     use tracing::error;
     
     // Setup terminal
@@ -320,12 +844,11 @@ async fn run_tui(
         tokio::select! {
             // Handle keyboard events asynchronously
             maybe_event = reader.next() => {
-                if let Some(Ok(Event::Key(key))) = maybe_event {
-                    if key.kind == KeyEventKind::Press {
-                        if let Some(cmd) = app.handle_key(key.code) {
-                            let _ = command_tx.send(cmd).await;
-                        }
-                    }
+                if let Some(Ok(Event::Key(key))) = maybe_event
+                    && key.kind == KeyEventKind::Press
+                    && let Some(cmd) = app.handle_key(key.code)
+                {
+                    let _ = command_tx.send(cmd).await;
                 }
             }
             
@@ -342,7 +865,7 @@ async fn run_tui(
 
         // Check quit
         if app.should_quit {
-            break Ok(());
+            break Ok(app.exit_action);
         }
     };
 
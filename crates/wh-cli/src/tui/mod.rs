@@ -1,8 +1,110 @@
 //! TUI Module for Rift
 //!
 //! Provides a ratatui-based terminal UI for monitoring connections and traffic.
+//! Builds without the `tui` feature run share and connect headless.
 
+#[cfg(feature = "tui")]
 mod app;
+#[cfg(feature = "tui")]
 mod ui;
 
-pub use app::{run_connect_tui, run_share_tui};
+#[cfg(feature = "tui")]
+pub use app::{run_attach_tui, run_connect_tui, run_share_tui};
+#[cfg(not(feature = "tui"))]
+pub use headless::{run_attach_tui, run_connect_tui, run_share_tui};
+
+use wh_core::stats::TrafficBreakdown;
+use wh_daemon::connections::PortStats;
+
+/// Format bytes for display
+pub(crate) fn format_bytes(bytes: u64) -> String {
+    const KB: u64 = 1024;
+    const MB: u64 = KB * 1024;
+    const GB: u64 = MB * 1024;
+
+    if bytes >= GB {
+        format!("{:.2} GB", bytes as f64 / GB as f64)
+    } else if bytes >= MB {
+        format!("{:.2} MB", bytes as f64 / MB as f64)
+    } else if bytes >= KB {
+        format!("{:.2} KB", bytes as f64 / KB as f64)
+    } else {
+        format!("{} B", bytes)
+    }
+}
+
+/// One-line summary of the traffic classes, busiest first (e.g. "HTTP 3 (1.20 MB) · TLS 1 (4.00 KB)")
+pub(crate) fn format_traffic_classes(breakdown: &TrafficBreakdown) -> String {
+    let mut classes = breakdown.classes.clone();
+    classes.sort_by_key(|count| std::cmp::Reverse(count.bytes));
+    classes
+        .iter()
+        .map(|count| format!("{} {} ({})", count.class, count.connections, format_bytes(count.bytes)))
+        .collect::<Vec<_>>()
+        .join(" · ")
+}
+
+/// One-line summary of traffic per port (e.g. ":3000 2 (1.20 MB) · :5432 1 (4.00 KB)")
+pub(crate) fn format_port_stats(ports: &[PortStats]) -> String {
+    ports
+        .iter()
+        .map(|stats| format!(":{} {} ({})", stats.port, stats.connections, format_bytes(stats.bytes_sent + stats.bytes_received)))
+        .collect::<Vec<_>>()
+        .join(" · ")
+}
+
+/// What quitting the dashboard does to the session
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(not(feature = "tui"), allow(dead_code))]
+pub enum ExitAction {
+    /// Shut the daemon down with the dashboard
+    Stop,
+    /// Leave the daemon running without the dashboard (share mode)
+    KeepAlive,
+}
+
+/// Whether this build has the dashboard
+pub const AVAILABLE: bool = cfg!(feature = "tui");
+
+/// Stand-ins for builds without the dashboard; `main` never reaches them
+#[cfg(not(feature = "tui"))]
+mod headless {
+    use anyhow::{bail, Result};
+    use tokio::sync::mpsc;
+    use wh_core::HistoryLimits;
+    use wh_daemon::{DaemonCommand, DaemonEvent};
+
+    use super::ExitAction;
+
+    pub async fn run_share_tui(
+        _ports: Vec<u16>,
+        _link: String,
+        _limits: &HistoryLimits,
+        _keep_alive_on_exit: bool,
+        _event_rx: mpsc::Receiver<DaemonEvent>,
+        _command_tx: mpsc::Sender<DaemonCommand>,
+    ) -> Result<ExitAction> {
+        bail!("this build of rift has no TUI")
+    }
+
+    pub async fn run_connect_tui(
+        _peer_link: String,
+        _remote_port: u16,
+        _local_port: u16,
+        _limits: &HistoryLimits,
+        _event_rx: mpsc::Receiver<DaemonEvent>,
+        _command_tx: mpsc::Sender<DaemonCommand>,
+    ) -> Result<()> {
+        bail!("this build of rift has no TUI")
+    }
+
+    pub async fn run_attach_tui(
+        _ports: Vec<u16>,
+        _link: String,
+        _limits: &HistoryLimits,
+        _event_rx: mpsc::Receiver<DaemonEvent>,
+        _command_tx: mpsc::Sender<DaemonCommand>,
+    ) -> Result<()> {
+        bail!("this build of rift has no TUI")
+    }
+}
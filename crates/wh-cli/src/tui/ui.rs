@@ -2,10 +2,14 @@
 
 use ratatui::{
     prelude::*,
-    widgets::{Block, Borders, BorderType, List, ListItem, Paragraph, Sparkline},
+    widgets::{Block, Borders, BorderType, List, ListItem, Paragraph, Sparkline, Wrap},
 };
 
+use wh_core::control::HealthStatus;
+use wh_daemon::http_log::CapturedMessage;
+
 use super::app::{App, AppMode};
+use super::{format_bytes, format_port_stats, format_traffic_classes};
 
 /// Draw the main UI
 pub fn draw(f: &mut Frame, app: &App) {
@@ -24,12 +28,20 @@ pub fn draw(f: &mut Frame, app: &App) {
         .split(f.area());
 
     draw_header(f, app, chunks[0]);
-    draw_traffic_graph(f, app, chunks[1]);
+    if app.show_inspector {
+        draw_inspector(f, app, chunks[1]);
+    } else {
+        draw_traffic_graph(f, app, chunks[1]);
+    }
     draw_logs(f, app, chunks[2]);
 
     // Show approval popup if there's a pending connection request
     if app.pending_approval.is_some() {
         draw_approval_popup(f, app);
+    } else if app.pending_secrets.is_some() {
+        draw_secrets_popup(f, app);
+    } else if app.confirm_quit {
+        draw_quit_popup(f, app);
     }
 }
 
@@ -44,7 +56,7 @@ fn draw_header(f: &mut Frame, app: &App, area: Rect) {
         .split(area);
 
     // ASCII art title
-    let title_lines = vec![
+    let title_lines = [
         "██████╗ ██╗███████╗████████╗",
         "██╔══██╗██║██╔════╝╚══██╔══╝",
         "██████╔╝██║█████╗     ██║   ",
@@ -70,6 +82,7 @@ fn draw_header(f: &mut Frame, app: &App, area: Rect) {
     };
 
     let mode_text = match app.mode {
+        _ if app.read_only => "VIEWING (READ-ONLY)",
         AppMode::Share => "SHARE MODE",
         AppMode::Connect => "CONNECT MODE",
     };
@@ -83,12 +96,27 @@ fn draw_header(f: &mut Frame, app: &App, area: Rect) {
         Line::from(Span::styled(mode_text, Style::default().fg(Color::Cyan))),
         Line::from(vec![
             Span::styled("PORT: ", Style::default().fg(Color::DarkGray)),
-            Span::styled(format!("{}", app.port), Style::default().fg(Color::Cyan)),
+            Span::styled(app.ports_label(), Style::default().fg(Color::Cyan)),
         ]),
         Line::from(vec![
             Span::styled("PEERS: ", Style::default().fg(Color::DarkGray)),
             Span::styled(format!("{}", app.connections.len()), Style::default().fg(Color::Green)),
         ]),
+        match &app.service_health {
+            Some(health) => {
+                let color = match health.status {
+                    HealthStatus::Up => Color::Green,
+                    HealthStatus::Degraded => Color::Yellow,
+                    HealthStatus::Down => Color::Red,
+                };
+                let paused = if app.listener_paused { " (PAUSED)" } else { "" };
+                Line::from(vec![
+                    Span::styled("SERVICE: ", Style::default().fg(Color::DarkGray)),
+                    Span::styled(format!("{}{}", health.status.to_string().to_uppercase(), paused), Style::default().fg(color)),
+                ])
+            }
+            None => Line::from(""),
+        },
         if app.secrets_count > 0 {
             Line::from(vec![
                 Span::styled("SECRETS: ", Style::default().fg(Color::DarkGray)),
@@ -151,11 +179,14 @@ fn draw_traffic_graph(f: &mut Frame, app: &App, area: Rect) {
         ])
         .split(chunks[1]);
 
+    let (bytes_sent, bytes_received) = app.displayed_bytes();
+    let scope = if app.show_lifetime { "LIFETIME" } else { "SESSION" };
+
     // Upload stats
     let upload_text = vec![
         Line::from(Span::styled("↑ UPLOAD", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD))),
         Line::from(""),
-        Line::from(Span::styled(format_bytes(app.bytes_sent), Style::default().fg(Color::Green))),
+        Line::from(Span::styled(format_bytes(bytes_sent), Style::default().fg(Color::Green))),
     ];
 
     let upload_panel = Paragraph::new(upload_text)
@@ -173,7 +204,7 @@ fn draw_traffic_graph(f: &mut Frame, app: &App, area: Rect) {
     let download_text = vec![
         Line::from(Span::styled("↓ DOWNLOAD", Style::default().fg(Color::Blue).add_modifier(Modifier::BOLD))),
         Line::from(""),
-        Line::from(Span::styled(format_bytes(app.bytes_received), Style::default().fg(Color::Blue))),
+        Line::from(Span::styled(format_bytes(bytes_received), Style::default().fg(Color::Blue))),
     ];
 
     let download_panel = Paragraph::new(download_text)
@@ -188,12 +219,16 @@ fn draw_traffic_graph(f: &mut Frame, app: &App, area: Rect) {
     f.render_widget(download_panel, stats_chunks[1]);
 
     // Connection info
-    let total = app.bytes_sent + app.bytes_received;
-    let info_text = vec![
-        Line::from(Span::styled("⚡ TOTAL", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD))),
-        Line::from(""),
+    let total = bytes_sent + bytes_received;
+    let mut info_text = vec![
+        Line::from(Span::styled(format!("⚡ {} TOTAL", scope), Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD))),
+        // What the tunnelled connections carry, by their first bytes
+        Line::from(Span::styled(format_traffic_classes(&app.traffic_classes), Style::default().fg(Color::DarkGray))),
         Line::from(Span::styled(format_bytes(total), Style::default().fg(Color::Yellow))),
     ];
+    if app.shared_ports.len() > 1 {
+        info_text.push(Line::from(Span::styled(format_port_stats(&app.port_stats), Style::default().fg(Color::DarkGray))));
+    }
 
     let info_panel = Paragraph::new(info_text)
         .block(
@@ -207,6 +242,97 @@ fn draw_traffic_graph(f: &mut Frame, app: &App, area: Rect) {
     f.render_widget(info_panel, stats_chunks[2]);
 }
 
+/// Draw the HTTP request inspector
+fn draw_inspector(f: &mut Frame, app: &App, area: Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage(40),  // Requests
+            Constraint::Percentage(60),  // Selected request
+        ])
+        .split(area);
+
+    let items: Vec<ListItem> = if app.inspected.is_empty() {
+        vec![ListItem::new(Span::styled(
+            "No requests captured (needs --http-log)",
+            Style::default().fg(Color::DarkGray).add_modifier(Modifier::ITALIC),
+        ))]
+    } else {
+        app.inspected
+            .iter()
+            .rev()
+            .enumerate()
+            .map(|(i, record)| {
+                let exchange = &record.exchange;
+                let status_color = match exchange.status {
+                    200..=399 => Color::Green,
+                    400..=499 => Color::Yellow,
+                    _ => Color::Red,
+                };
+                let marker = if i == app.inspector_selected { "▶ " } else { "  " };
+                ListItem::new(Line::from(vec![
+                    Span::styled(marker, Style::default().fg(Color::Magenta)),
+                    Span::styled(format!("{} ", exchange.status), Style::default().fg(status_color)),
+                    Span::styled(format!("{} ", exchange.method), Style::default().fg(Color::Cyan)),
+                    Span::raw(exchange.path.clone()),
+                    Span::styled(format!(" {}ms", exchange.duration_ms), Style::default().fg(Color::DarkGray)),
+                ]))
+            })
+            .collect()
+    };
+
+    let list = List::new(items).block(
+        Block::default()
+            .title(" REQUESTS [↑↓ SELECT | ENTER REPLAY] ")
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(Color::Magenta))
+            .title_style(Style::default().fg(Color::Magenta).add_modifier(Modifier::BOLD)),
+    );
+    f.render_widget(list, chunks[0]);
+
+    let mut lines = Vec::new();
+    if let Some(record) = app.selected_exchange() {
+        lines.push(Line::from(Span::styled("REQUEST", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))));
+        match &record.request {
+            Some(request) => lines.extend(message_lines(request)),
+            None => lines.push(Line::from(Span::styled("(not captured)", Style::default().fg(Color::DarkGray)))),
+        }
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled("RESPONSE", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))));
+        lines.extend(message_lines(&record.response));
+    }
+
+    let detail = Paragraph::new(lines).block(
+        Block::default()
+            .title(" DETAIL ")
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(Color::Cyan))
+            .title_style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
+    );
+    f.render_widget(detail, chunks[1]);
+}
+
+/// Head and body preview of a captured message
+fn message_lines(message: &CapturedMessage) -> Vec<Line<'static>> {
+    let mut lines: Vec<Line> = message.head.lines().map(|line| Line::from(line.to_string())).collect();
+    if message.body_redacted {
+        lines.push(Line::from(Span::styled(
+            format!("[body redacted: {}]", format_bytes(message.body_size)),
+            Style::default().fg(Color::Yellow),
+        )));
+    } else if !message.body.is_empty() {
+        lines.push(Line::from(""));
+        lines.extend(
+            String::from_utf8_lossy(&message.body)
+                .lines()
+                .map(|line| Line::from(Span::styled(line.to_string(), Style::default().fg(Color::Gray)))),
+        );
+    }
+    lines
+}
+
 /// Draw the logs panel
 fn draw_logs(f: &mut Frame, app: &App, area: Rect) {
     let chunks = Layout::default()
@@ -227,7 +353,9 @@ fn draw_logs(f: &mut Frame, app: &App, area: Rect) {
         app.connections
             .iter()
             .map(|conn| {
-                let peer_short = if conn.peer_id.len() > 12 {
+                let peer_short = if let Some(nickname) = app.nicknames.get(&conn.peer_id) {
+                    nickname.clone()
+                } else if conn.peer_id.len() > 12 {
                     format!("{}..{}", &conn.peer_id[..6], &conn.peer_id[conn.peer_id.len()-4..])
                 } else {
                     conn.peer_id.clone()
@@ -239,12 +367,25 @@ fn draw_logs(f: &mut Frame, app: &App, area: Rect) {
                     format!("{}s", duration.as_secs())
                 };
 
-                ListItem::new(Line::from(vec![
+                let mut spans = vec![
                     Span::styled("◉ ", Style::default().fg(Color::Green)),
                     Span::styled(peer_short, Style::default().fg(Color::Cyan)),
                     Span::raw(" "),
                     Span::styled(format!("[{}]", duration_str), Style::default().fg(Color::DarkGray)),
-                ]))
+                ];
+                if let Some(rtt_ms) = conn.rtt_ms {
+                    spans.push(Span::styled(format!(" {}ms", rtt_ms), Style::default().fg(Color::Yellow)));
+                }
+                if conn.bytes_sent + conn.bytes_received > 0 {
+                    spans.push(Span::styled(
+                        format!(" ↑{} ↓{}", format_bytes(conn.bytes_sent), format_bytes(conn.bytes_received)),
+                        Style::default().fg(Color::DarkGray),
+                    ));
+                }
+                if conn.rate > 0 {
+                    spans.push(Span::styled(format!(" {}/s", format_bytes(conn.rate)), Style::default().fg(Color::Green)));
+                }
+                ListItem::new(Line::from(spans))
             })
             .collect()
     };
@@ -272,7 +413,7 @@ fn draw_logs(f: &mut Frame, app: &App, area: Rect) {
                 ("✗", Style::default().fg(Color::Red))
             } else if log.contains("connected") || log.contains("established") {
                 ("✓", Style::default().fg(Color::Green))
-            } else if log.contains("disconnected") {
+            } else if log.contains("disconnected") || log.contains("Warning") {
                 ("⚠", Style::default().fg(Color::Yellow))
             } else if log.contains("secrets") || log.contains("Secrets") {
                 ("🔐", Style::default().fg(Color::Magenta))
@@ -307,7 +448,11 @@ fn draw_logs(f: &mut Frame, app: &App, area: Rect) {
     };
 
     let footer_text = Span::styled(
-        " [q] QUIT | [h] HELP ",
+        match app.mode {
+            _ if app.read_only => " [q] DETACH | [h] HELP | [l] LIFETIME ",
+            AppMode::Connect => " [q] QUIT | [h] HELP | [z] RESET STATS | [i] INSPECT ",
+            AppMode::Share => " [q] QUIT | [h] HELP | [z] RESET STATS ",
+        },
         Style::default().fg(Color::DarkGray),
     );
 
@@ -325,12 +470,40 @@ fn draw_help(f: &mut Frame) {
         Line::from(""),
         Line::from(vec![
             Span::styled("  q  ", Style::default().fg(Color::Cyan)),
-            Span::raw(" - Quit application"),
+            Span::raw(" - Quit (asks to stop or keep sharing while peers are connected)"),
         ]),
         Line::from(vec![
             Span::styled("  h  ", Style::default().fg(Color::Cyan)),
             Span::raw(" - Toggle this help"),
         ]),
+        Line::from(vec![
+            Span::styled("  r  ", Style::default().fg(Color::Cyan)),
+            Span::raw(" - Revoke session tokens"),
+        ]),
+        Line::from(vec![
+            Span::styled("  p  ", Style::default().fg(Color::Cyan)),
+            Span::raw(" - List connected peers in the event log"),
+        ]),
+        Line::from(vec![
+            Span::styled("  z  ", Style::default().fg(Color::Cyan)),
+            Span::raw(" - Reset the session traffic counters"),
+        ]),
+        Line::from(vec![
+            Span::styled("  l  ", Style::default().fg(Color::Cyan)),
+            Span::raw(" - Show session / lifetime traffic"),
+        ]),
+        Line::from(vec![
+            Span::styled("  i  ", Style::default().fg(Color::Cyan)),
+            Span::raw(" - Toggle the HTTP inspector (connect, --http-log)"),
+        ]),
+        Line::from(vec![
+            Span::styled("  x  ", Style::default().fg(Color::Cyan)),
+            Span::raw(" - Stop looking for the host (connect, while connecting)"),
+        ]),
+        Line::from(vec![
+            Span::styled("ENTER", Style::default().fg(Color::Cyan)),
+            Span::raw(" - Replay the selected request"),
+        ]),
         Line::from(vec![
             Span::styled(" ESC ", Style::default().fg(Color::Cyan)),
             Span::raw(" - Quit application"),
@@ -361,10 +534,12 @@ fn draw_help(f: &mut Frame) {
 
 /// Draw connection approval popup
 fn draw_approval_popup(f: &mut Frame, app: &App) {
-    let area = centered_rect(60, 30, f.area());
+    let area = centered_rect(60, 40, f.area());
 
     if let Some(peer_id) = &app.pending_approval {
-        let peer_short = if peer_id.len() > 32 {
+        let peer_short = if let Some(nickname) = app.nicknames.get(peer_id) {
+            format!("{} ({}...)", nickname, &peer_id[..peer_id.len().min(16)])
+        } else if peer_id.len() > 32 {
             format!("{}...{}", &peer_id[..16], &peer_id[peer_id.len()-16..])
         } else {
             peer_id.clone()
@@ -381,6 +556,8 @@ fn draw_approval_popup(f: &mut Frame, app: &App) {
                 Span::styled("Peer: ", Style::default().fg(Color::DarkGray)),
                 Span::styled(peer_short, Style::default().fg(Color::Cyan)),
             ]),
+            attestation_line(app.attestations.get(peer_id)),
+            capabilities_line(app.capabilities.get(peer_id)),
             Line::from(""),
             Line::from(Span::styled(
                 "Allow this connection?",
@@ -412,6 +589,109 @@ fn draw_approval_popup(f: &mut Frame, app: &App) {
     }
 }
 
+/// Draw the popup for a secrets request with newly added keys
+fn draw_secrets_popup(f: &mut Frame, app: &App) {
+    let area = centered_rect(60, 40, f.area());
+
+    if let Some(prompt) = &app.pending_secrets {
+        let peer_id = &prompt.peer_id;
+        let peer_short = if let Some(nickname) = app.nicknames.get(peer_id) {
+            format!("{} ({}...)", nickname, &peer_id[..peer_id.len().min(16)])
+        } else if peer_id.len() > 32 {
+            format!("{}...{}", &peer_id[..16], &peer_id[peer_id.len()-16..])
+        } else {
+            peer_id.clone()
+        };
+        let shared = if prompt.keys.is_empty() { "none".to_string() } else { prompt.keys.join(", ") };
+
+        let popup_text = vec![
+            Line::from(""),
+            Line::from(Span::styled(
+                "🔐 SECRETS REQUESTED AGAIN",
+                Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+            )),
+            Line::from(""),
+            Line::from(vec![
+                Span::styled("Peer: ", Style::default().fg(Color::DarkGray)),
+                Span::styled(peer_short, Style::default().fg(Color::Cyan)),
+            ]),
+            Line::from(vec![
+                Span::styled("Shared before: ", Style::default().fg(Color::DarkGray)),
+                Span::styled(shared, Style::default().fg(Color::White)),
+            ]),
+            Line::from(vec![
+                Span::styled("New: ", Style::default().fg(Color::DarkGray)),
+                Span::styled(prompt.new_keys.join(", "), Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
+            ]),
+            Line::from(""),
+            Line::from(vec![
+                Span::styled(" [Y] ", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)),
+                Span::raw(" Same as before    "),
+                Span::styled(" [A] ", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)),
+                Span::raw(" Include new    "),
+                Span::styled(" [N] ", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
+                Span::raw(" Deny"),
+            ]),
+            Line::from(""),
+        ];
+
+        let popup_block = Paragraph::new(popup_text)
+            .block(
+                Block::default()
+                    .title(" 🔒 SECRETS APPROVAL ")
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Double)
+                    .border_style(Style::default().fg(Color::Yellow))
+                    .title_style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD))
+                    .style(Style::default().bg(Color::Black)),
+            )
+            .alignment(Alignment::Center)
+            .wrap(Wrap { trim: true });
+
+        f.render_widget(popup_block, area);
+    }
+}
+
+/// Draw the popup asking whether quitting stops the share
+fn draw_quit_popup(f: &mut Frame, app: &App) {
+    let area = centered_rect(60, 30, f.area());
+
+    let popup_text = vec![
+        Line::from(""),
+        Line::from(Span::styled(
+            "⚠ PEERS ARE CONNECTED",
+            Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+        )),
+        Line::from(""),
+        Line::from(format!("{} peer(s) are using localhost:{}", app.connections.len(), app.ports_label())),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled(" [S] ", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
+            Span::raw(" Stop sharing    "),
+            Span::styled(" [K] ", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)),
+            Span::raw(" Keep running    "),
+            Span::styled(" [C] ", Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
+            Span::raw(" Cancel"),
+        ]),
+        Line::from(""),
+    ];
+
+    let popup_block = Paragraph::new(popup_text)
+        .block(
+            Block::default()
+                .title(" QUIT ")
+                .borders(Borders::ALL)
+                .border_type(BorderType::Double)
+                .border_style(Style::default().fg(Color::Yellow))
+                .title_style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD))
+                .style(Style::default().bg(Color::Black)),
+        )
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: true });
+
+    f.render_widget(popup_block, area);
+}
+
 /// Helper to create a centered rect
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
     let popup_layout = Layout::default()
@@ -433,19 +713,30 @@ fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
         .split(popup_layout[1])[1]
 }
 
-/// Format bytes for display
-fn format_bytes(bytes: u64) -> String {
-    const KB: u64 = 1024;
-    const MB: u64 = KB * 1024;
-    const GB: u64 = MB * 1024;
-
-    if bytes >= GB {
-        format!("{:.2} GB", bytes as f64 / GB as f64)
-    } else if bytes >= MB {
-        format!("{:.2} MB", bytes as f64 / MB as f64)
-    } else if bytes >= KB {
-        format!("{:.2} KB", bytes as f64 / KB as f64)
-    } else {
-        format!("{} B", bytes)
+/// Describe the optional features a peer announced, if any
+fn capabilities_line(capabilities: Option<&wh_core::PeerCapabilities>) -> Line<'static> {
+    match capabilities {
+        Some(capabilities) => Line::from(vec![
+            Span::styled("Supports ", Style::default().fg(Color::DarkGray)),
+            Span::styled(capabilities.to_string(), Style::default().fg(Color::White)),
+        ]),
+        None => Line::from(Span::styled("Features unknown", Style::default().fg(Color::DarkGray))),
+    }
+}
+
+/// Describe the identity claims a peer presented, if any
+fn attestation_line(attestation: Option<&(String, bool)>) -> Line<'static> {
+    match attestation {
+        Some((claims, true)) => Line::from(vec![
+            Span::styled("Claims to be ", Style::default().fg(Color::DarkGray)),
+            Span::styled(claims.clone(), Style::default().fg(Color::White)),
+            Span::styled(" (signature valid)", Style::default().fg(Color::Green)),
+        ]),
+        Some((claims, false)) => Line::from(vec![
+            Span::styled("Claims to be ", Style::default().fg(Color::DarkGray)),
+            Span::styled(claims.clone(), Style::default().fg(Color::White)),
+            Span::styled(" (signature INVALID)", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
+        ]),
+        None => Line::from(Span::styled("No identity claims", Style::default().fg(Color::DarkGray))),
     }
 }
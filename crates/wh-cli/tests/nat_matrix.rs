@@ -0,0 +1,106 @@
+//! NAT traversal matrix
+//!
+//! Runs `rift share` and `rift connect` in separate network namespaces behind
+//! simulated NATs and checks how they find each other: hole punched when the
+//! NATs allow it, through the relay when they don't, and back after an outage.
+//!
+//! Linux only, needs root, iproute2 and nft, and the `netns-tests` feature;
+//! without them the tests fail rather than pass without running:
+//!
+//! ```bash
+//! sudo -E cargo test -p wh-cli --features netns-tests --test nat_matrix
+//! ```
+
+#![cfg(target_os = "linux")]
+
+mod netns;
+
+use netns::{Nat, Topology};
+use std::time::Duration;
+
+const SHARED_PORT: u16 = 3000;
+const LOCAL_PORT: u16 = 8080;
+
+/// Time for the first tunnel: reservation, circuit, approval
+const SETUP: Duration = Duration::from_secs(60);
+
+/// Time for DCUtR to upgrade the relayed connection once it is up
+const UPGRADE: Duration = Duration::from_secs(10);
+
+#[test]
+fn test_open_sites_keep_tunnel_without_relay() {
+    let mut net = Topology::new("open");
+    let host = net.add_site(Nat::Open);
+    let client = net.add_site(Nat::Open);
+
+    let link = net.share(&host, SHARED_PORT);
+    net.connect(&client, &link, LOCAL_PORT);
+    assert!(net.tunnel_works(&client, LOCAL_PORT, SETUP), "no tunnel between open sites");
+
+    std::thread::sleep(UPGRADE);
+    net.stop_relay();
+    assert!(
+        net.tunnel_works(&client, LOCAL_PORT, Duration::from_secs(30)),
+        "tunnel between open sites needed the relay"
+    );
+}
+
+#[test]
+fn test_cone_nats_hole_punch() {
+    let mut net = Topology::new("cone");
+    let host = net.add_site(Nat::PortRestricted);
+    let client = net.add_site(Nat::PortRestricted);
+
+    let link = net.share(&host, SHARED_PORT);
+    net.connect(&client, &link, LOCAL_PORT);
+    assert!(net.tunnel_works(&client, LOCAL_PORT, SETUP), "no tunnel between cone NATs");
+    assert!(net.relay_opened_circuit(Duration::ZERO), "peers behind NATs met without the relay");
+
+    // Only a hole-punched connection survives the relay going away
+    std::thread::sleep(UPGRADE);
+    net.stop_relay();
+    assert!(
+        net.tunnel_works(&client, LOCAL_PORT, Duration::from_secs(30)),
+        "hole punching failed between cone NATs"
+    );
+}
+
+#[test]
+fn test_symmetric_nats_fall_back_to_relay() {
+    let mut net = Topology::new("symmetric");
+    let host = net.add_site(Nat::Symmetric);
+    let client = net.add_site(Nat::Symmetric);
+
+    let link = net.share(&host, SHARED_PORT);
+    net.connect(&client, &link, LOCAL_PORT);
+    assert!(net.tunnel_works(&client, LOCAL_PORT, SETUP), "no relayed tunnel between symmetric NATs");
+    assert!(net.relay_opened_circuit(Duration::ZERO), "symmetric NATs met without the relay");
+
+    // Nothing direct to fall back on: the tunnel goes with the relay
+    std::thread::sleep(UPGRADE);
+    net.stop_relay();
+    std::thread::sleep(Duration::from_secs(10));
+    assert!(
+        !net.tunnel_works(&client, LOCAL_PORT, Duration::ZERO),
+        "symmetric NATs kept a tunnel without the relay"
+    );
+}
+
+#[test]
+fn test_connect_recovers_after_uplink_outage() {
+    let mut net = Topology::new("reconnect");
+    let host = net.add_site(Nat::Open);
+    let client = net.add_site(Nat::PortRestricted);
+
+    let link = net.share(&host, SHARED_PORT);
+    net.connect(&client, &link, LOCAL_PORT);
+    assert!(net.tunnel_works(&client, LOCAL_PORT, SETUP), "no tunnel before the outage");
+
+    // Longer than the idle timeout, so the connection is really gone
+    net.set_uplink(&client, false);
+    std::thread::sleep(Duration::from_secs(15));
+    assert!(!net.tunnel_works(&client, LOCAL_PORT, Duration::ZERO), "tunnel worked while unplugged");
+
+    net.set_uplink(&client, true);
+    assert!(net.tunnel_works(&client, LOCAL_PORT, SETUP), "connect did not recover after the outage");
+}
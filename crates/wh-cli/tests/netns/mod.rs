@@ -0,0 +1,439 @@
+//! Network Namespace Test Harness
+//!
+//! Builds a small internet out of Linux network namespaces: a bridge standing
+//! in for the public network, a relay on it, and sites that are either on the
+//! bridge directly or behind a NAT router. Each node runs the real `rift`
+//! binary inside its namespace, so hole punching, relay fallback and
+//! reconnection go through the same code paths as on real networks.
+//!
+//! Needs root, iproute2, and nft for the NAT sites. The tests only build with
+//! the `netns-tests` feature, so a machine missing any of these fails them
+//! rather than passing them without running anything.
+
+#![allow(dead_code)]
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Public address of the relay on the simulated internet
+const RELAY_IP: &str = "203.0.113.10";
+
+/// UDP port the relay listens on
+const RELAY_PORT: u16 = 4001;
+
+/// Namespace holding the bridge that stands in for the internet
+const INTERNET: &str = "inet";
+
+/// How a site reaches the internet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Nat {
+    /// Public address on the bridge, no filtering
+    Open,
+    /// Endpoint-independent mapping, address and port dependent filtering
+    PortRestricted,
+    /// A fresh random port for every destination; hole punching cannot work
+    Symmetric,
+}
+
+/// A site: a host namespace, and a router namespace in front of it unless [`Nat::Open`]
+#[derive(Debug, Clone)]
+pub struct Site {
+    pub nat: Nat,
+    /// Namespace the rift node and the shared service run in
+    pub host: String,
+    /// Namespace of the NAT router
+    pub router: Option<String>,
+    /// Bridge port of this site in the internet namespace
+    uplink: String,
+    /// Public address of the site (the router's, behind a NAT)
+    pub public_ip: String,
+}
+
+/// A running `rift` process and everything it printed
+pub struct Node {
+    pub name: String,
+    child: Child,
+    output: Arc<Mutex<Vec<String>>>,
+}
+
+impl Node {
+    /// Wait for a line of output containing `needle`
+    pub fn wait_for_line(&self, needle: &str, timeout: Duration) -> Option<String> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(line) = self.output.lock().unwrap().iter().find(|line| line.contains(needle)) {
+                return Some(line.clone());
+            }
+            if Instant::now() >= deadline {
+                return None;
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+    }
+
+    /// Whether a line containing `needle` was printed
+    pub fn printed(&self, needle: &str) -> bool {
+        self.output.lock().unwrap().iter().any(|line| line.contains(needle))
+    }
+
+    fn stop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// A simulated internet with a relay and any number of sites
+pub struct Topology {
+    prefix: String,
+    dir: tempfile::TempDir,
+    namespaces: Vec<String>,
+    sites: usize,
+    relay: Option<(Node, String)>,
+    nodes: Vec<Node>,
+}
+
+impl Topology {
+    /// Set up the internet and its relay
+    ///
+    /// Panics when this machine cannot run network namespaces.
+    pub fn new(name: &str) -> Self {
+        if let Some(missing) = missing_prerequisite() {
+            panic!("{}: {}", name, missing);
+        }
+
+        let mut topology = Self {
+            prefix: format!("rift-{}-{}", std::process::id(), name),
+            dir: tempfile::tempdir().expect("temp dir"),
+            namespaces: Vec::new(),
+            sites: 0,
+            relay: None,
+            nodes: Vec::new(),
+        };
+        let internet = topology.namespace(INTERNET);
+        ip(&["-n", &internet, "link", "add", "br0", "type", "bridge"]);
+        ip(&["-n", &internet, "link", "set", "br0", "up"]);
+
+        let relay = topology.namespace("relay");
+        topology.plug(&relay, "relay", &format!("{}/24", RELAY_IP));
+        topology.start_relay();
+        topology
+    }
+
+    /// Add a site reaching the internet through the given NAT
+    ///
+    /// Panics for a NAT site when nft is missing.
+    pub fn add_site(&mut self, nat: Nat) -> Site {
+        if nat != Nat::Open && !succeeds(Command::new("nft").arg("--version")) {
+            panic!("{:?} site needs nft for its NAT router", nat);
+        }
+        self.sites += 1;
+        let index = self.sites;
+        let uplink = format!("site{}", index);
+        let public_ip = format!("203.0.113.{}", 20 + index);
+        let host = self.namespace(&format!("h{}", index));
+
+        if nat == Nat::Open {
+            self.plug(&host, &uplink, &format!("{}/24", public_ip));
+            return Site { nat, host, router: None, uplink, public_ip };
+        }
+
+        let router = self.namespace(&format!("r{}", index));
+        self.plug(&router, &uplink, &format!("{}/24", public_ip));
+        ip(&["link", "add", "lan", "netns", &router, "type", "veth", "peer", "name", "eth0", "netns", &host]);
+        ip(&["-n", &router, "addr", "add", &format!("10.0.{}.1/24", index), "dev", "lan"]);
+        ip(&["-n", &router, "link", "set", "lan", "up"]);
+        ip(&["-n", &host, "addr", "add", &format!("10.0.{}.2/24", index), "dev", "eth0"]);
+        ip(&["-n", &host, "link", "set", "eth0", "up"]);
+        ip(&["-n", &host, "route", "add", "default", "via", &format!("10.0.{}.1", index)]);
+        run(Command::new("ip").args(["netns", "exec", &router, "sysctl", "-qw", "net.ipv4.ip_forward=1"]));
+        nft(&router, &nat_rules(nat));
+
+        Site { nat, host, router: Some(router), uplink, public_ip }
+    }
+
+    /// Share `port` on a site (answering with an echo service) and return the link
+    pub fn share(&mut self, site: &Site, port: u16) -> String {
+        serve_echo(&site.host, port);
+        let node = self.spawn(site, &["--quiet", "share", &port.to_string(), "--auto-approve"]);
+        let link = node
+            .wait_for_line("rift://", Duration::from_secs(30))
+            .unwrap_or_else(|| panic!("share on {} printed no link\n{}", site.host, self.logs()));
+        self.nodes.push(node);
+        link.trim().to_string()
+    }
+
+    /// Connect a site to a link, forwarding `local_port` in its namespace
+    pub fn connect(&mut self, site: &Site, link: &str, local_port: u16) {
+        let node = self.spawn(site, &["--no-tui", "connect", link, "--local-port", &local_port.to_string()]);
+        self.nodes.push(node);
+    }
+
+    /// Whether a round trip through the tunnel at `local_port` on a site succeeds within `timeout`
+    pub fn tunnel_works(&self, site: &Site, local_port: u16, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut attempt = 0u32;
+        loop {
+            attempt += 1;
+            if echo_round_trip(&site.host, local_port, attempt) {
+                return true;
+            }
+            if Instant::now() >= deadline {
+                return false;
+            }
+            std::thread::sleep(Duration::from_millis(500));
+        }
+    }
+
+    /// Whether the relay carried a circuit between two peers
+    pub fn relay_opened_circuit(&self, timeout: Duration) -> bool {
+        self.relay.as_ref().is_some_and(|(relay, _)| relay.wait_for_line("+ circuit", timeout).is_some())
+    }
+
+    /// Kill the relay, leaving only direct connections
+    pub fn stop_relay(&mut self) {
+        if let Some((mut relay, _)) = self.relay.take() {
+            relay.stop();
+        }
+    }
+
+    /// Unplug or replug a site from the internet
+    pub fn set_uplink(&self, site: &Site, up: bool) {
+        let state = if up { "up" } else { "down" };
+        ip(&["-n", &self.name(INTERNET), "link", "set", &site.uplink, state]);
+    }
+
+    /// Everything the nodes printed, for failure messages
+    pub fn logs(&self) -> String {
+        let relay = self.relay.iter().map(|(relay, _)| relay);
+        relay
+            .chain(&self.nodes)
+            .map(|node| format!("--- {} ---\n{}", node.name, node.output.lock().unwrap().join("\n")))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn start_relay(&mut self) {
+        let external = format!("/ip4/{}/udp/{}/quic-v1", RELAY_IP, RELAY_PORT);
+        let config_home = self.config_home("relay", None);
+        let relay = spawn_rift(
+            "relay",
+            &self.name("relay"),
+            &config_home,
+            &["relay", "--port", &RELAY_PORT.to_string(), "--external-addr", &external],
+        );
+        let peer_id = relay
+            .wait_for_line("Rift relay ", Duration::from_secs(30))
+            .and_then(|line| line.split_whitespace().last().map(str::to_string))
+            .expect("relay printed no peer ID");
+        self.relay = Some((relay, format!("{}/p2p/{}", external, peer_id)));
+    }
+
+    fn spawn(&self, site: &Site, args: &[&str]) -> Node {
+        let relay = self.relay.as_ref().map(|(_, address)| address.as_str());
+        let name = format!("{} {}", site.host, args.iter().find(|arg| !arg.starts_with('-')).unwrap_or(&""));
+        let config_home = self.config_home(&site.host, relay);
+        spawn_rift(&name, &site.host, &config_home, args)
+    }
+
+    /// XDG config home of a node, with a config.toml pointing at the relay only
+    fn config_home(&self, node: &str, relay: Option<&str>) -> PathBuf {
+        let home = self.dir.path().join(node);
+        let dir = home.join("rift");
+        std::fs::create_dir_all(&dir).expect("config dir");
+        let relays = relay.map(|relay| format!("\"{}\"", relay)).unwrap_or_default();
+        let config = format!(
+            "enable_mdns = false\n\
+             bootstrap_peers = []\n\
+             relays = [{}]\n\
+             keystore = \"file\"\n\
+             \n\
+             [transport]\n\
+             idle_timeout_secs = 5\n\
+             keep_alive_interval_secs = 1\n",
+            relays
+        );
+        std::fs::write(dir.join("config.toml"), config).expect("write config");
+        home
+    }
+
+    /// Create a namespace with loopback up
+    fn namespace(&mut self, suffix: &str) -> String {
+        let name = self.name(suffix);
+        ip(&["netns", "add", &name]);
+        ip(&["-n", &name, "link", "set", "lo", "up"]);
+        self.namespaces.push(name.clone());
+        name
+    }
+
+    /// Connect a namespace to the bridge with the given address
+    fn plug(&self, namespace: &str, port: &str, address: &str) {
+        let internet = self.name(INTERNET);
+        ip(&["link", "add", "eth0", "netns", namespace, "type", "veth", "peer", "name", port, "netns", &internet]);
+        ip(&["-n", &internet, "link", "set", port, "master", "br0", "up"]);
+        ip(&["-n", namespace, "addr", "add", address, "dev", "eth0"]);
+        ip(&["-n", namespace, "link", "set", "eth0", "up"]);
+    }
+
+    fn name(&self, suffix: &str) -> String {
+        format!("{}-{}", self.prefix, suffix)
+    }
+}
+
+impl Drop for Topology {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            eprintln!("{}", self.logs());
+        }
+        self.stop_relay();
+        for node in &mut self.nodes {
+            node.stop();
+        }
+        for namespace in self.namespaces.iter().rev() {
+            let _ = Command::new("ip").args(["netns", "del", namespace]).status();
+        }
+    }
+}
+
+/// Why this machine can't build the topology, if it can't
+fn missing_prerequisite() -> Option<&'static str> {
+    // SAFETY: geteuid has no preconditions
+    if unsafe { libc::geteuid() } != 0 {
+        return Some("needs root for network namespaces");
+    }
+    if !succeeds(Command::new("ip").args(["netns", "list"])) {
+        return Some("needs iproute2 (`ip netns`)");
+    }
+    None
+}
+
+/// nftables ruleset of a NAT router: masquerade out of `eth0`, drop unsolicited traffic in
+fn nat_rules(nat: Nat) -> String {
+    let masquerade = match nat {
+        Nat::Symmetric => "masquerade fully-random",
+        _ => "masquerade",
+    };
+    format!(
+        "table ip nat {{\n\
+           chain postrouting {{\n\
+             type nat hook postrouting priority srcnat; policy accept;\n\
+             oifname \"eth0\" {}\n\
+           }}\n\
+         }}\n\
+         table ip filter {{\n\
+           chain forward {{\n\
+             type filter hook forward priority filter; policy drop;\n\
+             ct state established,related accept\n\
+             iifname \"lan\" accept\n\
+           }}\n\
+         }}\n",
+        masquerade
+    )
+}
+
+fn spawn_rift(name: &str, namespace: &str, config_home: &Path, args: &[&str]) -> Node {
+    let mut child = Command::new("ip")
+        .args(["netns", "exec", namespace, env!("CARGO_BIN_EXE_rift")])
+        .args(args)
+        .env("XDG_CONFIG_HOME", config_home)
+        .env("HOME", config_home)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap_or_else(|e| panic!("failed to start rift in {}: {}", namespace, e));
+
+    let output = Arc::new(Mutex::new(Vec::new()));
+    collect_lines(child.stdout.take().expect("stdout"), output.clone());
+    collect_lines(child.stderr.take().expect("stderr"), output.clone());
+    Node { name: name.to_string(), child, output }
+}
+
+fn collect_lines(stream: impl Read + Send + 'static, output: Arc<Mutex<Vec<String>>>) {
+    std::thread::spawn(move || {
+        for line in BufReader::new(stream).lines().map_while(|line| line.ok()) {
+            output.lock().unwrap().push(line);
+        }
+    });
+}
+
+/// Run `f` on a thread inside a namespace; sockets it creates stay in that namespace
+fn in_namespace<T: Send + 'static>(namespace: &str, f: impl FnOnce() -> T + Send + 'static) -> T {
+    let path = format!("/var/run/netns/{}", namespace);
+    std::thread::spawn(move || {
+        let file = File::open(&path).unwrap_or_else(|e| panic!("failed to open {}: {}", path, e));
+        // SAFETY: a valid namespace fd, and only this thread switches namespaces
+        let rc = unsafe { libc::setns(file.as_raw_fd(), libc::CLONE_NEWNET) };
+        assert_eq!(rc, 0, "setns into {} failed: {}", path, std::io::Error::last_os_error());
+        f()
+    })
+    .join()
+    .expect("namespace thread panicked")
+}
+
+/// Echo every connection to `port` on the namespace's loopback
+fn serve_echo(namespace: &str, port: u16) {
+    let listener = in_namespace(namespace, move || TcpListener::bind(("127.0.0.1", port)).expect("bind echo service"));
+    std::thread::spawn(move || {
+        for mut socket in listener.incoming().map_while(|socket| socket.ok()) {
+            std::thread::spawn(move || {
+                let mut buf = [0u8; 1024];
+                while let Ok(n) = socket.read(&mut buf) {
+                    if n == 0 || socket.write_all(&buf[..n]).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+}
+
+/// Send a line through `port` on the namespace's loopback and check it comes back
+fn echo_round_trip(namespace: &str, port: u16, attempt: u32) -> bool {
+    in_namespace(namespace, move || {
+        let addr = SocketAddr::from(([127, 0, 0, 1], port));
+        let Ok(mut socket) = TcpStream::connect_timeout(&addr, Duration::from_secs(2)) else {
+            return false;
+        };
+        let _ = socket.set_read_timeout(Some(Duration::from_secs(5)));
+        let message = format!("ping {}\n", attempt);
+        if socket.write_all(message.as_bytes()).is_err() {
+            return false;
+        }
+        let mut reply = vec![0u8; message.len()];
+        socket.read_exact(&mut reply).is_ok() && reply == message.as_bytes()
+    })
+}
+
+fn nft(namespace: &str, rules: &str) {
+    let mut child = Command::new("ip")
+        .args(["netns", "exec", namespace, "nft", "-f", "-"])
+        .stdin(Stdio::piped())
+        .spawn()
+        .expect("failed to run nft");
+    child.stdin.take().expect("stdin").write_all(rules.as_bytes()).expect("write nft rules");
+    assert!(child.wait().expect("nft").success(), "nft rejected the rules for {}", namespace);
+}
+
+fn ip(args: &[&str]) {
+    run(Command::new("ip").args(args));
+}
+
+fn run(command: &mut Command) {
+    let output = command.output().expect("failed to run command");
+    assert!(
+        output.status.success(),
+        "{:?} failed: {}",
+        command,
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+fn succeeds(command: &mut Command) -> bool {
+    command.stdout(Stdio::null()).stderr(Stdio::null()).status().is_ok_and(|status| status.success())
+}
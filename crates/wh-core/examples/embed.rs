@@ -0,0 +1,116 @@
+//! Minimal Embedder
+//!
+//! Runs a host and a client peer in one process using only the public wh-core
+//! API: build two networks, accept tunnel streams on the host and bridge them to
+//! a local echo service, then open a tunnel from the client and round-trip a message.
+//!
+//! Run with `cargo run -p wh-core --example embed`.
+
+use futures::StreamExt;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use libp2p::multiaddr::Protocol;
+use tokio_util::compat::FuturesAsyncReadCompatExt;
+use wh_core::{NetworkEvent, PeerNetwork, RiftConfig, bridge_stream_to_tcp, open_tunnel_stream, read_tunnel_request};
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let state = tempfile::tempdir()?;
+
+    // The local service being shared: echoes whatever it receives
+    let service = TcpListener::bind("127.0.0.1:0").await?;
+    let service_port = service.local_addr()?.port();
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = service.accept().await {
+            tokio::spawn(async move {
+                let (mut read, mut write) = socket.split();
+                let _ = tokio::io::copy(&mut read, &mut write).await;
+            });
+        }
+    });
+
+    // Host: listen and bridge every incoming tunnel stream to the service
+    let host_config = RiftConfig {
+        identity_path: state.path().join("host.key"),
+        ..RiftConfig::new().with_mdns(false).with_relay(false)
+    };
+    let mut host = PeerNetwork::new(host_config).await?;
+    let host_id = *host.peer_id();
+    let mut host_events = host.take_event_receiver();
+    let mut tunnels = host.take_incoming_streams();
+    host.start_listening().await?;
+
+    tokio::spawn(async move {
+        while let Some((peer_id, stream)) = tunnels.next().await {
+            println!("host: tunnel stream from {}", peer_id);
+            tokio::spawn(async move {
+                // Each stream names the port it wants; this host only shares one
+                let (request, stream) = read_tunnel_request(stream).await?;
+                if request.port != service_port {
+                    return Ok(());
+                }
+                bridge_stream_to_tcp(stream, request.port).await
+            });
+        }
+    });
+
+    // Learn a loopback address the client can dial
+    let host_addr = loop {
+        match host.poll_once().await {
+            Some(()) => {}
+            None => return Err("host network stopped".into()),
+        }
+        if let Ok(NetworkEvent::Listening { address }) = host_events.try_recv()
+            && address.iter().any(|p| p == Protocol::Ip4([127, 0, 0, 1].into()))
+        {
+            break address;
+        }
+    };
+    tokio::spawn(async move {
+        loop {
+            host.poll_once().await;
+        }
+    });
+    tokio::spawn(async move { while host_events.recv().await.is_some() {} });
+
+    // Client: dial the host directly and wait for the connection
+    let client_config = RiftConfig {
+        identity_path: state.path().join("client.key"),
+        ..RiftConfig::new().with_mdns(false).with_relay(false)
+    };
+    let mut client = PeerNetwork::new(client_config).await?;
+    let mut client_events = client.take_event_receiver();
+    let mut control = client.stream_control();
+    let protocols = client.protocols().clone();
+    client.start_listening().await?;
+    client.dial_peer_at(host_id, host_addr).await?;
+    tokio::spawn(async move {
+        loop {
+            client.poll_once().await;
+        }
+    });
+
+    tokio::time::timeout(Duration::from_secs(10), async {
+        while let Some(event) = client_events.recv().await {
+            if let NetworkEvent::PeerConnected { peer_id, .. } = event
+                && peer_id == host_id
+            {
+                break;
+            }
+        }
+    })
+    .await?;
+    tokio::spawn(async move { while client_events.recv().await.is_some() {} });
+
+    // Open a tunnel and round-trip a message through the host's service
+    let stream = open_tunnel_stream(&mut control, &protocols, host_id, service_port).await?;
+    let mut stream = stream.compat();
+    stream.write_all(b"hello through rift").await?;
+
+    let mut buf = [0u8; 64];
+    let n = tokio::time::timeout(Duration::from_secs(5), stream.read(&mut buf)).await??;
+    println!("client: echoed {:?}", String::from_utf8_lossy(&buf[..n]));
+
+    Ok(())
+}
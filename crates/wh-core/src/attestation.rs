@@ -0,0 +1,125 @@
+//! Peer Identity Attestations
+//!
+//! A self-signed claim ("alice, acme, alice@acme.dev") signed with the peer's
+//! identity key. The signature only proves the claim came from the holder of
+//! that peer ID; it does not prove the claim itself is true.
+
+use libp2p::identity::{Keypair, PublicKey};
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::error::{RiftError, Result};
+
+/// Domain separator so attestation signatures can't be replayed elsewhere
+const SIGNING_CONTEXT: &[u8] = b"rift-attestation-v1:";
+
+/// Identity claims a peer makes about itself
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Attestation {
+    /// Display name, e.g. "alice"
+    pub alias: String,
+    /// Organization, e.g. "acme"
+    pub organization: Option<String>,
+    /// Contact, e.g. an email address
+    pub contact: Option<String>,
+}
+
+impl Attestation {
+    /// Sign the claims with an identity keypair
+    pub fn sign(self, keypair: &Keypair) -> Result<SignedAttestation> {
+        let signature = keypair
+            .sign(&self.signing_bytes()?)
+            .map_err(|e| RiftError::SigningFailed(format!("Failed to sign attestation: {}", e)))?;
+
+        Ok(SignedAttestation {
+            claims: self,
+            public_key: keypair.public().encode_protobuf(),
+            signature,
+        })
+    }
+
+    fn signing_bytes(&self) -> Result<Vec<u8>> {
+        let mut bytes = SIGNING_CONTEXT.to_vec();
+        bytes.extend(bincode::serialize(self)?);
+        Ok(bytes)
+    }
+}
+
+impl fmt::Display for Attestation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.alias)?;
+        if let Some(org) = &self.organization {
+            write!(f, "@{}", org)?;
+        }
+        if let Some(contact) = &self.contact {
+            write!(f, " <{}>", contact)?;
+        }
+        Ok(())
+    }
+}
+
+/// Claims together with the signing key and signature
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedAttestation {
+    /// The claims
+    pub claims: Attestation,
+    /// Protobuf-encoded public key of the signer
+    pub public_key: Vec<u8>,
+    /// Signature over the claims
+    pub signature: Vec<u8>,
+}
+
+impl SignedAttestation {
+    /// Check that the signature is valid and made by `peer_id`'s key
+    pub fn verify(&self, peer_id: &PeerId) -> bool {
+        let Ok(public_key) = PublicKey::try_decode_protobuf(&self.public_key) else {
+            return false;
+        };
+        if public_key.to_peer_id() != *peer_id {
+            return false;
+        }
+        match self.claims.signing_bytes() {
+            Ok(bytes) => public_key.verify(&bytes, &self.signature),
+            Err(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claims() -> Attestation {
+        Attestation {
+            alias: "alice".to_string(),
+            organization: Some("acme".to_string()),
+            contact: None,
+        }
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let keypair = Keypair::generate_ed25519();
+        let peer_id = keypair.public().to_peer_id();
+        let signed = claims().sign(&keypair).unwrap();
+
+        assert!(signed.verify(&peer_id));
+        assert_eq!(signed.claims.to_string(), "alice@acme");
+    }
+
+    #[test]
+    fn test_verify_rejects_tampering_and_other_peers() {
+        let keypair = Keypair::generate_ed25519();
+        let peer_id = keypair.public().to_peer_id();
+        let signed = claims().sign(&keypair).unwrap();
+
+        let mut tampered = signed.clone();
+        tampered.claims.alias = "mallory".to_string();
+        assert!(!tampered.verify(&peer_id));
+
+        let other = Keypair::generate_ed25519().public().to_peer_id();
+        assert!(!signed.verify(&other));
+    }
+}
@@ -11,5 +11,8 @@ pub const BIN_NAME: &str = "rift";
 /// Protocol link scheme (e.g., rift://PEER_ID)
 pub const LINK_SCHEME: &str = "rift";
 
+/// Default first segment of every protocol ID (`/rift/tunnel/1.2.0`)
+pub const PROTOCOL_NAMESPACE: &str = "rift";
+
 /// Short description
 pub const DESCRIPTION: &str = "Local-First P2P Tunneling Tool";
@@ -3,13 +3,187 @@
 //! Handles loading and saving of Rift configuration including
 //! identity keys, known peers, and user preferences.
 
+use libp2p::{multiaddr::Protocol, Multiaddr, PeerId};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
 use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
 
+use crate::attestation::Attestation;
+use crate::brand::PROTOCOL_NAMESPACE;
 use crate::error::{RiftError, Result};
+use crate::network::behaviour::Protocols;
+use crate::network::exit::ExitPolicy;
+use crate::network::policy::{PeerAccess, ProtocolPolicy};
+use crate::network::rendezvous::DEFAULT_RENDEZVOUS_NAMESPACE;
+use crate::network::transport::TransportConfig;
+use crate::secrets::{Keystore, Redaction};
+use crate::spool::Spool;
+use crate::state::{FileStore, StateBackend, StateConfig, StateStore};
+
+/// Environment variable naming the configuration directory
+pub const CONFIG_DIR_ENV: &str = "RIFT_CONFIG_DIR";
+
+/// Directory set with [`RiftConfig::override_config_dir`] (e.g. `--config-dir`)
+static CONFIG_DIR_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Config keys that can be applied to a running daemon without a restart
+pub const HOT_RELOADABLE_FIELDS: &[&str] = &[
+    "sni_routes",
+    "session_token_ttl_secs",
+    "reconnect_grace_secs",
+    "tunnel_idle_timeout_secs",
+    "protocol_policy",
+    "peer_access",
+    "port_policies",
+    "reject_ephemeral_peers",
+    "target_retry",
+    "max_request_body_bytes",
+    "rate_limit",
+    "links",
+    "viewers",
+];
+
+/// Settings given on the command line, layered over the config file
+///
+/// Kept apart from the file so a reload re-applies them instead of dropping
+/// them (`--sni`) or reporting them as changes (`--max-rate`).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConfigOverrides(toml::Table);
+
+impl ConfigOverrides {
+    /// The settings `config` has on top of `file`, the config it was built from
+    pub fn between(file: &RiftConfig, config: &RiftConfig) -> Result<Self> {
+        Ok(Self(table_diff(&RiftConfig::to_table(file)?, RiftConfig::to_table(config)?)))
+    }
+
+    /// `config` with these settings layered on top
+    pub fn apply(&self, config: &RiftConfig) -> Result<RiftConfig> {
+        let mut table = RiftConfig::to_table(config)?;
+        merge_table(&mut table, &self.0);
+        let mut layered: RiftConfig = toml::Value::Table(table)
+            .try_into()
+            .map_err(|e| RiftError::ConfigError(e.to_string()))?;
+        layered.ephemeral_identity = config.ephemeral_identity;
+        layered.state_store = config.state_store.clone();
+        Ok(layered)
+    }
+}
+
+/// Entries of `config` that differ from `file`, descending into tables
+fn table_diff(file: &toml::Table, config: toml::Table) -> toml::Table {
+    let mut diff = toml::Table::new();
+    for (key, value) in config {
+        match (file.get(&key), value) {
+            (Some(toml::Value::Table(file)), toml::Value::Table(table)) => {
+                let nested = table_diff(file, table);
+                if !nested.is_empty() {
+                    diff.insert(key, toml::Value::Table(nested));
+                }
+            }
+            (Some(old), value) if *old == value => {}
+            (_, value) => {
+                diff.insert(key, value);
+            }
+        }
+    }
+    diff
+}
+
+/// Write `layer` over `table`, merging tables key by key
+fn merge_table(table: &mut toml::Table, layer: &toml::Table) {
+    for (key, value) in layer {
+        match (table.get_mut(key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(layer)) => merge_table(base, layer),
+            _ => {
+                table.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+/// Outcome of applying a reloaded configuration
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigChanges {
+    /// Changed keys that were applied live
+    pub applied: Vec<String>,
+
+    /// Changed keys that only take effect after a restart
+    pub requires_restart: Vec<String>,
+}
+
+impl ConfigChanges {
+    /// Whether the reloaded file differed at all
+    pub fn is_empty(&self) -> bool {
+        self.applied.is_empty() && self.requires_restart.is_empty()
+    }
+}
+
+/// Keys that are left out of a serialized default configuration, as dotted paths
+///
+/// Unset `Option`s are not written, so these are listed for typo suggestions.
+const OPTIONAL_KEYS: &[&str] = &[
+    "rendezvous_server",
+    "dns_resolver",
+    "attestation",
+    "swarm_key_path",
+    "agent_version",
+    "network.psk_path",
+    "remote_control.listen",
+    "remote_control.token",
+    "state.path",
+];
+
+/// A setting found wrong by [`RiftConfig::parse`] or [`RiftConfig::validate`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigIssue {
+    /// Path of the setting, e.g. `relays[1]` or `dial_retry.jitter_percent`
+    pub field: String,
+
+    /// What is wrong with it
+    pub problem: String,
+
+    /// How to fix it, when there is an obvious fix
+    pub hint: Option<String>,
+}
+
+impl ConfigIssue {
+    fn new(field: impl Into<String>, problem: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            problem: problem.into(),
+            hint: None,
+        }
+    }
+
+    fn with_hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.problem)?;
+        if let Some(hint) = &self.hint {
+            write!(f, " ({})", hint)?;
+        }
+        Ok(())
+    }
+}
+
+/// Public IPFS bootstrap nodes used when no others are configured (use sparingly!)
+pub const DEFAULT_BOOTSTRAP_PEERS: &[&str] = &[
+    "/dnsaddr/bootstrap.libp2p.io/p2p/QmNnooDu7bfjPFoTZYxMNLWUQJyrVwtbZg5gBMjTezGAJN",
+    "/dnsaddr/bootstrap.libp2p.io/p2p/QmQCU2EcMqAqQPR2i9bChDtGNJchTbq5TbXJJ16u19uLTa",
+];
 
 /// Main configuration for Rift
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct RiftConfig {
     /// Path to the identity keypair file
     pub identity_path: PathBuf,
@@ -17,26 +191,873 @@ pub struct RiftConfig {
     /// Port for the local QUIC listener (0 = random)
     pub listen_port: u16,
 
+    /// Where to listen instead of every interface (empty = `0.0.0.0` and `::`)
+    ///
+    /// Each entry is an IP address (`10.8.0.2`), an interface name (`wg0`,
+    /// every address it has) or a full multiaddr used as is
+    /// (`/ip4/10.8.0.2/udp/4001/quic-v1`). IPs and interfaces get the usual
+    /// listeners on `listen_port`, plus WebSocket and WebRTC when enabled.
+    pub listen_addrs: Vec<String>,
+
+    /// WebSocket listener for peers whose network only allows HTTP(S) egress
+    pub websocket: WebSocketConfig,
+
+    /// WebRTC listener for browser peers (builds with the `webrtc` feature)
+    pub webrtc: WebRtcConfig,
+
+    /// QUIC timeouts, keep-alive and stream limits
+    pub transport: TransportConfig,
+
     /// Enable mDNS for local network peer discovery
     pub enable_mdns: bool,
 
+    /// How long a discovered peer is remembered without being re-announced
+    pub discovery_ttl_secs: u64,
+
+    /// Announce shared services to peers on the presence topic (`rift peers`)
+    pub announce_services: bool,
+
     /// Enable relay client for NAT traversal
     pub enable_relay: bool,
 
     /// Bootstrap peers for initial discovery
     pub bootstrap_peers: Vec<String>,
 
-    /// Rendezvous server address (optional)
+    /// Relay servers to reserve a slot on, each ending in `/p2p/<peer id>`
+    ///
+    /// Peers that cannot reach us directly connect through these; `connect()`
+    /// also tries them when dialing a peer.
+    pub relays: Vec<String>,
+
+    /// Picking one of several configured relays to keep our reservation on
+    pub relay_selection: RelaySelectionConfig,
+
+    /// Settings of `rift relay`
+    pub relay_server: RelayServerConfig,
+
+    /// Rendezvous server address, ending in `/p2p/<peer id>` (optional)
     pub rendezvous_server: Option<String>,
 
+    /// Namespace this node registers under and looks peers up in at the rendezvous server
+    pub rendezvous_namespace: String,
+
+    /// Name server for links naming a DNS host (`1.1.1.1` or `1.1.1.1:53`; default: the system's)
+    pub dns_resolver: Option<String>,
+
+    /// First segment of every protocol ID (`/<namespace>/tunnel/1.0.0`)
+    ///
+    /// Deployments sharing a LAN but using different namespaces (staging and
+    /// production tooling) do not talk to each other.
+    pub protocol_namespace: String,
+
     /// Maximum number of concurrent connections
     pub max_connections: usize,
 
     /// Connection timeout in seconds
     pub connection_timeout_secs: u64,
 
+    /// Close tunnelled connections that relayed nothing either way for this many seconds (0 = never)
+    pub tunnel_idle_timeout_secs: u64,
+
     /// Enable debug logging
     pub debug: bool,
+
+    /// TLS passthrough routes: SNI server name -> local port
+    ///
+    /// When non-empty, the share side peeks the ClientHello of each tunnel
+    /// stream and forwards it to the matching port (`*.domain` wildcards allowed).
+    pub sni_routes: HashMap<String, u16>,
+
+    /// Names peers can connect to instead of a port (`rift share 5432 --name db`)
+    pub service_names: HashMap<String, u16>,
+
+    /// Other machines `rift share --target HOST:PORT` may forward to: `HOST` (any port) or `HOST:PORT`
+    ///
+    /// This machine is always allowed; anything else must be listed here.
+    pub share_targets: Vec<String>,
+
+    /// Lifetime of session resumption tokens issued to approved peers (0 = disabled)
+    pub session_token_ttl_secs: u64,
+
+    /// Approved peers reconnecting within this many seconds skip the prompt (0 = disabled)
+    pub reconnect_grace_secs: u64,
+
+    /// Caps on in-memory logs and histories
+    pub history: HistoryLimits,
+
+    /// Pre-warmed connections to the shared local service
+    pub target_pool: TargetPoolConfig,
+
+    /// Retries when the shared local service refuses connections
+    pub target_retry: TargetRetryConfig,
+
+    /// Socket options of the local listeners `rift connect` accepts clients on
+    pub socket_options: SocketOptionsConfig,
+
+    /// Largest HTTP request body peers may send to the shared service (0 = unlimited)
+    ///
+    /// Larger requests are answered with `413 Payload Too Large` on the share side.
+    pub max_request_body_bytes: u64,
+
+    /// Bandwidth cap for tunnels to the shared port (share side)
+    pub rate_limit: RateLimitConfig,
+
+    /// Daemon load above which new tunnelled connections are refused
+    pub resource_limits: ResourceLimitsConfig,
+
+    /// Probing of the shared service and what peers do while it is down
+    pub health: HealthConfig,
+
+    /// Checks `rift share` runs before it starts sharing
+    pub preflight: PreflightConfig,
+
+    /// Built-in middlewares run on bridged connections
+    pub middleware: MiddlewareConfig,
+
+    /// How `rift connect` retries a peer that cannot be dialed yet
+    pub dial_retry: DialRetryConfig,
+
+    /// Re-dialing the host after the connection to it dropped (connect side)
+    pub auto_reconnect: AutoReconnectConfig,
+
+    /// Re-listening and re-checking peers after the machine wakes from sleep
+    pub wake_recovery: WakeRecoveryConfig,
+
+    /// TCP control API for `rift --remote` (off unless `listen` is set)
+    pub remote_control: RemoteControlConfig,
+
+    /// Spread each tunnelled connection over several streams (connect side)
+    pub striping: StripingConfig,
+
+    /// Reopen dropped streams and replay the in-flight HTTP request (connect side)
+    pub migration: MigrationConfig,
+
+    /// Log HTTP requests made through the tunnel to the TUI and `access.log` (connect side)
+    pub http_log: bool,
+
+    /// Which sub-protocols peers may open streams for
+    pub protocol_policy: ProtocolPolicy,
+
+    /// Peers admitted without approval or refused outright (share side)
+    pub peer_access: PeerAccess,
+
+    /// Rules for tunnels to particular shared ports (share side)
+    pub port_policies: Vec<PortPolicy>,
+
+    /// Revocable link IDs that connecting peers must present (share side)
+    pub links: LinksConfig,
+
+    /// Peer IDs allowed to watch this daemon's dashboard read-only with `rift attach`
+    pub viewers: Vec<String>,
+
+    /// Identity claims signed and presented to hosts we connect to
+    pub attestation: Option<Attestation>,
+
+    /// Which network this node joins (public, or private with a pre-shared key)
+    pub network: NetworkConfig,
+
+    /// Older name of `network.psk_path`, still honored when that is unset
+    pub swarm_key_path: Option<PathBuf>,
+
+    /// Where the secrets keypair is kept (`keyring` or `file`)
+    pub keystore: Keystore,
+
+    /// Secrets not delivered in full, by key (`masked` or `reference`; share side)
+    pub secrets_redaction: HashMap<String, Redaction>,
+
+    /// Where the peer book, session tokens, status snapshots and audit log are kept
+    pub state: StateConfig,
+
+    /// Refuse streams from peers announcing a throwaway identity
+    ///
+    /// Advisory only: the announcement is self-declared, so a peer can leave it
+    /// out, and streams opened before identify completes are not checked. Use
+    /// `peer_access` to keep peers out.
+    pub reject_ephemeral_peers: bool,
+
+    /// Agent version announced to peers over identify (default `rift/<version>`)
+    ///
+    /// Set e.g. `"rift"` to keep the version private. The capability flags and
+    /// the ephemeral marker (for throwaway identities, since hosts may refuse
+    /// those) are still appended.
+    pub agent_version: Option<String>,
+
+    /// Never contact anything but the peers, relays and servers configured here
+    ///
+    /// Rift sends no telemetry today; this makes that a guarantee. Features
+    /// that reach out on their own (update checks, gateways) must ask
+    /// [`RiftConfig::check_outbound`] first and stay off when it fails.
+    pub no_telemetry: bool,
+
+    /// Use a throwaway identity for this session instead of `identity_path`
+    ///
+    /// Runtime-only (set by `rift connect --ephemeral`), never read from or written to disk.
+    #[serde(skip)]
+    pub ephemeral_identity: bool,
+
+    /// Store handed in by an embedder, used instead of `state`
+    ///
+    /// Runtime-only (see [`RiftConfig::with_state_store`]).
+    #[serde(skip)]
+    pub state_store: Option<Arc<dyn StateStore>>,
+}
+
+/// Settings for pooling host-side connections to the local target
+///
+/// Intended for request/response services such as HTTP dev servers, where
+/// browsers open many short-lived sockets. Only ports listed as HTTP are
+/// pooled: a service that speaks first (SMTP, MySQL, SSH) would greet a warm
+/// connection before any peer is there to read the greeting.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TargetPoolConfig {
+    /// Keep warm connections to the target
+    pub enabled: bool,
+
+    /// Shared ports serving HTTP, the only ones pooled
+    pub http_ports: Vec<u16>,
+
+    /// Idle connections kept per target port
+    pub max_idle: usize,
+
+    /// Seconds an idle connection is kept before being discarded
+    pub idle_timeout_secs: u64,
+}
+
+impl Default for TargetPoolConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            http_ports: Vec::new(),
+            max_idle: 4,
+            idle_timeout_secs: 30,
+        }
+    }
+}
+
+impl TargetPoolConfig {
+    /// Whether connections to a local port are pooled
+    pub fn pools(&self, port: u16) -> bool {
+        self.enabled && self.http_ports.contains(&port)
+    }
+}
+
+/// Socket options of the local listeners, for high connection rates
+///
+/// Keepalive settings left at 0 keep the OS defaults.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SocketOptionsConfig {
+    /// Set SO_REUSEPORT, so several processes can accept on the same port (Unix only)
+    pub reuse_port: bool,
+
+    /// Connections the OS queues before they are accepted
+    pub backlog: u32,
+
+    /// Idle seconds before TCP keepalive probes start on accepted connections (0 = no keepalive)
+    pub keepalive_secs: u64,
+
+    /// Seconds between keepalive probes (0 = OS default)
+    pub keepalive_interval_secs: u64,
+
+    /// Unanswered probes before the connection is dropped (0 = OS default)
+    pub keepalive_retries: u32,
+}
+
+impl Default for SocketOptionsConfig {
+    fn default() -> Self {
+        Self {
+            reuse_port: false,
+            backlog: 1024,
+            keepalive_secs: 0,
+            keepalive_interval_secs: 0,
+            keepalive_retries: 0,
+        }
+    }
+}
+
+/// Retry budget for connecting to the local target
+///
+/// Lets tunnel streams ride out a dev server restarting instead of failing
+/// the remote request immediately.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TargetRetryConfig {
+    /// Connection attempts before giving up (1 = no retry)
+    pub attempts: u32,
+
+    /// Delay before the first retry, doubled after each failure
+    pub initial_backoff_ms: u64,
+
+    /// Upper bound on the delay between attempts
+    pub max_backoff_ms: u64,
+}
+
+impl TargetRetryConfig {
+    /// Delay to wait after the given failed attempt (1-based)
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        exponential_backoff(self.initial_backoff_ms, self.max_backoff_ms, attempt)
+    }
+}
+
+/// `initial_ms` doubled for each failed attempt after the first, capped at `max_ms`
+fn exponential_backoff(initial_ms: u64, max_ms: u64, attempt: u32) -> Duration {
+    let factor = 1u64 << attempt.saturating_sub(1).min(16);
+    Duration::from_millis(initial_ms.saturating_mul(factor).min(max_ms))
+}
+
+impl Default for TargetRetryConfig {
+    fn default() -> Self {
+        Self {
+            attempts: 5,
+            initial_backoff_ms: 100,
+            max_backoff_ms: 1_000,
+        }
+    }
+}
+
+/// Settings for striping bulk transfers across parallel streams
+///
+/// Helps single large downloads on high bandwidth-delay paths, where one
+/// stream's flow-control window limits throughput. Costs a little latency,
+/// so it is off by default.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StripingConfig {
+    /// Streams per connection (1 = no striping, at most 16)
+    pub streams: u8,
+
+    /// Bytes sent per stream before moving to the next one
+    pub chunk_size: usize,
+}
+
+impl Default for StripingConfig {
+    fn default() -> Self {
+        Self {
+            streams: 1,
+            chunk_size: 64 * 1024,
+        }
+    }
+}
+
+/// Best-effort connection migration
+///
+/// When the stream under a local connection drops before any of the response
+/// came back, a new stream is opened and the request sent so far is replayed,
+/// so the local client never sees a reset. Only idempotent HTTP/1.x requests
+/// (GET, HEAD, OPTIONS, PUT, DELETE, TRACE) and idle connections are resumed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MigrationConfig {
+    /// Resume dropped streams (off by default)
+    pub enabled: bool,
+
+    /// Largest request kept for replay; bigger requests are not resumed
+    pub max_replay_bytes: usize,
+
+    /// Attempts at reopening a dropped stream
+    pub max_attempts: u32,
+}
+
+impl Default for MigrationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_replay_bytes: 64 * 1024,
+            max_attempts: 3,
+        }
+    }
+}
+
+/// What a bandwidth cap applies to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RateLimitScope {
+    /// Each tunnelled connection gets the full rate
+    #[default]
+    Connection,
+    /// All connections of one peer share the rate
+    Peer,
+}
+
+/// Bandwidth cap for tunnels to the shared port
+///
+/// Counts both directions together. Bursts of up to one second's worth of
+/// bytes pass unthrottled.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    /// Bytes per second (0 = unlimited)
+    pub max_bytes_per_sec: u64,
+
+    /// Cap each connection or each peer
+    pub scope: RateLimitScope,
+}
+
+/// Guardrails against the daemon overloading its own machine
+///
+/// Once a limit is reached, new tunnelled connections are refused (open ones
+/// keep running) and a warning is shown. Every limit is off (0) by default.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResourceLimitsConfig {
+    /// Spawned daemon tasks (bridges, exchanges) running at once
+    pub max_tasks: usize,
+
+    /// Bytes of copy buffers held by open bridges
+    pub max_buffered_bytes: u64,
+
+    /// Resident memory of the daemon process
+    pub max_rss_bytes: u64,
+}
+
+/// Health signal of the shared service
+///
+/// The host connects to its shared port every `interval_secs` and tells its
+/// peers whether the service is up, slow or down.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HealthConfig {
+    /// Seconds between probes of the shared service (0 = no health signal)
+    pub interval_secs: u64,
+
+    /// Connects slower than this many milliseconds report the service as degraded
+    pub degraded_ms: u64,
+
+    /// Stop accepting local connections while the host reports its service down (connect side)
+    pub pause_when_down: bool,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: 10,
+            degraded_ms: 1000,
+            pause_when_down: true,
+        }
+    }
+}
+
+/// What a failed preflight check does
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PreflightAction {
+    /// Skip the check
+    Off,
+    /// Print a warning and share anyway
+    Warn,
+    /// Exit without sharing
+    Fail,
+}
+
+/// Checks `rift share` runs before it starts sharing
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PreflightConfig {
+    /// Nothing accepts connections on a shared local port yet
+    pub listener: PreflightAction,
+
+    /// Another rift daemon on this machine already shares the port
+    pub conflict: PreflightAction,
+}
+
+impl Default for PreflightConfig {
+    fn default() -> Self {
+        Self {
+            listener: PreflightAction::Warn,
+            conflict: PreflightAction::Fail,
+        }
+    }
+}
+
+/// Middlewares built into rift (feature `plugins`), by the name that turns them on
+pub const BUILTIN_MIDDLEWARES: &[&str] = &["inject-headers", "demo-filter"];
+
+/// Built-in middlewares run on bridged connections, in the order listed
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MiddlewareConfig {
+    /// Names of the middlewares to run (see [`BUILTIN_MIDDLEWARES`])
+    pub enabled: Vec<String>,
+
+    /// Headers `inject-headers` adds to HTTP requests
+    pub inject_headers: BTreeMap<String, String>,
+
+    /// Words `demo-filter` masks in responses
+    pub demo_filter_words: Vec<String>,
+}
+
+/// Whether tunnels to a port wait for the host's approval
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PortApproval {
+    /// As the share session decides (`--auto-approve`, peers approved so far)
+    #[default]
+    Session,
+    /// Bridge without asking
+    Auto,
+    /// Ask even when sharing with `--auto-approve`
+    Require,
+}
+
+/// Rules for tunnels to one shared port
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PortPolicy {
+    /// Shared port the rules apply to
+    pub port: u16,
+
+    /// Whether tunnels wait for approval
+    pub approval: PortApproval,
+
+    /// Peer IDs that may use the port; when non-empty, everyone else is refused
+    pub allow: Vec<String>,
+
+    /// Drop what peers send, so they only receive what the service sends (e.g. a log stream)
+    pub read_only: bool,
+
+    /// Connections open to the port at once (0 = no limit)
+    pub max_connections: usize,
+}
+
+impl PortPolicy {
+    /// Whether `peer_id` may use the port
+    pub fn allows(&self, peer_id: &PeerId) -> bool {
+        self.allow.is_empty() || self.allow.contains(&peer_id.to_string())
+    }
+}
+
+/// Settings of the network a node joins
+///
+/// With a pre-shared key every connection has to prove the key before
+/// anything else is exchanged, so the node never talks to strangers or to the
+/// public bootstrap nodes. Private nodes use TCP instead of QUIC, WebSocket
+/// and WebRTC.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkConfig {
+    /// Swarm key file (`rift identity genkey --swarm`), relative to the state directory
+    pub psk_path: Option<PathBuf>,
+}
+
+/// Revocable links
+///
+/// With `require` set, `rift share` hands out links carrying an ID
+/// (`rift://<PEER_ID>/<PORT>?link=<ID>`) and only peers that present a known,
+/// unexpired and unrevoked ID get tunnels. Allowlisted peers are exempt.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LinksConfig {
+    /// Refuse tunnels to peers without a valid link ID
+    pub require: bool,
+
+    /// Seconds a newly created link stays valid (0 = until revoked)
+    pub default_ttl_secs: u64,
+}
+
+/// Backoff between attempts at dialing a peer (connect side)
+///
+/// Attempts fail while the peer's addresses are still unknown, e.g. until
+/// mDNS or the rendezvous server found it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DialRetryConfig {
+    /// Dial attempts before giving up (1 = no retry)
+    pub attempts: u32,
+
+    /// Delay before the first retry, doubled after each failure
+    pub initial_backoff_ms: u64,
+
+    /// Upper bound on the delay between attempts
+    pub max_backoff_ms: u64,
+
+    /// Random spread of each delay, in percent either way
+    pub jitter_percent: u8,
+}
+
+impl DialRetryConfig {
+    /// Delay to wait after the given failed attempt (1-based), without jitter
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        exponential_backoff(self.initial_backoff_ms, self.max_backoff_ms, attempt)
+    }
+
+    /// [`backoff`](Self::backoff) moved randomly by up to `jitter_percent`
+    pub fn jittered_backoff(&self, attempt: u32) -> Duration {
+        let base = self.backoff(attempt).as_millis() as u64;
+        let spread = base * u64::from(self.jitter_percent.min(100)) / 100;
+        if spread == 0 {
+            return Duration::from_millis(base);
+        }
+        Duration::from_millis(base - spread + rand::random::<u64>() % (2 * spread + 1))
+    }
+}
+
+impl Default for DialRetryConfig {
+    fn default() -> Self {
+        Self {
+            attempts: 10,
+            initial_backoff_ms: 250,
+            max_backoff_ms: 1_000,
+            jitter_percent: 20,
+        }
+    }
+}
+
+/// Probing of the configured relays (see `rift relays status`)
+///
+/// With two or more relays configured, each is asked for a reservation and
+/// pinged; only the fastest one that accepts keeps it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RelaySelectionConfig {
+    /// Keep a reservation on the best relay only (false = on every relay)
+    pub enabled: bool,
+
+    /// Seconds between probe rounds
+    pub interval_secs: u64,
+}
+
+impl RelaySelectionConfig {
+    /// Time between probe rounds
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_secs)
+    }
+}
+
+impl Default for RelaySelectionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_secs: 300,
+        }
+    }
+}
+
+/// Settings for re-dialing a host whose connection dropped
+///
+/// While the host is unreachable the local port stops accepting, so clients
+/// wait in the listen backlog instead of being refused.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutoReconnectConfig {
+    /// Re-dial the host when the connection drops
+    pub enabled: bool,
+
+    /// Dial attempts before giving up (0 = keep trying)
+    pub attempts: u32,
+
+    /// Delay before the first attempt, doubled after each failure
+    pub initial_backoff_ms: u64,
+
+    /// Upper bound on the delay between attempts
+    pub max_backoff_ms: u64,
+}
+
+impl AutoReconnectConfig {
+    /// Delay before the given attempt (1-based)
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        exponential_backoff(self.initial_backoff_ms, self.max_backoff_ms, attempt)
+    }
+
+    /// Whether another attempt may follow the given one
+    pub fn may_retry(&self, attempt: u32) -> bool {
+        self.attempts == 0 || attempt < self.attempts
+    }
+}
+
+impl Default for AutoReconnectConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            attempts: 0,
+            initial_backoff_ms: 500,
+            max_backoff_ms: 30_000,
+        }
+    }
+}
+
+/// Settings for recovering after the machine slept
+///
+/// QUIC connections and mDNS registrations die silently while a laptop is
+/// suspended. A jump of the clocks between two housekeeping ticks means the
+/// process was frozen; the node then re-listens, re-advertises and pings
+/// every connected peer, dropping the ones that no longer answer.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WakeRecoveryConfig {
+    /// Detect sleep and recover
+    pub enabled: bool,
+
+    /// Gap between housekeeping ticks treated as sleep
+    pub jump_threshold_secs: u64,
+
+    /// Echo round trips sent to each peer after waking
+    pub ping_burst: u32,
+
+    /// Time a peer gets to answer the whole burst
+    pub ping_timeout_ms: u64,
+}
+
+impl Default for WakeRecoveryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            jump_threshold_secs: 15,
+            ping_burst: 3,
+            ping_timeout_ms: 5_000,
+        }
+    }
+}
+
+/// Control API on a TCP port, for managing a daemon from another machine
+///
+/// Every request carries a bearer token. Without `token`, the daemon
+/// generates one on first start and keeps it in `control_token` in the state
+/// directory. Plain TCP: put it on a VPN or behind an SSH tunnel rather than
+/// on the open internet.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RemoteControlConfig {
+    /// Address to listen on, e.g. `"127.0.0.1:7422"` (None = off)
+    ///
+    /// The protocol is plain TCP, token included: other addresses should only
+    /// be reachable over SSH, a VPN such as WireGuard, or a TLS proxy.
+    pub listen: Option<String>,
+
+    /// Bearer token clients must present (None = generated)
+    pub token: Option<String>,
+}
+
+/// Settings for accepting connections over WebSocket (`/ws`, `/wss`)
+///
+/// Dialing `/ws` and `/wss` addresses always works; this only controls the
+/// listener. The listener speaks plain `/ws`: for `/wss`, put it behind a
+/// TLS-terminating reverse proxy and list the proxy in `external_addrs`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebSocketConfig {
+    /// Listen for WebSocket connections
+    pub enabled: bool,
+
+    /// TCP port of the WebSocket listener (0 = random)
+    pub port: u16,
+
+    /// Public WebSocket addresses of this node, e.g. `/dns4/rift.example.com/tcp/443/wss`
+    ///
+    /// Announced to peers and the rendezvous server.
+    pub external_addrs: Vec<String>,
+}
+
+/// Settings for accepting browser connections over WebRTC (`/webrtc-direct`)
+///
+/// Only builds with the `webrtc` feature have the transport; others ignore this.
+/// The certificate is kept in `webrtc_cert.pem` next to the identity key.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebRtcConfig {
+    /// Listen for WebRTC connections
+    pub enabled: bool,
+
+    /// UDP port of the WebRTC listener (0 = random; must differ from `listen_port`)
+    pub port: u16,
+}
+
+/// Settings of a self-hosted relay server (`rift relay`)
+///
+/// Circuits carry whole tunnel sessions when hole punching fails, so the
+/// limits are more generous than libp2p's hole-punching-only defaults.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RelayServerConfig {
+    /// UDP port the relay listens on (QUIC)
+    pub listen_port: u16,
+
+    /// TCP port of an additional WebSocket listener (0 = none)
+    ///
+    /// Lets clients behind HTTP(S)-only egress reach the relay, typically on
+    /// 80, or on 443 behind a TLS-terminating proxy for `/wss`.
+    pub websocket_port: u16,
+
+    /// Publicly reachable addresses of the relay, announced to its clients
+    pub external_addrs: Vec<String>,
+
+    /// Also serve as a rendezvous point
+    pub rendezvous: bool,
+
+    /// Circuit reservations held at once
+    pub max_reservations: usize,
+
+    /// Reservations held at once by a single peer
+    pub max_reservations_per_peer: usize,
+
+    /// Seconds a reservation lasts before it must be renewed
+    pub reservation_duration_secs: u64,
+
+    /// Circuits relayed at once
+    pub max_circuits: usize,
+
+    /// Circuits relayed at once for a single peer
+    pub max_circuits_per_peer: usize,
+
+    /// Seconds a circuit may stay open
+    pub max_circuit_duration_secs: u64,
+
+    /// Bytes a circuit may carry in total (0 = unlimited)
+    pub max_circuit_bytes: u64,
+}
+
+impl Default for RelayServerConfig {
+    fn default() -> Self {
+        Self {
+            listen_port: 4001,
+            websocket_port: 0,
+            external_addrs: Vec::new(),
+            rendezvous: true,
+            max_reservations: 128,
+            max_reservations_per_peer: 4,
+            reservation_duration_secs: 3600,
+            max_circuits: 64,
+            max_circuits_per_peer: 8,
+            max_circuit_duration_secs: 3600,
+            max_circuit_bytes: 1024 * 1024 * 1024,
+        }
+    }
+}
+
+/// Size caps for bounded logs and histories
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistoryLimits {
+    /// Event log lines kept by the TUI
+    pub log_lines: usize,
+
+    /// Traffic samples kept for the throughput graph
+    pub traffic_samples: usize,
+
+    /// Connection entries tracked for display
+    pub connections: usize,
+
+    /// Tunnel streams held per peer while waiting for approval
+    pub pending_streams: usize,
+
+    /// HTTP exchanges kept for the inspector (connect side, `http_log`)
+    pub inspected_requests: usize,
+
+    /// Largest body kept per inspected message; bigger bodies are redacted
+    pub inspect_body_bytes: usize,
+}
+
+impl Default for HistoryLimits {
+    fn default() -> Self {
+        Self {
+            log_lines: 100,
+            traffic_samples: 120,
+            connections: 256,
+            pending_streams: 32,
+            inspected_requests: 50,
+            inspect_body_bytes: 16 * 1024,
+        }
+    }
 }
 
 impl Default for RiftConfig {
@@ -44,17 +1065,64 @@ impl Default for RiftConfig {
         Self {
             identity_path: Self::default_identity_path(),
             listen_port: 0,
+            listen_addrs: Vec::new(),
+            websocket: WebSocketConfig::default(),
+            webrtc: WebRtcConfig::default(),
+            transport: TransportConfig::default(),
             enable_mdns: true,
+            discovery_ttl_secs: 360,
+            announce_services: true,
             enable_relay: true,
-            // Public IPFS relays for testing (use sparingly!)
-            bootstrap_peers: vec![
-                "/dnsaddr/bootstrap.libp2p.io/p2p/QmNnooDu7bfjPFoTZYxMNLWUQJyrVwtbZg5gBMjTezGAJN".to_string(),
-                "/dnsaddr/bootstrap.libp2p.io/p2p/QmQCU2EcMqAqQPR2i9bChDtGNJchTbq5TbXJJ16u19uLTa".to_string(),
-            ],
+            bootstrap_peers: DEFAULT_BOOTSTRAP_PEERS.iter().map(|peer| peer.to_string()).collect(),
+            relays: Vec::new(),
+            relay_selection: RelaySelectionConfig::default(),
+            relay_server: RelayServerConfig::default(),
             rendezvous_server: None,
+            rendezvous_namespace: DEFAULT_RENDEZVOUS_NAMESPACE.to_string(),
+            dns_resolver: None,
+            protocol_namespace: PROTOCOL_NAMESPACE.to_string(),
             max_connections: 64,
             connection_timeout_secs: 30,
+            tunnel_idle_timeout_secs: 0,
             debug: false,
+            sni_routes: HashMap::new(),
+            service_names: HashMap::new(),
+            share_targets: Vec::new(),
+            session_token_ttl_secs: 600,
+            reconnect_grace_secs: 300,
+            history: HistoryLimits::default(),
+            target_pool: TargetPoolConfig::default(),
+            socket_options: SocketOptionsConfig::default(),
+            target_retry: TargetRetryConfig::default(),
+            max_request_body_bytes: 0,
+            rate_limit: RateLimitConfig::default(),
+            resource_limits: ResourceLimitsConfig::default(),
+            health: HealthConfig::default(),
+            preflight: PreflightConfig::default(),
+            middleware: MiddlewareConfig::default(),
+            dial_retry: DialRetryConfig::default(),
+            auto_reconnect: AutoReconnectConfig::default(),
+            wake_recovery: WakeRecoveryConfig::default(),
+            remote_control: RemoteControlConfig::default(),
+            striping: StripingConfig::default(),
+            migration: MigrationConfig::default(),
+            http_log: false,
+            protocol_policy: ProtocolPolicy::default(),
+            peer_access: PeerAccess::default(),
+            port_policies: Vec::new(),
+            links: LinksConfig::default(),
+            viewers: Vec::new(),
+            attestation: None,
+            network: NetworkConfig::default(),
+            swarm_key_path: None,
+            keystore: Keystore::default(),
+            secrets_redaction: HashMap::new(),
+            state: StateConfig::default(),
+            reject_ephemeral_peers: false,
+            agent_version: None,
+            no_telemetry: false,
+            ephemeral_identity: false,
+            state_store: None,
         }
     }
 }
@@ -66,9 +1134,303 @@ impl RiftConfig {
     }
 
     /// Loads configuration from a file
+    ///
+    /// Fails listing every problem found by [`Self::parse`].
     pub fn load(path: &PathBuf) -> Result<Self> {
         let contents = std::fs::read_to_string(path)?;
-        toml::from_str(&contents).map_err(|e| RiftError::ConfigError(e.to_string()))
+        Self::parse(&contents).map_err(|e| match e {
+            RiftError::ConfigError(message) => RiftError::ConfigError(format!("{}: {}", path.display(), message)),
+            e => e,
+        })
+    }
+
+    /// Parses a configuration file's contents and checks every setting
+    ///
+    /// The problems found by [`Self::validate`] are reported together in one
+    /// error. Unknown keys (typos, with the closest known key as a suggestion)
+    /// are only logged as warnings, so a file written for another version
+    /// still loads.
+    pub fn parse(contents: &str) -> Result<Self> {
+        let (config, warnings) = Self::parse_with_warnings(contents)?;
+        for warning in &warnings {
+            tracing::warn!("Configuration: {}", warning);
+        }
+        Ok(config)
+    }
+
+    /// Like [`Self::parse`], returning the unknown keys instead of logging them
+    pub fn parse_with_warnings(contents: &str) -> Result<(Self, Vec<ConfigIssue>)> {
+        let mut warnings = Vec::new();
+        let config: RiftConfig = serde_ignored::deserialize(toml::Deserializer::new(contents), |path| {
+            warnings.push(unknown_key(&path));
+        })
+        .map_err(|e| RiftError::ConfigError(e.to_string()))?;
+        let issues = config.validate();
+
+        if issues.is_empty() {
+            return Ok((config, warnings));
+        }
+        let lines: Vec<String> = issues.iter().map(|issue| format!("  - {}", issue)).collect();
+        Err(RiftError::ConfigError(format!(
+            "{} problem(s) found\n{}",
+            issues.len(),
+            lines.join("\n")
+        )))
+    }
+
+    /// Checks values that parse but cannot work, e.g. malformed addresses or conflicting settings
+    ///
+    /// Called by [`Self::load`]; embedders building a configuration in code
+    /// can call it before handing the configuration to the network.
+    pub fn validate(&self) -> Vec<ConfigIssue> {
+        let mut issues = Vec::new();
+
+        if let Err(e) = self.protocols() {
+            issues.push(
+                ConfigIssue::new("protocol_namespace", e.to_string())
+                    .with_hint(format!("e.g. \"{}-staging\"", PROTOCOL_NAMESPACE)),
+            );
+        }
+        if self.max_connections == 0 {
+            issues.push(ConfigIssue::new("max_connections", "0 refuses every connection").with_hint("the default is 64"));
+        }
+
+        for (i, addr) in self.bootstrap_peers.iter().enumerate() {
+            check_multiaddr(&mut issues, &format!("bootstrap_peers[{}]", i), addr, false);
+        }
+        for (i, addr) in self.relays.iter().enumerate() {
+            check_multiaddr(&mut issues, &format!("relays[{}]", i), addr, true);
+        }
+        if let Some(addr) = &self.rendezvous_server {
+            check_multiaddr(&mut issues, "rendezvous_server", addr, true);
+        }
+        for (i, addr) in self.listen_addrs.iter().enumerate() {
+            // IPs and interface names are resolved when listening
+            if addr.starts_with('/') {
+                check_multiaddr(&mut issues, &format!("listen_addrs[{}]", i), addr, false);
+            }
+        }
+        for (i, addr) in self.websocket.external_addrs.iter().enumerate() {
+            check_multiaddr(&mut issues, &format!("websocket.external_addrs[{}]", i), addr, false);
+        }
+        for (i, addr) in self.relay_server.external_addrs.iter().enumerate() {
+            check_multiaddr(&mut issues, &format!("relay_server.external_addrs[{}]", i), addr, false);
+        }
+
+        if let Some(resolver) = &self.dns_resolver
+            && resolver.parse::<SocketAddr>().is_err()
+            && resolver.parse::<IpAddr>().is_err()
+        {
+            issues.push(
+                ConfigIssue::new("dns_resolver", format!("'{}' is not an IP address", resolver))
+                    .with_hint("e.g. \"1.1.1.1\" or \"1.1.1.1:53\""),
+            );
+        }
+        if let Some(listen) = &self.remote_control.listen {
+            let has_port = listen.rsplit_once(':').is_some_and(|(_, port)| port.parse::<u16>().is_ok());
+            if !has_port {
+                issues.push(
+                    ConfigIssue::new("remote_control.listen", format!("'{}' has no port", listen))
+                        .with_hint("e.g. \"127.0.0.1:7422\""),
+                );
+            }
+        }
+
+        for (server_name, port) in &self.sni_routes {
+            if *port == 0 {
+                issues.push(ConfigIssue::new(format!("sni_routes.\"{}\"", server_name), "port 0 cannot be shared"));
+            }
+        }
+        for (name, port) in &self.service_names {
+            let field = format!("service_names.\"{}\"", name);
+            if *port == 0 {
+                issues.push(ConfigIssue::new(field.clone(), "port 0 cannot be shared"));
+            }
+            if name.is_empty() || name.parse::<u16>().is_ok() || name.contains(['/', '?', ' ', ',', ':']) {
+                issues.push(
+                    ConfigIssue::new(field, "not a usable service name")
+                        .with_hint("use letters, digits and dashes, not just a port number"),
+                );
+            }
+        }
+        let sockets = &self.socket_options;
+        if sockets.backlog == 0 {
+            issues.push(ConfigIssue::new("socket_options.backlog", "0 leaves no room for pending connections").with_hint("the default is 1024"));
+        }
+        if sockets.keepalive_secs == 0 && (sockets.keepalive_interval_secs > 0 || sockets.keepalive_retries > 0) {
+            issues.push(
+                ConfigIssue::new("socket_options.keepalive_secs", "keepalive is off, so its interval and retries are ignored")
+                    .with_hint("set keepalive_secs to turn it on"),
+            );
+        }
+        for (i, name) in self.middleware.enabled.iter().enumerate() {
+            if !BUILTIN_MIDDLEWARES.contains(&name.as_str()) {
+                issues.push(
+                    ConfigIssue::new(format!("middleware.enabled[{}]", i), format!("no middleware named '{}'", name))
+                        .with_hint(format!("built in: {}", BUILTIN_MIDDLEWARES.join(", "))),
+                );
+            }
+        }
+        for (name, value) in &self.middleware.inject_headers {
+            if name.is_empty() || name.contains([':', ' ', '\r', '\n']) || value.contains(['\r', '\n']) {
+                issues.push(
+                    ConfigIssue::new(format!("middleware.inject_headers.{}", name), "not a valid HTTP header")
+                        .with_hint("header names have no spaces or colons; values fit on one line"),
+                );
+            }
+        }
+        for (i, rule) in self.share_targets.iter().enumerate() {
+            if ExitPolicy::localhost().with_rule(rule).is_err() {
+                issues.push(
+                    ConfigIssue::new(format!("share_targets[{}]", i), format!("'{}' is not a target", rule))
+                        .with_hint("use HOST or HOST:PORT, e.g. \"192.168.1.50:8443\""),
+                );
+            }
+        }
+        if self.dial_retry.jitter_percent > 100 {
+            issues.push(ConfigIssue::new(
+                "dial_retry.jitter_percent",
+                format!("{}% would make delays negative", self.dial_retry.jitter_percent),
+            ).with_hint("at most 100"));
+        }
+
+        if self.relay_selection.enabled && self.relay_selection.interval_secs < 30 {
+            issues.push(ConfigIssue::new(
+                "relay_selection.interval_secs",
+                format!("probing relays every {}s leaves no time between rounds", self.relay_selection.interval_secs),
+            ).with_hint("at least 30"));
+        }
+
+        let peer_lists = [
+            ("peer_access.allow", &self.peer_access.allow),
+            ("peer_access.block", &self.peer_access.block),
+            ("viewers", &self.viewers),
+        ];
+        for (name, peers) in peer_lists {
+            for (i, peer) in peers.iter().enumerate() {
+                if peer.parse::<PeerId>().is_err() {
+                    issues.push(
+                        ConfigIssue::new(format!("{}[{}]", name, i), format!("'{}' is not a peer ID", peer))
+                            .with_hint("peer IDs look like 12D3KooW...; see `rift info`"),
+                    );
+                }
+            }
+        }
+
+        let mut policy_ports = HashSet::new();
+        for (i, policy) in self.port_policies.iter().enumerate() {
+            if !policy_ports.insert(policy.port) {
+                issues.push(
+                    ConfigIssue::new(format!("port_policies[{}].port", i), format!("port {} already has rules", policy.port))
+                        .with_hint("merge them into one [[port_policies]] entry"),
+                );
+            }
+            for (j, peer) in policy.allow.iter().enumerate() {
+                if peer.parse::<PeerId>().is_err() {
+                    issues.push(
+                        ConfigIssue::new(format!("port_policies[{}].allow[{}]", i, j), format!("'{}' is not a peer ID", peer))
+                            .with_hint("peer IDs look like 12D3KooW...; see `rift info`"),
+                    );
+                }
+            }
+        }
+
+        // Settings that cancel each other out
+        if !self.relays.is_empty() && !self.enable_relay {
+            issues.push(
+                ConfigIssue::new("relays", "relays are never used while enable_relay = false")
+                    .with_hint("set enable_relay = true or remove the relays"),
+            );
+        }
+        if self.webrtc.enabled && self.webrtc.port != 0 && self.webrtc.port == self.listen_port {
+            issues.push(
+                ConfigIssue::new("webrtc.port", format!("UDP port {} is already taken by listen_port", self.listen_port))
+                    .with_hint("pick another port, or 0 for a random one"),
+            );
+        }
+        if self.is_private_network() {
+            if self.websocket.enabled {
+                issues.push(
+                    ConfigIssue::new("websocket.enabled", "private networks only listen on TCP")
+                        .with_hint("remove network.psk_path or turn WebSocket off"),
+                );
+            }
+            if self.webrtc.enabled {
+                issues.push(
+                    ConfigIssue::new("webrtc.enabled", "private networks only listen on TCP")
+                        .with_hint("remove network.psk_path or turn WebRTC off"),
+                );
+            }
+        }
+        if self.network.psk_path.is_some() && self.swarm_key_path.is_some() {
+            issues.push(
+                ConfigIssue::new("swarm_key_path", "ignored because network.psk_path is set")
+                    .with_hint("remove swarm_key_path"),
+            );
+        }
+        if cfg!(not(feature = "sqlite")) && self.state.backend == StateBackend::Sqlite {
+            issues.push(
+                ConfigIssue::new("state.backend", "this build has no SQLite support")
+                    .with_hint("use \"file\", or a build with the `sqlite` feature"),
+            );
+        }
+
+        issues
+    }
+
+    /// Loads configuration from a file, falling back to defaults if it does not exist
+    pub fn load_or_default(path: &PathBuf) -> Result<Self> {
+        if path.exists() {
+            Self::load(path)
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    /// Applies the hot-reloadable settings of `new` to this configuration
+    ///
+    /// Keys outside [`HOT_RELOADABLE_FIELDS`] are left untouched and reported
+    /// as requiring a restart.
+    pub fn apply_reload(&mut self, new: &RiftConfig) -> Result<ConfigChanges> {
+        let mut current = Self::to_table(self)?;
+        let incoming = Self::to_table(new)?;
+
+        let mut changes = ConfigChanges::default();
+        let mut keys: Vec<&String> = current.keys().chain(incoming.keys()).collect();
+        keys.sort();
+        keys.dedup();
+
+        for key in keys {
+            if current.get(key) == incoming.get(key) {
+                continue;
+            }
+            if HOT_RELOADABLE_FIELDS.contains(&key.as_str()) {
+                changes.applied.push(key.clone());
+            } else {
+                changes.requires_restart.push(key.clone());
+            }
+        }
+
+        for key in &changes.applied {
+            match incoming.get(key) {
+                Some(value) => current.insert(key.clone(), value.clone()),
+                None => current.remove(key),
+            };
+        }
+
+        let mut reloaded: RiftConfig = toml::Value::Table(current)
+            .try_into()
+            .map_err(|e| RiftError::ConfigError(e.to_string()))?;
+        // Runtime-only settings are not part of the file
+        reloaded.ephemeral_identity = self.ephemeral_identity;
+        reloaded.state_store = self.state_store.clone();
+        *self = reloaded;
+
+        Ok(changes)
+    }
+
+    fn to_table(config: &RiftConfig) -> Result<toml::Table> {
+        toml::Table::try_from(config).map_err(|e| RiftError::ConfigError(e.to_string()))
     }
 
     /// Saves configuration to a file
@@ -85,34 +1447,141 @@ impl RiftConfig {
     }
 
     /// Returns the default configuration directory
+    ///
+    /// In order: the directory given to [`Self::override_config_dir`],
+    /// `$RIFT_CONFIG_DIR`, `$XDG_CONFIG_HOME/rift` (on every platform), then
+    /// the platform's config directory (`~/.config/rift` on Linux).
     pub fn default_config_dir() -> PathBuf {
-        dirs::config_dir()
+        if let Some(dir) = CONFIG_DIR_OVERRIDE.get() {
+            return dir.clone();
+        }
+        if let Some(dir) = std::env::var_os(CONFIG_DIR_ENV).filter(|dir| !dir.is_empty()) {
+            return PathBuf::from(dir);
+        }
+        // The XDG spec says to ignore relative paths
+        let xdg = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute());
+        xdg.or_else(dirs::config_dir)
             .unwrap_or_else(|| PathBuf::from("."))
             .join("rift")
     }
 
+    /// Use `dir` as the configuration directory for the rest of the process
+    ///
+    /// Holds for the config file, identity, peer book and state of every
+    /// configuration created afterwards. Fails if another directory was set.
+    pub fn override_config_dir(dir: PathBuf) -> Result<()> {
+        let set = CONFIG_DIR_OVERRIDE.get_or_init(|| dir.clone());
+        if *set != dir {
+            return Err(RiftError::ConfigError(format!(
+                "Configuration directory already set to {}",
+                set.display()
+            )));
+        }
+        Ok(())
+    }
+
     /// Returns the default configuration file path
     pub fn default_config_path() -> PathBuf {
         Self::default_config_dir().join("config.toml")
     }
 
+    /// Directory holding daemon state files (alongside the identity key)
+    pub fn state_dir(&self) -> PathBuf {
+        self.identity_path
+            .parent()
+            .map(PathBuf::from)
+            .unwrap_or_else(Self::default_config_dir)
+    }
+
+    /// Spool for short-lived secret files, inside the state directory
+    pub fn spool(&self) -> Spool {
+        Spool::new(&self.state_dir())
+    }
+
     /// Returns the default identity keypair path
     pub fn default_identity_path() -> PathBuf {
         Self::default_config_dir().join("identity.key")
     }
 
+    /// Protocol IDs under `protocol_namespace`
+    pub fn protocols(&self) -> Result<Protocols> {
+        Protocols::new(&self.protocol_namespace)
+    }
+
+    /// Swarm key path, resolved against the state directory when relative
+    pub fn swarm_key(&self) -> Option<PathBuf> {
+        self.network
+            .psk_path
+            .as_ref()
+            .or(self.swarm_key_path.as_ref())
+            .map(|path| self.state_dir().join(path))
+    }
+
+    /// The state store: one handed in with [`Self::with_state_store`], else the configured backend
+    pub fn open_state_store(&self) -> Result<Arc<dyn StateStore>> {
+        if let Some(store) = &self.state_store {
+            return Ok(store.clone());
+        }
+        match self.state.backend {
+            StateBackend::File => Ok(Arc::new(FileStore::new(self.state_dir()))),
+            #[cfg(feature = "sqlite")]
+            StateBackend::Sqlite => {
+                let path = self.state_dir().join(self.state.path.as_deref().unwrap_or(crate::state::STATE_DB_FILE.as_ref()));
+                Ok(Arc::new(crate::state::SqliteStore::open(&path)?))
+            }
+            #[cfg(not(feature = "sqlite"))]
+            StateBackend::Sqlite => Err(RiftError::ConfigError(
+                "state.backend = \"sqlite\" needs a build with the `sqlite` feature".to_string(),
+            )),
+        }
+    }
+
+    /// Make sure `feature` may contact a service nobody configured (e.g. an update check)
+    ///
+    /// Fails when `no_telemetry` is set.
+    pub fn check_outbound(&self, feature: &str) -> Result<()> {
+        if self.no_telemetry {
+            return Err(RiftError::ConfigError(format!("{} is disabled by no_telemetry", feature)));
+        }
+        Ok(())
+    }
+
+    /// The rules for tunnels to `port`, if any
+    pub fn port_policy(&self, port: u16) -> Option<&PortPolicy> {
+        self.port_policies.iter().find(|policy| policy.port == port)
+    }
+
+    /// Whether this node only talks to holders of a swarm key
+    pub fn is_private_network(&self) -> bool {
+        self.swarm_key().is_some()
+    }
+
     /// Builder pattern: set listen port
     pub fn with_listen_port(mut self, port: u16) -> Self {
         self.listen_port = port;
         self
     }
 
+    /// Builder pattern: listen on an IP, interface or multiaddr instead of every interface
+    pub fn with_listen_addr(mut self, addr: impl Into<String>) -> Self {
+        self.listen_addrs.push(addr.into());
+        self
+    }
+
     /// Builder pattern: enable/disable mDNS
     pub fn with_mdns(mut self, enable: bool) -> Self {
         self.enable_mdns = enable;
         self
     }
 
+    /// Builder pattern: set the protocol namespace
+    pub fn with_protocol_namespace(mut self, namespace: impl Into<String>) -> Self {
+        self.protocol_namespace = namespace.into();
+        self
+    }
+
     /// Builder pattern: enable/disable relay
     pub fn with_relay(mut self, enable: bool) -> Self {
         self.enable_relay = enable;
@@ -136,6 +1605,181 @@ impl RiftConfig {
         self.debug = debug;
         self
     }
+
+    /// Builder pattern: pool connections to these local HTTP ports (none disables pooling)
+    pub fn with_target_pool(mut self, http_ports: Vec<u16>) -> Self {
+        self.target_pool.enabled = !http_ports.is_empty();
+        self.target_pool.http_ports = http_ports;
+        self
+    }
+
+    /// Builder pattern: stripe each connection across `streams` parallel streams
+    pub fn with_striping(mut self, streams: u8) -> Self {
+        self.striping.streams = streams;
+        self
+    }
+
+    /// Builder pattern: cap tunnels to the shared port at `bytes_per_sec` (0 = unlimited)
+    pub fn with_max_rate(mut self, bytes_per_sec: u64, scope: RateLimitScope) -> Self {
+        self.rate_limit = RateLimitConfig {
+            max_bytes_per_sec: bytes_per_sec,
+            scope,
+        };
+        self
+    }
+
+    /// Builder pattern: log HTTP requests made through the tunnel
+    pub fn with_http_log(mut self, enable: bool) -> Self {
+        self.http_log = enable;
+        self
+    }
+
+    /// Builder pattern: choose where the secrets keypair is kept
+    pub fn with_keystore(mut self, keystore: Keystore) -> Self {
+        self.keystore = keystore;
+        self
+    }
+
+    /// Builder pattern: keep state in this store instead of the configured backend
+    pub fn with_state_store(mut self, store: Arc<dyn StateStore>) -> Self {
+        self.state_store = Some(store);
+        self
+    }
+
+    /// Builder pattern: keep the identity and state in `dir`
+    ///
+    /// Lets several nodes run side by side in one process or on one machine.
+    pub fn with_config_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.identity_path = dir.into().join("identity.key");
+        self
+    }
+
+    /// Builder pattern: use a throwaway identity that is never persisted
+    pub fn with_ephemeral_identity(mut self, ephemeral: bool) -> Self {
+        self.ephemeral_identity = ephemeral;
+        self
+    }
+
+    /// Builder pattern: forbid contacting services nobody configured
+    pub fn with_no_telemetry(mut self, no_telemetry: bool) -> Self {
+        self.no_telemetry = no_telemetry;
+        self
+    }
+
+    /// Builder pattern: let peers reach a shared port by name
+    pub fn with_service_name(mut self, name: impl Into<String>, port: u16) -> Self {
+        self.service_names.insert(name.into(), port);
+        self
+    }
+
+    /// Builder pattern: allow `rift share --target` to forward to `HOST` or `HOST:PORT`
+    pub fn with_share_target(mut self, rule: impl Into<String>) -> Self {
+        self.share_targets.push(rule.into());
+        self
+    }
+
+    /// Targets a share may forward to: this machine plus `share_targets`
+    ///
+    /// Invalid entries are left out (and reported by [`validate`](Self::validate)).
+    pub fn share_target_policy(&self) -> ExitPolicy {
+        self.share_targets.iter().fold(ExitPolicy::localhost(), |policy, rule| {
+            policy.clone().with_rule(rule).unwrap_or(policy)
+        })
+    }
+
+    /// Builder pattern: route a TLS server name to a local port
+    pub fn with_sni_route(mut self, server_name: impl Into<String>, port: u16) -> Self {
+        self.sni_routes
+            .insert(server_name.into().to_ascii_lowercase(), port);
+        self
+    }
+}
+
+/// Report `value` unless it is a multiaddr (ending in `/p2p/<peer id>` if `needs_peer`)
+fn check_multiaddr(issues: &mut Vec<ConfigIssue>, field: &str, value: &str, needs_peer: bool) {
+    let issue = match value.parse::<Multiaddr>() {
+        Ok(mut addr) => {
+            if !needs_peer || matches!(addr.pop(), Some(Protocol::P2p(_))) {
+                return;
+            }
+            ConfigIssue::new(field, format!("'{}' does not name the peer", value)).with_hint("end it with /p2p/<peer id>")
+        }
+        Err(_) => {
+            let issue = ConfigIssue::new(field, format!("'{}' is not a multiaddr", value));
+            let peer = if needs_peer { "/p2p/<peer id>" } else { "" };
+            if let Ok(addr) = value.parse::<SocketAddr>() {
+                let ip = if addr.is_ipv4() { "ip4" } else { "ip6" };
+                issue.with_hint(format!("write it as /{}/{}/udp/{}/quic-v1{}", ip, addr.ip(), addr.port(), peer))
+            } else if value.starts_with("rift://") {
+                issue.with_hint("this is a Rift link, not an address")
+            } else if !value.starts_with('/') {
+                issue.with_hint("multiaddrs start with '/', e.g. /dns4/relay.example.com/udp/4001/quic-v1")
+            } else {
+                issue
+            }
+        }
+    };
+    issues.push(issue);
+}
+
+/// Issue for a key the configuration does not have, suggesting the closest known one
+fn unknown_key(path: &serde_ignored::Path) -> ConfigIssue {
+    let mut keys = Vec::new();
+    let mut field = String::new();
+    collect_path(path, &mut keys, &mut field);
+    let issue = ConfigIssue::new(field, "unknown setting");
+
+    let Some((key, parents)) = keys.split_last() else {
+        return issue;
+    };
+    let prefix: String = parents.iter().map(|parent| format!("{}.", parent)).collect();
+    let mut known: Vec<String> = OPTIONAL_KEYS
+        .iter()
+        .filter_map(|optional| optional.strip_prefix(prefix.as_str()))
+        .filter(|rest| !rest.contains('.'))
+        .map(str::to_string)
+        .collect();
+    let mut table = RiftConfig::to_table(&RiftConfig::default()).ok();
+    for parent in parents {
+        table = table.and_then(|table| match table.get(parent) {
+            Some(toml::Value::Table(inner)) => Some(inner.clone()),
+            _ => None,
+        });
+    }
+    known.extend(table.into_iter().flat_map(|table| table.into_iter().map(|(key, _)| key)));
+
+    let closest = known
+        .iter()
+        .map(|candidate| (strsim::damerau_levenshtein(key, candidate), candidate))
+        .filter(|(distance, _)| *distance <= (key.len() / 3).max(2))
+        .min();
+    match closest {
+        Some((_, candidate)) => issue.with_hint(format!("did you mean `{}{}`?", prefix, candidate)),
+        None => issue,
+    }
+}
+
+/// Map keys along `path` and its display form (`a.b[2].c`)
+fn collect_path(path: &serde_ignored::Path, keys: &mut Vec<String>, field: &mut String) {
+    use serde_ignored::Path;
+    match path {
+        Path::Root => {}
+        Path::Seq { parent, index } => {
+            collect_path(parent, keys, field);
+            field.push_str(&format!("[{}]", index));
+        }
+        Path::Map { parent, key } => {
+            collect_path(parent, keys, field);
+            if !field.is_empty() {
+                field.push('.');
+            }
+            field.push_str(key);
+            keys.push(key.clone());
+        }
+        Path::Some { parent } | Path::NewtypeStruct { parent } | Path::NewtypeVariant { parent } => {
+            collect_path(parent, keys, field);
+        }
+    }
 }
 
 #[cfg(test)]
@@ -150,6 +1794,68 @@ mod tests {
         assert!(config.enable_relay);
     }
 
+    #[test]
+    fn test_history_limits_from_partial_toml() {
+        let config: RiftConfig = toml::from_str("[history]\nlog_lines = 500\n").unwrap();
+        assert_eq!(config.history.log_lines, 500);
+        assert_eq!(config.history.traffic_samples, HistoryLimits::default().traffic_samples);
+    }
+
+    #[test]
+    fn test_target_retry_backoff_is_capped() {
+        let retry = TargetRetryConfig::default();
+        assert_eq!(retry.backoff(1), Duration::from_millis(100));
+        assert_eq!(retry.backoff(3), Duration::from_millis(400));
+        assert_eq!(retry.backoff(10), Duration::from_millis(1_000));
+    }
+
+    #[test]
+    fn test_target_pool_covers_http_ports_only() {
+        let config: RiftConfig = toml::from_str("[target_pool]\nenabled = true\nhttp_ports = [3000]\n").unwrap();
+        assert!(config.target_pool.pools(3000));
+        assert!(!config.target_pool.pools(5432));
+
+        let off = RiftConfig::new().with_target_pool(Vec::new());
+        assert!(!off.target_pool.enabled);
+        assert!(!off.target_pool.pools(3000));
+        assert!(RiftConfig::new().with_target_pool(vec![8080]).target_pool.pools(8080));
+    }
+
+    #[test]
+    fn test_psk_path_and_legacy_swarm_key_path() {
+        let config: RiftConfig = toml::from_str("[network]\npsk_path = \"team.key\"\n").unwrap();
+        assert_eq!(config.swarm_key(), Some(config.state_dir().join("team.key")));
+        assert!(config.is_private_network());
+
+        let legacy: RiftConfig = toml::from_str("swarm_key_path = \"/etc/rift/swarm.key\"\n").unwrap();
+        assert_eq!(legacy.swarm_key(), Some(PathBuf::from("/etc/rift/swarm.key")));
+        assert!(!RiftConfig::default().is_private_network());
+    }
+
+    #[test]
+    fn test_dial_backoff_jitter_stays_in_range() {
+        let retry = DialRetryConfig::default();
+        assert_eq!(retry.backoff(2), Duration::from_millis(500));
+        for attempt in 1..=12 {
+            let base = retry.backoff(attempt).as_millis();
+            let delay = retry.jittered_backoff(attempt).as_millis();
+            assert!(delay >= base * 8 / 10 && delay <= base * 12 / 10);
+        }
+
+        let exact = DialRetryConfig { jitter_percent: 0, ..DialRetryConfig::default() };
+        assert_eq!(exact.jittered_backoff(3), Duration::from_millis(1_000));
+    }
+
+    #[test]
+    fn test_no_telemetry_refuses_outbound_features() {
+        let config = RiftConfig::default();
+        assert!(config.check_outbound("update check").is_ok());
+
+        let config: RiftConfig = toml::from_str("no_telemetry = true").unwrap();
+        let err = config.check_outbound("update check").unwrap_err();
+        assert!(err.to_string().contains("update check is disabled by no_telemetry"));
+    }
+
     #[test]
     fn test_builder_pattern() {
         let config = RiftConfig::new()
@@ -160,5 +1866,158 @@ mod tests {
         assert_eq!(config.listen_port, 8080);
         assert!(!config.enable_mdns);
         assert!(config.debug);
+
+        let isolated = RiftConfig::new().with_config_dir("/srv/rift-a");
+        assert_eq!(isolated.identity_path, PathBuf::from("/srv/rift-a/identity.key"));
+        assert_eq!(isolated.state_dir(), PathBuf::from("/srv/rift-a"));
+    }
+
+    #[test]
+    fn test_apply_reload_splits_hot_and_restart_fields() {
+        let mut config = RiftConfig::default();
+        let new = RiftConfig::new()
+            .with_listen_port(9000)
+            .with_sni_route("api.localhost", 3443);
+
+        let changes = config.apply_reload(&new).unwrap();
+
+        assert_eq!(changes.applied, vec!["sni_routes".to_string()]);
+        assert_eq!(changes.requires_restart, vec!["listen_port".to_string()]);
+        assert_eq!(config.sni_routes.get("api.localhost"), Some(&3443));
+        assert_eq!(config.listen_port, 0);
+    }
+
+    #[test]
+    fn test_reload_keeps_command_line_overrides() {
+        let file = RiftConfig::default().with_sni_route("app.localhost", 3443);
+        let mut config = file
+            .clone()
+            .with_sni_route("api.localhost", 8443)
+            .with_max_rate(1024, RateLimitScope::Peer);
+        let overrides = ConfigOverrides::between(&file, &config).unwrap();
+
+        // An unrelated edit of the file
+        let edited = file.clone().with_listen_port(9000);
+        let changes = config.apply_reload(&overrides.apply(&edited).unwrap()).unwrap();
+
+        assert_eq!(changes.applied, Vec::<String>::new());
+        assert_eq!(changes.requires_restart, vec!["listen_port".to_string()]);
+        assert_eq!(config.sni_routes.get("app.localhost"), Some(&3443));
+        assert_eq!(config.sni_routes.get("api.localhost"), Some(&8443));
+        assert_eq!(config.rate_limit.max_bytes_per_sec, 1024);
+    }
+
+    #[test]
+    fn test_parse_reports_every_problem_with_hints() {
+        assert!(RiftConfig::parse("").unwrap().validate().is_empty());
+
+        let err = RiftConfig::parse(
+            r#"
+            enable_relay = false
+            relays = ["203.0.113.7:4001"]
+            bootstrap_peers = ["/dnsaddr/bootstrap.libp2p.io", "rift://12D3KooW"]
+            viewers = ["laptop"]
+            "#,
+        )
+        .unwrap_err()
+        .to_string();
+
+        assert!(err.contains("4 problem(s) found"), "{}", err);
+        assert!(err.contains("relays[0]: '203.0.113.7:4001' is not a multiaddr (write it as /ip4/203.0.113.7/udp/4001/quic-v1/p2p/<peer id>)"));
+        assert!(err.contains("bootstrap_peers[1]: 'rift://12D3KooW' is not a multiaddr (this is a Rift link"));
+        assert!(err.contains("viewers[0]: 'laptop' is not a peer ID"));
+        assert!(err.contains("relays: relays are never used while enable_relay = false"));
+        assert!(!err.contains("bootstrap_peers[0]"));
+    }
+
+    #[test]
+    fn test_unknown_optional_keys_get_suggestions() {
+        let (config, warnings) = RiftConfig::parse_with_warnings(
+            "rendezvous_sever = \"/ip4/1.2.3.4/tcp/1\"\n[dial_retry]\natempts = 3\n",
+        )
+        .unwrap();
+        assert_eq!(config.dial_retry, RiftConfig::default().dial_retry);
+        let warnings: Vec<String> = warnings.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            warnings,
+            vec![
+                "rendezvous_sever: unknown setting (did you mean `rendezvous_server`?)".to_string(),
+                "dial_retry.atempts: unknown setting (did you mean `dial_retry.attempts`?)".to_string(),
+            ]
+        );
+
+        let err = RiftConfig::parse("[remote_control]\nlisten = \"0.0.0.0\"\n").unwrap_err().to_string();
+        assert!(err.contains("remote_control.listen: '0.0.0.0' has no port"), "{}", err);
+    }
+
+    #[test]
+    fn test_sni_routes_from_partial_toml() {
+        let config: RiftConfig = toml::from_str(
+            r#"
+            listen_port = 4001
+
+            [sni_routes]
+            "api.localhost" = 3443
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.listen_port, 4001);
+        assert!(config.enable_mdns);
+        assert_eq!(config.sni_routes.get("api.localhost"), Some(&3443));
+    }
+
+    #[test]
+    fn test_share_targets_allow_listed_hosts_only() {
+        use crate::network::exit::ExitRequest;
+
+        let config = RiftConfig::default()
+            .with_share_target("192.168.1.50:8443")
+            .with_share_target("nas.lan");
+        let policy = config.share_target_policy();
+        let target = |host: &str, port| ExitRequest { host: host.to_string(), port };
+        assert!(policy.allows(&target("192.168.1.50", 8443)));
+        assert!(!policy.allows(&target("192.168.1.50", 22)));
+        assert!(policy.allows(&target("nas.lan", 22)));
+        assert!(policy.allows(&target("localhost", 5432)));
+        assert!(!policy.allows(&target("10.0.0.1", 8443)));
+
+        let err = RiftConfig::parse("share_targets = [\"*\"]\n").unwrap_err().to_string();
+        assert!(err.contains("share_targets[0]: '*' is not a target"), "{}", err);
+    }
+
+    #[test]
+    fn test_port_policies_from_toml() {
+        let peer_id = PeerId::random();
+        let config = RiftConfig::parse(&format!(
+            r#"
+            [[port_policies]]
+            port = 5432
+            approval = "require"
+            allow = ["{}"]
+            max_connections = 2
+
+            [[port_policies]]
+            port = 8080
+            approval = "auto"
+            read_only = true
+            "#,
+            peer_id
+        ))
+        .unwrap();
+
+        let db = config.port_policy(5432).unwrap();
+        assert_eq!(db.approval, PortApproval::Require);
+        assert!(db.allows(&peer_id));
+        assert!(!db.allows(&PeerId::random()));
+        let logs = config.port_policy(8080).unwrap();
+        assert!(logs.read_only && logs.allows(&PeerId::random()));
+        assert!(config.port_policy(3000).is_none());
+
+        let err = RiftConfig::parse("[[port_policies]]\nport = 22\nallow = [\"laptop\"]\n\n[[port_policies]]\nport = 22\n")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("port_policies[0].allow[0]: 'laptop' is not a peer ID"), "{}", err);
+        assert!(err.contains("port_policies[1].port: port 22 already has rules"), "{}", err);
     }
 }
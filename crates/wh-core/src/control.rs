@@ -0,0 +1,116 @@
+//! Session Control Messages
+//!
+//! Small messages exchanged over the `/rift/control/1.0.0` protocol, framed
+//! with the same length prefix as the secrets exchange.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::attestation::SignedAttestation;
+
+/// Messages carried on a control stream
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ControlMessage {
+    /// Host -> connector: resumption token issued after an approval
+    SessionToken {
+        /// Opaque token value
+        token: String,
+        /// Seconds until the token expires
+        expires_in_secs: u64,
+    },
+
+    /// Connector -> host: resume a previously approved session
+    Resume {
+        /// Token received in an earlier `SessionToken`
+        token: String,
+    },
+
+    /// Connector -> host: the ID of the revocable link being used
+    Redeem {
+        /// ID from the `?link=` part of the link
+        link_id: String,
+    },
+
+    /// Either direction: signed identity claims of the sender
+    Attestation {
+        /// The sender's claims and signature
+        attestation: SignedAttestation,
+    },
+
+    /// Connector -> host: ask for a loopback fast path if both run on this machine
+    FastpathProbe {
+        /// The connector's machine fingerprint
+        fingerprint: String,
+    },
+
+    /// Host -> connector: fingerprints match, connect to this local port directly
+    FastpathOffer {
+        /// The shared port
+        port: u16,
+    },
+
+    /// Host -> connector: latest health of the shared service
+    Health {
+        /// Result of the host's last probe
+        health: ServiceHealth,
+    },
+
+    /// Viewer -> host: keep this stream open and stream the session to it (`rift attach`)
+    Attach,
+}
+
+/// Whether the shared service is answering
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    /// Accepting connections
+    Up,
+    /// Accepting connections, but slowly
+    Degraded,
+    /// Not accepting connections
+    Down,
+}
+
+impl fmt::Display for HealthStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            HealthStatus::Up => "up",
+            HealthStatus::Degraded => "degraded",
+            HealthStatus::Down => "down",
+        })
+    }
+}
+
+/// Health of the shared service as seen by the host
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServiceHealth {
+    pub status: HealthStatus,
+    /// Short human-readable detail, e.g. the connect error
+    pub message: String,
+}
+
+impl fmt::Display for ServiceHealth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.status, self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_control_message_framing_roundtrip() {
+        let message = ControlMessage::SessionToken {
+            token: "abc123".to_string(),
+            expires_in_secs: 600,
+        };
+
+        let mut buf = Vec::new();
+        crate::send_secrets(&mut buf, &message).await.unwrap();
+
+        let mut reader = &buf[..];
+        let decoded: ControlMessage = crate::receive_secrets(&mut reader).await.unwrap();
+        assert_eq!(decoded, message);
+    }
+}
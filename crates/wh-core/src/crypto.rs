@@ -1,12 +1,16 @@
 //! Cryptographic utilities for Rift
 //!
 //! Provides X25519 key exchange and AES-GCM encryption for secrets sharing.
+//!
+//! Secrets responses use a fresh ephemeral key per encryption, so a random
+//! nonce never repeats under a key.
 
 use aes_gcm::{
     aead::{Aead, KeyInit},
     Aes256Gcm, Nonce,
 };
 use rand::rngs::OsRng;
+use subtle::ConstantTimeEq;
 use x25519_dalek::{EphemeralSecret, PublicKey, StaticSecret};
 
 use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
@@ -19,6 +23,24 @@ pub const NONCE_SIZE: usize = 12;
 /// Size of the X25519 public key
 pub const PUBLIC_KEY_SIZE: usize = 32;
 
+/// Compare secret bytes (tokens, keys, tags) in constant time
+///
+/// Only the lengths may leak through timing.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.ct_eq(b).into()
+}
+
+/// Reject a shared secret made with a low-order public key
+///
+/// Such keys give an all-zero secret whatever our own key is, so anyone
+/// could read what is encrypted with it.
+fn check_contributory(shared_secret: &[u8; 32]) -> Result<()> {
+    if constant_time_eq(shared_secret, &[0u8; 32]) {
+        return Err(RiftError::InvalidPublicKey("low-order public key".to_string()));
+    }
+    Ok(())
+}
+
 /// Key pair for X25519 key exchange
 #[derive(Clone)]
 pub struct KeyPair {
@@ -103,6 +125,8 @@ pub struct SecretsCipher;
 
 impl SecretsCipher {
     /// Encrypt data using AES-256-GCM with a shared secret
+    ///
+    /// The nonce is random: use this once per key.
     pub fn encrypt(shared_secret: &[u8; 32], plaintext: &[u8]) -> Result<(Vec<u8>, [u8; NONCE_SIZE])> {
         let cipher = Aes256Gcm::new_from_slice(shared_secret)
             .map_err(|e| RiftError::EncryptionFailed(e.to_string()))?;
@@ -149,6 +173,7 @@ pub fn encrypt_for_recipient(
 
     // Derive shared secret
     let shared_secret = ephemeral.complete(recipient_public_key);
+    check_contributory(&shared_secret)?;
 
     // Encrypt with shared secret
     let (ciphertext, nonce) = SecretsCipher::encrypt(&shared_secret, plaintext)?;
@@ -165,6 +190,7 @@ pub fn decrypt_from_sender(
 ) -> Result<Vec<u8>> {
     // Derive shared secret
     let shared_secret = our_keypair.diffie_hellman(sender_ephemeral_public);
+    check_contributory(&shared_secret)?;
 
     // Decrypt
     SecretsCipher::decrypt(&shared_secret, ciphertext, nonce)
@@ -219,4 +245,58 @@ mod tests {
 
         assert_eq!(decrypted, plaintext);
     }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"token", b"token"));
+        assert!(!constant_time_eq(b"token", b"tokem"));
+        assert!(!constant_time_eq(b"token", b"token2"));
+        assert!(constant_time_eq(b"", b""));
+    }
+
+    #[test]
+    fn test_low_order_public_key_rejected() {
+        let recipient = KeyPair::generate();
+        // The identity point: every shared secret with it is zero
+        let low_order = [0u8; 32];
+        assert!(encrypt_for_recipient(&low_order, b"secret").is_err());
+        assert!(decrypt_from_sender(&recipient, &low_order, b"ciphertext", &[0u8; NONCE_SIZE]).is_err());
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn test_roundtrip_any_plaintext(plaintext in proptest::collection::vec(proptest::num::u8::ANY, 0..2048)) {
+            let recipient = KeyPair::generate();
+            let (ephemeral_public, ciphertext, nonce) =
+                encrypt_for_recipient(&recipient.public_key_bytes(), &plaintext).unwrap();
+            // AES-GCM appends a 16-byte tag
+            proptest::prop_assert_eq!(ciphertext.len(), plaintext.len() + 16);
+
+            let ephemeral_public: [u8; 32] = ephemeral_public.try_into().unwrap();
+            let decrypted = decrypt_from_sender(&recipient, &ephemeral_public, &ciphertext, &nonce).unwrap();
+            proptest::prop_assert_eq!(decrypted, plaintext);
+        }
+
+        #[test]
+        fn test_any_tampering_fails(
+            plaintext in proptest::collection::vec(proptest::num::u8::ANY, 0..512),
+            position in proptest::num::usize::ANY,
+            flip in 1u8..=255,
+        ) {
+            let shared_secret = [42u8; 32];
+            let (ciphertext, nonce) = SecretsCipher::encrypt(&shared_secret, &plaintext).unwrap();
+
+            let mut tampered = ciphertext.clone();
+            let index = position % tampered.len();
+            tampered[index] ^= flip;
+            proptest::prop_assert!(SecretsCipher::decrypt(&shared_secret, &tampered, &nonce).is_err());
+
+            let mut wrong_nonce = nonce;
+            wrong_nonce[position % NONCE_SIZE] ^= flip;
+            proptest::prop_assert!(SecretsCipher::decrypt(&shared_secret, &ciphertext, &wrong_nonce).is_err());
+
+            let truncated = &ciphertext[..position % ciphertext.len()];
+            proptest::prop_assert!(SecretsCipher::decrypt(&shared_secret, truncated, &nonce).is_err());
+        }
+    }
 }
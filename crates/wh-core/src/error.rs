@@ -52,6 +52,9 @@ pub enum RiftError {
     #[error("Tunnel not established")]
     TunnelNotEstablished,
 
+    #[error("Tunnel rejected by the host: {0}")]
+    TunnelRejected(String),
+
     #[error("Proxy forwarding error: {0}")]
     ProxyForwarding(String),
 
@@ -70,9 +73,15 @@ pub enum RiftError {
     #[error("Invalid public key: {0}")]
     InvalidPublicKey(String),
 
+    #[error("Signing failed: {0}")]
+    SigningFailed(String),
+
     #[error("Keyring access failed: {0}")]
     KeyringError(String),
 
+    #[error("OS keyring is locked or unavailable ({0}); unlock it or use `--keystore file`")]
+    KeyringLocked(String),
+
     #[error("Failed to parse env file: {0}")]
     EnvParseError(String),
 
@@ -93,6 +102,9 @@ pub enum RiftError {
 
     #[error("Serialization error: {0}")]
     Serialization(String),
+
+    #[error("State store error: {0}")]
+    StateStore(String),
 }
 
 impl From<libp2p::noise::Error> for RiftError {
@@ -0,0 +1,111 @@
+//! Event Fan-out
+//!
+//! Events are delivered both to the single take-once `mpsc` receiver the
+//! UI has always used and to any number of broadcast subscribers, exposed as
+//! [`futures::Stream`]s so consumers can use the usual stream combinators.
+
+use futures::stream::{BoxStream, Stream, StreamExt};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::sync::{broadcast, mpsc};
+use tracing::debug;
+
+/// Events buffered per broadcast subscriber before it starts missing some
+const BROADCAST_CAPACITY: usize = 256;
+
+/// Sends each event to the primary receiver and to every subscriber
+#[derive(Debug)]
+pub struct EventSender<T> {
+    tx: mpsc::Sender<T>,
+    broadcast: broadcast::Sender<T>,
+}
+
+impl<T> Clone for EventSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+            broadcast: self.broadcast.clone(),
+        }
+    }
+}
+
+impl<T: Clone + Send + 'static> EventSender<T> {
+    /// Create a sender and its primary receiver
+    pub fn channel(capacity: usize) -> (Self, mpsc::Receiver<T>) {
+        let (tx, rx) = mpsc::channel(capacity);
+        let (broadcast, _) = broadcast::channel(BROADCAST_CAPACITY);
+        (Self { tx, broadcast }, rx)
+    }
+
+    /// Send an event, waiting for room in the primary channel
+    pub async fn send(&self, event: T) -> Result<(), mpsc::error::SendError<T>> {
+        // No subscribers is not an error
+        let _ = self.broadcast.send(event.clone());
+        self.tx.send(event).await
+    }
+
+    /// Send an event without waiting; dropped for the primary receiver if it is full
+    pub fn try_send(&self, event: T) -> Result<(), mpsc::error::TrySendError<T>> {
+        let _ = self.broadcast.send(event.clone());
+        self.tx.try_send(event)
+    }
+
+    /// Subscribe to all events sent from now on
+    pub fn subscribe(&self) -> EventStream<T> {
+        EventStream::new(self.broadcast.subscribe())
+    }
+}
+
+/// Stream of events from an [`EventSender`]
+///
+/// A subscriber that falls more than 256 events behind skips the oldest ones.
+pub struct EventStream<T> {
+    inner: BoxStream<'static, T>,
+}
+
+impl<T: Clone + Send + 'static> EventStream<T> {
+    fn new(rx: broadcast::Receiver<T>) -> Self {
+        let inner = futures::stream::unfold(rx, |mut rx| async move {
+            loop {
+                match rx.recv().await {
+                    Ok(event) => return Some((event, rx)),
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        debug!("Event subscriber lagged, skipped {} events", missed);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        });
+        Self { inner: inner.boxed() }
+    }
+}
+
+impl<T> Stream for EventStream<T> {
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.inner.poll_next_unpin(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_every_subscriber_sees_every_event() {
+        let (sender, mut rx) = EventSender::channel(8);
+        let first = sender.subscribe();
+        let second = sender.subscribe();
+
+        for i in 0..3 {
+            sender.send(i).await.unwrap();
+        }
+        sender.try_send(3).unwrap();
+        drop(sender);
+
+        assert_eq!(first.collect::<Vec<_>>().await, vec![0, 1, 2, 3]);
+        assert_eq!(second.filter(|i| std::future::ready(i % 2 == 1)).collect::<Vec<_>>().await, vec![1, 3]);
+        assert_eq!(rx.recv().await, Some(0));
+    }
+}
@@ -0,0 +1,161 @@
+//! Content Hashing
+//!
+//! BLAKE3 digests for features that need integrity: transferred files are
+//! checked against the sender's hash, secrets carry a version hash of their
+//! contents, and the audit log chains every record to the one before it, so
+//! editing or removing a record breaks the chain from there on.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::Read;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::error::{Result, RiftError};
+
+/// Size of a digest in bytes
+pub const HASH_SIZE: usize = 32;
+
+/// Context string of chained hashes, so they never equal a plain content hash
+const CHAIN_CONTEXT: &str = "rift 2024 hash chain v1";
+
+/// A BLAKE3 digest, shown and serialized as lowercase hex
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ContentHash([u8; HASH_SIZE]);
+
+impl ContentHash {
+    /// Start of every chain: the "previous" hash of the first record
+    pub const GENESIS: Self = Self([0; HASH_SIZE]);
+
+    /// Digest bytes
+    pub fn as_bytes(&self) -> &[u8; HASH_SIZE] {
+        &self.0
+    }
+
+    /// First 8 hex digits, for logs and prompts
+    pub fn short(&self) -> String {
+        self.to_string()[..8].to_string()
+    }
+}
+
+impl From<blake3::Hash> for ContentHash {
+    fn from(hash: blake3::Hash) -> Self {
+        Self(*hash.as_bytes())
+    }
+}
+
+impl fmt::Display for ContentHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(blake3::Hash::from(self.0).to_hex().as_str())
+    }
+}
+
+impl fmt::Debug for ContentHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ContentHash({})", self)
+    }
+}
+
+impl FromStr for ContentHash {
+    type Err = RiftError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        blake3::Hash::from_hex(s)
+            .map(Self::from)
+            .map_err(|e| RiftError::InvalidMessage(format!("invalid hash '{}': {}", s, e)))
+    }
+}
+
+impl Serialize for ContentHash {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ContentHash {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        hex.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Hash a byte slice
+pub fn hash(data: &[u8]) -> ContentHash {
+    blake3::hash(data).into()
+}
+
+/// Hash a file without reading it into memory
+pub fn hash_file(path: &Path) -> Result<ContentHash> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Hasher::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher.finalize())
+}
+
+/// Hash of a record chained to the hash of the record before it
+///
+/// Start a chain from [`ContentHash::GENESIS`].
+pub fn chain(previous: &ContentHash, record: &[u8]) -> ContentHash {
+    let mut hasher = blake3::Hasher::new_derive_key(CHAIN_CONTEXT);
+    hasher.update(previous.as_bytes());
+    hasher.update(record);
+    hasher.finalize().into()
+}
+
+/// Incremental hashing, for data that arrives in pieces (e.g. a file transfer)
+#[derive(Debug, Clone, Default)]
+pub struct Hasher(blake3::Hasher);
+
+impl Hasher {
+    /// Start a new digest
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed more data
+    pub fn update(&mut self, data: &[u8]) -> &mut Self {
+        self.0.update(data);
+        self
+    }
+
+    /// Digest of everything fed so far
+    pub fn finalize(&self) -> ContentHash {
+        self.0.finalize().into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hashes_round_trip_and_chain() {
+        let whole = hash(b"hello world");
+        let mut hasher = Hasher::new();
+        hasher.update(b"hello ").update(b"world");
+        assert_eq!(hasher.finalize(), whole);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file");
+        std::fs::write(&path, b"hello world").unwrap();
+        assert_eq!(hash_file(&path).unwrap(), whole);
+
+        let hex = whole.to_string();
+        assert_eq!(hex.len(), HASH_SIZE * 2);
+        assert_eq!(hex.parse::<ContentHash>().unwrap(), whole);
+        assert_eq!(serde_json::to_string(&whole).unwrap(), format!("\"{}\"", hex));
+        assert!("not hex".parse::<ContentHash>().is_err());
+
+        // Chained hashes depend on the whole history, and differ from plain ones
+        let first = chain(&ContentHash::GENESIS, b"record");
+        assert_ne!(first, hash(b"record"));
+        assert_ne!(chain(&first, b"next"), chain(&ContentHash::GENESIS, b"next"));
+    }
+}
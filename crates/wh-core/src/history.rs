@@ -0,0 +1,138 @@
+//! Bounded History Buffers
+//!
+//! Fixed-capacity ring buffers for logs, traffic samples and other lists that
+//! would otherwise grow for as long as a share stays up.
+
+use std::collections::VecDeque;
+use std::collections::vec_deque;
+
+/// A ring buffer that drops its oldest entry once full
+#[derive(Debug, Clone)]
+pub struct History<T> {
+    items: VecDeque<T>,
+    capacity: usize,
+}
+
+impl<T> History<T> {
+    /// Create an empty history holding at most `capacity` entries
+    ///
+    /// A capacity of 0 is treated as 1 so the latest entry is always kept.
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            items: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Push an entry, returning the evicted oldest entry if the buffer was full
+    pub fn push(&mut self, item: T) -> Option<T> {
+        let evicted = if self.items.len() >= self.capacity {
+            self.items.pop_front()
+        } else {
+            None
+        };
+        self.items.push_back(item);
+        evicted
+    }
+
+    /// Keep only the entries matching the predicate
+    pub fn retain(&mut self, f: impl FnMut(&T) -> bool) {
+        self.items.retain(f);
+    }
+
+    /// Change the capacity, dropping the oldest entries if it shrinks
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity.max(1);
+        while self.items.len() > self.capacity {
+            self.items.pop_front();
+        }
+    }
+
+    /// Maximum number of entries kept
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of entries currently held
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Check if the history is empty
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Most recent entry
+    pub fn latest(&self) -> Option<&T> {
+        self.items.back()
+    }
+
+    /// Iterate from oldest to newest
+    pub fn iter(&self) -> vec_deque::Iter<'_, T> {
+        self.items.iter()
+    }
+
+    /// Iterate mutably from oldest to newest
+    pub fn iter_mut(&mut self) -> vec_deque::IterMut<'_, T> {
+        self.items.iter_mut()
+    }
+
+    /// Remove all entries
+    pub fn clear(&mut self) {
+        self.items.clear();
+    }
+}
+
+impl<T: Clone> History<T> {
+    /// Create a history pre-filled to capacity with `value`
+    pub fn filled(capacity: usize, value: T) -> Self {
+        let mut history = Self::new(capacity);
+        history.items.extend(std::iter::repeat_n(value, history.capacity));
+        history
+    }
+}
+
+impl<'a, T> IntoIterator for &'a History<T> {
+    type Item = &'a T;
+    type IntoIter = vec_deque::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_evicts_oldest() {
+        let mut history = History::new(3);
+        assert_eq!(history.push(1), None);
+        history.push(2);
+        history.push(3);
+        assert_eq!(history.push(4), Some(1));
+
+        assert_eq!(history.iter().copied().collect::<Vec<_>>(), vec![2, 3, 4]);
+        assert_eq!(history.latest(), Some(&4));
+    }
+
+    #[test]
+    fn test_filled_and_shrink() {
+        let mut history = History::filled(4, 0u64);
+        assert_eq!(history.len(), 4);
+
+        history.push(7);
+        history.set_capacity(2);
+        assert_eq!(history.iter().copied().collect::<Vec<_>>(), vec![0, 7]);
+
+        // Zero capacity still keeps the newest entry
+        let mut tiny = History::new(0);
+        tiny.push("a");
+        tiny.push("b");
+        assert_eq!(tiny.latest(), Some(&"b"));
+        assert_eq!(tiny.len(), 1);
+    }
+}
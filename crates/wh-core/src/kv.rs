@@ -0,0 +1,233 @@
+//! Replicated Key-Value Store
+//!
+//! A small last-writer-wins map kept in sync between paired peers over
+//! `/rift/kv/1.0.0`, for shared app state such as a feature-flag toggle during
+//! a pairing session. Deletes are kept as tombstones so they replicate too.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::{Result, RiftError};
+
+/// Longest key accepted, in bytes
+pub const MAX_KV_KEY_BYTES: usize = 256;
+
+/// Largest value accepted, in bytes (a full store must fit one message)
+pub const MAX_KV_VALUE_BYTES: usize = 4 * 1024;
+
+/// Most keys (including tombstones) kept in a store
+pub const MAX_KV_ENTRIES: usize = 1024;
+
+/// One versioned key, or the tombstone of a deleted one
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KvEntry {
+    pub key: String,
+    /// Current value (None = deleted)
+    pub value: Option<Vec<u8>>,
+    /// Unix time of the write in milliseconds
+    pub timestamp_ms: u64,
+    /// Peer ID of the writer, breaking timestamp ties
+    pub origin: String,
+}
+
+impl KvEntry {
+    /// Whether this write wins over `other` under last-writer-wins
+    pub fn supersedes(&self, other: &KvEntry) -> bool {
+        (self.timestamp_ms, &self.origin) > (other.timestamp_ms, &other.origin)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.key.is_empty() || self.key.len() > MAX_KV_KEY_BYTES {
+            return Err(RiftError::InvalidMessage(format!(
+                "key must be 1 to {} bytes",
+                MAX_KV_KEY_BYTES
+            )));
+        }
+        if self.value.as_ref().is_some_and(|value| value.len() > MAX_KV_VALUE_BYTES) {
+            return Err(RiftError::InvalidMessage(format!(
+                "value of '{}' exceeds {} bytes",
+                self.key, MAX_KV_VALUE_BYTES
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Messages carried on a KV stream, framed like the secrets exchange
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KvMessage {
+    /// The sender's full state; the receiver answers with an `Update` holding its own
+    Sync { entries: Vec<KvEntry> },
+
+    /// Entries that changed on the sender; no answer
+    Update { entries: Vec<KvEntry> },
+}
+
+/// A change applied to a store, for notifications
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KvChange {
+    pub key: String,
+    /// New value (None = deleted)
+    pub value: Option<Vec<u8>>,
+    /// Peer ID of the writer
+    pub origin: String,
+}
+
+impl From<&KvEntry> for KvChange {
+    fn from(entry: &KvEntry) -> Self {
+        Self {
+            key: entry.key.clone(),
+            value: entry.value.clone(),
+            origin: entry.origin.clone(),
+        }
+    }
+}
+
+/// Last-writer-wins map replicated between peers
+#[derive(Debug, Clone)]
+pub struct KvStore {
+    /// Our peer ID, stamped on local writes
+    origin: String,
+    entries: HashMap<String, KvEntry>,
+}
+
+impl KvStore {
+    /// Create an empty store writing as `origin`
+    pub fn new(origin: impl Into<String>) -> Self {
+        Self {
+            origin: origin.into(),
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Current value of a key
+    pub fn get(&self, key: &str) -> Option<&[u8]> {
+        self.entries.get(key).and_then(|entry| entry.value.as_deref())
+    }
+
+    /// Live keys and values, sorted by key
+    pub fn values(&self) -> Vec<(String, Vec<u8>)> {
+        let mut values: Vec<_> = self
+            .entries
+            .values()
+            .filter_map(|entry| Some((entry.key.clone(), entry.value.clone()?)))
+            .collect();
+        values.sort();
+        values
+    }
+
+    /// Every entry including tombstones, to send in a `Sync`
+    pub fn entries(&self) -> Vec<KvEntry> {
+        self.entries.values().cloned().collect()
+    }
+
+    /// Set a key locally; returns the entry to replicate
+    pub fn set(&mut self, key: impl Into<String>, value: Vec<u8>) -> Result<KvEntry> {
+        self.write(key.into(), Some(value))
+    }
+
+    /// Delete a key locally; returns the tombstone to replicate, or None if it was not set
+    pub fn delete(&mut self, key: &str) -> Result<Option<KvEntry>> {
+        if self.get(key).is_none() {
+            return Ok(None);
+        }
+        self.write(key.to_string(), None).map(Some)
+    }
+
+    /// Merge an entry from a peer; returns the change if it won
+    pub fn apply(&mut self, entry: KvEntry) -> Option<KvChange> {
+        if entry.validate().is_err() {
+            return None;
+        }
+        match self.entries.get(&entry.key) {
+            Some(current) if !entry.supersedes(current) => return None,
+            None if self.entries.len() >= MAX_KV_ENTRIES => return None,
+            _ => {}
+        }
+        let change = KvChange::from(&entry);
+        self.entries.insert(entry.key.clone(), entry);
+        Some(change)
+    }
+
+    fn write(&mut self, key: String, value: Option<Vec<u8>>) -> Result<KvEntry> {
+        // Stay ahead of the current version even if our clock is behind its writer's
+        let timestamp_ms = match self.entries.get(&key) {
+            Some(current) => unix_millis().max(current.timestamp_ms + 1),
+            None if self.entries.len() >= MAX_KV_ENTRIES => {
+                return Err(RiftError::InvalidMessage(format!(
+                    "store is full ({} keys)",
+                    MAX_KV_ENTRIES
+                )));
+            }
+            None => unix_millis(),
+        };
+        let entry = KvEntry {
+            key,
+            value,
+            timestamp_ms,
+            origin: self.origin.clone(),
+        };
+        entry.validate()?;
+        self.entries.insert(entry.key.clone(), entry.clone());
+        Ok(entry)
+    }
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_last_writer_wins_and_tombstones_replicate() {
+        let mut alice = KvStore::new("alice");
+        let mut bob = KvStore::new("bob");
+
+        let flag = alice.set("feature.dark_mode", b"on".to_vec()).unwrap();
+        let change = bob.apply(flag.clone()).unwrap();
+        assert_eq!(change.origin, "alice");
+        assert_eq!(bob.get("feature.dark_mode"), Some(&b"on"[..]));
+        assert!(bob.apply(flag).is_none(), "replays are ignored");
+
+        // Bob's later write wins on both sides, even against an older one arriving late
+        let older = KvEntry {
+            key: "feature.dark_mode".to_string(),
+            value: Some(b"stale".to_vec()),
+            timestamp_ms: 1,
+            origin: "alice".to_string(),
+        };
+        let newer = bob.set("feature.dark_mode", b"off".to_vec()).unwrap();
+        assert!(alice.apply(newer).is_some());
+        assert!(alice.apply(older).is_none());
+        assert_eq!(alice.get("feature.dark_mode"), Some(&b"off"[..]));
+
+        let tombstone = alice.delete("feature.dark_mode").unwrap().unwrap();
+        assert!(bob.apply(tombstone).unwrap().value.is_none());
+        assert!(bob.values().is_empty());
+        assert_eq!(bob.entries().len(), 1);
+        assert!(bob.delete("feature.dark_mode").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_rejects_oversized_entries() {
+        let mut store = KvStore::new("alice");
+        assert!(store.set("", b"x".to_vec()).is_err());
+        assert!(store.set("big", vec![0; MAX_KV_VALUE_BYTES + 1]).is_err());
+
+        let remote = KvEntry {
+            key: "k".repeat(MAX_KV_KEY_BYTES + 1),
+            value: Some(Vec::new()),
+            timestamp_ms: 1,
+            origin: "bob".to_string(),
+        };
+        assert!(store.apply(remote).is_none());
+        assert!(store.entries().is_empty());
+    }
+}
@@ -7,22 +7,47 @@
 //! - TCP proxy tunneling over QUIC streams
 //! - EnvVault secrets management
 
+pub mod attestation;
 pub mod brand;
 pub mod config;
+pub mod control;
 pub mod crypto;
 pub mod error;
+pub mod events;
+pub mod hash;
+pub mod history;
+pub mod kv;
 pub mod network;
+pub mod protocol;
 pub mod secrets;
+pub mod spool;
+pub mod state;
+pub mod stats;
+pub mod term;
 
-pub use config::RiftConfig;
+pub use config::{
+    AutoReconnectConfig, ConfigChanges, ConfigIssue, ConfigOverrides, DialRetryConfig, HealthConfig, HistoryLimits, LinksConfig, MiddlewareConfig, MigrationConfig, NetworkConfig, PortApproval, PortPolicy, PreflightAction, PreflightConfig, RateLimitConfig, RateLimitScope, RelaySelectionConfig, RelayServerConfig, RemoteControlConfig, ResourceLimitsConfig, RiftConfig,
+    SocketOptionsConfig, StripingConfig, TargetPoolConfig, TargetRetryConfig, WakeRecoveryConfig, WebRtcConfig, WebSocketConfig,
+};
 pub use error::{RiftError, Result};
+pub use events::{EventSender, EventStream};
+pub use hash::ContentHash;
+pub use history::History;
 pub use network::{
-    NetworkEvent, PeerNetwork, PeerIdentity,
-    bridge_stream_to_tcp, open_tunnel_stream,
+    ConnectionDirection, ConnectionPath, NatReport, NatType, NetworkEvent, PeerCapabilities, PeerNetwork, PeerInfo, PeerRtt, PeerAccess, PeerAccessDecision, PeerIdentity, ProtocolPolicy, RelayServer, RelayServerEvent, RiftProtocol,
+    TransportConfig,
+    answer_tunnel_request, bridge_stream_to_tcp, open_tunnel_stream, open_tunnel_stream_with, open_stripe_streams, read_tunnel_request,
     send_secrets, receive_secrets,
-    SECRETS_PROTOCOL, TUNNEL_PROTOCOL,
+    Protocols, SECRETS_PROTOCOL, TUNNEL_PROTOCOL, CONTROL_PROTOCOL, STRIPE_PROTOCOL, ECHO_PROTOCOL, KV_PROTOCOL, TERM_PROTOCOL, EXIT_PROTOCOL, SERVICES_PROTOCOL,
+    echo_round_trip, open_exit_stream, ExitPolicy, ExitRequest, ExitResponse, KnownRelays, KNOWN_RELAYS_FILE, RelayAvailability, RelayProbeReport, RELAY_PROBES_FILE, PeerBook, PEER_BOOK_FILE,
+    generate_swarm_key, load_swarm_key, save_swarm_key, PreSharedKey, SWARM_KEY_FILE,
+    SharedService, query_services, resolve_link,
 };
-pub use secrets::EnvVault;
+pub use secrets::{DeliveredSecret, EnvVault, Keystore, ReceivedSecrets, Redaction};
+pub use spool::{Spool, SpoolFile};
+pub use state::{FileStore, StateBackend, StateConfig, StateStore};
+#[cfg(feature = "sqlite")]
+pub use state::SqliteStore;
 
 // Re-export libp2p types we expose
 pub use libp2p::{PeerId, Multiaddr, Stream};
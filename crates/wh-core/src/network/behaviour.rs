@@ -5,24 +5,141 @@
 //! - Ping: Connection liveness  
 //! - mDNS: Local network discovery
 //! - Relay/DCUtR: NAT traversal
-//! - Stream: Raw bidirectional streams for tunnel data
+//! - AutoNAT: Reachability probes for NAT type detection
+//! - Rendezvous: Peer discovery through a rendezvous server
+//! - Gossipsub: Presence announcements of shared services
+//! - Stream: Raw bidirectional streams for tunnel data, direct connections first
 
 use libp2p::{
+    autonat,
     dcutr,
+    gossipsub,
     identify,
     mdns,
     ping,
     relay,
-    swarm::NetworkBehaviour,
+    rendezvous,
+    swarm::{behaviour::toggle::Toggle, NetworkBehaviour},
     StreamProtocol,
 };
 
+use super::direct_first::DirectFirst;
+use crate::brand::PROTOCOL_NAMESPACE;
+use crate::error::RiftError;
+
 /// The protocol identifier for Rift tunnel streams
-pub const TUNNEL_PROTOCOL: StreamProtocol = StreamProtocol::new("/rift/tunnel/1.0.0");
+///
+/// 1.1.0 starts each stream with a `TunnelRequest` naming the port, and 1.2.0
+/// answers it with a `TunnelResponse`; older peers cannot negotiate it.
+pub const TUNNEL_PROTOCOL: StreamProtocol = StreamProtocol::new("/rift/tunnel/1.2.0");
 
 /// The protocol identifier for Rift secrets exchange
 pub const SECRETS_PROTOCOL: StreamProtocol = StreamProtocol::new("/rift/secrets/1.0.0");
 
+/// The protocol identifier for Rift session control messages
+pub const CONTROL_PROTOCOL: StreamProtocol = StreamProtocol::new("/rift/control/1.0.0");
+
+/// The protocol identifier for striped tunnel streams
+///
+/// 1.1.0 stripe headers carry the shared port the transfer is for.
+pub const STRIPE_PROTOCOL: StreamProtocol = StreamProtocol::new("/rift/stripe/1.1.0");
+
+/// The protocol identifier for end-to-end echo checks (`rift ping`)
+pub const ECHO_PROTOCOL: StreamProtocol = StreamProtocol::new("/rift/echo/1.0.0");
+
+/// The protocol identifier for replicated key-value state
+pub const KV_PROTOCOL: StreamProtocol = StreamProtocol::new("/rift/kv/1.0.0");
+
+/// The protocol identifier for shared terminals (`rift term`)
+pub const TERM_PROTOCOL: StreamProtocol = StreamProtocol::new("/rift/term/1.0.0");
+
+/// The protocol identifier for exit mode (`rift exit`)
+pub const EXIT_PROTOCOL: StreamProtocol = StreamProtocol::new("/rift/exit/1.0.0");
+
+/// The protocol identifier for asking a node what it shares (`rift connect --list`)
+pub const SERVICES_PROTOCOL: StreamProtocol = StreamProtocol::new("/rift/services/1.0.0");
+
+/// Protocol IDs of one deployment, `/<namespace>/<name>/1.0.0`
+///
+/// The constants above are those of the default `rift` namespace. Nodes in
+/// different namespaces cannot open streams to each other, do not see each
+/// other's presence announcements and hang up on each other after identify.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Protocols {
+    namespace: String,
+    pub tunnel: StreamProtocol,
+    pub secrets: StreamProtocol,
+    pub control: StreamProtocol,
+    pub stripe: StreamProtocol,
+    pub echo: StreamProtocol,
+    pub kv: StreamProtocol,
+    pub term: StreamProtocol,
+    pub exit: StreamProtocol,
+    pub services: StreamProtocol,
+    /// Identify protocol version
+    pub identify: String,
+    /// Gossipsub topic of presence announcements
+    pub presence: String,
+}
+
+impl Protocols {
+    /// Protocol IDs under `namespace` (lowercase letters, digits, `-`, `_` and `.`)
+    pub fn new(namespace: &str) -> crate::error::Result<Self> {
+        let valid = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '-' | '_' | '.');
+        if namespace.is_empty() || !namespace.chars().all(valid) {
+            return Err(RiftError::ConfigError(format!(
+                "invalid protocol_namespace '{}': use lowercase letters, digits, '-', '_' and '.'",
+                namespace
+            )));
+        }
+        let id = |name: &str| format!("/{}/{}/1.0.0", namespace, name);
+        let stream = |name: &str| {
+            StreamProtocol::try_from_owned(id(name)).expect("protocol ID starts with a slash")
+        };
+        // Tunnel and stripe streams changed format since 1.0.0 (see TUNNEL_PROTOCOL)
+        let versioned = |name: &str, version: &str| {
+            StreamProtocol::try_from_owned(format!("/{}/{}/{}", namespace, name, version))
+                .expect("protocol ID starts with a slash")
+        };
+        Ok(Self {
+            namespace: namespace.to_string(),
+            tunnel: versioned("tunnel", "1.2.0"),
+            secrets: stream("secrets"),
+            control: stream("control"),
+            stripe: versioned("stripe", "1.1.0"),
+            echo: stream("echo"),
+            kv: stream("kv"),
+            term: stream("term"),
+            exit: stream("exit"),
+            services: stream("services"),
+            identify: id("id"),
+            presence: id("presence"),
+        })
+    }
+
+    /// The namespace these IDs are under
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    /// Whether an identify protocol version is that of a Rift node in another namespace
+    ///
+    /// Anything not shaped like `/<namespace>/id/1.0.0` (relays, bootstrap
+    /// nodes of other software) is not considered foreign.
+    pub fn is_foreign_identify(&self, protocol_version: &str) -> bool {
+        match protocol_version.strip_prefix('/').and_then(|rest| rest.strip_suffix("/id/1.0.0")) {
+            Some(namespace) => !namespace.contains('/') && namespace != self.namespace,
+            None => false,
+        }
+    }
+}
+
+impl Default for Protocols {
+    fn default() -> Self {
+        Self::new(PROTOCOL_NAMESPACE).expect("default namespace is valid")
+    }
+}
+
 /// Combined network behaviour for Rift
 #[derive(NetworkBehaviour)]
 #[behaviour(to_swarm = "RiftBehaviourEvent")]
@@ -31,24 +148,34 @@ pub struct RiftBehaviour {
     pub identify: identify::Behaviour,
     /// Ping protocol for connection liveness
     pub ping: ping::Behaviour,
-    /// mDNS for local network discovery
-    pub mdns: mdns::tokio::Behaviour,
-    /// Relay client for NAT traversal
-    pub relay: relay::client::Behaviour,
+    /// mDNS for local network discovery (off with `enable_mdns = false`)
+    pub mdns: Toggle<mdns::tokio::Behaviour>,
+    /// Relay client for NAT traversal (off with `enable_relay = false`)
+    pub relay: Toggle<relay::client::Behaviour>,
     /// DCUtR for hole punching
     pub dcutr: dcutr::Behaviour,
-    /// Stream behaviour for raw tunnel streams
-    pub stream: libp2p_stream::Behaviour,
+    /// AutoNAT dial-back probes (and answering those of peers)
+    pub autonat: autonat::Behaviour,
+    /// Rendezvous client for discovery beyond the LAN
+    pub rendezvous: rendezvous::client::Behaviour,
+    /// Gossipsub for presence announcements
+    pub gossipsub: gossipsub::Behaviour,
+    /// Stream behaviour for raw tunnel streams, preferring direct connections
+    pub stream: DirectFirst,
 }
 
 /// Events emitted by the Rift behaviour
 #[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
 pub enum RiftBehaviourEvent {
     Identify(identify::Event),
     Ping(ping::Event),
     Mdns(mdns::Event),
     Relay(relay::client::Event),
     Dcutr(dcutr::Event),
+    Autonat(autonat::Event),
+    Rendezvous(rendezvous::client::Event),
+    Gossipsub(gossipsub::Event),
     #[allow(dead_code)]
     Stream(()),
 }
@@ -83,8 +210,51 @@ impl From<dcutr::Event> for RiftBehaviourEvent {
     }
 }
 
+impl From<autonat::Event> for RiftBehaviourEvent {
+    fn from(event: autonat::Event) -> Self {
+        RiftBehaviourEvent::Autonat(event)
+    }
+}
+
+impl From<rendezvous::client::Event> for RiftBehaviourEvent {
+    fn from(event: rendezvous::client::Event) -> Self {
+        RiftBehaviourEvent::Rendezvous(event)
+    }
+}
+
+impl From<gossipsub::Event> for RiftBehaviourEvent {
+    fn from(event: gossipsub::Event) -> Self {
+        RiftBehaviourEvent::Gossipsub(event)
+    }
+}
+
 impl From<()> for RiftBehaviourEvent {
     fn from(_: ()) -> Self {
         RiftBehaviourEvent::Stream(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_protocols_follow_namespace() {
+        let default = Protocols::default();
+        assert_eq!(default.tunnel, TUNNEL_PROTOCOL);
+        assert_eq!(default.exit, EXIT_PROTOCOL);
+        assert_eq!(default.services, SERVICES_PROTOCOL);
+        assert_eq!(default.identify, "/rift/id/1.0.0");
+
+        let staging = Protocols::new("rift-staging").unwrap();
+        assert_eq!(staging.tunnel.as_ref(), "/rift-staging/tunnel/1.2.0");
+        assert_eq!(staging.presence, "/rift-staging/presence/1.0.0");
+        assert!(staging.is_foreign_identify("/rift/id/1.0.0"));
+        assert!(!staging.is_foreign_identify("/rift-staging/id/1.0.0"));
+        assert!(!staging.is_foreign_identify("ipfs/0.1.0"));
+
+        assert!(Protocols::new("").is_err());
+        assert!(Protocols::new("a/b").is_err());
+        assert!(Protocols::new("Prod").is_err());
+    }
+}
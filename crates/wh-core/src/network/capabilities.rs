@@ -0,0 +1,124 @@
+//! Peer Capabilities
+//!
+//! Nodes announce which optional features they serve in their identify agent
+//! version (`rift/0.3.0 caps=secrets,http proto=1.2.0`), so a peer knows what
+//! will work before it opens a tunnel. Peers from before the flags existed
+//! announce none of it and are reported as unknown rather than as capable of
+//! nothing.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use super::behaviour::TUNNEL_PROTOCOL;
+use super::policy::RiftProtocol;
+use crate::config::RiftConfig;
+
+/// Prefix of the capability flags (`caps=secrets,files`) in the agent version
+const CAPS_AGENT_PREFIX: &str = "caps=";
+
+/// Prefix of the highest tunnel protocol version (`proto=1.2.0`) in the agent version
+const PROTO_AGENT_PREFIX: &str = "proto=";
+
+/// Optional features a peer serves
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerCapabilities {
+    /// Answers secrets requests (`secrets` is on its protocol allowlist)
+    pub secrets: bool,
+    /// Accepts file transfers (`file` is on its protocol allowlist)
+    pub file_transfer: bool,
+    /// Logs HTTP requests through its tunnels (`http_log`)
+    pub http: bool,
+    /// Compresses tunnel traffic
+    pub compression: bool,
+    /// Highest tunnel protocol version it speaks (`1.2.0`)
+    pub max_protocol: String,
+}
+
+impl PeerCapabilities {
+    /// What a node running with `config` announces
+    pub fn for_config(config: &RiftConfig) -> Self {
+        let allowed = &config.protocol_policy.allow;
+        Self {
+            secrets: allowed.contains(&RiftProtocol::Secrets),
+            file_transfer: allowed.contains(&RiftProtocol::File),
+            http: config.http_log,
+            // No release compresses tunnel traffic yet; newer peers may announce it
+            compression: false,
+            max_protocol: TUNNEL_PROTOCOL.as_ref().rsplit('/').next().unwrap_or_default().to_string(),
+        }
+    }
+
+    /// The capabilities a peer announces in its agent version; None for peers that announce none
+    pub fn from_agent_version(agent_version: &str) -> Option<Self> {
+        let mut parts = agent_version.split(' ');
+        let max_protocol = parts.clone().find_map(|part| part.strip_prefix(PROTO_AGENT_PREFIX))?;
+        let flags: Vec<&str> = parts
+            .find_map(|part| part.strip_prefix(CAPS_AGENT_PREFIX))
+            .map(|list| list.split(',').collect())
+            .unwrap_or_default();
+        Some(Self {
+            secrets: flags.contains(&"secrets"),
+            file_transfer: flags.contains(&"files"),
+            http: flags.contains(&"http"),
+            compression: flags.contains(&"compression"),
+            max_protocol: max_protocol.to_string(),
+        })
+    }
+
+    /// Names of the features served, as announced
+    pub fn flags(&self) -> Vec<&'static str> {
+        [
+            (self.secrets, "secrets"),
+            (self.file_transfer, "files"),
+            (self.http, "http"),
+            (self.compression, "compression"),
+        ]
+        .into_iter()
+        .filter_map(|(on, name)| on.then_some(name))
+        .collect()
+    }
+
+    /// Tokens appended to the agent version
+    pub fn agent_tokens(&self) -> String {
+        let flags = self.flags();
+        if flags.is_empty() {
+            format!("{}{}", PROTO_AGENT_PREFIX, self.max_protocol)
+        } else {
+            format!("{}{} {}{}", CAPS_AGENT_PREFIX, flags.join(","), PROTO_AGENT_PREFIX, self.max_protocol)
+        }
+    }
+}
+
+impl fmt::Display for PeerCapabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flags = self.flags();
+        if flags.is_empty() {
+            write!(f, "no optional features, tunnel protocol {}", self.max_protocol)
+        } else {
+            write!(f, "{}, tunnel protocol {}", flags.join(", "), self.max_protocol)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capabilities_round_trip_through_agent_version() {
+        let config = RiftConfig::default().with_http_log(true);
+        let capabilities = PeerCapabilities::for_config(&config);
+        assert!(capabilities.secrets && capabilities.http);
+        assert!(!capabilities.file_transfer && !capabilities.compression);
+        assert_eq!(capabilities.max_protocol, "1.2.0");
+
+        let agent = format!("rift/0.3.0 {} (ephemeral)", capabilities.agent_tokens());
+        assert_eq!(agent, "rift/0.3.0 caps=secrets,http proto=1.2.0 (ephemeral)");
+        assert_eq!(PeerCapabilities::from_agent_version(&agent), Some(capabilities));
+        assert_eq!(
+            PeerCapabilities::from_agent_version("rift proto=1.3.0"),
+            Some(PeerCapabilities { max_protocol: "1.3.0".to_string(), ..Default::default() })
+        );
+        assert_eq!(PeerCapabilities::from_agent_version("rift/0.1.0 services=db:5432"), None);
+    }
+}
@@ -0,0 +1,229 @@
+//! Direct-First Streams
+//!
+//! libp2p-stream opens each new stream on a random connection to the peer.
+//! After a hole punch a peer has a direct connection next to the relayed one,
+//! and half of the new tunnels would keep crossing the relay. [`DirectFirst`]
+//! wraps the stream behaviour and hides relayed connections from it while a
+//! direct connection to the same peer is open, so new streams take the direct
+//! path. Streams already running on a relayed connection are left alone; the
+//! connection closes by itself once they are done. When the last direct
+//! connection goes away, the relayed ones are offered for new streams again.
+
+use libp2p::{
+    core::{transport::PortUse, ConnectedPoint, Endpoint},
+    swarm::{
+        behaviour::ConnectionEstablished, ConnectionClosed, ConnectionDenied, ConnectionId, FromSwarm,
+        NetworkBehaviour, THandler, THandlerInEvent, THandlerOutEvent, ToSwarm,
+    },
+    Multiaddr, PeerId,
+};
+use std::collections::{HashMap, HashSet};
+use std::task::{Context, Poll};
+
+/// Stream behaviour that opens new streams on direct connections when there are any
+pub struct DirectFirst {
+    inner: libp2p_stream::Behaviour,
+    /// Peer and endpoint of every open connection
+    connections: HashMap<ConnectionId, (PeerId, ConnectedPoint)>,
+    /// Relayed connections the stream behaviour was told are closed
+    hidden: HashSet<ConnectionId>,
+}
+
+impl Default for DirectFirst {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DirectFirst {
+    pub fn new() -> Self {
+        Self {
+            inner: libp2p_stream::Behaviour::new(),
+            connections: HashMap::new(),
+            hidden: HashSet::new(),
+        }
+    }
+
+    /// Get a control handle for opening and accepting streams
+    pub fn new_control(&self) -> libp2p_stream::Control {
+        self.inner.new_control()
+    }
+
+    fn has_direct(&self, peer_id: PeerId) -> bool {
+        self.connections
+            .values()
+            .any(|(peer, endpoint)| *peer == peer_id && !endpoint.is_relayed())
+    }
+
+    /// Take the peer's relayed connections out of the stream behaviour's choice
+    fn hide_relayed(&mut self, peer_id: PeerId) {
+        for (connection_id, (peer, endpoint)) in &self.connections {
+            if *peer != peer_id || !endpoint.is_relayed() || !self.hidden.insert(*connection_id) {
+                continue;
+            }
+            self.inner.on_swarm_event(FromSwarm::ConnectionClosed(ConnectionClosed {
+                peer_id,
+                connection_id: *connection_id,
+                endpoint,
+                cause: None,
+                remaining_established: 0,
+            }));
+        }
+    }
+
+    /// Offer the peer's hidden relayed connections to the stream behaviour again
+    fn expose_relayed(&mut self, peer_id: PeerId) {
+        for (connection_id, (peer, endpoint)) in &self.connections {
+            if *peer != peer_id || !self.hidden.remove(connection_id) {
+                continue;
+            }
+            self.inner.on_swarm_event(FromSwarm::ConnectionEstablished(ConnectionEstablished {
+                peer_id,
+                connection_id: *connection_id,
+                endpoint,
+                failed_addresses: &[],
+                other_established: 0,
+            }));
+        }
+    }
+}
+
+impl NetworkBehaviour for DirectFirst {
+    type ConnectionHandler = THandler<libp2p_stream::Behaviour>;
+    type ToSwarm = ();
+
+    fn handle_established_inbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        peer: PeerId,
+        local_addr: &Multiaddr,
+        remote_addr: &Multiaddr,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        self.inner
+            .handle_established_inbound_connection(connection_id, peer, local_addr, remote_addr)
+    }
+
+    fn handle_established_outbound_connection(
+        &mut self,
+        connection_id: ConnectionId,
+        peer: PeerId,
+        addr: &Multiaddr,
+        role_override: Endpoint,
+        port_use: PortUse,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        self.inner
+            .handle_established_outbound_connection(connection_id, peer, addr, role_override, port_use)
+    }
+
+    fn on_swarm_event(&mut self, event: FromSwarm) {
+        match event {
+            FromSwarm::ConnectionEstablished(established) => {
+                let peer_id = established.peer_id;
+                let relayed = established.endpoint.is_relayed();
+                let direct_open = self.has_direct(peer_id);
+                self.connections
+                    .insert(established.connection_id, (peer_id, established.endpoint.clone()));
+                if relayed && direct_open {
+                    self.hidden.insert(established.connection_id);
+                    return;
+                }
+                self.inner.on_swarm_event(event);
+                if !relayed {
+                    self.hide_relayed(peer_id);
+                }
+            }
+            FromSwarm::ConnectionClosed(closed) => {
+                self.connections.remove(&closed.connection_id);
+                if self.hidden.remove(&closed.connection_id) {
+                    return;
+                }
+                self.inner.on_swarm_event(event);
+                if !closed.endpoint.is_relayed() && !self.has_direct(closed.peer_id) {
+                    self.expose_relayed(closed.peer_id);
+                }
+            }
+            FromSwarm::AddressChange(change) => {
+                if let Some((_, endpoint)) = self.connections.get_mut(&change.connection_id) {
+                    *endpoint = change.new.clone();
+                }
+                self.inner.on_swarm_event(event);
+            }
+            _ => self.inner.on_swarm_event(event),
+        }
+    }
+
+    fn on_connection_handler_event(
+        &mut self,
+        peer_id: PeerId,
+        connection_id: ConnectionId,
+        event: THandlerOutEvent<Self>,
+    ) {
+        self.inner.on_connection_handler_event(peer_id, connection_id, event)
+    }
+
+    fn poll(&mut self, cx: &mut Context<'_>) -> Poll<ToSwarm<Self::ToSwarm, THandlerInEvent<Self>>> {
+        self.inner.poll(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn establish(behaviour: &mut DirectFirst, peer_id: PeerId, id: usize, address: &str) -> ConnectionId {
+        let connection_id = ConnectionId::new_unchecked(id);
+        let endpoint = ConnectedPoint::Dialer {
+            address: address.parse().unwrap(),
+            role_override: Endpoint::Dialer,
+            port_use: PortUse::Reuse,
+        };
+        behaviour.on_swarm_event(FromSwarm::ConnectionEstablished(ConnectionEstablished {
+            peer_id,
+            connection_id,
+            endpoint: &endpoint,
+            failed_addresses: &[],
+            other_established: 0,
+        }));
+        connection_id
+    }
+
+    fn close(behaviour: &mut DirectFirst, peer_id: PeerId, connection_id: ConnectionId) {
+        let endpoint = behaviour.connections[&connection_id].1.clone();
+        behaviour.on_swarm_event(FromSwarm::ConnectionClosed(ConnectionClosed {
+            peer_id,
+            connection_id,
+            endpoint: &endpoint,
+            cause: None,
+            remaining_established: 0,
+        }));
+    }
+
+    #[test]
+    fn test_relayed_connections_hidden_while_direct_is_open() {
+        let mut behaviour = DirectFirst::new();
+        let peer = PeerId::random();
+        let other = PeerId::random();
+        let relay = PeerId::random();
+        let circuit = format!("/ip4/203.0.113.1/udp/4001/quic-v1/p2p/{}/p2p-circuit", relay);
+
+        let relayed = establish(&mut behaviour, peer, 1, &circuit);
+        let other_relayed = establish(&mut behaviour, other, 2, &circuit);
+        assert!(behaviour.hidden.is_empty());
+
+        // Hole punch: the direct connection takes over new streams
+        let direct = establish(&mut behaviour, peer, 3, "/ip4/198.51.100.7/udp/4001/quic-v1");
+        assert_eq!(behaviour.hidden, HashSet::from([relayed]));
+
+        // Another relayed connection stays out of the way too
+        let late = establish(&mut behaviour, peer, 4, &circuit);
+        assert_eq!(behaviour.hidden, HashSet::from([relayed, late]));
+        close(&mut behaviour, peer, late);
+        assert_eq!(behaviour.hidden, HashSet::from([relayed]));
+
+        // Without a direct connection the relay carries new streams again
+        close(&mut behaviour, peer, direct);
+        assert!(behaviour.hidden.is_empty());
+        assert!(behaviour.connections.contains_key(&relayed));
+        assert!(behaviour.connections.contains_key(&other_relayed));
+    }
+}
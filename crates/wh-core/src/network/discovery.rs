@@ -0,0 +1,145 @@
+//! Discovery Cache
+//!
+//! mDNS re-announces every peer on the network periodically. This cache
+//! remembers what was already reported so discovery events (and address book
+//! updates) only happen for new peers or changed addresses.
+
+use libp2p::{Multiaddr, PeerId};
+use std::collections::{BTreeSet, HashMap};
+use std::time::{Duration, Instant};
+
+/// What changed for a peer after an observation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiscoveryChange {
+    /// First sighting (or first since the cached entry expired)
+    New(Vec<Multiaddr>),
+    /// Known peer announced addresses we had not seen
+    AddressesChanged(Vec<Multiaddr>),
+}
+
+#[derive(Debug, Clone)]
+struct CachedPeer {
+    addresses: BTreeSet<Multiaddr>,
+    last_seen: Instant,
+}
+
+/// Cache of discovered peers with a time-to-live
+#[derive(Debug, Clone)]
+pub struct DiscoveryCache {
+    ttl: Duration,
+    peers: HashMap<PeerId, CachedPeer>,
+}
+
+impl DiscoveryCache {
+    /// Create a cache forgetting peers not seen for `ttl`
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            peers: HashMap::new(),
+        }
+    }
+
+    /// Record a sighting of `peer_id` at `addresses`
+    ///
+    /// Returns None if nothing new was learned.
+    pub fn observe(
+        &mut self,
+        peer_id: PeerId,
+        addresses: impl IntoIterator<Item = Multiaddr>,
+        now: Instant,
+    ) -> Option<DiscoveryChange> {
+        let addresses: BTreeSet<Multiaddr> = addresses.into_iter().collect();
+
+        match self.peers.get_mut(&peer_id) {
+            Some(cached) if now.duration_since(cached.last_seen) <= self.ttl => {
+                cached.last_seen = now;
+                if addresses.is_subset(&cached.addresses) {
+                    return None;
+                }
+                cached.addresses.extend(addresses);
+                Some(DiscoveryChange::AddressesChanged(
+                    cached.addresses.iter().cloned().collect(),
+                ))
+            }
+            _ => {
+                let list = addresses.iter().cloned().collect();
+                self.peers.insert(peer_id, CachedPeer { addresses, last_seen: now });
+                Some(DiscoveryChange::New(list))
+            }
+        }
+    }
+
+    /// Forget a peer (e.g. its mDNS record expired); returns true if it was cached
+    pub fn remove(&mut self, peer_id: &PeerId) -> bool {
+        self.peers.remove(peer_id).is_some()
+    }
+
+    /// Forget every peer, so the next sighting of each is reported again
+    pub fn clear(&mut self) {
+        self.peers.clear();
+    }
+
+    /// Drop entries older than the TTL
+    pub fn prune(&mut self, now: Instant) {
+        let ttl = self.ttl;
+        self.peers
+            .retain(|_, cached| now.duration_since(cached.last_seen) <= ttl);
+    }
+
+    /// Currently cached peers and their addresses
+    pub fn peers(&self) -> impl Iterator<Item = (&PeerId, Vec<&Multiaddr>)> {
+        self.peers
+            .iter()
+            .map(|(peer_id, cached)| (peer_id, cached.addresses.iter().collect()))
+    }
+
+    /// Number of cached peers
+    pub fn len(&self) -> usize {
+        self.peers.len()
+    }
+
+    /// Check if no peers are cached
+    pub fn is_empty(&self) -> bool {
+        self.peers.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(port: u16) -> Multiaddr {
+        format!("/ip4/192.168.1.10/udp/{}/quic-v1", port).parse().unwrap()
+    }
+
+    #[test]
+    fn test_repeated_announcements_are_suppressed() {
+        let mut cache = DiscoveryCache::new(Duration::from_secs(60));
+        let peer = PeerId::random();
+        let now = Instant::now();
+
+        assert_eq!(
+            cache.observe(peer, [addr(1)], now),
+            Some(DiscoveryChange::New(vec![addr(1)]))
+        );
+        assert_eq!(cache.observe(peer, [addr(1)], now + Duration::from_secs(5)), None);
+        assert_eq!(
+            cache.observe(peer, [addr(2)], now + Duration::from_secs(10)),
+            Some(DiscoveryChange::AddressesChanged(vec![addr(1), addr(2)]))
+        );
+    }
+
+    #[test]
+    fn test_expired_entries_are_reported_again() {
+        let mut cache = DiscoveryCache::new(Duration::from_secs(60));
+        let peer = PeerId::random();
+        let now = Instant::now();
+
+        cache.observe(peer, [addr(1)], now);
+        let later = now + Duration::from_secs(120);
+        assert!(matches!(cache.observe(peer, [addr(1)], later), Some(DiscoveryChange::New(_))));
+
+        cache.prune(later + Duration::from_secs(120));
+        assert!(cache.is_empty());
+    }
+}
@@ -0,0 +1,190 @@
+//! DNS Peer Resolution
+//!
+//! Links may name a host by DNS instead of peer ID (`rift://peers.example.dev/3000`).
+//! The name is resolved the way libp2p resolves `/dnsaddr`: TXT records on
+//! `_dnsaddr.<name>` hold `dnsaddr=<multiaddr>/p2p/<PEER_ID>` entries, possibly
+//! pointing at further `/dnsaddr` names. The result is rewritten into a regular
+//! link carrying the peer ID and its addresses, so everything after resolution
+//! works as if the user had pasted a full link.
+
+use hickory_resolver::TokioAsyncResolver;
+use hickory_resolver::config::{NameServerConfigGroup, ResolverConfig, ResolverOpts};
+use hickory_resolver::error::ResolveErrorKind;
+use libp2p::multiaddr::Protocol;
+use libp2p::{Multiaddr, PeerId};
+use std::net::SocketAddr;
+use tracing::{debug, warn};
+
+use super::identity::PeerIdentity;
+use crate::error::{Result, RiftError};
+
+/// Prefix of the TXT records' owner name
+const DNSADDR_PREFIX: &str = "_dnsaddr.";
+
+/// Prefix of every TXT record value
+const DNSADDR_ENTRY: &str = "dnsaddr=";
+
+/// How deep `/dnsaddr` entries may point at further names
+const MAX_DEPTH: usize = 4;
+
+/// Most addresses taken from DNS for one peer
+const MAX_ADDRESSES: usize = 16;
+
+/// Whether the host part of a link is a DNS name rather than a peer ID
+pub fn is_dns_link(link: &str) -> bool {
+    link_host(link).is_some_and(is_dns_name)
+}
+
+/// Resolve a link naming a DNS host into one naming the peer
+///
+/// Links that already carry a peer ID are returned unchanged. Addresses and
+/// the link ID already in the query are kept; resolved addresses follow.
+/// `resolver` is a name server (`1.1.1.1` or `1.1.1.1:53`); without one the
+/// system configuration is used.
+pub async fn resolve_link(link: &str, resolver: Option<&str>) -> Result<String> {
+    let Some(name) = link_host(link).filter(|host| is_dns_name(host)) else {
+        return Ok(link.to_string());
+    };
+    let resolver = build_resolver(resolver)?;
+
+    let mut entries = Vec::new();
+    let mut pending = vec![(name.to_string(), 0)];
+    while let Some((name, depth)) = pending.pop() {
+        let lookup = resolver
+            .txt_lookup(format!("{}{}", DNSADDR_PREFIX, name))
+            .await
+            .map_err(|e| match e.kind() {
+                ResolveErrorKind::NoRecordsFound { .. } => {
+                    RiftError::PeerNotFound(format!("{} has no {}{} records", name, DNSADDR_PREFIX, name))
+                }
+                _ => RiftError::PeerNotFound(format!("{}: {}", name, e)),
+            })?;
+        for record in lookup.iter() {
+            let text: Vec<u8> = record.txt_data().iter().flat_map(|part| part.iter().copied()).collect();
+            let Some(addr) = parse_entry(&String::from_utf8_lossy(&text)) else {
+                continue;
+            };
+            match addr.iter().next() {
+                Some(Protocol::Dnsaddr(nested)) if depth < MAX_DEPTH => pending.push((nested.to_string(), depth + 1)),
+                Some(Protocol::Dnsaddr(nested)) => warn!("Not following {} (nested too deep)", nested),
+                _ => entries.push(addr),
+            }
+        }
+    }
+
+    let (peer_id, addresses) = select_peer(name, &entries)?;
+    debug!("Resolved {} to {} at {} address(es)", name, peer_id, addresses.len());
+    rewrite_link(link, peer_id, addresses)
+}
+
+/// Host part of a link: between `rift://` and the query
+fn link_host(link: &str) -> Option<&str> {
+    let (link, _) = PeerIdentity::split_link_query(link);
+    link.strip_prefix("rift://")
+}
+
+/// Looks like a DNS name and not like a peer ID
+fn is_dns_name(host: &str) -> bool {
+    host.parse::<PeerId>().is_err()
+        && host.contains('.')
+        && host
+            .split('.')
+            .all(|label| !label.is_empty() && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+}
+
+/// Multiaddr of a `dnsaddr=` TXT value
+fn parse_entry(text: &str) -> Option<Multiaddr> {
+    text.trim().strip_prefix(DNSADDR_ENTRY)?.parse().ok()
+}
+
+/// Peer of the first entry ending in `/p2p/<ID>`, and its addresses
+///
+/// The `/p2p` suffix is stripped. Entries of other peers are ignored, so a
+/// name should list a single peer.
+fn select_peer(name: &str, entries: &[Multiaddr]) -> Result<(PeerId, Vec<Multiaddr>)> {
+    let with_peer: Vec<(PeerId, Multiaddr)> = entries
+        .iter()
+        .filter_map(|addr| {
+            let mut addr = addr.clone();
+            match addr.pop() {
+                Some(Protocol::P2p(peer_id)) => Some((peer_id, addr)),
+                _ => None,
+            }
+        })
+        .collect();
+    let Some((peer_id, _)) = with_peer.first() else {
+        return Err(RiftError::PeerNotFound(format!(
+            "{} has no {}{} records with a peer ID",
+            name, DNSADDR_PREFIX, name
+        )));
+    };
+    let peer_id = *peer_id;
+    if with_peer.iter().any(|(other, _)| *other != peer_id) {
+        warn!("{} lists several peers, using {}", name, peer_id);
+    }
+    let addresses = with_peer
+        .into_iter()
+        .filter(|(other, _)| *other == peer_id)
+        .map(|(_, addr)| addr)
+        .take(MAX_ADDRESSES)
+        .collect();
+    Ok((peer_id, addresses))
+}
+
+/// The link with its host replaced by the peer ID and the addresses added
+fn rewrite_link(link: &str, peer_id: PeerId, resolved: Vec<Multiaddr>) -> Result<String> {
+    let query = PeerIdentity::split_link_query(link).1.map(|query| format!("?{}", query)).unwrap_or_default();
+    PeerIdentity::with_link_addresses(&format!("rift://{}{}", peer_id, query), &resolved)
+}
+
+fn build_resolver(server: Option<&str>) -> Result<TokioAsyncResolver> {
+    let Some(server) = server else {
+        return TokioAsyncResolver::tokio_from_system_conf()
+            .map_err(|e| RiftError::ConfigError(format!("Cannot read the system DNS configuration: {}", e)));
+    };
+    let addr = server
+        .parse::<SocketAddr>()
+        .or_else(|_| server.parse().map(|ip| SocketAddr::new(ip, 53)))
+        .map_err(|_| RiftError::ConfigError(format!("Invalid DNS resolver '{}' (expected an IP address)", server)))?;
+    let name_servers = NameServerConfigGroup::from_ips_clear(&[addr.ip()], addr.port(), true);
+    Ok(TokioAsyncResolver::tokio(
+        ResolverConfig::from_parts(None, Vec::new(), name_servers),
+        ResolverOpts::default(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dnsaddr_entries_become_a_link() {
+        let peer_id = PeerId::random();
+        let other = PeerId::random();
+        assert!(is_dns_link("rift://peers.example.dev?link=abc"));
+        assert!(!is_dns_link(&format!("rift://{}", peer_id)));
+        assert!(!is_dns_link("rift://not a host"));
+
+        let entries: Vec<Multiaddr> = [
+            format!("dnsaddr=/ip4/203.0.113.7/udp/9000/quic-v1/p2p/{}", peer_id),
+            "dnsaddr=/ip4/203.0.113.8/tcp/9000".to_string(),
+            format!("dnsaddr=/ip4/198.51.100.1/tcp/9000/p2p/{}", other),
+            format!("dnsaddr=/dns4/peers.example.dev/tcp/9001/p2p/{}", peer_id),
+            "v=spf1 -all".to_string(),
+        ]
+        .iter()
+        .filter_map(|text| parse_entry(text))
+        .collect();
+        assert_eq!(entries.len(), 4);
+
+        let (selected, addresses) = select_peer("peers.example.dev", &entries).unwrap();
+        assert_eq!(selected, peer_id);
+        assert_eq!(addresses.len(), 2);
+        assert!(select_peer("peers.example.dev", &entries[1..2]).is_err());
+
+        let link = rewrite_link("rift://peers.example.dev?link=abc", peer_id, addresses.clone()).unwrap();
+        assert_eq!(PeerIdentity::parse_rift_link(&link).unwrap(), peer_id);
+        assert_eq!(PeerIdentity::link_addresses(&link), addresses);
+        assert_eq!(PeerIdentity::link_id(&link), Some("abc"));
+    }
+}
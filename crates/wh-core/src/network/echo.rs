@@ -0,0 +1,97 @@
+//! Echo Protocol
+//!
+//! `/<namespace>/echo/1.0.0` sends every byte straight back. Unlike libp2p ping,
+//! which only proves the connection is alive, a successful echo proves that
+//! Rift streams can be opened and carry data end to end (`rift ping`).
+
+use libp2p::PeerId;
+use libp2p_stream as stream;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio_util::compat::FuturesAsyncReadCompatExt;
+
+use super::behaviour::Protocols;
+use crate::error::{Result, RiftError};
+
+/// Most bytes echoed on a single stream
+pub const MAX_ECHO_BYTES: u64 = 16 * 1024 * 1024;
+
+/// Echo everything read from `reader` back into `writer`, up to [`MAX_ECHO_BYTES`]
+pub async fn serve_echo<R, W>(reader: R, writer: &mut W) -> std::io::Result<u64>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut limited = reader.take(MAX_ECHO_BYTES);
+    let echoed = tokio::io::copy(&mut limited, writer).await?;
+    writer.shutdown().await?;
+    Ok(echoed)
+}
+
+/// Send `payload_size` random bytes to a peer over the echo protocol and time the round trip
+///
+/// The clock starts once the stream is open, so the result is the data
+/// round-trip time rather than the cost of negotiating the stream.
+pub async fn echo_round_trip(
+    control: &mut stream::Control,
+    protocols: &Protocols,
+    peer_id: PeerId,
+    payload_size: usize,
+) -> Result<Duration> {
+    if payload_size as u64 > MAX_ECHO_BYTES {
+        return Err(RiftError::StreamError(format!(
+            "echo payload of {} bytes exceeds the {} byte limit",
+            payload_size, MAX_ECHO_BYTES
+        )));
+    }
+
+    let stream = control
+        .open_stream(peer_id, protocols.echo.clone())
+        .await
+        .map_err(|e| RiftError::StreamError(format!("Failed to open echo stream: {:?}", e)))?;
+    let (mut read, mut write) = tokio::io::split(stream.compat());
+
+    let payload: Vec<u8> = (0..payload_size).map(|_| rand::random::<u8>()).collect();
+    let started = Instant::now();
+
+    // Write and read concurrently so large payloads cannot fill both windows
+    let send = async {
+        write.write_all(&payload).await?;
+        write.shutdown().await
+    };
+    let mut echoed = Vec::with_capacity(payload_size);
+    let receive = read.read_to_end(&mut echoed);
+    let (sent, received) = tokio::join!(send, receive);
+    sent?;
+    received?;
+
+    if echoed != payload {
+        return Err(RiftError::StreamError(format!(
+            "echo mismatch: sent {} bytes, got {} back",
+            payload.len(),
+            echoed.len()
+        )));
+    }
+    Ok(started.elapsed())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_serve_echo_returns_input() {
+        let (mut client, server) = tokio::io::duplex(64 * 1024);
+        let (server_read, mut server_write) = tokio::io::split(server);
+        let server = tokio::spawn(async move { serve_echo(server_read, &mut server_write).await });
+
+        let payload = vec![7u8; 40_000];
+        client.write_all(&payload).await.unwrap();
+        client.shutdown().await.unwrap();
+        let mut echoed = Vec::new();
+        client.read_to_end(&mut echoed).await.unwrap();
+
+        assert_eq!(echoed, payload);
+        assert_eq!(server.await.unwrap().unwrap(), payload.len() as u64);
+    }
+}
@@ -0,0 +1,205 @@
+//! Exit Mode
+//!
+//! `/rift/exit/1.0.0` lets a trusted peer reach destinations of its choosing
+//! through the host, like a personal VPN into a dev machine (`rift exit`).
+//! Each stream starts with an [`ExitRequest`] naming the destination; the host
+//! checks it against its [`ExitPolicy`] and answers with an [`ExitResponse`]
+//! before relaying any bytes.
+
+use libp2p::{PeerId, Stream};
+use libp2p_stream as stream;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use tokio_util::compat::{Compat, FuturesAsyncReadCompatExt};
+
+use super::behaviour::Protocols;
+use super::swarm::{receive_secrets, send_secrets};
+use crate::error::{Result, RiftError};
+
+/// Names that count as this machine in the default policy
+const LOCALHOST_NAMES: &[&str] = &["localhost", "127.0.0.1", "::1"];
+
+/// First message on an exit stream: where the peer wants to go
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExitRequest {
+    /// Host name or IP address, as seen from the host
+    pub host: String,
+    pub port: u16,
+}
+
+impl fmt::Display for ExitRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.host.contains(':') {
+            write!(f, "[{}]:{}", self.host, self.port)
+        } else {
+            write!(f, "{}:{}", self.host, self.port)
+        }
+    }
+}
+
+/// The host's answer to an [`ExitRequest`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExitResponse {
+    /// Connected; the stream now carries the destination's bytes
+    Connected,
+    /// The destination is outside the host's policy
+    Denied { reason: String },
+    /// Allowed, but the host could not connect
+    Failed { reason: String },
+}
+
+/// One allowed destination: a host, on one port or all of them
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ExitRule {
+    host: String,
+    port: Option<u16>,
+}
+
+/// Destinations an exit host forwards to; localhost only unless widened
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExitPolicy {
+    rules: Vec<ExitRule>,
+}
+
+impl ExitPolicy {
+    /// Any port on this machine (`localhost`, `127.0.0.1`, `::1`)
+    pub fn localhost() -> Self {
+        Self {
+            rules: LOCALHOST_NAMES
+                .iter()
+                .map(|host| ExitRule {
+                    host: host.to_string(),
+                    port: None,
+                })
+                .collect(),
+        }
+    }
+
+    /// Also allow `HOST` (any port) or `HOST:PORT`; IPv6 hosts go in brackets with a port
+    pub fn with_rule(mut self, rule: &str) -> Result<Self> {
+        let (host, port) = match rule.strip_prefix('[') {
+            Some(rest) => {
+                let (host, tail) = rest
+                    .split_once(']')
+                    .ok_or_else(|| RiftError::ConfigError(format!("invalid exit destination '{}'", rule)))?;
+                (host, tail.strip_prefix(':'))
+            }
+            None => match rule.split_once(':') {
+                Some((host, port)) if !port.contains(':') => (host, Some(port)),
+                _ => (rule, None),
+            },
+        };
+        if host.is_empty() || host == "*" {
+            return Err(RiftError::ConfigError(format!(
+                "exit destination '{}' must name a host",
+                rule
+            )));
+        }
+        let port = match port {
+            None | Some("*") => None,
+            Some(port) => Some(port.parse::<u16>().map_err(|_| {
+                RiftError::ConfigError(format!("invalid port in exit destination '{}'", rule))
+            })?),
+        };
+        self.rules.push(ExitRule {
+            host: normalize_host(host),
+            port,
+        });
+        Ok(self)
+    }
+
+    /// Whether a request may be forwarded
+    ///
+    /// Hosts are compared by name, not by what they resolve to: allowing
+    /// `localhost` does not allow other names pointing at 127.0.0.1.
+    pub fn allows(&self, request: &ExitRequest) -> bool {
+        let host = normalize_host(&request.host);
+        self.rules
+            .iter()
+            .any(|rule| rule.host == host && rule.port.is_none_or(|port| port == request.port))
+    }
+
+    /// Allowed destinations, for the consent prompt
+    pub fn describe(&self) -> Vec<String> {
+        self.rules
+            .iter()
+            .map(|rule| match rule.port {
+                Some(port) => ExitRequest {
+                    host: rule.host.clone(),
+                    port,
+                }
+                .to_string(),
+                None => format!("{} (any port)", rule.host),
+            })
+            .collect()
+    }
+}
+
+fn normalize_host(host: &str) -> String {
+    host.trim_start_matches('[')
+        .trim_end_matches(']')
+        .trim_end_matches('.')
+        .to_ascii_lowercase()
+}
+
+/// Ask a peer to connect to a destination on our behalf
+///
+/// Bytes may only be relayed over the returned stream when the response is
+/// [`ExitResponse::Connected`].
+pub async fn open_exit_stream(
+    control: &mut stream::Control,
+    protocols: &Protocols,
+    peer_id: PeerId,
+    request: &ExitRequest,
+) -> Result<(ExitResponse, Compat<Stream>)> {
+    let stream = control
+        .open_stream(peer_id, protocols.exit.clone())
+        .await
+        .map_err(|e| RiftError::StreamError(format!("Failed to open exit stream: {:?}", e)))?;
+    let mut stream = stream.compat();
+    send_secrets(&mut stream, request).await?;
+    let response = receive_secrets(&mut stream).await?;
+    Ok((response, stream))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(host: &str, port: u16) -> ExitRequest {
+        ExitRequest {
+            host: host.to_string(),
+            port,
+        }
+    }
+
+    #[test]
+    fn test_default_policy_is_localhost_only() {
+        let policy = ExitPolicy::localhost();
+        assert!(policy.allows(&request("localhost", 5432)));
+        assert!(policy.allows(&request("LOCALHOST.", 22)));
+        assert!(policy.allows(&request("::1", 8080)));
+        assert!(!policy.allows(&request("localhost.evil.com", 80)));
+        assert!(!policy.allows(&request("10.0.0.5", 5432)));
+    }
+
+    #[test]
+    fn test_rules_widen_the_policy() {
+        let policy = ExitPolicy::localhost()
+            .with_rule("db.internal:5432")
+            .unwrap()
+            .with_rule("10.0.0.7")
+            .unwrap()
+            .with_rule("[fd00::1]:443")
+            .unwrap();
+        assert!(policy.allows(&request("db.internal", 5432)));
+        assert!(!policy.allows(&request("db.internal", 5433)));
+        assert!(policy.allows(&request("10.0.0.7", 9000)));
+        assert!(policy.allows(&request("fd00::1", 443)));
+        assert!(!policy.allows(&request("fd00::1", 80)));
+
+        assert!(ExitPolicy::localhost().with_rule("*:80").is_err());
+        assert!(ExitPolicy::localhost().with_rule("host:http").is_err());
+        assert_eq!(policy.describe()[5], "[fd00::1]:443");
+    }
+}
@@ -0,0 +1,118 @@
+//! Relay Fallback
+//!
+//! Tracks which peers are reached over relay circuits, so a failed hole punch
+//! ends on a known path: the open circuit keeps carrying the traffic, or the
+//! peer is dialed through the relays again when no circuit is left.
+
+use libp2p::{multiaddr::Protocol, Multiaddr, PeerId};
+use std::collections::{HashMap, HashSet};
+
+/// What to do after a hole punch failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fallback {
+    /// A circuit through this relay is still open; traffic stays on it
+    KeepCircuit(Multiaddr),
+    /// No circuit is left; dial the peer through the relays
+    Redial,
+}
+
+/// Relayed connections per peer and peers waiting for a relayed re-dial
+#[derive(Debug, Default)]
+pub struct RelayFallback {
+    /// Relay of each open relayed connection, per peer
+    circuits: HashMap<PeerId, Vec<Multiaddr>>,
+    /// Peers dialed through a relay after a failed hole punch
+    redialing: HashSet<PeerId>,
+}
+
+impl RelayFallback {
+    /// Create an empty tracker
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record an opened connection; returns the relay if it completes a fallback re-dial
+    pub fn opened(&mut self, peer_id: PeerId, remote: &Multiaddr) -> Option<Multiaddr> {
+        let relay = circuit_relay(remote)?;
+        self.circuits.entry(peer_id).or_default().push(relay.clone());
+        self.redialing.remove(&peer_id).then_some(relay)
+    }
+
+    /// Record a closed connection
+    pub fn closed(&mut self, peer_id: PeerId, remote: &Multiaddr) {
+        let Some(relay) = circuit_relay(remote) else {
+            return;
+        };
+        if let Some(circuits) = self.circuits.get_mut(&peer_id) {
+            if let Some(index) = circuits.iter().position(|open| *open == relay) {
+                circuits.swap_remove(index);
+            }
+            if circuits.is_empty() {
+                self.circuits.remove(&peer_id);
+            }
+        }
+    }
+
+    /// Decide how to keep reaching a peer whose hole punch failed
+    pub fn hole_punch_failed(&mut self, peer_id: PeerId) -> Fallback {
+        match self.circuits.get(&peer_id).and_then(|circuits| circuits.first()) {
+            Some(relay) => Fallback::KeepCircuit(relay.clone()),
+            None => {
+                self.redialing.insert(peer_id);
+                Fallback::Redial
+            }
+        }
+    }
+
+    /// Give up on a pending re-dial (it failed, or the peer went away)
+    pub fn forget(&mut self, peer_id: &PeerId) {
+        self.redialing.remove(peer_id);
+    }
+}
+
+/// The relay part of a circuit address, `/p2p/<relay>` alone for inbound circuits
+fn circuit_relay(address: &Multiaddr) -> Option<Multiaddr> {
+    let mut relay = Multiaddr::empty();
+    for protocol in address.iter() {
+        if matches!(protocol, Protocol::P2pCircuit) {
+            return Some(relay);
+        }
+        relay.push(protocol);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn circuit(relay: &str, peer_id: PeerId) -> Multiaddr {
+        format!("{}/p2p-circuit/p2p/{}", relay, peer_id).parse().unwrap()
+    }
+
+    #[test]
+    fn test_failed_punch_keeps_open_circuit() {
+        let relay = format!("/ip4/203.0.113.1/udp/4001/quic-v1/p2p/{}", PeerId::random());
+        let peer_id = PeerId::random();
+        let mut fallback = RelayFallback::new();
+
+        assert_eq!(fallback.opened(peer_id, &"/ip4/198.51.100.2/udp/4001/quic-v1".parse().unwrap()), None);
+        assert_eq!(fallback.opened(peer_id, &circuit(&relay, peer_id)), None);
+        assert_eq!(fallback.hole_punch_failed(peer_id), Fallback::KeepCircuit(relay.parse().unwrap()));
+    }
+
+    #[test]
+    fn test_failed_punch_without_circuit_redials() {
+        let relay = format!("/ip4/203.0.113.1/udp/4001/quic-v1/p2p/{}", PeerId::random());
+        let peer_id = PeerId::random();
+        let mut fallback = RelayFallback::new();
+
+        fallback.opened(peer_id, &circuit(&relay, peer_id));
+        fallback.closed(peer_id, &circuit(&relay, peer_id));
+        assert_eq!(fallback.hole_punch_failed(peer_id), Fallback::Redial);
+
+        // The next relayed connection completes the fallback, once
+        assert_eq!(fallback.opened(peer_id, &circuit(&relay, peer_id)), Some(relay.parse().unwrap()));
+        assert_eq!(fallback.opened(peer_id, &circuit(&relay, peer_id)), None);
+    }
+}
@@ -0,0 +1,50 @@
+//! Loopback Fast Path
+//!
+//! When both peers run on the same machine (integration tests, demos) the
+//! connect side can reach the shared port directly instead of going through
+//! QUIC. Peers compare a machine fingerprint to decide whether that is safe.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// Files that identify this machine (and this boot) on Linux
+const MACHINE_ID_SOURCES: &[&str] = &[
+    "/etc/machine-id",
+    "/var/lib/dbus/machine-id",
+    "/proc/sys/kernel/random/boot_id",
+];
+
+/// Fingerprint of the local machine, equal for processes on the same host
+///
+/// Combines the machine and boot IDs with the hostname so containers sharing
+/// a kernel but not a network namespace do not match each other.
+pub fn machine_fingerprint() -> String {
+    let mut hasher = DefaultHasher::new();
+
+    for source in MACHINE_ID_SOURCES {
+        if let Ok(contents) = std::fs::read_to_string(source) {
+            contents.trim().hash(&mut hasher);
+        }
+    }
+    for var in ["HOSTNAME", "COMPUTERNAME"] {
+        if let Ok(name) = std::env::var(var) {
+            name.hash(&mut hasher);
+        }
+    }
+    if let Ok(name) = std::fs::read_to_string("/etc/hostname") {
+        name.trim().hash(&mut hasher);
+    }
+
+    format!("{:016x}", hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fingerprint_is_stable() {
+        assert_eq!(machine_fingerprint(), machine_fingerprint());
+        assert_eq!(machine_fingerprint().len(), 16);
+    }
+}
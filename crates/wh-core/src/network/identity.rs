@@ -2,11 +2,17 @@
 //!
 //! Handles libp2p keypair generation, storage, and peer ID derivation.
 
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
 use libp2p::identity::{Keypair, PeerId};
+use libp2p::Multiaddr;
+use libp2p::multiaddr::Protocol;
 use std::path::Path;
 
 use crate::error::{RiftError, Result};
 
+/// Multibase prefix of unpadded base64url, the encoding of addresses in links
+const ADDRESS_MULTIBASE: char = 'u';
+
 /// Manages the peer identity (keypair and peer ID)
 #[derive(Clone)]
 pub struct PeerIdentity {
@@ -22,6 +28,15 @@ impl PeerIdentity {
         Self { keypair, peer_id }
     }
 
+    /// Identity for this configuration: throwaway if ephemeral, else loaded from disk
+    pub fn for_config(config: &crate::config::RiftConfig) -> Result<Self> {
+        if config.ephemeral_identity {
+            Ok(Self::generate())
+        } else {
+            Self::load_or_generate(&config.identity_path)
+        }
+    }
+
     /// Load identity from a file or generate a new one
     pub fn load_or_generate(path: impl AsRef<Path>) -> Result<Self> {
         let path = path.as_ref();
@@ -94,8 +109,14 @@ impl PeerIdentity {
         format!("rift://{}", self.peer_id)
     }
 
-    /// Parse a peer ID from a Rift link
+    /// Generate a Rift link that tells peers where to dial us
+    pub fn to_rift_link_with_addresses(&self, addresses: &[Multiaddr]) -> String {
+        format!("{}{}", self.to_rift_link(), Self::link_query(addresses, None))
+    }
+
+    /// Parse a peer ID from a Rift link (the query after `?` is ignored)
     pub fn parse_rift_link(link: &str) -> Result<PeerId> {
+        let (link, _) = Self::split_link_query(link);
         let peer_id_str = link
             .strip_prefix("rift://")
             .ok_or_else(|| RiftError::InvalidPeerId("Link must start with rift://".to_string()))?;
@@ -104,6 +125,102 @@ impl PeerIdentity {
             .parse()
             .map_err(|e| RiftError::InvalidPeerId(format!("Invalid peer ID: {}", e)))
     }
+
+    /// Build the query of a link: `?addrs=<A>,<B>&link=<ID>`, or nothing
+    ///
+    /// Addresses are multibase encoded (unpadded base64url of their bytes).
+    pub fn link_query(addresses: &[Multiaddr], link_id: Option<&str>) -> String {
+        let mut params = Vec::new();
+        if !addresses.is_empty() {
+            let encoded: Vec<String> = addresses
+                .iter()
+                .map(|addr| format!("{}{}", ADDRESS_MULTIBASE, URL_SAFE_NO_PAD.encode(addr.to_vec())))
+                .collect();
+            params.push(format!("addrs={}", encoded.join(",")));
+        }
+        if let Some(id) = link_id {
+            params.push(format!("link={}", id));
+        }
+        if params.is_empty() {
+            String::new()
+        } else {
+            format!("?{}", params.join("&"))
+        }
+    }
+
+    /// Split a link into the part before `?` and its query
+    pub fn split_link_query(link: &str) -> (&str, Option<&str>) {
+        match link.split_once('?') {
+            Some((rest, query)) => (rest, Some(query)),
+            None => (link, None),
+        }
+    }
+
+    /// ID of a revocable link (`link=<ID>`)
+    pub fn link_id(link: &str) -> Option<&str> {
+        Self::link_param(link, "link").filter(|id| !id.is_empty())
+    }
+
+    /// Addresses embedded in a link (`addrs=...`); undecodable ones are skipped
+    pub fn link_addresses(link: &str) -> Vec<Multiaddr> {
+        let Some(addrs) = Self::link_param(link, "addrs") else {
+            return Vec::new();
+        };
+        addrs
+            .split(',')
+            .filter_map(|encoded| {
+                let bytes = URL_SAFE_NO_PAD.decode(encoded.strip_prefix(ADDRESS_MULTIBASE)?).ok()?;
+                Multiaddr::try_from(bytes).ok()
+            })
+            .collect()
+    }
+
+    /// Link to the peer a multiaddr names (`.../p2p/<ID>`), with the rest of
+    /// the multiaddr as the address to dial
+    ///
+    /// A bare `/dnsaddr/<host>` becomes `rift://<host>`, resolved when connecting.
+    pub fn link_from_multiaddr(addr: &Multiaddr) -> Result<String> {
+        let mut address = addr.clone();
+        match address.pop() {
+            Some(Protocol::P2p(peer_id)) if address.is_empty() => Ok(format!("rift://{}", peer_id)),
+            Some(Protocol::P2p(peer_id)) => Ok(format!("rift://{}{}", peer_id, Self::link_query(&[address], None))),
+            Some(Protocol::Dnsaddr(host)) if address.is_empty() => Ok(format!("rift://{}", host)),
+            _ => Err(RiftError::InvalidPeerId(format!(
+                "{} does not name a peer (expected an address ending in /p2p/<PEER_ID>)",
+                addr
+            ))),
+        }
+    }
+
+    /// The link with `addresses` added after the ones it carries
+    ///
+    /// A trailing `/p2p/<ID>` is stripped from each; it has to name the
+    /// link's peer, when the link names one.
+    pub fn with_link_addresses(link: &str, addresses: &[Multiaddr]) -> Result<String> {
+        let peer_id = Self::parse_rift_link(link).ok();
+        let mut merged = Self::link_addresses(link);
+        for addr in addresses {
+            let mut addr = addr.clone();
+            if let Some(Protocol::P2p(named)) = addr.iter().last() {
+                if peer_id.is_some_and(|peer_id| peer_id != named) {
+                    return Err(RiftError::InvalidPeerId(format!("{} names another peer than the link", addr)));
+                }
+                addr.pop();
+            }
+            if !merged.contains(&addr) {
+                merged.push(addr);
+            }
+        }
+        let (base, _) = Self::split_link_query(link);
+        Ok(format!("{}{}", base, Self::link_query(&merged, Self::link_id(link))))
+    }
+
+    fn link_param<'a>(link: &'a str, name: &str) -> Option<&'a str> {
+        Self::split_link_query(link)
+            .1?
+            .split('&')
+            .find_map(|param| param.strip_prefix(name)?.strip_prefix('='))
+    }
 }
 
 impl std::fmt::Debug for PeerIdentity {
@@ -143,6 +260,22 @@ mod tests {
         assert_eq!(original.peer_id(), loaded.peer_id());
     }
 
+    #[test]
+    fn test_ephemeral_identity_is_not_persisted() {
+        let temp = tempdir().unwrap();
+        let config = crate::config::RiftConfig {
+            identity_path: temp.path().join("identity.key"),
+            ..Default::default()
+        }
+        .with_ephemeral_identity(true);
+
+        let first = PeerIdentity::for_config(&config).unwrap();
+        let second = PeerIdentity::for_config(&config).unwrap();
+
+        assert_ne!(first.peer_id(), second.peer_id());
+        assert!(!config.identity_path.exists());
+    }
+
     #[test]
     fn test_rift_link() {
         let identity = PeerIdentity::generate();
@@ -152,5 +285,50 @@ mod tests {
         
         let parsed = PeerIdentity::parse_rift_link(&link).unwrap();
         assert_eq!(*identity.peer_id(), parsed);
+
+        let revocable = format!("{}/3000?link=abc123", link);
+        assert_eq!(PeerIdentity::link_id(&revocable), Some("abc123"));
+        assert_eq!(PeerIdentity::split_link_query(&revocable), (format!("{}/3000", link).as_str(), Some("link=abc123")));
+        assert_eq!(PeerIdentity::link_id(&link), None);
+    }
+
+    #[test]
+    fn test_rift_link_with_addresses() {
+        let identity = PeerIdentity::generate();
+        let addresses: Vec<Multiaddr> = vec![
+            "/ip4/203.0.113.7/udp/4001/quic-v1".parse().unwrap(),
+            format!("/ip4/198.51.100.2/udp/4001/quic-v1/p2p/{}/p2p-circuit", PeerId::random()).parse().unwrap(),
+        ];
+
+        let link = identity.to_rift_link_with_addresses(&addresses);
+        assert_eq!(PeerIdentity::parse_rift_link(&link).unwrap(), *identity.peer_id());
+        assert_eq!(PeerIdentity::link_addresses(&link), addresses);
+        assert_eq!(identity.to_rift_link_with_addresses(&[]), identity.to_rift_link());
+
+        let link = format!("{}/3000{}", identity.to_rift_link(), PeerIdentity::link_query(&addresses[..1], Some("abc123")));
+        assert_eq!(PeerIdentity::link_addresses(&link), addresses[..1]);
+        assert_eq!(PeerIdentity::link_id(&link), Some("abc123"));
+    }
+
+    #[test]
+    fn test_link_from_multiaddr() {
+        let peer_id = PeerId::random();
+        let addr: Multiaddr = format!("/ip4/1.2.3.4/udp/9000/quic-v1/p2p/{}", peer_id).parse().unwrap();
+        let link = PeerIdentity::link_from_multiaddr(&addr).unwrap();
+        assert_eq!(PeerIdentity::parse_rift_link(&link).unwrap(), peer_id);
+        assert_eq!(PeerIdentity::link_addresses(&link), vec!["/ip4/1.2.3.4/udp/9000/quic-v1".parse::<Multiaddr>().unwrap()]);
+        assert_eq!(
+            PeerIdentity::link_from_multiaddr(&"/dnsaddr/peers.example.dev".parse().unwrap()).unwrap(),
+            "rift://peers.example.dev"
+        );
+        assert!(PeerIdentity::link_from_multiaddr(&"/ip4/1.2.3.4/tcp/9000".parse().unwrap()).is_err());
+
+        // Extra addresses keep the link ID and must name the same peer
+        let extra: Multiaddr = format!("/ip4/5.6.7.8/tcp/9000/p2p/{}", peer_id).parse().unwrap();
+        let merged = PeerIdentity::with_link_addresses(&format!("rift://{}?link=abc", peer_id), &[addr, extra]).unwrap();
+        assert_eq!(PeerIdentity::link_addresses(&merged).len(), 2);
+        assert_eq!(PeerIdentity::link_id(&merged), Some("abc"));
+        let other: Multiaddr = format!("/ip4/5.6.7.8/tcp/9000/p2p/{}", PeerId::random()).parse().unwrap();
+        assert!(PeerIdentity::with_link_addresses(&merged, &[other]).is_err());
     }
 }
@@ -0,0 +1,252 @@
+//! Known Relays
+//!
+//! Relays that worked in earlier sessions, with their last observed latency.
+//! They are tried before the public bootstrap defaults, best first, and can
+//! be pinned or forgotten with `rift relays`. Persisted in the state dir.
+
+use libp2p::{multiaddr::Protocol, Multiaddr};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+use crate::error::{Result, RiftError};
+
+/// File name of the known-relay list inside the state directory
+pub const KNOWN_RELAYS_FILE: &str = "known_relays.json";
+
+/// Unpinned relays not used for this long are dropped (30 days)
+const STALE_AFTER_SECS: u64 = 30 * 24 * 60 * 60;
+
+/// A relay remembered from a successful session or pinned by the user
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KnownRelay {
+    /// Relay address, ending in `/p2p/<peer id>`
+    pub address: String,
+    /// Last observed round-trip time to the relay
+    pub latency_ms: Option<u64>,
+    /// Unix timestamp (seconds) of the last successful use (0 = never)
+    pub last_success: u64,
+    /// Number of successful uses
+    pub successes: u32,
+    /// Kept first in line and never pruned
+    pub pinned: bool,
+}
+
+/// Persistent list of known relays
+#[derive(Debug, Default)]
+pub struct KnownRelays {
+    /// Backing file (None = in-memory only)
+    path: Option<PathBuf>,
+    relays: Vec<KnownRelay>,
+}
+
+impl KnownRelays {
+    /// Create an in-memory list
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the list from a file, starting empty if it is missing or unreadable
+    pub fn load(path: PathBuf) -> Self {
+        let relays = match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+                warn!("Ignoring unreadable relay list {}: {}", path.display(), e);
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        };
+        let mut known = Self {
+            path: Some(path),
+            relays,
+        };
+        known.prune(unix_now());
+        known
+    }
+
+    /// Remember a relay that just carried a reservation or circuit
+    pub fn record_success(&mut self, address: &Multiaddr, latency: Option<Duration>) {
+        let relay = self.entry(address);
+        relay.successes += 1;
+        relay.last_success = unix_now();
+        if let Some(latency) = latency {
+            relay.latency_ms = Some(latency.as_millis() as u64);
+        }
+        self.persist();
+    }
+
+    /// Pin a relay, adding it if it is not known yet
+    pub fn pin(&mut self, address: &Multiaddr) -> Result<()> {
+        if !matches!(address.iter().last(), Some(Protocol::P2p(_))) {
+            return Err(RiftError::ConfigError(format!(
+                "relay address '{}' must end in /p2p/<peer id>",
+                address
+            )));
+        }
+        self.entry(address).pinned = true;
+        self.persist();
+        Ok(())
+    }
+
+    /// Forget a relay; returns false if it was not known
+    pub fn forget(&mut self, address: &str) -> bool {
+        let before = self.relays.len();
+        self.relays.retain(|relay| relay.address != address);
+        let removed = self.relays.len() != before;
+        if removed {
+            self.persist();
+        }
+        removed
+    }
+
+    /// Drop unpinned relays that have not worked for a long time
+    pub fn prune(&mut self, now: u64) {
+        let before = self.relays.len();
+        self.relays
+            .retain(|relay| relay.pinned || now.saturating_sub(relay.last_success) < STALE_AFTER_SECS);
+        if self.relays.len() != before {
+            self.persist();
+        }
+    }
+
+    /// Known relays, best first: pinned, then lowest latency, then most used
+    pub fn list(&self) -> Vec<&KnownRelay> {
+        let mut relays: Vec<&KnownRelay> = self.relays.iter().collect();
+        relays.sort_by_key(|relay| {
+            (
+                !relay.pinned,
+                relay.latency_ms.unwrap_or(u64::MAX),
+                std::cmp::Reverse(relay.successes),
+            )
+        });
+        relays
+    }
+
+    /// Addresses of the known relays, best first
+    pub fn preferred(&self) -> Vec<Multiaddr> {
+        self.list()
+            .into_iter()
+            .filter_map(|relay| relay.address.parse().ok())
+            .collect()
+    }
+
+    /// Number of known relays
+    pub fn len(&self) -> usize {
+        self.relays.len()
+    }
+
+    /// Check if no relays are known
+    pub fn is_empty(&self) -> bool {
+        self.relays.is_empty()
+    }
+
+    fn entry(&mut self, address: &Multiaddr) -> &mut KnownRelay {
+        let address = address.to_string();
+        let index = match self.relays.iter().position(|relay| relay.address == address) {
+            Some(index) => index,
+            None => {
+                self.relays.push(KnownRelay {
+                    address,
+                    latency_ms: None,
+                    last_success: 0,
+                    successes: 0,
+                    pinned: false,
+                });
+                self.relays.len() - 1
+            }
+        };
+        &mut self.relays[index]
+    }
+
+    fn persist(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+
+        let result = serde_json::to_string_pretty(&self.relays)
+            .map_err(RiftError::from)
+            .and_then(|json| {
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(path, json)?;
+                Ok(())
+            });
+
+        if let Err(e) = result {
+            warn!("Failed to persist relay list to {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// The relay part of a relayed address (`<relay>/p2p-circuit/...`)
+pub fn relay_of_circuit(address: &Multiaddr) -> Option<Multiaddr> {
+    let mut relay = Multiaddr::empty();
+    for protocol in address.iter() {
+        if matches!(protocol, Protocol::P2pCircuit) {
+            // A bare `/p2p/<relay>` prefix (inbound circuits) is not dialable
+            let dialable = relay.iter().count() > 1 && matches!(relay.iter().last(), Some(Protocol::P2p(_)));
+            return dialable.then_some(relay);
+        }
+        relay.push(protocol);
+    }
+    None
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p::PeerId;
+
+    fn relay_addr(port: u16) -> Multiaddr {
+        format!("/ip4/203.0.113.7/udp/{}/quic-v1/p2p/{}", port, PeerId::random())
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_relays_ranked_and_persisted() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(KNOWN_RELAYS_FILE);
+        let (slow, fast, pinned) = (relay_addr(4001), relay_addr(4002), relay_addr(4003));
+
+        let mut relays = KnownRelays::load(path.clone());
+        relays.record_success(&slow, Some(Duration::from_millis(180)));
+        relays.record_success(&fast, Some(Duration::from_millis(35)));
+        relays.record_success(&fast, None);
+        relays.pin(&pinned).unwrap();
+        assert!(relays.pin(&"/ip4/203.0.113.7/udp/4001/quic-v1".parse().unwrap()).is_err());
+
+        let relays = KnownRelays::load(path);
+        assert_eq!(relays.preferred(), vec![pinned, fast.clone(), slow.clone()]);
+        assert_eq!(relays.list()[1].successes, 2);
+        assert_eq!(relays.list()[1].latency_ms, Some(35));
+
+        let mut relays = relays;
+        assert!(relays.forget(&slow.to_string()));
+        assert!(!relays.forget(&slow.to_string()));
+        assert_eq!(relays.len(), 2);
+
+        relays.prune(unix_now() + STALE_AFTER_SECS);
+        assert_eq!(relays.len(), 1);
+        assert!(relays.list()[0].pinned);
+    }
+
+    #[test]
+    fn test_relay_of_circuit() {
+        let relay = relay_addr(4001);
+        let circuit = relay.clone().with(Protocol::P2pCircuit).with(Protocol::P2p(PeerId::random()));
+        assert_eq!(relay_of_circuit(&circuit), Some(relay.clone()));
+        assert_eq!(relay_of_circuit(&relay), None);
+
+        let inbound: Multiaddr = format!("/p2p/{}/p2p-circuit/p2p/{}", PeerId::random(), PeerId::random()).parse().unwrap();
+        assert_eq!(relay_of_circuit(&inbound), None);
+    }
+}
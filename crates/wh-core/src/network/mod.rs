@@ -4,14 +4,55 @@
 //! and NAT hole punching capabilities.
 
 pub mod behaviour;
+pub mod capabilities;
+pub mod direct_first;
+pub mod discovery;
+pub mod dnsaddr;
+pub mod echo;
+pub mod exit;
+pub mod fallback;
+#[cfg(feature = "local-fastpath")]
+pub mod fastpath;
 pub mod identity;
+pub mod known_relays;
+pub mod nat;
+pub mod peer_book;
+pub mod policy;
+pub mod presence;
+pub mod private;
+pub mod relay_select;
+pub mod relay_server;
+pub mod rendezvous;
+pub mod sni;
+pub mod stripe;
 pub mod swarm;
+pub mod transport;
+pub mod wake;
+#[cfg(feature = "webrtc")]
+pub mod webrtc;
 
-pub use behaviour::{RiftBehaviour, RiftBehaviourEvent, TUNNEL_PROTOCOL, SECRETS_PROTOCOL};
+pub use behaviour::{RiftBehaviour, RiftBehaviourEvent, Protocols, TUNNEL_PROTOCOL, SECRETS_PROTOCOL, CONTROL_PROTOCOL, STRIPE_PROTOCOL, ECHO_PROTOCOL, KV_PROTOCOL, TERM_PROTOCOL, EXIT_PROTOCOL, SERVICES_PROTOCOL};
+pub use capabilities::PeerCapabilities;
+pub use direct_first::DirectFirst;
+pub use discovery::{DiscoveryCache, DiscoveryChange};
+pub use dnsaddr::{is_dns_link, resolve_link};
+pub use echo::{echo_round_trip, serve_echo};
+pub use exit::{open_exit_stream, ExitPolicy, ExitRequest, ExitResponse};
 pub use identity::PeerIdentity;
+pub use known_relays::{KnownRelay, KnownRelays, KNOWN_RELAYS_FILE};
+pub use nat::{NatDetector, NatReport, NatType};
+pub use peer_book::{KnownPeer, PeerBook, PEER_BOOK_FILE};
+pub use policy::{PeerAccess, PeerAccessDecision, ProtocolPolicy, RiftProtocol};
+pub use presence::{query_services, serve_services, PresenceAnnouncement, SharedService, PRESENCE_TOPIC};
+pub use private::{generate_swarm_key, load_swarm_key, save_swarm_key, PreSharedKey, SWARM_KEY_FILE};
+pub use relay_select::{RelayAvailability, RelayProbe, RelayProbeReport, RelaySelector, RELAY_PROBES_FILE};
+pub use relay_server::{RelayServer, RelayServerEvent};
+pub use rendezvous::{RendezvousPoint, DEFAULT_RENDEZVOUS_NAMESPACE};
+pub use transport::TransportConfig;
+pub use wake::{ping_burst, WakeDetector};
 pub use swarm::{
-    NetworkEvent, PeerNetwork, PeerInfo, 
-    bridge_stream_to_tcp, open_tunnel_stream,
+    ConnectionDirection, ConnectionPath, NetworkEvent, PeerNetwork, PeerInfo, PeerRtt, listen_addresses,
+    answer_tunnel_request, bridge_stream_to_tcp, open_tunnel_stream, open_tunnel_stream_with, open_stripe_streams, read_tunnel_request, is_ephemeral_agent, advertised_services,
     send_secrets, receive_secrets,
     send_secrets_to_peer, receive_secrets_from_stream,
 };
@@ -0,0 +1,260 @@
+//! NAT Type Detection
+//!
+//! Classifies how this node sits behind NAT from what the network already
+//! tells it: the addresses peers observed us at (identify), AutoNAT dial-back
+//! probes and hole punch outcomes. Observations from the LAN say nothing about
+//! the NAT and are ignored.
+
+use libp2p::multiaddr::Protocol;
+use libp2p::{Multiaddr, PeerId};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+
+/// How reachable this node is from the internet
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NatType {
+    /// Not enough evidence yet
+    #[default]
+    Unknown,
+    /// Publicly reachable, no NAT in the way
+    Open,
+    /// Same public port towards every peer: hole punching usually works
+    Cone,
+    /// A new public port per peer: direct connections need a relay
+    Symmetric,
+    /// Only TCP gets out, so QUIC cannot be used
+    UdpBlocked,
+}
+
+impl NatType {
+    /// One-line advice for the user
+    pub fn advice(&self) -> &'static str {
+        match self {
+            Self::Unknown => "not enough peers seen yet to tell",
+            Self::Open => "peers can dial this node directly",
+            Self::Cone => "direct connections work through hole punching",
+            Self::Symmetric => "hole punching rarely works; traffic will go through relays",
+            Self::UdpBlocked => "QUIC is blocked; use WebSocket relays (see `rift relay --websocket-port`)",
+        }
+    }
+}
+
+impl fmt::Display for NatType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Unknown => "unknown",
+            Self::Open => "open",
+            Self::Cone => "cone",
+            Self::Symmetric => "symmetric",
+            Self::UdpBlocked => "UDP blocked",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Evidence behind the current [`NatType`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NatReport {
+    /// Current classification
+    pub nat_type: NatType,
+    /// AutoNAT verdict: Some(true) once peers could dial us back
+    pub reachable: Option<bool>,
+    /// Public addresses peers observed us at
+    pub observed: Vec<Multiaddr>,
+    /// Hole punches that succeeded
+    pub hole_punches_succeeded: u32,
+    /// Hole punches that failed
+    pub hole_punches_failed: u32,
+}
+
+/// Public address a peer saw one of our connections come from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Observation {
+    ip: IpAddr,
+    port: u16,
+    udp: bool,
+}
+
+impl Observation {
+    /// Parse a direct IP address; relayed and LAN addresses yield None
+    fn public(address: &Multiaddr) -> Option<Self> {
+        let mut ip = None;
+        let mut transport = None;
+        for protocol in address.iter() {
+            match protocol {
+                Protocol::Ip4(addr) => ip = Some(IpAddr::V4(addr)),
+                Protocol::Ip6(addr) => ip = Some(IpAddr::V6(addr)),
+                Protocol::Udp(port) => transport = Some((port, true)),
+                Protocol::Tcp(port) => transport = Some((port, false)),
+                Protocol::P2pCircuit => return None,
+                _ => {}
+            }
+        }
+        let (port, udp) = transport?;
+        let ip = ip.filter(is_global)?;
+        Some(Self { ip, port, udp })
+    }
+}
+
+/// Whether an IP is routable on the internet (not LAN, loopback or CGNAT)
+fn is_global(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let cgnat = ip.octets()[0] == 100 && (ip.octets()[1] & 0xc0) == 64;
+            !(ip.is_private() || ip.is_loopback() || ip.is_link_local() || ip.is_unspecified() || ip.is_broadcast() || cgnat)
+        }
+        IpAddr::V6(ip) => {
+            let unique_local = (ip.segments()[0] & 0xfe00) == 0xfc00;
+            let link_local = (ip.segments()[0] & 0xffc0) == 0xfe80;
+            !(ip.is_loopback() || ip.is_unspecified() || unique_local || link_local)
+        }
+    }
+}
+
+/// Collects NAT evidence and classifies it
+#[derive(Debug, Default)]
+pub struct NatDetector {
+    /// Latest observed address per observing peer
+    observed: HashMap<PeerId, Multiaddr>,
+    reachable: Option<bool>,
+    hole_punches_succeeded: u32,
+    hole_punches_failed: u32,
+    current: NatType,
+}
+
+impl NatDetector {
+    /// Create a detector with no evidence
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the address a peer observed us at (identify)
+    pub fn observe_address(&mut self, peer_id: PeerId, address: &Multiaddr) {
+        if Observation::public(address).is_some() {
+            self.observed.insert(peer_id, address.clone());
+        }
+    }
+
+    /// Record the AutoNAT verdict (None while unknown)
+    pub fn set_reachable(&mut self, reachable: Option<bool>) {
+        self.reachable = reachable;
+    }
+
+    /// Record a hole punch outcome
+    pub fn hole_punch(&mut self, succeeded: bool) {
+        if succeeded {
+            self.hole_punches_succeeded += 1;
+        } else {
+            self.hole_punches_failed += 1;
+        }
+    }
+
+    /// Re-classify; returns the new type if it changed
+    ///
+    /// `listen_addrs` are the addresses our listeners are bound to: a peer
+    /// seeing one of them means there is no NAT at all.
+    pub fn update(&mut self, listen_addrs: &[Multiaddr]) -> Option<NatType> {
+        let nat_type = self.classify(listen_addrs);
+        (nat_type != self.current).then(|| {
+            self.current = nat_type;
+            nat_type
+        })
+    }
+
+    /// Current classification
+    pub fn nat_type(&self) -> NatType {
+        self.current
+    }
+
+    /// Current classification with its evidence
+    pub fn report(&self) -> NatReport {
+        let mut observed: Vec<Multiaddr> = self.observed.values().cloned().collect();
+        observed.sort();
+        observed.dedup();
+        NatReport {
+            nat_type: self.current,
+            reachable: self.reachable,
+            observed,
+            hole_punches_succeeded: self.hole_punches_succeeded,
+            hole_punches_failed: self.hole_punches_failed,
+        }
+    }
+
+    fn classify(&self, listen_addrs: &[Multiaddr]) -> NatType {
+        let observations: Vec<Observation> = self.observed.values().filter_map(Observation::public).collect();
+        let local_ips: Vec<IpAddr> = listen_addrs.iter().filter_map(Observation::public).map(|o| o.ip).collect();
+
+        if self.reachable == Some(true) || observations.iter().any(|o| local_ips.contains(&o.ip)) {
+            return NatType::Open;
+        }
+
+        let udp: Vec<(IpAddr, u16)> = observations.iter().filter(|o| o.udp).map(|o| (o.ip, o.port)).collect();
+        if udp.len() >= 2 {
+            // Endpoint-independent mapping keeps one public port for every peer
+            return if udp.iter().all(|mapping| *mapping == udp[0]) {
+                NatType::Cone
+            } else {
+                NatType::Symmetric
+            };
+        }
+        if udp.is_empty() && !observations.is_empty() {
+            return NatType::UdpBlocked;
+        }
+        if self.hole_punches_succeeded > 0 {
+            return NatType::Cone;
+        }
+        NatType::Unknown
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(s: &str) -> Multiaddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_classifies_port_mapping() {
+        let mut nat = NatDetector::new();
+        nat.observe_address(PeerId::random(), &addr("/ip4/203.0.113.7/udp/40001/quic-v1"));
+        assert_eq!(nat.update(&[]), None);
+
+        nat.observe_address(PeerId::random(), &addr("/ip4/203.0.113.7/udp/40001/quic-v1"));
+        assert_eq!(nat.update(&[]), Some(NatType::Cone));
+
+        nat.observe_address(PeerId::random(), &addr("/ip4/203.0.113.7/udp/51234/quic-v1"));
+        assert_eq!(nat.update(&[]), Some(NatType::Symmetric));
+        assert_eq!(nat.report().observed.len(), 2);
+    }
+
+    #[test]
+    fn test_open_udp_blocked_and_ignored_addresses() {
+        let mut nat = NatDetector::new();
+        nat.observe_address(PeerId::random(), &addr("/ip4/192.168.1.20/udp/4001/quic-v1"));
+        nat.observe_address(PeerId::random(), &addr("/ip4/100.64.3.9/udp/4001/quic-v1"));
+        assert_eq!(nat.update(&[]), None);
+
+        nat.observe_address(PeerId::random(), &addr("/ip4/198.51.100.4/tcp/443/ws"));
+        assert_eq!(nat.update(&[]), Some(NatType::UdpBlocked));
+
+        let listen = [addr("/ip4/198.51.100.4/udp/4001/quic-v1")];
+        assert_eq!(nat.update(&listen), Some(NatType::Open));
+    }
+
+    #[test]
+    fn test_autonat_and_hole_punch_evidence() {
+        let mut nat = NatDetector::new();
+        nat.hole_punch(false);
+        assert_eq!(nat.update(&[]), None);
+        nat.hole_punch(true);
+        assert_eq!(nat.update(&[]), Some(NatType::Cone));
+        nat.set_reachable(Some(true));
+        assert_eq!(nat.update(&[]), Some(NatType::Open));
+        assert_eq!(nat.report().hole_punches_failed, 1);
+    }
+}
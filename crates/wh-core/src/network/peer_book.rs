@@ -0,0 +1,337 @@
+//! Peer Book
+//!
+//! Addresses of peers seen in earlier sessions, learned from identify. They
+//! are handed to the swarm at startup, so dialing a peer we met before does
+//! not wait for mDNS or the rendezvous server. Persisted in the state store.
+//!
+//! Entries also hold the nickname set with `rift peers rename`, which the
+//! daemon shows in place of the peer ID.
+
+use libp2p::{Multiaddr, PeerId};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+use crate::error::{Result, RiftError};
+use crate::state::StateStore;
+
+/// Name of the peer book in the state store
+pub const PEER_BOOK_FILE: &str = "peers.json";
+
+/// Peers not seen for this long are dropped (30 days)
+const STALE_AFTER_SECS: u64 = 30 * 24 * 60 * 60;
+
+/// Most peers remembered; the least recently seen go first
+const MAX_PEERS: usize = 256;
+
+/// Most addresses remembered per peer
+const MAX_ADDRESSES: usize = 16;
+
+/// Unchanged entries are rewritten at most this often
+const TOUCH_INTERVAL_SECS: u64 = 60 * 60;
+
+/// Longest nickname, in characters
+pub const MAX_NICKNAME_LEN: usize = 32;
+
+/// What we remember about a peer
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KnownPeer {
+    /// Listen addresses the peer reported
+    pub addresses: Vec<String>,
+    /// Unix timestamp (seconds) the peer was last identified
+    pub last_seen: u64,
+    /// Name the user gave the peer
+    #[serde(default)]
+    pub nickname: Option<String>,
+}
+
+impl KnownPeer {
+    /// Addresses that still parse as multiaddrs
+    pub fn multiaddrs(&self) -> Vec<Multiaddr> {
+        self.addresses.iter().filter_map(|addr| addr.parse().ok()).collect()
+    }
+}
+
+/// Persistent address book of known peers
+#[derive(Debug, Default)]
+pub struct PeerBook {
+    /// Where the book is kept (None = in-memory only)
+    store: Option<Arc<dyn StateStore>>,
+    /// Entries keyed by peer ID
+    peers: HashMap<String, KnownPeer>,
+}
+
+impl PeerBook {
+    /// Create an in-memory book
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the book from a state store, starting empty if it is missing or unreadable
+    pub fn open(store: Arc<dyn StateStore>) -> Self {
+        let peers = match store.load(PEER_BOOK_FILE) {
+            Ok(Some(contents)) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+                warn!("Ignoring unreadable peer book {}: {}", store.location(PEER_BOOK_FILE), e);
+                HashMap::new()
+            }),
+            Ok(None) => HashMap::new(),
+            Err(e) => {
+                warn!("Failed to read peer book {}: {}", store.location(PEER_BOOK_FILE), e);
+                HashMap::new()
+            }
+        };
+        let mut book = Self {
+            store: Some(store),
+            peers,
+        };
+        book.prune(unix_now());
+        book
+    }
+
+    /// Entries as last saved to the store
+    fn load_stored(&self) -> Option<HashMap<String, KnownPeer>> {
+        let contents = self.store.as_ref()?.load(PEER_BOOK_FILE).ok()??;
+        serde_json::from_str(&contents).ok()
+    }
+
+    /// Remember the addresses a peer just reported
+    pub fn record(&mut self, peer_id: &PeerId, addresses: &[Multiaddr]) {
+        let now = unix_now();
+        let addresses: Vec<String> = addresses
+            .iter()
+            .take(MAX_ADDRESSES)
+            .map(|addr| addr.to_string())
+            .collect();
+        let peer = self.peers.entry(peer_id.to_string()).or_default();
+        let changed = peer.addresses != addresses;
+        if !changed && now.saturating_sub(peer.last_seen) < TOUCH_INTERVAL_SECS {
+            return;
+        }
+        peer.addresses = addresses;
+        peer.last_seen = now;
+        // Keep nicknames another process set since we loaded the book
+        self.reload_nicknames();
+        self.prune(now);
+        self.persist();
+    }
+
+    /// Entry of a peer, if it is known
+    pub fn get(&self, peer_id: &PeerId) -> Option<&KnownPeer> {
+        self.peers.get(&peer_id.to_string())
+    }
+
+    /// Nickname of a peer, if it has one
+    pub fn nickname(&self, peer_id: &PeerId) -> Option<&str> {
+        self.peers.get(&peer_id.to_string())?.nickname.as_deref()
+    }
+
+    /// Every peer with a nickname
+    pub fn nicknames(&self) -> HashMap<PeerId, String> {
+        self.peers
+            .iter()
+            .filter_map(|(peer_id, peer)| Some((peer_id.parse().ok()?, peer.nickname.clone()?)))
+            .collect()
+    }
+
+    /// Peer going by a nickname
+    pub fn find_nickname(&self, nickname: &str) -> Option<PeerId> {
+        self.peers
+            .iter()
+            .find(|(_, peer)| peer.nickname.as_deref() == Some(nickname))
+            .and_then(|(peer_id, _)| peer_id.parse().ok())
+    }
+
+    /// Give a peer a nickname, or remove it with None
+    ///
+    /// Peers not met yet get an entry of their own, counted as seen now. Fails
+    /// if the nickname is malformed or another peer already has it.
+    pub fn set_nickname(&mut self, peer_id: &PeerId, nickname: Option<String>) -> Result<()> {
+        if let Some(nickname) = &nickname {
+            validate_nickname(nickname)?;
+            if let Some(other) = self.find_nickname(nickname).filter(|other| other != peer_id) {
+                return Err(RiftError::ConfigError(format!("nickname '{}' is already used by {}", nickname, other)));
+            }
+        }
+        match self.peers.get_mut(&peer_id.to_string()) {
+            Some(peer) => peer.nickname = nickname,
+            None if nickname.is_some() => {
+                self.peers.insert(peer_id.to_string(), KnownPeer {
+                    addresses: Vec::new(),
+                    last_seen: unix_now(),
+                    nickname,
+                });
+            }
+            None => return Ok(()),
+        }
+        self.persist();
+        Ok(())
+    }
+
+    /// Take over the nicknames saved to the store by another process
+    ///
+    /// Returns whether any changed.
+    pub fn reload_nicknames(&mut self) -> bool {
+        let Some(stored) = self.load_stored() else {
+            return false;
+        };
+        let named = |peers: &HashMap<String, KnownPeer>| -> HashMap<String, String> {
+            peers
+                .iter()
+                .filter_map(|(peer_id, peer)| Some((peer_id.clone(), peer.nickname.clone()?)))
+                .collect()
+        };
+        let nicknames = named(&stored);
+        if nicknames == named(&self.peers) {
+            return false;
+        }
+        for peer in self.peers.values_mut() {
+            peer.nickname = None;
+        }
+        for (peer_id, nickname) in nicknames {
+            let peer = self.peers.entry(peer_id.clone()).or_insert_with(|| stored[&peer_id].clone());
+            peer.nickname = Some(nickname);
+        }
+        true
+    }
+
+    /// Known peers with their dialable addresses
+    pub fn addresses(&self) -> Vec<(PeerId, Vec<Multiaddr>)> {
+        self.peers
+            .iter()
+            .filter_map(|(peer_id, peer)| Some((peer_id.parse().ok()?, peer.multiaddrs())))
+            .collect()
+    }
+
+    /// Drop stale peers and keep the most recently seen within the cap
+    pub fn prune(&mut self, now: u64) {
+        self.peers
+            .retain(|_, peer| now.saturating_sub(peer.last_seen) < STALE_AFTER_SECS);
+        if self.peers.len() > MAX_PEERS {
+            let mut seen: Vec<u64> = self.peers.values().map(|peer| peer.last_seen).collect();
+            seen.sort_unstable_by(|a, b| b.cmp(a));
+            let cutoff = seen[MAX_PEERS - 1];
+            self.peers.retain(|_, peer| peer.last_seen >= cutoff);
+        }
+    }
+
+    /// Number of known peers
+    pub fn len(&self) -> usize {
+        self.peers.len()
+    }
+
+    /// Check if no peers are known
+    pub fn is_empty(&self) -> bool {
+        self.peers.is_empty()
+    }
+
+    fn persist(&self) {
+        let Some(store) = &self.store else {
+            return;
+        };
+
+        let result = serde_json::to_string_pretty(&self.peers)
+            .map_err(RiftError::from)
+            .and_then(|json| store.save(PEER_BOOK_FILE, &json));
+
+        if let Err(e) = result {
+            warn!("Failed to persist peer book to {}: {}", store.location(PEER_BOOK_FILE), e);
+        }
+    }
+}
+
+/// Check that a nickname can be shown in place of a peer ID
+///
+/// Up to [`MAX_NICKNAME_LEN`] characters, no whitespace or control characters
+/// at the ends, and not itself a peer ID.
+pub fn validate_nickname(nickname: &str) -> Result<()> {
+    let invalid = |reason: &str| Err(RiftError::ConfigError(format!("invalid nickname '{}': {}", nickname, reason)));
+    if nickname.trim().is_empty() {
+        return invalid("empty");
+    }
+    if nickname.chars().count() > MAX_NICKNAME_LEN {
+        return invalid(&format!("longer than {} characters", MAX_NICKNAME_LEN));
+    }
+    if nickname.trim() != nickname || nickname.chars().any(char::is_control) {
+        return invalid("leading or trailing whitespace, or control characters");
+    }
+    if nickname.parse::<PeerId>().is_ok() {
+        return invalid("looks like a peer ID");
+    }
+    Ok(())
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::FileStore;
+
+    #[test]
+    fn test_peer_book_persists_addresses() {
+        let dir = tempfile::tempdir().unwrap();
+        let store: Arc<dyn StateStore> = Arc::new(FileStore::new(dir.path().to_path_buf()));
+        let peer = PeerId::random();
+        let addr: Multiaddr = "/ip4/192.168.1.20/udp/4001/quic-v1".parse().unwrap();
+
+        let mut book = PeerBook::open(store.clone());
+        book.record(&peer, std::slice::from_ref(&addr));
+
+        let mut book = PeerBook::open(store.clone());
+        assert_eq!(book.addresses(), vec![(peer, vec![addr.clone()])]);
+        assert!(book.get(&peer).unwrap().nickname.is_none());
+
+        // Nicknames set by hand survive address updates
+        book.peers.get_mut(&peer.to_string()).unwrap().nickname = Some("laptop".to_string());
+        book.persist();
+        let moved: Multiaddr = "/ip4/192.168.1.21/udp/4001/quic-v1".parse().unwrap();
+        book.record(&peer, std::slice::from_ref(&moved));
+
+        let mut book = PeerBook::open(store);
+        assert_eq!(book.get(&peer).unwrap().multiaddrs(), vec![moved]);
+        assert_eq!(book.get(&peer).unwrap().nickname.as_deref(), Some("laptop"));
+
+        book.prune(unix_now() + STALE_AFTER_SECS);
+        assert!(book.is_empty());
+    }
+
+    #[test]
+    fn test_nicknames_set_by_another_process() {
+        let dir = tempfile::tempdir().unwrap();
+        let store: Arc<dyn StateStore> = Arc::new(FileStore::new(dir.path().to_path_buf()));
+        let (alice, bob) = (PeerId::random(), PeerId::random());
+        let addr: Multiaddr = "/ip4/192.168.1.20/udp/4001/quic-v1".parse().unwrap();
+
+        // The daemon's book, loaded before the nicknames were set
+        let mut daemon = PeerBook::open(store.clone());
+
+        let mut cli = PeerBook::open(store.clone());
+        cli.set_nickname(&alice, Some("alice-laptop".to_string())).unwrap();
+        assert!(cli.set_nickname(&bob, Some("alice-laptop".to_string())).is_err());
+        assert!(cli.set_nickname(&bob, Some(" bob".to_string())).is_err());
+        assert!(cli.set_nickname(&bob, Some(alice.to_string())).is_err());
+
+        // Recording addresses does not overwrite the nickname
+        daemon.record(&alice, std::slice::from_ref(&addr));
+        assert_eq!(daemon.nickname(&alice), Some("alice-laptop"));
+        assert!(!daemon.reload_nicknames());
+
+        let book = PeerBook::open(store.clone());
+        assert_eq!(book.find_nickname("alice-laptop"), Some(alice));
+        assert_eq!(book.get(&alice).unwrap().multiaddrs(), vec![addr]);
+
+        cli.set_nickname(&alice, None).unwrap();
+        assert!(daemon.reload_nicknames());
+        assert!(daemon.nicknames().is_empty());
+
+        cli.set_nickname(&bob, Some("bob".to_string())).unwrap();
+        assert_eq!(PeerBook::open(store).nicknames(), HashMap::from([(bob, "bob".to_string())]));
+    }
+}
@@ -0,0 +1,201 @@
+//! Protocol Allowlist
+//!
+//! Decides which Rift sub-protocols a peer may open streams for. Anything not
+//! explicitly allowed is rejected when the stream is accepted. [`PeerAccess`]
+//! decides which peers get that far at all.
+
+use libp2p::{PeerId, StreamProtocol};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+use super::behaviour::{CONTROL_PROTOCOL, ECHO_PROTOCOL, EXIT_PROTOCOL, KV_PROTOCOL, SECRETS_PROTOCOL, SERVICES_PROTOCOL, STRIPE_PROTOCOL, TERM_PROTOCOL, TUNNEL_PROTOCOL};
+
+/// Rift sub-protocols that can be allowed or denied
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RiftProtocol {
+    /// TCP tunnel streams (plain and striped)
+    Tunnel,
+    /// EnvVault secrets exchange
+    Secrets,
+    /// Session control messages (tokens, resumption)
+    Control,
+    /// Echo checks (`rift ping`)
+    Echo,
+    /// Replicated key-value state
+    Kv,
+    /// Shared terminals (`rift term`)
+    Term,
+    /// Exit mode (`rift exit`)
+    Exit,
+    /// Listing what a node shares (`rift connect --list`)
+    Services,
+    /// File transfer
+    File,
+    /// Chat messages
+    Chat,
+    /// Clipboard sync
+    Clipboard,
+}
+
+impl RiftProtocol {
+    /// Map a libp2p stream protocol to its Rift sub-protocol
+    pub fn from_stream_protocol(protocol: &StreamProtocol) -> Option<Self> {
+        if *protocol == TUNNEL_PROTOCOL || *protocol == STRIPE_PROTOCOL {
+            Some(Self::Tunnel)
+        } else if *protocol == SECRETS_PROTOCOL {
+            Some(Self::Secrets)
+        } else if *protocol == CONTROL_PROTOCOL {
+            Some(Self::Control)
+        } else if *protocol == ECHO_PROTOCOL {
+            Some(Self::Echo)
+        } else if *protocol == KV_PROTOCOL {
+            Some(Self::Kv)
+        } else if *protocol == TERM_PROTOCOL {
+            Some(Self::Term)
+        } else if *protocol == EXIT_PROTOCOL {
+            Some(Self::Exit)
+        } else if *protocol == SERVICES_PROTOCOL {
+            Some(Self::Services)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for RiftProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Tunnel => "tunnel",
+            Self::Secrets => "secrets",
+            Self::Control => "control",
+            Self::Echo => "echo",
+            Self::Kv => "kv",
+            Self::Term => "term",
+            Self::Exit => "exit",
+            Self::Services => "services",
+            Self::File => "file",
+            Self::Chat => "chat",
+            Self::Clipboard => "clipboard",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Deny-by-default allowlist of sub-protocols, globally and per peer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProtocolPolicy {
+    /// Protocols any peer may use
+    pub allow: Vec<RiftProtocol>,
+
+    /// Per-peer allowlists (peer ID -> protocols), replacing `allow` for that peer
+    pub peers: HashMap<String, Vec<RiftProtocol>>,
+}
+
+impl Default for ProtocolPolicy {
+    fn default() -> Self {
+        Self {
+            allow: vec![RiftProtocol::Tunnel, RiftProtocol::Secrets, RiftProtocol::Control, RiftProtocol::Echo, RiftProtocol::Kv, RiftProtocol::Term, RiftProtocol::Exit, RiftProtocol::Services],
+            peers: HashMap::new(),
+        }
+    }
+}
+
+impl ProtocolPolicy {
+    /// Check whether a peer may open streams for a protocol
+    pub fn is_allowed(&self, peer_id: &PeerId, protocol: RiftProtocol) -> bool {
+        self.peers
+            .get(&peer_id.to_string())
+            .unwrap_or(&self.allow)
+            .contains(&protocol)
+    }
+}
+
+/// Peers pre-authorized or refused outright, checked before the approval flow
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PeerAccess {
+    /// Peer IDs admitted without approval; when non-empty, everyone else is refused
+    pub allow: Vec<String>,
+
+    /// Peer IDs always refused, even if also on `allow`
+    pub block: Vec<String>,
+}
+
+/// How a peer fares against the [`PeerAccess`] lists
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeerAccessDecision {
+    /// On the allowlist: skip interactive approval
+    Allowed,
+    /// No list applies: the usual approval flow decides
+    Unlisted,
+    /// On the blocklist, or missing from a non-empty allowlist
+    Blocked,
+}
+
+impl PeerAccess {
+    /// Decide what to do with a peer
+    pub fn decide(&self, peer_id: &PeerId) -> PeerAccessDecision {
+        let peer = peer_id.to_string();
+        if self.block.contains(&peer) {
+            PeerAccessDecision::Blocked
+        } else if self.allow.contains(&peer) {
+            PeerAccessDecision::Allowed
+        } else if self.allow.is_empty() {
+            PeerAccessDecision::Unlisted
+        } else {
+            PeerAccessDecision::Blocked
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_per_peer_list_overrides_global() {
+        let restricted = PeerId::random();
+        let other = PeerId::random();
+
+        let mut policy = ProtocolPolicy::default();
+        policy.peers.insert(restricted.to_string(), vec![RiftProtocol::Tunnel]);
+
+        assert!(policy.is_allowed(&restricted, RiftProtocol::Tunnel));
+        assert!(!policy.is_allowed(&restricted, RiftProtocol::Secrets));
+        assert!(policy.is_allowed(&other, RiftProtocol::Secrets));
+        assert!(!policy.is_allowed(&other, RiftProtocol::Clipboard));
+    }
+
+    #[test]
+    fn test_peer_access_lists() {
+        let friend = PeerId::random();
+        let foe = PeerId::random();
+        let stranger = PeerId::random();
+
+        let mut access = PeerAccess::default();
+        assert_eq!(access.decide(&stranger), PeerAccessDecision::Unlisted);
+
+        access.block.push(foe.to_string());
+        assert_eq!(access.decide(&foe), PeerAccessDecision::Blocked);
+        assert_eq!(access.decide(&stranger), PeerAccessDecision::Unlisted);
+
+        access.allow.push(friend.to_string());
+        access.allow.push(foe.to_string());
+        assert_eq!(access.decide(&friend), PeerAccessDecision::Allowed);
+        assert_eq!(access.decide(&foe), PeerAccessDecision::Blocked);
+        assert_eq!(access.decide(&stranger), PeerAccessDecision::Blocked);
+    }
+
+    #[test]
+    fn test_policy_from_toml() {
+        let policy: ProtocolPolicy = toml::from_str("allow = [\"tunnel\"]\n").unwrap();
+        assert_eq!(policy.allow, vec![RiftProtocol::Tunnel]);
+        assert_eq!(
+            RiftProtocol::from_stream_protocol(&SECRETS_PROTOCOL),
+            Some(RiftProtocol::Secrets)
+        );
+    }
+}
@@ -0,0 +1,157 @@
+//! Presence Announcements
+//!
+//! Nodes publish the services they share on a gossipsub topic, so
+//! `rift peers` can list what is available on a team network without links
+//! being passed around by hand. Announcements are repeated periodically and
+//! whenever a peer joins the topic; an empty one withdraws the node's services.
+//!
+//! A node can also be asked directly over `/<namespace>/services/1.0.0`: the
+//! asker opens a stream and gets one [`PresenceAnnouncement`] back, so
+//! `rift connect --list` works without gossipsub or waiting for the next
+//! announcement.
+
+use libp2p::{gossipsub, PeerId};
+use libp2p_stream as stream;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio_util::compat::FuturesAsyncReadCompatExt;
+
+use super::behaviour::Protocols;
+use super::swarm::{receive_secrets, send_secrets};
+use crate::error::{Result, RiftError};
+
+/// Gossipsub topic carrying presence announcements (default namespace)
+pub const PRESENCE_TOPIC: &str = "/rift/presence/1.0.0";
+
+/// How often a sharing node repeats its announcement
+pub const PRESENCE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Largest presence message accepted from a peer
+pub const MAX_PRESENCE_BYTES: usize = 16 * 1024;
+
+/// The presence topic of a protocol namespace
+pub fn presence_topic(protocols: &Protocols) -> gossipsub::IdentTopic {
+    gossipsub::IdentTopic::new(&protocols.presence)
+}
+
+/// A port a node shares, optionally under a name
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SharedService {
+    /// Service name (e.g. an SNI host name), if it has one
+    pub name: Option<String>,
+    /// Port on the sharing node
+    pub port: u16,
+}
+
+impl fmt::Display for SharedService {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "{} (port {})", name, self.port),
+            None => write!(f, "port {}", self.port),
+        }
+    }
+}
+
+/// Message published on the presence topic (signed by the announcing node)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PresenceAnnouncement {
+    /// Everything the node currently shares
+    pub services: Vec<SharedService>,
+}
+
+/// What this node announces and when it is due again
+#[derive(Debug, Default)]
+pub struct PresenceState {
+    services: Vec<SharedService>,
+    announce_at: Option<Instant>,
+}
+
+impl PresenceState {
+    /// Services currently announced
+    pub fn services(&self) -> &[SharedService] {
+        &self.services
+    }
+
+    /// Replace the announced services, making an announcement due
+    pub fn set_services(&mut self, services: Vec<SharedService>) {
+        self.services = services;
+        self.announce_at = None;
+    }
+
+    /// Record a publication; the next one follows after [`PRESENCE_INTERVAL`]
+    pub fn announced(&mut self, now: Instant) {
+        self.announce_at = Some(now + PRESENCE_INTERVAL);
+    }
+
+    /// Whether the services should be published (again)
+    pub fn announcement_due(&self, now: Instant) -> bool {
+        !self.services.is_empty() && self.announce_at.is_none_or(|at| now >= at)
+    }
+}
+
+/// Answer a services query with what this node shares
+pub async fn serve_services<W: AsyncWrite + Unpin>(writer: &mut W, services: &[SharedService]) -> Result<()> {
+    let announcement = PresenceAnnouncement {
+        services: services.to_vec(),
+    };
+    send_secrets(writer, &announcement).await?;
+    writer.shutdown().await?;
+    Ok(())
+}
+
+/// Read the answer to a services query
+pub async fn read_services<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Vec<SharedService>> {
+    let announcement: PresenceAnnouncement = receive_secrets(reader).await?;
+    Ok(announcement.services)
+}
+
+/// Ask a connected peer which services it shares
+pub async fn query_services(
+    control: &mut stream::Control,
+    protocols: &Protocols,
+    peer_id: PeerId,
+) -> Result<Vec<SharedService>> {
+    let stream = control
+        .open_stream(peer_id, protocols.services.clone())
+        .await
+        .map_err(|e| RiftError::StreamError(format!("Failed to open services stream: {:?}", e)))?;
+    read_services(&mut stream.compat()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presence_repeats_announcements() {
+        let now = Instant::now();
+        let mut state = PresenceState::default();
+        assert!(!state.announcement_due(now));
+
+        state.set_services(vec![SharedService { name: None, port: 3000 }]);
+        assert!(state.announcement_due(now));
+        state.announced(now);
+        assert!(!state.announcement_due(now + Duration::from_secs(1)));
+        assert!(state.announcement_due(now + PRESENCE_INTERVAL));
+
+        state.set_services(vec![SharedService {
+            name: Some("api.local".to_string()),
+            port: 3000,
+        }]);
+        assert!(state.announcement_due(now + Duration::from_secs(1)));
+        assert_eq!(state.services()[0].to_string(), "api.local (port 3000)");
+    }
+
+    #[tokio::test]
+    async fn test_services_query_round_trip() {
+        let (mut client, mut server) = tokio::io::duplex(1024);
+        let services = vec![
+            SharedService { name: None, port: 3000 },
+            SharedService { name: Some("api.localhost".to_string()), port: 3443 },
+        ];
+        serve_services(&mut server, &services).await.unwrap();
+        assert_eq!(read_services(&mut client).await.unwrap(), services);
+    }
+}
@@ -0,0 +1,122 @@
+//! Private Networks
+//!
+//! A pre-shared swarm key (libp2p pnet) that every connection has to prove
+//! before anything else is exchanged. Nodes without the key cannot complete a
+//! handshake, so someone who learns a rift:// link still cannot join.
+//!
+//! pnet encrypts a raw byte stream, which QUIC does not expose, so private
+//! nodes talk TCP (noise + yamux) inside the pnet layer instead.
+
+use libp2p::{
+    core::{muxing::StreamMuxerBox, transport::Boxed, upgrade::Version},
+    identity::Keypair,
+    noise, tcp, yamux, PeerId, Transport,
+};
+use std::path::Path;
+use std::time::Duration;
+
+pub use libp2p::pnet::PreSharedKey;
+use libp2p::pnet::PnetConfig;
+
+use super::transport::TransportConfig;
+use crate::error::{Result, RiftError};
+use crate::state::write_private;
+
+/// File name of the swarm key written by `rift identity genkey --swarm`
+pub const SWARM_KEY_FILE: &str = "swarm.key";
+
+/// Generate a new random swarm key
+pub fn generate_swarm_key() -> PreSharedKey {
+    PreSharedKey::new(rand::random())
+}
+
+/// Load a swarm key in the go-ipfs `swarm.key` format
+pub fn load_swarm_key(path: &Path) -> Result<PreSharedKey> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| RiftError::ConfigError(format!("cannot read swarm key {}: {}", path.display(), e)))?;
+    contents
+        .trim()
+        .parse()
+        .map_err(|e| RiftError::ConfigError(format!("invalid swarm key {}: {}", path.display(), e)))
+}
+
+/// Save a swarm key readable only by its owner
+pub fn save_swarm_key(path: &Path, key: &PreSharedKey) -> Result<()> {
+    write_private(path, format!("{}\n", key))
+}
+
+/// TCP transport that only completes handshakes with holders of `key`
+pub fn private_transport(
+    keypair: &Keypair,
+    key: PreSharedKey,
+    config: &TransportConfig,
+) -> std::result::Result<Boxed<(PeerId, StreamMuxerBox)>, Box<dyn std::error::Error + Send + Sync>> {
+    let noise = noise::Config::new(keypair)?;
+    Ok(tcp::tokio::Transport::new(tcp::Config::default().nodelay(true))
+        .and_then(move |socket, _| PnetConfig::new(key).handshake(socket))
+        .upgrade(Version::V1)
+        .authenticate(noise)
+        .multiplex(yamux::Config::default())
+        .timeout(Duration::from_secs(config.connection_timeout_secs))
+        .boxed())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_swarm_key_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(SWARM_KEY_FILE);
+        let key = generate_swarm_key();
+
+        save_swarm_key(&path, &key).unwrap();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(contents.starts_with("/key/swarm/psk/1.0.0/\n/base16/\n"));
+        let loaded = load_swarm_key(&path).unwrap();
+        assert_eq!(loaded.fingerprint().to_string(), key.fingerprint().to_string());
+        assert_ne!(generate_swarm_key().fingerprint().to_string(), key.fingerprint().to_string());
+
+        std::fs::write(&path, "not a key").unwrap();
+        assert!(load_swarm_key(&path).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_transport_only_connects_key_holders() {
+        use libp2p::core::transport::{DialOpts, ListenerId, PortUse, TransportEvent};
+        use libp2p::core::Endpoint;
+        use std::pin::Pin;
+
+        let key = generate_swarm_key();
+        let config = TransportConfig::new().with_connection_timeout(Duration::from_secs(5));
+        let mut listener = private_transport(&Keypair::generate_ed25519(), key, &config).unwrap();
+        listener
+            .listen_on(ListenerId::next(), "/ip4/127.0.0.1/tcp/0".parse().unwrap())
+            .unwrap();
+        let address = loop {
+            let event = futures::future::poll_fn(|cx| Pin::new(&mut listener).poll(cx)).await;
+            if let TransportEvent::NewAddress { listen_addr, .. } = event {
+                break listen_addr;
+            }
+        };
+        tokio::spawn(async move {
+            loop {
+                let event = futures::future::poll_fn(|cx| Pin::new(&mut listener).poll(cx)).await;
+                if let TransportEvent::Incoming { upgrade, .. } = event {
+                    tokio::spawn(upgrade);
+                }
+            }
+        });
+
+        let opts = DialOpts {
+            role: Endpoint::Dialer,
+            port_use: PortUse::New,
+        };
+        let mut member = private_transport(&Keypair::generate_ed25519(), key, &config).unwrap();
+        assert!(member.dial(address.clone(), opts).unwrap().await.is_ok());
+
+        let mut outsider = private_transport(&Keypair::generate_ed25519(), generate_swarm_key(), &config).unwrap();
+        assert!(outsider.dial(address, opts).unwrap().await.is_err());
+    }
+}
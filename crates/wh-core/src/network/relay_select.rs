@@ -0,0 +1,382 @@
+//! Relay Selection
+//!
+//! With two or more relays configured, holding a reservation on every one of
+//! them ties up slots the relays could give to others. Instead the configured
+//! relays are probed: each is asked for a reservation and its connection is
+//! pinged. When the probe window closes, the relay that took the reservation
+//! and answers fastest keeps it and the others are released. A relay at its
+//! reservation limit refuses and is skipped.
+//!
+//! Probes repeat every `relay_selection.interval_secs`. The choice only moves
+//! when another relay is clearly faster or the chosen one lost our
+//! reservation. The latest results are written to the state dir for
+//! `rift relays status`.
+
+use libp2p::{multiaddr::Protocol, Multiaddr, PeerId};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+use crate::error::{Result, RiftError};
+
+/// File name of the latest probe results inside the state directory
+pub const RELAY_PROBES_FILE: &str = "relay_probes.json";
+
+/// How long relays get to answer a probe before one is picked
+pub const PROBE_WINDOW: Duration = Duration::from_secs(10);
+
+/// How much faster (in percent) another relay must be to replace the chosen one
+const SWITCH_MARGIN_PERCENT: u128 = 20;
+
+/// Whether a relay took our reservation in the last probe
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum RelayAvailability {
+    /// No answer yet
+    Pending,
+    /// The relay holds a reservation for us
+    Accepted,
+    /// The relay refused, is full or could not be reached
+    Refused { reason: String },
+}
+
+/// What the last probe of a relay found
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RelayProbe {
+    /// Relay address, ending in `/p2p/<peer id>`
+    pub address: String,
+    /// Average ping round trip to the relay
+    pub rtt_ms: Option<u64>,
+    /// Answer to our reservation
+    pub availability: RelayAvailability,
+}
+
+/// Probe results and the relay they led to, as shown by `rift relays status`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RelayProbeReport {
+    /// Relay keeping our reservation (None = none took one)
+    pub chosen: Option<String>,
+    /// One entry per configured relay
+    pub probes: Vec<RelayProbe>,
+    /// Unix timestamp (seconds) of the decision
+    pub decided_at: u64,
+}
+
+impl RelayProbeReport {
+    /// Read the results written by the last session
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        match std::fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents).map(Some).map_err(|e| {
+                RiftError::ConfigError(format!("unreadable relay probes {}: {}", path.display(), e))
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Outcome of a probe round
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelayDecision {
+    /// Relay keeping our reservation
+    pub chosen: Multiaddr,
+    /// Its average round trip
+    pub rtt: Option<Duration>,
+    /// Whether the choice moved to another relay
+    pub changed: bool,
+    /// Relays whose reservation (or pending request) should be dropped
+    pub release: Vec<PeerId>,
+}
+
+#[derive(Debug)]
+struct Candidate {
+    peer_id: PeerId,
+    address: Multiaddr,
+    rtt: Option<Duration>,
+    availability: RelayAvailability,
+}
+
+/// Probes the configured relays and keeps track of the best one
+#[derive(Debug)]
+pub struct RelaySelector {
+    /// Where the results are written (None = in-memory only)
+    path: Option<PathBuf>,
+    candidates: Vec<Candidate>,
+    chosen: Option<PeerId>,
+    /// End of the running probe round
+    decide_at: Option<Instant>,
+    next_probe: Instant,
+    interval: Duration,
+}
+
+impl RelaySelector {
+    /// Selector over the relays ending in `/p2p/<peer id>`; None unless there are two
+    pub fn new(relays: &[Multiaddr], interval: Duration, path: Option<PathBuf>) -> Option<Self> {
+        let mut candidates: Vec<Candidate> = Vec::new();
+        for address in relays {
+            let Some(Protocol::P2p(peer_id)) = address.iter().last() else {
+                continue;
+            };
+            if candidates.iter().all(|candidate| candidate.peer_id != peer_id) {
+                candidates.push(Candidate {
+                    peer_id,
+                    address: address.clone(),
+                    rtt: None,
+                    availability: RelayAvailability::Pending,
+                });
+            }
+        }
+        (candidates.len() > 1).then(|| Self {
+            path,
+            candidates,
+            chosen: None,
+            decide_at: None,
+            next_probe: Instant::now() + interval,
+            interval,
+        })
+    }
+
+    /// Start a probe round; returns the relays to ask for a reservation
+    ///
+    /// Relays in `held` already hold one and are not asked again.
+    pub fn start_probe(&mut self, now: Instant, held: &HashSet<PeerId>) -> Vec<Multiaddr> {
+        self.decide_at = Some(now + PROBE_WINDOW);
+        let mut ask = Vec::new();
+        for candidate in &mut self.candidates {
+            if held.contains(&candidate.peer_id) {
+                candidate.availability = RelayAvailability::Accepted;
+            } else {
+                candidate.availability = RelayAvailability::Pending;
+                ask.push(candidate.address.clone());
+            }
+        }
+        ask
+    }
+
+    /// Whether the next probe round should start
+    pub fn probe_due(&self, now: Instant) -> bool {
+        self.decide_at.is_none() && now >= self.next_probe
+    }
+
+    /// Whether the running probe round is over
+    pub fn decision_due(&self, now: Instant) -> bool {
+        self.decide_at.is_some_and(|at| now >= at)
+    }
+
+    /// Note a ping round trip to a relay
+    pub fn record_rtt(&mut self, peer_id: &PeerId, rtt: Duration) {
+        if let Some(candidate) = self.candidate_mut(peer_id) {
+            candidate.rtt = Some(rtt);
+        }
+    }
+
+    /// Note the answer to a reservation; losing the chosen relay makes a probe due
+    pub fn record_reservation(&mut self, peer_id: &PeerId, outcome: std::result::Result<(), String>, now: Instant) {
+        let lost_chosen = outcome.is_err() && self.chosen == Some(*peer_id);
+        if let Some(candidate) = self.candidate_mut(peer_id) {
+            candidate.availability = match outcome {
+                Ok(()) => RelayAvailability::Accepted,
+                Err(reason) => RelayAvailability::Refused { reason },
+            };
+        }
+        if lost_chosen {
+            self.chosen = None;
+            self.next_probe = now;
+        }
+    }
+
+    /// Close the probe round and pick a relay
+    ///
+    /// Returns None (and releases nothing) if no relay took a reservation.
+    pub fn decide(&mut self, now: Instant) -> Option<RelayDecision> {
+        self.decide_at = None;
+        self.next_probe = now + self.interval;
+
+        let best = self
+            .candidates
+            .iter()
+            .filter(|candidate| candidate.availability == RelayAvailability::Accepted)
+            .min_by_key(|candidate| candidate.rtt.unwrap_or(Duration::MAX));
+        let current = self
+            .candidates
+            .iter()
+            .find(|candidate| Some(candidate.peer_id) == self.chosen)
+            .filter(|candidate| candidate.availability == RelayAvailability::Accepted);
+        let pick = match (current, best) {
+            (_, None) => None,
+            (Some(current), Some(best)) if !clearly_faster(best.rtt, current.rtt) => Some(current),
+            (_, Some(best)) => Some(best),
+        };
+
+        let decision = pick.map(|pick| RelayDecision {
+            chosen: pick.address.clone(),
+            rtt: pick.rtt,
+            changed: self.chosen != Some(pick.peer_id),
+            release: self
+                .candidates
+                .iter()
+                .map(|candidate| candidate.peer_id)
+                .filter(|peer_id| *peer_id != pick.peer_id)
+                .collect(),
+        });
+        self.chosen = pick.map(|pick| pick.peer_id);
+        self.persist();
+        decision
+    }
+
+    /// The relay keeping our reservation
+    pub fn chosen(&self) -> Option<&Multiaddr> {
+        let chosen = self.chosen?;
+        self.candidates
+            .iter()
+            .find(|candidate| candidate.peer_id == chosen)
+            .map(|candidate| &candidate.address)
+    }
+
+    /// The latest results
+    pub fn report(&self) -> RelayProbeReport {
+        RelayProbeReport {
+            chosen: self.chosen().map(|address| address.to_string()),
+            probes: self
+                .candidates
+                .iter()
+                .map(|candidate| RelayProbe {
+                    address: candidate.address.to_string(),
+                    rtt_ms: candidate.rtt.map(|rtt| rtt.as_millis() as u64),
+                    availability: candidate.availability.clone(),
+                })
+                .collect(),
+            decided_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+        }
+    }
+
+    fn candidate_mut(&mut self, peer_id: &PeerId) -> Option<&mut Candidate> {
+        self.candidates.iter_mut().find(|candidate| candidate.peer_id == *peer_id)
+    }
+
+    fn persist(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+
+        let result = serde_json::to_string_pretty(&self.report())
+            .map_err(RiftError::from)
+            .and_then(|json| {
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(path, json)?;
+                Ok(())
+            });
+
+        if let Err(e) = result {
+            warn!("Failed to persist relay probes to {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Whether `candidate` beats `current` by more than the switch margin
+fn clearly_faster(candidate: Option<Duration>, current: Option<Duration>) -> bool {
+    match (candidate, current) {
+        (Some(candidate), Some(current)) => {
+            candidate.as_millis() * 100 < current.as_millis() * (100 - SWITCH_MARGIN_PERCENT)
+        }
+        (Some(_), None) => true,
+        (None, _) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn relay_addr(port: u16) -> Multiaddr {
+        format!("/ip4/203.0.113.7/udp/{}/quic-v1/p2p/{}", port, PeerId::random())
+            .parse()
+            .unwrap()
+    }
+
+    fn peer_of(address: &Multiaddr) -> PeerId {
+        match address.iter().last() {
+            Some(Protocol::P2p(peer_id)) => peer_id,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_picks_fastest_relay_that_took_a_reservation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(RELAY_PROBES_FILE);
+        let (slow, fast, full) = (relay_addr(4001), relay_addr(4002), relay_addr(4003));
+        assert!(RelaySelector::new(std::slice::from_ref(&slow), Duration::from_secs(300), None).is_none());
+
+        let relays = [slow.clone(), fast.clone(), full.clone()];
+        let mut selector = RelaySelector::new(&relays, Duration::from_secs(300), Some(path.clone())).unwrap();
+        let now = Instant::now();
+        assert_eq!(selector.start_probe(now, &HashSet::new()).len(), 3);
+        assert!(!selector.decision_due(now));
+
+        selector.record_rtt(&peer_of(&slow), Duration::from_millis(120));
+        selector.record_rtt(&peer_of(&fast), Duration::from_millis(30));
+        selector.record_rtt(&peer_of(&full), Duration::from_millis(5));
+        selector.record_reservation(&peer_of(&slow), Ok(()), now);
+        selector.record_reservation(&peer_of(&fast), Ok(()), now);
+        selector.record_reservation(&peer_of(&full), Err("reservation limit".to_string()), now);
+
+        let later = now + PROBE_WINDOW;
+        assert!(selector.decision_due(later));
+        let decision = selector.decide(later).unwrap();
+        assert_eq!(decision.chosen, fast);
+        assert!(decision.changed);
+        assert_eq!(decision.release.len(), 2);
+        assert!(!decision.release.contains(&peer_of(&fast)));
+
+        let report = RelayProbeReport::load(&path).unwrap().unwrap();
+        assert_eq!(report.chosen, Some(fast.to_string()));
+        assert_eq!(report.probes[2].availability, RelayAvailability::Refused { reason: "reservation limit".to_string() });
+        assert!(RelayProbeReport::load(&dir.path().join("missing.json")).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_choice_moves_only_for_a_clearly_faster_relay() {
+        let (a, b) = (relay_addr(4001), relay_addr(4002));
+        let mut selector = RelaySelector::new(&[a.clone(), b.clone()], Duration::from_secs(60), None).unwrap();
+        let now = Instant::now();
+        selector.start_probe(now, &HashSet::new());
+        selector.record_rtt(&peer_of(&a), Duration::from_millis(50));
+        selector.record_rtt(&peer_of(&b), Duration::from_millis(80));
+        selector.record_reservation(&peer_of(&a), Ok(()), now);
+        selector.record_reservation(&peer_of(&b), Ok(()), now);
+        assert_eq!(selector.decide(now).unwrap().chosen, a);
+
+        // Slightly faster is not worth moving the reservation
+        let now = now + Duration::from_secs(60);
+        assert!(selector.probe_due(now));
+        let held = HashSet::from([peer_of(&a)]);
+        assert_eq!(selector.start_probe(now, &held), vec![b.clone()]);
+        selector.record_rtt(&peer_of(&b), Duration::from_millis(45));
+        selector.record_reservation(&peer_of(&b), Ok(()), now);
+        let decision = selector.decide(now).unwrap();
+        assert_eq!(decision.chosen, a);
+        assert!(!decision.changed);
+
+        // Losing the reservation makes a probe due and hands the choice over
+        selector.record_reservation(&peer_of(&a), Err("relay went away".to_string()), now);
+        assert!(selector.probe_due(now));
+        assert_eq!(selector.chosen(), None);
+        selector.start_probe(now, &HashSet::new());
+        selector.record_reservation(&peer_of(&b), Ok(()), now);
+        let decision = selector.decide(now).unwrap();
+        assert_eq!(decision.chosen, b);
+        assert!(decision.changed);
+
+        // Nobody answering releases nothing
+        selector.start_probe(now, &HashSet::new());
+        assert_eq!(selector.decide(now), None);
+    }
+}
@@ -0,0 +1,287 @@
+//! Relay Server
+//!
+//! `rift relay` runs an always-reachable node that relays circuits for peers
+//! behind NATs and, optionally, serves as their rendezvous point, so a team
+//! can self-host NAT traversal instead of relying on public bootstrap nodes.
+//! An optional WebSocket listener serves clients limited to HTTP(S) egress.
+
+use futures::StreamExt;
+use libp2p::{
+    identify, identity::Keypair, ping, relay, rendezvous,
+    swarm::{behaviour::toggle::Toggle, NetworkBehaviour, SwarmEvent},
+    Multiaddr, PeerId, Swarm,
+};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+use super::identity::PeerIdentity;
+use super::private::{load_swarm_key, private_transport};
+use crate::config::{RelayServerConfig, RiftConfig};
+use crate::error::{self, RiftError};
+
+/// Behaviour of a relay server node
+#[derive(NetworkBehaviour)]
+pub struct RelayServerBehaviour {
+    /// Tells clients the address the relay sees them at
+    pub identify: identify::Behaviour,
+    /// Connection liveness
+    pub ping: ping::Behaviour,
+    /// Circuit relay v2 server
+    pub relay: relay::Behaviour,
+    /// Rendezvous point, if enabled
+    pub rendezvous: Toggle<rendezvous::server::Behaviour>,
+}
+
+/// Events reported by the relay server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RelayServerEvent {
+    /// Listening on an address
+    Listening { address: Multiaddr },
+    /// A peer reserved (or renewed) a circuit slot
+    ReservationAccepted { peer_id: PeerId, renewed: bool },
+    /// A reservation was refused because of the limits
+    ReservationDenied { peer_id: PeerId },
+    /// A reservation ran out without being renewed
+    ReservationExpired { peer_id: PeerId },
+    /// A circuit between two peers was opened
+    CircuitOpened { src: PeerId, dst: PeerId },
+    /// A circuit was refused because of the limits or a missing reservation
+    CircuitDenied { src: PeerId, dst: PeerId },
+    /// A circuit was closed
+    CircuitClosed { src: PeerId, dst: PeerId },
+    /// A peer registered at the rendezvous point
+    PeerRegistered { peer_id: PeerId, namespace: String },
+}
+
+/// A self-hosted relay (and rendezvous) server
+pub struct RelayServer {
+    identity: PeerIdentity,
+    config: RelayServerConfig,
+    /// Whether the relay runs in a private network (TCP only)
+    private: bool,
+    swarm: Swarm<RelayServerBehaviour>,
+}
+
+impl RelayServer {
+    /// Create a relay server using the node identity and `relay_server` settings of `config`
+    pub async fn new(config: &RiftConfig) -> error::Result<Self> {
+        let identity = PeerIdentity::for_config(config)?;
+        let local_peer_id = *identity.peer_id();
+        let server_config = config.relay_server.clone();
+        let relay_config = relay_config(&server_config);
+        let enable_rendezvous = server_config.rendezvous;
+        let transport = config.transport.clone();
+        let swarm_key = config.swarm_key().map(|path| load_swarm_key(&path)).transpose()?;
+        let private = swarm_key.is_some();
+        let identify_protocol = config.protocols()?.identify;
+
+        let behaviour = |key: &Keypair| RelayServerBehaviour {
+            identify: identify::Behaviour::new(identify::Config::new(
+                identify_protocol,
+                key.public(),
+            )),
+            ping: ping::Behaviour::new(ping::Config::new()),
+            relay: relay::Behaviour::new(local_peer_id, relay_config),
+            rendezvous: Toggle::from(
+                enable_rendezvous.then(|| rendezvous::server::Behaviour::new(rendezvous::server::Config::default())),
+            ),
+        };
+        let swarm = match swarm_key {
+            Some(swarm_key) => libp2p::SwarmBuilder::with_existing_identity(identity.keypair().clone())
+                .with_tokio()
+                .with_other_transport(|key| private_transport(key, swarm_key, &transport))
+                .map_err(|e| RiftError::NetworkInitialization(e.to_string()))?
+                .with_dns()
+                .map_err(|e| RiftError::NetworkInitialization(e.to_string()))?
+                .with_behaviour(behaviour)
+                .map_err(|e| RiftError::NetworkInitialization(e.to_string()))?
+                .with_swarm_config(|c| transport.apply_to_swarm(c))
+                .build(),
+            None => libp2p::SwarmBuilder::with_existing_identity(identity.keypair().clone())
+                .with_tokio()
+                .with_quic_config(|quic| transport.apply_to_quic(quic))
+                .with_dns()
+                .map_err(|e| RiftError::NetworkInitialization(e.to_string()))?
+                .with_websocket(libp2p::noise::Config::new, libp2p::yamux::Config::default)
+                .await
+                .map_err(|e| RiftError::NetworkInitialization(e.to_string()))?
+                .with_behaviour(behaviour)
+                .map_err(|e| RiftError::NetworkInitialization(e.to_string()))?
+                .with_swarm_config(|c| transport.apply_to_swarm(c))
+                .build(),
+        };
+
+        Ok(Self {
+            identity,
+            config: server_config,
+            private,
+            swarm,
+        })
+    }
+
+    /// Peer ID of the relay
+    pub fn peer_id(&self) -> &PeerId {
+        self.identity.peer_id()
+    }
+
+    /// Listen on the configured port and announce the configured external addresses
+    pub fn start_listening(&mut self) -> error::Result<()> {
+        if self.private {
+            let listen_addr: Multiaddr = format!("/ip4/0.0.0.0/tcp/{}", self.config.listen_port)
+                .parse()
+                .map_err(|e| RiftError::NetworkInitialization(format!("Invalid address: {}", e)))?;
+            self.swarm
+                .listen_on(listen_addr)
+                .map_err(|e| RiftError::NetworkInitialization(e.to_string()))?;
+            if let Ok(addr) = format!("/ip6/::/tcp/{}", self.config.listen_port).parse() {
+                let _ = self.swarm.listen_on(addr);
+            }
+        } else {
+            self.listen_public()?;
+        }
+
+        for addr in &self.config.external_addrs {
+            let addr: Multiaddr = addr
+                .parse()
+                .map_err(|e| RiftError::ConfigError(format!("invalid relay external address '{}': {}", addr, e)))?;
+            self.swarm.add_external_address(addr);
+        }
+        Ok(())
+    }
+
+    /// Listen for QUIC (and WebSocket) clients
+    fn listen_public(&mut self) -> error::Result<()> {
+        let listen_addr: Multiaddr = format!("/ip4/0.0.0.0/udp/{}/quic-v1", self.config.listen_port)
+            .parse()
+            .map_err(|e| RiftError::NetworkInitialization(format!("Invalid address: {}", e)))?;
+        self.swarm
+            .listen_on(listen_addr)
+            .map_err(|e| RiftError::NetworkInitialization(e.to_string()))?;
+        if let Ok(addr) = format!("/ip6/::/udp/{}/quic-v1", self.config.listen_port).parse() {
+            let _ = self.swarm.listen_on(addr);
+        }
+        if self.config.websocket_port != 0 {
+            let ws_addr: Multiaddr = format!("/ip4/0.0.0.0/tcp/{}/ws", self.config.websocket_port)
+                .parse()
+                .map_err(|e| RiftError::NetworkInitialization(format!("Invalid address: {}", e)))?;
+            self.swarm
+                .listen_on(ws_addr)
+                .map_err(|e| RiftError::NetworkInitialization(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// Addresses clients should use to reach the relay
+    pub fn external_addresses(&self) -> Vec<Multiaddr> {
+        self.swarm.external_addresses().cloned().collect()
+    }
+
+    /// Drive the relay until the next reportable event
+    pub async fn next_event(&mut self) -> RelayServerEvent {
+        loop {
+            let event = self.swarm.select_next_some().await;
+            if let Some(event) = self.handle_swarm_event(event) {
+                return event;
+            }
+        }
+    }
+
+    fn handle_swarm_event(&mut self, event: SwarmEvent<RelayServerBehaviourEvent>) -> Option<RelayServerEvent> {
+        match event {
+            SwarmEvent::NewListenAddr { address, .. } => {
+                info!("Relay listening on {}", address);
+                Some(RelayServerEvent::Listening { address })
+            }
+            SwarmEvent::Behaviour(RelayServerBehaviourEvent::Identify(identify::Event::Received { info, .. })) => {
+                // Without configured addresses, trust what clients see us as
+                if self.config.external_addrs.is_empty() {
+                    self.swarm.add_external_address(info.observed_addr);
+                }
+                None
+            }
+            SwarmEvent::Behaviour(RelayServerBehaviourEvent::Relay(event)) => relay_event(event),
+            SwarmEvent::Behaviour(RelayServerBehaviourEvent::Rendezvous(
+                rendezvous::server::Event::PeerRegistered { peer, registration },
+            )) => Some(RelayServerEvent::PeerRegistered {
+                peer_id: peer,
+                namespace: registration.namespace.to_string(),
+            }),
+            SwarmEvent::Behaviour(RelayServerBehaviourEvent::Rendezvous(event)) => {
+                debug!("Rendezvous event: {:?}", event);
+                None
+            }
+            SwarmEvent::IncomingConnectionError { error, .. } => {
+                warn!("Incoming connection failed: {}", error);
+                None
+            }
+            _ => None,
+        }
+    }
+}
+
+fn relay_event(event: relay::Event) -> Option<RelayServerEvent> {
+    match event {
+        relay::Event::ReservationReqAccepted { src_peer_id, renewed } => Some(RelayServerEvent::ReservationAccepted {
+            peer_id: src_peer_id,
+            renewed,
+        }),
+        relay::Event::ReservationReqDenied { src_peer_id } => {
+            Some(RelayServerEvent::ReservationDenied { peer_id: src_peer_id })
+        }
+        relay::Event::ReservationTimedOut { src_peer_id } => {
+            Some(RelayServerEvent::ReservationExpired { peer_id: src_peer_id })
+        }
+        relay::Event::CircuitReqAccepted { src_peer_id, dst_peer_id } => Some(RelayServerEvent::CircuitOpened {
+            src: src_peer_id,
+            dst: dst_peer_id,
+        }),
+        relay::Event::CircuitReqDenied { src_peer_id, dst_peer_id } => Some(RelayServerEvent::CircuitDenied {
+            src: src_peer_id,
+            dst: dst_peer_id,
+        }),
+        relay::Event::CircuitClosed { src_peer_id, dst_peer_id, .. } => Some(RelayServerEvent::CircuitClosed {
+            src: src_peer_id,
+            dst: dst_peer_id,
+        }),
+        event => {
+            debug!("Relay event: {:?}", event);
+            None
+        }
+    }
+}
+
+/// libp2p relay limits for the configured settings, keeping the default rate limiters
+fn relay_config(config: &RelayServerConfig) -> relay::Config {
+    relay::Config {
+        max_reservations: config.max_reservations,
+        max_reservations_per_peer: config.max_reservations_per_peer,
+        reservation_duration: Duration::from_secs(config.reservation_duration_secs),
+        max_circuits: config.max_circuits,
+        max_circuits_per_peer: config.max_circuits_per_peer,
+        max_circuit_duration: Duration::from_secs(config.max_circuit_duration_secs),
+        max_circuit_bytes: config.max_circuit_bytes,
+        ..relay::Config::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relay_config_applies_limits() {
+        let config = RelayServerConfig {
+            max_circuits: 3,
+            max_circuit_duration_secs: 90,
+            max_circuit_bytes: 0,
+            ..RelayServerConfig::default()
+        };
+
+        let relay = relay_config(&config);
+        assert_eq!(relay.max_circuits, 3);
+        assert_eq!(relay.max_circuit_duration, Duration::from_secs(90));
+        assert_eq!(relay.max_circuit_bytes, 0);
+        assert_eq!(relay.max_reservations, config.max_reservations);
+        assert!(!relay.reservation_rate_limiters.is_empty());
+    }
+}
@@ -0,0 +1,113 @@
+//! Rendezvous Point
+//!
+//! Fallback for finding peers that mDNS cannot see. When a rendezvous server
+//! is configured, the node registers its addresses there under a namespace,
+//! and `connect()` asks the server for registrations in that namespace when it
+//! has no address to dial a peer at.
+
+use libp2p::{multiaddr::Protocol, Multiaddr, PeerId};
+use std::time::{Duration, Instant};
+
+use crate::error::{Result, RiftError};
+
+/// Namespace registered under unless configured otherwise
+pub const DEFAULT_RENDEZVOUS_NAMESPACE: &str = "rift";
+
+/// A rendezvous server: its peer ID and where to reach it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RendezvousPoint {
+    pub peer_id: PeerId,
+    pub address: Multiaddr,
+}
+
+impl RendezvousPoint {
+    /// Parse a server address, which must end in `/p2p/<peer id>`
+    pub fn parse(s: &str) -> Result<Self> {
+        let mut address: Multiaddr = s
+            .parse()
+            .map_err(|e| RiftError::ConfigError(format!("invalid rendezvous_server '{}': {}", s, e)))?;
+        match address.pop() {
+            Some(Protocol::P2p(peer_id)) => Ok(Self { peer_id, address }),
+            _ => Err(RiftError::ConfigError(format!(
+                "rendezvous_server '{}' must end in /p2p/<peer id>",
+                s
+            ))),
+        }
+    }
+}
+
+/// Registration and lookup bookkeeping for a rendezvous point
+#[derive(Debug)]
+pub struct RendezvousState {
+    point: RendezvousPoint,
+    renew_at: Option<Instant>,
+    lookup_pending: bool,
+}
+
+impl RendezvousState {
+    /// Track a point we are not registered with yet
+    pub fn new(point: RendezvousPoint) -> Self {
+        Self {
+            point,
+            renew_at: None,
+            lookup_pending: false,
+        }
+    }
+
+    /// The rendezvous server
+    pub fn point(&self) -> &RendezvousPoint {
+        &self.point
+    }
+
+    /// Record a registration valid for `ttl`; it is renewed halfway through
+    pub fn registered(&mut self, ttl: Duration, now: Instant) {
+        self.renew_at = Some(now + ttl / 2);
+    }
+
+    /// Whether an existing registration should be renewed
+    pub fn renewal_due(&self, now: Instant) -> bool {
+        self.renew_at.is_some_and(|renew_at| now >= renew_at)
+    }
+
+    /// Mark a lookup as sent; false if one is already in flight
+    pub fn start_lookup(&mut self) -> bool {
+        !std::mem::replace(&mut self.lookup_pending, true)
+    }
+
+    /// Mark the in-flight lookup as answered
+    pub fn finish_lookup(&mut self) {
+        self.lookup_pending = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_requires_peer_id() {
+        let peer_id = PeerId::random();
+        let point = RendezvousPoint::parse(&format!("/ip4/203.0.113.7/udp/4001/quic-v1/p2p/{}", peer_id)).unwrap();
+        assert_eq!(point.peer_id, peer_id);
+        assert_eq!(point.address.to_string(), "/ip4/203.0.113.7/udp/4001/quic-v1");
+
+        assert!(RendezvousPoint::parse("/ip4/203.0.113.7/udp/4001/quic-v1").is_err());
+        assert!(RendezvousPoint::parse("not an address").is_err());
+    }
+
+    #[test]
+    fn test_renewal_and_lookup_bookkeeping() {
+        let mut state = RendezvousState::new(RendezvousPoint::parse(&format!("/ip4/127.0.0.1/udp/1/quic-v1/p2p/{}", PeerId::random())).unwrap());
+        let now = Instant::now();
+        assert!(!state.renewal_due(now));
+
+        state.registered(Duration::from_secs(7200), now);
+        assert!(!state.renewal_due(now + Duration::from_secs(3599)));
+        assert!(state.renewal_due(now + Duration::from_secs(3600)));
+
+        assert!(state.start_lookup());
+        assert!(!state.start_lookup());
+        state.finish_lookup();
+        assert!(state.start_lookup());
+    }
+}
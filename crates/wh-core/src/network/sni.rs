@@ -0,0 +1,274 @@
+//! TLS SNI Peeking
+//!
+//! Reads the TLS ClientHello at the start of a passthrough stream and extracts
+//! the Server Name Indication, so one share can front several local HTTPS services.
+
+use std::collections::HashMap;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::error::{RiftError, Result};
+
+/// TLS record content type for handshake messages
+const CONTENT_TYPE_HANDSHAKE: u8 = 0x16;
+
+/// Handshake message type for ClientHello
+const HANDSHAKE_CLIENT_HELLO: u8 = 0x01;
+
+/// Extension type for server_name
+const EXTENSION_SERVER_NAME: u16 = 0x0000;
+
+/// Maximum bytes buffered while waiting for a complete ClientHello record
+pub const MAX_CLIENT_HELLO_SIZE: usize = 16 * 1024 + 5;
+
+/// Result of inspecting the first bytes of a stream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SniPeek {
+    /// A ClientHello carrying this server name
+    Found(String),
+    /// A complete ClientHello without a server_name extension
+    NoSni,
+    /// The bytes are not a TLS ClientHello
+    NotTls,
+    /// More bytes are needed to decide
+    Incomplete,
+}
+
+/// Inspect a buffer that starts at the beginning of a TLS connection
+pub fn peek_sni(buf: &[u8]) -> SniPeek {
+    if buf.is_empty() {
+        return SniPeek::Incomplete;
+    }
+    if buf[0] != CONTENT_TYPE_HANDSHAKE {
+        return SniPeek::NotTls;
+    }
+    if buf.len() < 5 {
+        return SniPeek::Incomplete;
+    }
+    if buf[1] != 0x03 {
+        return SniPeek::NotTls;
+    }
+
+    let record_len = u16::from_be_bytes([buf[3], buf[4]]) as usize;
+    if buf.len() < 5 + record_len {
+        return SniPeek::Incomplete;
+    }
+
+    parse_client_hello(&buf[5..5 + record_len]).unwrap_or(SniPeek::NotTls)
+}
+
+/// Parse a handshake record body, returning None if it is malformed
+fn parse_client_hello(record: &[u8]) -> Option<SniPeek> {
+    let mut cur = Cursor::new(record);
+
+    if cur.u8()? != HANDSHAKE_CLIENT_HELLO {
+        return Some(SniPeek::NotTls);
+    }
+    let _handshake_len = cur.u24()?;
+
+    // client_version + random
+    cur.skip(2 + 32)?;
+    // session_id
+    let len = cur.u8()? as usize;
+    cur.skip(len)?;
+    // cipher_suites
+    let len = cur.u16()? as usize;
+    cur.skip(len)?;
+    // compression_methods
+    let len = cur.u8()? as usize;
+    cur.skip(len)?;
+
+    // Extensions are optional in old ClientHellos
+    if cur.remaining() == 0 {
+        return Some(SniPeek::NoSni);
+    }
+
+    let extensions_len = cur.u16()? as usize;
+    let mut extensions = Cursor::new(cur.take(extensions_len)?);
+
+    while extensions.remaining() > 0 {
+        let ext_type = extensions.u16()?;
+        let ext_len = extensions.u16()? as usize;
+        let ext_data = extensions.take(ext_len)?;
+
+        if ext_type == EXTENSION_SERVER_NAME {
+            let mut names = Cursor::new(ext_data);
+            let list_len = names.u16()? as usize;
+            let mut list = Cursor::new(names.take(list_len)?);
+
+            while list.remaining() > 0 {
+                let name_type = list.u8()?;
+                let name_len = list.u16()? as usize;
+                let name = list.take(name_len)?;
+
+                // name_type 0 = host_name
+                if name_type == 0 {
+                    let name = std::str::from_utf8(name).ok()?;
+                    return Some(SniPeek::Found(name.to_ascii_lowercase()));
+                }
+            }
+        }
+    }
+
+    Some(SniPeek::NoSni)
+}
+
+/// Read from a stream until the ClientHello can be inspected
+///
+/// Returns the bytes consumed (which must be forwarded to the upstream first)
+/// together with the server name, if any.
+pub async fn read_client_hello<R: AsyncRead + Unpin>(
+    reader: &mut R,
+) -> Result<(Vec<u8>, Option<String>)> {
+    let mut buf = Vec::with_capacity(1024);
+    let mut chunk = [0u8; 4096];
+
+    loop {
+        match peek_sni(&buf) {
+            SniPeek::Found(name) => return Ok((buf, Some(name))),
+            SniPeek::NoSni | SniPeek::NotTls => return Ok((buf, None)),
+            SniPeek::Incomplete if buf.len() >= MAX_CLIENT_HELLO_SIZE => return Ok((buf, None)),
+            SniPeek::Incomplete => {}
+        }
+
+        let n = reader
+            .read(&mut chunk)
+            .await
+            .map_err(|e| RiftError::StreamError(format!("Failed to read ClientHello: {}", e)))?;
+        if n == 0 {
+            return Ok((buf, None));
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+}
+
+/// Look up the local port for a server name
+///
+/// Exact matches win over wildcard entries of the form `*.example.test`.
+pub fn route_sni(routes: &HashMap<String, u16>, server_name: &str) -> Option<u16> {
+    let server_name = server_name.to_ascii_lowercase();
+
+    if let Some(port) = routes.get(&server_name) {
+        return Some(*port);
+    }
+
+    let (_, parent) = server_name.split_once('.')?;
+    routes.get(&format!("*.{}", parent)).copied()
+}
+
+/// Minimal big-endian reader over a byte slice
+struct Cursor<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.remaining() < len {
+            return None;
+        }
+        let slice = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Some(slice)
+    }
+
+    fn skip(&mut self, len: usize) -> Option<()> {
+        self.take(len).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|b| b[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take(2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u24(&mut self) -> Option<u32> {
+        self.take(3).map(|b| u32::from_be_bytes([0, b[0], b[1], b[2]]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a minimal TLS 1.2 ClientHello record with an optional SNI
+    fn client_hello(server_name: Option<&str>) -> Vec<u8> {
+        let mut extensions = Vec::new();
+        if let Some(name) = server_name {
+            let name = name.as_bytes();
+            let mut sni = Vec::new();
+            sni.extend_from_slice(&((name.len() + 3) as u16).to_be_bytes());
+            sni.push(0);
+            sni.extend_from_slice(&(name.len() as u16).to_be_bytes());
+            sni.extend_from_slice(name);
+
+            extensions.extend_from_slice(&EXTENSION_SERVER_NAME.to_be_bytes());
+            extensions.extend_from_slice(&(sni.len() as u16).to_be_bytes());
+            extensions.extend_from_slice(&sni);
+        }
+
+        let mut body = vec![0x03, 0x03];
+        body.extend_from_slice(&[0u8; 32]);
+        body.push(0); // session_id
+        body.extend_from_slice(&[0x00, 0x02, 0x13, 0x01]); // one cipher suite
+        body.extend_from_slice(&[0x01, 0x00]); // null compression
+        body.extend_from_slice(&(extensions.len() as u16).to_be_bytes());
+        body.extend_from_slice(&extensions);
+
+        let mut handshake = vec![HANDSHAKE_CLIENT_HELLO];
+        handshake.extend_from_slice(&(body.len() as u32).to_be_bytes()[1..]);
+        handshake.extend_from_slice(&body);
+
+        let mut record = vec![CONTENT_TYPE_HANDSHAKE, 0x03, 0x01];
+        record.extend_from_slice(&(handshake.len() as u16).to_be_bytes());
+        record.extend_from_slice(&handshake);
+        record
+    }
+
+    #[test]
+    fn test_peek_sni_found() {
+        let hello = client_hello(Some("API.localhost"));
+        assert_eq!(peek_sni(&hello), SniPeek::Found("api.localhost".to_string()));
+    }
+
+    #[test]
+    fn test_peek_sni_incomplete_and_not_tls() {
+        let hello = client_hello(Some("api.localhost"));
+        assert_eq!(peek_sni(&hello[..10]), SniPeek::Incomplete);
+        assert_eq!(peek_sni(b"GET / HTTP/1.1\r\n"), SniPeek::NotTls);
+        assert_eq!(peek_sni(&client_hello(None)), SniPeek::NoSni);
+    }
+
+    #[test]
+    fn test_route_sni() {
+        let mut routes = HashMap::new();
+        routes.insert("api.localhost".to_string(), 3443);
+        routes.insert("*.dev.test".to_string(), 4443);
+
+        assert_eq!(route_sni(&routes, "api.localhost"), Some(3443));
+        assert_eq!(route_sni(&routes, "web.dev.test"), Some(4443));
+        assert_eq!(route_sni(&routes, "other.localhost"), None);
+    }
+
+    #[tokio::test]
+    async fn test_read_client_hello_returns_prefix() {
+        let hello = client_hello(Some("web.localhost"));
+        let mut data = hello.clone();
+        data.extend_from_slice(b"trailing");
+
+        let mut reader = &data[..];
+        let (prefix, name) = read_client_hello(&mut reader).await.unwrap();
+
+        assert_eq!(name.as_deref(), Some("web.localhost"));
+        assert!(prefix.starts_with(&hello));
+    }
+}
@@ -0,0 +1,268 @@
+//! Striped Transfers
+//!
+//! Splits one TCP connection across several QUIC streams so a single bulk
+//! transfer is not capped by one stream's flow-control window. Each stream
+//! starts with a [`StripeHeader`]; chunks are then dealt round-robin, so the
+//! receiver restores the original order by reading the streams in turn.
+//! Every stream has its own writer with a chunk queued, so one stream waiting
+//! on flow control does not stop the others from sending.
+//!
+//! Chunk framing: `[len: u32 BE][data]`, with a zero length marking the end.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::io;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+
+/// Most streams a single transfer may be striped across
+pub const MAX_STRIPES: u8 = 16;
+
+/// Largest chunk accepted from a peer
+pub const MAX_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Chunks waiting for each stream's writer
+const STRIPE_QUEUE_CHUNKS: usize = 1;
+
+/// Most buffer bytes one striped bridge holds, both directions together
+///
+/// Sending holds the chunk being read plus, per stream, one queued and one
+/// being written; receiving holds one.
+pub fn striped_buffer_bytes(streams: usize, chunk_size: usize) -> u64 {
+    let chunk_size = chunk_size.clamp(1, MAX_CHUNK_SIZE) as u64;
+    (2 + streams as u64 * (STRIPE_QUEUE_CHUNKS as u64 + 1)) * chunk_size
+}
+
+/// Handshake sent at the start of every stream of a striped transfer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StripeHeader {
+    /// Random ID shared by all streams of one transfer
+    pub session: u64,
+    /// Position of this stream in the rotation
+    pub index: u8,
+    /// Number of streams in the transfer
+    pub total: u8,
+    /// Shared port the transfer is for
+    pub port: u16,
+}
+
+impl StripeHeader {
+    /// Check the header describes a usable stripe set
+    pub fn is_valid(&self) -> bool {
+        self.total >= 1 && self.total <= MAX_STRIPES && self.index < self.total
+    }
+}
+
+/// Copy `reader` into `writers`, one chunk per writer in turn
+///
+/// Each writer sends from its own queue, so reading only waits once the
+/// queue of the writer whose turn it is fills up. `progress` is called with
+/// each chunk read.
+pub async fn stripe_copy<R, W>(
+    reader: &mut R,
+    writers: &mut [W],
+    chunk_size: usize,
+    mut progress: impl FnMut(&[u8]),
+) -> io::Result<u64>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let chunk_size = chunk_size.clamp(1, MAX_CHUNK_SIZE);
+    let (queues, chunks): (Vec<_>, Vec<_>) = writers
+        .iter()
+        .map(|_| mpsc::channel::<Vec<u8>>(STRIPE_QUEUE_CHUNKS))
+        .unzip();
+
+    let read = async move {
+        let mut total = 0u64;
+        let mut next = 0;
+        loop {
+            let mut buf = vec![0u8; chunk_size];
+            let n = reader.read(&mut buf).await?;
+            buf.truncate(n);
+            if n > 0 {
+                total += n as u64;
+                progress(&buf);
+            }
+            // An empty chunk is the end marker
+            if queues[next].send(buf).await.is_err() {
+                return Err(io::Error::new(io::ErrorKind::BrokenPipe, "stripe writer stopped"));
+            }
+            if n == 0 {
+                return Ok(total);
+            }
+            next = (next + 1) % queues.len();
+        }
+    };
+    let write = futures::future::try_join_all(writers.iter_mut().zip(chunks).map(|(writer, mut chunks)| async move {
+        while let Some(chunk) = chunks.recv().await {
+            writer.write_all(&(chunk.len() as u32).to_be_bytes()).await?;
+            writer.write_all(&chunk).await?;
+        }
+        writer.shutdown().await
+    }));
+
+    let (total, _) = futures::future::try_join(read, write).await?;
+    Ok(total)
+}
+
+/// Reassemble chunks striped by [`stripe_copy`] into `writer`
+///
+/// `progress` is called with each chunk received.
+pub async fn unstripe_copy<R, W>(
+    readers: &mut [R],
+    writer: &mut W,
+    mut progress: impl FnMut(&[u8]),
+) -> io::Result<u64>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut buf = Vec::new();
+    let mut total = 0u64;
+    let mut next = 0;
+
+    loop {
+        let reader = &mut readers[next];
+        let mut len_buf = [0u8; 4];
+        reader.read_exact(&mut len_buf).await?;
+        let len = u32::from_be_bytes(len_buf) as usize;
+        if len == 0 {
+            break;
+        }
+        if len > MAX_CHUNK_SIZE {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "stripe chunk too large"));
+        }
+
+        buf.resize(len, 0);
+        reader.read_exact(&mut buf).await?;
+        writer.write_all(&buf).await?;
+        total += len as u64;
+        progress(&buf);
+        next = (next + 1) % readers.len();
+    }
+
+    writer.shutdown().await?;
+    Ok(total)
+}
+
+struct PartialSet<S> {
+    started_at: Instant,
+    total: u8,
+    streams: Vec<Option<S>>,
+}
+
+/// Collects the streams of striped transfers until each set is complete
+pub struct StripeAssembler<K, S> {
+    timeout: Duration,
+    pending: HashMap<K, PartialSet<S>>,
+}
+
+impl<K: Eq + Hash + Clone, S> StripeAssembler<K, S> {
+    /// Create an assembler giving up on sets not completed within `timeout`
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Add a stream; returns the full set, in stripe order, once all have arrived
+    ///
+    /// Streams with an invalid header or one disagreeing with the rest of the
+    /// set are dropped.
+    pub fn add(&mut self, key: K, header: StripeHeader, stream: S) -> Option<Vec<S>> {
+        if !header.is_valid() {
+            return None;
+        }
+
+        let set = self.pending.entry(key.clone()).or_insert_with(|| PartialSet {
+            started_at: Instant::now(),
+            total: header.total,
+            streams: std::iter::repeat_with(|| None).take(header.total as usize).collect(),
+        });
+        if set.total != header.total {
+            return None;
+        }
+        set.streams[header.index as usize] = Some(stream);
+
+        if !set.streams.iter().all(Option::is_some) {
+            return None;
+        }
+        self.pending
+            .remove(&key)
+            .map(|set| set.streams.into_iter().flatten().collect())
+    }
+
+    /// Drop sets that have been waiting longer than the timeout
+    pub fn prune(&mut self) {
+        let timeout = self.timeout;
+        self.pending
+            .retain(|_, set| set.started_at.elapsed() <= timeout);
+    }
+
+    /// Number of incomplete sets
+    pub fn pending_count(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_stripe_round_trip() {
+        let data: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        let (mut writers, mut readers): (Vec<_>, Vec<_>) =
+            (0..3).map(|_| tokio::io::duplex(1 << 20)).unzip();
+
+        let sent = stripe_copy(&mut data.as_slice(), &mut writers, 4096, |_| {})
+            .await
+            .unwrap();
+        let mut out = Vec::new();
+        let received = unstripe_copy(&mut readers, &mut out, |_| {}).await.unwrap();
+
+        assert_eq!(sent, data.len() as u64);
+        assert_eq!(received, sent);
+        assert_eq!(out, data);
+    }
+
+    #[tokio::test]
+    async fn test_stalled_stripe_does_not_hold_up_others() {
+        let data = vec![7u8; 64];
+        // Nobody reads the first stream, which only buffers 8 bytes
+        let (stalled, _stalled_reader) = tokio::io::duplex(8);
+        let (open, mut open_reader) = tokio::io::duplex(1 << 16);
+        let mut writers = vec![stalled, open];
+        let copy = tokio::spawn(async move {
+            let _ = stripe_copy(&mut data.as_slice(), &mut writers, 16, |_| {}).await;
+        });
+
+        let mut chunk = [0u8; 4 + 16];
+        tokio::time::timeout(Duration::from_secs(5), open_reader.read_exact(&mut chunk))
+            .await
+            .expect("second stream got its chunk")
+            .unwrap();
+        assert_eq!(&chunk[..4], &16u32.to_be_bytes());
+        copy.abort();
+    }
+
+    #[test]
+    fn test_assembler_waits_for_full_set() {
+        let mut assembler = StripeAssembler::new(Duration::from_secs(10));
+        let header = |index| StripeHeader { session: 7, index, total: 3, port: 3000 };
+
+        assert_eq!(assembler.add("peer", header(2), 'c'), None);
+        assert_eq!(assembler.add("peer", header(0), 'a'), None);
+        assert_eq!(assembler.add("peer", StripeHeader { total: 4, ..header(1) }, 'x'), None);
+        assert_eq!(assembler.add("peer", header(1), 'b'), Some(vec!['a', 'b', 'c']));
+        assert_eq!(assembler.pending_count(), 0);
+
+        assert_eq!(assembler.add("peer", StripeHeader { session: 8, index: 5, total: 2, port: 3000 }, 'z'), None);
+        assert_eq!(assembler.pending_count(), 0);
+    }
+}
@@ -4,23 +4,108 @@
 
 use futures::StreamExt;
 use libp2p::{
-    identify, mdns, ping,
-    swarm::SwarmEvent,
+    autonat, gossipsub, identify, mdns, ping, relay, rendezvous,
+    multiaddr::Protocol,
+    swarm::{dial_opts::DialOpts, ConnectionId, SwarmEvent},
+    core::transport::ListenerId,
     Multiaddr, PeerId, Swarm, Stream,
 };
+#[cfg(feature = "webrtc")]
+use libp2p::core::transport::OptionalTransport;
 use libp2p_stream as stream;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use tokio::net::TcpStream;
 use tokio::sync::{mpsc, RwLock};
 use tokio_util::compat::FuturesAsyncReadCompatExt;
 use tracing::{debug, error, info, warn};
 
-use super::behaviour::{RiftBehaviour, RiftBehaviourEvent, TUNNEL_PROTOCOL, SECRETS_PROTOCOL};
+use super::behaviour::{Protocols, RiftBehaviour, RiftBehaviourEvent};
+use super::capabilities::PeerCapabilities;
+use super::discovery::{DiscoveryCache, DiscoveryChange};
+use super::direct_first::DirectFirst;
+use super::dnsaddr;
+use super::fallback::{Fallback, RelayFallback};
 use super::identity::PeerIdentity;
-use crate::config::RiftConfig;
+use super::known_relays::{relay_of_circuit, KnownRelays, KNOWN_RELAYS_FILE};
+use super::nat::{NatDetector, NatReport, NatType};
+use super::peer_book::PeerBook;
+use super::presence::{presence_topic, PresenceAnnouncement, PresenceState, SharedService, MAX_PRESENCE_BYTES};
+use super::private::{load_swarm_key, private_transport};
+use super::relay_select::{RelaySelector, RELAY_PROBES_FILE};
+use super::rendezvous::{RendezvousPoint, RendezvousState};
+use crate::config::{RiftConfig, DEFAULT_BOOTSTRAP_PEERS};
 use crate::error::{RiftError, Result};
+use crate::events::{EventSender, EventStream};
+use crate::protocol::{decode, TunnelRequest, TunnelResponse, MAX_MESSAGE_BYTES, MAX_TUNNEL_REQUEST_BYTES};
+
+/// Most addresses put in a link by [`PeerNetwork::link_addresses`]
+const MAX_LINK_ADDRESSES: usize = 6;
+
+/// How long an incoming tunnel stream may take to send its [`TunnelRequest`]
+const TUNNEL_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long to keep collecting listen addresses after the first one
+const LISTEN_SETTLE_TIME: Duration = Duration::from_millis(200);
+
+/// Time between two reads of the peer book for nicknames set by other processes
+const NICKNAME_RELOAD_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How traffic to a peer travels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionPath {
+    /// Direct QUIC connection (LAN or hole-punched)
+    Direct,
+    /// Through a circuit relay
+    Relay,
+    /// Straight to the shared port on this machine (local-fastpath)
+    Loopback,
+}
+
+impl ConnectionPath {
+    /// Classify a connection by its remote address
+    pub fn from_address(address: &Multiaddr) -> Self {
+        if address.iter().any(|p| matches!(p, Protocol::P2pCircuit)) {
+            Self::Relay
+        } else {
+            Self::Direct
+        }
+    }
+}
+
+impl fmt::Display for ConnectionPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Direct => "direct",
+            Self::Relay => "relay",
+            Self::Loopback => "loopback",
+        })
+    }
+}
+
+/// Which side opened the first connection to a peer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionDirection {
+    /// The peer dialed us
+    Inbound,
+    /// We dialed the peer
+    Outbound,
+}
+
+impl fmt::Display for ConnectionDirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Inbound => "inbound",
+            Self::Outbound => "outbound",
+        })
+    }
+}
 
 /// Events emitted by the peer network
 #[derive(Debug, Clone)]
@@ -28,11 +113,42 @@ pub enum NetworkEvent {
     /// Listening on an address
     Listening { address: Multiaddr },
     /// Peer connected
-    PeerConnected { peer_id: PeerId },
+    PeerConnected { peer_id: PeerId, path: ConnectionPath },
     /// Peer disconnected  
     PeerDisconnected { peer_id: PeerId },
+    /// Peer found on the local network (first time, or with new addresses)
+    PeerDiscovered { peer_id: PeerId, addresses: Vec<Multiaddr> },
+    /// Peer's mDNS record expired
+    PeerExpired { peer_id: PeerId },
+    /// Peer identified itself (agent version from identify)
+    PeerIdentified { peer_id: PeerId, agent_version: String },
     /// Hole punch succeeded
     HolePunchSucceeded { peer_id: PeerId },
+    /// A direct connection joined a relayed one and answered its first ping;
+    /// new streams now take it. `relayed_rtt` is the relay's average round trip
+    PathUpgraded { peer_id: PeerId, relayed_rtt: Option<Duration>, direct_rtt: Duration },
+    /// Hole punch failed; traffic to the peer goes through `relay`, with `rtt` round trips
+    UsingRelay { peer_id: PeerId, relay: Multiaddr, rtt: Option<Duration> },
+    /// A relay holds a slot for us, so unreachable peers can get to us through it
+    RelayReservationAccepted { relay_peer_id: PeerId, renewal: bool },
+    /// A relay reservation could not be made
+    RelayReservationFailed { relay_peer_id: PeerId, reason: String },
+    /// A relay reservation we held lapsed or its relay went away
+    RelayReservationLost { relay_peer_id: PeerId, reason: String },
+    /// Probing picked another of the configured relays to keep our reservation
+    RelaySelected { relay: Multiaddr, rtt: Option<Duration> },
+    /// We reached a peer through a circuit on this relay
+    RelayCircuitOpened { relay_peer_id: PeerId },
+    /// A peer reached us through one of our relay reservations
+    RelayCircuitAccepted { src_peer_id: PeerId },
+    /// New evidence changed the detected NAT type
+    NatTypeChanged { nat_type: NatType },
+    /// Dialing a peer failed; the next attempt follows after `delay`
+    DialRetrying { peer_id: PeerId, attempt: u32, max_attempts: u32, delay: Duration },
+    /// A ping to a connected peer came back (every 15 seconds per peer)
+    PeerRtt { peer_id: PeerId, rtt: PeerRtt },
+    /// Peer announced what it shares (empty when it stopped sharing)
+    ServicesAnnounced { peer_id: PeerId, services: Vec<SharedService> },
     /// Error occurred
     Error { message: String },
 }
@@ -41,8 +157,96 @@ pub enum NetworkEvent {
 #[derive(Debug, Clone)]
 pub struct PeerInfo {
     pub peer_id: PeerId,
+    /// Listen addresses the peer reported over identify
     pub addresses: Vec<Multiaddr>,
     pub connected_at: Instant,
+    /// Who dialed the first connection
+    pub direction: ConnectionDirection,
+    /// Direct once any connection to the peer is, relay otherwise
+    pub path: ConnectionPath,
+    /// Round-trip times, once the first ping came back
+    pub rtt: Option<PeerRtt>,
+    /// Optional features the peer announced over identify; None until then, or if it announces none
+    pub capabilities: Option<PeerCapabilities>,
+}
+
+/// Round-trip times to a peer, from the ping protocol
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeerRtt {
+    /// Latest round trip
+    pub last: Duration,
+    /// Rolling average, each ping weighing 1/8 (like TCP's smoothed RTT)
+    pub average: Duration,
+}
+
+impl PeerRtt {
+    fn new(rtt: Duration) -> Self {
+        Self { last: rtt, average: rtt }
+    }
+
+    fn record(&mut self, rtt: Duration) {
+        self.last = rtt;
+        self.average = (self.average * 7 + rtt) / 8;
+    }
+}
+
+/// Open connections to each peer and the path of each
+///
+/// libp2p may hold several connections to one peer (e.g. relayed and direct);
+/// only the first opening and last closing matter to the rest of Rift, and
+/// which path the best connection left takes.
+#[derive(Debug, Default)]
+struct ConnectionCounts {
+    connections: HashMap<PeerId, HashMap<ConnectionId, ConnectionPath>>,
+}
+
+impl ConnectionCounts {
+    /// Count a new connection; true if it is the peer's only one
+    fn opened(&mut self, peer_id: PeerId, connection_id: ConnectionId, path: ConnectionPath) -> bool {
+        let connections = self.connections.entry(peer_id).or_default();
+        connections.insert(connection_id, path);
+        connections.len() == 1
+    }
+
+    /// Count a closed connection; true if it was the peer's last one
+    fn closed(&mut self, peer_id: PeerId, connection_id: ConnectionId) -> bool {
+        let Some(connections) = self.connections.get_mut(&peer_id) else {
+            return true;
+        };
+        connections.remove(&connection_id);
+        if connections.is_empty() {
+            self.connections.remove(&peer_id);
+            return true;
+        }
+        false
+    }
+
+    /// Path of the peer's best connection: direct if any of them is
+    fn path(&self, peer_id: &PeerId) -> Option<ConnectionPath> {
+        let connections = self.connections.get(peer_id)?;
+        connections
+            .values()
+            .copied()
+            .find(|path| *path == ConnectionPath::Direct)
+            .or_else(|| connections.values().copied().next())
+    }
+
+    /// Whether a connection is relayed while the peer also has a direct one
+    fn is_detour(&self, peer_id: &PeerId, connection_id: ConnectionId) -> bool {
+        self.connections
+            .get(peer_id)
+            .and_then(|connections| connections.get(&connection_id))
+            .is_some_and(|path| *path == ConnectionPath::Relay)
+            && self.path(peer_id) == Some(ConnectionPath::Direct)
+    }
+}
+
+/// A direct connection opened next to relayed ones, waiting for its first ping
+#[derive(Debug, Clone, Copy)]
+struct PendingUpgrade {
+    connection_id: ConnectionId,
+    /// Average round trip over the relay until then
+    relayed_rtt: Option<Duration>,
 }
 
 /// High-level peer network manager
@@ -55,10 +259,44 @@ pub struct PeerNetwork {
     swarm: Swarm<RiftBehaviour>,
     /// Known peers
     peers: Arc<RwLock<HashMap<PeerId, PeerInfo>>>,
-    /// Event sender
-    event_tx: mpsc::Sender<NetworkEvent>,
+    /// Event sender (primary receiver plus subscribers)
+    event_tx: EventSender<NetworkEvent>,
     /// Event receiver
     event_rx: Option<mpsc::Receiver<NetworkEvent>>,
+    /// Peers already reported by mDNS
+    discovery: DiscoveryCache,
+    /// Open connections per peer, so peer events fire once per peer
+    connections: ConnectionCounts,
+    /// Rendezvous server registration, if one is configured
+    rendezvous: Option<RendezvousState>,
+    /// Relays that worked in earlier sessions
+    known_relays: KnownRelays,
+    /// Addresses of peers seen in earlier sessions
+    peer_book: PeerBook,
+    /// When the peer book's nicknames were last re-read
+    nicknames_read_at: Option<Instant>,
+    /// Ping round-trip times of each connected peer
+    rtts: HashMap<PeerId, PeerRtt>,
+    /// Relayed peers that just got a direct connection, until it is pinged
+    upgrades: HashMap<PeerId, PendingUpgrade>,
+    /// Services announced on the presence topic
+    presence: PresenceState,
+    /// Whether we listen to the presence topic (and dial discovered peers)
+    presence_subscribed: bool,
+    /// Addresses our listeners are bound to
+    listen_addrs: Vec<Multiaddr>,
+    /// Open listeners and the address each was asked to listen on
+    listeners: HashMap<ListenerId, Multiaddr>,
+    /// Evidence on the NAT in front of us
+    nat: NatDetector,
+    /// Relayed connections, for falling back when hole punching fails
+    fallback: RelayFallback,
+    /// Relays currently holding a reservation for us
+    relay_reservations: HashSet<PeerId>,
+    /// Probing of the configured relays, when there are several to pick from
+    relay_selector: Option<RelaySelector>,
+    /// Protocol IDs of our namespace
+    protocols: Protocols,
     /// Running flag
     running: bool,
 }
@@ -66,49 +304,140 @@ pub struct PeerNetwork {
 impl PeerNetwork {
     /// Create a new peer network
     pub async fn new(config: RiftConfig) -> Result<Self> {
-        let identity = PeerIdentity::load_or_generate(&config.identity_path)?;
+        let identity = PeerIdentity::for_config(&config)?;
         let local_peer_id = *identity.peer_id();
         let keypair = identity.keypair().clone();
 
         info!("Local peer ID: {}", local_peer_id);
+        let agent_version = agent_version(&config);
+        let rendezvous_point = config
+            .rendezvous_server
+            .as_deref()
+            .map(RendezvousPoint::parse)
+            .transpose()?;
 
-        // Build the swarm
-        let swarm = libp2p::SwarmBuilder::with_existing_identity(keypair)
-            .with_tokio()
-            .with_quic()
-            .with_relay_client(libp2p::noise::Config::new, libp2p::yamux::Config::default)
-            .map_err(|e| RiftError::NetworkInitialization(e.to_string()))?
-            .with_behaviour(|key, relay| {
-                let identify = identify::Behaviour::new(
-                    identify::Config::new("/rift/id/1.0.0".to_string(), key.public())
-                        .with_agent_version(format!("rift/{}", env!("CARGO_PKG_VERSION")))
-                        .with_push_listen_addr_updates(true),
-                );
+        if config.webrtc.enabled && !cfg!(feature = "webrtc") {
+            warn!("webrtc.enabled is set, but this build has no WebRTC support (feature \"webrtc\")");
+        }
+        let swarm_key = config.swarm_key().map(|path| load_swarm_key(&path)).transpose()?;
+        if let Some(key) = &swarm_key {
+            info!("Private network, swarm key fingerprint {}", key.fingerprint());
+        }
+        #[cfg(feature = "webrtc")]
+        let webrtc_certificate = if config.webrtc.enabled && swarm_key.is_none() {
+            Some(super::webrtc::certificate_for_config(&config)?)
+        } else {
+            None
+        };
 
-                let ping = ping::Behaviour::new(
-                    ping::Config::new()
-                        .with_interval(std::time::Duration::from_secs(15))
-                        .with_timeout(std::time::Duration::from_secs(10)),
-                );
+        let transport = config.transport.clone();
+        let (enable_mdns, enable_relay) = (config.enable_mdns, config.enable_relay);
+        let protocols = config.protocols()?;
+        let identify_protocol = protocols.identify.clone();
+        if !enable_mdns {
+            info!("mDNS disabled, not announcing on the local network");
+        }
+        let behaviour = |key: &libp2p::identity::Keypair,
+                         relay: relay::client::Behaviour|
+         -> std::result::Result<RiftBehaviour, Box<dyn std::error::Error + Send + Sync>> {
+            let identify = identify::Behaviour::new(
+                identify::Config::new(identify_protocol, key.public())
+                    .with_agent_version(agent_version)
+                    .with_push_listen_addr_updates(true),
+            );
+
+            let ping = ping::Behaviour::new(
+                ping::Config::new()
+                    .with_interval(std::time::Duration::from_secs(15))
+                    .with_timeout(std::time::Duration::from_secs(10)),
+            );
+
+            // Without mDNS nothing is broadcast on the LAN, not even a query
+            let mdns = enable_mdns
+                .then(|| mdns::tokio::Behaviour::new(mdns::Config::default(), local_peer_id))
+                .transpose()?;
+            let dcutr = libp2p::dcutr::Behaviour::new(local_peer_id);
+            let autonat = autonat::Behaviour::new(local_peer_id, autonat::Config::default());
+            let stream = DirectFirst::new();
+            let rendezvous = rendezvous::client::Behaviour::new(key.clone());
+            let gossipsub = gossipsub::Behaviour::new(
+                gossipsub::MessageAuthenticity::Signed(key.clone()),
+                gossipsub::ConfigBuilder::default()
+                    .max_transmit_size(MAX_PRESENCE_BYTES)
+                    .build()?,
+            )?;
 
-                let mdns = mdns::tokio::Behaviour::new(mdns::Config::default(), local_peer_id)?;
-                let dcutr = libp2p::dcutr::Behaviour::new(local_peer_id);
-                let stream = stream::Behaviour::new();
-
-                Ok(RiftBehaviour {
-                    identify,
-                    ping,
-                    mdns,
-                    relay,
-                    dcutr,
-                    stream,
-                })
+            Ok(RiftBehaviour {
+                identify,
+                ping,
+                mdns: mdns.into(),
+                relay: enable_relay.then_some(relay).into(),
+                dcutr,
+                autonat,
+                rendezvous,
+                gossipsub,
+                stream,
             })
-            .map_err(|e| RiftError::NetworkInitialization(e.to_string()))?
-            .with_swarm_config(|c| c.with_idle_connection_timeout(std::time::Duration::from_secs(3600))) // 1 hour idle timeout
-            .build();
+        };
 
-        let (event_tx, event_rx) = mpsc::channel(256);
+        // Build the swarm
+        let swarm = match swarm_key {
+            Some(swarm_key) => libp2p::SwarmBuilder::with_existing_identity(keypair)
+                .with_tokio()
+                .with_other_transport(|key| private_transport(key, swarm_key, &transport))
+                .map_err(|e| RiftError::NetworkInitialization(e.to_string()))?
+                .with_dns()
+                .map_err(|e| RiftError::NetworkInitialization(e.to_string()))?
+                .with_relay_client(libp2p::noise::Config::new, libp2p::yamux::Config::default)
+                .map_err(|e| RiftError::NetworkInitialization(e.to_string()))?
+                .with_behaviour(behaviour)
+                .map_err(|e| RiftError::NetworkInitialization(e.to_string()))?
+                .with_swarm_config(|c| transport.apply_to_swarm(c))
+                .build(),
+            None => {
+                let builder = libp2p::SwarmBuilder::with_existing_identity(keypair)
+                    .with_tokio()
+                    .with_quic_config(|quic| transport.apply_to_quic(quic));
+                #[cfg(feature = "webrtc")]
+                let builder = builder
+                    .with_other_transport(|key| match webrtc_certificate {
+                        Some(certificate) => OptionalTransport::some(super::webrtc::transport(key, certificate)),
+                        None => OptionalTransport::none(),
+                    })
+                    .map_err(|e| RiftError::NetworkInitialization(e.to_string()))?;
+                builder
+                    .with_dns()
+                    .map_err(|e| RiftError::NetworkInitialization(e.to_string()))?
+                    .with_websocket(libp2p::noise::Config::new, libp2p::yamux::Config::default)
+                    .await
+                    .map_err(|e| RiftError::NetworkInitialization(e.to_string()))?
+                    .with_relay_client(libp2p::noise::Config::new, libp2p::yamux::Config::default)
+                    .map_err(|e| RiftError::NetworkInitialization(e.to_string()))?
+                    .with_behaviour(behaviour)
+                    .map_err(|e| RiftError::NetworkInitialization(e.to_string()))?
+                    .with_swarm_config(|c| transport.apply_to_swarm(c))
+                    .build()
+            }
+        };
+
+        let (event_tx, event_rx) = EventSender::channel(256);
+        // Throwaway identities leave no trace of where they connected through
+        let (known_relays, peer_book) = if config.ephemeral_identity {
+            (KnownRelays::new(), PeerBook::new())
+        } else {
+            (
+                KnownRelays::load(config.state_dir().join(KNOWN_RELAYS_FILE)),
+                PeerBook::open(config.open_state_store()?),
+            )
+        };
+
+        let relay_selector = if config.enable_relay && config.relay_selection.enabled {
+            let relays: Vec<Multiaddr> = config.relays.iter().filter_map(|relay| relay.parse().ok()).collect();
+            let path = (!config.ephemeral_identity).then(|| config.state_dir().join(RELAY_PROBES_FILE));
+            RelaySelector::new(&relays, config.relay_selection.interval(), path)
+        } else {
+            None
+        };
 
         let mut network = Self {
             identity,
@@ -117,11 +446,52 @@ impl PeerNetwork {
             peers: Arc::new(RwLock::new(HashMap::new())),
             event_tx,
             event_rx: Some(event_rx),
+            discovery: DiscoveryCache::new(Duration::from_secs(config.discovery_ttl_secs)),
+            connections: ConnectionCounts::default(),
+            rendezvous: rendezvous_point.map(RendezvousState::new),
+            known_relays,
+            peer_book,
+            nicknames_read_at: None,
+            rtts: HashMap::new(),
+            upgrades: HashMap::new(),
+            presence: PresenceState::default(),
+            presence_subscribed: false,
+            listen_addrs: Vec::new(),
+            listeners: HashMap::new(),
+            nat: NatDetector::new(),
+            fallback: RelayFallback::new(),
+            relay_reservations: HashSet::new(),
+            relay_selector,
+            protocols,
             running: false,
         };
 
-        // Dial bootstrap peers for relay/DHT connectivity
-        for peer_addr in &config.bootstrap_peers {
+        // Registration happens once the server has told us our external address
+        if let Some(point) = network.rendezvous.as_ref().map(|state| state.point().clone()) {
+            info!("Dialing rendezvous server: {}/p2p/{}", point.address, point.peer_id);
+            network.swarm.add_peer_address(point.peer_id, point.address);
+            if let Err(e) = network.swarm.dial(point.peer_id) {
+                warn!("Failed to dial rendezvous server: {}", e);
+            }
+        }
+
+        // Peers met before can be dialed right away
+        for (peer_id, addresses) in network.peer_book.addresses() {
+            for addr in addresses {
+                network.swarm.add_peer_address(peer_id, addr);
+            }
+        }
+
+        // Dial bootstrap peers for relay/DHT connectivity; relays that worked
+        // before replace the public defaults
+        let use_known_relays = !network.known_relays.is_empty()
+            && config.bootstrap_peers.iter().eq(DEFAULT_BOOTSTRAP_PEERS.iter());
+        let bootstrap_peers: &[String] = if use_known_relays { &[] } else { &config.bootstrap_peers };
+        // The public nodes do not hold the swarm key of a private network
+        let bootstrap_peers = bootstrap_peers.iter().filter(|peer| {
+            !(config.is_private_network() && DEFAULT_BOOTSTRAP_PEERS.contains(&peer.as_str()))
+        });
+        for peer_addr in bootstrap_peers {
             if let Ok(addr) = peer_addr.parse::<Multiaddr>() {
                 info!("Dialing bootstrap peer: {}", addr);
                 let _ = network.swarm.dial(addr);
@@ -131,6 +501,11 @@ impl PeerNetwork {
         Ok(network)
     }
 
+    /// Get our identity
+    pub fn identity(&self) -> &PeerIdentity {
+        &self.identity
+    }
+
     /// Get our peer ID
     pub fn peer_id(&self) -> &PeerId {
         self.identity.peer_id()
@@ -141,19 +516,90 @@ impl PeerNetwork {
         self.identity.to_rift_link()
     }
 
+    /// Where peers can dial us, for embedding in a link
+    ///
+    /// Confirmed external addresses first, then circuits through our relays,
+    /// then the non-loopback addresses we listen on.
+    pub fn link_addresses(&self) -> Vec<Multiaddr> {
+        let circuits = if self.config.enable_relay {
+            self.relay_addresses()
+                .into_iter()
+                .take(2)
+                .map(|relay| relay.with(Protocol::P2pCircuit))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let local = self.listen_addrs.iter().filter(|addr| {
+            !addr.iter().any(|p| match p {
+                Protocol::Ip4(ip) => ip.is_loopback() || ip.is_unspecified(),
+                Protocol::Ip6(ip) => ip.is_loopback() || ip.is_unspecified() || ip.is_unicast_link_local(),
+                Protocol::P2pCircuit => true,
+                _ => false,
+            })
+        });
+
+        let mut addresses: Vec<Multiaddr> = Vec::new();
+        for addr in self.swarm.external_addresses().chain(&circuits).chain(local) {
+            if !addresses.contains(addr) {
+                addresses.push(addr.clone());
+            }
+        }
+        addresses.truncate(MAX_LINK_ADDRESSES);
+        addresses
+    }
+
+    /// Detected NAT type and the evidence behind it
+    ///
+    /// Starts as [`NatType::Unknown`]; [`NetworkEvent::NatTypeChanged`] reports
+    /// each change as connections to peers bring in more evidence.
+    pub fn nat_report(&self) -> NatReport {
+        self.nat.report()
+    }
+
+    /// Re-classify the NAT and report a change
+    async fn update_nat(&mut self) {
+        if let Some(nat_type) = self.nat.update(&self.listen_addrs) {
+            info!("NAT type: {} ({})", nat_type, nat_type.advice());
+            let _ = self.event_tx.send(NetworkEvent::NatTypeChanged { nat_type }).await;
+        }
+    }
+
+    /// Process swarm events until the listeners have their addresses, or `timeout` passes
+    pub async fn wait_for_listen_addrs(&mut self, timeout: Duration) {
+        let _ = tokio::time::timeout(timeout, async {
+            while self.listen_addrs.is_empty() {
+                self.poll_once().await;
+            }
+        })
+        .await;
+        // The other interfaces are reported right after the first
+        let _ = tokio::time::timeout(LISTEN_SETTLE_TIME, async {
+            loop {
+                self.poll_once().await;
+            }
+        })
+        .await;
+    }
+
     /// Take the event receiver
     pub fn take_event_receiver(&mut self) -> mpsc::Receiver<NetworkEvent> {
         self.event_rx.take().expect("Event receiver already taken")
     }
 
-    /// Take incoming streams receiver for handling tunnel connections
+    /// Subscribe to network events; any number of subscribers may exist
+    pub fn events(&self) -> EventStream<NetworkEvent> {
+        self.event_tx.subscribe()
+    }
+
+    /// Take incoming streams receiver for handling tunnel connections (each starts with a TunnelRequest)
     pub fn take_incoming_streams(&mut self) -> stream::IncomingStreams {
         // Clone the control and create new incoming streams
         self.swarm
             .behaviour()
             .stream
             .new_control()
-            .accept(TUNNEL_PROTOCOL)
+            .accept(self.protocols.tunnel.clone())
             .unwrap()
     }
     
@@ -163,7 +609,77 @@ impl PeerNetwork {
             .behaviour()
             .stream
             .new_control()
-            .accept(SECRETS_PROTOCOL)
+            .accept(self.protocols.secrets.clone())
+            .unwrap()
+    }
+
+    /// Take incoming control streams receiver (session tokens, resumption)
+    pub fn take_incoming_control_streams(&mut self) -> stream::IncomingStreams {
+        self.swarm
+            .behaviour()
+            .stream
+            .new_control()
+            .accept(self.protocols.control.clone())
+            .unwrap()
+    }
+
+    /// Get incoming striped tunnel streams (each starts with a StripeHeader)
+    pub fn take_incoming_stripe_streams(&mut self) -> stream::IncomingStreams {
+        self.swarm
+            .behaviour()
+            .stream
+            .new_control()
+            .accept(self.protocols.stripe.clone())
+            .unwrap()
+    }
+
+    /// Get incoming echo streams (`rift ping` checks)
+    pub fn take_incoming_echo_streams(&mut self) -> stream::IncomingStreams {
+        self.swarm
+            .behaviour()
+            .stream
+            .new_control()
+            .accept(self.protocols.echo.clone())
+            .unwrap()
+    }
+
+    /// Get incoming services queries (`rift connect --list`)
+    pub fn take_incoming_services_streams(&mut self) -> stream::IncomingStreams {
+        self.swarm
+            .behaviour()
+            .stream
+            .new_control()
+            .accept(self.protocols.services.clone())
+            .unwrap()
+    }
+
+    /// Get incoming key-value sync streams
+    pub fn take_incoming_kv_streams(&mut self) -> stream::IncomingStreams {
+        self.swarm
+            .behaviour()
+            .stream
+            .new_control()
+            .accept(self.protocols.kv.clone())
+            .unwrap()
+    }
+
+    /// Get incoming shared-terminal streams
+    pub fn take_incoming_term_streams(&mut self) -> stream::IncomingStreams {
+        self.swarm
+            .behaviour()
+            .stream
+            .new_control()
+            .accept(self.protocols.term.clone())
+            .unwrap()
+    }
+
+    /// Get incoming exit-mode streams
+    pub fn take_incoming_exit_streams(&mut self) -> stream::IncomingStreams {
+        self.swarm
+            .behaviour()
+            .stream
+            .new_control()
+            .accept(self.protocols.exit.clone())
             .unwrap()
     }
 
@@ -172,32 +688,445 @@ impl PeerNetwork {
         self.swarm.behaviour().stream.new_control()
     }
 
-    /// Start listening
-    pub async fn start_listening(&mut self) -> Result<Vec<Multiaddr>> {
-        let listen_addr: Multiaddr = format!("/ip4/0.0.0.0/udp/{}/quic-v1", self.config.listen_port)
-            .parse()
-            .map_err(|e| RiftError::NetworkInitialization(format!("Invalid address: {}", e)))?;
+    /// Protocol IDs to open streams with, those of `protocol_namespace`
+    pub fn protocols(&self) -> &Protocols {
+        &self.protocols
+    }
 
+    /// Listen to presence announcements of other nodes
+    ///
+    /// Peers found on the local network are dialed from then on, since
+    /// announcements only reach connected peers.
+    pub fn watch_presence(&mut self) -> Result<()> {
+        if self.presence_subscribed {
+            return Ok(());
+        }
         self.swarm
-            .listen_on(listen_addr)
+            .behaviour_mut()
+            .gossipsub
+            .subscribe(&presence_topic(&self.protocols))
             .map_err(|e| RiftError::NetworkInitialization(e.to_string()))?;
+        self.presence_subscribed = true;
+        let discovered: Vec<PeerId> = self.discovery.peers().map(|(peer_id, _)| *peer_id).collect();
+        for peer_id in discovered {
+            if !self.swarm.is_connected(&peer_id) {
+                let _ = self.swarm.dial(peer_id);
+            }
+        }
+        Ok(())
+    }
+
+    /// Announce the services this node shares; an empty list withdraws them
+    pub fn announce_services(&mut self, services: Vec<SharedService>) -> Result<()> {
+        self.watch_presence()?;
+        let withdrawn = services.is_empty() && !self.presence.services().is_empty();
+        self.presence.set_services(services);
+        if withdrawn || !self.presence.services().is_empty() {
+            self.publish_presence();
+        }
+        Ok(())
+    }
+
+    /// Publish the current services on the presence topic
+    fn publish_presence(&mut self) {
+        self.presence.announced(Instant::now());
+        let announcement = PresenceAnnouncement {
+            services: self.presence.services().to_vec(),
+        };
+        let data = match bincode::serialize(&announcement) {
+            Ok(data) => data,
+            Err(e) => {
+                warn!("Failed to encode presence announcement: {}", e);
+                return;
+            }
+        };
+        match self.swarm.behaviour_mut().gossipsub.publish(presence_topic(&self.protocols), data) {
+            Ok(_) => debug!("Announced {} services", announcement.services.len()),
+            // Nobody to tell yet; the next announcement or a newcomer's subscription retries
+            Err(gossipsub::PublishError::InsufficientPeers) => {}
+            Err(e) => debug!("Failed to publish presence: {}", e),
+        }
+    }
 
-        // Also try IPv6
-        if let Ok(addr) = format!("/ip6/::/udp/{}/quic-v1", self.config.listen_port).parse() {
-            let _ = self.swarm.listen_on(addr);
+    /// Start listening
+    pub async fn start_listening(&mut self) -> Result<Vec<Multiaddr>> {
+        let addrs = listen_addresses(&self.config)?;
+        if !self.config.listen_addrs.is_empty() {
+            let list: Vec<String> = addrs.iter().map(ToString::to_string).collect();
+            info!("Listening only on {}", list.join(", "));
+        }
+        let mut listen_addrs = addrs.into_iter();
+
+        // The first listener (IPv4 QUIC by default) is required; the rest are best effort
+        if let Some(listen_addr) = listen_addrs.next() {
+            let id = self
+                .swarm
+                .listen_on(listen_addr.clone())
+                .map_err(|e| RiftError::NetworkInitialization(e.to_string()))?;
+            self.listeners.insert(id, listen_addr);
+        }
+        for addr in listen_addrs {
+            self.listen(addr);
+        }
+
+        // Public /ws or /wss addresses (e.g. a TLS proxy in front of the listener)
+        for addr in &self.config.websocket.external_addrs {
+            let addr: Multiaddr = addr
+                .parse()
+                .map_err(|e| RiftError::ConfigError(format!("invalid websocket external address '{}': {}", addr, e)))?;
+            self.swarm.add_external_address(addr);
+        }
+
+        // Reserve a slot on each relay so unreachable peers can still get to us
+        if self.config.enable_relay {
+            self.reserve_relay_slots();
         }
 
         self.running = true;
         Ok(self.swarm.listeners().cloned().collect())
     }
 
+    /// Listen on an address, logging failure
+    fn listen(&mut self, addr: Multiaddr) {
+        match self.swarm.listen_on(addr.clone()) {
+            Ok(id) => {
+                self.listeners.insert(id, addr);
+            }
+            Err(e) => warn!("Failed to listen on {}: {}", addr, e),
+        }
+    }
+
+    /// Ask relays for a reservation: every relay, or all configured ones as a probe
+    fn reserve_relay_slots(&mut self) {
+        let relays = match self.relay_selector.as_mut() {
+            Some(selector) => {
+                let relays = selector.start_probe(Instant::now(), &self.relay_reservations);
+                if !relays.is_empty() {
+                    info!("Probing {} relay(s) for the best one", relays.len());
+                }
+                relays
+            }
+            None => self.relay_addresses(),
+        };
+        for relay in relays {
+            info!("Reserving a circuit on relay {}", relay);
+            self.listen(relay.with(Protocol::P2pCircuit));
+        }
+    }
+
+    /// End a probe round: keep the reservation on the best relay, drop the others
+    async fn select_relay(&mut self) {
+        let Some(selector) = self.relay_selector.as_mut() else {
+            return;
+        };
+        let Some(decision) = selector.decide(Instant::now()) else {
+            warn!("No configured relay took a reservation; probing again later");
+            return;
+        };
+
+        // Forgetting the listeners first keeps their closing from being reported as lost reservations
+        let released: Vec<ListenerId> = self
+            .listeners
+            .iter()
+            .filter(|(_, addr)| circuit_relay_peer(addr).is_some_and(|relay| decision.release.contains(&relay)))
+            .map(|(id, _)| *id)
+            .collect();
+        for id in released {
+            self.listeners.remove(&id);
+            self.swarm.remove_listener(id);
+        }
+        for relay_peer_id in &decision.release {
+            self.relay_reservations.remove(relay_peer_id);
+        }
+
+        if decision.changed {
+            info!("Keeping our reservation on relay {} (rtt {:?})", decision.chosen, decision.rtt);
+            let _ = self
+                .event_tx
+                .send(NetworkEvent::RelaySelected { relay: decision.chosen, rtt: decision.rtt })
+                .await;
+        }
+    }
+
+    /// The relay picked by the last probe round, if several are configured
+    pub fn selected_relay(&self) -> Option<&Multiaddr> {
+        self.relay_selector.as_ref().and_then(RelaySelector::chosen)
+    }
+
+    /// Re-listen and re-advertise after the machine woke from sleep
+    ///
+    /// Relay reservations lapse and LAN peers may have moved while suspended:
+    /// reservations are made again, listeners that closed are reopened and
+    /// the next mDNS answer of every LAN peer is treated as new, so it is
+    /// dialed and reported again. Live QUIC listeners
+    /// are kept, since closing one would drop every connection on its socket.
+    /// Returns the connected peers; their connections may be dead too and
+    /// should be checked (see [`super::wake::ping_burst`]).
+    pub fn recover_after_wake(&mut self) -> Vec<PeerId> {
+        let circuits: Vec<ListenerId> = self
+            .listeners
+            .iter()
+            .filter(|(_, addr)| addr.iter().any(|p| p == Protocol::P2pCircuit))
+            .map(|(id, _)| *id)
+            .collect();
+        for id in circuits {
+            self.listeners.remove(&id);
+            self.swarm.remove_listener(id);
+        }
+        self.relay_reservations.clear();
+        if self.config.enable_relay {
+            self.reserve_relay_slots();
+        }
+
+        let open: Vec<Multiaddr> = self.listeners.values().cloned().collect();
+        let addrs = listen_addresses(&self.config).unwrap_or_else(|e| {
+            warn!("Cannot reopen listeners: {}", e);
+            Vec::new()
+        });
+        for addr in addrs {
+            if !open.contains(&addr) {
+                info!("Reopening listener on {}", addr);
+                self.listen(addr);
+            }
+        }
+
+        self.discovery.clear();
+        self.register_with_rendezvous();
+        if !self.presence.services().is_empty() {
+            self.publish_presence();
+        }
+
+        self.swarm.connected_peers().copied().collect()
+    }
+
+    /// Connected peers, longest connected first
+    pub async fn list_peers(&mut self) -> Vec<PeerInfo> {
+        let mut peers: Vec<PeerInfo> = self.peers.read().await.values().cloned().collect();
+        peers.sort_by_key(|peer| peer.connected_at);
+        peers
+    }
+
+    /// Relays currently holding a reservation for us
+    ///
+    /// Empty means peers that cannot dial us directly have no way in.
+    pub fn relay_reservations(&self) -> Vec<PeerId> {
+        self.relay_reservations.iter().copied().collect()
+    }
+
+    /// Ways this node finds peers and gets found, for diagnostics
+    ///
+    /// mDNS and the relay client only count when their behaviour is running.
+    pub fn discovery_mechanisms(&self) -> Vec<String> {
+        let mut mechanisms = Vec::new();
+        if self.swarm.behaviour().mdns.is_enabled() {
+            mechanisms.push("mDNS".to_string());
+        }
+        if let Some(state) = &self.rendezvous {
+            mechanisms.push(format!("rendezvous {}", state.point().peer_id));
+        }
+        if self.swarm.behaviour().relay.is_enabled() {
+            mechanisms.push(format!("relay client ({} relay(s))", self.relay_addresses().len()));
+        }
+        if !self.peer_book.is_empty() {
+            mechanisms.push(format!("peer book ({} peer(s))", self.peer_book.len()));
+        }
+        mechanisms
+    }
+
+    /// Round-trip times to a connected peer, once a ping came back
+    pub fn peer_rtt(&self, peer_id: &PeerId) -> Option<PeerRtt> {
+        self.rtts.get(peer_id).copied()
+    }
+
+    /// Nickname the user gave a peer (`rift peers rename`)
+    ///
+    /// Names set while the node runs are seen within a few seconds.
+    pub fn peer_nickname(&mut self, peer_id: &PeerId) -> Option<String> {
+        self.refresh_nicknames();
+        self.peer_book.nickname(peer_id).map(str::to_string)
+    }
+
+    /// Every peer with a nickname
+    pub fn peer_nicknames(&mut self) -> HashMap<PeerId, String> {
+        self.refresh_nicknames();
+        self.peer_book.nicknames()
+    }
+
+    /// Re-read the peer book's nicknames, at most every [`NICKNAME_RELOAD_INTERVAL`]
+    fn refresh_nicknames(&mut self) {
+        if self.nicknames_read_at.is_some_and(|at| at.elapsed() < NICKNAME_RELOAD_INTERVAL) {
+            return;
+        }
+        self.peer_book.reload_nicknames();
+        self.nicknames_read_at = Some(Instant::now());
+    }
+
+    /// Whether any connection to a peer is open
+    pub fn is_connected(&self, peer_id: &PeerId) -> bool {
+        self.swarm.is_connected(peer_id)
+    }
+
+    /// Close every connection to a peer
+    pub fn disconnect_peer(&mut self, peer_id: PeerId) {
+        let _ = self.swarm.disconnect_peer_id(peer_id);
+    }
+
     /// Connect to a peer by their Rift link
+    ///
+    /// If the peer cannot be dialed yet, its addresses are looked up at the
+    /// rendezvous server (when configured) so that a retry can succeed. Links
+    /// naming a DNS host are resolved through its dnsaddr records first.
+    ///
+    /// A multiaddr ending in `/p2p/<ID>` may be passed instead of a link; it
+    /// is dialed as is, skipping discovery.
     pub async fn connect(&mut self, link: &str) -> Result<PeerId> {
+        let resolved = self.resolve_link(link).await?;
+        let link = resolved.as_str();
         let peer_id = PeerIdentity::parse_rift_link(link)?;
-        self.dial_peer(peer_id).await?;
+        if self.config.enable_relay {
+            for relay in self.relay_addresses() {
+                let circuit = relay.with(Protocol::P2pCircuit).with(Protocol::P2p(peer_id));
+                self.swarm.add_peer_address(peer_id, circuit);
+            }
+        }
+        // Addresses embedded in the link are dialed first, then what the swarm knows
+        let hints = PeerIdentity::link_addresses(link);
+        let dialed = if hints.is_empty() {
+            self.dial_peer(peer_id).await
+        } else {
+            debug!("Dialing {} at {} address(es) from the link", peer_id, hints.len());
+            let opts = libp2p::swarm::dial_opts::DialOpts::peer_id(peer_id)
+                .addresses(hints)
+                .extend_addresses_through_behaviour()
+                .build();
+            self.swarm.dial(opts).map_err(|e| RiftError::DialError(e.to_string()))
+        };
+        if let Err(e) = dialed {
+            self.lookup_via_rendezvous();
+            return Err(e);
+        }
         Ok(peer_id)
     }
 
+    /// A link naming the peer by ID, from a multiaddr or a link naming a DNS host
+    ///
+    /// The returned future does not borrow the network, so it can run in a task.
+    pub fn resolve_link(&self, link: &str) -> impl Future<Output = Result<String>> + Send + 'static {
+        // Owned up front: the swarm is not Sync, so no borrow of it may be held across the lookup
+        let link = match link.parse::<Multiaddr>() {
+            Ok(addr) => PeerIdentity::link_from_multiaddr(&addr),
+            Err(_) => Ok(link.to_string()),
+        };
+        let resolver = self.config.dns_resolver.clone();
+        async move { dnsaddr::resolve_link(&link?, resolver.as_deref()).await }
+    }
+
+    /// Connect to a peer, retrying with backoff while it cannot be dialed yet
+    ///
+    /// Follows `config.dial_retry`. The swarm keeps running between attempts
+    /// so that discovery can find the peer; each retry is announced with
+    /// [`NetworkEvent::DialRetrying`].
+    pub async fn connect_with_retry(&mut self, link: &str) -> Result<PeerId> {
+        // Resolved once, so retries do not repeat the lookup
+        let resolved = self.resolve_link(link).await?;
+        let link = resolved.as_str();
+        let peer_id = PeerIdentity::parse_rift_link(link)?;
+        let policy = self.config.dial_retry.clone();
+        let max_attempts = policy.attempts.max(1);
+        let mut attempt = 1;
+        loop {
+            match self.connect(link).await {
+                Ok(peer_id) => return Ok(peer_id),
+                Err(e) if attempt < max_attempts => {
+                    let delay = policy.jittered_backoff(attempt);
+                    if attempt == 1 {
+                        info!("Waiting for peer discovery...");
+                    }
+                    debug!("Dial attempt {}/{} to {} failed ({}), next in {:?}", attempt, max_attempts, peer_id, e, delay);
+                    let _ = self
+                        .event_tx
+                        .send(NetworkEvent::DialRetrying { peer_id, attempt, max_attempts, delay })
+                        .await;
+                    let _ = tokio::time::timeout(delay, async {
+                        loop {
+                            self.poll_once().await;
+                        }
+                    })
+                    .await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// The selected relay, other configured relays that parse as multiaddrs,
+    /// then known relays best first
+    fn relay_addresses(&self) -> Vec<Multiaddr> {
+        let mut relays: Vec<Multiaddr> = self
+            .config
+            .relays
+            .iter()
+            .filter_map(|relay| match relay.parse::<Multiaddr>() {
+                Ok(addr) => Some(addr),
+                Err(e) => {
+                    warn!("Ignoring invalid relay address '{}': {}", relay, e);
+                    None
+                }
+            })
+            .collect();
+        let selected = self.selected_relay().and_then(|selected| relays.iter().position(|relay| relay == selected));
+        if let Some(index) = selected {
+            relays[..=index].rotate_right(1);
+        }
+        for relay in self.known_relays.preferred() {
+            if !relays.contains(&relay) {
+                relays.push(relay);
+            }
+        }
+        relays
+    }
+
+    /// Remember a relay that just worked, with the latest latency to it
+    fn record_relay_success(&mut self, relay: Multiaddr) {
+        let latency = match relay.iter().last() {
+            Some(Protocol::P2p(relay_peer_id)) => self.rtts.get(&relay_peer_id).map(|rtt| rtt.average),
+            _ => None,
+        };
+        debug!("Relay {} works (latency {:?})", relay, latency);
+        self.known_relays.record_success(&relay, latency);
+    }
+
+    /// Keep a peer reachable through a relay after hole punching failed
+    async fn fall_back_to_relay(&mut self, peer_id: PeerId) {
+        match self.fallback.hole_punch_failed(peer_id) {
+            Fallback::KeepCircuit(relay) => self.report_using_relay(peer_id, relay).await,
+            Fallback::Redial => {
+                let circuits: Vec<Multiaddr> = self
+                    .relay_addresses()
+                    .into_iter()
+                    .map(|relay| relay.with(Protocol::P2pCircuit).with(Protocol::P2p(peer_id)))
+                    .collect();
+                if circuits.is_empty() || !self.config.enable_relay {
+                    self.fallback.forget(&peer_id);
+                    return;
+                }
+                info!("Dialing {} through {} relay(s) instead", peer_id, circuits.len());
+                let opts = DialOpts::peer_id(peer_id).addresses(circuits).build();
+                if let Err(e) = self.swarm.dial(opts) {
+                    debug!("Relayed dial to {} not started: {}", peer_id, e);
+                    self.fallback.forget(&peer_id);
+                }
+            }
+        }
+    }
+
+    async fn report_using_relay(&mut self, peer_id: PeerId, relay: Multiaddr) {
+        let rtt = self.rtts.get(&peer_id).map(|rtt| rtt.average);
+        info!("Traffic to {} goes through relay {} (rtt {:?})", peer_id, relay, rtt);
+        let _ = self.event_tx.send(NetworkEvent::UsingRelay { peer_id, relay, rtt }).await;
+    }
+
     /// Dial a specific peer
     pub async fn dial_peer(&mut self, peer_id: PeerId) -> Result<()> {
         self.swarm
@@ -206,6 +1135,17 @@ impl PeerNetwork {
         Ok(())
     }
 
+    /// Dial a peer at a known address
+    pub async fn dial_peer_at(&mut self, peer_id: PeerId, addr: Multiaddr) -> Result<()> {
+        let opts = libp2p::swarm::dial_opts::DialOpts::peer_id(peer_id)
+            .addresses(vec![addr])
+            .build();
+        self.swarm
+            .dial(opts)
+            .map_err(|e| RiftError::DialError(e.to_string()))?;
+        Ok(())
+    }
+
     /// Add a peer address
     pub fn add_peer_address(&mut self, peer_id: PeerId, addr: Multiaddr) {
         self.swarm.add_peer_address(peer_id, addr);
@@ -216,10 +1156,10 @@ impl PeerNetwork {
         info!("Starting Rift network...");
 
         while self.running {
-            if let Some(event) = self.swarm.next().await {
-                if let Err(e) = self.handle_swarm_event(event).await {
-                    error!("Error handling swarm event: {}", e);
-                }
+            if let Some(event) = self.swarm.next().await
+                && let Err(e) = self.handle_swarm_event(event).await
+            {
+                error!("Error handling swarm event: {}", e);
             }
         }
 
@@ -237,25 +1177,88 @@ impl PeerNetwork {
     }
 
     async fn handle_swarm_event(&mut self, event: SwarmEvent<RiftBehaviourEvent>) -> Result<()> {
+        if self.rendezvous.as_ref().is_some_and(|state| state.renewal_due(Instant::now())) {
+            self.register_with_rendezvous();
+        }
+        if self.presence.announcement_due(Instant::now()) {
+            self.publish_presence();
+        }
+        if self.relay_selector.as_ref().is_some_and(|selector| selector.decision_due(Instant::now())) {
+            self.select_relay().await;
+        } else if self.relay_selector.as_ref().is_some_and(|selector| selector.probe_due(Instant::now())) {
+            self.reserve_relay_slots();
+        }
+
         match event {
             SwarmEvent::NewListenAddr { address, .. } => {
                 info!("Listening on {}", address);
+                self.listen_addrs.push(address.clone());
+                // Browsers need the full address, certificate hash included
+                if address.iter().any(|p| matches!(p, Protocol::WebRTCDirect)) {
+                    info!("Browsers can connect at {}/p2p/{}", address, self.swarm.local_peer_id());
+                }
                 let _ = self.event_tx.send(NetworkEvent::Listening { address }).await;
             }
 
-            SwarmEvent::ConnectionEstablished { peer_id, .. } => {
-                info!("Connected to peer: {}", peer_id);
-                let info = PeerInfo {
-                    peer_id,
-                    addresses: Vec::new(),
-                    connected_at: Instant::now(),
-                };
-                self.peers.write().await.insert(peer_id, info);
-                let _ = self.event_tx.send(NetworkEvent::PeerConnected { peer_id }).await;
+            SwarmEvent::ConnectionEstablished { peer_id, connection_id, endpoint, .. } => {
+                let path = ConnectionPath::from_address(endpoint.get_remote_address());
+                if let Some(relay) = relay_of_circuit(endpoint.get_remote_address()) {
+                    self.record_relay_success(relay);
+                }
+                let fallback_relay = self.fallback.opened(peer_id, endpoint.get_remote_address());
+                let previous_path = self.connections.path(&peer_id);
+                if self.connections.opened(peer_id, connection_id, path) {
+                    info!("Connected to peer: {} ({})", peer_id, path);
+                    let info = PeerInfo {
+                        peer_id,
+                        addresses: Vec::new(),
+                        connected_at: Instant::now(),
+                        direction: if endpoint.is_dialer() { ConnectionDirection::Outbound } else { ConnectionDirection::Inbound },
+                        path,
+                        rtt: None,
+                        capabilities: None,
+                    };
+                    self.peers.write().await.insert(peer_id, info);
+                    let _ = self.event_tx.send(NetworkEvent::PeerConnected { peer_id, path }).await;
+                } else {
+                    debug!("Additional connection to peer: {} ({})", peer_id, path);
+                    // A hole punch adds a direct connection next to the relayed one
+                    if path == ConnectionPath::Direct && previous_path == Some(ConnectionPath::Relay) {
+                        info!("Direct connection to {} next to the relayed one; new streams take it", peer_id);
+                        let relayed_rtt = self.rtts.get(&peer_id).map(|rtt| rtt.average);
+                        self.upgrades.insert(peer_id, PendingUpgrade { connection_id, relayed_rtt });
+                    }
+                    if path == ConnectionPath::Direct
+                        && let Some(peer_info) = self.peers.write().await.get_mut(&peer_id)
+                    {
+                        peer_info.path = path;
+                    }
+                }
+                if let Some(relay) = fallback_relay {
+                    self.report_using_relay(peer_id, relay).await;
+                }
             }
 
-            SwarmEvent::ConnectionClosed { peer_id, .. } => {
+            SwarmEvent::ConnectionClosed { peer_id, connection_id, endpoint, .. } => {
+                self.fallback.closed(peer_id, endpoint.get_remote_address());
+                if self.upgrades.get(&peer_id).is_some_and(|upgrade| upgrade.connection_id == connection_id) {
+                    self.upgrades.remove(&peer_id);
+                }
+                if !self.connections.closed(peer_id, connection_id) {
+                    debug!("Closed one of several connections to peer: {}", peer_id);
+                    // Losing the direct connection leaves the relayed ones to carry new streams
+                    if let Some(path) = self.connections.path(&peer_id)
+                        && let Some(peer_info) = self.peers.write().await.get_mut(&peer_id)
+                        && peer_info.path != path
+                    {
+                        info!("Traffic to {} is back on the {} path", peer_id, path);
+                        peer_info.path = path;
+                        self.rtts.remove(&peer_id);
+                    }
+                    return Ok(());
+                }
                 info!("Disconnected from peer: {}", peer_id);
+                self.rtts.remove(&peer_id);
                 self.peers.write().await.remove(&peer_id);
                 let _ = self.event_tx.send(NetworkEvent::PeerDisconnected { peer_id }).await;
             }
@@ -264,12 +1267,41 @@ impl PeerNetwork {
                 self.handle_behaviour_event(event).await?;
             }
 
-            SwarmEvent::OutgoingConnectionError { peer_id, error, .. } => {
-                if let Some(peer_id) = peer_id {
-                    warn!("Failed to connect to {}: {}", peer_id, error);
+            SwarmEvent::ExpiredListenAddr { address, .. } => {
+                self.listen_addrs.retain(|addr| *addr != address);
+            }
+
+            SwarmEvent::ListenerClosed { listener_id, addresses, reason } => {
+                self.listen_addrs.retain(|addr| !addresses.contains(addr));
+                let Some(addr) = self.listeners.remove(&listener_id) else {
+                    return Ok(());
+                };
+                debug!("Listener on {} closed: {:?}", addr, reason);
+                // Closing a circuit listener ends the reservation behind it
+                if let Some(relay_peer_id) = circuit_relay_peer(&addr) {
+                    let reason = match reason {
+                        Ok(()) => "closed".to_string(),
+                        Err(e) => e.to_string(),
+                    };
+                    if let Some(selector) = self.relay_selector.as_mut() {
+                        selector.record_reservation(&relay_peer_id, Err(reason.clone()), Instant::now());
+                    }
+                    let event = if self.relay_reservations.remove(&relay_peer_id) {
+                        warn!("Lost our reservation on relay {}: {}", relay_peer_id, reason);
+                        NetworkEvent::RelayReservationLost { relay_peer_id, reason }
+                    } else {
+                        warn!("Relay {} refused or never answered our reservation: {}", relay_peer_id, reason);
+                        NetworkEvent::RelayReservationFailed { relay_peer_id, reason }
+                    };
+                    let _ = self.event_tx.send(event).await;
                 }
             }
 
+            SwarmEvent::OutgoingConnectionError { peer_id: Some(peer_id), error, .. } => {
+                warn!("Failed to connect to {}: {}", peer_id, error);
+                self.fallback.forget(&peer_id);
+            }
+
             _ => {}
         }
 
@@ -279,32 +1311,194 @@ impl PeerNetwork {
     async fn handle_behaviour_event(&mut self, event: RiftBehaviourEvent) -> Result<()> {
         match event {
             RiftBehaviourEvent::Mdns(mdns::Event::Discovered(peers)) => {
+                let mut announced: HashMap<PeerId, Vec<Multiaddr>> = HashMap::new();
                 for (peer_id, addr) in peers {
-                    debug!("Discovered peer via mDNS: {} at {}", peer_id, addr);
-                    self.swarm.add_peer_address(peer_id, addr);
+                    announced.entry(peer_id).or_default().push(addr);
+                }
+
+                let now = Instant::now();
+                self.discovery.prune(now);
+                for (peer_id, addrs) in announced {
+                    let Some(change) = self.discovery.observe(peer_id, addrs, now) else {
+                        continue;
+                    };
+                    let addresses = match change {
+                        DiscoveryChange::New(addresses) => {
+                            debug!("Discovered peer via mDNS: {} at {:?}", peer_id, addresses);
+                            addresses
+                        }
+                        DiscoveryChange::AddressesChanged(addresses) => {
+                            debug!("Peer {} announced new addresses: {:?}", peer_id, addresses);
+                            addresses
+                        }
+                    };
+                    for addr in &addresses {
+                        self.swarm.add_peer_address(peer_id, addr.clone());
+                    }
+                    // Presence travels over connections, so connect to everyone we find
+                    if self.presence_subscribed && !self.swarm.is_connected(&peer_id) {
+                        let _ = self.swarm.dial(peer_id);
+                    }
+                    let _ = self.event_tx.send(NetworkEvent::PeerDiscovered { peer_id, addresses }).await;
+                }
+            }
+
+            RiftBehaviourEvent::Mdns(mdns::Event::Expired(peers)) => {
+                let mut expired: Vec<PeerId> = peers.into_iter().map(|(peer_id, _)| peer_id).collect();
+                expired.dedup();
+                for peer_id in expired {
+                    if self.discovery.remove(&peer_id) {
+                        debug!("mDNS record expired for {}", peer_id);
+                        let _ = self.event_tx.send(NetworkEvent::PeerExpired { peer_id }).await;
+                    }
                 }
             }
 
             RiftBehaviourEvent::Identify(identify::Event::Received { peer_id, info, .. }) => {
                 debug!("Identified peer {}: {:?}", peer_id, info.agent_version);
+                // Relays serve every namespace; other Rift nodes only their own
+                let relay = info.protocols.contains(&relay::HOP_PROTOCOL_NAME);
+                if !relay && self.protocols.is_foreign_identify(&info.protocol_version) {
+                    debug!("Disconnecting {}: protocol namespace {}", peer_id, info.protocol_version);
+                    if self.discovery.remove(&peer_id) {
+                        let _ = self.event_tx.send(NetworkEvent::PeerExpired { peer_id }).await;
+                    }
+                    let _ = self.swarm.disconnect_peer_id(peer_id);
+                    return Ok(());
+                }
+                let _ = self.event_tx.send(NetworkEvent::PeerIdentified {
+                    peer_id,
+                    agent_version: info.agent_version.clone(),
+                }).await;
                 for addr in &info.listen_addrs {
                     self.swarm.add_peer_address(peer_id, addr.clone());
                 }
+                self.peer_book.record(&peer_id, &info.listen_addrs);
+                if self.rendezvous.as_ref().is_some_and(|state| state.point().peer_id == peer_id) {
+                    self.swarm.add_external_address(info.observed_addr.clone());
+                    self.register_with_rendezvous();
+                }
                 if let Some(peer_info) = self.peers.write().await.get_mut(&peer_id) {
                     peer_info.addresses = info.listen_addrs;
+                    peer_info.capabilities = PeerCapabilities::from_agent_version(&info.agent_version);
                 }
+                self.nat.observe_address(peer_id, &info.observed_addr);
+                self.update_nat().await;
             }
 
             RiftBehaviourEvent::Dcutr(libp2p::dcutr::Event { remote_peer_id, result }) => {
                 match result {
                     Ok(_) => {
                         info!("Hole punch succeeded with {}", remote_peer_id);
+                        self.nat.hole_punch(true);
                         let _ = self.event_tx.send(NetworkEvent::HolePunchSucceeded { peer_id: remote_peer_id }).await;
                     }
                     Err(e) => {
                         warn!("Hole punch failed with {}: {:?}", remote_peer_id, e);
+                        self.nat.hole_punch(false);
+                        self.fall_back_to_relay(remote_peer_id).await;
+                    }
+                }
+                self.update_nat().await;
+            }
+
+            RiftBehaviourEvent::Autonat(autonat::Event::StatusChanged { new, .. }) => {
+                debug!("AutoNAT status: {:?}", new);
+                self.nat.set_reachable(match new {
+                    autonat::NatStatus::Public(_) => Some(true),
+                    autonat::NatStatus::Private => Some(false),
+                    autonat::NatStatus::Unknown => None,
+                });
+                self.update_nat().await;
+            }
+
+            RiftBehaviourEvent::Rendezvous(event) => self.handle_rendezvous_event(event),
+
+            RiftBehaviourEvent::Ping(ping::Event { peer, connection, result: Ok(_) })
+                if self.connections.is_detour(&peer, connection) =>
+            {
+                // New streams take the direct connection; the relay's round trips don't apply to them
+            }
+
+            RiftBehaviourEvent::Ping(ping::Event { peer, connection, result: Ok(rtt) }) => {
+                let upgrade = self.upgrades.get(&peer).copied().filter(|upgrade| upgrade.connection_id == connection);
+                let rtt = match upgrade {
+                    Some(PendingUpgrade { relayed_rtt, .. }) => {
+                        self.upgrades.remove(&peer);
+                        info!("Traffic to {} upgraded to direct (rtt {:?} -> {:?})", peer, relayed_rtt, rtt);
+                        let _ = self
+                            .event_tx
+                            .send(NetworkEvent::PathUpgraded { peer_id: peer, relayed_rtt, direct_rtt: rtt })
+                            .await;
+                        // The average starts over on the new path
+                        let rtt = PeerRtt::new(rtt);
+                        self.rtts.insert(peer, rtt);
+                        rtt
                     }
+                    None => *self.rtts.entry(peer).and_modify(|known| known.record(rtt)).or_insert(PeerRtt::new(rtt)),
+                };
+                if let Some(selector) = self.relay_selector.as_mut() {
+                    selector.record_rtt(&peer, rtt.average);
+                }
+                if let Some(peer_info) = self.peers.write().await.get_mut(&peer) {
+                    peer_info.rtt = Some(rtt);
+                }
+                let _ = self.event_tx.send(NetworkEvent::PeerRtt { peer_id: peer, rtt }).await;
+            }
+
+            RiftBehaviourEvent::Relay(relay::client::Event::ReservationReqAccepted { relay_peer_id, renewal, .. }) => {
+                if !renewal {
+                    info!("Relay {} accepted our reservation", relay_peer_id);
+                }
+                let relay = self
+                    .relay_addresses()
+                    .into_iter()
+                    .find(|addr| addr.iter().last() == Some(Protocol::P2p(relay_peer_id)));
+                if let Some(relay) = relay {
+                    self.record_relay_success(relay);
+                }
+                self.relay_reservations.insert(relay_peer_id);
+                if let Some(selector) = self.relay_selector.as_mut() {
+                    selector.record_reservation(&relay_peer_id, Ok(()), Instant::now());
                 }
+                let _ = self.event_tx.send(NetworkEvent::RelayReservationAccepted { relay_peer_id, renewal }).await;
+            }
+
+            RiftBehaviourEvent::Relay(relay::client::Event::OutboundCircuitEstablished { relay_peer_id, .. }) => {
+                debug!("Opened a circuit through relay {}", relay_peer_id);
+                let _ = self.event_tx.send(NetworkEvent::RelayCircuitOpened { relay_peer_id }).await;
+            }
+
+            RiftBehaviourEvent::Relay(relay::client::Event::InboundCircuitEstablished { src_peer_id, .. }) => {
+                debug!("{} reached us through a relay", src_peer_id);
+                let _ = self.event_tx.send(NetworkEvent::RelayCircuitAccepted { src_peer_id }).await;
+            }
+
+            RiftBehaviourEvent::Gossipsub(gossipsub::Event::Message { message, .. }) => {
+                if message.topic != presence_topic(&self.protocols).hash() {
+                    return Ok(());
+                }
+                let Some(peer_id) = message.source else {
+                    return Ok(());
+                };
+                match decode::<PresenceAnnouncement>(&message.data) {
+                    Ok(announcement) => {
+                        debug!("{} announced {} services", peer_id, announcement.services.len());
+                        let _ = self.event_tx.send(NetworkEvent::ServicesAnnounced {
+                            peer_id,
+                            services: announcement.services,
+                        }).await;
+                    }
+                    Err(e) => debug!("Ignoring malformed presence message from {}: {}", peer_id, e),
+                }
+            }
+
+            // Newcomers should not wait for the next periodic announcement
+            RiftBehaviourEvent::Gossipsub(gossipsub::Event::Subscribed { peer_id, topic })
+                if topic == presence_topic(&self.protocols).hash() && !self.presence.services().is_empty() =>
+            {
+                debug!("{} joined the presence topic", peer_id);
+                self.publish_presence();
             }
 
             // Stream events are handled separately via incoming_streams
@@ -316,6 +1510,79 @@ impl PeerNetwork {
         Ok(())
     }
 
+    fn handle_rendezvous_event(&mut self, event: rendezvous::client::Event) {
+        let local_peer_id = *self.identity.peer_id();
+        let Some(state) = self.rendezvous.as_mut() else {
+            return;
+        };
+
+        match event {
+            rendezvous::client::Event::Registered { namespace, ttl, .. } => {
+                info!("Registered at rendezvous server under '{}' for {}s", namespace, ttl);
+                state.registered(Duration::from_secs(ttl), Instant::now());
+            }
+            rendezvous::client::Event::RegisterFailed { error, .. } => {
+                warn!("Rendezvous registration failed: {:?}", error);
+            }
+            rendezvous::client::Event::Discovered { registrations, .. } => {
+                state.finish_lookup();
+                for registration in registrations {
+                    let peer_id = registration.record.peer_id();
+                    if peer_id == local_peer_id {
+                        continue;
+                    }
+                    debug!("Rendezvous knows {} at {:?}", peer_id, registration.record.addresses());
+                    for addr in registration.record.addresses() {
+                        self.swarm.add_peer_address(peer_id, addr.clone());
+                    }
+                }
+            }
+            rendezvous::client::Event::DiscoverFailed { error, .. } => {
+                state.finish_lookup();
+                warn!("Rendezvous lookup failed: {:?}", error);
+            }
+            rendezvous::client::Event::Expired { .. } => {}
+        }
+    }
+
+    /// Register (or renew) our addresses at the rendezvous server
+    fn register_with_rendezvous(&mut self) {
+        let Some(state) = self.rendezvous.as_ref() else {
+            return;
+        };
+        let Some(namespace) = self.rendezvous_namespace() else {
+            return;
+        };
+        if let Err(e) = self.swarm.behaviour_mut().rendezvous.register(namespace, state.point().peer_id, None) {
+            warn!("Cannot register at rendezvous server: {}", e);
+        }
+    }
+
+    /// Ask the rendezvous server for the peers registered in our namespace
+    fn lookup_via_rendezvous(&mut self) {
+        let Some(namespace) = self.rendezvous_namespace() else {
+            return;
+        };
+        let Some(state) = self.rendezvous.as_mut() else {
+            return;
+        };
+        if state.start_lookup() {
+            debug!("Looking up peers at the rendezvous server");
+            let server = state.point().peer_id;
+            self.swarm.behaviour_mut().rendezvous.discover(Some(namespace), None, None, server);
+        }
+    }
+
+    fn rendezvous_namespace(&self) -> Option<rendezvous::Namespace> {
+        match rendezvous::Namespace::new(self.config.rendezvous_namespace.clone()) {
+            Ok(namespace) => Some(namespace),
+            Err(e) => {
+                warn!("Invalid rendezvous namespace '{}': {}", self.config.rendezvous_namespace, e);
+                None
+            }
+        }
+    }
+
     /// Shutdown the network
     pub async fn shutdown(&mut self) {
         info!("Shutting down Rift network...");
@@ -323,23 +1590,176 @@ impl PeerNetwork {
     }
 }
 
-/// Open a new outgoing stream to a peer for tunneling
+/// Marker appended to the agent version by peers using a throwaway identity
+///
+/// Self-declared: it is a courtesy to hosts, not something a peer can be held to.
+pub const EPHEMERAL_AGENT_MARKER: &str = "(ephemeral)";
+
+/// Prefix of the named services (`services=db:5432,web:3000`) in the agent version
+const SERVICES_AGENT_PREFIX: &str = "services=";
+
+/// Identify agent version announced by this node, with its capabilities
+fn agent_version(config: &RiftConfig) -> String {
+    let mut base = match &config.agent_version {
+        Some(agent_version) => agent_version.clone(),
+        None => format!("rift/{}", env!("CARGO_PKG_VERSION")),
+    };
+    if !config.service_names.is_empty() {
+        let mut services: Vec<String> = config
+            .service_names
+            .iter()
+            .map(|(name, port)| format!("{}:{}", name, port))
+            .collect();
+        services.sort();
+        base = format!("{} {}{}", base, SERVICES_AGENT_PREFIX, services.join(","));
+    }
+    base = format!("{} {}", base, PeerCapabilities::for_config(config).agent_tokens());
+    if config.ephemeral_identity {
+        format!("{} {}", base, EPHEMERAL_AGENT_MARKER)
+    } else {
+        base
+    }
+}
+
+/// Named services a peer announces in its agent version
+pub fn advertised_services(agent_version: &str) -> Vec<SharedService> {
+    let Some(list) = agent_version.split(' ').find_map(|part| part.strip_prefix(SERVICES_AGENT_PREFIX)) else {
+        return Vec::new();
+    };
+    list.split(',')
+        .filter_map(|entry| {
+            let (name, port) = entry.rsplit_once(':')?;
+            Some(SharedService {
+                name: Some(name.to_string()),
+                port: port.parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+/// Whether an agent version announces a throwaway identity
+pub fn is_ephemeral_agent(agent_version: &str) -> bool {
+    agent_version.ends_with(EPHEMERAL_AGENT_MARKER)
+}
+
+/// The relay behind a circuit listener address (`<relay>/p2p/<id>/p2p-circuit`)
+fn circuit_relay_peer(address: &Multiaddr) -> Option<PeerId> {
+    if !address.iter().any(|p| matches!(p, Protocol::P2pCircuit)) {
+        return None;
+    }
+    address
+        .iter()
+        .take_while(|p| !matches!(p, Protocol::P2pCircuit))
+        .filter_map(|p| match p {
+            Protocol::P2p(peer_id) => Some(peer_id),
+            _ => None,
+        })
+        .last()
+}
+
+/// Open a new outgoing stream to a peer for tunneling to its shared `port`
+///
+/// The stream starts with a [`TunnelRequest`] naming the port, so one
+/// connection can carry tunnels to every port the peer shares.
 pub async fn open_tunnel_stream(
     control: &mut stream::Control,
+    protocols: &Protocols,
+    peer_id: PeerId,
+    port: u16,
+) -> Result<Stream> {
+    open_tunnel_stream_with(control, protocols, peer_id, &TunnelRequest::new(port)).await
+}
+
+/// Like [`open_tunnel_stream`], starting the stream with a prepared request
+/// (e.g. one naming a service instead of a port)
+///
+/// Waits for the host's [`TunnelResponse`]; a rejection fails with
+/// [`RiftError::TunnelRejected`] and its reason.
+pub async fn open_tunnel_stream_with(
+    control: &mut stream::Control,
+    protocols: &Protocols,
     peer_id: PeerId,
+    request: &TunnelRequest,
 ) -> Result<Stream> {
-    control
-        .open_stream(peer_id, TUNNEL_PROTOCOL)
+    let stream = control
+        .open_stream(peer_id, protocols.tunnel.clone())
+        .await
+        .map_err(|e| RiftError::StreamError(format!("Failed to open stream: {:?}", e)))?;
+    let mut stream = stream.compat();
+    send_secrets(&mut stream, request).await?;
+    let response: TunnelResponse = receive_secrets(&mut stream).await?;
+    if !response.accepted {
+        return Err(RiftError::TunnelRejected(response.reason.unwrap_or_else(|| "no reason given".to_string())));
+    }
+    Ok(stream.into_inner())
+}
+
+/// Read the [`TunnelRequest`] an incoming tunnel stream starts with
+///
+/// The peer waits for an answer: send one with [`answer_tunnel_request`]
+/// before bridging (as [`bridge_stream_to_tcp`] does), or when refusing.
+/// Requests over [`MAX_TUNNEL_REQUEST_BYTES`] or slower than
+/// [`TUNNEL_REQUEST_TIMEOUT`] fail, as the peer is not approved yet.
+pub async fn read_tunnel_request(stream: Stream) -> Result<(TunnelRequest, Stream)> {
+    let mut stream = stream.compat();
+    let request = tokio::time::timeout(TUNNEL_REQUEST_TIMEOUT, receive_limited(&mut stream, MAX_TUNNEL_REQUEST_BYTES))
         .await
-        .map_err(|e| RiftError::StreamError(format!("Failed to open stream: {:?}", e)))
+        .map_err(|_| RiftError::StreamError("Timed out waiting for the tunnel request".to_string()))??;
+    Ok((request, stream.into_inner()))
+}
+
+/// Answer the [`TunnelRequest`] of an incoming tunnel stream
+///
+/// After an acceptance the rest of the stream is the tunnelled connection.
+pub async fn answer_tunnel_request(stream: Stream, response: &TunnelResponse) -> Result<Stream> {
+    let mut stream = stream.compat();
+    send_secrets(&mut stream, response).await?;
+    Ok(stream.into_inner())
+}
+
+/// Open `count` striped tunnel streams to a peer's shared `port`, sending each its header
+///
+/// Fails if the peer does not support striping; callers fall back to
+/// [`open_tunnel_stream`].
+pub async fn open_stripe_streams(
+    control: &mut stream::Control,
+    protocols: &Protocols,
+    peer_id: PeerId,
+    port: u16,
+    count: u8,
+) -> Result<Vec<Stream>> {
+    let count = count.clamp(1, super::stripe::MAX_STRIPES);
+    let session = rand::random::<u64>();
+    let mut streams = Vec::with_capacity(count as usize);
+
+    for index in 0..count {
+        let stream = control
+            .open_stream(peer_id, protocols.stripe.clone())
+            .await
+            .map_err(|e| RiftError::StreamError(format!("Failed to open stripe stream: {:?}", e)))?;
+        let mut stream = stream.compat();
+        let header = super::stripe::StripeHeader { session, index, total: count, port };
+        send_secrets(&mut stream, &header).await?;
+        streams.push(stream.into_inner());
+    }
+    Ok(streams)
 }
 
 /// Bridge a QUIC stream to a local TCP connection
 /// This is the core tunnel logic - just pump bytes bidirectionally
+///
+/// Answers the stream's tunnel request first: accepted once the local port
+/// is connected, rejected with the error otherwise.
 pub async fn bridge_stream_to_tcp(stream: Stream, target_port: u16) -> Result<()> {
-    let tcp = TcpStream::connect(format!("127.0.0.1:{}", target_port))
-        .await
-        .map_err(|e| RiftError::ProxyError(format!("Failed to connect to local port {}: {}", target_port, e)))?;
+    let tcp = match TcpStream::connect(format!("127.0.0.1:{}", target_port)).await {
+        Ok(tcp) => tcp,
+        Err(e) => {
+            let reason = format!("Failed to connect to local port {}: {}", target_port, e);
+            let _ = answer_tunnel_request(stream, &TunnelResponse::rejected(reason.clone())).await;
+            return Err(RiftError::ProxyError(reason));
+        }
+    };
+    let stream = answer_tunnel_request(stream, &TunnelResponse::accepted(0)).await?;
 
     // Convert futures AsyncRead/Write to tokio AsyncRead/Write using compat
     let stream = stream.compat();
@@ -368,6 +1788,73 @@ pub async fn bridge_stream_to_tcp(stream: Stream, target_port: u16) -> Result<()
     Ok(())
 }
 
+/// Addresses to listen on: QUIC over IPv4 first, then IPv6, then WebSocket if enabled
+/// Addresses the node listens on, from `listen_addrs` (every interface when empty)
+///
+/// Fails on entries that are neither an IP, a multiaddr nor the name of an
+/// interface with an address.
+pub fn listen_addresses(config: &RiftConfig) -> Result<Vec<Multiaddr>> {
+    let mut addrs = Vec::new();
+    let mut ips = Vec::new();
+    for entry in &config.listen_addrs {
+        let entry = entry.trim();
+        if entry.starts_with('/') {
+            let addr = entry
+                .parse()
+                .map_err(|e| RiftError::ConfigError(format!("invalid listen address '{}': {}", entry, e)))?;
+            addrs.push(addr);
+        } else if let Ok(ip) = entry.parse::<IpAddr>() {
+            ips.push(ip);
+        } else {
+            let interface_ips = interface_addresses(entry)?;
+            if interface_ips.is_empty() {
+                return Err(RiftError::ConfigError(format!(
+                    "listen address '{}' is neither an IP, a multiaddr nor an interface with an address",
+                    entry
+                )));
+            }
+            ips.extend(interface_ips);
+        }
+    }
+    let wildcard = config.listen_addrs.is_empty();
+    if wildcard {
+        ips = vec![Ipv4Addr::UNSPECIFIED.into(), Ipv6Addr::UNSPECIFIED.into()];
+    }
+
+    // Private networks run over TCP only
+    if config.is_private_network() {
+        addrs.extend(ips.iter().map(|ip| Multiaddr::from(*ip).with(Protocol::Tcp(config.listen_port))));
+        return Ok(addrs);
+    }
+    for ip in &ips {
+        addrs.push(Multiaddr::from(*ip).with(Protocol::Udp(config.listen_port)).with(Protocol::QuicV1));
+    }
+    // By default WebSocket and WebRTC only listen on IPv4
+    let extra_ips: Vec<IpAddr> = ips.into_iter().filter(|ip| !(wildcard && ip.is_ipv6())).collect();
+    if config.websocket.enabled {
+        for ip in &extra_ips {
+            addrs.push(Multiaddr::from(*ip).with(Protocol::Tcp(config.websocket.port)).with(Protocol::Ws("/".into())));
+        }
+    }
+    if cfg!(feature = "webrtc") && config.webrtc.enabled {
+        for ip in &extra_ips {
+            addrs.push(Multiaddr::from(*ip).with(Protocol::Udp(config.webrtc.port)).with(Protocol::WebRTCDirect));
+        }
+    }
+    Ok(addrs)
+}
+
+/// Addresses of the network interface called `name`
+fn interface_addresses(name: &str) -> Result<Vec<IpAddr>> {
+    let interfaces = if_addrs::get_if_addrs()
+        .map_err(|e| RiftError::ConfigError(format!("cannot list network interfaces: {}", e)))?;
+    Ok(interfaces
+        .into_iter()
+        .filter(|interface| interface.name == name)
+        .map(|interface| interface.ip())
+        .collect())
+}
+
 /// Send a serializable message with length prefix
 pub async fn send_secrets<T: serde::Serialize, W: tokio::io::AsyncWrite + Unpin>(
     writer: &mut W,
@@ -389,6 +1876,14 @@ pub async fn send_secrets<T: serde::Serialize, W: tokio::io::AsyncWrite + Unpin>
 /// Receive a deserializable message with length prefix
 pub async fn receive_secrets<T: serde::de::DeserializeOwned, R: tokio::io::AsyncRead + Unpin>(
     reader: &mut R,
+) -> Result<T> {
+    receive_limited(reader, MAX_MESSAGE_BYTES).await
+}
+
+/// Receive a length-prefixed message of at most `max_bytes`
+async fn receive_limited<T: serde::de::DeserializeOwned, R: tokio::io::AsyncRead + Unpin>(
+    reader: &mut R,
+    max_bytes: u64,
 ) -> Result<T> {
     use tokio::io::AsyncReadExt;
     
@@ -396,31 +1891,39 @@ pub async fn receive_secrets<T: serde::de::DeserializeOwned, R: tokio::io::Async
     reader.read_exact(&mut len_buf).await
         .map_err(|e| RiftError::StreamError(format!("Failed to read length: {}", e)))?;
     
-    let len = u32::from_be_bytes(len_buf) as usize;
-    if len > 10 * 1024 * 1024 {
+    let len = u32::from_be_bytes(len_buf) as u64;
+    if len > max_bytes {
         return Err(RiftError::StreamError("Message too large".to_string()));
     }
     
-    let mut buf = vec![0u8; len];
-    reader.read_exact(&mut buf).await
+    // Grow the buffer as data arrives instead of trusting the declared length
+    let mut buf = Vec::new();
+    reader.take(len).read_to_end(&mut buf).await
         .map_err(|e| RiftError::StreamError(format!("Failed to read data: {}", e)))?;
+    if (buf.len() as u64) < len {
+        return Err(RiftError::StreamError(format!(
+            "Failed to read data: stream ended after {} of {} bytes",
+            buf.len(),
+            len
+        )));
+    }
     
-    bincode::deserialize(&buf)
+    decode(&buf)
         .map_err(|e| RiftError::Serialization(format!("Failed to deserialize: {}", e)))
 }
 
 /// Send secrets to a peer over a dedicated stream
 pub async fn send_secrets_to_peer(
     control: &mut stream::Control,
+    protocols: &Protocols,
     peer_id: PeerId,
     secrets_response: &crate::secrets::SecretsResponse,
 ) -> Result<()> {
-    use super::behaviour::SECRETS_PROTOCOL;
     use tokio::io::AsyncWriteExt;
     
     // Open a stream for secrets
     let stream = control
-        .open_stream(peer_id, SECRETS_PROTOCOL)
+        .open_stream(peer_id, protocols.secrets.clone())
         .await
         .map_err(|e| RiftError::StreamError(format!("Failed to open secrets stream: {:?}", e)))?;
     
@@ -445,13 +1948,215 @@ pub async fn receive_secrets_from_stream(stream: Stream) -> Result<crate::secret
     let mut stream = stream.compat();
     let mut data = Vec::new();
     
-    stream.read_to_end(&mut data).await
+    (&mut stream).take(MAX_MESSAGE_BYTES + 1).read_to_end(&mut data).await
         .map_err(|e| RiftError::StreamError(format!("Failed to read secrets: {}", e)))?;
+    if data.len() as u64 > MAX_MESSAGE_BYTES {
+        return Err(RiftError::StreamError("Secrets message too large".to_string()));
+    }
     
-    let response: crate::secrets::SecretsResponse = bincode::deserialize(&data)
+    let response: crate::secrets::SecretsResponse = decode(&data)
         .map_err(|e| RiftError::Serialization(format!("Failed to deserialize secrets: {}", e)))?;
     
     info!("Received {} bytes of encrypted secrets", data.len());
     Ok(response)
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_peer_rtt_keeps_last_and_rolling_average() {
+        let ms = Duration::from_millis;
+        let mut rtt = PeerRtt::new(ms(80));
+        assert_eq!(rtt.average, ms(80));
+
+        rtt.record(ms(160));
+        assert_eq!(rtt.last, ms(160));
+        assert_eq!(rtt.average, ms(90));
+
+        // One slow ping barely moves the average
+        for _ in 0..20 {
+            rtt.record(ms(10));
+        }
+        rtt.record(ms(500));
+        assert_eq!(rtt.last, ms(500));
+        assert!(rtt.average < ms(80));
+    }
+
+    #[test]
+    fn test_circuit_relay_peer_only_for_circuit_listeners() {
+        let relay = PeerId::random();
+        let circuit: Multiaddr = format!("/ip4/203.0.113.10/udp/4001/quic-v1/p2p/{}/p2p-circuit", relay).parse().unwrap();
+        assert_eq!(circuit_relay_peer(&circuit), Some(relay));
+
+        let direct: Multiaddr = format!("/ip4/203.0.113.10/udp/4001/quic-v1/p2p/{}", relay).parse().unwrap();
+        assert_eq!(circuit_relay_peer(&direct), None);
+        let no_relay_id: Multiaddr = "/ip4/203.0.113.10/udp/4001/quic-v1/p2p-circuit".parse().unwrap();
+        assert_eq!(circuit_relay_peer(&no_relay_id), None);
+    }
+
+    #[test]
+    fn test_connection_counts_report_first_and_last() {
+        let mut counts = ConnectionCounts::default();
+        let peer = PeerId::random();
+        let other = PeerId::random();
+        let id = ConnectionId::new_unchecked;
+
+        assert!(counts.opened(peer, id(1), ConnectionPath::Relay));
+        assert!(!counts.opened(peer, id(2), ConnectionPath::Direct));
+        assert!(counts.opened(other, id(3), ConnectionPath::Relay));
+        assert_eq!(counts.path(&peer), Some(ConnectionPath::Direct));
+        assert!(counts.is_detour(&peer, id(1)));
+        assert!(!counts.is_detour(&peer, id(2)));
+        assert!(!counts.is_detour(&other, id(3)));
+
+        assert!(!counts.closed(peer, id(2)));
+        assert_eq!(counts.path(&peer), Some(ConnectionPath::Relay));
+        assert!(counts.closed(peer, id(1)));
+        assert_eq!(counts.path(&peer), None);
+        assert!(counts.closed(other, id(3)));
+        assert!(counts.opened(peer, id(4), ConnectionPath::Direct));
+    }
+
+    #[tokio::test]
+    async fn test_receive_secrets_rejects_bad_frames() {
+        let request = crate::secrets::SecretsRequest { public_key: vec![9; 32], known: Default::default() };
+        let mut frame = Vec::new();
+        send_secrets(&mut frame, &request).await.unwrap();
+        let decoded: crate::secrets::SecretsRequest = receive_secrets(&mut &frame[..]).await.unwrap();
+        assert_eq!(decoded.public_key, request.public_key);
+
+        // Truncated body
+        let truncated = &frame[..frame.len() - 1];
+        assert!(receive_secrets::<crate::secrets::SecretsRequest, _>(&mut &truncated[..]).await.is_err());
+
+        // Declared length over the limit
+        let oversized = u32::MAX.to_be_bytes();
+        assert!(receive_secrets::<crate::secrets::SecretsRequest, _>(&mut &oversized[..]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tunnel_requests_are_capped() {
+        let mut frame = Vec::new();
+        send_secrets(&mut frame, &TunnelRequest::new(3000).with_service(Some("db".to_string()))).await.unwrap();
+        let request: TunnelRequest = receive_limited(&mut &frame[..], MAX_TUNNEL_REQUEST_BYTES).await.unwrap();
+        assert_eq!(request.service.as_deref(), Some("db"));
+
+        let mut frame = Vec::new();
+        send_secrets(&mut frame, &TunnelRequest::new(3000).with_service(Some("x".repeat(600)))).await.unwrap();
+        assert!(receive_limited::<TunnelRequest, _>(&mut &frame[..], MAX_TUNNEL_REQUEST_BYTES).await.is_err());
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn test_secrets_frames_roundtrip_and_truncation(
+            encrypted_data in proptest::collection::vec(proptest::num::u8::ANY, 0..1024),
+            nonce in proptest::collection::vec(proptest::num::u8::ANY, 0..16),
+            cut in proptest::num::usize::ANY,
+        ) {
+            let response = crate::secrets::SecretsResponse {
+                ephemeral_public_key: vec![1; 32],
+                encrypted_data,
+                nonce,
+                sender_public_key: vec![2; 32],
+                unchanged: vec!["API_URL".to_string()],
+            };
+            futures::executor::block_on(async {
+                let mut frame = Vec::new();
+                send_secrets(&mut frame, &response).await.unwrap();
+                let decoded: crate::secrets::SecretsResponse = receive_secrets(&mut &frame[..]).await.unwrap();
+                proptest::prop_assert_eq!(&decoded.encrypted_data, &response.encrypted_data);
+                proptest::prop_assert_eq!(&decoded.nonce, &response.nonce);
+
+                // Any frame cut short is an error, never a partial message
+                let truncated = &frame[..cut % frame.len()];
+                proptest::prop_assert!(
+                    receive_secrets::<crate::secrets::SecretsResponse, _>(&mut &truncated[..]).await.is_err()
+                );
+                Ok(())
+            })?;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_discovery_follows_mdns_and_relay_flags() {
+        let state = tempfile::tempdir().unwrap();
+        let config = RiftConfig {
+            identity_path: state.path().join("identity.key"),
+            bootstrap_peers: Vec::new(),
+            ..Default::default()
+        }
+        .with_mdns(false)
+        .with_relay(false);
+        let network = PeerNetwork::new(config.clone()).await.unwrap();
+        assert!(!network.swarm.behaviour().mdns.is_enabled());
+        assert!(network.discovery_mechanisms().is_empty());
+
+        let network = PeerNetwork::new(config.with_mdns(true).with_relay(true)).await.unwrap();
+        let mechanisms = network.discovery_mechanisms();
+        assert_eq!(mechanisms[0], "mDNS");
+        assert!(mechanisms[1].starts_with("relay client"));
+    }
+
+    #[test]
+    fn test_listen_addresses_add_websocket_when_enabled() {
+        let mut config = RiftConfig::default().with_listen_port(4001);
+        let addrs: Vec<String> = listen_addresses(&config).unwrap().iter().map(|addr| addr.to_string()).collect();
+        assert_eq!(addrs, vec!["/ip4/0.0.0.0/udp/4001/quic-v1", "/ip6/::/udp/4001/quic-v1"]);
+
+        config.websocket.enabled = true;
+        config.websocket.port = 8080;
+        let addrs = listen_addresses(&config).unwrap();
+        assert_eq!(addrs.len(), 3);
+        assert_eq!(addrs[0].to_string(), "/ip4/0.0.0.0/udp/4001/quic-v1");
+        assert_eq!(addrs[2].to_string(), "/ip4/0.0.0.0/tcp/8080/ws");
+
+        // Builds without the webrtc feature ignore the setting
+        config.webrtc.enabled = true;
+        config.webrtc.port = 4002;
+        let addrs = listen_addresses(&config).unwrap();
+        if cfg!(feature = "webrtc") {
+            assert_eq!(addrs[3].to_string(), "/ip4/0.0.0.0/udp/4002/webrtc-direct");
+        } else {
+            assert_eq!(addrs.len(), 3);
+        }
+    }
+
+    #[test]
+    fn test_agent_version_override_keeps_ephemeral_marker() {
+        let mut config = RiftConfig::default();
+        assert!(agent_version(&config).starts_with("rift/"));
+
+        config.agent_version = Some("rift".to_string());
+        assert_eq!(agent_version(&config), "rift caps=secrets proto=1.2.0");
+
+        config.ephemeral_identity = true;
+        assert!(is_ephemeral_agent(&agent_version(&config)));
+
+        let config = config.with_service_name("db", 5432).with_service_name("web", 3000);
+        let agent = agent_version(&config);
+        assert_eq!(agent, "rift services=db:5432,web:3000 caps=secrets proto=1.2.0 (ephemeral)");
+        assert!(is_ephemeral_agent(&agent));
+        let services = advertised_services(&agent);
+        assert_eq!(services.len(), 2);
+        assert_eq!(services[0].name.as_deref(), Some("db"));
+        assert_eq!(services[0].port, 5432);
+        assert!(advertised_services("rift/0.1.0").is_empty());
+    }
+
+    #[test]
+    fn test_listen_addresses_from_config() {
+        let config = RiftConfig::default()
+            .with_listen_port(4001)
+            .with_listen_addr("10.8.0.2")
+            .with_listen_addr("/ip6/fd00::1/udp/5000/quic-v1");
+        let addrs: Vec<String> = listen_addresses(&config).unwrap().iter().map(|addr| addr.to_string()).collect();
+        assert_eq!(addrs, vec!["/ip6/fd00::1/udp/5000/quic-v1", "/ip4/10.8.0.2/udp/4001/quic-v1"]);
+
+        for bad in ["/ip4/10.8.0.2/udp/nope", "no-such-interface0"] {
+            let config = RiftConfig::default().with_listen_addr(bad);
+            assert!(matches!(listen_addresses(&config), Err(RiftError::ConfigError(_))), "{}", bad);
+        }
+    }
+}
@@ -1,57 +1,65 @@
 //! Transport Configuration for Rift
 //!
-//! Configures the QUIC transport with noise encryption for secure P2P communication.
+//! Tunables of the QUIC transport and the swarm's connection handling,
+//! set in the `[transport]` section of `config.toml`.
 
 use libp2p::{
     quic,
     identity::Keypair,
+    swarm,
 };
+use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
-use crate::error::Result;
-
 /// Default connection timeout
 pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
 
 /// Default idle connection timeout
 pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
 
+/// Default time a connection without open streams is kept
+pub const DEFAULT_IDLE_CONNECTION_TIMEOUT: Duration = Duration::from_secs(3600);
+
 /// Build the QUIC transport for Rift
 pub fn build_quic_transport(
     keypair: &Keypair,
-) -> Result<quic::tokio::Transport> {
-    let config = quic::Config::new(keypair);
-    
-    Ok(quic::tokio::Transport::new(config))
+    config: &TransportConfig,
+) -> quic::tokio::Transport {
+    let quic_config = config.apply_to_quic(quic::Config::new(keypair));
+
+    quic::tokio::Transport::new(quic_config)
 }
 
 /// Transport configuration options
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct TransportConfig {
-    /// Connection timeout
-    pub connection_timeout: Duration,
+    /// Seconds allowed for the QUIC handshake (capped by `idle_timeout_secs`)
+    pub connection_timeout_secs: u64,
 
-    /// Idle connection timeout
-    pub idle_timeout: Duration,
+    /// Seconds without any packet from the peer before a connection is dropped
+    pub idle_timeout_secs: u64,
 
-    /// Maximum incoming streams per connection
+    /// Streams a peer may have open at once on one connection
+    ///
+    /// Each tunnelled TCP connection uses one stream (more when striped).
     pub max_incoming_streams: u32,
 
-    /// Maximum outgoing streams per connection  
-    pub max_outgoing_streams: u32,
+    /// Seconds between keep-alive packets (keep below `idle_timeout_secs`)
+    pub keep_alive_interval_secs: u64,
 
-    /// Keep alive interval
-    pub keep_alive_interval: Duration,
+    /// Seconds a connection without open streams stays up before it is closed
+    pub idle_connection_timeout_secs: u64,
 }
 
 impl Default for TransportConfig {
     fn default() -> Self {
         Self {
-            connection_timeout: DEFAULT_TIMEOUT,
-            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+            connection_timeout_secs: DEFAULT_TIMEOUT.as_secs(),
+            idle_timeout_secs: DEFAULT_IDLE_TIMEOUT.as_secs(),
             max_incoming_streams: 256,
-            max_outgoing_streams: 256,
-            keep_alive_interval: Duration::from_secs(15),
+            keep_alive_interval_secs: 15,
+            idle_connection_timeout_secs: DEFAULT_IDLE_CONNECTION_TIMEOUT.as_secs(),
         }
     }
 }
@@ -64,13 +72,13 @@ impl TransportConfig {
 
     /// Set connection timeout
     pub fn with_connection_timeout(mut self, timeout: Duration) -> Self {
-        self.connection_timeout = timeout;
+        self.connection_timeout_secs = timeout.as_secs();
         self
     }
 
     /// Set idle timeout
     pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
-        self.idle_timeout = timeout;
+        self.idle_timeout_secs = timeout.as_secs();
         self
     }
 
@@ -82,7 +90,54 @@ impl TransportConfig {
 
     /// Set keep alive interval
     pub fn with_keep_alive_interval(mut self, interval: Duration) -> Self {
-        self.keep_alive_interval = interval;
+        self.keep_alive_interval_secs = interval.as_secs();
+        self
+    }
+
+    /// Set how long connections without streams are kept
+    pub fn with_idle_connection_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_connection_timeout_secs = timeout.as_secs();
         self
     }
+
+    /// Apply the QUIC settings to a libp2p QUIC config
+    pub fn apply_to_quic(&self, mut config: quic::Config) -> quic::Config {
+        config.handshake_timeout = Duration::from_secs(self.connection_timeout_secs);
+        config.max_idle_timeout = self.idle_timeout_secs.saturating_mul(1000).min(u32::MAX as u64) as u32;
+        config.keep_alive_interval = Duration::from_secs(self.keep_alive_interval_secs);
+        config.max_concurrent_stream_limit = self.max_incoming_streams;
+        config
+    }
+
+    /// Apply the connection settings to a swarm config
+    pub fn apply_to_swarm(&self, config: swarm::Config) -> swarm::Config {
+        config.with_idle_connection_timeout(Duration::from_secs(self.idle_connection_timeout_secs))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quic_config_takes_transport_settings() {
+        let config = TransportConfig::new()
+            .with_connection_timeout(Duration::from_secs(12))
+            .with_idle_timeout(Duration::from_secs(90))
+            .with_max_incoming_streams(64)
+            .with_keep_alive_interval(Duration::from_secs(20));
+        let quic = config.apply_to_quic(quic::Config::new(&Keypair::generate_ed25519()));
+
+        assert_eq!(quic.handshake_timeout, Duration::from_secs(12));
+        assert_eq!(quic.max_idle_timeout, 90_000);
+        assert_eq!(quic.max_concurrent_stream_limit, 64);
+        assert_eq!(quic.keep_alive_interval, Duration::from_secs(20));
+    }
+
+    #[test]
+    fn test_transport_config_from_toml() {
+        let config: TransportConfig = toml::from_str("max_incoming_streams = 1024\n").unwrap();
+        assert_eq!(config.max_incoming_streams, 1024);
+        assert_eq!(config.idle_connection_timeout_secs, 3600);
+    }
 }
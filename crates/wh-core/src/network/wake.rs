@@ -0,0 +1,109 @@
+//! Wake From Sleep
+//!
+//! Notices that the machine was suspended and checks which peers survived.
+//! Linux stops the monotonic clock during suspend while macOS keeps it
+//! running, so a jump of either the monotonic or the wall clock between two
+//! checks counts as sleep.
+
+use libp2p::PeerId;
+use libp2p_stream as stream;
+use std::time::{Duration, Instant, SystemTime};
+
+use super::behaviour::Protocols;
+use super::echo::echo_round_trip;
+
+/// Bytes echoed per round trip of a ping burst
+const BURST_PAYLOAD_BYTES: usize = 32;
+
+/// Detects clock jumps between periodic checks
+#[derive(Debug, Clone)]
+pub struct WakeDetector {
+    threshold: Duration,
+    last_instant: Instant,
+    last_wall: SystemTime,
+}
+
+impl WakeDetector {
+    /// Create a detector treating gaps over `threshold` as sleep
+    pub fn new(threshold: Duration) -> Self {
+        Self::starting_at(threshold, Instant::now(), SystemTime::now())
+    }
+
+    fn starting_at(threshold: Duration, instant: Instant, wall: SystemTime) -> Self {
+        Self {
+            threshold,
+            last_instant: instant,
+            last_wall: wall,
+        }
+    }
+
+    /// Record a check; returns how long we were asleep if the clocks jumped
+    pub fn check(&mut self) -> Option<Duration> {
+        self.check_at(Instant::now(), SystemTime::now())
+    }
+
+    fn check_at(&mut self, instant: Instant, wall: SystemTime) -> Option<Duration> {
+        let monotonic = instant.saturating_duration_since(self.last_instant);
+        // The wall clock may be set backwards; only forward jumps mean sleep
+        let wall_elapsed = wall.duration_since(self.last_wall).unwrap_or_default();
+        self.last_instant = instant;
+        self.last_wall = wall;
+
+        let gap = monotonic.max(wall_elapsed);
+        (gap > self.threshold).then_some(gap)
+    }
+}
+
+/// Send `count` small echo round trips to a peer; true if all came back within `timeout`
+///
+/// libp2p keeps a connection that died during sleep open until its idle
+/// timeout fires; a burst of echoes finds out right away.
+pub async fn ping_burst(
+    mut control: stream::Control,
+    protocols: Protocols,
+    peer_id: PeerId,
+    count: u32,
+    timeout: Duration,
+) -> bool {
+    let burst = async {
+        for _ in 0..count.max(1) {
+            echo_round_trip(&mut control, &protocols, peer_id, BURST_PAYLOAD_BYTES).await?;
+        }
+        Ok::<_, crate::error::RiftError>(())
+    };
+    matches!(tokio::time::timeout(timeout, burst).await, Ok(Ok(())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detects_jump_of_either_clock() {
+        let threshold = Duration::from_secs(15);
+        let instant = Instant::now();
+        let wall = SystemTime::now();
+        let mut detector = WakeDetector::starting_at(threshold, instant, wall);
+
+        let second = Duration::from_secs(1);
+        assert_eq!(detector.check_at(instant + second, wall + second), None);
+
+        // Linux: the monotonic clock stood still, the wall clock moved on
+        let asleep = Duration::from_secs(600);
+        assert_eq!(detector.check_at(instant + second * 2, wall + asleep), Some(asleep - second));
+
+        // macOS: both clocks kept running
+        let resumed = detector.check_at(instant + asleep * 2, wall + asleep * 2);
+        assert!(resumed.is_some_and(|gap| gap > threshold));
+    }
+
+    #[test]
+    fn test_ignores_wall_clock_set_backwards() {
+        let instant = Instant::now();
+        let wall = SystemTime::now();
+        let mut detector = WakeDetector::starting_at(Duration::from_secs(15), instant, wall);
+
+        let hour = Duration::from_secs(3600);
+        assert_eq!(detector.check_at(instant + Duration::from_secs(1), wall - hour), None);
+    }
+}
@@ -0,0 +1,71 @@
+//! WebRTC Transport
+//!
+//! A `/webrtc-direct` listener so a browser can reach a node without
+//! installing the CLI. The listen address embeds a hash of the DTLS
+//! certificate, so the certificate is kept next to the identity key; a new
+//! one on every start would invalidate addresses handed out before.
+
+use libp2p::identity::Keypair;
+use libp2p_webrtc::tokio::{Certificate, Transport};
+use std::path::Path;
+
+use crate::config::RiftConfig;
+use crate::error::{RiftError, Result};
+use crate::state::write_private;
+
+/// File name of the WebRTC certificate inside the state directory
+pub const WEBRTC_CERT_FILE: &str = "webrtc_cert.pem";
+
+/// Certificate for this configuration: throwaway if ephemeral, else loaded from disk
+pub fn certificate_for_config(config: &RiftConfig) -> Result<Certificate> {
+    if config.ephemeral_identity {
+        generate_certificate()
+    } else {
+        load_or_generate_certificate(config.state_dir().join(WEBRTC_CERT_FILE))
+    }
+}
+
+/// Load the certificate from a PEM file, or generate and save a new one
+pub fn load_or_generate_certificate(path: impl AsRef<Path>) -> Result<Certificate> {
+    let path = path.as_ref();
+
+    if path.exists() {
+        let pem = std::fs::read_to_string(path)?;
+        return Certificate::from_pem(&pem)
+            .map_err(|e| RiftError::ConfigError(format!("Invalid WebRTC certificate {}: {}", path.display(), e)));
+    }
+
+    let certificate = generate_certificate()?;
+    // The file holds the private key too
+    write_private(path, certificate.serialize_pem())?;
+
+    Ok(certificate)
+}
+
+/// Build the WebRTC transport for the swarm
+pub fn transport(keypair: &Keypair, certificate: Certificate) -> Transport {
+    Transport::new(keypair.clone(), certificate)
+}
+
+fn generate_certificate() -> Result<Certificate> {
+    Certificate::generate(&mut rand::thread_rng())
+        .map_err(|e| RiftError::NetworkInitialization(format!("Failed to generate WebRTC certificate: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_certificate_is_persisted() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(WEBRTC_CERT_FILE);
+
+        let first = load_or_generate_certificate(&path).unwrap();
+        let second = load_or_generate_certificate(&path).unwrap();
+        assert_eq!(first.serialize_pem(), second.serialize_pem());
+
+        std::fs::write(&path, "not a certificate").unwrap();
+        assert!(load_or_generate_certificate(&path).is_err());
+    }
+}
@@ -4,14 +4,21 @@
 //! This includes message types for tunnel establishment, data forwarding,
 //! and secrets exchange.
 
-use serde::{Deserialize, Serialize};
+use bincode::Options;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 /// Protocol identifier for Rift
-pub const RIFT_PROTOCOL: &str = "/rift/tunnel/1.0.0";
+pub const RIFT_PROTOCOL: &str = "/rift/tunnel/1.2.0";
 
 /// Protocol identifier for secrets exchange
 pub const RIFT_SECRETS_PROTOCOL: &str = "/rift/secrets/1.0.0";
 
+/// Largest encoded message accepted from a peer (10 MiB)
+pub const MAX_MESSAGE_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Largest encoded [`TunnelRequest`] accepted, read before the peer is approved
+pub const MAX_TUNNEL_REQUEST_BYTES: u64 = 512;
+
 /// Trait defining the Rift protocol behavior
 pub trait RiftProtocol: Send + Sync {
     /// Get the protocol identifier
@@ -31,7 +38,7 @@ pub struct DefaultProtocol {
 impl Default for DefaultProtocol {
     fn default() -> Self {
         Self {
-            version: "1.0.0".to_string(),
+            version: "1.2.0".to_string(),
         }
     }
 }
@@ -42,7 +49,7 @@ impl RiftProtocol for DefaultProtocol {
     }
 
     fn is_supported(&self, version: &str) -> bool {
-        version == "1.0.0"
+        version == "1.2.0"
     }
 }
 
@@ -113,6 +120,9 @@ pub struct TunnelRequest {
 
     /// Whether to request secrets
     pub request_secrets: bool,
+
+    /// Service name (`rift share --name`) the sharing peer resolves to a port; overrides `port`
+    pub service: Option<String>,
 }
 
 impl TunnelRequest {
@@ -121,9 +131,15 @@ impl TunnelRequest {
             port,
             public_key: None,
             request_secrets: false,
+            service: None,
         }
     }
 
+    pub fn with_service(mut self, service: Option<String>) -> Self {
+        self.service = service;
+        self
+    }
+
     pub fn with_secrets(mut self, public_key: Vec<u8>) -> Self {
         self.public_key = Some(public_key);
         self.request_secrets = true;
@@ -273,10 +289,22 @@ impl Message {
 
     /// Deserialize message from bytes
     pub fn from_bytes(data: &[u8]) -> Result<Self, bincode::Error> {
-        bincode::deserialize(data)
+        decode(data)
     }
 }
 
+/// Decode a bincode value received from a peer
+///
+/// Same wire format as `bincode::deserialize`, but declared lengths adding
+/// up to more than [`MAX_MESSAGE_BYTES`] are rejected before allocating.
+pub fn decode<T: DeserializeOwned>(data: &[u8]) -> Result<T, bincode::Error> {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .with_limit(MAX_MESSAGE_BYTES)
+        .deserialize(data)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -298,6 +326,22 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_decode_rejects_oversized_lengths() {
+        let bytes = Message::new(7, MessagePayload::Ping(PingMessage { timestamp: 1 })).to_bytes().unwrap();
+        assert_eq!(decode::<Message>(&bytes).unwrap().id, 7);
+        assert_eq!(bincode::deserialize::<Message>(&bytes).unwrap().id, 7);
+
+        // A byte vector claiming to be far larger than the limit
+        let mut huge = u64::MAX.to_le_bytes().to_vec();
+        huge.extend_from_slice(&[0u8; 16]);
+        assert!(decode::<Vec<u8>>(&huge).is_err());
+
+        let mut over = (MAX_MESSAGE_BYTES + 1).to_le_bytes().to_vec();
+        over.extend_from_slice(&[0u8; 16]);
+        assert!(decode::<String>(&over).is_err());
+    }
+
     #[test]
     fn test_data_frame() {
         let frame = DataFrame::new(1, 0, vec![1, 2, 3, 4]).with_fin();
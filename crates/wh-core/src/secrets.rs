@@ -1,29 +1,190 @@
 //! EnvVault - Secrets Management for Rift
 //!
 //! Provides secure storage and sharing of environment variables.
-//! Uses the system keyring for local storage and X25519/AES-GCM for transit.
+//! Uses the system keyring (or a key file) for local storage and X25519/AES-GCM
+//! for transit.
+//!
+//! The host may hold some values back: keys marked [`Redaction::Masked`] are
+//! delivered with all but their last characters masked, and keys marked
+//! [`Redaction::Reference`] are not delivered at all; the receiver takes
+//! those from its own vault or environment instead.
+//!
+//! A receiver that asked before keeps what it got ([`ReceivedSecrets`],
+//! encrypted in its state store) and sends digests of it with the next
+//! request; the host then only sends the secrets that changed. The digests are
+//! keyed with the Diffie-Hellman secret of both long-term keys, so they reveal
+//! nothing about the values to anyone else. First-time and ephemeral
+//! requesters send no digests and get everything.
 
 use std::collections::HashMap;
+use std::fmt;
 use std::path::Path;
+use std::str::FromStr;
 
 use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+#[cfg(feature = "keyring")]
 use keyring::Entry;
 use serde::{Deserialize, Serialize};
 
-use crate::crypto::{decrypt_from_sender, encrypt_for_recipient, KeyPair, NONCE_SIZE};
+use crate::config::RiftConfig;
+use crate::crypto::{constant_time_eq, decrypt_from_sender, encrypt_for_recipient, KeyPair, SecretsCipher, NONCE_SIZE};
 use crate::error::{RiftError, Result};
+use crate::hash::{ContentHash, Hasher};
+use crate::spool::{Spool, SpoolFile};
+use crate::state::{write_private, StateStore};
 
 /// Service name for keyring storage
+#[cfg(feature = "keyring")]
 const KEYRING_SERVICE: &str = "rift";
 
 /// Key for storing the identity keypair
+#[cfg(feature = "keyring")]
 const IDENTITY_KEY: &str = "identity";
 
+/// File (inside the state dir) holding the secrets keypair with `Keystore::File`
+pub const SECRETS_KEY_FILE: &str = "secrets.key";
+
+/// Directory of the state store holding the secrets last received from each host
+pub const RECEIVED_SECRETS_DIR: &str = "received_secrets";
+
+/// Context of the keys of differential request digests
+const DIGEST_CONTEXT: &str = "rift 2024 secrets digest v1";
+
+/// Context of the key encrypting received secrets at rest
+const RECEIVED_CONTEXT: &str = "rift 2024 received secrets v1";
+
+/// Where the long-term secrets keypair is stored
+///
+/// Builds without the `keyring` feature default to (and only support) `File`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Keystore {
+    /// The OS keyring (Keychain, Secret Service, Credential Manager)
+    #[cfg_attr(feature = "keyring", default)]
+    Keyring,
+    /// A file in the state directory, for headless machines or locked keyrings
+    #[cfg_attr(not(feature = "keyring"), default)]
+    File,
+}
+
+impl FromStr for Keystore {
+    type Err = RiftError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "keyring" => Ok(Self::Keyring),
+            "file" => Ok(Self::File),
+            other => Err(RiftError::ConfigError(format!(
+                "Unknown keystore '{}' (expected 'keyring' or 'file')",
+                other
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for Keystore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Keyring => "keyring",
+            Self::File => "file",
+        })
+    }
+}
+
+/// Characters of a masked value still shown
+pub const MASK_VISIBLE_CHARS: usize = 4;
+
+/// Values shorter than this are masked entirely
+const MASK_MIN_LEN: usize = 3 * MASK_VISIBLE_CHARS;
+
+/// How much of a secret leaves the host
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Redaction {
+    /// The value itself
+    #[default]
+    Full,
+    /// Only the last [`MASK_VISIBLE_CHARS`] characters (e.g. `****1234`)
+    #[serde(alias = "deliver-masked")]
+    Masked,
+    /// Nothing but the name; the receiver fills in its own value
+    #[serde(alias = "deliver-reference")]
+    Reference,
+}
+
+impl FromStr for Redaction {
+    type Err = RiftError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "full" => Ok(Self::Full),
+            "masked" | "deliver-masked" => Ok(Self::Masked),
+            "reference" | "deliver-reference" => Ok(Self::Reference),
+            other => Err(RiftError::ConfigError(format!(
+                "Unknown redaction '{}' (expected 'full', 'masked' or 'reference')",
+                other
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for Redaction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Full => "full",
+            Self::Masked => "masked",
+            Self::Reference => "reference",
+        })
+    }
+}
+
+/// A secret as it travels to the receiver
+///
+/// Full values are plain JSON strings, so hosts that redact nothing stay
+/// readable by older receivers.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum DeliveredSecret {
+    /// The value itself
+    Value(String),
+    /// The value with all but its last characters masked
+    Masked {
+        /// What the receiver may see, e.g. `****1234`
+        masked: String,
+    },
+    /// Withheld; the receiver looks up `reference` in its own vault
+    Reference {
+        /// Name to look up
+        reference: String,
+    },
+}
+
+impl DeliveredSecret {
+    /// Digest under a key only the host and the requester can derive
+    fn digest(&self, key: &[u8; 32]) -> ContentHash {
+        let bytes = serde_json::to_vec(self).unwrap_or_default();
+        blake3::keyed_hash(key, &bytes).into()
+    }
+}
+
+/// Mask all but the last [`MASK_VISIBLE_CHARS`] characters of a value
+pub fn mask_value(value: &str) -> String {
+    let chars = value.chars().count();
+    let visible = if chars >= MASK_MIN_LEN { MASK_VISIBLE_CHARS } else { 0 };
+    // A fixed-length mask, so the length of the value stays private too
+    let tail: String = value.chars().skip(chars - visible).collect();
+    format!("****{}", tail)
+}
+
 /// Request for secrets from a peer
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SecretsRequest {
     /// Requester's public key
     pub public_key: Vec<u8>,
+    /// Keyed digests of the secrets the requester already holds from this
+    /// host; empty asks for everything
+    #[serde(default)]
+    pub known: HashMap<String, ContentHash>,
 }
 
 /// Response containing encrypted secrets
@@ -37,6 +198,25 @@ pub struct SecretsResponse {
     pub nonce: Vec<u8>,
     /// Sender's public key (for identification)
     pub sender_public_key: Vec<u8>,
+    /// Keys left out because the requester's copy is current
+    #[serde(default)]
+    pub unchanged: Vec<String>,
+}
+
+/// What a receiver got from a host, kept to make the next request differential
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReceivedSecrets {
+    /// The host's long-term secrets public key
+    pub sender_public_key: Vec<u8>,
+    /// Secrets as delivered
+    pub secrets: HashMap<String, DeliveredSecret>,
+}
+
+/// Received secrets as stored: encrypted under a key derived from ours
+#[derive(Serialize, Deserialize)]
+struct SealedReceivedSecrets {
+    nonce: String,
+    data: String,
 }
 
 /// EnvVault manages secrets for Rift tunnels
@@ -47,6 +227,9 @@ pub struct EnvVault {
 
     /// Our keypair for encryption
     keypair: KeyPair,
+
+    /// Keys not delivered in full (the rest are)
+    redaction: HashMap<String, Redaction>,
 }
 
 impl EnvVault {
@@ -55,6 +238,7 @@ impl EnvVault {
         Self {
             secrets: HashMap::new(),
             keypair: KeyPair::generate(),
+            redaction: HashMap::new(),
         }
     }
 
@@ -63,39 +247,58 @@ impl EnvVault {
         Self {
             secrets: HashMap::new(),
             keypair,
+            redaction: HashMap::new(),
         }
     }
 
     /// Load or create identity keypair from system keyring
+    #[cfg(feature = "keyring")]
     pub fn load_or_create_identity() -> Result<KeyPair> {
-        let entry = Entry::new(KEYRING_SERVICE, IDENTITY_KEY)
-            .map_err(|e| RiftError::KeyringError(e.to_string()))?;
+        let entry = Entry::new(KEYRING_SERVICE, IDENTITY_KEY).map_err(keyring_error)?;
 
         // Try to load existing key
         match entry.get_password() {
-            Ok(key_b64) => {
-                let key_bytes = BASE64.decode(&key_b64)
-                    .map_err(|e| RiftError::KeyringError(format!("Invalid key format: {}", e)))?;
-                
-                if key_bytes.len() != 32 {
-                    return Err(RiftError::KeyringError("Invalid key length".to_string()));
-                }
-
-                let mut arr = [0u8; 32];
-                arr.copy_from_slice(&key_bytes);
-                Ok(KeyPair::from_secret_bytes(arr))
-            }
-            Err(_) => {
+            Ok(key_b64) => decode_secret_key(&key_b64),
+            Err(keyring::Error::NoEntry) => {
                 // Generate new keypair and store it
                 let keypair = KeyPair::generate();
                 let key_b64 = BASE64.encode(keypair.secret_key_bytes());
                 
-                entry
-                    .set_password(&key_b64)
-                    .map_err(|e| RiftError::KeyringError(e.to_string()))?;
+                entry.set_password(&key_b64).map_err(keyring_error)?;
 
                 Ok(keypair)
             }
+            Err(e) => Err(keyring_error(e)),
+        }
+    }
+
+    /// Load or create identity keypair from system keyring
+    #[cfg(not(feature = "keyring"))]
+    pub fn load_or_create_identity() -> Result<KeyPair> {
+        Err(RiftError::KeyringError(
+            "this build has no keyring support; use keystore = \"file\"".to_string(),
+        ))
+    }
+
+    /// Load or create identity keypair stored in a file (base64, mode 0600)
+    pub fn load_or_create_identity_file(path: impl AsRef<Path>) -> Result<KeyPair> {
+        let path = path.as_ref();
+
+        if path.exists() {
+            return decode_secret_key(std::fs::read_to_string(path)?.trim());
+        }
+
+        let keypair = KeyPair::generate();
+        write_private(path, BASE64.encode(keypair.secret_key_bytes()))?;
+
+        Ok(keypair)
+    }
+
+    /// Load or create the identity keypair from the keystore chosen in `config`
+    pub fn load_identity_for(config: &RiftConfig) -> Result<KeyPair> {
+        match config.keystore {
+            Keystore::Keyring => Self::load_or_create_identity(),
+            Keystore::File => Self::load_or_create_identity_file(config.state_dir().join(SECRETS_KEY_FILE)),
         }
     }
 
@@ -112,6 +315,26 @@ impl EnvVault {
         Ok(vault)
     }
 
+    /// Create a new EnvVault from a .env file, using the keystore and
+    /// redaction policy chosen in `config`
+    pub fn from_file_for(path: impl AsRef<Path>, config: &RiftConfig) -> Result<Self> {
+        let keypair = Self::load_identity_for(config)?;
+        let mut vault = Self::with_keypair(keypair).with_redaction(config.secrets_redaction.clone());
+        vault.load_env_file(path)?;
+        Ok(vault)
+    }
+
+    /// Builder pattern: hold values back from peers, by key
+    pub fn with_redaction(mut self, redaction: HashMap<String, Redaction>) -> Self {
+        self.redaction = redaction;
+        self
+    }
+
+    /// How much of a secret peers receive
+    pub fn redaction(&self, key: &str) -> Redaction {
+        self.redaction.get(key).copied().unwrap_or_default()
+    }
+
     /// Load secrets from a .env file
     pub fn load_env_file(&mut self, path: impl AsRef<Path>) -> Result<()> {
         let path = path.as_ref();
@@ -168,12 +391,11 @@ impl EnvVault {
         let value = value.trim();
 
         // Handle quoted values
-        if (value.starts_with('"') && value.ends_with('"'))
-            || (value.starts_with('\'') && value.ends_with('\''))
+        if value.len() >= 2
+            && ((value.starts_with('"') && value.ends_with('"'))
+                || (value.starts_with('\'') && value.ends_with('\'')))
         {
-            if value.len() >= 2 {
-                return value[1..value.len() - 1].to_string();
-            }
+            return value[1..value.len() - 1].to_string();
         }
 
         value.to_string()
@@ -199,6 +421,20 @@ impl EnvVault {
         &self.secrets
     }
 
+    /// Copy of the vault holding only the given keys, with the same keypair
+    pub fn scoped<'a>(&self, keys: impl IntoIterator<Item = &'a str>) -> Self {
+        let secrets = keys
+            .into_iter()
+            .filter_map(|key| self.secrets.get_key_value(key))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        Self {
+            secrets,
+            keypair: self.keypair.clone(),
+            redaction: self.redaction.clone(),
+        }
+    }
+
     /// Check if there are any secrets
     pub fn is_empty(&self) -> bool {
         self.secrets.is_empty()
@@ -209,15 +445,69 @@ impl EnvVault {
         self.secrets.len()
     }
 
+    /// Hash of the names and values, changing whenever any secret does
+    ///
+    /// Independent of order; safe to log, since the values cannot be recovered from it.
+    pub fn version(&self) -> ContentHash {
+        let mut keys: Vec<&String> = self.secrets.keys().collect();
+        keys.sort();
+        let mut hasher = Hasher::new();
+        for key in keys {
+            let value = &self.secrets[key];
+            // Length prefixes keep "A"="BC" apart from "AB"="C"
+            hasher
+                .update(&(key.len() as u64).to_le_bytes())
+                .update(key.as_bytes())
+                .update(&(value.len() as u64).to_le_bytes())
+                .update(value.as_bytes());
+        }
+        hasher.finalize()
+    }
+
     /// Create a secrets request message
     pub fn create_secrets_request(&self) -> SecretsRequest {
+        self.create_secrets_request_since(None)
+    }
+
+    /// Create a secrets request asking only for what changed since `previous`
+    pub fn create_secrets_request_since(&self, previous: Option<&ReceivedSecrets>) -> SecretsRequest {
+        let known = previous
+            .and_then(|previous| {
+                let host_key: [u8; 32] = previous.sender_public_key.as_slice().try_into().ok()?;
+                let key = self.digest_key(&host_key);
+                Some(
+                    previous
+                        .secrets
+                        .iter()
+                        .map(|(name, secret)| (name.clone(), secret.digest(&key)))
+                        .collect(),
+                )
+            })
+            .unwrap_or_default();
         SecretsRequest {
             public_key: self.keypair.public_key_bytes().to_vec(),
+            known,
         }
     }
 
-    /// Encrypt secrets for a requesting peer
+    /// Key of the digests exchanged with a peer, from our keypair and its public key
+    fn digest_key(&self, peer_public_key: &[u8; 32]) -> [u8; 32] {
+        blake3::derive_key(DIGEST_CONTEXT, &self.keypair.diffie_hellman(peer_public_key))
+    }
+
+    /// Encrypt secrets for a requesting peer, redacted as configured
     pub fn encrypt_for_peer(&self, peer_public_key: &[u8]) -> Result<SecretsResponse> {
+        self.encrypt_changes_for_peer(peer_public_key, &HashMap::new())
+    }
+
+    /// Encrypt the secrets whose digest differs from the requester's `known` ones
+    ///
+    /// The others are listed in [`SecretsResponse::unchanged`].
+    pub fn encrypt_changes_for_peer(
+        &self,
+        peer_public_key: &[u8],
+        known: &HashMap<String, ContentHash>,
+    ) -> Result<SecretsResponse> {
         if peer_public_key.len() != 32 {
             return Err(RiftError::InvalidPublicKey(
                 "Public key must be 32 bytes".to_string(),
@@ -227,8 +517,34 @@ impl EnvVault {
         let mut peer_key = [0u8; 32];
         peer_key.copy_from_slice(peer_public_key);
 
-        // Serialize secrets
-        let secrets_json = serde_json::to_vec(&self.secrets)?;
+        // Redact, then leave out what the requester holds already
+        let mut delivered: HashMap<&String, DeliveredSecret> = self
+            .secrets
+            .iter()
+            .map(|(key, value)| {
+                let secret = match self.redaction(key) {
+                    Redaction::Full => DeliveredSecret::Value(value.clone()),
+                    Redaction::Masked => DeliveredSecret::Masked { masked: mask_value(value) },
+                    Redaction::Reference => DeliveredSecret::Reference { reference: key.clone() },
+                };
+                (key, secret)
+            })
+            .collect();
+        let mut unchanged = Vec::new();
+        if !known.is_empty() {
+            let digest_key = self.digest_key(&peer_key);
+            delivered.retain(|key, secret| {
+                let current = known
+                    .get(*key)
+                    .is_some_and(|digest| constant_time_eq(digest.as_bytes(), secret.digest(&digest_key).as_bytes()));
+                if current {
+                    unchanged.push((*key).clone());
+                }
+                !current
+            });
+            unchanged.sort();
+        }
+        let secrets_json = serde_json::to_vec(&delivered)?;
 
         // Encrypt
         let (ephemeral_public, encrypted_data, nonce) =
@@ -239,11 +555,38 @@ impl EnvVault {
             encrypted_data,
             nonce: nonce.to_vec(),
             sender_public_key: self.keypair.public_key_bytes().to_vec(),
+            unchanged,
         })
     }
 
-    /// Decrypt secrets from a peer's response
+    /// Decrypt the usable secrets of a peer's response, filling in references
+    ///
+    /// Masked values and references missing from this vault and the
+    /// environment are left out; use [`EnvVault::open_from_peer`] to see them.
     pub fn decrypt_from_peer(&self, response: &SecretsResponse) -> Result<HashMap<String, String>> {
+        Ok(self
+            .open_from_peer(response)?
+            .into_iter()
+            .filter_map(|(key, secret)| self.resolve(&secret).map(|value| (key, value)))
+            .collect())
+    }
+
+    /// Usable value of a delivered secret, taking references from this vault
+    /// or else the environment; None for masked values
+    pub fn resolve(&self, secret: &DeliveredSecret) -> Option<String> {
+        match secret {
+            DeliveredSecret::Value(value) => Some(value.clone()),
+            DeliveredSecret::Masked { .. } => None,
+            DeliveredSecret::Reference { reference } => self
+                .secrets
+                .get(reference)
+                .cloned()
+                .or_else(|| std::env::var(reference).ok()),
+        }
+    }
+
+    /// Decrypt secrets from a peer's response as delivered
+    pub fn open_from_peer(&self, response: &SecretsResponse) -> Result<HashMap<String, DeliveredSecret>> {
         if response.ephemeral_public_key.len() != 32 {
             return Err(RiftError::InvalidPublicKey(
                 "Ephemeral public key must be 32 bytes".to_string(),
@@ -269,10 +612,56 @@ impl EnvVault {
             &nonce,
         )?;
 
-        let secrets: HashMap<String, String> = serde_json::from_slice(&plaintext)?;
+        let secrets: HashMap<String, DeliveredSecret> = serde_json::from_slice(&plaintext)?;
         Ok(secrets)
     }
 
+    /// Decrypt a response to [`EnvVault::create_secrets_request_since`],
+    /// taking the unchanged secrets from `previous`
+    pub fn open_changes_from_peer(
+        &self,
+        response: &SecretsResponse,
+        previous: Option<&ReceivedSecrets>,
+    ) -> Result<ReceivedSecrets> {
+        let mut secrets = self.open_from_peer(response)?;
+        let previous = previous.filter(|previous| previous.sender_public_key == response.sender_public_key);
+        for key in &response.unchanged {
+            let kept = previous
+                .and_then(|previous| previous.secrets.get(key))
+                .ok_or_else(|| RiftError::InvalidMessage(format!("host left out {} but we hold no copy", key)))?;
+            secrets.insert(key.clone(), kept.clone());
+        }
+        Ok(ReceivedSecrets {
+            sender_public_key: response.sender_public_key.clone(),
+            secrets,
+        })
+    }
+
+    /// Secrets last received from `host`, if they were kept and still decrypt
+    pub fn load_received(&self, store: &dyn StateStore, host: &str) -> Option<ReceivedSecrets> {
+        let contents = store.load(&received_name(host)).ok()??;
+        let sealed: SealedReceivedSecrets = serde_json::from_str(&contents).ok()?;
+        let nonce: [u8; NONCE_SIZE] = BASE64.decode(sealed.nonce).ok()?.try_into().ok()?;
+        let data = BASE64.decode(sealed.data).ok()?;
+        let json = SecretsCipher::decrypt(&self.received_key(), &data, &nonce).ok()?;
+        serde_json::from_slice(&json).ok()
+    }
+
+    /// Keep what was received from `host` for the next request, encrypted under our key
+    pub fn save_received(&self, store: &dyn StateStore, host: &str, received: &ReceivedSecrets) -> Result<()> {
+        let json = serde_json::to_vec(received)?;
+        let (data, nonce) = SecretsCipher::encrypt(&self.received_key(), &json)?;
+        let sealed = SealedReceivedSecrets {
+            nonce: BASE64.encode(nonce),
+            data: BASE64.encode(data),
+        };
+        store.save(&received_name(host), &serde_json::to_string(&sealed)?)
+    }
+
+    fn received_key(&self) -> [u8; 32] {
+        blake3::derive_key(RECEIVED_CONTEXT, &self.keypair.secret_key_bytes())
+    }
+
     /// Export secrets to .env format string
     pub fn to_env_format(&self) -> String {
         let mut lines = Vec::new();
@@ -290,17 +679,9 @@ impl EnvVault {
         lines.join("\n")
     }
 
-    /// Write secrets to a temporary file
-    pub fn write_to_temp_file(&self) -> Result<std::path::PathBuf> {
-        let temp_dir = tempfile::tempdir()?;
-        let temp_path = temp_dir.path().join(".env.rift");
-        
-        std::fs::write(&temp_path, self.to_env_format())?;
-        
-        // Keep the temp directory around (don't delete on drop)
-        std::mem::forget(temp_dir);
-        
-        Ok(temp_path)
+    /// Write secrets to a file in the spool, deleted when the guard is dropped
+    pub fn write_to_temp_file(&self, spool: &Spool) -> Result<SpoolFile> {
+        spool.write(".env", self.to_env_format().as_bytes())
     }
 
     /// Inject secrets into the current process environment
@@ -319,6 +700,35 @@ impl EnvVault {
     }
 }
 
+/// State store name of the secrets received from `host`
+fn received_name(host: &str) -> String {
+    format!("{}/{}.json", RECEIVED_SECRETS_DIR, host)
+}
+
+/// Decode a base64 X25519 secret key
+fn decode_secret_key(key_b64: &str) -> Result<KeyPair> {
+    let key_bytes = BASE64.decode(key_b64)
+        .map_err(|e| RiftError::KeyringError(format!("Invalid key format: {}", e)))?;
+
+    if key_bytes.len() != 32 {
+        return Err(RiftError::KeyringError("Invalid key length".to_string()));
+    }
+
+    let mut arr = [0u8; 32];
+    arr.copy_from_slice(&key_bytes);
+    Ok(KeyPair::from_secret_bytes(arr))
+}
+
+/// Map a keyring failure, singling out a locked or inaccessible store
+#[cfg(feature = "keyring")]
+fn keyring_error(err: keyring::Error) -> RiftError {
+    match err {
+        keyring::Error::NoStorageAccess(e) => RiftError::KeyringLocked(e.to_string()),
+        keyring::Error::PlatformFailure(e) => RiftError::KeyringLocked(e.to_string()),
+        other => RiftError::KeyringError(other.to_string()),
+    }
+}
+
 impl Default for EnvVault {
     fn default() -> Self {
         Self::new()
@@ -335,6 +745,42 @@ pub struct StoredSecrets {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_file_keystore_persists_keypair() {
+        let temp = tempfile::tempdir().unwrap();
+        let config = RiftConfig {
+            identity_path: temp.path().join("identity.key"),
+            ..Default::default()
+        }
+        .with_keystore(Keystore::File);
+
+        let first = EnvVault::load_identity_for(&config).unwrap();
+        let second = EnvVault::load_identity_for(&config).unwrap();
+
+        assert_eq!(first.public_key_bytes(), second.public_key_bytes());
+        assert!(temp.path().join(SECRETS_KEY_FILE).exists());
+        assert_eq!("file".parse::<Keystore>().unwrap(), Keystore::File);
+        assert!("vault".parse::<Keystore>().is_err());
+    }
+
+    #[test]
+    fn test_version_follows_contents() {
+        let mut vault = EnvVault::new();
+        vault.set("A", "BC");
+        let version = vault.version();
+
+        let mut other = EnvVault::new();
+        other.set("AB", "C");
+        assert_ne!(other.version(), version);
+
+        vault.set("B", "1");
+        let mut reordered = EnvVault::new();
+        reordered.set("B", "1");
+        reordered.set("A", "BC");
+        assert_eq!(reordered.version(), vault.version());
+        assert_ne!(vault.version(), version);
+    }
+
     #[test]
     fn test_parse_env() {
         let mut vault = EnvVault::new();
@@ -383,6 +829,99 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_redacted_delivery() {
+        let mut host = EnvVault::new().with_redaction(HashMap::from([
+            ("STRIPE_KEY".to_string(), Redaction::Masked),
+            ("PIN".to_string(), Redaction::Masked),
+            ("PROD_DB_URL".to_string(), Redaction::Reference),
+            ("UNSET_URL".to_string(), Redaction::Reference),
+        ]));
+        host.set("API_URL", "http://localhost:3000");
+        host.set("STRIPE_KEY", "sk_live_abcdef1234");
+        host.set("PIN", "123456");
+        host.set("PROD_DB_URL", "postgres://prod/db");
+        host.set("UNSET_URL", "postgres://prod/other");
+
+        let mut receiver = EnvVault::new();
+        receiver.set("PROD_DB_URL", "postgres://localhost/db");
+        let response = host.scoped(host.secrets().keys().map(String::as_str)).encrypt_for_peer(&receiver.public_key()).unwrap();
+
+        let delivered = receiver.open_from_peer(&response).unwrap();
+        assert_eq!(delivered["API_URL"], DeliveredSecret::Value("http://localhost:3000".to_string()));
+        assert_eq!(delivered["STRIPE_KEY"], DeliveredSecret::Masked { masked: "****1234".to_string() });
+        assert_eq!(delivered["PIN"], DeliveredSecret::Masked { masked: "****".to_string() });
+        assert_eq!(delivered["PROD_DB_URL"], DeliveredSecret::Reference { reference: "PROD_DB_URL".to_string() });
+
+        let secrets = receiver.decrypt_from_peer(&response).unwrap();
+        assert_eq!(secrets["PROD_DB_URL"], "postgres://localhost/db");
+        assert!(!secrets.contains_key("UNSET_URL"));
+        assert!(!secrets.contains_key("STRIPE_KEY"));
+        assert!(!String::from_utf8_lossy(&serde_json::to_vec(&delivered).unwrap()).contains("prod"));
+        assert_eq!("deliver-masked".parse::<Redaction>().unwrap(), Redaction::Masked);
+    }
+
+    #[test]
+    fn test_differential_request_sends_only_changes() {
+        let mut host = EnvVault::new();
+        host.set("API_URL", "http://localhost:3000");
+        host.set("API_KEY", "old");
+        host.set("GONE", "soon");
+        let receiver = EnvVault::new();
+
+        // First request: everything
+        let request = receiver.create_secrets_request_since(None);
+        assert!(request.known.is_empty());
+        let response = host.encrypt_changes_for_peer(&request.public_key, &request.known).unwrap();
+        assert!(response.unchanged.is_empty());
+        let first = receiver.open_changes_from_peer(&response, None).unwrap();
+        assert_eq!(first.secrets.len(), 3);
+
+        host.set("API_KEY", "new");
+        host.remove("GONE");
+        host.set("ADDED", "1");
+        let request = receiver.create_secrets_request_since(Some(&first));
+        assert_eq!(request.known.len(), 3);
+        let response = host.encrypt_changes_for_peer(&request.public_key, &request.known).unwrap();
+        assert_eq!(response.unchanged, vec!["API_URL".to_string()]);
+        assert_eq!(receiver.open_from_peer(&response).unwrap().len(), 2);
+
+        let second = receiver.open_changes_from_peer(&response, Some(&first)).unwrap();
+        assert_eq!(second.secrets.len(), 3);
+        assert_eq!(second.secrets["API_URL"], DeliveredSecret::Value("http://localhost:3000".to_string()));
+        assert_eq!(second.secrets["API_KEY"], DeliveredSecret::Value("new".to_string()));
+        assert!(!second.secrets.contains_key("GONE"));
+
+        // Digests made for another host match nothing here
+        let other_host = EnvVault::new();
+        let response = other_host.encrypt_changes_for_peer(&request.public_key, &request.known).unwrap();
+        assert!(response.unchanged.is_empty());
+
+        // Nor can a response skip keys the receiver never got
+        let response = host.encrypt_changes_for_peer(&request.public_key, &request.known).unwrap();
+        assert!(receiver.open_changes_from_peer(&response, None).is_err());
+    }
+
+    #[test]
+    fn test_received_secrets_kept_encrypted() {
+        let temp = tempfile::tempdir().unwrap();
+        let store = crate::state::FileStore::new(temp.path().to_path_buf());
+        let receiver = EnvVault::new();
+        let received = ReceivedSecrets {
+            sender_public_key: vec![3; 32],
+            secrets: HashMap::from([("TOKEN".to_string(), DeliveredSecret::Value("hunter2".to_string()))]),
+        };
+
+        receiver.save_received(&store, "host-a", &received).unwrap();
+        assert_eq!(receiver.load_received(&store, "host-a"), Some(received));
+        assert_eq!(receiver.load_received(&store, "host-b"), None);
+        let stored = store.load(&received_name("host-a")).unwrap().unwrap();
+        assert!(!stored.contains("hunter2"));
+
+        // Another identity cannot read it
+        assert_eq!(EnvVault::new().load_received(&store, "host-a"), None);
+    }
+
     #[test]
     fn test_to_env_format() {
         let mut vault = EnvVault::new();
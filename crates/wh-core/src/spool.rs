@@ -0,0 +1,204 @@
+//! Spool Directory
+//!
+//! Short-lived files holding secrets (a decrypted `.env` for a child process,
+//! a file on its way to `--save-secrets`) are written to `spool/` in the state
+//! directory rather than the system temp dir, which is shared between users
+//! on most unix machines and cleaned on its own schedule.
+//!
+//! Each file comes back as a [`SpoolFile`] that deletes it when dropped unless
+//! it is persisted. Files are prefixed with the writer's process ID, so
+//! [`Spool::clean`] can sweep what a killed process left behind without
+//! touching files of sessions still running.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use tempfile::TempPath;
+
+use crate::error::Result;
+
+/// Spool directory name inside the state directory
+pub const SPOOL_DIR: &str = "spool";
+
+/// Where the owner of a file cannot be checked, files older than this are stale
+const STALE_AFTER: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Private directory for short-lived files
+#[derive(Debug, Clone)]
+pub struct Spool {
+    dir: PathBuf,
+}
+
+impl Spool {
+    /// Spool inside a state directory
+    pub fn new(state_dir: &Path) -> Self {
+        Self {
+            dir: state_dir.join(SPOOL_DIR),
+        }
+    }
+
+    /// The spool directory
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Write `contents` to a new file readable by the current user only
+    ///
+    /// `name` becomes the end of the file name (e.g. `.env`), so tools that
+    /// go by extension still recognise the file.
+    pub fn write(&self, name: &str, contents: &[u8]) -> Result<SpoolFile> {
+        self.create_dir()?;
+        // tempfile creates the file 0600 on unix and without sharing on Windows
+        let mut file = tempfile::Builder::new()
+            .prefix(&format!("{}-", std::process::id()))
+            .suffix(&format!("-{}", name))
+            .tempfile_in(&self.dir)?;
+        file.write_all(contents)?;
+        file.as_file().sync_all()?;
+        Ok(SpoolFile {
+            path: file.into_temp_path(),
+        })
+    }
+
+    /// Delete files left behind by processes that are no longer running
+    ///
+    /// Returns how many were removed.
+    pub fn clean(&self) -> usize {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return 0;
+        };
+        let own = std::process::id();
+        entries
+            .flatten()
+            .filter(|entry| {
+                let owner = entry
+                    .file_name()
+                    .to_str()
+                    .and_then(|name| name.split_once('-'))
+                    .and_then(|(pid, _)| pid.parse::<u32>().ok());
+                match owner {
+                    Some(pid) if pid == own => false,
+                    Some(pid) => match process_running(pid) {
+                        Some(running) => !running,
+                        None => is_older_than(entry, STALE_AFTER),
+                    },
+                    None => is_older_than(entry, STALE_AFTER),
+                }
+            })
+            .filter(|entry| fs::remove_file(entry.path()).is_ok())
+            .count()
+    }
+
+    /// Create the directory, private to the current user on unix
+    ///
+    /// On Windows the state directory lives in the user's profile, whose
+    /// permissions the spool inherits.
+    fn create_dir(&self) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&self.dir, fs::Permissions::from_mode(0o700))?;
+        }
+        Ok(())
+    }
+}
+
+/// A file in the spool, deleted when dropped unless persisted
+#[derive(Debug)]
+pub struct SpoolFile {
+    path: TempPath,
+}
+
+impl SpoolFile {
+    /// Where the file is
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Keep the file where it is and return its path
+    pub fn persist(self) -> Result<PathBuf> {
+        Ok(self.path.keep().map_err(|e| e.error)?)
+    }
+
+    /// Move the file to `dest`, replacing what is there
+    ///
+    /// Falls back to copying when `dest` is on another filesystem; the spooled
+    /// copy is then deleted as usual.
+    pub fn persist_to(self, dest: &Path) -> Result<()> {
+        match self.path.persist(dest) {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                fs::copy(&e.path, dest)?;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Whether a process exists, None where that cannot be told cheaply
+fn process_running(pid: u32) -> Option<bool> {
+    if cfg!(target_os = "linux") {
+        Some(Path::new("/proc").join(pid.to_string()).exists())
+    } else {
+        None
+    }
+}
+
+fn is_older_than(entry: &fs::DirEntry, age: Duration) -> bool {
+    entry
+        .metadata()
+        .and_then(|meta| meta.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .is_some_and(|elapsed| elapsed > age)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spool_file_deleted_unless_persisted() {
+        let state = tempfile::tempdir().unwrap();
+        let spool = Spool::new(state.path());
+
+        let file = spool.write(".env", b"KEY=value").unwrap();
+        let path = file.path().to_path_buf();
+        assert!(path.starts_with(state.path().join(SPOOL_DIR)));
+        assert!(path.to_string_lossy().ends_with(".env"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "KEY=value");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+            assert_eq!(fs::metadata(spool.dir()).unwrap().permissions().mode() & 0o777, 0o700);
+        }
+        drop(file);
+        assert!(!path.exists());
+
+        let kept = spool.write(".env", b"KEY=value").unwrap().persist().unwrap();
+        assert!(kept.exists());
+
+        let dest = state.path().join("saved.env");
+        spool.write(".env", b"KEY=saved").unwrap().persist_to(&dest).unwrap();
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "KEY=saved");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_clean_removes_files_of_dead_processes() {
+        let state = tempfile::tempdir().unwrap();
+        let spool = Spool::new(state.path());
+        let ours = spool.write(".env", b"mine").unwrap().persist().unwrap();
+        // Above the kernel's pid_max, so never a running process
+        let orphan = spool.dir().join("4194305-abc-.env");
+        fs::write(&orphan, "left over").unwrap();
+
+        assert_eq!(spool.clean(), 1);
+        assert!(ours.exists());
+        assert!(!orphan.exists());
+    }
+}
@@ -0,0 +1,152 @@
+//! File-backed state store: one file per document in the state directory
+//!
+//! Files are created readable by their owner only, since session tokens are
+//! among the documents. Documents are written to a temporary file and renamed
+//! into place, so a reader never sees one half written.
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use super::StateStore;
+use crate::error::Result;
+
+/// Suffix of documents being written, left out of listings
+const TEMP_SUFFIX: &str = ".tmp";
+
+/// Write a file only its owner can read, such as a key or token
+///
+/// The file is created with mode 0600 rather than restricted after writing,
+/// so its contents are never readable by others, even briefly.
+pub fn write_private(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut options = OpenOptions::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.write(true).create(true).truncate(true).open(path)?;
+    // An existing file keeps its mode when opened
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+    }
+    file.write_all(contents.as_ref())?;
+    Ok(())
+}
+
+/// Documents as files and logs as JSON-lines files under a directory
+#[derive(Debug, Clone)]
+pub struct FileStore {
+    dir: PathBuf,
+}
+
+impl FileStore {
+    /// Store rooted at `dir` (usually the state directory)
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Path of a document or log
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+
+    /// Open a document for writing, creating it and its directory as needed
+    fn open(&self, name: &str, options: &mut OpenOptions) -> Result<File> {
+        let path = self.path(name);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        Ok(options.create(true).open(path)?)
+    }
+}
+
+impl StateStore for FileStore {
+    fn load(&self, name: &str) -> Result<Option<String>> {
+        match std::fs::read_to_string(self.path(name)) {
+            Ok(contents) => Ok(Some(contents)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save(&self, name: &str, contents: &str) -> Result<()> {
+        // Unique per writer, so two processes saving the same document do not share one
+        static WRITES: AtomicU64 = AtomicU64::new(0);
+        let temp = format!(
+            "{}.{}.{}{}",
+            name,
+            std::process::id(),
+            WRITES.fetch_add(1, Ordering::Relaxed),
+            TEMP_SUFFIX
+        );
+        let mut file = self.open(&temp, OpenOptions::new().write(true).truncate(true))?;
+        let written = file.write_all(contents.as_bytes());
+        drop(file);
+        let written = written.and_then(|()| std::fs::rename(self.path(&temp), self.path(name)));
+        if let Err(e) = written {
+            let _ = std::fs::remove_file(self.path(&temp));
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
+    fn remove(&self, name: &str) -> Result<()> {
+        match std::fs::remove_file(self.path(name)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<String>> {
+        let (dir, file_prefix) = match prefix.rsplit_once('/') {
+            Some((dir, file_prefix)) => (Some(dir), file_prefix),
+            None => (None, prefix),
+        };
+        let entries = match std::fs::read_dir(dir.map_or(self.dir.clone(), |dir| self.dir.join(dir))) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(entries
+            .flatten()
+            .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_file()))
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .filter(|file| file.starts_with(file_prefix) && !file.ends_with(TEMP_SUFFIX))
+            .map(|file| match dir {
+                Some(dir) => format!("{}/{}", dir, file),
+                None => file,
+            })
+            .collect())
+    }
+
+    fn append(&self, log: &str, record: &str) -> Result<()> {
+        let mut line = record.to_string();
+        line.push('\n');
+        let mut file = self.open(log, OpenOptions::new().append(true))?;
+        file.write_all(line.as_bytes())?;
+        Ok(())
+    }
+
+    fn records(&self, log: &str) -> Result<Vec<String>> {
+        Ok(self
+            .load(log)?
+            .map(|contents| contents.lines().map(str::to_string).collect())
+            .unwrap_or_default())
+    }
+
+    fn location(&self, name: &str) -> String {
+        self.path(name).display().to_string()
+    }
+}
@@ -0,0 +1,161 @@
+//! State Stores
+//!
+//! Where a node keeps what outlives a session: the peer book, session tokens,
+//! status snapshots and the audit log. Each is a named document (replaced as a
+//! whole) or a log (appended to) in a [`StateStore`]. Names are the file names
+//! the file store uses, e.g. `peers.json` or `status/<pid>.json`.
+//!
+//! [`FileStore`] keeps them as files in the state directory (the default).
+//! [`SqliteStore`] (feature `sqlite`) keeps them in one database, for servers
+//! that want atomic writes and SQL over the audit log. Embedders can hand in
+//! their own store with [`RiftConfig::with_state_store`](crate::RiftConfig::with_state_store).
+
+mod file;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+pub use file::{write_private, FileStore};
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStore;
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::error::Result;
+
+/// Default database file of the sqlite store inside the state directory
+pub const STATE_DB_FILE: &str = "state.db";
+
+/// Storage for daemon state that outlives a session
+///
+/// Implementations must be safe to share between tasks, and between the
+/// processes of one state directory (`rift share` next to `rift status`).
+pub trait StateStore: Send + Sync + fmt::Debug {
+    /// Contents of a document, None if it was never saved
+    fn load(&self, name: &str) -> Result<Option<String>>;
+
+    /// Replace a document
+    ///
+    /// Readers, in this process or another, must see either the old contents
+    /// or the new ones, never a partial write.
+    fn save(&self, name: &str, contents: &str) -> Result<()>;
+
+    /// Delete a document; deleting a missing one is not an error
+    fn remove(&self, name: &str) -> Result<()>;
+
+    /// Names of the documents starting with `prefix`
+    fn list(&self, prefix: &str) -> Result<Vec<String>>;
+
+    /// Append a record to a log
+    fn append(&self, log: &str, record: &str) -> Result<()>;
+
+    /// Records of a log, oldest first
+    fn records(&self, log: &str) -> Result<Vec<String>>;
+
+    /// Where a document or log lives, for messages to the user
+    fn location(&self, name: &str) -> String;
+}
+
+/// Which [`StateStore`] the daemon opens
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StateBackend {
+    /// One file per document in the state directory
+    #[default]
+    File,
+    /// One sqlite database (builds with the `sqlite` feature)
+    Sqlite,
+}
+
+impl fmt::Display for StateBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::File => "file",
+            Self::Sqlite => "sqlite",
+        })
+    }
+}
+
+/// Where daemon state is kept
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StateConfig {
+    /// `file` (default) or `sqlite`
+    pub backend: StateBackend,
+
+    /// Database of the sqlite backend, resolved against the state directory
+    /// when relative (default `state.db`)
+    pub path: Option<PathBuf>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Behaviour every backend must share
+    pub(super) fn exercise(store: &dyn StateStore) {
+        assert_eq!(store.load("peers.json").unwrap(), None);
+        store.save("peers.json", "{}").unwrap();
+        store.save("peers.json", "{\"a\":1}").unwrap();
+        assert_eq!(store.load("peers.json").unwrap().as_deref(), Some("{\"a\":1}"));
+
+        store.save("status/1.json", "one").unwrap();
+        store.save("status/2.json", "two").unwrap();
+        let mut listed = store.list("status/").unwrap();
+        listed.sort();
+        assert_eq!(listed, vec!["status/1.json", "status/2.json"]);
+
+        store.remove("status/1.json").unwrap();
+        store.remove("status/1.json").unwrap();
+        assert_eq!(store.list("status/").unwrap(), vec!["status/2.json"]);
+        assert!(store.list("missing/").unwrap().is_empty());
+
+        assert!(store.records("audit.log").unwrap().is_empty());
+        store.append("audit.log", "first").unwrap();
+        store.append("audit.log", "second").unwrap();
+        assert_eq!(store.records("audit.log").unwrap(), vec!["first", "second"]);
+    }
+
+    #[test]
+    fn test_file_store() {
+        let dir = tempfile::tempdir().unwrap();
+        exercise(&FileStore::new(dir.path().to_path_buf()));
+        assert!(dir.path().join("status").join("2.json").exists());
+
+        // A document still being written is not listed
+        std::fs::write(dir.path().join("status").join("3.json.42.0.tmp"), "{").unwrap();
+        let store = FileStore::new(dir.path().to_path_buf());
+        assert_eq!(store.list("status/").unwrap(), vec!["status/2.json"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_private_is_owner_only() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keys").join("key.pem");
+        write_private(&path, "secret").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "secret");
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+
+        // A file left readable by others is restricted when rewritten
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        write_private(&path, "new").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SqliteStore::open(&dir.path().join(STATE_DB_FILE)).unwrap();
+        exercise(&store);
+
+        // A second connection (another process) sees the same state
+        let other = SqliteStore::open(&dir.path().join(STATE_DB_FILE)).unwrap();
+        assert_eq!(other.records("audit.log").unwrap().len(), 2);
+    }
+}
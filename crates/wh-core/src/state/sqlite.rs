@@ -0,0 +1,122 @@
+//! SQLite-backed state store: every document and log in one database
+//!
+//! Documents are rows of `documents (name, contents, updated_at)` and log
+//! records rows of `records (id, log, record, created_at)`, so audit entries
+//! can be queried with e.g. `json_extract(record, '$.action')`.
+
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use super::StateStore;
+use crate::error::{Result, RiftError};
+
+/// How long a write waits for another process holding the database
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+const SCHEMA: &str = "
+    PRAGMA journal_mode = WAL;
+    CREATE TABLE IF NOT EXISTS documents (
+        name TEXT PRIMARY KEY,
+        contents TEXT NOT NULL,
+        updated_at INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS records (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        log TEXT NOT NULL,
+        record TEXT NOT NULL,
+        created_at INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS records_by_log ON records (log, id);
+";
+
+/// State kept in one sqlite database
+#[derive(Debug)]
+pub struct SqliteStore {
+    path: PathBuf,
+    connection: Mutex<Connection>,
+}
+
+impl SqliteStore {
+    /// Open (creating if needed) the database at `path`
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let connection = Connection::open(path)?;
+        connection.busy_timeout(BUSY_TIMEOUT)?;
+        connection.execute_batch(SCHEMA)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            connection: Mutex::new(connection),
+        })
+    }
+
+    fn with<T>(&self, f: impl FnOnce(&Connection) -> rusqlite::Result<T>) -> Result<T> {
+        let connection = self.connection.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        Ok(f(&connection)?)
+    }
+}
+
+impl StateStore for SqliteStore {
+    fn load(&self, name: &str) -> Result<Option<String>> {
+        self.with(|db| {
+            db.query_row("SELECT contents FROM documents WHERE name = ?1", [name], |row| row.get(0))
+                .optional()
+        })
+    }
+
+    fn save(&self, name: &str, contents: &str) -> Result<()> {
+        self.with(|db| {
+            db.execute(
+                "INSERT INTO documents (name, contents, updated_at) VALUES (?1, ?2, unixepoch())
+                 ON CONFLICT (name) DO UPDATE SET contents = excluded.contents, updated_at = excluded.updated_at",
+                params![name, contents],
+            )
+        })?;
+        Ok(())
+    }
+
+    fn remove(&self, name: &str) -> Result<()> {
+        self.with(|db| db.execute("DELETE FROM documents WHERE name = ?1", [name]))?;
+        Ok(())
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<String>> {
+        self.with(|db| {
+            let mut query =
+                db.prepare("SELECT name FROM documents WHERE substr(name, 1, length(?1)) = ?1 ORDER BY name")?;
+            let names = query.query_map([prefix], |row| row.get(0))?;
+            names.collect()
+        })
+    }
+
+    fn append(&self, log: &str, record: &str) -> Result<()> {
+        self.with(|db| {
+            db.execute(
+                "INSERT INTO records (log, record, created_at) VALUES (?1, ?2, unixepoch())",
+                params![log, record],
+            )
+        })?;
+        Ok(())
+    }
+
+    fn records(&self, log: &str) -> Result<Vec<String>> {
+        self.with(|db| {
+            let mut query = db.prepare("SELECT record FROM records WHERE log = ?1 ORDER BY id")?;
+            let records = query.query_map([log], |row| row.get(0))?;
+            records.collect()
+        })
+    }
+
+    fn location(&self, name: &str) -> String {
+        format!("{} ({})", self.path.display(), name)
+    }
+}
+
+impl From<rusqlite::Error> for RiftError {
+    fn from(err: rusqlite::Error) -> Self {
+        RiftError::StateStore(err.to_string())
+    }
+}
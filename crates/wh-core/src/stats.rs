@@ -0,0 +1,247 @@
+//! Traffic Statistics
+//!
+//! Lock-free fixed-bucket histograms for timing tunnel setup, cheap enough to
+//! record on every stream, and a heuristic classifier that labels tunnelled
+//! connections by their first bytes.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Upper bounds of the histogram buckets in milliseconds (last bucket is open-ended)
+pub const BUCKET_BOUNDS_MS: &[u64] = &[1, 2, 5, 10, 20, 50, 100, 200, 500, 1_000, 2_000, 5_000, 10_000];
+
+/// Histogram of durations with fixed millisecond buckets
+#[derive(Debug)]
+pub struct LatencyHistogram {
+    buckets: [AtomicU64; BUCKET_BOUNDS_MS.len() + 1],
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self {
+            buckets: std::array::from_fn(|_| AtomicU64::new(0)),
+        }
+    }
+}
+
+impl LatencyHistogram {
+    /// Create an empty histogram
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record one sample
+    pub fn record(&self, latency: Duration) {
+        let ms = latency.as_millis() as u64;
+        let index = BUCKET_BOUNDS_MS
+            .iter()
+            .position(|bound| ms <= *bound)
+            .unwrap_or(BUCKET_BOUNDS_MS.len());
+        self.buckets[index].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Total number of samples
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().map(|b| b.load(Ordering::Relaxed)).sum()
+    }
+
+    /// Upper bound (ms) of the bucket holding the `q` quantile, None if empty
+    ///
+    /// Samples beyond the last bound report that bound.
+    pub fn percentile_ms(&self, q: f64) -> Option<u64> {
+        let counts: Vec<u64> = self.buckets.iter().map(|b| b.load(Ordering::Relaxed)).collect();
+        let total: u64 = counts.iter().sum();
+        if total == 0 {
+            return None;
+        }
+
+        let target = ((total as f64) * q.clamp(0.0, 1.0)).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (index, count) in counts.iter().enumerate() {
+            seen += count;
+            if seen >= target {
+                let bound = BUCKET_BOUNDS_MS.get(index).or(BUCKET_BOUNDS_MS.last());
+                return bound.copied();
+            }
+        }
+        BUCKET_BOUNDS_MS.last().copied()
+    }
+
+    /// Summarize as count, p50 and p95
+    pub fn summary(&self) -> LatencySummary {
+        LatencySummary {
+            count: self.count(),
+            p50_ms: self.percentile_ms(0.50),
+            p95_ms: self.percentile_ms(0.95),
+        }
+    }
+
+    /// Clear all samples
+    pub fn reset(&self) {
+        for bucket in &self.buckets {
+            bucket.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Point-in-time summary of a histogram
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LatencySummary {
+    /// Number of samples
+    pub count: u64,
+    /// Median, as a bucket upper bound in milliseconds
+    pub p50_ms: Option<u64>,
+    /// 95th percentile, as a bucket upper bound in milliseconds
+    pub p95_ms: Option<u64>,
+}
+
+/// Bytes of a connection's first chunk looked at by [`TrafficClass::classify`]
+pub const CLASSIFY_SAMPLE_BYTES: usize = 16;
+
+/// Request methods that start an HTTP/1 request line
+const HTTP_METHODS: &[&[u8]] = &[
+    b"GET ", b"POST ", b"PUT ", b"DELETE ", b"HEAD ", b"OPTIONS ", b"PATCH ", b"CONNECT ", b"TRACE ",
+];
+
+/// Kind of traffic on a tunnelled connection, guessed from its first bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrafficClass {
+    /// TLS handshake (HTTPS and other TLS services)
+    Tls,
+    /// Plain HTTP/1 or HTTP/2 with prior knowledge
+    Http,
+    /// SSH version banner
+    Ssh,
+    /// Anything else
+    Unknown,
+}
+
+impl TrafficClass {
+    /// Classify a connection by the first bytes seen in either direction
+    ///
+    /// Only the first [`CLASSIFY_SAMPLE_BYTES`] are looked at. SSH servers
+    /// speak first, the other protocols start with the client.
+    pub fn classify(first_bytes: &[u8]) -> Self {
+        let sample = &first_bytes[..first_bytes.len().min(CLASSIFY_SAMPLE_BYTES)];
+        match sample {
+            // Handshake record, protocol major version 3 (SSL 3.0 to TLS 1.3)
+            [0x16, 0x03, 0x00..=0x04, ..] => Self::Tls,
+            _ if sample.starts_with(b"SSH-") => Self::Ssh,
+            _ if sample.starts_with(b"PRI * HTTP/2") || sample.starts_with(b"HTTP/1.") => Self::Http,
+            _ if HTTP_METHODS.iter().any(|method| sample.starts_with(method)) => Self::Http,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for TrafficClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Tls => "TLS",
+            Self::Http => "HTTP",
+            Self::Ssh => "SSH",
+            Self::Unknown => "other",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Connections and bytes of one traffic class
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClassCount {
+    /// The traffic class
+    pub class: TrafficClass,
+    /// Connections classified as this
+    pub connections: u64,
+    /// Bytes relayed by those connections, both directions together
+    pub bytes: u64,
+}
+
+/// Per-class connection and byte counts, ordered by class
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrafficBreakdown {
+    /// One entry per class seen so far
+    pub classes: Vec<ClassCount>,
+}
+
+impl TrafficBreakdown {
+    /// Count one connection of `class` with its bytes
+    pub fn add(&mut self, class: TrafficClass, bytes: u64) {
+        match self.classes.binary_search_by_key(&class, |count| count.class) {
+            Ok(index) => {
+                let count = &mut self.classes[index];
+                count.connections += 1;
+                count.bytes += bytes;
+            }
+            Err(index) => self.classes.insert(index, ClassCount { class, connections: 1, bytes }),
+        }
+    }
+
+    /// Check if no connection was classified
+    pub fn is_empty(&self) -> bool {
+        self.classes.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentiles() {
+        let histogram = LatencyHistogram::new();
+        assert_eq!(histogram.percentile_ms(0.5), None);
+
+        for _ in 0..90 {
+            histogram.record(Duration::from_millis(3));
+        }
+        for _ in 0..10 {
+            histogram.record(Duration::from_millis(150));
+        }
+
+        let summary = histogram.summary();
+        assert_eq!(summary.count, 100);
+        assert_eq!(summary.p50_ms, Some(5));
+        assert_eq!(summary.p95_ms, Some(200));
+    }
+
+    #[test]
+    fn test_overflow_bucket_and_reset() {
+        let histogram = LatencyHistogram::new();
+        histogram.record(Duration::from_secs(60));
+        assert_eq!(histogram.percentile_ms(0.5), Some(10_000));
+
+        histogram.reset();
+        assert_eq!(histogram.count(), 0);
+    }
+
+    #[test]
+    fn test_classify_first_bytes() {
+        assert_eq!(TrafficClass::classify(&[0x16, 0x03, 0x01, 0x02, 0x00]), TrafficClass::Tls);
+        assert_eq!(TrafficClass::classify(b"GET / HTTP/1.1\r\nHost: x\r\n"), TrafficClass::Http);
+        assert_eq!(TrafficClass::classify(b"HTTP/1.1 200 OK\r\n"), TrafficClass::Http);
+        assert_eq!(TrafficClass::classify(b"PRI * HTTP/2.0\r\n\r\nSM"), TrafficClass::Http);
+        assert_eq!(TrafficClass::classify(b"SSH-2.0-OpenSSH_9.6\r\n"), TrafficClass::Ssh);
+        assert_eq!(TrafficClass::classify(b"GETTING"), TrafficClass::Unknown);
+        assert_eq!(TrafficClass::classify(&[0x16, 0x03]), TrafficClass::Unknown);
+    }
+
+    #[test]
+    fn test_breakdown_counts_per_class() {
+        let mut breakdown = TrafficBreakdown::default();
+        breakdown.add(TrafficClass::Unknown, 5);
+        breakdown.add(TrafficClass::Http, 100);
+        breakdown.add(TrafficClass::Http, 20);
+
+        assert_eq!(
+            breakdown.classes,
+            vec![
+                ClassCount { class: TrafficClass::Http, connections: 2, bytes: 120 },
+                ClassCount { class: TrafficClass::Unknown, connections: 1, bytes: 5 },
+            ]
+        );
+    }
+}
@@ -0,0 +1,113 @@
+//! Shared Terminals
+//!
+//! Messages of `/rift/term/1.0.0`, which streams a host's terminal to approved
+//! viewers (`rift term share` / `rift term view`). Viewers are read-only unless
+//! the host allows input. Frames use the same length-prefixed bincode encoding
+//! as the secrets exchange.
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// Largest chunk of output sent in one message
+pub const MAX_TERM_CHUNK: usize = 16 * 1024;
+
+/// Output kept for viewers who join after the session started
+pub const SCROLLBACK_BYTES: usize = 64 * 1024;
+
+/// Messages carried on a terminal stream
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TermMessage {
+    /// Host -> viewer, first message: terminal size and whether input is accepted
+    Hello { cols: u16, rows: u16, interactive: bool },
+
+    /// Host -> viewer: terminal output
+    Output { data: Vec<u8> },
+
+    /// Host -> viewer: the host terminal was resized
+    Resize { cols: u16, rows: u16 },
+
+    /// Host -> viewer: the shared program exited
+    Exit { code: u32 },
+
+    /// Viewer -> host: keystrokes, ignored unless the session is interactive
+    Input { data: Vec<u8> },
+}
+
+impl TermMessage {
+    /// Split output into messages of at most [`MAX_TERM_CHUNK`] bytes
+    pub fn output(data: &[u8]) -> impl Iterator<Item = TermMessage> + '_ {
+        data.chunks(MAX_TERM_CHUNK)
+            .map(|chunk| TermMessage::Output { data: chunk.to_vec() })
+    }
+}
+
+/// Ring buffer of recent output, replayed to late joiners
+#[derive(Debug, Clone)]
+pub struct Scrollback {
+    buf: VecDeque<u8>,
+    capacity: usize,
+}
+
+impl Scrollback {
+    /// Create a buffer keeping the last `capacity` bytes
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            buf: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Append output, dropping the oldest bytes beyond capacity
+    pub fn push(&mut self, data: &[u8]) {
+        let data = &data[data.len().saturating_sub(self.capacity)..];
+        let overflow = (self.buf.len() + data.len()).saturating_sub(self.capacity);
+        self.buf.drain(..overflow);
+        self.buf.extend(data);
+    }
+
+    /// Buffered output, oldest first
+    pub fn contents(&self) -> Vec<u8> {
+        self.buf.iter().copied().collect()
+    }
+}
+
+impl Default for Scrollback {
+    fn default() -> Self {
+        Self::new(SCROLLBACK_BYTES)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scrollback_keeps_the_tail() {
+        let mut scrollback = Scrollback::new(8);
+        scrollback.push(b"hello");
+        assert_eq!(scrollback.contents(), b"hello");
+
+        scrollback.push(b" world");
+        assert_eq!(scrollback.contents(), b"lo world");
+
+        scrollback.push(b"0123456789");
+        assert_eq!(scrollback.contents(), b"23456789");
+    }
+
+    #[tokio::test]
+    async fn test_output_is_chunked_and_framed() {
+        let data = vec![b'x'; MAX_TERM_CHUNK * 2 + 1];
+        let messages: Vec<_> = TermMessage::output(&data).collect();
+        assert_eq!(messages.len(), 3);
+
+        let mut wire = Vec::new();
+        for message in &messages {
+            crate::send_secrets(&mut wire, message).await.unwrap();
+        }
+        let mut reader = &wire[..];
+        for message in &messages {
+            let decoded: TermMessage = crate::receive_secrets(&mut reader).await.unwrap();
+            assert_eq!(&decoded, message);
+        }
+    }
+}
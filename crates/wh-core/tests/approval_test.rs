@@ -113,6 +113,7 @@ async fn test_approval_event_flow() {
 }
 
 #[tokio::test]
+#[allow(clippy::assertions_on_constants)]
 async fn test_auto_approve_bypasses_check() {
     // Simulate auto-approve mode (no approval channel needed)
     let auto_approve = true;
@@ -89,11 +89,10 @@ async fn test_end_to_end_tunnel() -> Result<(), Box<dyn std::error::Error>> {
     
     // Step 2: Start Peer A (Sharer) - shares port 3000
     println!("[Setup] Starting Peer A (sharer)");
-    let peer_a_config = wh_core::RiftConfig {
-        listen_port: 9001,
-        identity_path: std::path::PathBuf::from("/tmp/rift_test_peer_a"),
-        ..Default::default()
-    };
+    let peer_a_dir = tempfile::tempdir()?;
+    let peer_a_config = wh_core::RiftConfig::new()
+        .with_listen_port(9001)
+        .with_config_dir(peer_a_dir.path());
     
     let mut peer_a_network = wh_core::PeerNetwork::new(peer_a_config).await?;
     peer_a_network.start_listening().await?;
@@ -110,7 +109,14 @@ async fn test_end_to_end_tunnel() -> Result<(), Box<dyn std::error::Error>> {
         while let Some((_peer_id, stream)) = peer_a_incoming.next().await {
             println!("[Peer A] Incoming stream! Bridging to localhost:3000");
             tokio::spawn(async move {
-                if let Err(e) = wh_core::bridge_stream_to_tcp(stream, 3000).await {
+                let bridged = match wh_core::read_tunnel_request(stream).await {
+                    Ok((request, stream)) => {
+                        assert_eq!(request.port, 3000);
+                        wh_core::bridge_stream_to_tcp(stream, request.port).await
+                    }
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = bridged {
                     eprintln!("[Peer A] Bridge error: {}", e);
                 } else {
                     println!("[Peer A] Bridge completed successfully");
@@ -129,11 +135,10 @@ async fn test_end_to_end_tunnel() -> Result<(), Box<dyn std::error::Error>> {
     
     // Step 3: Start Peer B (Connector) - connects to Peer A, listens on 8080
     println!("[Setup] Starting Peer B (connector)");
-    let peer_b_config = wh_core::RiftConfig {
-        listen_port: 9002,
-        identity_path: std::path::PathBuf::from("/tmp/rift_test_peer_b"),
-        ..Default::default()
-    };
+    let peer_b_dir = tempfile::tempdir()?;
+    let peer_b_config = wh_core::RiftConfig::new()
+        .with_listen_port(9002)
+        .with_config_dir(peer_b_dir.path());
     
     let mut peer_b_network = wh_core::PeerNetwork::new(peer_b_config).await?;
     peer_b_network.start_listening().await?;
@@ -229,7 +234,7 @@ async fn test_end_to_end_tunnel() -> Result<(), Box<dyn std::error::Error>> {
                                     tokio::spawn(async move {
                                         use tokio_util::compat::FuturesAsyncReadCompatExt;
                                         
-                                        match wh_core::open_tunnel_stream(&mut control, peer_id).await {
+                                        match wh_core::open_tunnel_stream(&mut control, &wh_core::Protocols::default(), peer_id, 3000).await {
                                             Ok(stream) => {
                                                 println!("[Peer B] Opened stream to peer, starting bridge");
                                                 let stream = stream.compat();
@@ -0,0 +1,208 @@
+//! Read-Only Viewers
+//!
+//! A peer listed in `viewers` may send [`ControlMessage::Attach`] on a control
+//! stream and keep it open. The daemon then streams its session model (status
+//! snapshot and connected peers) on that stream about once a second, and
+//! `rift attach` on the other end renders it in the usual dashboard. Viewers
+//! only watch: nothing they send after the attach is read.
+//!
+//! [`ControlMessage::Attach`]: wh_core::control::ControlMessage::Attach
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tokio::io::AsyncWrite;
+use tokio::sync::watch;
+use wh_core::{send_secrets, Result};
+
+use crate::server::{DaemonEvent, PeerRecord};
+use crate::status::StatusSnapshot;
+
+/// What a viewer is shown of the host
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionView {
+    /// The host's link
+    pub link: String,
+    /// Stats and open connections, as written for `rift status`
+    pub snapshot: StatusSnapshot,
+    /// Connected peers
+    pub peers: Vec<PeerRecord>,
+}
+
+/// Frames sent by the host to a viewer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ViewerUpdate {
+    /// The current session, as [`SessionView`] JSON
+    ///
+    /// JSON rather than nested fields: peer records leave out unset fields,
+    /// which the binary framing cannot express.
+    Session { view: String },
+
+    /// The host did not let this peer attach
+    Refused { reason: String },
+}
+
+impl ViewerUpdate {
+    /// Wrap a session view
+    pub fn session(view: &SessionView) -> Result<Self> {
+        Ok(ViewerUpdate::Session {
+            view: serde_json::to_string(view)?,
+        })
+    }
+}
+
+impl SessionView {
+    /// Read the view out of an update, or the host's reason for refusing
+    pub fn from_update(update: ViewerUpdate) -> std::result::Result<Self, String> {
+        match update {
+            ViewerUpdate::Session { view } => serde_json::from_str(&view).map_err(|e| format!("bad session view: {}", e)),
+            ViewerUpdate::Refused { reason } => Err(reason),
+        }
+    }
+
+    /// Dashboard events that bring a viewer from `previous` to this view
+    ///
+    /// The first view announces the host and every peer; later ones only the
+    /// peers that came or went. Stats and round trips are sent every time.
+    pub fn events_since(&self, previous: Option<&SessionView>) -> Vec<DaemonEvent> {
+        let mut events = Vec::new();
+        let known: HashMap<&str, Option<&str>> = previous
+            .map(|view| view.peers.iter().map(|peer| (peer.peer_id.as_str(), peer.nickname.as_deref())).collect())
+            .unwrap_or_default();
+        if previous.is_none() {
+            events.push(DaemonEvent::Ready {
+                peer_id: self.snapshot.peer_id.clone(),
+                link: self.link.clone(),
+            });
+        }
+
+        for peer in &self.peers {
+            match known.get(peer.peer_id.as_str()) {
+                None => events.push(DaemonEvent::PeerConnected {
+                    peer_id: peer.peer_id.clone(),
+                    nickname: peer.nickname.clone(),
+                }),
+                // Renamed since the last view
+                Some(nickname) if *nickname != peer.nickname.as_deref() => {
+                    if let Some(nickname) = &peer.nickname {
+                        events.push(DaemonEvent::PeerNamed {
+                            peer_id: peer.peer_id.clone(),
+                            nickname: nickname.clone(),
+                        });
+                    }
+                }
+                Some(_) => {}
+            }
+            if let Some(rtt_ms) = peer.rtt_ms {
+                events.push(DaemonEvent::PeerRtt {
+                    peer_id: peer.peer_id.clone(),
+                    last_ms: rtt_ms,
+                    average_ms: rtt_ms,
+                });
+            }
+        }
+        if let Some(previous) = previous {
+            for peer in &previous.peers {
+                if !self.peers.iter().any(|current| current.peer_id == peer.peer_id) {
+                    events.push(DaemonEvent::PeerDisconnected {
+                        peer_id: peer.peer_id.clone(),
+                    });
+                }
+            }
+        }
+
+        let snapshot = &self.snapshot;
+        events.push(DaemonEvent::StatsUpdate {
+            bytes_sent: snapshot.bytes_sent,
+            bytes_received: snapshot.bytes_received,
+            session_bytes_sent: snapshot.session_bytes_sent,
+            session_bytes_received: snapshot.session_bytes_received,
+            active_connections: snapshot.active_connections,
+            live_tasks: snapshot.live_tasks,
+            traffic_classes: snapshot.traffic_classes.clone(),
+            ports: snapshot.ports.clone(),
+            peers: snapshot.peer_traffic.clone(),
+        });
+        events
+    }
+}
+
+/// Stream every new view to a viewer until it goes away or the daemon stops
+pub async fn serve_viewer<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    mut views: watch::Receiver<Option<SessionView>>,
+) -> Result<()> {
+    loop {
+        let view = views.borrow_and_update().clone();
+        if let Some(view) = view {
+            send_secrets(writer, &ViewerUpdate::session(&view)?).await?;
+        }
+        if views.changed().await.is_err() {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wh_core::{receive_secrets, ConnectionDirection, ConnectionPath};
+
+    fn peer(peer_id: &str, nickname: Option<&str>) -> PeerRecord {
+        PeerRecord {
+            peer_id: peer_id.to_string(),
+            nickname: nickname.map(str::to_string),
+            addresses: Vec::new(),
+            connected_secs: 5,
+            direction: ConnectionDirection::Inbound,
+            path: ConnectionPath::Direct,
+            rtt_ms: None,
+            capabilities: None,
+        }
+    }
+
+    #[test]
+    fn test_events_follow_peers_coming_and_going() {
+        let first = SessionView {
+            link: "rift://host".to_string(),
+            peers: vec![peer("a", Some("laptop")), peer("b", None)],
+            ..Default::default()
+        };
+        let events = first.events_since(None);
+        assert!(matches!(&events[0], DaemonEvent::Ready { link, .. } if link == "rift://host"));
+        assert!(events.iter().any(|e| matches!(e, DaemonEvent::PeerConnected { nickname: Some(nickname), .. } if nickname == "laptop")));
+        assert_eq!(events.iter().filter(|e| matches!(e, DaemonEvent::PeerConnected { .. })).count(), 2);
+
+        let second = SessionView {
+            peers: vec![peer("b", None), peer("c", None)],
+            ..first.clone()
+        };
+        let events = second.events_since(Some(&first));
+        assert!(!events.iter().any(|e| matches!(e, DaemonEvent::Ready { .. })));
+        assert!(events.iter().any(|e| matches!(e, DaemonEvent::PeerConnected { peer_id, .. } if peer_id == "c")));
+        assert!(events.iter().any(|e| matches!(e, DaemonEvent::PeerDisconnected { peer_id } if peer_id == "a")));
+        assert!(matches!(events.last(), Some(DaemonEvent::StatsUpdate { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_serve_viewer_streams_views_until_the_daemon_stops() {
+        let (views_tx, views_rx) = watch::channel(None);
+        let (mut host, mut viewer) = tokio::io::duplex(64 * 1024);
+        let server = tokio::spawn(async move { serve_viewer(&mut host, views_rx).await });
+
+        let view = SessionView {
+            link: "rift://host".to_string(),
+            peers: vec![peer("a", None)],
+            ..Default::default()
+        };
+        views_tx.send_replace(Some(view.clone()));
+        let update: ViewerUpdate = receive_secrets(&mut viewer).await.unwrap();
+        assert_eq!(SessionView::from_update(update).unwrap(), view);
+
+        drop(views_tx);
+        server.await.unwrap().unwrap();
+        assert_eq!(
+            SessionView::from_update(ViewerUpdate::Refused { reason: "not a viewer".to_string() }),
+            Err("not a viewer".to_string())
+        );
+    }
+}
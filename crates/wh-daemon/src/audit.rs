@@ -0,0 +1,165 @@
+//! Audit Log
+//!
+//! Append-only record of security decisions (rejected streams and the like),
+//! one JSON object per record in the state store (per line with the file store).
+//!
+//! Records are hash-chained: `prev` holds the chain hash up to the record
+//! before, so editing or deleting a record breaks the chain at the next one
+//! and [`AuditLog::verify`] points at it. Records written before chaining
+//! existed have no `prev` and are not checked.
+
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+use wh_core::hash::{self, ContentHash};
+use wh_core::{Result, StateStore};
+
+use crate::tokens::unix_now;
+
+/// Name of the audit log in the state store
+pub const AUDIT_LOG_FILE: &str = "audit.log";
+
+/// A single audit record
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntry<'a> {
+    /// Unix timestamp (seconds)
+    pub timestamp: u64,
+    /// Peer the decision concerns
+    pub peer_id: &'a str,
+    /// What happened, e.g. `stream_rejected`
+    pub action: &'a str,
+    /// Free-form detail
+    pub detail: &'a str,
+    /// Chain hash of the records before this one
+    pub prev: ContentHash,
+}
+
+/// Outcome of [`AuditLog::verify`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChainStatus {
+    /// Every record matches the ones before it; holds the record count
+    Intact(usize),
+    /// Index (from 0) of the first record whose `prev` does not match
+    Broken(usize),
+}
+
+/// Appends audit entries to the state store
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    store: Arc<dyn StateStore>,
+    /// Held from reading the last record until the new one is written
+    append_lock: Arc<Mutex<()>>,
+}
+
+impl AuditLog {
+    /// Create an audit log writing to `store`
+    pub fn new(store: Arc<dyn StateStore>) -> Self {
+        Self {
+            store,
+            append_lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// Where the log is kept, for messages to the user
+    pub fn location(&self) -> String {
+        self.store.location(AUDIT_LOG_FILE)
+    }
+
+    /// Record an entry; failures are logged but never fatal
+    pub fn record(&self, peer_id: &str, action: &str, detail: &str) {
+        warn!(target: "rift::audit", "{} {}: {}", action, peer_id, detail);
+
+        if let Err(e) = self.append(peer_id, action, detail) {
+            warn!("Failed to write audit log {}: {}", self.location(), e);
+        }
+    }
+
+    /// Check the hash chain
+    pub fn verify(&self) -> Result<ChainStatus> {
+        let records = self.store.records(AUDIT_LOG_FILE)?;
+        let mut head = ContentHash::GENESIS;
+        let mut chained = false;
+        for (index, record) in records.iter().enumerate() {
+            match record_prev(record) {
+                Some(prev) if prev == head => chained = true,
+                // Unchained records may only precede the chain
+                None if !chained => {}
+                _ => return Ok(ChainStatus::Broken(index)),
+            }
+            head = chain_head(record);
+        }
+        Ok(ChainStatus::Intact(records.len()))
+    }
+
+    fn append(&self, peer_id: &str, action: &str, detail: &str) -> Result<()> {
+        let _guard = self.append_lock.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let prev = self
+            .store
+            .records(AUDIT_LOG_FILE)?
+            .last()
+            .map_or(ContentHash::GENESIS, |last| chain_head(last));
+        let entry = AuditEntry {
+            timestamp: unix_now(),
+            peer_id,
+            action,
+            detail,
+            prev,
+        };
+        self.store.append(AUDIT_LOG_FILE, &serde_json::to_string(&entry)?)
+    }
+}
+
+/// The `prev` hash a record carries, None for records from before chaining
+fn record_prev(record: &str) -> Option<ContentHash> {
+    let value: serde_json::Value = serde_json::from_str(record).ok()?;
+    value.get("prev")?.as_str()?.parse().ok()
+}
+
+/// Chain hash up to and including a record
+fn chain_head(record: &str) -> ContentHash {
+    hash::chain(&record_prev(record).unwrap_or(ContentHash::GENESIS), record.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_appends_json_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = AuditLog::new(Arc::new(wh_core::FileStore::new(dir.path().to_path_buf())));
+
+        log.record("peer-a", "stream_rejected", "protocol secrets not allowed");
+        log.record("peer-b", "stream_rejected", "protocol tunnel not allowed");
+
+        let contents = std::fs::read_to_string(dir.path().join(AUDIT_LOG_FILE)).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 2);
+
+        let first: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(first["peer_id"], "peer-a");
+        assert_eq!(first["action"], "stream_rejected");
+    }
+
+    #[test]
+    fn test_chain_detects_edits() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(AUDIT_LOG_FILE);
+        // A record from before chaining stays readable
+        std::fs::write(&path, "{\"timestamp\":1,\"peer_id\":\"old\",\"action\":\"link_redeemed\",\"detail\":\"x\"}\n").unwrap();
+        let log = AuditLog::new(Arc::new(wh_core::FileStore::new(dir.path().to_path_buf())));
+
+        for peer in ["peer-a", "peer-b", "peer-c"] {
+            log.record(peer, "stream_rejected", "protocol secrets not allowed");
+        }
+        assert_eq!(log.verify().unwrap(), ChainStatus::Intact(4));
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(&path, contents.replace("peer-b", "peer-x")).unwrap();
+        assert_eq!(log.verify().unwrap(), ChainStatus::Broken(3));
+
+        let lines: Vec<&str> = contents.lines().collect();
+        std::fs::write(&path, format!("{}\n{}\n{}\n", lines[0], lines[1], lines[3])).unwrap();
+        assert_eq!(log.verify().unwrap(), ChainStatus::Broken(2));
+    }
+}
@@ -0,0 +1,122 @@
+//! Connect Attempts
+//!
+//! Connect-side state while `DaemonCommand::Connect` looks for the host. The
+//! link is resolved in a task, then the run loop dials whenever an attempt is
+//! due, so commands (including `DaemonCommand::CancelConnect`) and network
+//! events keep being handled between attempts.
+
+use std::time::Instant;
+use wh_core::{DialRetryConfig, PeerId};
+
+/// A connect command still looking for its host
+#[derive(Debug)]
+pub struct ConnectAttempt {
+    /// The link as given
+    pub link: String,
+    /// Port on the host
+    pub port: u16,
+    /// Local port to listen on (defaults to `port`)
+    pub local_port: Option<u16>,
+    /// Address the local listener binds to
+    pub bind_addr: String,
+    /// Further ports on the host, each with the local port to listen on
+    pub forwards: Vec<(u16, u16)>,
+    /// Link naming the host by ID and the host, once resolved
+    target: Option<(String, PeerId)>,
+    /// Attempts made so far
+    attempts: u32,
+    next_at: Instant,
+}
+
+impl ConnectAttempt {
+    /// Start with the link still to be resolved
+    pub fn new(link: String, port: u16, local_port: Option<u16>, bind_addr: String) -> Self {
+        Self {
+            link,
+            port,
+            local_port,
+            bind_addr,
+            forwards: Vec::new(),
+            target: None,
+            attempts: 0,
+            next_at: Instant::now(),
+        }
+    }
+
+    /// Also forward these (host port, local port) pairs over the connection
+    pub fn with_forwards(mut self, forwards: Vec<(u16, u16)>) -> Self {
+        self.forwards = forwards;
+        self
+    }
+
+    /// The link resolved to `link` naming `peer_id`; the first attempt is due right away
+    pub fn resolved(&mut self, link: String, peer_id: PeerId, now: Instant) {
+        self.target = Some((link, peer_id));
+        self.next_at = now;
+    }
+
+    /// Resolved link to dial and the host it names
+    pub fn target(&self) -> Option<(&str, PeerId)> {
+        self.target.as_ref().map(|(link, peer_id)| (link.as_str(), *peer_id))
+    }
+
+    /// When the next attempt is due; None while the link is being resolved
+    pub fn next_at(&self) -> Option<Instant> {
+        self.target.as_ref().map(|_| self.next_at)
+    }
+
+    /// Take the due attempt and schedule the one after; returns its number (1-based)
+    pub fn start_attempt(&mut self, policy: &DialRetryConfig, now: Instant) -> u32 {
+        self.attempts += 1;
+        self.next_at = now + policy.jittered_backoff(self.attempts);
+        self.attempts
+    }
+
+    /// Whether the attempts allowed by `policy` are used up
+    pub fn exhausted(&self, policy: &DialRetryConfig) -> bool {
+        self.attempts >= policy.attempts.max(1)
+    }
+
+    /// Attempts made so far
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// The host's peer ID once known, the link otherwise (as events name it)
+    pub fn peer_label(&self) -> String {
+        match &self.target {
+            Some((_, peer_id)) => peer_id.to_string(),
+            None => self.link.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_attempts_wait_for_resolution_and_run_out() {
+        let policy = DialRetryConfig {
+            attempts: 2,
+            jitter_percent: 0,
+            ..DialRetryConfig::default()
+        };
+        let mut attempt = ConnectAttempt::new("rift://example".to_string(), 3000, None, "127.0.0.1".to_string());
+        assert_eq!(attempt.next_at(), None);
+        assert_eq!(attempt.peer_label(), "rift://example");
+
+        let peer_id = PeerId::random();
+        let now = Instant::now();
+        attempt.resolved(format!("rift://{}/3000", peer_id), peer_id, now);
+        assert_eq!(attempt.next_at(), Some(now));
+        assert_eq!(attempt.peer_label(), peer_id.to_string());
+
+        assert_eq!(attempt.start_attempt(&policy, now), 1);
+        assert_eq!(attempt.next_at(), Some(now + Duration::from_millis(250)));
+        assert!(!attempt.exhausted(&policy));
+        assert_eq!(attempt.start_attempt(&policy, now), 2);
+        assert!(attempt.exhausted(&policy));
+    }
+}
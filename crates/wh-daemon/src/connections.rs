@@ -0,0 +1,646 @@
+//! Connection Table
+//!
+//! Live record of every tunnelled TCP connection, kept by the bridge tasks
+//! themselves so the daemon can report per-connection detail on request and
+//! close a single connection without touching the rest of the peer's session.
+//! Rates are sampled with [`ConnectionTable::sample_rates`], once a second.
+//! Guards given a lifecycle channel also report each connection opening and
+//! closing as a [`ConnectionEvent`], which the daemon passes on to its clients.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::borrow::Cow;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use wh_core::stats::{TrafficBreakdown, TrafficClass};
+use tokio::sync::mpsc;
+use wh_core::ConnectionPath;
+
+use crate::middleware::{ConnectionInfo, Direction, MiddlewareChain};
+use crate::tokens::unix_now;
+
+/// Snapshot of one tunnelled connection
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConnectionRecord {
+    /// Daemon-local connection ID
+    pub id: u64,
+    /// Remote peer
+    pub peer_id: String,
+    /// "share" (peer reached our port) or "connect" (we reached theirs)
+    pub session: String,
+    /// Shared port the connection ends up at
+    pub port: u16,
+    /// How the traffic travels
+    pub path: ConnectionPath,
+    /// Unix timestamp (seconds) the connection was opened
+    pub started_at: u64,
+    /// Unix timestamp (seconds) of the last byte relayed
+    pub last_activity: u64,
+    /// Bytes sent towards the peer
+    pub bytes_sent: u64,
+    /// Bytes received from the peer
+    pub bytes_received: u64,
+    /// Kind of traffic, once the first bytes were seen
+    #[serde(default)]
+    pub class: Option<TrafficClass>,
+    /// Seconds the connection has been open
+    #[serde(default)]
+    pub duration_secs: u64,
+    /// Bytes per second sent towards the peer, over the last sample
+    #[serde(default)]
+    pub rate_sent: u64,
+    /// Bytes per second received from the peer, over the last sample
+    #[serde(default)]
+    pub rate_received: u64,
+}
+
+/// Connections and bytes of one port, closed and open connections together
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PortStats {
+    pub port: u16,
+    /// Connections open right now
+    pub open: usize,
+    /// Connections since the daemon started
+    pub connections: u64,
+    /// Bytes sent towards the peer
+    pub bytes_sent: u64,
+    /// Bytes received from the peer
+    pub bytes_received: u64,
+}
+
+impl PortStats {
+    fn add(&mut self, entry: &Entry) {
+        self.connections += 1;
+        self.bytes_sent += entry.bytes_sent.load(Ordering::Relaxed);
+        self.bytes_received += entry.bytes_received.load(Ordering::Relaxed);
+    }
+}
+
+/// Connections, bytes and current rate of one peer, closed and open connections together
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerStats {
+    pub peer_id: String,
+    /// Connections open right now
+    pub open: usize,
+    /// Connections since the daemon started
+    pub connections: u64,
+    /// Bytes sent towards the peer
+    pub bytes_sent: u64,
+    /// Bytes received from the peer
+    pub bytes_received: u64,
+    /// Bytes per second sent towards the peer by the open connections
+    pub rate_sent: u64,
+    /// Bytes per second received from the peer by the open connections
+    pub rate_received: u64,
+}
+
+impl PeerStats {
+    fn add(&mut self, entry: &Entry) {
+        self.connections += 1;
+        self.bytes_sent += entry.bytes_sent.load(Ordering::Relaxed);
+        self.bytes_received += entry.bytes_received.load(Ordering::Relaxed);
+    }
+}
+
+/// Byte counts at the last rate sample, and the rates measured then
+#[derive(Debug, Clone, Copy)]
+struct RateSample {
+    at: Instant,
+    sent: u64,
+    received: u64,
+    rate_sent: u64,
+    rate_received: u64,
+}
+
+#[derive(Debug)]
+struct Entry {
+    peer_id: String,
+    session: &'static str,
+    port: u16,
+    path: ConnectionPath,
+    started_at: u64,
+    opened: Instant,
+    last_activity: AtomicU64,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    class: OnceLock<TrafficClass>,
+    rate: Mutex<RateSample>,
+    /// Cancelled by [`ConnectionTable::close`]
+    closed: CancellationToken,
+}
+
+impl Entry {
+    fn bytes(&self) -> u64 {
+        self.bytes_sent.load(Ordering::Relaxed) + self.bytes_received.load(Ordering::Relaxed)
+    }
+}
+
+/// Registry of open connections, shared with bridge tasks
+#[derive(Debug, Default)]
+pub struct ConnectionTable {
+    next_id: AtomicU64,
+    entries: Mutex<HashMap<u64, Arc<Entry>>>,
+    /// Traffic classes of the connections already closed
+    closed_classes: Mutex<TrafficBreakdown>,
+    /// Totals per port of the connections already closed
+    closed_ports: Mutex<BTreeMap<u16, PortStats>>,
+    /// Totals per peer of the connections already closed
+    closed_peers: Mutex<BTreeMap<String, PeerStats>>,
+}
+
+impl ConnectionTable {
+    /// Create an empty table
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    /// Register a connection; it is removed when the returned guard is dropped
+    pub fn open(
+        self: &Arc<Self>,
+        peer_id: impl ToString,
+        session: &'static str,
+        port: u16,
+        path: ConnectionPath,
+    ) -> ConnectionGuard {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let now = unix_now();
+        let opened = Instant::now();
+        let entry = Arc::new(Entry {
+            peer_id: peer_id.to_string(),
+            session,
+            port,
+            path,
+            started_at: now,
+            opened,
+            last_activity: AtomicU64::new(now),
+            bytes_sent: AtomicU64::new(0),
+            bytes_received: AtomicU64::new(0),
+            class: OnceLock::new(),
+            rate: Mutex::new(RateSample {
+                at: opened,
+                sent: 0,
+                received: 0,
+                rate_sent: 0,
+                rate_received: 0,
+            }),
+            closed: CancellationToken::new(),
+        });
+        self.entries.lock().unwrap().insert(id, entry.clone());
+
+        ConnectionGuard {
+            id,
+            entry,
+            table: self.clone(),
+            middlewares: None,
+            events: None,
+        }
+    }
+
+    /// Records of all open connections, oldest first
+    ///
+    /// `path_of` may override the path recorded at open time, e.g. after a
+    /// relayed connection was upgraded to a direct one.
+    pub fn records(&self, path_of: impl Fn(&str) -> Option<ConnectionPath>) -> Vec<ConnectionRecord> {
+        let mut records: Vec<ConnectionRecord> = self
+            .entries
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, entry)| {
+                let rate = *entry.rate.lock().unwrap();
+                ConnectionRecord {
+                    id: *id,
+                    peer_id: entry.peer_id.clone(),
+                    session: entry.session.to_string(),
+                    port: entry.port,
+                    path: match entry.path {
+                        ConnectionPath::Loopback => ConnectionPath::Loopback,
+                        path => path_of(&entry.peer_id).unwrap_or(path),
+                    },
+                    started_at: entry.started_at,
+                    last_activity: entry.last_activity.load(Ordering::Relaxed),
+                    bytes_sent: entry.bytes_sent.load(Ordering::Relaxed),
+                    bytes_received: entry.bytes_received.load(Ordering::Relaxed),
+                    class: entry.class.get().copied(),
+                    duration_secs: entry.opened.elapsed().as_secs(),
+                    rate_sent: rate.rate_sent,
+                    rate_received: rate.rate_received,
+                }
+            })
+            .collect();
+        records.sort_by_key(|record| record.id);
+        records
+    }
+
+    /// Close an open connection: its bridge task stops and drops both ends
+    ///
+    /// Returns the peer and port of the connection, or None if no open
+    /// connection has this ID.
+    pub fn close(&self, id: u64) -> Option<(String, u16)> {
+        let entries = self.entries.lock().unwrap();
+        let entry = entries.get(&id)?;
+        entry.closed.cancel();
+        Some((entry.peer_id.clone(), entry.port))
+    }
+
+    /// Close the open connections that relayed nothing for `timeout_secs` as of `now`
+    ///
+    /// Returns the ID, peer, port and idle seconds of each one. Loopback
+    /// connections only count their bytes once done, so they are left alone.
+    pub fn close_idle(&self, timeout_secs: u64, now: u64) -> Vec<(u64, String, u16, u64)> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .iter()
+            .filter(|(_, entry)| entry.path != ConnectionPath::Loopback && !entry.closed.is_cancelled())
+            .filter_map(|(id, entry)| {
+                let idle = now.saturating_sub(entry.last_activity.load(Ordering::Relaxed));
+                (idle >= timeout_secs).then(|| {
+                    entry.closed.cancel();
+                    (*id, entry.peer_id.clone(), entry.port, idle)
+                })
+            })
+            .collect()
+    }
+
+    /// Connections and bytes per traffic class, closed and open connections together
+    ///
+    /// Connections that have not relayed a byte yet are not counted.
+    pub fn traffic_classes(&self) -> TrafficBreakdown {
+        let mut breakdown = self.closed_classes.lock().unwrap().clone();
+        for entry in self.entries.lock().unwrap().values() {
+            if let Some(class) = entry.class.get() {
+                breakdown.add(*class, entry.bytes());
+            }
+        }
+        breakdown
+    }
+
+    /// Connections and bytes per port, ordered by port
+    pub fn port_stats(&self) -> Vec<PortStats> {
+        let mut ports = self.closed_ports.lock().unwrap().clone();
+        for entry in self.entries.lock().unwrap().values() {
+            let stats = ports.entry(entry.port).or_insert_with(|| PortStats { port: entry.port, ..Default::default() });
+            stats.open += 1;
+            stats.add(entry);
+        }
+        ports.into_values().collect()
+    }
+
+    /// Connections, bytes and current rate per peer, ordered by peer ID
+    pub fn peer_stats(&self) -> Vec<PeerStats> {
+        let mut peers = self.closed_peers.lock().unwrap().clone();
+        for entry in self.entries.lock().unwrap().values() {
+            let stats = peers
+                .entry(entry.peer_id.clone())
+                .or_insert_with(|| PeerStats { peer_id: entry.peer_id.clone(), ..Default::default() });
+            let rate = *entry.rate.lock().unwrap();
+            stats.open += 1;
+            stats.add(entry);
+            stats.rate_sent += rate.rate_sent;
+            stats.rate_received += rate.rate_received;
+        }
+        peers.into_values().collect()
+    }
+
+    /// Measure the rate of every open connection since the previous sample
+    pub fn sample_rates(&self, now: Instant) {
+        for entry in self.entries.lock().unwrap().values() {
+            let mut rate = entry.rate.lock().unwrap();
+            let elapsed = now.saturating_duration_since(rate.at).as_secs_f64();
+            if elapsed <= 0.0 {
+                continue;
+            }
+            let sent = entry.bytes_sent.load(Ordering::Relaxed);
+            let received = entry.bytes_received.load(Ordering::Relaxed);
+            *rate = RateSample {
+                at: now,
+                sent,
+                received,
+                rate_sent: (sent.saturating_sub(rate.sent) as f64 / elapsed) as u64,
+                rate_received: (received.saturating_sub(rate.received) as f64 / elapsed) as u64,
+            };
+        }
+    }
+
+    /// Number of open connections of a session ("share" or "connect") on `port`
+    pub fn open_on_port(&self, session: &str, port: u16) -> usize {
+        self.entries
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|entry| entry.session == session && entry.port == port)
+            .count()
+    }
+
+    /// Number of open connections
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    /// Check if no connections are open
+    pub fn is_empty(&self) -> bool {
+        self.entries.lock().unwrap().is_empty()
+    }
+}
+
+/// A connection opening or closing, as reported by its guard
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectionEvent {
+    /// A tunnelled connection was registered
+    Opened { connection_id: u64, peer_id: String, port: u16 },
+    /// A tunnelled connection closed after relaying these bytes (sent towards the peer, received from it)
+    Closed { connection_id: u64, peer_id: String, port: u16, bytes_sent: u64, bytes_received: u64, duration_ms: u64 },
+}
+
+/// Handle held by a bridge task for the lifetime of its connection
+#[derive(Debug)]
+pub struct ConnectionGuard {
+    id: u64,
+    entry: Arc<Entry>,
+    table: Arc<ConnectionTable>,
+    /// Middlewares run on this connection, with what they are told about it
+    middlewares: Option<(MiddlewareChain, ConnectionInfo)>,
+    /// Where the opening and closing of the connection are reported
+    events: Option<mpsc::UnboundedSender<ConnectionEvent>>,
+}
+
+impl ConnectionGuard {
+    /// Run `chain` on this connection, starting with its `on_connect` hooks
+    pub fn with_middlewares(mut self, chain: MiddlewareChain) -> Self {
+        if chain.is_empty() {
+            return self;
+        }
+        let info = ConnectionInfo {
+            id: self.id,
+            peer_id: self.entry.peer_id.clone(),
+            session: self.entry.session,
+            port: self.entry.port,
+        };
+        chain.on_connect(&info);
+        self.middlewares = Some((chain, info));
+        self
+    }
+
+    /// Report the connection as opened now, and as closed once the guard is dropped
+    ///
+    /// The channel is unbounded so that a guard dropped while its reader is
+    /// busy still gets its close through; only a gone reader loses events.
+    pub fn with_events(mut self, events: mpsc::UnboundedSender<ConnectionEvent>) -> Self {
+        let _ = events.send(ConnectionEvent::Opened {
+            connection_id: self.id,
+            peer_id: self.entry.peer_id.clone(),
+            port: self.entry.port,
+        });
+        self.events = Some(events);
+        self
+    }
+
+    /// A chunk about to be relayed, as the middlewares leave it
+    pub fn filter<'a>(&self, direction: Direction, chunk: &'a [u8]) -> Cow<'a, [u8]> {
+        match &self.middlewares {
+            Some((chain, info)) => chain.apply(info, direction, chunk),
+            None => Cow::Borrowed(chunk),
+        }
+    }
+
+    /// ID of the connection in the table
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Time since the connection was registered
+    pub fn elapsed(&self) -> Duration {
+        self.entry.opened.elapsed()
+    }
+
+    /// Resolves once the connection is closed with [`ConnectionTable::close`]
+    pub async fn closed(&self) {
+        self.entry.closed.cancelled().await
+    }
+
+    /// Run a bridge until it ends or the connection is closed
+    ///
+    /// Returns None, having dropped the bridge and its sockets, when the
+    /// connection was closed with [`ConnectionTable::close`].
+    pub async fn run<F: Future>(&self, bridge: F) -> Option<F::Output> {
+        tokio::select! {
+            output = bridge => Some(output),
+            _ = self.closed() => None,
+        }
+    }
+
+    /// Classify the connection by the first bytes relayed in either direction
+    ///
+    /// Only the first non-empty chunk is looked at; later calls are cheap no-ops.
+    pub fn sniff(&self, data: &[u8]) {
+        if self.entry.class.get().is_none() && !data.is_empty() {
+            let _ = self.entry.class.set(TrafficClass::classify(data));
+        }
+    }
+
+    /// Count bytes sent towards the peer
+    pub fn record_sent(&self, bytes: u64) {
+        self.entry.bytes_sent.fetch_add(bytes, Ordering::Relaxed);
+        self.entry.last_activity.store(unix_now(), Ordering::Relaxed);
+    }
+
+    /// Count bytes received from the peer
+    pub fn record_received(&self, bytes: u64) {
+        self.entry.bytes_received.fetch_add(bytes, Ordering::Relaxed);
+        self.entry.last_activity.store(unix_now(), Ordering::Relaxed);
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.table.entries.lock().unwrap().remove(&self.id);
+        let bytes_sent = self.entry.bytes_sent.load(Ordering::Relaxed);
+        let bytes_received = self.entry.bytes_received.load(Ordering::Relaxed);
+        if let Some((chain, info)) = &self.middlewares {
+            chain.on_close(info, bytes_sent, bytes_received);
+        }
+        if let Some(events) = &self.events {
+            let _ = events.send(ConnectionEvent::Closed {
+                connection_id: self.id,
+                peer_id: self.entry.peer_id.clone(),
+                port: self.entry.port,
+                bytes_sent,
+                bytes_received,
+                duration_ms: self.entry.opened.elapsed().as_millis() as u64,
+            });
+        }
+        if let Some(class) = self.entry.class.get() {
+            self.table.closed_classes.lock().unwrap().add(*class, self.entry.bytes());
+        }
+        let port = self.entry.port;
+        self.table
+            .closed_ports
+            .lock()
+            .unwrap()
+            .entry(port)
+            .or_insert_with(|| PortStats { port, ..Default::default() })
+            .add(&self.entry);
+        let peer_id = &self.entry.peer_id;
+        self.table
+            .closed_peers
+            .lock()
+            .unwrap()
+            .entry(peer_id.clone())
+            .or_insert_with(|| PeerStats { peer_id: peer_id.clone(), ..Default::default() })
+            .add(&self.entry);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guard_tracks_bytes_and_unregisters() {
+        let table = ConnectionTable::new();
+        let first = table.open("peer-a", "share", 3000, ConnectionPath::Relay);
+        let second = table.open("peer-b", "connect", 8080, ConnectionPath::Loopback);
+
+        first.record_sent(10);
+        first.record_received(32);
+
+        let records = table.records(|peer| (peer == "peer-a").then_some(ConnectionPath::Direct));
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].id, first.id());
+        assert_eq!((records[0].bytes_sent, records[0].bytes_received), (10, 32));
+        assert_eq!(records[0].path, ConnectionPath::Direct);
+        assert_eq!(records[1].path, ConnectionPath::Loopback);
+
+        drop(first);
+        assert_eq!(table.len(), 1);
+        drop(second);
+        assert!(table.is_empty());
+    }
+
+    #[test]
+    fn test_guard_reports_opening_and_closing() {
+        let (events, mut rx) = mpsc::unbounded_channel();
+        let table = ConnectionTable::new();
+        let conn = table.open("peer-a", "share", 3000, ConnectionPath::Direct).with_events(events);
+        let id = conn.id();
+        conn.record_sent(5);
+        conn.record_received(7);
+        drop(conn);
+
+        assert_eq!(
+            rx.try_recv().unwrap(),
+            ConnectionEvent::Opened { connection_id: id, peer_id: "peer-a".to_string(), port: 3000 }
+        );
+        assert!(matches!(
+            rx.try_recv().unwrap(),
+            ConnectionEvent::Closed { connection_id, port: 3000, bytes_sent: 5, bytes_received: 7, .. } if connection_id == id
+        ));
+    }
+
+    #[test]
+    fn test_traffic_classes_keep_closed_connections() {
+        let table = ConnectionTable::new();
+        let tls = table.open("peer-a", "share", 443, ConnectionPath::Direct);
+        let idle = table.open("peer-a", "share", 443, ConnectionPath::Direct);
+        tls.sniff(&[0x16, 0x03, 0x01, 0x00, 0xc8]);
+        tls.sniff(b"GET / HTTP/1.1\r\n");
+        tls.record_received(200);
+        drop(tls);
+
+        let http = table.open("peer-b", "share", 80, ConnectionPath::Direct);
+        http.sniff(b"GET / HTTP/1.1\r\n");
+        http.record_received(16);
+        assert_eq!(table.records(|_| None)[1].class, Some(TrafficClass::Http));
+
+        let classes = table.traffic_classes().classes;
+        assert_eq!(classes.len(), 2);
+        assert_eq!((classes[0].class, classes[0].connections, classes[0].bytes), (TrafficClass::Tls, 1, 200));
+        assert_eq!((classes[1].class, classes[1].connections, classes[1].bytes), (TrafficClass::Http, 1, 16));
+        drop(idle);
+        assert_eq!(table.traffic_classes().classes.len(), 2);
+    }
+
+    #[test]
+    fn test_port_stats_add_up_per_port() {
+        let table = ConnectionTable::new();
+        let web = table.open("peer-a", "share", 3000, ConnectionPath::Direct);
+        web.record_sent(100);
+        drop(web);
+        let web = table.open("peer-b", "share", 3000, ConnectionPath::Direct);
+        web.record_received(50);
+        let db = table.open("peer-a", "share", 5432, ConnectionPath::Direct);
+        db.record_sent(7);
+
+        let ports = table.port_stats();
+        assert_eq!(ports.len(), 2);
+        assert_eq!(
+            ports[0],
+            PortStats { port: 3000, open: 1, connections: 2, bytes_sent: 100, bytes_received: 50 }
+        );
+        assert_eq!(ports[1], PortStats { port: 5432, open: 1, connections: 1, bytes_sent: 7, bytes_received: 0 });
+        drop(db);
+        assert_eq!(table.port_stats()[1].open, 0);
+    }
+
+    #[test]
+    fn test_peer_stats_sum_bytes_and_rates() {
+        let table = ConnectionTable::new();
+        let first = table.open("peer-a", "share", 3000, ConnectionPath::Direct);
+        let second = table.open("peer-a", "share", 5432, ConnectionPath::Direct);
+        let other = table.open("peer-b", "share", 3000, ConnectionPath::Direct);
+        let start = Instant::now();
+
+        first.record_sent(1_000);
+        second.record_received(500);
+        other.record_sent(10);
+        table.sample_rates(start + Duration::from_secs(2));
+        let records = table.records(|_| None);
+        assert!(records[0].rate_sent > 400 && records[0].rate_sent <= 500);
+
+        drop(other);
+        let peers = table.peer_stats();
+        assert_eq!(peers.len(), 2);
+        assert_eq!((peers[0].peer_id.as_str(), peers[0].open, peers[0].connections), ("peer-a", 2, 2));
+        assert_eq!((peers[0].bytes_sent, peers[0].bytes_received), (1_000, 500));
+        assert!(peers[0].rate_sent > 0 && peers[0].rate_received > 0);
+        assert_eq!((peers[1].open, peers[1].bytes_sent, peers[1].rate_sent), (0, 10, 0));
+
+        // Nothing new since the last sample
+        table.sample_rates(start + Duration::from_secs(3));
+        assert_eq!(table.peer_stats()[0].rate_sent, 0);
+    }
+
+    #[tokio::test]
+    async fn test_close_stops_only_that_connection() {
+        let table = ConnectionTable::new();
+        let stuck = table.open("peer-a", "share", 3000, ConnectionPath::Direct);
+        let other = table.open("peer-a", "share", 3000, ConnectionPath::Direct);
+
+        assert_eq!(table.close(stuck.id()), Some(("peer-a".to_string(), 3000)));
+        assert_eq!(stuck.run(std::future::pending::<()>()).await, None);
+        assert_eq!(other.run(async { 7 }).await, Some(7));
+        assert_eq!(table.close(99), None);
+    }
+
+    #[tokio::test]
+    async fn test_close_idle_closes_quiet_connections_once() {
+        let table = ConnectionTable::new();
+        let quiet = table.open("peer-a", "share", 3000, ConnectionPath::Direct);
+        let busy = table.open("peer-a", "share", 3000, ConnectionPath::Relay);
+        let loopback = table.open("peer-b", "connect", 3000, ConnectionPath::Loopback);
+        let now = unix_now();
+
+        assert!(table.close_idle(60, now).is_empty());
+        let later = now + 60;
+        busy.entry.last_activity.store(later, Ordering::Relaxed);
+        assert_eq!(table.close_idle(60, later), vec![(quiet.id(), "peer-a".to_string(), 3000, 60)]);
+        assert_eq!(quiet.run(std::future::pending::<()>()).await, None);
+        assert_eq!(busy.run(async { 7 }).await, Some(7));
+        assert_eq!(loopback.run(async { 7 }).await, Some(7));
+        // Already closed, so not reported again
+        assert!(table.close_idle(60, later).is_empty());
+    }
+}
@@ -0,0 +1,58 @@
+//! Service Health Probe
+//!
+//! The host side connects to its shared port (or its `--target`) now and then; the outcome is
+//! sent to peers as a [`ServiceHealth`] over the control protocol.
+
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use wh_core::control::{HealthStatus, ServiceHealth};
+use wh_core::HealthConfig;
+
+/// Longest a probe waits for the service to accept
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Connect to `host:port` once and classify the result
+pub async fn probe(host: &str, port: u16, config: &HealthConfig) -> ServiceHealth {
+    let started = Instant::now();
+    match tokio::time::timeout(PROBE_TIMEOUT, TcpStream::connect((host, port))).await {
+        Ok(Ok(_)) => {
+            let elapsed = started.elapsed();
+            if elapsed > Duration::from_millis(config.degraded_ms) {
+                ServiceHealth {
+                    status: HealthStatus::Degraded,
+                    message: format!("slow to accept ({}ms)", elapsed.as_millis()),
+                }
+            } else {
+                ServiceHealth {
+                    status: HealthStatus::Up,
+                    message: "accepting connections".to_string(),
+                }
+            }
+        }
+        Ok(Err(e)) => ServiceHealth {
+            status: HealthStatus::Down,
+            message: e.to_string(),
+        },
+        Err(_) => ServiceHealth {
+            status: HealthStatus::Down,
+            message: format!("no answer within {}s", PROBE_TIMEOUT.as_secs()),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_probe_reports_up_and_down() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let config = HealthConfig::default();
+
+        assert_eq!(probe("127.0.0.1", port, &config).await.status, HealthStatus::Up);
+        drop(listener);
+        assert_eq!(probe("127.0.0.1", port, &config).await.status, HealthStatus::Down);
+    }
+}
@@ -0,0 +1,633 @@
+//! HTTP Request Logging
+//!
+//! Passively follows HTTP/1.x traffic through a tunnelled connection and
+//! reports each request's method, path, status and duration, optionally
+//! keeping whole exchanges for the inspector. Bytes are never altered;
+//! traffic that does not parse as HTTP is simply ignored.
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Instant;
+use tracing::{info, warn};
+
+use crate::tokens::unix_now;
+
+/// File name of the access log inside the state directory
+pub const ACCESS_LOG_FILE: &str = "access.log";
+
+/// Largest message head (start line plus headers) that is buffered
+const MAX_HEAD_SIZE: usize = 64 * 1024;
+
+/// A completed request/response pair
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HttpExchange {
+    /// Request method, e.g. `GET`
+    pub method: String,
+    /// Request target, e.g. `/api/users?page=2`
+    pub path: String,
+    /// Response status code
+    pub status: u16,
+    /// Milliseconds from the request head to the response head
+    pub duration_ms: u64,
+}
+
+impl fmt::Display for HttpExchange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {} {}ms", self.method, self.path, self.status, self.duration_ms)
+    }
+}
+
+/// Start line and headers of a message
+#[derive(Debug, Clone)]
+pub(crate) struct MessageHead {
+    pub(crate) start_line: String,
+    headers: Vec<(String, String)>,
+}
+
+impl MessageHead {
+    fn parse(bytes: &[u8]) -> Self {
+        let text = String::from_utf8_lossy(bytes);
+        let mut lines = text.split("\r\n");
+        let start_line = lines.next().unwrap_or_default().to_string();
+        let headers = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+            .collect();
+        Self { start_line, headers }
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Body framing declared by the headers, if any
+    pub(crate) fn declared_body(&self) -> Option<Body> {
+        if self
+            .header("transfer-encoding")
+            .is_some_and(|value| value.to_ascii_lowercase().contains("chunked"))
+        {
+            return Some(Body::Chunked);
+        }
+        self.header("content-length")
+            .and_then(|value| value.parse().ok())
+            .map(Body::Length)
+    }
+}
+
+/// How the body following a head is delimited
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Body {
+    Length(u64),
+    Chunked,
+    UntilClose,
+    /// Protocol switched (`101`); the rest of the stream is not HTTP
+    Upgrade,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Head,
+    Fixed(u64),
+    ChunkSize,
+    ChunkData(u64),
+    Trailer,
+    UntilClose,
+    Upgraded,
+    Broken,
+}
+
+/// A message kept for inspection
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CapturedMessage {
+    /// Start line and headers, without the blank line ending them
+    pub head: String,
+    /// Raw body bytes as sent, empty if redacted
+    pub body: Vec<u8>,
+    /// Full body size in bytes
+    pub body_size: u64,
+    /// The body exceeded the capture limit and was dropped
+    pub body_redacted: bool,
+}
+
+impl CapturedMessage {
+    fn new(head: String) -> Self {
+        Self {
+            head,
+            body: Vec::new(),
+            body_size: 0,
+            body_redacted: false,
+        }
+    }
+
+    fn push_body(&mut self, bytes: &[u8], max_body: usize) {
+        self.body_size += bytes.len() as u64;
+        if self.body_redacted {
+            return;
+        }
+        if self.body.len() + bytes.len() > max_body {
+            self.body = Vec::new();
+            self.body_redacted = true;
+        } else {
+            self.body.extend_from_slice(bytes);
+        }
+    }
+
+    /// The message as it went over the wire, if the body was kept
+    pub fn to_bytes(&self) -> Option<Vec<u8>> {
+        if self.body_redacted {
+            return None;
+        }
+        let mut bytes = Vec::with_capacity(self.head.len() + 4 + self.body.len());
+        bytes.extend_from_slice(self.head.as_bytes());
+        bytes.extend_from_slice(b"\r\n\r\n");
+        bytes.extend_from_slice(&self.body);
+        Some(bytes)
+    }
+}
+
+/// A request/response pair kept for the inspector
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InspectedExchange {
+    /// Summary of the exchange
+    pub exchange: HttpExchange,
+    /// The request, if it was captured in full
+    pub request: Option<CapturedMessage>,
+    /// The response
+    pub response: CapturedMessage,
+}
+
+/// Receives what a [`MessageParser`] finds
+pub(crate) trait MessageSink {
+    /// A head was parsed; return its body framing, or `None` if it is not HTTP
+    fn head(&mut self, head: MessageHead) -> Option<Body>;
+
+    /// `len` bytes of body data went by (chunk framing excluded)
+    fn body(&mut self, _len: usize) {}
+
+    /// The current message ended (`captured` is set when capturing)
+    fn end(&mut self, _captured: Option<CapturedMessage>) {}
+}
+
+/// Splits one direction of an HTTP/1.x byte stream into messages
+#[derive(Debug)]
+pub(crate) struct MessageParser {
+    buf: Vec<u8>,
+    state: State,
+    /// Body capture limit, `None` when messages are not captured
+    max_body: Option<usize>,
+    current: Option<CapturedMessage>,
+}
+
+impl MessageParser {
+    pub(crate) fn new(max_body: Option<usize>) -> Self {
+        Self {
+            buf: Vec::new(),
+            state: State::Head,
+            max_body,
+            current: None,
+        }
+    }
+
+    fn push_body(&mut self, range: std::ops::Range<usize>) {
+        if let (Some(current), Some(max_body)) = (&mut self.current, self.max_body) {
+            current.push_body(&self.buf[range], max_body);
+        }
+    }
+
+    fn end_message(&mut self, sink: &mut impl MessageSink) {
+        self.state = State::Head;
+        sink.end(self.current.take());
+    }
+
+    /// Feed bytes through the parser, reporting messages to `sink`
+    pub(crate) fn feed(&mut self, data: &[u8], sink: &mut impl MessageSink) {
+        match self.state {
+            State::Broken | State::Upgraded => return,
+            State::UntilClose if self.current.is_none() => return,
+            _ => {}
+        }
+        self.buf.extend_from_slice(data);
+        let mut pos = 0;
+
+        while pos < self.buf.len() {
+            let rest = &self.buf[pos..];
+            match self.state {
+                State::Broken | State::Upgraded => {
+                    pos = self.buf.len();
+                }
+                State::UntilClose => {
+                    sink.body(self.buf.len() - pos);
+                    self.push_body(pos..self.buf.len());
+                    pos = self.buf.len();
+                }
+                State::Fixed(remaining) | State::ChunkData(remaining) => {
+                    let n = remaining.min(rest.len() as u64) as usize;
+                    sink.body(n);
+                    self.push_body(pos..pos + n);
+                    pos += n;
+                    let remaining = remaining - n as u64;
+                    match self.state {
+                        State::Fixed(_) if remaining == 0 => self.end_message(sink),
+                        State::Fixed(_) => self.state = State::Fixed(remaining),
+                        _ if remaining == 0 => self.state = State::ChunkSize,
+                        _ => self.state = State::ChunkData(remaining),
+                    }
+                }
+                State::Head => {
+                    // Tolerate stray line breaks between messages
+                    if rest[0] == b'\r' || rest[0] == b'\n' {
+                        pos += 1;
+                        continue;
+                    }
+                    let Some(end) = rest.windows(4).position(|w| w == b"\r\n\r\n") else {
+                        if rest.len() > MAX_HEAD_SIZE {
+                            self.state = State::Broken;
+                        }
+                        break;
+                    };
+                    let head = MessageHead::parse(&rest[..end]);
+                    self.current = self
+                        .max_body
+                        .map(|_| CapturedMessage::new(String::from_utf8_lossy(&rest[..end]).into_owned()));
+                    pos += end + 4;
+                    match sink.head(head) {
+                        None => {
+                            self.current = None;
+                            self.state = State::Broken;
+                        }
+                        Some(Body::Length(0)) => self.end_message(sink),
+                        Some(Body::Length(n)) => self.state = State::Fixed(n),
+                        Some(Body::Chunked) => self.state = State::ChunkSize,
+                        Some(Body::UntilClose) => self.state = State::UntilClose,
+                        Some(Body::Upgrade) => {
+                            self.end_message(sink);
+                            self.state = State::Upgraded;
+                        }
+                    }
+                }
+                State::ChunkSize | State::Trailer => {
+                    let Some(end) = rest.iter().position(|b| *b == b'\n') else {
+                        if rest.len() > MAX_HEAD_SIZE {
+                            self.state = State::Broken;
+                        }
+                        break;
+                    };
+                    let line = String::from_utf8_lossy(&rest[..end]).trim().to_string();
+                    self.push_body(pos..pos + end + 1);
+                    pos += end + 1;
+                    match (self.state, line.as_str()) {
+                        // CRLF closing the previous chunk's data
+                        (State::ChunkSize, "") => {}
+                        (State::ChunkSize, _) => {
+                            let size = line.split(';').next().unwrap_or_default().trim();
+                            self.state = match u64::from_str_radix(size, 16) {
+                                Ok(0) => State::Trailer,
+                                Ok(size) => State::ChunkData(size),
+                                Err(_) => State::Broken,
+                            };
+                        }
+                        (_, "") => self.end_message(sink),
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        self.buf.drain(..pos.min(self.buf.len()));
+        if matches!(self.state, State::Broken | State::Upgraded) {
+            self.buf = Vec::new();
+            self.current = None;
+        }
+    }
+
+    /// Whether the parser gave up on the stream (not HTTP, or upgraded)
+    pub(crate) fn stopped(&self) -> bool {
+        matches!(self.state, State::Broken | State::Upgraded)
+    }
+
+    /// The connection closed; ends a body delimited by the close
+    fn close(&mut self, sink: &mut impl MessageSink) {
+        if self.state == State::UntilClose {
+            self.end_message(sink);
+        }
+    }
+}
+
+#[derive(Debug)]
+struct PendingRequest {
+    method: String,
+    path: String,
+    started_at: Instant,
+    captured: Option<CapturedMessage>,
+}
+
+struct RequestSink<'a> {
+    pending: &'a mut VecDeque<PendingRequest>,
+}
+
+impl MessageSink for RequestSink<'_> {
+    fn head(&mut self, head: MessageHead) -> Option<Body> {
+        let mut parts = head.start_line.split_whitespace();
+        let (method, path, version) = (parts.next()?, parts.next()?, parts.next()?);
+        if !version.starts_with("HTTP/1.") {
+            return None;
+        }
+        self.pending.push_back(PendingRequest {
+            method: method.to_string(),
+            path: path.to_string(),
+            started_at: Instant::now(),
+            captured: None,
+        });
+        // Requests without framing headers carry no body
+        Some(head.declared_body().unwrap_or(Body::Length(0)))
+    }
+
+    fn end(&mut self, captured: Option<CapturedMessage>) {
+        if let Some(request) = self.pending.back_mut() {
+            request.captured = captured;
+        }
+    }
+}
+
+struct ResponseSink<'a> {
+    pending: &'a mut VecDeque<PendingRequest>,
+    in_flight: &'a mut Option<(HttpExchange, Option<CapturedMessage>)>,
+    exchanges: Vec<HttpExchange>,
+    inspected: &'a mut Vec<InspectedExchange>,
+}
+
+impl MessageSink for ResponseSink<'_> {
+    fn head(&mut self, head: MessageHead) -> Option<Body> {
+        let mut parts = head.start_line.split_whitespace();
+        let version = parts.next()?;
+        let status: u16 = parts.next()?.parse().ok()?;
+        if !version.starts_with("HTTP/1.") {
+            return None;
+        }
+        // Interim responses precede the real one
+        if (100..200).contains(&status) && status != 101 {
+            return Some(Body::Length(0));
+        }
+
+        let request = self.pending.pop_front();
+        let head_request = request.as_ref().is_some_and(|r| r.method == "HEAD");
+        if let Some(request) = request {
+            let exchange = HttpExchange {
+                method: request.method,
+                path: request.path,
+                status,
+                duration_ms: request.started_at.elapsed().as_millis() as u64,
+            };
+            self.exchanges.push(exchange.clone());
+            *self.in_flight = Some((exchange, request.captured));
+        }
+
+        Some(match status {
+            // Upgraded connections (WebSocket etc.) are no longer HTTP
+            101 => Body::Upgrade,
+            204 | 304 => Body::Length(0),
+            _ if head_request => Body::Length(0),
+            _ => head.declared_body().unwrap_or(Body::UntilClose),
+        })
+    }
+
+    fn end(&mut self, captured: Option<CapturedMessage>) {
+        if let (Some((exchange, request)), Some(response)) = (self.in_flight.take(), captured) {
+            self.inspected.push(InspectedExchange {
+                exchange,
+                request,
+                response,
+            });
+        }
+    }
+}
+
+/// Pairs requests sent to the peer with the responses coming back
+#[derive(Debug)]
+pub struct HttpObserver {
+    requests: MessageParser,
+    responses: MessageParser,
+    pending: VecDeque<PendingRequest>,
+    in_flight: Option<(HttpExchange, Option<CapturedMessage>)>,
+    inspected: Vec<InspectedExchange>,
+}
+
+impl Default for HttpObserver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HttpObserver {
+    /// Create an observer for a fresh connection
+    pub fn new() -> Self {
+        Self::with_capture(None)
+    }
+
+    /// Create an observer that also keeps whole messages for inspection
+    ///
+    /// Bodies larger than `max_body` bytes are redacted.
+    pub fn capturing(max_body: usize) -> Self {
+        Self::with_capture(Some(max_body))
+    }
+
+    fn with_capture(max_body: Option<usize>) -> Self {
+        Self {
+            requests: MessageParser::new(max_body),
+            responses: MessageParser::new(max_body),
+            pending: VecDeque::new(),
+            in_flight: None,
+            inspected: Vec::new(),
+        }
+    }
+
+    /// Observe bytes travelling from the local client towards the peer
+    pub fn on_request_bytes(&mut self, data: &[u8]) {
+        let mut sink = RequestSink {
+            pending: &mut self.pending,
+        };
+        self.requests.feed(data, &mut sink);
+    }
+
+    /// Observe bytes travelling from the peer back to the local client
+    ///
+    /// Returns the exchanges whose response head arrived with these bytes.
+    pub fn on_response_bytes(&mut self, data: &[u8]) -> Vec<HttpExchange> {
+        let mut sink = ResponseSink {
+            pending: &mut self.pending,
+            in_flight: &mut self.in_flight,
+            exchanges: Vec::new(),
+            inspected: &mut self.inspected,
+        };
+        self.responses.feed(data, &mut sink);
+        sink.exchanges
+    }
+
+    /// The connection closed; completes a response delimited by the close
+    pub fn on_close(&mut self) {
+        let mut sink = ResponseSink {
+            pending: &mut self.pending,
+            in_flight: &mut self.in_flight,
+            exchanges: Vec::new(),
+            inspected: &mut self.inspected,
+        };
+        self.responses.close(&mut sink);
+    }
+
+    /// Take the exchanges captured in full since the last call
+    pub fn take_inspected(&mut self) -> Vec<InspectedExchange> {
+        std::mem::take(&mut self.inspected)
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct AccessEntry<'a> {
+    timestamp: u64,
+    peer_id: &'a str,
+    #[serde(flatten)]
+    exchange: &'a HttpExchange,
+}
+
+/// Appends completed exchanges to an access log file
+#[derive(Debug, Clone)]
+pub struct AccessLog {
+    path: PathBuf,
+}
+
+impl AccessLog {
+    /// Create an access log writing to `path`
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// Path of the log file
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    /// Record an exchange; failures are logged but never fatal
+    pub fn record(&self, peer_id: &str, exchange: &HttpExchange) {
+        info!(target: "rift::http", "{}", exchange);
+        let entry = AccessEntry {
+            timestamp: unix_now(),
+            peer_id,
+            exchange,
+        };
+        if let Err(e) = self.append(&entry) {
+            warn!("Failed to write access log {}: {}", self.path.display(), e);
+        }
+    }
+
+    fn append(&self, entry: &AccessEntry<'_>) -> std::io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        file.write_all(line.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(exchanges: &[HttpExchange]) -> Vec<(String, String, u16)> {
+        exchanges
+            .iter()
+            .map(|e| (e.method.clone(), e.path.clone(), e.status))
+            .collect()
+    }
+
+    #[test]
+    fn test_pairs_pipelined_requests_across_split_reads() {
+        let mut observer = HttpObserver::new();
+        let requests = b"GET / HTTP/1.1\r\nHost: a\r\n\r\nPOST /api HTTP/1.1\r\nContent-Length: 5\r\n\r\nhelloHEAD /x HTTP/1.1\r\n\r\n";
+        for chunk in requests.chunks(7) {
+            observer.on_request_bytes(chunk);
+        }
+
+        let responses: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Length: 3\r\n\r\nabcHTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 201 Created\r\nTransfer-Encoding: chunked\r\n\r\n4\r\nabcd\r\n0\r\n\r\nHTTP/1.1 404 Not Found\r\nContent-Length: 9\r\n\r\n";
+        let mut exchanges = Vec::new();
+        for chunk in responses.chunks(5) {
+            exchanges.extend(observer.on_response_bytes(chunk));
+        }
+
+        assert_eq!(
+            summary(&exchanges),
+            vec![
+                ("GET".into(), "/".into(), 200),
+                ("POST".into(), "/api".into(), 201),
+                ("HEAD".into(), "/x".into(), 404),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ignores_non_http_and_upgrades() {
+        let mut observer = HttpObserver::new();
+        observer.on_request_bytes(b"\x16\x03\x01\x02\x00binary handshake\r\n\r\n");
+        assert!(observer.on_response_bytes(b"HTTP/1.1 200 OK\r\n\r\n").is_empty());
+
+        let mut observer = HttpObserver::new();
+        observer.on_request_bytes(b"GET /ws HTTP/1.1\r\nUpgrade: websocket\r\n\r\n");
+        let exchanges = observer.on_response_bytes(b"HTTP/1.1 101 Switching Protocols\r\n\r\nHTTP/1.1 200 OK\r\n\r\n");
+        assert_eq!(summary(&exchanges), vec![("GET".into(), "/ws".into(), 101)]);
+    }
+
+    #[test]
+    fn test_captures_exchanges_for_inspection() {
+        let mut observer = HttpObserver::capturing(16);
+        let post = b"POST /upload HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n0\r\n\r\n";
+        observer.on_request_bytes(post);
+        observer.on_request_bytes(b"GET /big HTTP/1.1\r\n\r\n");
+
+        observer.on_response_bytes(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok");
+        observer.on_response_bytes(b"HTTP/1.0 200 OK\r\n\r\n0123456789abcdefghij");
+        let first = observer.take_inspected();
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].request.as_ref().and_then(|r| r.to_bytes()), Some(post.to_vec()));
+        assert_eq!(first[0].response.body, b"ok");
+
+        // Body delimited by the close; too large to keep
+        observer.on_close();
+        let second = observer.take_inspected();
+        assert_eq!(second.len(), 1);
+        assert_eq!(second[0].exchange.path, "/big");
+        assert!(second[0].response.body_redacted);
+        assert_eq!(second[0].response.body_size, 20);
+    }
+
+    #[test]
+    fn test_access_log_appends_json_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = AccessLog::new(dir.path().join(ACCESS_LOG_FILE));
+        let exchange = HttpExchange {
+            method: "GET".into(),
+            path: "/health".into(),
+            status: 200,
+            duration_ms: 12,
+        };
+        log.record("peer-a", &exchange);
+        log.record("peer-a", &exchange);
+
+        let contents = std::fs::read_to_string(log.path()).unwrap();
+        let lines: Vec<serde_json::Value> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["path"], "/health");
+        assert_eq!(lines[0]["duration_ms"], 12);
+        assert_eq!(exchange.to_string(), "GET /health 200 12ms");
+    }
+}
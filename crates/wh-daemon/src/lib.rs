@@ -2,8 +2,30 @@
 //!
 //! Background service that manages P2P connections, tunnels, and secrets sharing.
 
+pub mod attach;
+pub mod audit;
+pub mod connect_attempt;
+pub mod connections;
+pub mod health;
+pub mod http_log;
+pub mod links;
+pub mod middleware;
+pub mod migration;
+pub mod pool;
+pub mod preflight;
+pub mod rate_limit;
+pub mod reconnect;
+pub mod remote;
+pub mod resources;
+pub mod secret_scopes;
+pub mod secrets_queue;
 pub mod server;
 pub mod session;
+pub mod sockets;
+pub mod status;
+pub mod tasks;
+pub mod tokens;
+pub mod upload_guard;
 
 pub use server::{DaemonCommand, DaemonEvent, DaemonServer};
 pub use session::{ConnectSession, ShareSession};
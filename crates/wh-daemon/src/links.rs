@@ -0,0 +1,278 @@
+//! Revocable Links
+//!
+//! Peer IDs are long-lived, so a plain rift:// link works for as long as the
+//! host keeps its identity. A revocable link adds an ID that the connecting
+//! peer presents before opening tunnels; the host can let IDs expire or
+//! revoke them (`rift links revoke <ID>`). Guest links (`rift share --guest`)
+//! also cut off the peers that used them once they expire. Links are persisted
+//! in the state dir.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::time::SystemTime;
+use tracing::warn;
+use wh_core::crypto::constant_time_eq;
+
+use crate::tokens::unix_now;
+
+/// File name of the link store inside the state directory
+pub const LINKS_FILE: &str = "links.json";
+
+/// A link handed out by the host
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IssuedLink {
+    /// Random ID carried in the link as `?link=<ID>`
+    pub id: String,
+    /// Note for the host, e.g. who the link was given to
+    pub label: Option<String>,
+    /// Unix timestamp (seconds) of creation
+    pub created_at: u64,
+    /// Unix timestamp (seconds) after which the link is invalid
+    pub expires_at: Option<u64>,
+    /// Unix timestamp (seconds) of revocation
+    pub revoked_at: Option<u64>,
+    /// Time-boxed guest access: holders lose their tunnels and approvals at expiry
+    #[serde(default)]
+    pub guest: bool,
+}
+
+impl IssuedLink {
+    /// Whether the link can be used at `now`
+    pub fn status(&self, now: u64) -> LinkStatus {
+        if self.revoked_at.is_some() {
+            LinkStatus::Revoked
+        } else if self.expires_at.is_some_and(|at| now >= at) {
+            LinkStatus::Expired
+        } else {
+            LinkStatus::Valid
+        }
+    }
+}
+
+/// State of a link ID presented by a peer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkStatus {
+    /// Known, unexpired and not revoked
+    Valid,
+    /// Past its expiry
+    Expired,
+    /// Revoked by the host
+    Revoked,
+    /// Never issued by this host
+    Unknown,
+}
+
+impl fmt::Display for LinkStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LinkStatus::Valid => write!(f, "valid"),
+            LinkStatus::Expired => write!(f, "expired"),
+            LinkStatus::Revoked => write!(f, "revoked"),
+            LinkStatus::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+/// Persistent store of issued links
+#[derive(Debug, Default)]
+pub struct LinkStore {
+    /// Backing file (None = in-memory only)
+    path: Option<PathBuf>,
+    /// Modification time of the file when last read or written
+    modified: Option<SystemTime>,
+    /// Links keyed by ID
+    links: HashMap<String, IssuedLink>,
+}
+
+impl LinkStore {
+    /// Create an in-memory store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the store from a file, starting empty if it is missing or unreadable
+    pub fn load(path: PathBuf) -> Self {
+        let mut store = Self {
+            path: Some(path),
+            modified: None,
+            links: HashMap::new(),
+        };
+        store.read();
+        store
+    }
+
+    /// Re-read the file if another process (e.g. `rift links revoke`) changed it
+    ///
+    /// Returns whether the store was reloaded.
+    pub fn reload_if_changed(&mut self) -> bool {
+        let Some(path) = &self.path else {
+            return false;
+        };
+        let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        if modified == self.modified {
+            return false;
+        }
+        self.read();
+        true
+    }
+
+    /// Create a link, valid for `ttl_secs` (0 = until revoked)
+    pub fn issue(&mut self, ttl_secs: u64, label: Option<String>) -> IssuedLink {
+        self.insert(ttl_secs, label, false)
+    }
+
+    /// Create a guest link, valid for `ttl_secs`
+    pub fn issue_guest(&mut self, ttl_secs: u64) -> IssuedLink {
+        self.insert(ttl_secs, Some("guest".to_string()), true)
+    }
+
+    fn insert(&mut self, ttl_secs: u64, label: Option<String>, guest: bool) -> IssuedLink {
+        let now = unix_now();
+        let link = IssuedLink {
+            id: generate_id(),
+            label,
+            created_at: now,
+            expires_at: (ttl_secs > 0).then(|| now + ttl_secs),
+            revoked_at: None,
+            guest,
+        };
+        self.links.insert(link.id.clone(), link.clone());
+        self.persist();
+        link
+    }
+
+    /// Look up an issued link, comparing IDs in constant time
+    pub fn get(&self, id: &str) -> Option<&IssuedLink> {
+        self.links
+            .values()
+            .find(|link| constant_time_eq(link.id.as_bytes(), id.as_bytes()))
+    }
+
+    /// Check a link ID presented by a peer
+    pub fn status(&self, id: &str, now: u64) -> LinkStatus {
+        self.get(id)
+            .map(|link| link.status(now))
+            .unwrap_or(LinkStatus::Unknown)
+    }
+
+    /// Revoke a link; returns false if the ID is unknown
+    ///
+    /// Revoking twice keeps the first revocation time.
+    pub fn revoke(&mut self, id: &str) -> bool {
+        let Some(link) = self.links.get_mut(id) else {
+            return false;
+        };
+        if link.revoked_at.is_none() {
+            link.revoked_at = Some(unix_now());
+            self.persist();
+        }
+        true
+    }
+
+    /// All links, oldest first
+    pub fn list(&self) -> Vec<&IssuedLink> {
+        let mut links: Vec<&IssuedLink> = self.links.values().collect();
+        links.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.id.cmp(&b.id)));
+        links
+    }
+
+    /// Check if the store holds no links
+    pub fn is_empty(&self) -> bool {
+        self.links.is_empty()
+    }
+
+    fn read(&mut self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        self.modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        self.links = match std::fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str::<Vec<IssuedLink>>(&contents)
+                .map(|list| list.into_iter().map(|l| (l.id.clone(), l)).collect())
+                .unwrap_or_else(|e| {
+                    warn!("Ignoring unreadable link store {}: {}", path.display(), e);
+                    HashMap::new()
+                }),
+            Err(_) => HashMap::new(),
+        };
+    }
+
+    fn persist(&mut self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+
+        let result = serde_json::to_string_pretty(&self.list())
+            .map_err(wh_core::RiftError::from)
+            .and_then(|json| {
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(path, json)?;
+                Ok(())
+            });
+
+        match result {
+            Ok(()) => self.modified = std::fs::metadata(path).and_then(|m| m.modified()).ok(),
+            Err(e) => warn!("Failed to persist links to {}: {}", path.display(), e),
+        }
+    }
+}
+
+/// Generate a random 128-bit link ID, hex encoded
+fn generate_id() -> String {
+    let bytes: [u8; 16] = rand::random();
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_issue_expire_and_revoke() {
+        let mut store = LinkStore::new();
+        let open = store.issue(0, Some("alice".to_string()));
+        let short = store.issue(60, None);
+        let now = unix_now();
+
+        assert_eq!(store.status(&open.id, now), LinkStatus::Valid);
+        assert_eq!(store.status(&short.id, now + 60), LinkStatus::Expired);
+        assert_eq!(store.status("not-a-link", now), LinkStatus::Unknown);
+
+        assert!(store.revoke(&open.id));
+        assert!(!store.revoke("not-a-link"));
+        assert_eq!(store.status(&open.id, now), LinkStatus::Revoked);
+    }
+
+    #[test]
+    fn test_guest_link_is_marked_and_expires() {
+        let mut store = LinkStore::new();
+        let guest = store.issue_guest(3600);
+        let now = unix_now();
+
+        assert!(store.get(&guest.id).is_some_and(|link| link.guest));
+        assert!(!store.issue(0, None).guest);
+        assert_eq!(store.status(&guest.id, now), LinkStatus::Valid);
+        assert_eq!(store.status(&guest.id, now + 3600), LinkStatus::Expired);
+    }
+
+    #[test]
+    fn test_revocation_seen_by_running_host() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(LINKS_FILE);
+
+        let mut host = LinkStore::load(path.clone());
+        let link = host.issue(0, None);
+        assert!(!host.reload_if_changed());
+
+        // `rift links revoke` runs in another process
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        assert!(LinkStore::load(path).revoke(&link.id));
+
+        assert!(host.reload_if_changed());
+        assert_eq!(host.status(&link.id, unix_now()), LinkStatus::Revoked);
+    }
+}
@@ -0,0 +1,294 @@
+//! Connection Middleware
+//!
+//! Hooks that plugins compiled into the binary run on bridged connections:
+//! when one opens, on every chunk of bytes relayed (which they may rewrite),
+//! and when it closes. Embedders register a [`Middleware`] with
+//! `DaemonServer::add_middleware`; the built-in ones (feature `plugins`) are
+//! turned on by name in the `middleware` config section.
+//!
+//! Hooks run in registration order on the bridge task, so they must not block.
+//! Chunks are whatever one read returned: a middleware looking for a pattern
+//! may see it split across two chunks. Striped, migrating and loopback bridges
+//! only get `on_connect` and `on_close`.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::sync::Arc;
+use wh_core::MiddlewareConfig;
+
+/// Which way a chunk travels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// From the client towards the shared service
+    Request,
+    /// From the shared service back to the client
+    Response,
+}
+
+/// The connection a hook runs for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionInfo {
+    /// ID in the connection table
+    pub id: u64,
+    /// The peer at the other end of the tunnel
+    pub peer_id: String,
+    /// "share" or "connect"
+    pub session: &'static str,
+    /// Shared port the connection goes to
+    pub port: u16,
+}
+
+/// Hooks run on bridged connections
+pub trait Middleware: Send + Sync {
+    /// Name shown in logs
+    fn name(&self) -> &str;
+
+    /// A connection was opened
+    fn on_connect(&self, _conn: &ConnectionInfo) {}
+
+    /// A chunk is about to be relayed; it may be changed, grown or emptied
+    fn on_data_chunk(&self, _conn: &ConnectionInfo, _direction: Direction, _chunk: &mut Vec<u8>) {}
+
+    /// A connection closed after relaying these bytes (sent towards the peer, received from it)
+    fn on_close(&self, _conn: &ConnectionInfo, _bytes_sent: u64, _bytes_received: u64) {}
+}
+
+/// Middlewares in the order they run
+#[derive(Clone, Default)]
+pub struct MiddlewareChain {
+    middlewares: Vec<Arc<dyn Middleware>>,
+}
+
+impl fmt::Debug for MiddlewareChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.middlewares.iter().map(|m| m.name())).finish()
+    }
+}
+
+impl MiddlewareChain {
+    /// Run `middleware` after the ones already added
+    pub fn push(&mut self, middleware: Arc<dyn Middleware>) {
+        self.middlewares.push(middleware);
+    }
+
+    /// Whether no middleware was added
+    pub fn is_empty(&self) -> bool {
+        self.middlewares.is_empty()
+    }
+
+    /// Tell every middleware a connection opened
+    pub fn on_connect(&self, conn: &ConnectionInfo) {
+        for middleware in &self.middlewares {
+            middleware.on_connect(conn);
+        }
+    }
+
+    /// The chunk after every middleware had its turn; borrowed when there are none
+    pub fn apply<'a>(&self, conn: &ConnectionInfo, direction: Direction, chunk: &'a [u8]) -> Cow<'a, [u8]> {
+        if self.middlewares.is_empty() {
+            return Cow::Borrowed(chunk);
+        }
+        let mut chunk = chunk.to_vec();
+        for middleware in &self.middlewares {
+            middleware.on_data_chunk(conn, direction, &mut chunk);
+        }
+        Cow::Owned(chunk)
+    }
+
+    /// Tell every middleware a connection closed
+    pub fn on_close(&self, conn: &ConnectionInfo, bytes_sent: u64, bytes_received: u64) {
+        for middleware in &self.middlewares {
+            middleware.on_close(conn, bytes_sent, bytes_received);
+        }
+    }
+
+    /// The built-in middlewares `config` turns on, and the names it could not turn on
+    ///
+    /// Those are unknown, or left out of this build (feature `plugins`).
+    pub fn from_config(config: &MiddlewareConfig) -> (Self, Vec<String>) {
+        let mut chain = Self::default();
+        let mut missing = Vec::new();
+        for name in &config.enabled {
+            match builtin(name, config) {
+                Some(middleware) => chain.push(middleware),
+                None => missing.push(name.clone()),
+            }
+        }
+        (chain, missing)
+    }
+}
+
+#[cfg(feature = "plugins")]
+fn builtin(name: &str, config: &MiddlewareConfig) -> Option<Arc<dyn Middleware>> {
+    match name {
+        "inject-headers" => Some(Arc::new(plugins::InjectHeaders::new(&config.inject_headers))),
+        "demo-filter" => Some(Arc::new(plugins::DemoFilter::new(&config.demo_filter_words))),
+        _ => None,
+    }
+}
+
+#[cfg(not(feature = "plugins"))]
+fn builtin(_name: &str, _config: &MiddlewareConfig) -> Option<Arc<dyn Middleware>> {
+    None
+}
+
+/// Built-in middlewares
+#[cfg(feature = "plugins")]
+pub mod plugins {
+    use super::{ConnectionInfo, Direction, Middleware};
+    use std::collections::BTreeMap;
+
+    /// HTTP methods a request chunk may start with
+    const METHODS: &[&str] = &["GET ", "POST ", "PUT ", "PATCH ", "DELETE ", "HEAD ", "OPTIONS "];
+
+    /// Adds headers to HTTP requests, after the request line
+    ///
+    /// Only chunks starting with a request line are touched, so requests on a
+    /// kept-alive connection get them too, but a request line split across two
+    /// reads does not.
+    #[derive(Debug, Clone)]
+    pub struct InjectHeaders {
+        headers: Vec<u8>,
+    }
+
+    impl InjectHeaders {
+        pub fn new(headers: &BTreeMap<String, String>) -> Self {
+            let headers = headers
+                .iter()
+                .flat_map(|(name, value)| format!("{}: {}\r\n", name, value).into_bytes())
+                .collect();
+            Self { headers }
+        }
+    }
+
+    impl Middleware for InjectHeaders {
+        fn name(&self) -> &str {
+            "inject-headers"
+        }
+
+        fn on_data_chunk(&self, _conn: &ConnectionInfo, direction: Direction, chunk: &mut Vec<u8>) {
+            if direction != Direction::Request || !METHODS.iter().any(|method| chunk.starts_with(method.as_bytes())) {
+                return;
+            }
+            if let Some(end) = chunk.windows(2).position(|pair| pair == b"\r\n") {
+                chunk.splice(end + 2..end + 2, self.headers.iter().copied());
+            }
+        }
+    }
+
+    /// Masks words in responses with `*`, for demos in front of an audience
+    ///
+    /// Matches ASCII words case-insensitively; the length of the response is kept.
+    #[derive(Debug, Clone)]
+    pub struct DemoFilter {
+        words: Vec<Vec<u8>>,
+    }
+
+    impl DemoFilter {
+        pub fn new(words: &[String]) -> Self {
+            let words = words
+                .iter()
+                .filter(|word| !word.is_empty())
+                .map(|word| word.to_ascii_lowercase().into_bytes())
+                .collect();
+            Self { words }
+        }
+    }
+
+    impl Middleware for DemoFilter {
+        fn name(&self) -> &str {
+            "demo-filter"
+        }
+
+        fn on_data_chunk(&self, _conn: &ConnectionInfo, direction: Direction, chunk: &mut Vec<u8>) {
+            if direction != Direction::Response {
+                return;
+            }
+            for word in &self.words {
+                let mut start = 0;
+                while start + word.len() <= chunk.len() {
+                    if chunk[start..start + word.len()].eq_ignore_ascii_case(word) {
+                        chunk[start..start + word.len()].fill(b'*');
+                        start += word.len();
+                    } else {
+                        start += 1;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    #[derive(Default)]
+    struct Counting {
+        closed: AtomicU64,
+    }
+
+    impl Middleware for Counting {
+        fn name(&self) -> &str {
+            "counting"
+        }
+
+        fn on_data_chunk(&self, _conn: &ConnectionInfo, _direction: Direction, chunk: &mut Vec<u8>) {
+            chunk.make_ascii_uppercase();
+        }
+
+        fn on_close(&self, _conn: &ConnectionInfo, bytes_sent: u64, bytes_received: u64) {
+            self.closed.fetch_add(bytes_sent + bytes_received, Ordering::Relaxed);
+        }
+    }
+
+    fn conn() -> ConnectionInfo {
+        ConnectionInfo {
+            id: 1,
+            peer_id: "peer-a".to_string(),
+            session: "share",
+            port: 3000,
+        }
+    }
+
+    #[test]
+    fn test_chain_runs_hooks_in_order() {
+        let mut chain = MiddlewareChain::default();
+        assert!(matches!(chain.apply(&conn(), Direction::Request, b"get"), Cow::Borrowed(b"get")));
+
+        let counting = Arc::new(Counting::default());
+        chain.push(counting.clone());
+        assert_eq!(chain.apply(&conn(), Direction::Request, b"get").as_ref(), b"GET");
+        chain.on_close(&conn(), 3, 4);
+        assert_eq!(counting.closed.load(Ordering::Relaxed), 7);
+
+        let config = MiddlewareConfig {
+            enabled: vec!["no-such-plugin".to_string()],
+            ..Default::default()
+        };
+        let (chain, missing) = MiddlewareChain::from_config(&config);
+        assert!(chain.is_empty());
+        assert_eq!(missing, vec!["no-such-plugin".to_string()]);
+    }
+
+    #[cfg(feature = "plugins")]
+    #[test]
+    fn test_builtin_plugins_rewrite_http() {
+        let config = MiddlewareConfig {
+            enabled: vec!["inject-headers".to_string(), "demo-filter".to_string()],
+            inject_headers: [("X-Demo".to_string(), "1".to_string())].into(),
+            demo_filter_words: vec!["darn".to_string()],
+        };
+        let (chain, missing) = MiddlewareChain::from_config(&config);
+        assert!(missing.is_empty());
+
+        let request = chain.apply(&conn(), Direction::Request, b"GET / HTTP/1.1\r\nHost: x\r\n\r\n");
+        assert_eq!(request.as_ref(), b"GET / HTTP/1.1\r\nX-Demo: 1\r\nHost: x\r\n\r\n");
+        let body = chain.apply(&conn(), Direction::Request, b"not a request line\r\n");
+        assert_eq!(body.as_ref(), b"not a request line\r\n");
+
+        let response = chain.apply(&conn(), Direction::Response, b"Darn it, darn.");
+        assert_eq!(response.as_ref(), b"**** it, ****.");
+    }
+}
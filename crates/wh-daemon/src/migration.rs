@@ -0,0 +1,349 @@
+//! Connection Migration
+//!
+//! Best-effort resumption of the connect-side bridge: when the stream to the
+//! host drops while a local client waits for its response, a new stream is
+//! opened and the request bytes sent so far are written to it again. This is
+//! only safe for idempotent HTTP/1.x requests and for idle connections, and
+//! only until the first response byte was passed on to the client.
+
+use std::future::Future;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tracing::debug;
+use wh_core::MigrationConfig;
+
+use crate::http_log::{Body, MessageHead, MessageParser, MessageSink};
+
+/// Pause before the first attempt at reopening, doubled after each failure
+const REOPEN_BACKOFF: Duration = Duration::from_millis(250);
+
+/// HTTP methods whose requests may be sent twice
+const IDEMPOTENT_METHODS: &[&str] = &["GET", "HEAD", "OPTIONS", "PUT", "DELETE", "TRACE"];
+
+/// Follows the request in flight on one connection
+#[derive(Debug)]
+pub struct RequestTracker {
+    /// Request bytes sent since the last response started
+    pending: Vec<u8>,
+    /// False once `pending` outgrew the limit and was dropped
+    replayable: bool,
+    /// Splits `pending` into requests, as clients may pipeline several
+    parser: MessageParser,
+    /// Whether every request head in `pending` has an idempotent method
+    idempotent: bool,
+    /// Whether response bytes reached the client since the request began
+    responded: bool,
+    max_bytes: usize,
+}
+
+impl RequestTracker {
+    /// Track requests of up to `max_bytes`
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            pending: Vec::new(),
+            replayable: true,
+            parser: MessageParser::new(None),
+            idempotent: true,
+            responded: false,
+            max_bytes,
+        }
+    }
+
+    /// Record bytes sent by the client; after a response they start a new request
+    pub fn on_request(&mut self, data: &[u8]) {
+        if self.responded {
+            self.pending.clear();
+            self.replayable = true;
+            self.parser = MessageParser::new(None);
+            self.idempotent = true;
+            self.responded = false;
+        }
+        if !self.replayable {
+            return;
+        }
+        if self.pending.len() + data.len() > self.max_bytes {
+            self.pending = Vec::new();
+            self.replayable = false;
+        } else {
+            self.pending.extend_from_slice(data);
+            self.parser.feed(data, &mut IdempotencySink { idempotent: &mut self.idempotent });
+        }
+    }
+
+    /// Record response bytes passed on to the client
+    pub fn on_response(&mut self) {
+        self.responded = true;
+    }
+
+    /// Whether the connection can move to a new stream without the client noticing
+    ///
+    /// Every request sent so far must be idempotent, not just the first of a pipeline.
+    pub fn can_resume(&self) -> bool {
+        let idempotent = is_idempotent(&self.pending) && self.idempotent && !self.parser.stopped();
+        !self.responded && self.replayable && (self.pending.is_empty() || idempotent)
+    }
+
+    /// Bytes to send again on the new stream
+    pub fn replay(&self) -> &[u8] {
+        &self.pending
+    }
+}
+
+/// Whether a request starts with an idempotent HTTP method
+///
+/// Also covers a first request whose head has not arrived in full yet.
+fn is_idempotent(request: &[u8]) -> bool {
+    let Some(end) = request.iter().position(|&b| b == b' ') else {
+        return false;
+    };
+    std::str::from_utf8(&request[..end]).is_ok_and(|method| IDEMPOTENT_METHODS.contains(&method))
+}
+
+/// Clears `idempotent` on a request that may not be sent twice
+struct IdempotencySink<'a> {
+    idempotent: &'a mut bool,
+}
+
+impl MessageSink for IdempotencySink<'_> {
+    fn head(&mut self, head: MessageHead) -> Option<Body> {
+        let method = head.start_line.split_whitespace().next().unwrap_or_default();
+        if !IDEMPOTENT_METHODS.contains(&method) {
+            *self.idempotent = false;
+        }
+        // Requests without framing headers carry no body
+        Some(head.declared_body().unwrap_or(Body::Length(0)))
+    }
+}
+
+/// Bridge a local connection to a stream, moving to a new stream if it drops
+///
+/// `reopen` opens a replacement stream (None if that failed). `on_sent` and
+/// `on_received` see every chunk forwarded; `on_migrated` is told how many
+/// bytes were replayed after each successful move.
+pub async fn bridge_migrating<S, R, Fut>(
+    tcp_stream: TcpStream,
+    stream: S,
+    mut reopen: R,
+    config: &MigrationConfig,
+    mut on_sent: impl FnMut(&[u8]),
+    mut on_received: impl FnMut(&[u8]),
+    mut on_migrated: impl FnMut(usize),
+) where
+    S: AsyncRead + AsyncWrite + Unpin,
+    R: FnMut() -> Fut,
+    Fut: Future<Output = Option<S>>,
+{
+    let (mut tcp_read, mut tcp_write) = tcp_stream.into_split();
+    let mut stream = stream;
+    let mut tracker = RequestTracker::new(config.max_replay_bytes);
+    let mut up = [0u8; 8192];
+    let mut down = [0u8; 8192];
+
+    loop {
+        // An error on the stream side in either direction means it dropped
+        let dropped = tokio::select! {
+            read = tcp_read.read(&mut up) => match read {
+                Ok(0) | Err(_) => return,
+                Ok(n) => {
+                    tracker.on_request(&up[..n]);
+                    on_sent(&up[..n]);
+                    stream.write_all(&up[..n]).await.is_err()
+                }
+            },
+            read = stream.read(&mut down) => match read {
+                Ok(0) => return,
+                Ok(n) => {
+                    tracker.on_response();
+                    on_received(&down[..n]);
+                    if tcp_write.write_all(&down[..n]).await.is_err() {
+                        return;
+                    }
+                    false
+                }
+                Err(e) => {
+                    debug!("Tunnel stream dropped: {}", e);
+                    true
+                }
+            },
+        };
+        if !dropped {
+            continue;
+        }
+        if !tracker.can_resume() {
+            debug!("Dropped stream cannot be resumed transparently");
+            return;
+        }
+
+        let mut replacement = None;
+        let mut backoff = REOPEN_BACKOFF;
+        for _ in 0..config.max_attempts {
+            tokio::time::sleep(backoff).await;
+            replacement = reopen().await;
+            if replacement.is_some() {
+                break;
+            }
+            backoff *= 2;
+        }
+        let Some(mut new_stream) = replacement else {
+            debug!("Could not reopen the dropped stream");
+            return;
+        };
+        if new_stream.write_all(tracker.replay()).await.is_err() {
+            return;
+        }
+        on_migrated(tracker.replay().len());
+        stream = new_stream;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+    use tokio::io::{DuplexStream, ReadBuf};
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_tracker_resumes_idempotent_requests_only() {
+        let mut tracker = RequestTracker::new(1024);
+        assert!(tracker.can_resume());
+
+        tracker.on_request(b"GET /items HTTP/1.1\r\n");
+        assert!(tracker.can_resume());
+        assert_eq!(tracker.replay(), b"GET /items HTTP/1.1\r\n");
+
+        tracker.on_response();
+        assert!(!tracker.can_resume());
+
+        tracker.on_request(b"POST /items HTTP/1.1\r\n");
+        assert!(!tracker.can_resume());
+    }
+
+    #[test]
+    fn test_tracker_checks_every_pipelined_request() {
+        let mut tracker = RequestTracker::new(1024);
+        tracker.on_request(b"GET /items HTTP/1.1\r\nHost: a\r\n\r\n");
+        assert!(tracker.can_resume());
+
+        tracker.on_request(b"POST /items HTTP/1.1\r\nHost: a\r\nContent-Length: 2\r\n\r\nhi");
+        assert!(!tracker.can_resume());
+
+        // A new request after the response starts over
+        tracker.on_response();
+        tracker.on_request(b"GET /items HTTP/1.1\r\nHost: a\r\n\r\nHEAD / HTTP/1.1\r\n\r\n");
+        assert!(tracker.can_resume());
+
+        let mut small = RequestTracker::new(8);
+        small.on_request(b"GET /a-long-path HTTP/1.1\r\n");
+        assert!(!small.can_resume());
+    }
+
+    /// A stream whose connection is lost as soon as a request was written to it
+    #[derive(Default)]
+    struct Dropped {
+        written: bool,
+        reader: Option<std::task::Waker>,
+    }
+
+    impl AsyncRead for Dropped {
+        fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, _: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+            let this = self.get_mut();
+            if this.written {
+                Poll::Ready(Err(std::io::ErrorKind::ConnectionReset.into()))
+            } else {
+                this.reader = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+
+    impl AsyncWrite for Dropped {
+        fn poll_write(self: Pin<&mut Self>, _: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+            let this = self.get_mut();
+            this.written = true;
+            if let Some(waker) = this.reader.take() {
+                waker.wake();
+            }
+            Poll::Ready(Ok(buf.len()))
+        }
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+        fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    enum TestStream {
+        Dropped(Dropped),
+        Live(DuplexStream),
+    }
+
+    impl AsyncRead for TestStream {
+        fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+            match self.get_mut() {
+                TestStream::Dropped(s) => Pin::new(s).poll_read(cx, buf),
+                TestStream::Live(s) => Pin::new(s).poll_read(cx, buf),
+            }
+        }
+    }
+
+    impl AsyncWrite for TestStream {
+        fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+            match self.get_mut() {
+                TestStream::Dropped(s) => Pin::new(s).poll_write(cx, buf),
+                TestStream::Live(s) => Pin::new(s).poll_write(cx, buf),
+            }
+        }
+        fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            match self.get_mut() {
+                TestStream::Dropped(s) => Pin::new(s).poll_flush(cx),
+                TestStream::Live(s) => Pin::new(s).poll_flush(cx),
+            }
+        }
+        fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            match self.get_mut() {
+                TestStream::Dropped(s) => Pin::new(s).poll_shutdown(cx),
+                TestStream::Live(s) => Pin::new(s).poll_shutdown(cx),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dropped_stream_replays_request() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (local, _) = listener.accept().await.unwrap();
+
+        let (bridge_end, mut host_end) = tokio::io::duplex(1024);
+        let mut replacement = Some(TestStream::Live(bridge_end));
+        let migrated = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = migrated.clone();
+        let bridge = tokio::spawn(async move {
+            let reopen = move || std::future::ready(replacement.take());
+            let on_migrated = move |n| {
+                counter.store(n, std::sync::atomic::Ordering::Relaxed);
+            };
+            bridge_migrating(local, TestStream::Dropped(Dropped::default()), reopen, &MigrationConfig::default(), |_| {}, |_| {}, on_migrated).await;
+        });
+
+        let request = b"GET / HTTP/1.1\r\nHost: x\r\n\r\n";
+        client.write_all(request).await.unwrap();
+
+        // The host side of the new stream sees the request once, then answers
+        let mut received = vec![0u8; request.len()];
+        host_end.read_exact(&mut received).await.unwrap();
+        assert_eq!(&received, request);
+        host_end.write_all(b"HTTP/1.1 200 OK\r\n\r\n").await.unwrap();
+
+        let mut response = [0u8; 19];
+        client.read_exact(&mut response).await.unwrap();
+        assert_eq!(&response, b"HTTP/1.1 200 OK\r\n\r\n");
+        assert_eq!(migrated.load(std::sync::atomic::Ordering::Relaxed), request.len());
+
+        drop(host_end);
+        bridge.await.unwrap();
+    }
+}
@@ -0,0 +1,226 @@
+//! Target Connection Pool
+//!
+//! Keeps a few pre-established TCP connections to the shared local service so
+//! a burst of tunnel streams (e.g. a browser opening many sockets) does not pay
+//! for a fresh localhost connect each time. Refills run in the daemon's
+//! [`TaskSet`], so they stop on shutdown like every other task.
+
+use futures::FutureExt;
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tracing::debug;
+use wh_core::TargetRetryConfig;
+
+use crate::tasks::TaskSet;
+
+/// Host shared ports are served from unless the share names a target (`rift share --target`)
+pub const LOCALHOST: &str = "127.0.0.1";
+
+/// Pool of idle connections to local target ports
+#[derive(Debug)]
+pub struct TargetPool {
+    /// Idle connections kept per port
+    max_idle: usize,
+    /// How long an idle connection may wait before it is discarded
+    idle_timeout: Duration,
+    /// Idle connections by port, oldest first
+    idle: Mutex<HashMap<u16, VecDeque<(TcpStream, Instant)>>>,
+    /// Where background refills are spawned
+    tasks: TaskSet,
+}
+
+impl TargetPool {
+    /// Create a pool keeping up to `max_idle` warm connections per port, refilled in `tasks`
+    pub fn new(max_idle: usize, idle_timeout: Duration, tasks: TaskSet) -> Arc<Self> {
+        Arc::new(Self {
+            max_idle,
+            idle_timeout,
+            idle: Mutex::new(HashMap::new()),
+            tasks,
+        })
+    }
+
+    /// Get a connection to `127.0.0.1:port`, reusing a warm one if available
+    ///
+    /// The pool is topped up in the background afterwards.
+    pub async fn checkout(self: &Arc<Self>, port: u16, retry: &TargetRetryConfig) -> io::Result<TcpStream> {
+        let warm = self.take_idle(port);
+
+        let pool = self.clone();
+        self.tasks.spawn(async move { pool.refill(port).await });
+
+        match warm {
+            Some(stream) => {
+                debug!("Reusing warm connection to localhost:{}", port);
+                Ok(stream)
+            }
+            None => connect_target(LOCALHOST, port, retry).await,
+        }
+    }
+
+    /// Number of idle connections currently held for a port
+    pub fn idle_count(&self, port: u16) -> usize {
+        self.idle
+            .lock()
+            .unwrap()
+            .get(&port)
+            .map(|conns| conns.len())
+            .unwrap_or(0)
+    }
+
+    fn take_idle(&self, port: u16) -> Option<TcpStream> {
+        let mut idle = self.idle.lock().unwrap();
+        let conns = idle.get_mut(&port)?;
+
+        while let Some((stream, parked_at)) = conns.pop_front() {
+            if parked_at.elapsed() < self.idle_timeout && is_alive(&stream) {
+                return Some(stream);
+            }
+        }
+        None
+    }
+
+    async fn refill(&self, port: u16) {
+        while self.idle_count(port) < self.max_idle {
+            match TcpStream::connect((LOCALHOST, port)).await {
+                Ok(stream) => {
+                    let mut idle = self.idle.lock().unwrap();
+                    let conns = idle.entry(port).or_default();
+                    if conns.len() >= self.max_idle {
+                        break;
+                    }
+                    conns.push_back((stream, Instant::now()));
+                }
+                Err(e) => {
+                    debug!("Failed to pre-warm connection to localhost:{}: {}", port, e);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Connect to `host:port`, retrying with backoff while the service is down
+pub async fn connect_target(host: &str, port: u16, retry: &TargetRetryConfig) -> io::Result<TcpStream> {
+    let mut attempt = 1;
+    loop {
+        match TcpStream::connect((host, port)).await {
+            Ok(stream) => return Ok(stream),
+            Err(e) if attempt < retry.attempts => {
+                let delay = retry.backoff(attempt);
+                debug!(
+                    "Connect to {}:{} failed ({}), retrying in {:?}",
+                    host, port, e, delay
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Check without blocking or consuming data that the service has not closed the socket
+fn is_alive(stream: &TcpStream) -> bool {
+    let mut buf = [0u8; 1];
+    match stream.peek(&mut buf).now_or_never() {
+        // Nothing to read yet - still open
+        None => true,
+        // Server-first protocols may have sent a greeting
+        Some(Ok(n)) => n > 0,
+        Some(Err(_)) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_checkout_prewarms_connections() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                held.push(stream);
+            }
+        });
+
+        let pool = TargetPool::new(2, Duration::from_secs(30), TaskSet::new());
+        let retry = TargetRetryConfig::default();
+        let _first = pool.checkout(port, &retry).await.unwrap();
+
+        for _ in 0..50 {
+            if pool.idle_count(port) == 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(pool.idle_count(port), 2);
+
+        let _second = pool.checkout(port, &retry).await.unwrap();
+        assert!(pool.idle_count(port) <= 2);
+    }
+
+    #[tokio::test]
+    async fn test_no_refill_after_shutdown() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                held.push(stream);
+            }
+        });
+
+        let tasks = TaskSet::new();
+        let pool = TargetPool::new(2, Duration::from_secs(30), tasks.clone());
+        tasks.shutdown().await;
+        let _conn = pool.checkout(port, &TargetRetryConfig::default()).await.unwrap();
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(pool.idle_count(port), 0);
+        assert_eq!(tasks.live(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_checkout_without_listener_fails() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+
+        let retry = TargetRetryConfig {
+            attempts: 1,
+            ..Default::default()
+        };
+        let pool = TargetPool::new(2, Duration::from_secs(30), TaskSet::new());
+        assert!(pool.checkout(port, &retry).await.is_err());
+        assert_eq!(pool.idle_count(port), 0);
+    }
+
+    #[tokio::test]
+    async fn test_connect_target_waits_for_restart() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+
+        // Service comes back shortly after the first attempt fails
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(150)).await;
+            let listener = TcpListener::bind(("127.0.0.1", port)).await.unwrap();
+            let _ = listener.accept().await;
+        });
+
+        let retry = TargetRetryConfig {
+            attempts: 10,
+            initial_backoff_ms: 50,
+            max_backoff_ms: 100,
+        };
+        assert!(connect_target(LOCALHOST, port, &retry).await.is_ok());
+    }
+}
@@ -0,0 +1,119 @@
+//! Share Preflight
+//!
+//! Checks `rift share` runs before it starts: that something accepts
+//! connections on each shared local port, and that no other rift daemon on
+//! this machine (one writing status snapshots to the same state store) already
+//! shares it. What a failed check does is set by [`PreflightConfig`].
+
+use std::fmt;
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+use wh_core::{PreflightAction, PreflightConfig, StateStore};
+
+use crate::status::StatusSnapshot;
+
+/// How long to wait for a shared port to accept a probe connection
+pub const PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A failed preflight check
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PreflightIssue {
+    /// Nothing accepts connections on the port
+    NotListening { port: u16 },
+    /// Another daemon (by process ID) shares the port already
+    SharedElsewhere { port: u16, pid: u32 },
+}
+
+impl PreflightIssue {
+    /// Config setting of the check that found it
+    pub fn setting(&self) -> &'static str {
+        match self {
+            PreflightIssue::NotListening { .. } => "preflight.listener",
+            PreflightIssue::SharedElsewhere { .. } => "preflight.conflict",
+        }
+    }
+}
+
+impl fmt::Display for PreflightIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PreflightIssue::NotListening { port } => {
+                write!(f, "nothing is listening on localhost:{}; start the service first", port)
+            }
+            PreflightIssue::SharedElsewhere { port, pid } => {
+                write!(f, "port {} is already shared by another rift daemon (pid {})", port, pid)
+            }
+        }
+    }
+}
+
+/// Run the checks `config` turns on for sharing local `ports`
+///
+/// Returns each failed check with what it should do (never `Off`). Daemons
+/// are found through the status snapshots in `store`, if given.
+pub fn check_share(ports: &[u16], config: &PreflightConfig, store: Option<&dyn StateStore>) -> Vec<(PreflightAction, PreflightIssue)> {
+    let mut issues = Vec::new();
+    if config.conflict != PreflightAction::Off
+        && let Some(store) = store
+    {
+        let own_pid = std::process::id();
+        for snapshot in StatusSnapshot::read_all(store) {
+            if snapshot.pid == own_pid || snapshot.mode != "share" {
+                continue;
+            }
+            for port in ports.iter().filter(|port| snapshot.shared_ports.contains(port)) {
+                issues.push((config.conflict, PreflightIssue::SharedElsewhere { port: *port, pid: snapshot.pid }));
+            }
+        }
+    }
+    if config.listener != PreflightAction::Off {
+        for port in ports {
+            let addr = SocketAddr::from(([127, 0, 0, 1], *port));
+            if TcpStream::connect_timeout(&addr, PROBE_TIMEOUT).is_err() {
+                issues.push((config.listener, PreflightIssue::NotListening { port: *port }));
+            }
+        }
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_check_share_finds_missing_listener_and_other_daemon() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = wh_core::FileStore::new(dir.path().to_path_buf());
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let listening = listener.local_addr().unwrap().port();
+        let free = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+
+        let other = StatusSnapshot {
+            pid: std::process::id() + 1,
+            mode: "share".to_string(),
+            port: Some(listening),
+            shared_ports: vec![listening],
+            ..Default::default()
+        };
+        other.touch().write(&store).unwrap();
+
+        let config = PreflightConfig::default();
+        let issues = check_share(&[listening, free], &config, Some(&store));
+        assert_eq!(
+            issues,
+            vec![
+                (PreflightAction::Fail, PreflightIssue::SharedElsewhere { port: listening, pid: std::process::id() + 1 }),
+                (PreflightAction::Warn, PreflightIssue::NotListening { port: free }),
+            ]
+        );
+
+        let off = PreflightConfig {
+            listener: PreflightAction::Off,
+            conflict: PreflightAction::Off,
+        };
+        assert!(check_share(&[listening, free], &off, Some(&store)).is_empty());
+        assert!(check_share(&[listening], &config, None).is_empty());
+    }
+}
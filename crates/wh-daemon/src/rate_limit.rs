@@ -0,0 +1,202 @@
+//! Bandwidth Limiting
+//!
+//! Token buckets throttling the share-side bridges, so a host can cap what a
+//! connection or a peer pulls through the tunnel (`rift share --max-rate`).
+
+use libp2p::PeerId;
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{ready, Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::Sleep;
+use wh_core::{RateLimitConfig, RateLimitScope};
+
+/// Token bucket refilled at a fixed byte rate
+///
+/// Holds up to one second's worth of bytes. Reservations may overdraw the
+/// bucket; the next read or write then waits until the debt is paid back, so
+/// several connections sharing one limiter together stay under the rate.
+#[derive(Debug)]
+pub struct RateLimiter {
+    bytes_per_sec: u64,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl RateLimiter {
+    /// Create a limiter starting with a full bucket
+    pub fn new(bytes_per_sec: u64) -> Self {
+        Self {
+            bytes_per_sec: bytes_per_sec.max(1),
+            bucket: Mutex::new(Bucket {
+                tokens: bytes_per_sec.max(1) as f64,
+                refilled_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Take `bytes` from the bucket, returning how long to wait before passing more
+    fn reserve(&self, bytes: u64, now: Instant) -> Duration {
+        let rate = self.bytes_per_sec as f64;
+        let mut bucket = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+        let elapsed = now.saturating_duration_since(bucket.refilled_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(rate);
+        bucket.refilled_at = now;
+        bucket.tokens -= bytes as f64;
+        if bucket.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-bucket.tokens / rate)
+        }
+    }
+}
+
+/// Reader or writer charging every transferred byte to a limiter
+///
+/// Without a limiter it passes everything straight through.
+pub struct Throttled<T> {
+    inner: T,
+    limiter: Option<Arc<RateLimiter>>,
+    delay: Option<Pin<Box<Sleep>>>,
+}
+
+impl<T> Throttled<T> {
+    /// Throttle `inner` by `limiter`
+    pub fn new(inner: T, limiter: Option<Arc<RateLimiter>>) -> Self {
+        Self {
+            inner,
+            limiter,
+            delay: None,
+        }
+    }
+
+    /// Wait out the delay owed for earlier transfers
+    fn poll_delay(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        if let Some(delay) = &mut self.delay {
+            ready!(delay.as_mut().poll(cx));
+            self.delay = None;
+        }
+        Poll::Ready(())
+    }
+
+    fn charge(&mut self, bytes: usize) {
+        if let Some(limiter) = &self.limiter {
+            let wait = limiter.reserve(bytes as u64, Instant::now());
+            if !wait.is_zero() {
+                self.delay = Some(Box::pin(tokio::time::sleep(wait)));
+            }
+        }
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for Throttled<T> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        ready!(self.poll_delay(cx));
+        let before = buf.filled().len();
+        ready!(Pin::new(&mut self.inner).poll_read(cx, buf))?;
+        let read = buf.filled().len() - before;
+        self.charge(read);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for Throttled<T> {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        ready!(self.poll_delay(cx));
+        let written = ready!(Pin::new(&mut self.inner).poll_write(cx, buf))?;
+        self.charge(written);
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// Hands out the limiter for each new tunnelled connection
+#[derive(Debug)]
+pub struct RateLimiters {
+    config: RateLimitConfig,
+    per_peer: Mutex<HashMap<PeerId, Arc<RateLimiter>>>,
+}
+
+impl RateLimiters {
+    /// Limiters following the configured cap
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            per_peer: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Limiter for a new connection of `peer_id`, or None when unlimited
+    pub fn limiter_for(&self, peer_id: PeerId) -> Option<Arc<RateLimiter>> {
+        let rate = self.config.max_bytes_per_sec;
+        if rate == 0 {
+            return None;
+        }
+        match self.config.scope {
+            RateLimitScope::Connection => Some(Arc::new(RateLimiter::new(rate))),
+            RateLimitScope::Peer => {
+                let mut per_peer = self.per_peer.lock().unwrap_or_else(|e| e.into_inner());
+                Some(per_peer.entry(peer_id).or_insert_with(|| Arc::new(RateLimiter::new(rate))).clone())
+            }
+        }
+    }
+
+    /// Forget peers without open connections
+    pub fn prune(&self) {
+        let mut per_peer = self.per_peer.lock().unwrap_or_else(|e| e.into_inner());
+        per_peer.retain(|_, limiter| Arc::strong_count(limiter) > 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_allows_one_second_burst_then_throttles() {
+        let limiter = RateLimiter::new(1000);
+        let start = limiter.bucket.lock().unwrap().refilled_at;
+
+        assert_eq!(limiter.reserve(1000, start), Duration::ZERO);
+        assert_eq!(limiter.reserve(500, start), Duration::from_millis(500));
+        // Half a second later the debt is paid; the next 250 bytes wait 250ms
+        let later = start + Duration::from_millis(500);
+        assert_eq!(limiter.reserve(250, later), Duration::from_millis(250));
+    }
+
+    #[test]
+    fn test_peer_scope_shares_one_limiter() {
+        let peer = PeerId::random();
+        let config = RateLimitConfig {
+            max_bytes_per_sec: 1024,
+            scope: RateLimitScope::Peer,
+        };
+        let limiters = RateLimiters::new(config);
+        let first = limiters.limiter_for(peer).unwrap();
+        let second = limiters.limiter_for(peer).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(!Arc::ptr_eq(&first, &limiters.limiter_for(PeerId::random()).unwrap()));
+
+        drop((first, second));
+        limiters.prune();
+        assert!(limiters.per_peer.lock().unwrap().get(&peer).is_none());
+
+        assert!(RateLimiters::new(RateLimitConfig::default()).limiter_for(peer).is_none());
+    }
+}
@@ -0,0 +1,87 @@
+//! Auto-Reconnect
+//!
+//! Connect-side state while the dropped connection to the host is re-dialed.
+//! The run loop dials whenever an attempt is due and drops the state once the
+//! host is connected again.
+
+use std::time::{Duration, Instant};
+use wh_core::{AutoReconnectConfig, PeerId};
+
+/// Schedule of the attempts at reaching a host again
+#[derive(Debug)]
+pub struct Reconnect {
+    /// The host being re-dialed
+    pub peer_id: PeerId,
+    /// Attempts made so far
+    attempts: u32,
+    next_at: Instant,
+    since: Instant,
+}
+
+impl Reconnect {
+    /// Start after the connection dropped at `now`; the first attempt waits the initial backoff
+    pub fn new(peer_id: PeerId, config: &AutoReconnectConfig, now: Instant) -> Self {
+        Self {
+            peer_id,
+            attempts: 0,
+            next_at: now + config.backoff(1),
+            since: now,
+        }
+    }
+
+    /// When the next attempt is due
+    pub fn next_at(&self) -> Instant {
+        self.next_at
+    }
+
+    /// Take the due attempt and schedule the one after; None once all attempts are used
+    pub fn start_attempt(&mut self, config: &AutoReconnectConfig, now: Instant) -> Option<u32> {
+        if !config.may_retry(self.attempts) {
+            return None;
+        }
+        self.attempts += 1;
+        self.next_at = now + config.backoff(self.attempts + 1);
+        Some(self.attempts)
+    }
+
+    /// Make the next attempt right away (e.g. the network just came back)
+    pub fn retry_now(&mut self, now: Instant) {
+        self.next_at = now;
+    }
+
+    /// Attempts made so far
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// Time since the connection dropped
+    pub fn downtime(&self, now: Instant) -> Duration {
+        now.duration_since(self.since)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attempts_back_off_and_run_out() {
+        let config = AutoReconnectConfig {
+            attempts: 2,
+            ..AutoReconnectConfig::default()
+        };
+        let dropped = Instant::now();
+        let mut reconnect = Reconnect::new(PeerId::random(), &config, dropped);
+        assert_eq!(reconnect.next_at(), dropped + Duration::from_millis(500));
+
+        let now = reconnect.next_at();
+        assert_eq!(reconnect.start_attempt(&config, now), Some(1));
+        assert_eq!(reconnect.next_at(), now + Duration::from_millis(1_000));
+        reconnect.retry_now(now);
+        assert_eq!(reconnect.next_at(), now);
+        assert_eq!(reconnect.start_attempt(&config, reconnect.next_at()), Some(2));
+        assert_eq!(reconnect.start_attempt(&config, reconnect.next_at()), None);
+        assert_eq!(reconnect.attempts(), 2);
+        assert!(reconnect.downtime(now) >= Duration::from_millis(500));
+    }
+}
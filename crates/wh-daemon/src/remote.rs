@@ -0,0 +1,256 @@
+//! Remote Control
+//!
+//! The daemon's commands on a TCP port, for `rift --remote <HOST:PORT>` on
+//! another machine. A client sends one JSON line carrying the bearer token and
+//! a command, and gets one JSON line back. Off unless `remote_control.listen`
+//! is set. Nothing is encrypted, so the daemon warns when it listens beyond
+//! loopback; reach it through SSH, a VPN or a TLS proxy.
+
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, info, warn};
+use wh_core::crypto::constant_time_eq;
+use wh_core::state::write_private;
+use wh_core::{RemoteControlConfig, Result, RiftError};
+
+use crate::audit::AuditLog;
+use crate::server::DaemonCommand;
+use crate::status::StatusSnapshot;
+use crate::tokens::generate_token;
+
+/// File name of the generated token inside the state directory
+pub const CONTROL_TOKEN_FILE: &str = "control_token";
+
+/// Longest request line accepted
+const MAX_REQUEST_BYTES: u64 = 4096;
+
+/// Time a client gets to send its request and read the answer
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Pause before refusing a wrong token, to slow down guessing
+const BAD_TOKEN_DELAY: Duration = Duration::from_millis(500);
+
+/// What a remote client asks the daemon to do
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum RemoteCommand {
+    /// Report the daemon's status, as `rift status` shows it
+    Status,
+    /// Start the session byte counters from zero
+    ResetStats,
+    /// Revoke session resumption tokens of a peer (or all peers if None)
+    RevokeSessionTokens { peer_id: Option<String> },
+    /// Close one tunnelled connection, by the ID `rift status` lists
+    CloseConnection { connection_id: u64 },
+    /// Stop the daemon
+    Shutdown,
+}
+
+/// A request line: the bearer token and the command
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RemoteRequest {
+    pub token: String,
+    #[serde(flatten)]
+    pub command: RemoteCommand,
+}
+
+/// The daemon's answer to a request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum RemoteResponse {
+    /// Answer to [`RemoteCommand::Status`]
+    Status { status: Box<StatusSnapshot> },
+    /// The command was handed to the daemon
+    Ok,
+    /// The request was refused or failed
+    Error { message: String },
+}
+
+/// The token clients must present: from the config, or generated once into the state dir
+pub fn load_or_generate_token(config: &RemoteControlConfig, state_dir: &Path) -> Result<String> {
+    if let Some(token) = &config.token {
+        if token.trim().is_empty() {
+            return Err(RiftError::ConfigError("remote_control.token is empty".to_string()));
+        }
+        return Ok(token.trim().to_string());
+    }
+
+    let path = state_dir.join(CONTROL_TOKEN_FILE);
+    if let Ok(contents) = std::fs::read_to_string(&path)
+        && !contents.trim().is_empty()
+    {
+        return Ok(contents.trim().to_string());
+    }
+
+    let token = generate_token();
+    write_private(&path, format!("{}\n", token))?;
+    info!("Generated remote control token in {}", path.display());
+    Ok(token)
+}
+
+/// Answer remote control requests until the listener fails
+pub async fn serve(listener: TcpListener, token: String, commands: mpsc::Sender<DaemonCommand>, audit_log: AuditLog) {
+    loop {
+        let (socket, addr) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                warn!("Remote control accept failed: {}", e);
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                continue;
+            }
+        };
+        let token = token.clone();
+        let commands = commands.clone();
+        let audit_log = audit_log.clone();
+        tokio::spawn(async move {
+            match tokio::time::timeout(REQUEST_TIMEOUT, handle(socket, addr, &token, &commands, &audit_log)).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => debug!("Remote control request from {} failed: {}", addr, e),
+                Err(_) => debug!("Remote control request from {} timed out", addr),
+            }
+        });
+    }
+}
+
+async fn handle(
+    socket: TcpStream,
+    addr: SocketAddr,
+    token: &str,
+    commands: &mpsc::Sender<DaemonCommand>,
+    audit_log: &AuditLog,
+) -> Result<()> {
+    let (read, mut write) = socket.into_split();
+    let mut line = String::new();
+    BufReader::new(read.take(MAX_REQUEST_BYTES)).read_line(&mut line).await?;
+
+    let response = match serde_json::from_str::<RemoteRequest>(&line) {
+        Err(e) => RemoteResponse::Error { message: format!("invalid request: {}", e) },
+        Ok(request) if !token_matches(&request.token, token) => {
+            audit_log.record(&addr.to_string(), "remote_control_refused", "wrong token");
+            tokio::time::sleep(BAD_TOKEN_DELAY).await;
+            RemoteResponse::Error { message: "invalid token".to_string() }
+        }
+        Ok(request) => {
+            debug!("Remote control request from {}: {:?}", addr, request.command);
+            if request.command != RemoteCommand::Status {
+                audit_log.record(&addr.to_string(), "remote_control", &format!("{:?}", request.command));
+            }
+            execute(request.command, commands).await
+        }
+    };
+
+    let mut json = serde_json::to_string(&response)?;
+    json.push('\n');
+    write.write_all(json.as_bytes()).await?;
+    Ok(())
+}
+
+async fn execute(command: RemoteCommand, commands: &mpsc::Sender<DaemonCommand>) -> RemoteResponse {
+    let stopped = || RemoteResponse::Error { message: "daemon is shutting down".to_string() };
+    let command = match command {
+        RemoteCommand::Status => {
+            let (reply, status) = oneshot::channel();
+            if commands.send(DaemonCommand::Status { reply }).await.is_err() {
+                return stopped();
+            }
+            return match status.await {
+                Ok(status) => RemoteResponse::Status { status: Box::new(status) },
+                Err(_) => stopped(),
+            };
+        }
+        RemoteCommand::ResetStats => DaemonCommand::ResetStats,
+        RemoteCommand::RevokeSessionTokens { peer_id } => DaemonCommand::RevokeSessionTokens { peer_id },
+        RemoteCommand::CloseConnection { connection_id } => DaemonCommand::CloseConnection { connection_id },
+        RemoteCommand::Shutdown => DaemonCommand::Shutdown,
+    };
+    match commands.send(command).await {
+        Ok(()) => RemoteResponse::Ok,
+        Err(_) => stopped(),
+    }
+}
+
+/// Send one command to a remote daemon
+pub async fn request(addr: &str, token: &str, command: RemoteCommand) -> Result<RemoteResponse> {
+    let exchange = async {
+        let mut socket = TcpStream::connect(addr).await?;
+        let request = RemoteRequest { token: token.to_string(), command };
+        let mut json = serde_json::to_string(&request)?;
+        json.push('\n');
+        socket.write_all(json.as_bytes()).await?;
+
+        let mut line = String::new();
+        BufReader::new(socket).read_line(&mut line).await?;
+        serde_json::from_str::<RemoteResponse>(&line)
+            .map_err(|e| RiftError::InvalidMessage(format!("bad remote control response: {}", e)))
+    };
+    tokio::time::timeout(REQUEST_TIMEOUT + BAD_TOKEN_DELAY, exchange)
+        .await
+        .map_err(|_| RiftError::ConnectionFailed {
+            peer_id: addr.to_string(),
+            reason: "remote control request timed out".to_string(),
+        })?
+}
+
+/// Compare tokens in constant time
+fn token_matches(presented: &str, expected: &str) -> bool {
+    constant_time_eq(presented.as_bytes(), expected.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn start(token: &str, dir: &Path) -> (String, mpsc::Receiver<DaemonCommand>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let (command_tx, command_rx) = mpsc::channel(4);
+        let audit_log = AuditLog::new(std::sync::Arc::new(wh_core::FileStore::new(dir.to_path_buf())));
+        tokio::spawn(serve(listener, token.to_string(), command_tx, audit_log));
+        (addr, command_rx)
+    }
+
+    #[tokio::test]
+    async fn test_commands_reach_daemon_with_token_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let (addr, mut commands) = start("secret", dir.path()).await;
+
+        let refused = request(&addr, "guess", RemoteCommand::Shutdown).await.unwrap();
+        assert_eq!(refused, RemoteResponse::Error { message: "invalid token".to_string() });
+        assert!(commands.try_recv().is_err());
+
+        let status = tokio::spawn({
+            let addr = addr.clone();
+            async move { request(&addr, "secret", RemoteCommand::Status).await.unwrap() }
+        });
+        match commands.recv().await.unwrap() {
+            DaemonCommand::Status { reply } => {
+                let _ = reply.send(StatusSnapshot { pid: 42, ..Default::default() });
+            }
+            other => panic!("unexpected command {:?}", other),
+        }
+        match status.await.unwrap() {
+            RemoteResponse::Status { status } => assert_eq!(status.pid, 42),
+            other => panic!("unexpected response {:?}", other),
+        }
+
+        assert_eq!(request(&addr, "secret", RemoteCommand::Shutdown).await.unwrap(), RemoteResponse::Ok);
+        assert!(matches!(commands.recv().await, Some(DaemonCommand::Shutdown)));
+    }
+
+    #[test]
+    fn test_token_generated_once_unless_configured() {
+        let dir = tempfile::tempdir().unwrap();
+        let generated = load_or_generate_token(&RemoteControlConfig::default(), dir.path()).unwrap();
+        assert_eq!(generated.len(), 64);
+        assert_eq!(load_or_generate_token(&RemoteControlConfig::default(), dir.path()).unwrap(), generated);
+
+        let configured = RemoteControlConfig { listen: None, token: Some("from-config".to_string()) };
+        assert_eq!(load_or_generate_token(&configured, dir.path()).unwrap(), "from-config");
+        assert!(!token_matches("from-confiG", "from-config"));
+    }
+}
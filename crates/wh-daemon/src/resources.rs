@@ -0,0 +1,199 @@
+//! Resource Guards
+//!
+//! Optional limits on what the daemon may consume on its own machine: running
+//! tasks, bridge copy buffers and resident memory. Over a limit, new tunnelled
+//! connections are shed instead of letting an accidental load test through the
+//! tunnel take the laptop down with it.
+
+use std::fmt;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use wh_core::ResourceLimitsConfig;
+
+/// Copy buffers of a single-stream bridge (one per direction)
+pub const BRIDGE_BUFFER_BYTES: u64 = 2 * 8192;
+
+/// Minimum time between two warnings about the same guard
+const WARNING_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Time between two measurements of the daemon's memory
+const MEMORY_SAMPLE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Why a new connection was refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overload {
+    Tasks { live: usize, max: usize },
+    Buffers { buffered: u64, max: u64 },
+    Memory { rss: u64, max: u64 },
+}
+
+impl fmt::Display for Overload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Overload::Tasks { live, max } => write!(f, "{} tasks running (limit {})", live, max),
+            Overload::Buffers { buffered, max } => {
+                write!(f, "{} bytes buffered by bridges (limit {})", buffered, max)
+            }
+            Overload::Memory { rss, max } => write!(f, "daemon uses {} bytes of memory (limit {})", rss, max),
+        }
+    }
+}
+
+/// Buffer bytes held by one bridge, released when dropped
+#[derive(Debug)]
+pub struct BufferLease {
+    buffered: Arc<AtomicU64>,
+    bytes: u64,
+}
+
+impl Drop for BufferLease {
+    fn drop(&mut self) {
+        self.buffered.fetch_sub(self.bytes, Ordering::Relaxed);
+    }
+}
+
+/// Admission control for new tunnelled connections
+#[derive(Debug)]
+pub struct ResourceGuard {
+    limits: ResourceLimitsConfig,
+    buffered: Arc<AtomicU64>,
+    rss: Arc<AtomicU64>,
+    last_warning: Mutex<Option<Instant>>,
+}
+
+impl ResourceGuard {
+    /// Guard enforcing `limits` (0 = no limit)
+    pub fn new(limits: ResourceLimitsConfig) -> Self {
+        Self {
+            limits,
+            buffered: Arc::new(AtomicU64::new(0)),
+            rss: Arc::new(AtomicU64::new(0)),
+            last_warning: Mutex::new(None),
+        }
+    }
+
+    /// Admit a connection needing `buffer_bytes` of copy buffers
+    pub fn admit(&self, live_tasks: usize, buffer_bytes: u64) -> Result<BufferLease, Overload> {
+        let limits = &self.limits;
+        if limits.max_tasks > 0 && live_tasks >= limits.max_tasks {
+            return Err(Overload::Tasks {
+                live: live_tasks,
+                max: limits.max_tasks,
+            });
+        }
+        let rss = self.rss.load(Ordering::Relaxed);
+        if limits.max_rss_bytes > 0 && rss > limits.max_rss_bytes {
+            return Err(Overload::Memory {
+                rss,
+                max: limits.max_rss_bytes,
+            });
+        }
+        let buffered = self.buffered.fetch_add(buffer_bytes, Ordering::Relaxed) + buffer_bytes;
+        let lease = BufferLease {
+            buffered: self.buffered.clone(),
+            bytes: buffer_bytes,
+        };
+        if limits.max_buffered_bytes > 0 && buffered > limits.max_buffered_bytes {
+            return Err(Overload::Buffers {
+                buffered: buffered - buffer_bytes,
+                max: limits.max_buffered_bytes,
+            });
+        }
+        Ok(lease)
+    }
+
+    /// Task measuring the daemon's memory now and then, or None without a memory limit
+    ///
+    /// Measuring runs `ps` outside Linux, so it happens on a blocking thread
+    /// rather than in the caller's event loop.
+    pub fn memory_sampler(&self) -> Option<impl Future<Output = ()> + Send + 'static> {
+        if self.limits.max_rss_bytes == 0 {
+            return None;
+        }
+        let rss = self.rss.clone();
+        Some(async move {
+            let mut interval = tokio::time::interval(MEMORY_SAMPLE_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Ok(Some(sampled)) = tokio::task::spawn_blocking(resident_memory).await {
+                    rss.store(sampled, Ordering::Relaxed);
+                }
+            }
+        })
+    }
+
+    /// The overload if the last memory sample is above the limit
+    pub fn memory_overload(&self) -> Option<Overload> {
+        let rss = self.rss.load(Ordering::Relaxed);
+        let max = self.limits.max_rss_bytes;
+        (max > 0 && rss > max).then_some(Overload::Memory { rss, max })
+    }
+
+    /// Whether to surface a warning now (at most one every few seconds)
+    pub fn should_warn(&self) -> bool {
+        let mut last = self.last_warning.lock().unwrap_or_else(|e| e.into_inner());
+        if last.is_some_and(|at| at.elapsed() < WARNING_INTERVAL) {
+            return false;
+        }
+        *last = Some(Instant::now());
+        true
+    }
+}
+
+/// Resident memory of this process in bytes, where the platform reports it
+pub fn resident_memory() -> Option<u64> {
+    #[cfg(target_os = "linux")]
+    {
+        let status = std::fs::read_to_string("/proc/self/status").ok()?;
+        let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+        let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+        Some(kib * 1024)
+    }
+    #[cfg(all(unix, not(target_os = "linux")))]
+    {
+        let output = std::process::Command::new("ps")
+            .args(["-o", "rss=", "-p", &std::process::id().to_string()])
+            .output()
+            .ok()?;
+        let kib: u64 = String::from_utf8_lossy(&output.stdout).trim().parse().ok()?;
+        Some(kib * 1024)
+    }
+    #[cfg(not(unix))]
+    {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guard_sheds_over_limits_and_releases_buffers() {
+        let guard = ResourceGuard::new(ResourceLimitsConfig {
+            max_tasks: 10,
+            max_buffered_bytes: 2 * BRIDGE_BUFFER_BYTES,
+            max_rss_bytes: 0,
+        });
+        assert!(matches!(guard.admit(10, BRIDGE_BUFFER_BYTES), Err(Overload::Tasks { .. })));
+
+        let first = guard.admit(1, BRIDGE_BUFFER_BYTES).unwrap();
+        let _second = guard.admit(2, BRIDGE_BUFFER_BYTES).unwrap();
+        assert!(matches!(guard.admit(3, BRIDGE_BUFFER_BYTES), Err(Overload::Buffers { .. })));
+
+        drop(first);
+        assert!(guard.admit(2, BRIDGE_BUFFER_BYTES).is_ok());
+
+        assert!(guard.should_warn());
+        assert!(!guard.should_warn());
+    }
+
+    #[test]
+    fn test_resident_memory_is_reported() {
+        if cfg!(target_os = "linux") {
+            assert!(resident_memory().unwrap() > 0);
+        }
+    }
+}
@@ -0,0 +1,227 @@
+//! Secrets Scopes
+//!
+//! Which secrets each peer received, remembered across sessions. A peer that
+//! asks again gets the same keys by default; keys added to the secrets file
+//! since are only shared once the host grants them, in the approval prompt or
+//! with `rift peers secrets <PEER> --add`. Persisted in the state dir.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::path::PathBuf;
+use std::time::SystemTime;
+use tracing::warn;
+
+use crate::tokens::unix_now;
+
+/// File name of the scope store inside the state directory
+pub const SECRET_SCOPES_FILE: &str = "secret_scopes.json";
+
+/// The secrets a peer may receive
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SecretScope {
+    /// Peer the scope belongs to
+    pub peer_id: String,
+    /// Secret names (values are never stored here)
+    pub keys: BTreeSet<String>,
+    /// Unix timestamp (seconds) of the last change
+    pub updated_at: u64,
+}
+
+/// Split of the available secrets for one request
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScopedKeys {
+    /// Shared by default: the remembered scope, or everything for a first request
+    pub shared: Vec<String>,
+    /// Available but never granted to this peer
+    pub new: Vec<String>,
+}
+
+/// Persistent store of per-peer secrets scopes
+#[derive(Debug, Default)]
+pub struct SecretScopes {
+    /// Backing file (None = in-memory only)
+    path: Option<PathBuf>,
+    /// Modification time of the file when last read or written
+    modified: Option<SystemTime>,
+    /// Scopes keyed by peer ID
+    scopes: HashMap<String, SecretScope>,
+}
+
+impl SecretScopes {
+    /// Create an in-memory store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the store from a file, starting empty if it is missing or unreadable
+    pub fn load(path: PathBuf) -> Self {
+        let mut store = Self {
+            path: Some(path),
+            modified: None,
+            scopes: HashMap::new(),
+        };
+        store.read();
+        store
+    }
+
+    /// Re-read the file if another process (e.g. `rift peers secrets`) changed it
+    pub fn reload_if_changed(&mut self) -> bool {
+        let Some(path) = &self.path else {
+            return false;
+        };
+        let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        if modified == self.modified {
+            return false;
+        }
+        self.read();
+        true
+    }
+
+    /// Scope of a peer, if it ever received secrets
+    pub fn get(&self, peer_id: &str) -> Option<&SecretScope> {
+        self.scopes.get(peer_id)
+    }
+
+    /// Split `available` secret names into the default share and the new ones
+    pub fn resolve<'a>(&self, peer_id: &str, available: impl IntoIterator<Item = &'a String>) -> ScopedKeys {
+        let mut available: Vec<String> = available.into_iter().cloned().collect();
+        available.sort();
+        match self.scopes.get(peer_id) {
+            Some(scope) => {
+                let (shared, new) = available.into_iter().partition(|key| scope.keys.contains(key));
+                ScopedKeys { shared, new }
+            }
+            None => ScopedKeys { shared: available, new: Vec::new() },
+        }
+    }
+
+    /// Add keys to a peer's scope, creating it if needed
+    pub fn grant(&mut self, peer_id: &str, keys: impl IntoIterator<Item = String>) {
+        let scope = self.scopes.entry(peer_id.to_string()).or_insert_with(|| SecretScope {
+            peer_id: peer_id.to_string(),
+            keys: BTreeSet::new(),
+            updated_at: 0,
+        });
+        let before = scope.keys.len();
+        scope.keys.extend(keys);
+        // A first grant is recorded even when empty, so later keys count as new
+        if scope.keys.len() != before || scope.updated_at == 0 {
+            scope.updated_at = unix_now();
+            self.persist();
+        }
+    }
+
+    /// Remove keys from a peer's scope; returns false if the peer has none
+    pub fn revoke<'a>(&mut self, peer_id: &str, keys: impl IntoIterator<Item = &'a String>) -> bool {
+        let Some(scope) = self.scopes.get_mut(peer_id) else {
+            return false;
+        };
+        for key in keys {
+            scope.keys.remove(key);
+        }
+        scope.updated_at = unix_now();
+        self.persist();
+        true
+    }
+
+    /// Forget a peer's scope, so its next request is treated as the first
+    pub fn forget(&mut self, peer_id: &str) -> bool {
+        let removed = self.scopes.remove(peer_id).is_some();
+        if removed {
+            self.persist();
+        }
+        removed
+    }
+
+    /// All scopes, by peer ID
+    pub fn list(&self) -> Vec<&SecretScope> {
+        let mut scopes: Vec<&SecretScope> = self.scopes.values().collect();
+        scopes.sort_by(|a, b| a.peer_id.cmp(&b.peer_id));
+        scopes
+    }
+
+    /// Check if no peer has a scope
+    pub fn is_empty(&self) -> bool {
+        self.scopes.is_empty()
+    }
+
+    fn read(&mut self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        self.modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        self.scopes = match std::fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str::<Vec<SecretScope>>(&contents)
+                .map(|list| list.into_iter().map(|s| (s.peer_id.clone(), s)).collect())
+                .unwrap_or_else(|e| {
+                    warn!("Ignoring unreadable secrets scopes {}: {}", path.display(), e);
+                    HashMap::new()
+                }),
+            Err(_) => HashMap::new(),
+        };
+    }
+
+    fn persist(&mut self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+
+        let result = serde_json::to_string_pretty(&self.list())
+            .map_err(wh_core::RiftError::from)
+            .and_then(|json| {
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(path, json)?;
+                Ok(())
+            });
+
+        match result {
+            Ok(()) => self.modified = std::fs::metadata(path).and_then(|m| m.modified()).ok(),
+            Err(e) => warn!("Failed to persist secrets scopes to {}: {}", path.display(), e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_first_request_shares_all_then_new_keys_are_held_back() {
+        let mut scopes = SecretScopes::new();
+        let first = scopes.resolve("peer", &keys(&["DB_URL", "API_KEY"]));
+        assert_eq!(first, ScopedKeys { shared: keys(&["API_KEY", "DB_URL"]), new: Vec::new() });
+        scopes.grant("peer", first.shared);
+
+        let next = scopes.resolve("peer", &keys(&["DB_URL", "API_KEY", "STRIPE_KEY"]));
+        assert_eq!(next, ScopedKeys { shared: keys(&["API_KEY", "DB_URL"]), new: keys(&["STRIPE_KEY"]) });
+
+        assert!(scopes.revoke("peer", &keys(&["API_KEY"])));
+        let trimmed = scopes.resolve("peer", &keys(&["DB_URL", "API_KEY"]));
+        assert_eq!(trimmed.shared, keys(&["DB_URL"]));
+        assert_eq!(trimmed.new, keys(&["API_KEY"]));
+    }
+
+    #[test]
+    fn test_scopes_survive_restart_and_cli_edits() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(SECRET_SCOPES_FILE);
+
+        let mut host = SecretScopes::load(path.clone());
+        host.grant("peer", keys(&["DB_URL"]));
+        assert!(!host.reload_if_changed());
+
+        // `rift peers secrets peer --add STRIPE_KEY` runs in another process
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        SecretScopes::load(path.clone()).grant("peer", keys(&["STRIPE_KEY"]));
+
+        assert!(host.reload_if_changed());
+        assert_eq!(host.get("peer").unwrap().keys.len(), 2);
+        assert!(SecretScopes::load(path).forget("peer"));
+    }
+}
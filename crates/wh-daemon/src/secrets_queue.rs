@@ -0,0 +1,180 @@
+//! Queued Secrets Requests
+//!
+//! `rift connect --request-secrets --save-secrets FILE` against a host that
+//! cannot be reached leaves the request here instead of failing. Whichever
+//! daemon runs when the host next connects asks it for the secrets, writes
+//! them to the file and reports [`DaemonEvent::QueuedSecretsDelivered`].
+//! Requests are dropped after a week. Persisted in the state store.
+//!
+//! [`DaemonEvent::QueuedSecretsDelivered`]: crate::server::DaemonEvent::QueuedSecretsDelivered
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tracing::warn;
+use wh_core::secrets::{EnvVault, SecretsResponse};
+use wh_core::{receive_secrets, send_secrets, ReceivedSecrets, Result, Spool, StateStore};
+
+use crate::tokens::unix_now;
+
+/// Name of the queue in the state store
+pub const SECRETS_QUEUE_FILE: &str = "secrets_queue.json";
+
+/// Queued requests older than this are dropped (7 days)
+const QUEUE_TTL_SECS: u64 = 7 * 24 * 60 * 60;
+
+/// A secrets request waiting for its host to connect
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueuedSecretsRequest {
+    /// Host to ask
+    pub peer_id: String,
+    /// Absolute path the secrets are written to
+    pub save_path: PathBuf,
+    /// Unix timestamp (seconds) of queueing
+    pub queued_at: u64,
+}
+
+/// Persistent queue of secrets requests, at most one per host
+#[derive(Debug, Default)]
+pub struct SecretsQueue {
+    /// Where the queue is kept (None = in-memory only)
+    store: Option<Arc<dyn StateStore>>,
+    requests: Vec<QueuedSecretsRequest>,
+}
+
+impl SecretsQueue {
+    /// Create an in-memory queue
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the queue from a state store, starting empty if it is missing or unreadable
+    pub fn open(state: Arc<dyn StateStore>) -> Self {
+        let requests = match state.load(SECRETS_QUEUE_FILE) {
+            Ok(Some(contents)) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+                warn!("Ignoring unreadable secrets queue {}: {}", state.location(SECRETS_QUEUE_FILE), e);
+                Vec::new()
+            }),
+            Ok(None) => Vec::new(),
+            Err(e) => {
+                warn!("Failed to read secrets queue {}: {}", state.location(SECRETS_QUEUE_FILE), e);
+                Vec::new()
+            }
+        };
+
+        let mut queue = Self {
+            store: Some(state),
+            requests,
+        };
+        queue.prune(unix_now());
+        queue
+    }
+
+    /// Queue a request, replacing an earlier one for the same host
+    pub fn queue(&mut self, peer_id: &str, save_path: PathBuf) {
+        self.requests.retain(|request| request.peer_id != peer_id);
+        self.requests.push(QueuedSecretsRequest {
+            peer_id: peer_id.to_string(),
+            save_path,
+            queued_at: unix_now(),
+        });
+        self.persist();
+    }
+
+    /// Take the request waiting for a host, if there is one
+    pub fn take(&mut self, peer_id: &str) -> Option<QueuedSecretsRequest> {
+        let index = self.requests.iter().position(|request| request.peer_id == peer_id)?;
+        let request = self.requests.remove(index);
+        self.persist();
+        Some(request)
+    }
+
+    /// Requests still waiting, oldest first
+    pub fn requests(&self) -> &[QueuedSecretsRequest] {
+        &self.requests
+    }
+
+    /// Drop requests queued too long ago
+    pub fn prune(&mut self, now: u64) {
+        let before = self.requests.len();
+        self.requests
+            .retain(|request| now.saturating_sub(request.queued_at) < QUEUE_TTL_SECS);
+        if self.requests.len() != before {
+            self.persist();
+        }
+    }
+
+    fn persist(&self) {
+        let Some(store) = &self.store else {
+            return;
+        };
+        let result = serde_json::to_string_pretty(&self.requests)
+            .map_err(Into::into)
+            .and_then(|json| store.save(SECRETS_QUEUE_FILE, &json));
+        if let Err(e) = result {
+            warn!("Failed to persist secrets queue to {}: {}", store.location(SECRETS_QUEUE_FILE), e);
+        }
+    }
+}
+
+/// Ask a host for its secrets on an open secrets stream
+///
+/// Sends what we hold from `previous`, so the host only sends changes, and
+/// returns the full set with the unchanged secrets filled in.
+pub async fn fetch_secrets<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: S,
+    vault: &EnvVault,
+    previous: Option<&ReceivedSecrets>,
+) -> Result<(SecretsResponse, ReceivedSecrets)> {
+    let (mut read, mut write) = tokio::io::split(stream);
+    send_secrets(&mut write, &vault.create_secrets_request_since(previous)).await?;
+    let response: SecretsResponse = receive_secrets(&mut read).await?;
+    let received = vault.open_changes_from_peer(&response, previous)?;
+    Ok((response, received))
+}
+
+/// Write the secrets we can resolve to a `.env` file; returns how many were written
+///
+/// Spooled first so the file appears whole and readable by us only.
+pub fn write_env_file(spool: &Spool, vault: &EnvVault, received: &ReceivedSecrets, path: &Path) -> Result<usize> {
+    let mut keys: Vec<&String> = received.secrets.keys().collect();
+    keys.sort();
+    let mut content = String::new();
+    let mut written = 0;
+    for key in keys {
+        if let Some(value) = vault.resolve(&received.secrets[key]) {
+            content.push_str(&format!("{}={}\n", key, value));
+            written += 1;
+        }
+    }
+    spool.write(".env", content.as_bytes())?.persist_to(path)?;
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wh_core::FileStore;
+
+    #[test]
+    fn test_queue_keeps_one_request_per_host_across_restarts() {
+        let dir = tempfile::tempdir().unwrap();
+        let state: Arc<dyn StateStore> = Arc::new(FileStore::new(dir.path().to_path_buf()));
+
+        let mut queue = SecretsQueue::open(state.clone());
+        queue.queue("host-a", PathBuf::from("/tmp/a.env"));
+        queue.queue("host-b", PathBuf::from("/tmp/b.env"));
+        queue.queue("host-a", PathBuf::from("/tmp/a2.env"));
+
+        let mut queue = SecretsQueue::open(state.clone());
+        assert_eq!(queue.requests().len(), 2);
+        assert_eq!(queue.take("host-a").unwrap().save_path, PathBuf::from("/tmp/a2.env"));
+        assert!(queue.take("host-a").is_none());
+
+        let mut queue = SecretsQueue::open(state);
+        assert_eq!(queue.requests().len(), 1);
+        queue.prune(unix_now() + QUEUE_TTL_SECS);
+        assert!(queue.requests().is_empty());
+    }
+}
@@ -3,19 +3,65 @@
 //! Main daemon that orchestrates the P2P network, sessions, and UI updates.
 
 use futures::StreamExt;
+use serde::{Deserialize, Serialize};
 use wh_core::{
-    open_tunnel_stream,
+    answer_tunnel_request, open_tunnel_stream_with, open_stripe_streams, read_tunnel_request,
     send_secrets, receive_secrets,
-    NetworkEvent, PeerNetwork, RiftConfig, Result, PeerId,
+    ConfigOverrides, ConnectionDirection, ConnectionPath, EventSender, PeerCapabilities, EventStream, Multiaddr, NatType, NetworkEvent, PeerAccessDecision, PeerNetwork, PortApproval, RiftConfig, RiftError, Result, PeerId,
+    PeerIdentity,
+    ExitRequest, SharedService, StateStore,
+    protocol::{TunnelRequest, TunnelResponse},
+    control::{ControlMessage, HealthStatus, ServiceHealth},
+    kv::{KvEntry, KvMessage, KvStore},
+    stats::{LatencyHistogram, TrafficBreakdown, CLASSIFY_SAMPLE_BYTES},
+    network::echo::serve_echo,
+    network::presence::serve_services,
+    network::wake::{ping_burst, WakeDetector},
+    network::stripe::{striped_buffer_bytes, stripe_copy, unstripe_copy, StripeAssembler, StripeHeader},
+    RiftProtocol,
+    libp2p_stream,
     secrets::EnvVault,
+    Protocols,
 };
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
 use tokio::net::TcpListener;
-use tokio::sync::{mpsc, oneshot};
+use tokio::sync::{mpsc, oneshot, watch};
 use tokio_util::compat::FuturesAsyncReadCompatExt;
 use tracing::{debug, error, info, warn};
 
+use crate::attach::{serve_viewer, SessionView, ViewerUpdate};
+use crate::audit::AuditLog;
+use crate::connect_attempt::ConnectAttempt;
+use crate::connections::{ConnectionEvent, ConnectionGuard, ConnectionRecord, ConnectionTable, PeerStats, PortStats};
+use crate::health::probe;
+use crate::http_log::{AccessLog, HttpExchange, HttpObserver, InspectedExchange, ACCESS_LOG_FILE};
+use crate::links::{LinkStatus, LinkStore, LINKS_FILE};
+use crate::middleware::{Direction, Middleware, MiddlewareChain};
+use crate::migration::bridge_migrating;
+use crate::pool::{connect_target, TargetPool, LOCALHOST};
+use crate::rate_limit::{RateLimiter, RateLimiters, Throttled};
+use crate::reconnect::Reconnect;
+use crate::resources::{BufferLease, ResourceGuard, BRIDGE_BUFFER_BYTES};
+use crate::secret_scopes::{ScopedKeys, SecretScopes, SECRET_SCOPES_FILE};
+use crate::secrets_queue::{fetch_secrets, write_env_file, SecretsQueue};
+use crate::remote;
+use crate::sockets::{accept_any, apply_keepalive, bind_listener};
+use crate::status::StatusSnapshot;
+use crate::tasks::TaskSet;
+use crate::tokens::{unix_now, SessionTokenStore};
+use crate::upload_guard::{GuardedWriter, UploadGuard};
+
+/// How long an incoming peer waits for the user to approve it
+const APPROVAL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long a replayed HTTP request may take to complete
+const REPLAY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long [`DaemonServer::link_addresses`] waits for the listeners to come up
+const LISTEN_ADDR_WAIT: Duration = Duration::from_secs(2);
+
 /// Events from the daemon to the UI
 #[derive(Debug, Clone)]
 pub enum DaemonEvent {
@@ -25,31 +71,176 @@ pub enum DaemonEvent {
     /// Listening on address
     Listening { address: String },
 
-    /// Peer connected
-    PeerConnected { peer_id: String },
+    /// Peer connected, with the user's nickname for it if it has one
+    PeerConnected { peer_id: String, nickname: Option<String> },
 
     /// Peer disconnected
     PeerDisconnected { peer_id: String },
 
+    /// The user's nickname for a peer, to show instead of its ID
+    ///
+    /// Sent for every named peer at startup, so events about peers that have
+    /// not connected (approval prompts, dial retries) show names too. Peers
+    /// connecting later carry their name on [`DaemonEvent::PeerConnected`].
+    PeerNamed { peer_id: String, nickname: String },
+
+    /// Peer found on the local network
+    PeerDiscovered { peer_id: String, addresses: Vec<String> },
+
+    /// Peer announced the optional features it serves, over identify
+    PeerCapabilities { peer_id: String, capabilities: PeerCapabilities },
+
+    /// Peer presented identity claims (`valid` = signed by its own key)
+    PeerAttested { peer_id: String, claims: String, valid: bool },
+
+    /// Hole punching failed and traffic to the peer goes through a relay
+    UsingRelay { peer_id: String, relay: String, rtt_ms: Option<u64> },
+
+    /// A relayed peer got a direct connection, which new streams now take
+    PathUpgraded { peer_id: String, relayed_ms: Option<u64>, direct_ms: u64 },
+
+    /// A relay took our reservation: peers that cannot dial us can come through it
+    RelayReserved { relay_peer_id: String },
+
+    /// A relay reservation failed (`lost` = it had been accepted before)
+    RelayReservationEnded { relay_peer_id: String, reason: String, lost: bool },
+
+    /// Probing the configured relays moved our reservation to `relay`
+    RelaySelected { relay: String, rtt_ms: Option<u64> },
+
+    /// Answer to [`DaemonCommand::ListPeers`]: the connected peers
+    PeerList { peers: Vec<PeerRecord> },
+
+    /// A ping to a connected peer came back: latest and rolling average round trip
+    PeerRtt { peer_id: String, last_ms: u64, average_ms: u64 },
+
+    /// New evidence changed the detected NAT type
+    NatDetected { nat_type: NatType },
+
+    /// Dialing the peer to connect to failed and will be retried
+    ///
+    /// `discovery` names the ways the peer is being looked for meanwhile (mDNS, rendezvous, ...).
+    DialRetrying { peer_id: String, attempt: u32, max_attempts: u32, discovery: Vec<String> },
+
+    /// [`DaemonCommand::CancelConnect`] stopped looking for the host after `attempts` dials
+    ///
+    /// `peer_id` is the link itself when it was still being resolved.
+    ConnectCancelled { peer_id: String, attempts: u32 },
+
     /// Tunnel established
     TunnelEstablished { peer_id: String, port: u16 },
 
-    /// New tunnel connection
-    TunnelConnection { connection_id: u64 },
+    /// Connecting to the host or binding the local port failed (connect side)
+    ///
+    /// `peer_id` is the link itself when it holds no valid peer ID.
+    TunnelFailed { peer_id: String, reason: String },
+
+    /// The machine woke from sleep; listeners were renewed and `peers` connections are being checked
+    WokeFromSleep { asleep_secs: u64, peers: usize },
+
+    /// A connection did not survive sleep and was dropped
+    PeerLostInSleep { peer_id: String },
+
+    /// The host is connected again after the connection dropped (connect side)
+    TunnelReconnected { peer_id: String, attempts: u32, downtime_ms: u64 },
+
+    /// A tunnelled connection opened, to or from `port` of the peer
+    ConnectionOpened { connection_id: u64, peer_id: String, port: u16 },
+
+    /// A tunnelled connection closed after relaying these bytes (sent towards the peer, received from it)
+    ConnectionClosed { connection_id: u64, peer_id: String, port: u16, bytes_sent: u64, bytes_received: u64, duration_ms: u64 },
 
     /// Incoming connection request (waiting for approval)
     IncomingConnectionRequest { peer_id: String },
 
+    /// A stream was refused by the protocol policy
+    StreamRejected { peer_id: String, protocol: String },
+
+    /// A peer on the blocklist (or missing from the allowlist) was refused
+    PeerBlocked { peer_id: String },
+
+    /// A peer skipped approval by presenting a valid session token
+    SessionResumed { peer_id: String },
+
+    /// A peer on the `viewers` list attached a read-only dashboard (`rift attach`)
+    ViewerAttached { peer_id: String },
+
+    /// A previously approved peer reconnected within the grace window
+    ReconnectApproved { peer_id: String },
+
+    /// A guest link expired: the peer was disconnected and its approvals removed
+    GuestExpired { peer_id: String },
+
+    /// A local connection moved to a new stream after its stream dropped (connect side)
+    ConnectionMigrated { peer_id: String, replayed_bytes: usize },
+
+    /// A tunnelled connection was closed with [`DaemonCommand::CloseConnection`]
+    ConnectionTerminated { connection_id: u64, peer_id: String, port: u16 },
+
+    /// A tunnelled connection was closed after relaying nothing for `idle_secs`
+    ConnectionTimedOut { connection_id: u64, peer_id: String, port: u16, idle_secs: u64 },
+
+    /// An HTTP request through the tunnel completed (connect side, `http_log`)
+    HttpRequest { exchange: HttpExchange },
+
+    /// A request/response pair was captured in full for the inspector
+    HttpInspected { record: InspectedExchange },
+
     /// Secrets received
     SecretsReceived { count: usize },
 
+    /// The host was away, so the secrets request waits until it connects (connect side)
+    SecretsRequestQueued { peer_id: String, save_path: String },
+
+    /// A queued secrets request was answered once the host connected; `count` secrets went to `save_path`
+    QueuedSecretsDelivered { peer_id: String, count: usize, save_path: String },
+
+    /// A queued secrets request failed once the host connected
+    QueuedSecretsFailed { peer_id: String, reason: String },
+
+    /// A peer asks for secrets again and some were added since its last request (share side)
+    ///
+    /// `keys` are shared by default; `new_keys` only if the host accepts them.
+    SecretsApprovalRequest { peer_id: String, keys: Vec<String>, new_keys: Vec<String> },
+
+    /// Secrets were sent to a peer; `withheld` were available but not granted (share side)
+    SecretsShared { peer_id: String, count: usize, withheld: Vec<String> },
+
+    /// A shared key-value entry changed (`value` None = deleted; `peer_id` = writer)
+    KvChanged { key: String, value: Option<Vec<u8>>, peer_id: String },
+
     /// Statistics update
     StatsUpdate {
         bytes_sent: u64,
         bytes_received: u64,
+        /// Bytes sent since the session started or the counters were reset
+        session_bytes_sent: u64,
+        /// Bytes received since the session started or the counters were reset
+        session_bytes_received: u64,
         active_connections: u64,
+        live_tasks: usize,
+        /// Connections and bytes per kind of traffic (TLS, HTTP, SSH, other)
+        traffic_classes: TrafficBreakdown,
+        /// Connections and bytes per port
+        ports: Vec<PortStats>,
+        /// Connections, bytes and current rate per peer
+        peers: Vec<PeerStats>,
     },
 
+    /// Configuration file was reloaded
+    ConfigReloaded {
+        applied: Vec<String>,
+        requires_restart: Vec<String>,
+    },
+
+    /// Health of the shared service changed
+    ///
+    /// `paused` is set on the connect side while local connections are not accepted.
+    ServiceHealth { health: ServiceHealth, paused: bool },
+
+    /// The daemon hit a resource limit and is refusing new connections
+    ResourceWarning { message: String },
+
     /// Error occurred
     Error { message: String },
 
@@ -57,14 +248,55 @@ pub enum DaemonEvent {
     Shutdown,
 }
 
+/// A connected peer, as reported in [`DaemonEvent::PeerList`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerRecord {
+    pub peer_id: String,
+    /// Name the user gave the peer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nickname: Option<String>,
+    /// Listen addresses the peer reported
+    pub addresses: Vec<String>,
+    /// Seconds since the first connection opened
+    pub connected_secs: u64,
+    /// Who dialed the first connection
+    pub direction: ConnectionDirection,
+    /// How tunnel traffic to the peer travels
+    pub path: ConnectionPath,
+    /// Rolling average ping round trip, once known
+    pub rtt_ms: Option<u64>,
+    /// Optional features the peer announced; None if it announces none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capabilities: Option<PeerCapabilities>,
+}
+
+impl From<ConnectionEvent> for DaemonEvent {
+    fn from(event: ConnectionEvent) -> Self {
+        match event {
+            ConnectionEvent::Opened { connection_id, peer_id, port } => {
+                DaemonEvent::ConnectionOpened { connection_id, peer_id, port }
+            }
+            ConnectionEvent::Closed { connection_id, peer_id, port, bytes_sent, bytes_received, duration_ms } => {
+                DaemonEvent::ConnectionClosed { connection_id, peer_id, port, bytes_sent, bytes_received, duration_ms }
+            }
+        }
+    }
+}
+
 /// Commands to the daemon
 #[derive(Debug)]
 pub enum DaemonCommand {
-    /// Share a port
+    /// Share one or more ports over the same peer connections
     Share {
-        port: u16,
+        ports: Vec<u16>,
         secrets_path: Option<PathBuf>,
         auto_approve: bool,
+        /// Refuse tunnels without a valid link ID, whatever `links.require` says
+        require_link: bool,
+        /// Ports served by another machine (`--target HOST:PORT`), shared besides `ports`
+        ///
+        /// Each must be allowed by `share_targets` in the config.
+        targets: Vec<(String, u16)>,
     },
 
     /// Connect to a peer
@@ -73,17 +305,66 @@ pub enum DaemonCommand {
         port: u16,
         local_port: Option<u16>,
         bind_addr: String,
+        /// Service name the host resolves instead of `port` (`rift connect <link> db`)
+        service: Option<String>,
+        /// Further ports on the host, each with the local port to listen on, tunnelled over the same connection
+        forwards: Vec<(u16, u16)>,
     },
 
+    /// Stop a [`Connect`](Self::Connect) that is still looking for the host
+    CancelConnect,
+
     /// Approve an incoming connection
     ApproveConnection { peer_id: String },
 
     /// Deny an incoming connection
     DenyConnection { peer_id: String },
 
+    /// Answer a secrets request: the remembered keys, plus the new ones if `include_new`
+    ApproveSecrets { peer_id: String, include_new: bool },
+
+    /// Refuse a secrets request
+    DenySecrets { peer_id: String },
+
+    /// Ask an unreachable host for its secrets once it connects, writing them to `save_path`
+    QueueSecretsRequest { peer_id: String, save_path: PathBuf },
+
     /// Stop a session
     StopSession { session_id: u64 },
 
+    /// Re-read the watched configuration file and apply safe changes
+    ReloadConfig,
+
+    /// Revoke session resumption tokens of a peer (or all peers if None)
+    RevokeSessionTokens { peer_id: Option<String> },
+
+    /// Report every open tunnelled connection
+    ListConnections { reply: oneshot::Sender<Vec<ConnectionRecord>> },
+
+    /// Close one tunnelled connection (an ID from `ListConnections`), leaving the peer's session up
+    CloseConnection { connection_id: u64 },
+
+    /// Report the connected peers as a [`DaemonEvent::PeerList`]
+    ListPeers,
+
+    /// Report the daemon's status, as written for `rift status`
+    Status { reply: oneshot::Sender<StatusSnapshot> },
+
+    /// Send a captured HTTP request through the tunnel again (connect side)
+    ReplayRequest { request: Vec<u8> },
+
+    /// Set a shared key-value entry and replicate it to session peers
+    KvSet { key: String, value: Vec<u8> },
+
+    /// Delete a shared key-value entry and replicate the deletion
+    KvDelete { key: String },
+
+    /// Report the live shared key-value entries
+    KvList { reply: oneshot::Sender<Vec<(String, Vec<u8>)>> },
+
+    /// Start the session byte counters from zero (lifetime totals are kept)
+    ResetStats,
+
     /// Shutdown daemon
     Shutdown,
 }
@@ -91,18 +372,75 @@ pub enum DaemonCommand {
 /// Shared traffic stats (atomic for cross-task updates)
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc as StdArc;
+use std::sync::Mutex as StdMutex;
 
 #[derive(Debug, Default)]
 pub struct TrafficStats {
     pub bytes_sent: AtomicU64,
     pub bytes_received: AtomicU64,
     pub active_connections: AtomicU64,
+    /// Connect side: local TCP accept to first byte relayed from the host
+    pub stream_open_latency: LatencyHistogram,
+    /// Host side: stream accept to target TCP established
+    pub target_connect_latency: LatencyHistogram,
+    /// Lifetime totals at the start of the current session
+    session_sent_base: AtomicU64,
+    session_received_base: AtomicU64,
+}
+
+impl TrafficStats {
+    /// Bytes sent and received since the session started or was reset
+    pub fn session_bytes(&self) -> (u64, u64) {
+        let sent = self.bytes_sent.load(Ordering::Relaxed);
+        let received = self.bytes_received.load(Ordering::Relaxed);
+        (
+            sent.saturating_sub(self.session_sent_base.load(Ordering::Relaxed)),
+            received.saturating_sub(self.session_received_base.load(Ordering::Relaxed)),
+        )
+    }
+
+    /// Start the session counters from zero; lifetime totals keep counting
+    pub fn reset_session(&self) {
+        self.session_sent_base
+            .store(self.bytes_sent.load(Ordering::Relaxed), Ordering::Relaxed);
+        self.session_received_base
+            .store(self.bytes_received.load(Ordering::Relaxed), Ordering::Relaxed);
+    }
+}
+
+/// One tunnelled connection: a plain stream or a complete stripe set
+enum TunnelStreams {
+    Single(libp2p::Stream),
+    Striped(Vec<libp2p::Stream>),
+}
+
+/// Tunnel streams held back while the user decides on a peer, with their shared ports
+struct PendingApproval {
+    requested_at: Instant,
+    streams: Vec<(u16, TunnelStreams)>,
+}
+
+/// A secrets request waiting for the host to decide on newly added keys
+struct PendingSecrets {
+    requested_at: Instant,
+    scoped: ScopedKeys,
+    /// Keys to send; dropping it refuses the request
+    reply: oneshot::Sender<Vec<String>>,
 }
 
+/// Stream control with the protocol IDs of our namespace, cloned into tasks
+#[derive(Clone)]
+struct Streams {
+    control: libp2p_stream::Control,
+    protocols: Protocols,
+}
+
+/// How long the streams of a striped connection may take to all arrive
+const STRIPE_ASSEMBLY_TIMEOUT: Duration = Duration::from_secs(10);
+
 /// Main daemon server
 pub struct DaemonServer {
     /// Configuration
-    #[allow(dead_code)]
     config: RiftConfig,
 
     /// P2P Network (owned, not shared)
@@ -115,7 +453,7 @@ pub struct DaemonServer {
     link: String,
 
     /// Event sender
-    event_tx: mpsc::Sender<DaemonEvent>,
+    event_tx: EventSender<DaemonEvent>,
 
     /// Event receiver
     event_rx: mpsc::Receiver<DaemonEvent>,
@@ -126,25 +464,162 @@ pub struct DaemonServer {
     /// Command receiver
     command_rx: mpsc::Receiver<DaemonCommand>,
 
+    /// Connections opening and closing, passed on as events by the run loop
+    connection_tx: mpsc::UnboundedSender<ConnectionEvent>,
+
+    /// Connection lifecycle receiver
+    connection_rx: mpsc::UnboundedReceiver<ConnectionEvent>,
+
     /// Running flag
     running: bool,
 
-    /// Pending connection approvals (peer_id -> response channel)
-    pending_approvals: HashMap<String, oneshot::Sender<bool>>,
+    /// Watched config file and its last seen modification time
+    config_watch: Option<(PathBuf, Option<SystemTime>)>,
+
+    /// Command-line settings re-applied over each reloaded config file
+    config_overrides: ConfigOverrides,
+
+    /// Pending connection approvals with the streams waiting on them
+    pending_approvals: HashMap<PeerId, PendingApproval>,
+
+    /// Peers approved for the lifetime of their current connection
+    approved_peers: HashSet<PeerId>,
+
+    /// Ports with `approval = "require"` the user approved per peer, for its current connection
+    approved_ports: HashSet<(PeerId, u16)>,
+
+    /// Approved peers whose connection dropped, by disconnect time
+    recently_approved: HashMap<PeerId, Instant>,
+
+    /// Peer book, session tokens, status snapshots and audit log
+    state: StdArc<dyn StateStore>,
+
+    /// Session tokens issued to approved peers (host side)
+    session_tokens: SessionTokenStore,
+
+    /// Session tokens received from hosts (connect side)
+    resume_tokens: HashMap<PeerId, (String, Instant)>,
+
+    /// Revocable links handed out by this host
+    links: LinkStore,
+
+    /// Secrets each peer received before (share side)
+    secret_scopes: SecretScopes,
+
+    /// Secrets requests waiting on the host
+    pending_secrets: HashMap<PeerId, PendingSecrets>,
+
+    /// Our secrets requests waiting for their host to connect (connect side)
+    secrets_queue: SecretsQueue,
+
+    /// Link ID each connected peer presented (host side)
+    link_grants: HashMap<PeerId, String>,
+
+    /// Guest link each peer redeemed, kept across reconnects until it expires
+    guest_peers: HashMap<PeerId, String>,
+
+    /// Links are required for this share session (guest links)
+    require_link: bool,
+
+    /// Connected peers announcing a throwaway identity
+    ephemeral_peers: HashSet<PeerId>,
+
+    /// Striped connections whose streams have not all arrived yet (host side)
+    stripe_sets: StripeAssembler<(PeerId, u64), libp2p::Stream>,
+
+    /// Peers that proved they run on this machine (host side, local-fastpath)
+    local_peers: HashSet<PeerId>,
+
+    /// Shared port reachable directly on loopback (connect side, local-fastpath)
+    fastpath_port: Option<u16>,
+
+    /// Shared ports served by another machine, and that machine (host side, `--target`)
+    share_targets: HashMap<u16, String>,
+
+    /// Latest health of the shared service (probed on the host, reported to the connect side)
+    service_health: Option<ServiceHealth>,
     
     /// Traffic statistics (shared with spawned tasks)
     traffic_stats: StdArc<TrafficStats>,
+
+    /// Warm connections to the shared service (if enabled)
+    target_pool: Option<StdArc<TargetPool>>,
+
+    /// Bandwidth caps for bridges to the shared service
+    rate_limiters: RateLimiters,
+
+    /// Load limits deciding whether new connections are admitted
+    resources: ResourceGuard,
+
+    /// Record of rejected streams and other security decisions
+    audit_log: AuditLog,
+
+    /// Access log of HTTP requests through the tunnel (if `http_log` is on)
+    access_log: Option<AccessLog>,
+
+    /// Spawned bridges and exchanges, cancelled and awaited on shutdown
+    tasks: TaskSet,
+
+    /// Open tunnelled connections, kept up to date by the bridge tasks
+    connections: StdArc<ConnectionTable>,
+
+    /// Middlewares run on every tunnelled connection
+    middlewares: MiddlewareChain,
+
+    /// Current path to each connected peer
+    peer_paths: HashMap<PeerId, ConnectionPath>,
+
+    /// Key-value state replicated with session peers
+    kv: KvStore,
 }
 
 impl DaemonServer {
     /// Create a new daemon server
     pub async fn new(config: RiftConfig) -> Result<Self> {
+        // Opened once, so the network and the daemon share one store
+        let state = config.open_state_store()?;
+        let config = config.with_state_store(state.clone());
+        let swept = config.spool().clean();
+        if swept > 0 {
+            debug!("Removed {} spool file(s) left by earlier sessions", swept);
+        }
         let network = PeerNetwork::new(config.clone()).await?;
         let peer_id = network.peer_id().to_string();
         let link = network.rift_link();
 
-        let (event_tx, event_rx) = mpsc::channel(256);
+        let (event_tx, event_rx) = EventSender::channel(256);
         let (command_tx, command_rx) = mpsc::channel(64);
+        let (connection_tx, connection_rx) = mpsc::unbounded_channel();
+        let session_tokens = SessionTokenStore::open(state.clone());
+        let links = LinkStore::load(config.state_dir().join(LINKS_FILE));
+        let secret_scopes = SecretScopes::load(config.state_dir().join(SECRET_SCOPES_FILE));
+        let secrets_queue = if config.ephemeral_identity {
+            SecretsQueue::new()
+        } else {
+            SecretsQueue::open(state.clone())
+        };
+        let audit_log = AuditLog::new(state.clone());
+        let access_log = config
+            .http_log
+            .then(|| AccessLog::new(config.state_dir().join(ACCESS_LOG_FILE)));
+        let tasks = TaskSet::new();
+        if config.target_pool.enabled && config.target_pool.http_ports.is_empty() {
+            warn!("target_pool is enabled but lists no http_ports, so nothing is pooled");
+        }
+        let target_pool = (config.target_pool.enabled && !config.target_pool.http_ports.is_empty()).then(|| {
+            TargetPool::new(
+                config.target_pool.max_idle,
+                Duration::from_secs(config.target_pool.idle_timeout_secs),
+                tasks.clone(),
+            )
+        });
+        let rate_limiters = RateLimiters::new(config.rate_limit.clone());
+        let resources = ResourceGuard::new(config.resource_limits.clone());
+        let kv = KvStore::new(peer_id.clone());
+        let (middlewares, missing) = MiddlewareChain::from_config(&config.middleware);
+        for name in missing {
+            warn!("Middleware {} is not built into this binary (feature plugins)", name);
+        }
 
         Ok(Self {
             config,
@@ -155,12 +630,105 @@ impl DaemonServer {
             event_rx,
             command_tx,
             command_rx,
+            connection_tx,
+            connection_rx,
             running: false,
+            config_watch: None,
+            config_overrides: ConfigOverrides::default(),
             pending_approvals: HashMap::new(),
+            approved_peers: HashSet::new(),
+            approved_ports: HashSet::new(),
+            recently_approved: HashMap::new(),
+            state,
+            session_tokens,
+            resume_tokens: HashMap::new(),
+            links,
+            secret_scopes,
+            pending_secrets: HashMap::new(),
+            secrets_queue,
+            link_grants: HashMap::new(),
+            guest_peers: HashMap::new(),
+            require_link: false,
+            ephemeral_peers: HashSet::new(),
+            stripe_sets: StripeAssembler::new(STRIPE_ASSEMBLY_TIMEOUT),
+            local_peers: HashSet::new(),
+            fastpath_port: None,
+            share_targets: HashMap::new(),
+            service_health: None,
             traffic_stats: StdArc::new(TrafficStats::default()),
+            target_pool,
+            rate_limiters,
+            resources,
+            audit_log,
+            access_log,
+            tasks,
+            connections: ConnectionTable::new(),
+            middlewares,
+            peer_paths: HashMap::new(),
+            kv,
         })
     }
 
+    /// Run `middleware` on every tunnelled connection opened from now on, after those in the config
+    pub fn add_middleware(&mut self, middleware: StdArc<dyn Middleware>) {
+        info!("Using middleware {}", middleware.name());
+        self.middlewares.push(middleware);
+    }
+
+    /// Watch a config file for changes and hot-reload safe settings
+    ///
+    /// `overrides` (see [`ConfigOverrides::between`]) stay on top of every reload.
+    pub fn watch_config(&mut self, path: PathBuf, overrides: ConfigOverrides) {
+        let modified = Self::config_modified(&path);
+        self.config_watch = Some((path, modified));
+        self.config_overrides = overrides;
+    }
+
+    fn config_modified(path: &PathBuf) -> Option<SystemTime> {
+        std::fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
+
+    /// Reload the watched config file, applying hot-reloadable settings
+    async fn reload_config(&mut self) {
+        let Some((path, _)) = &self.config_watch else {
+            warn!("Reload requested but no config file is being watched");
+            return;
+        };
+        let path = path.clone();
+
+        let result = RiftConfig::load(&path)
+            .and_then(|new| self.config_overrides.apply(&new))
+            .and_then(|new| self.config.apply_reload(&new));
+        match result {
+            Ok(changes) => {
+                if changes.is_empty() {
+                    debug!("Config reloaded from {} with no changes", path.display());
+                    return;
+                }
+                // Connections opened from now on get limiters with the new cap
+                if changes.applied.iter().any(|key| key == "rate_limit") {
+                    self.rate_limiters = RateLimiters::new(self.config.rate_limit.clone());
+                }
+                info!(
+                    "Config reloaded from {} (applied: {:?}, requires restart: {:?})",
+                    path.display(),
+                    changes.applied,
+                    changes.requires_restart
+                );
+                let _ = self.event_tx.send(DaemonEvent::ConfigReloaded {
+                    applied: changes.applied,
+                    requires_restart: changes.requires_restart,
+                }).await;
+            }
+            Err(e) => {
+                error!("Failed to reload config from {}: {}", path.display(), e);
+                let _ = self.event_tx.send(DaemonEvent::Error {
+                    message: format!("Failed to reload config: {}", e),
+                }).await;
+            }
+        }
+    }
+
     /// Get the command sender
     pub fn command_sender(&self) -> mpsc::Sender<DaemonCommand> {
         self.command_tx.clone()
@@ -174,6 +742,43 @@ impl DaemonServer {
         old_rx
     }
 
+    /// Subscribe to daemon events; unlike the receiver, any number of subscribers may exist
+    pub fn events(&self) -> EventStream<DaemonEvent> {
+        self.event_tx.subscribe()
+    }
+
+    /// Wait until the tunnel to a host is up, returning the local port it listens on
+    ///
+    /// The connect session is identified by the host's peer ID. Subscribes right
+    /// away, so call it before sending [`DaemonCommand::Connect`]; the returned
+    /// future does not borrow the daemon and can be awaited while [`run`](Self::run)
+    /// drives it. Fails when the connection or local listener fails, the daemon
+    /// shuts down, or `timeout` passes first.
+    pub fn wait_until_tunnel_ready(
+        &self,
+        peer_id: PeerId,
+        timeout: Duration,
+    ) -> impl std::future::Future<Output = Result<u16>> + Send + 'static {
+        let events = self.events();
+        async move {
+            let peer_id = peer_id.to_string();
+            match tokio::time::timeout(timeout, tunnel_ready(events, peer_id.clone())).await {
+                Ok(result) => result,
+                Err(_) => Err(RiftError::ConnectionFailed {
+                    peer_id,
+                    reason: format!("tunnel not ready after {}s", timeout.as_secs()),
+                }),
+            }
+        }
+    }
+
+    /// Subscribe to the raw network events
+    ///
+    /// Must be called before [`run`](Self::run), which takes over the network.
+    pub fn network_events(&self) -> Option<EventStream<NetworkEvent>> {
+        self.network.as_ref().map(PeerNetwork::events)
+    }
+
     /// Get peer ID
     pub async fn peer_id(&self) -> String {
         self.peer_id.clone()
@@ -184,6 +789,17 @@ impl DaemonServer {
         self.link.clone()
     }
 
+    /// Addresses to embed in a link, once the listeners are up
+    ///
+    /// Must be called after [`start`](Self::start) and before [`run`](Self::run).
+    pub async fn link_addresses(&mut self) -> Vec<Multiaddr> {
+        let Some(network) = self.network.as_mut() else {
+            return Vec::new();
+        };
+        network.wait_for_listen_addrs(LISTEN_ADDR_WAIT).await;
+        network.link_addresses()
+    }
+
     /// Start the daemon
     pub async fn start(&mut self) -> Result<()> {
         info!("Starting Rift daemon...");
@@ -200,6 +816,13 @@ impl DaemonServer {
                     })
                     .await;
             }
+
+            for (peer_id, nickname) in network.peer_nicknames() {
+                let _ = self
+                    .event_tx
+                    .send(DaemonEvent::PeerNamed { peer_id: peer_id.to_string(), nickname })
+                    .await;
+            }
         }
 
         // Send ready event
@@ -215,213 +838,1414 @@ impl DaemonServer {
         Ok(())
     }
 
-    /// Run the daemon main loop
-    pub async fn run(&mut self) -> Result<()> {
-        // Take ownership of network for the run loop
-        let mut network = self.network.take().expect("Network should be available");
-        let mut network_rx = network.take_event_receiver();
-        let mut incoming_streams = network.take_incoming_streams();
-        let mut incoming_secrets_streams = network.take_incoming_secrets_streams();
-        let event_tx = self.event_tx.clone();
-        let traffic_stats = self.traffic_stats.clone();
-
-        // Track share session target port
-        let mut share_port: Option<u16> = None;
-        
-        // Track auto-approve setting
-        let mut auto_approve = false;
-        
-        // Track secrets to share
-        let mut share_secrets: Option<EnvVault> = None;
-        
-        // Track connect session info
-        let mut connect_info: Option<(PeerId, u16, TcpListener)> = None;
-        let stream_control = network.stream_control();
-        
-        // Stats update timer - send stats every 100ms for smooth graph updates
-        let mut stats_interval = tokio::time::interval(tokio::time::Duration::from_millis(100));
-        stats_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
-
-        // Main event loop
-        while self.running {
-            tokio::select! {
-                // Periodic stats update
-                _ = stats_interval.tick() => {
-                    let _ = event_tx.send(DaemonEvent::StatsUpdate {
-                        bytes_sent: traffic_stats.bytes_sent.load(Ordering::Relaxed),
-                        bytes_received: traffic_stats.bytes_received.load(Ordering::Relaxed),
-                        active_connections: traffic_stats.active_connections.load(Ordering::Relaxed),
-                    }).await;
+    /// Spawn a task bridging an approved tunnel stream to the shared port
+    fn spawn_bridge(&self, peer_id: PeerId, stream: libp2p::Stream, port: u16) {
+        let Some(lease) = self.admit_connection(BRIDGE_BUFFER_BYTES) else {
+            self.reject_tunnel(peer_id, TunnelStreams::Single(stream), "the host is overloaded, try again later");
+            return;
+        };
+        let host = self.target_host(port).to_string();
+        info!("Bridging stream from {} to {}:{}", peer_id, host, port);
+        let stats = self.traffic_stats.clone();
+        let config = self.config.clone();
+        let pool = self.target_pool.clone();
+        let limiter = self.rate_limiters.limiter_for(peer_id);
+        let conn = self.open_connection(peer_id, "share", port);
+        // Spawn a task to bridge this stream to localhost:port with traffic tracking
+        self.tasks.spawn(async move {
+            let _lease = lease;
+            stats.active_connections.fetch_add(1, Ordering::Relaxed);
+            match conn.run(bridge_with_stats(stream, (&host, port), &config, pool.as_ref(), limiter, stats.clone(), &conn)).await {
+                Some(Ok((sent, recv))) => {
+                    debug!("Stream from {} closed. Sent: {}, Recv: {}", peer_id, sent, recv);
                 }
-                // Handle commands
-                Some(command) = self.command_rx.recv() => {
-                    match command {
-                        DaemonCommand::Share { port, secrets_path, auto_approve: auto_approve_flag } => {
-                            info!("Share command received for port {} (auto_approve={})", port, auto_approve_flag);
-                            share_port = Some(port);
-                            auto_approve = auto_approve_flag;
-                            
-                            // Load secrets if provided
-                            if let Some(path) = secrets_path {
-                                match EnvVault::from_file(&path) {
-                                    Ok(vault) => {
-                                        info!("Loaded secrets from {}", path.display());
-                                        share_secrets = Some(vault);
-                                    }
-                                    Err(e) => {
-                                        error!("Failed to load secrets from {}: {}", path.display(), e);
-                                        let _ = event_tx.send(DaemonEvent::Error {
-                                            message: format!("Failed to load secrets: {}", e),
-                                        }).await;
-                                    }
-                                }
-                            }
-                        }
-                        DaemonCommand::Connect { link, port, local_port, bind_addr } => {
-                            info!("Connect command received for {} port {}", link, port);
-                            
-                            // Retry connection with backoff for peer discovery
-                            // Give mDNS time to discover the peer (usually takes 100-200ms)
-                            let mut retry_count = 0;
-                            let max_retries = 20;
-                            let retry_delay = tokio::time::Duration::from_millis(250);
-                            
-                            let connection_result = loop {
-                                // Poll network to process mDNS events
-                                let _ = network.poll_once().await;
-                                
-                                match network.connect(&link).await {
-                                    Ok(peer_id) => break Ok(peer_id),
-                                    Err(e) if retry_count < max_retries => {
-                                        if retry_count == 0 {
-                                            info!("Waiting for peer discovery...");
-                                        }
-                                        retry_count += 1;
-                                        tokio::time::sleep(retry_delay).await;
-                                    }
-                                    Err(e) => break Err(e),
-                                }
-                            };
-                            
-                            match connection_result {
-                                Ok(peer_id) => {
-                                    info!("Connected to peer {}", peer_id);
-                                    // Start local TCP listener
-                                    let local = local_port.unwrap_or(port);
-                                    match TcpListener::bind(format!("{}:{}", bind_addr, local)).await {
-                                        Ok(listener) => {
-                                            info!("Local proxy listening on {}:{}", bind_addr, local);
-                                            connect_info = Some((peer_id, port, listener));
-                                            let _ = event_tx.send(DaemonEvent::TunnelEstablished {
-                                                peer_id: peer_id.to_string(),
-                                                port: local,
-                                            }).await;
-                                        }
-                                        Err(e) => {
-                                            error!("Failed to bind local port {}: {}", local, e);
-                                            let _ = event_tx.send(DaemonEvent::Error {
-                                                message: format!("Failed to bind port {}: {}", local, e),
-                                            }).await;
-                                        }
-                                    }
-                                }
-                                Err(e) => {
-                                    error!("Failed to connect to {}: {}", link, e);
-                                    let _ = event_tx.send(DaemonEvent::Error {
-                                        message: format!("Failed to connect: {}", e),
-                                    }).await;
-                                }
-                            }
-                        }
-                        DaemonCommand::ApproveConnection { peer_id } => {
-                            info!("Approving connection from {}", peer_id);
-                            if let Some(tx) = self.pending_approvals.remove(&peer_id) {
-                                let _ = tx.send(true);
-                            }
-                        }
-                        DaemonCommand::DenyConnection { peer_id } => {
-                            info!("Denying connection from {}", peer_id);
-                            if let Some(tx) = self.pending_approvals.remove(&peer_id) {
-                                let _ = tx.send(false);
-                            }
-                        }
-                        DaemonCommand::Shutdown => {
-                            info!("Shutdown command received");
-                            self.running = false;
-                        }
-                        DaemonCommand::StopSession { .. } => {}
+                Some(Err(e)) => {
+                    warn!("Stream bridge ended: {}", e);
+                }
+                None => debug!("Stream from {} closed on request", peer_id),
+            }
+            stats.active_connections.fetch_sub(1, Ordering::Relaxed);
+        });
+    }
+
+    /// Spawn a task bridging a complete stripe set to the shared port
+    fn spawn_striped_bridge(&self, peer_id: PeerId, streams: Vec<libp2p::Stream>, port: u16) {
+        let Some(lease) = self.admit_connection(striped_buffer_bytes(streams.len(), self.config.striping.chunk_size)) else {
+            return;
+        };
+        let host = self.target_host(port).to_string();
+        info!("Bridging {} striped streams from {} to {}:{}", streams.len(), peer_id, host, port);
+        let stats = self.traffic_stats.clone();
+        let retry = self.config.target_retry.clone();
+        let chunk_size = self.config.striping.chunk_size;
+        let guard = (self.config.max_request_body_bytes > 0).then(|| UploadGuard::new(self.config.max_request_body_bytes));
+        let limiter = self.rate_limiters.limiter_for(peer_id);
+        let conn = self.open_connection(peer_id, "share", port);
+        self.tasks.spawn(async move {
+            let _lease = lease;
+            stats.active_connections.fetch_add(1, Ordering::Relaxed);
+            match connect_target(&host, port, &retry).await {
+                Ok(tcp) => {
+                    stats.target_connect_latency.record(conn.elapsed());
+                    match conn.run(bridge_striped(tcp, streams, chunk_size, stats.clone(), &conn, None, guard, limiter)).await {
+                        Some((sent, recv)) => debug!("Striped stream from {} closed. Sent: {}, Recv: {}", peer_id, sent, recv),
+                        None => debug!("Striped stream from {} closed on request", peer_id),
                     }
                 }
+                Err(e) => warn!("Failed to connect to {}:{}: {}", host, port, e),
+            }
+            stats.active_connections.fetch_sub(1, Ordering::Relaxed);
+        });
+    }
 
-                // Handle incoming streams (host side - share)
-                Some((peer_id, stream)) = incoming_streams.next() => {
-                    if let Some(port) = share_port {
-                        let peer_id_str = peer_id.to_string();
-                        info!("Incoming stream from {} - checking approval...", peer_id_str);
-                        
-                        // Check if auto-approve is enabled
-                        let approved = if auto_approve {
-                            info!("Auto-approving connection from {}", peer_id_str);
-                            true
-                        } else {
-                            // Request approval from UI
-                            let (approval_tx, approval_rx) = oneshot::channel();
-                            self.pending_approvals.insert(peer_id_str.clone(), approval_tx);
-                            
-                            let _ = event_tx.send(DaemonEvent::IncomingConnectionRequest {
-                                peer_id: peer_id_str.clone(),
+    /// Host a shared port is served from: its `--target`, or this machine
+    fn target_host(&self, port: u16) -> &str {
+        self.share_targets.get(&port).map(String::as_str).unwrap_or(LOCALHOST)
+    }
+
+    /// Check the resource limits before taking on a new tunnelled connection
+    ///
+    /// Returns None, and warns the UI now and then, when the connection must be shed.
+    fn admit_connection(&self, buffer_bytes: u64) -> Option<BufferLease> {
+        match self.resources.admit(self.tasks.live(), buffer_bytes) {
+            Ok(lease) => Some(lease),
+            Err(overload) => {
+                warn!("Refusing new connection: {}", overload);
+                if self.resources.should_warn() {
+                    let _ = self.event_tx.try_send(DaemonEvent::ResourceWarning {
+                        message: format!("Refusing new connections: {}", overload),
+                    });
+                }
+                None
+            }
+        }
+    }
+
+    /// Close the tunnelled connections idle for longer than `tunnel_idle_timeout_secs`
+    async fn close_idle_connections(&mut self) {
+        let timeout = self.config.tunnel_idle_timeout_secs;
+        if timeout == 0 {
+            return;
+        }
+        for (connection_id, peer_id, port, idle_secs) in self.connections.close_idle(timeout, unix_now()) {
+            info!("Connection #{} from {} on port {} timed out after {}s idle", connection_id, peer_id, port, idle_secs);
+            self.audit_log.record(&peer_id, "connection_timed_out", &format!("#{} on port {} after {}s idle", connection_id, port, idle_secs));
+            let _ = self
+                .event_tx
+                .send(DaemonEvent::ConnectionTimedOut { connection_id, peer_id, port, idle_secs })
+                .await;
+        }
+    }
+
+    /// Register a tunnelled connection to a peer in the connection table
+    fn open_connection(&self, peer_id: PeerId, session: &'static str, port: u16) -> ConnectionGuard {
+        let path = self.peer_paths.get(&peer_id).copied().unwrap_or(ConnectionPath::Direct);
+        self.connections
+            .open(peer_id, session, port, path)
+            .with_middlewares(self.middlewares.clone())
+            .with_events(self.connection_tx.clone())
+    }
+
+    /// Per-connection detail for `ListConnections` and status snapshots
+    fn connection_records(&self) -> Vec<ConnectionRecord> {
+        self.connections.records(|peer| {
+            peer.parse::<PeerId>()
+                .ok()
+                .and_then(|peer_id| self.peer_paths.get(&peer_id).copied())
+        })
+    }
+
+    /// The port a tunnel request asks for, resolving a service name; the refusal if the name is unknown
+    fn requested_port(&self, peer_id: &PeerId, request: &TunnelRequest) -> std::result::Result<u16, String> {
+        let Some(service) = &request.service else {
+            return Ok(request.port);
+        };
+        self.config.service_names.get(service).copied().ok_or_else(|| {
+            warn!("Refusing tunnel from {}: no service named {}", peer_id, service);
+            let reason = format!("no service named {}", service);
+            self.audit_log.record(&peer_id.to_string(), "port_refused", &reason);
+            reason
+        })
+    }
+
+    /// Refuse a tunnel, telling the peer why; striped streams have no answer and are just dropped
+    fn reject_tunnel(&self, peer_id: PeerId, streams: TunnelStreams, reason: impl Into<String>) {
+        let TunnelStreams::Single(stream) = streams else {
+            return;
+        };
+        let response = TunnelResponse::rejected(reason.into());
+        self.tasks.spawn(async move {
+            if let Err(e) = answer_tunnel_request(stream, &response).await {
+                debug!("Failed to send tunnel rejection to {}: {}", peer_id, e);
+            }
+        });
+    }
+
+    /// Refuse the tunnels held while a peer awaited approval
+    fn reject_pending(&mut self, peer_id: PeerId, reason: &str) {
+        if let Some(pending) = self.pending_approvals.remove(&peer_id) {
+            for (_, streams) in pending.streams {
+                self.reject_tunnel(peer_id, streams, reason);
+            }
+        }
+    }
+
+    /// Bridge a tunnelled connection to the port it asked for, or hold it until the peer is approved
+    fn accept_tunnel(&mut self, peer_id: PeerId, streams: TunnelStreams, requested: u16, share_ports: &[u16], auto_approve: bool) {
+        if share_ports.is_empty() {
+            warn!("Received stream but no share session active");
+            self.reject_tunnel(peer_id, streams, "nothing is shared");
+            return;
+        }
+        let Some(port) = share_target(requested, share_ports) else {
+            warn!("Refusing tunnel from {} to port {}: not shared", peer_id, requested);
+            let reason = format!("port {} not shared", requested);
+            self.audit_log.record(&peer_id.to_string(), "port_refused", &reason);
+            self.reject_tunnel(peer_id, streams, reason);
+            return;
+        };
+
+        let policy = self.config.port_policy(port);
+        if policy.is_some_and(|policy| !policy.allows(&peer_id)) {
+            warn!("Refusing tunnel from {} to port {}: not on its allowlist", peer_id, port);
+            let reason = format!("port {} is not open to this peer", port);
+            self.audit_log.record(&peer_id.to_string(), "port_refused", &reason);
+            self.reject_tunnel(peer_id, streams, reason);
+            return;
+        }
+        let approval = policy.map(|policy| policy.approval).unwrap_or_default();
+
+        let preauthorized = self.config.peer_access.decide(&peer_id) == PeerAccessDecision::Allowed;
+        if !preauthorized && let Some(reason) = self.link_refusal(&peer_id) {
+            warn!("Refusing tunnel from {}: {}", peer_id, reason);
+            self.audit_log.record(&peer_id.to_string(), "link_refused", &reason);
+            let _ = self.event_tx.try_send(DaemonEvent::StreamRejected {
+                peer_id: peer_id.to_string(),
+                protocol: RiftProtocol::Tunnel.to_string(),
+            });
+            self.reject_tunnel(peer_id, streams, reason);
+            return;
+        }
+
+        let approved = preauthorized || self.approved_peers.contains(&peer_id);
+        let port_approved = self.approved_ports.contains(&(peer_id, port));
+        match admission(approval, auto_approve, approved, port_approved) {
+            Admission::Bridge => self.bridge_tunnel(peer_id, streams, port),
+            Admission::GraceOrAsk if self.reconnected_within_grace(&peer_id) => {
+                info!("Peer {} reconnected within the grace window - approved", peer_id);
+                self.audit_log.record(&peer_id.to_string(), "reconnect_approved", "within grace window");
+                let _ = self.event_tx.try_send(DaemonEvent::ReconnectApproved {
+                    peer_id: peer_id.to_string(),
+                });
+                self.approve_peer(peer_id, false);
+                self.bridge_tunnel(peer_id, streams, port);
+            }
+            _ => self.hold_for_approval(peer_id, streams, port),
+        }
+    }
+
+    /// Hold a tunnel until the user answers the approval prompt for its peer
+    fn hold_for_approval(&mut self, peer_id: PeerId, streams: TunnelStreams, port: u16) {
+        if let Some(pending) = self.pending_approvals.get_mut(&peer_id) {
+            // Already waiting on the user; hold this stream too, up to the cap
+            if pending.streams.len() < self.config.history.pending_streams {
+                pending.streams.push((port, streams));
+            } else {
+                debug!("Dropping stream from {}: too many awaiting approval", peer_id);
+                self.reject_tunnel(peer_id, streams, "too many connections awaiting approval");
+            }
+        } else {
+            info!("Incoming stream from {} to port {} - waiting for approval", peer_id, port);
+            self.pending_approvals.insert(peer_id, PendingApproval {
+                requested_at: Instant::now(),
+                streams: vec![(port, streams)],
+            });
+            let _ = self.event_tx.try_send(DaemonEvent::IncomingConnectionRequest {
+                peer_id: peer_id.to_string(),
+            });
+        }
+    }
+
+    fn bridge_tunnel(&self, peer_id: PeerId, streams: TunnelStreams, port: u16) {
+        if let Some(policy) = self.config.port_policy(port) {
+            if policy.max_connections > 0 && self.connections.open_on_port("share", port) >= policy.max_connections {
+                warn!("Refusing tunnel from {} to port {}: {} connections open already", peer_id, port, policy.max_connections);
+                self.reject_tunnel(peer_id, streams, format!("port {} has its maximum of {} connections open", port, policy.max_connections));
+                return;
+            }
+            // Striped sets are not filtered, so read-only ports only take single streams
+            if policy.read_only && matches!(streams, TunnelStreams::Striped(_)) {
+                warn!("Refusing striped tunnel from {} to read-only port {}", peer_id, port);
+                return;
+            }
+        }
+        match streams {
+            TunnelStreams::Single(stream) => self.spawn_bridge(peer_id, stream, port),
+            TunnelStreams::Striped(streams) => self.spawn_striped_bridge(peer_id, streams, port),
+        }
+    }
+
+    /// Check (and consume) a recent approval of a peer that dropped
+    fn reconnected_within_grace(&mut self, peer_id: &PeerId) -> bool {
+        let grace = Duration::from_secs(self.config.reconnect_grace_secs);
+        self.recently_approved
+            .remove(peer_id)
+            .is_some_and(|dropped_at| !grace.is_zero() && dropped_at.elapsed() <= grace)
+    }
+
+    /// Mark a peer approved and release any streams held for it
+    ///
+    /// Only the user's own answer (`by_user`) approves ports that require it;
+    /// a session token or the grace window leaves their streams held.
+    fn approve_peer(&mut self, peer_id: PeerId, by_user: bool) {
+        self.approved_peers.insert(peer_id);
+        let Some(mut pending) = self.pending_approvals.remove(&peer_id) else {
+            return;
+        };
+        let mut held = Vec::new();
+        for (port, streams) in pending.streams.drain(..) {
+            if self.requires_approval(port) {
+                if !by_user {
+                    held.push((port, streams));
+                    continue;
+                }
+                self.approved_ports.insert((peer_id, port));
+            }
+            self.bridge_tunnel(peer_id, streams, port);
+        }
+        if !held.is_empty() {
+            pending.streams = held;
+            self.pending_approvals.insert(peer_id, pending);
+        }
+    }
+
+    /// Whether a shared port has `approval = "require"`
+    fn requires_approval(&self, port: u16) -> bool {
+        self.config.port_policy(port).is_some_and(|policy| policy.approval == PortApproval::Require)
+    }
+
+    /// Issue a session token to a freshly approved peer over a control stream
+    fn issue_session_token(&mut self, peer_id: PeerId, control: &Streams) {
+        let ttl = self.config.session_token_ttl_secs;
+        if ttl == 0 {
+            return;
+        }
+
+        let token = self.session_tokens.issue(&peer_id.to_string(), ttl);
+        let message = ControlMessage::SessionToken {
+            token: token.token,
+            expires_in_secs: ttl,
+        };
+        let control = control.clone();
+        self.tasks.spawn(async move {
+            if let Err(e) = send_control_message(control, peer_id, &message).await {
+                debug!("Failed to send session token to {}: {}", peer_id, e);
+            }
+        });
+    }
+
+    /// Record the link ID a peer presented, if this host issued it and it is still valid
+    fn redeem_link(&mut self, peer_id: PeerId, link_id: String) {
+        let status = self.links.status(&link_id, unix_now());
+        if status != LinkStatus::Valid {
+            warn!("Peer {} presented a {} link", peer_id, status);
+            self.audit_log.record(&peer_id.to_string(), "link_refused", &format!("link {} {}", link_id, status));
+            return;
+        }
+        if self.link_grants.get(&peer_id) != Some(&link_id) {
+            info!("Peer {} presented link {}", peer_id, link_id);
+            self.audit_log.record(&peer_id.to_string(), "link_redeemed", &link_id);
+            if self.links.get(&link_id).is_some_and(|link| link.guest) {
+                self.guest_peers.insert(peer_id, link_id.clone());
+            }
+            self.link_grants.insert(peer_id, link_id);
+        }
+    }
+
+    /// Cut off peers whose guest link expired or was revoked
+    ///
+    /// Their tunnels close with the connection; approvals, the grace window and
+    /// session tokens go too, so coming back means a new link and a new prompt.
+    fn expire_guests(&mut self, network: &mut PeerNetwork) {
+        let now = unix_now();
+        let expired: Vec<(PeerId, String)> = self
+            .guest_peers
+            .iter()
+            .filter(|(_, link_id)| self.links.status(link_id, now) != LinkStatus::Valid)
+            .map(|(peer_id, link_id)| (*peer_id, link_id.clone()))
+            .collect();
+
+        for (peer_id, link_id) in expired {
+            info!("Guest access of {} ended (link {})", peer_id, link_id);
+            self.guest_peers.remove(&peer_id);
+            self.link_grants.remove(&peer_id);
+            self.approved_peers.remove(&peer_id);
+            self.approved_ports.retain(|(peer, _)| *peer != peer_id);
+            self.recently_approved.remove(&peer_id);
+            self.pending_approvals.remove(&peer_id);
+            let tokens = self.session_tokens.revoke(Some(&peer_id.to_string()));
+            if network.is_connected(&peer_id) {
+                network.disconnect_peer(peer_id);
+            }
+            self.audit_log.record(
+                &peer_id.to_string(),
+                "guest_expired",
+                &format!("link {}, {} session token(s) revoked", link_id, tokens),
+            );
+            let _ = self.event_tx.try_send(DaemonEvent::GuestExpired {
+                peer_id: peer_id.to_string(),
+            });
+        }
+    }
+
+    /// Why a peer may not open tunnels under `links.require`, if it may not
+    ///
+    /// Checked at every stream, so a revocation applies to the next connection.
+    fn link_refusal(&self, peer_id: &PeerId) -> Option<String> {
+        if !self.config.links.require && !self.require_link {
+            return None;
+        }
+        let Some(link_id) = self.link_grants.get(peer_id) else {
+            return Some("no link presented".to_string());
+        };
+        match self.links.status(link_id, unix_now()) {
+            LinkStatus::Valid => None,
+            status => Some(format!("link {} {}", link_id, status)),
+        }
+    }
+
+    /// Check an incoming stream against the peer lists and the protocol policy
+    ///
+    /// Rejected streams are audited and should be dropped by the caller.
+    fn admit_stream(&self, peer_id: PeerId, protocol: RiftProtocol) -> bool {
+        if self.config.peer_access.decide(&peer_id) == PeerAccessDecision::Blocked {
+            self.audit_log.record(&peer_id.to_string(), "peer_blocked", &format!("{} stream refused", protocol));
+            let _ = self.event_tx.try_send(DaemonEvent::PeerBlocked {
+                peer_id: peer_id.to_string(),
+            });
+            return false;
+        }
+
+        let reason = if !self.config.protocol_policy.is_allowed(&peer_id, protocol) {
+            format!("protocol {} not allowed", protocol)
+        } else if self.config.reject_ephemeral_peers && self.ephemeral_peers.contains(&peer_id) {
+            // Best effort: the marker is self-declared and may arrive after the first streams
+            "ephemeral identities not accepted".to_string()
+        } else {
+            return true;
+        };
+
+        self.audit_log.record(&peer_id.to_string(), "stream_rejected", &reason);
+        let _ = self.event_tx.try_send(DaemonEvent::StreamRejected {
+            peer_id: peer_id.to_string(),
+            protocol: protocol.to_string(),
+        });
+        false
+    }
+
+    /// Tell an approved same-machine peer it may skip QUIC for the shared ports
+    ///
+    /// One offer per port; the peer takes the one for the port it connects to.
+    fn offer_fastpath(&self, peer_id: PeerId, share_ports: &[u16], control: &Streams) {
+        if share_ports.is_empty() || !self.local_peers.contains(&peer_id) {
+            return;
+        }
+
+        // Ports served by another machine are not on the peer's loopback, and
+        // ports with a policy need every connection to pass through the tunnel
+        let ports: Vec<u16> = share_ports
+            .iter()
+            .copied()
+            .filter(|port| !self.share_targets.contains_key(port) && self.config.port_policy(*port).is_none())
+            .collect();
+        if ports.is_empty() {
+            return;
+        }
+        info!("Offering loopback fast path to {}", peer_id);
+        let control = control.clone();
+        self.tasks.spawn(async move {
+            for port in ports {
+                let message = ControlMessage::FastpathOffer { port };
+                if let Err(e) = send_control_message(control.clone(), peer_id, &message).await {
+                    debug!("Failed to offer fast path to {}: {}", peer_id, e);
+                }
+            }
+        });
+    }
+
+    /// Record a probe of the shared service and pass it on to session peers (host side)
+    fn publish_health(&mut self, health: ServiceHealth, auto_approve: bool, control: &Streams) {
+        if self.service_health.as_ref().map(|known| known.status) != Some(health.status) {
+            info!("Shared service is {}", health);
+            let _ = self.event_tx.try_send(DaemonEvent::ServiceHealth {
+                health: health.clone(),
+                paused: false,
+            });
+        }
+        self.service_health = Some(health.clone());
+
+        let peers: Vec<PeerId> = if auto_approve {
+            self.peer_paths.keys().copied().collect()
+        } else {
+            self.approved_peers.iter().copied().collect()
+        };
+        for peer_id in peers {
+            let message = ControlMessage::Health { health: health.clone() };
+            let control = control.clone();
+            self.tasks.spawn(async move {
+                if let Err(e) = send_control_message(control, peer_id, &message).await {
+                    debug!("Failed to send service health to {}: {}", peer_id, e);
+                }
+            });
+        }
+    }
+
+    /// Take the host's report on its shared service (connect side)
+    fn receive_health(&mut self, health: ServiceHealth) {
+        let changed = self.service_health.as_ref().map(|known| known.status) != Some(health.status);
+        self.service_health = Some(health.clone());
+        if !changed {
+            return;
+        }
+        let paused = self.accept_paused();
+        if paused {
+            warn!("Host reports its service {}; not accepting local connections", health);
+        } else {
+            info!("Host reports its service {}", health);
+        }
+        let _ = self.event_tx.try_send(DaemonEvent::ServiceHealth { health, paused });
+    }
+
+    /// Whether the local listener waits for the host's service to come back
+    fn accept_paused(&self) -> bool {
+        self.config.health.pause_when_down
+            && self.service_health.as_ref().is_some_and(|health| health.status == HealthStatus::Down)
+    }
+
+    /// Whether a peer may write to the shared key-value state
+    fn kv_trusted(&self, peer_id: &PeerId, host: Option<PeerId>, auto_approve: bool) -> bool {
+        auto_approve
+            || host == Some(*peer_id)
+            || self.approved_peers.contains(peer_id)
+            || self.recently_approved.contains_key(peer_id)
+    }
+
+    /// Exchange full key-value state with a peer; its answer comes back through `kv_tx`
+    fn sync_kv(
+        &self,
+        peer_id: PeerId,
+        control: &Streams,
+        kv_tx: &mpsc::Sender<(PeerId, KvMessage, Option<libp2p::Stream>)>,
+    ) {
+        let message = KvMessage::Sync { entries: self.kv.entries() };
+        let control = control.clone();
+        let kv_tx = kv_tx.clone();
+        self.tasks.spawn(async move {
+            match send_kv_message(control, peer_id, &message).await {
+                Ok(Some(reply)) => {
+                    let _ = kv_tx.send((peer_id, reply, None)).await;
+                }
+                Ok(None) => {}
+                Err(e) => debug!("Key-value sync with {} failed: {}", peer_id, e),
+            }
+        });
+    }
+
+    /// Send the secrets request queued for a host that just connected, if any
+    fn request_queued_secrets(&mut self, peer_id: PeerId, control: &Streams) {
+        let host = peer_id.to_string();
+        let Some(request) = self.secrets_queue.take(&host) else {
+            return;
+        };
+        info!("{} is back; sending the queued secrets request", peer_id);
+        let mut control = control.clone();
+        let config = self.config.clone();
+        let state = self.state.clone();
+        let events = self.event_tx.clone();
+        self.tasks.spawn(async move {
+            let result: Result<usize> = async {
+                let vault = EnvVault::with_keypair(EnvVault::load_identity_for(&config)?);
+                let previous = vault.load_received(state.as_ref(), &host);
+                let stream = control
+                    .control
+                    .open_stream(peer_id, control.protocols.secrets.clone())
+                    .await
+                    .map_err(|e| wh_core::RiftError::StreamError(format!("Failed to open secrets stream: {:?}", e)))?;
+                let (_, received) = fetch_secrets(stream.compat(), &vault, previous.as_ref()).await?;
+                if let Err(e) = vault.save_received(state.as_ref(), &host, &received) {
+                    warn!("Failed to keep the received secrets for next time: {}", e);
+                }
+                write_env_file(&config.spool(), &vault, &received, &request.save_path)
+            }
+            .await;
+            let save_path = request.save_path.display().to_string();
+            let event = match result {
+                Ok(count) => {
+                    info!("Queued secrets request answered by {}: {} secret(s) written to {}", host, count, save_path);
+                    DaemonEvent::QueuedSecretsDelivered { peer_id: host, count, save_path }
+                }
+                Err(e) => {
+                    warn!("Queued secrets request to {} failed: {}", host, e);
+                    DaemonEvent::QueuedSecretsFailed { peer_id: host, reason: e.to_string() }
+                }
+            };
+            let _ = events.send(event).await;
+        });
+    }
+
+    /// Merge entries from a peer, announcing and returning the ones that won
+    fn merge_kv(&mut self, entries: Vec<KvEntry>) -> Vec<KvEntry> {
+        let mut changed = Vec::new();
+        for entry in entries {
+            if let Some(change) = self.kv.apply(entry.clone()) {
+                debug!("Key '{}' changed by {}", change.key, change.origin);
+                let _ = self.event_tx.try_send(DaemonEvent::KvChanged {
+                    key: change.key,
+                    value: change.value,
+                    peer_id: change.origin,
+                });
+                changed.push(entry);
+            }
+        }
+        changed
+    }
+
+    /// Replicate changed entries to every session peer except the one they came from
+    fn publish_kv(
+        &self,
+        entries: Vec<KvEntry>,
+        from: Option<PeerId>,
+        host: Option<PeerId>,
+        auto_approve: bool,
+        control: &Streams,
+    ) {
+        if entries.is_empty() {
+            return;
+        }
+        let mut peers: Vec<PeerId> = if auto_approve {
+            self.peer_paths.keys().copied().collect()
+        } else {
+            self.approved_peers.iter().copied().collect()
+        };
+        peers.extend(host.filter(|host| !peers.contains(host)));
+
+        for peer_id in peers.into_iter().filter(|peer_id| Some(*peer_id) != from) {
+            let message = KvMessage::Update { entries: entries.clone() };
+            let control = control.clone();
+            self.tasks.spawn(async move {
+                if let Err(e) = send_kv_message(control, peer_id, &message).await {
+                    debug!("Failed to send key-value update to {}: {}", peer_id, e);
+                }
+            });
+        }
+    }
+
+    /// The connected peers, as reported to the dashboard and viewers
+    async fn peer_records(network: &mut PeerNetwork, peer_paths: &HashMap<PeerId, ConnectionPath>) -> Vec<PeerRecord> {
+        network
+            .list_peers()
+            .await
+            .into_iter()
+            .map(|peer| PeerRecord {
+                peer_id: peer.peer_id.to_string(),
+                nickname: network.peer_nickname(&peer.peer_id),
+                addresses: peer.addresses.iter().map(ToString::to_string).collect(),
+                connected_secs: peer.connected_at.elapsed().as_secs(),
+                direction: peer.direction,
+                // The daemon also knows about loopback fast paths and relay fallbacks
+                path: peer_paths.get(&peer.peer_id).copied().unwrap_or(peer.path),
+                rtt_ms: peer.rtt.map(|rtt| rtt.average.as_millis() as u64),
+                capabilities: peer.capabilities,
+            })
+            .collect()
+    }
+
+    /// Publish a status snapshot for `rift status`
+    fn write_status(&self, share_ports: &[u16], connect_port: Option<u16>) {
+        if let Err(e) = self.status_snapshot(share_ports, connect_port).write(self.state.as_ref()) {
+            debug!("Failed to write status snapshot: {}", e);
+        }
+    }
+
+    /// Current status of this daemon
+    fn status_snapshot(&self, share_ports: &[u16], connect_port: Option<u16>) -> StatusSnapshot {
+        let (mode, port) = match (share_ports.first().copied(), connect_port) {
+            (Some(port), _) => ("share", Some(port)),
+            (None, Some(port)) => ("connect", Some(port)),
+            (None, None) => ("idle", None),
+        };
+        let stats = &self.traffic_stats;
+        let (session_bytes_sent, session_bytes_received) = stats.session_bytes();
+        StatusSnapshot {
+            pid: std::process::id(),
+            mode: mode.to_string(),
+            peer_id: self.peer_id.clone(),
+            port,
+            shared_ports: share_ports.to_vec(),
+            bytes_sent: stats.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: stats.bytes_received.load(Ordering::Relaxed),
+            session_bytes_sent,
+            session_bytes_received,
+            active_connections: stats.active_connections.load(Ordering::Relaxed),
+            live_tasks: self.tasks.live(),
+            connections: self.connection_records(),
+            traffic_classes: self.connections.traffic_classes(),
+            ports: self.connections.port_stats(),
+            peer_traffic: self.connections.peer_stats(),
+            stream_open: stats.stream_open_latency.summary(),
+            target_connect: stats.target_connect_latency.summary(),
+            updated_at: 0,
+        }
+        .touch()
+    }
+
+    /// Serve the control API on TCP for `rift --remote`, if configured
+    async fn start_remote_control(&mut self) {
+        let Some(listen) = &self.config.remote_control.listen else {
+            return;
+        };
+        let started = async {
+            let token = remote::load_or_generate_token(&self.config.remote_control, &self.config.state_dir())?;
+            let listener = tokio::net::TcpListener::bind(listen).await?;
+            Ok::<_, wh_core::RiftError>((token, listener))
+        };
+        match started.await {
+            Ok((token, listener)) => {
+                info!("Remote control listening on {}", listen);
+                // Token and commands travel in plain text
+                if listener.local_addr().is_ok_and(|addr| !addr.ip().is_loopback()) {
+                    let message = format!(
+                        "Remote control on {} is unencrypted: reach it through an SSH tunnel or VPN, or listen on 127.0.0.1",
+                        listen
+                    );
+                    warn!("{}", message);
+                    let _ = self.event_tx.try_send(DaemonEvent::Error { message });
+                }
+                self.tasks.spawn(remote::serve(listener, token, self.command_tx.clone(), self.audit_log.clone()));
+            }
+            Err(e) => {
+                warn!("Remote control not available on {}: {}", listen, e);
+                let _ = self.event_tx.try_send(DaemonEvent::Error {
+                    message: format!("Remote control not available on {}: {}", listen, e),
+                });
+            }
+        }
+    }
+
+    /// Drop approval requests nobody answered in time
+    fn expire_pending_approvals(&mut self) {
+        let expired: Vec<PeerId> = self
+            .pending_approvals
+            .iter()
+            .filter(|(_, pending)| pending.requested_at.elapsed() > APPROVAL_TIMEOUT)
+            .map(|(peer_id, _)| *peer_id)
+            .collect();
+
+        for peer_id in expired {
+            warn!("Approval timeout for {}", peer_id);
+            self.reject_pending(peer_id, "the host did not answer the approval request in time");
+        }
+    }
+
+    /// Remember the keys a peer gets and release them to the waiting request
+    fn share_secrets(&mut self, peer_id: PeerId, keys: Vec<String>, withheld: Vec<String>, reply: oneshot::Sender<Vec<String>>) {
+        self.secret_scopes.grant(&peer_id.to_string(), keys.iter().cloned());
+        if !withheld.is_empty() {
+            info!("Withholding {} new secret(s) from {}", withheld.len(), peer_id);
+        }
+        let _ = self.event_tx.try_send(DaemonEvent::SecretsShared {
+            peer_id: peer_id.to_string(),
+            count: keys.len(),
+            withheld,
+        });
+        let _ = reply.send(keys);
+    }
+
+    /// Answer secrets requests nobody decided on with the remembered scope
+    fn expire_pending_secrets(&mut self) {
+        let expired: Vec<PeerId> = self
+            .pending_secrets
+            .iter()
+            .filter(|(_, pending)| pending.requested_at.elapsed() > APPROVAL_TIMEOUT)
+            .map(|(peer_id, _)| *peer_id)
+            .collect();
+
+        for peer_id in expired {
+            if let Some(pending) = self.pending_secrets.remove(&peer_id) {
+                debug!("No answer on new secrets for {}, sharing the remembered ones", peer_id);
+                self.share_secrets(peer_id, pending.scoped.shared, pending.scoped.new, pending.reply);
+            }
+        }
+    }
+
+    /// Run the daemon main loop
+    pub async fn run(&mut self) -> Result<()> {
+        // Take ownership of network for the run loop
+        let mut network = self.network.take().expect("Network should be available");
+        let mut network_rx = network.take_event_receiver();
+        let mut incoming_streams = network.take_incoming_streams();
+        let mut incoming_secrets_streams = network.take_incoming_secrets_streams();
+        let mut incoming_control_streams = network.take_incoming_control_streams();
+        let mut incoming_stripe_streams = network.take_incoming_stripe_streams();
+        let mut incoming_echo_streams = network.take_incoming_echo_streams();
+        let mut incoming_services_streams = network.take_incoming_services_streams();
+        let (control_tx, mut control_rx) = mpsc::channel::<(PeerId, ControlMessage)>(64);
+        let (stripe_tx, mut stripe_rx) = mpsc::channel::<(PeerId, StripeHeader, libp2p::Stream)>(64);
+        let mut incoming_kv_streams = network.take_incoming_kv_streams();
+        let (kv_tx, mut kv_rx) = mpsc::channel::<(PeerId, KvMessage, Option<libp2p::Stream>)>(64);
+        // Control streams kept open by `rift attach`, and the session they are shown
+        let (attach_tx, mut attach_rx) = mpsc::channel::<(PeerId, libp2p::Stream)>(8);
+        let (views, _) = watch::channel::<Option<SessionView>>(None);
+        let event_tx = self.event_tx.clone();
+        let traffic_stats = self.traffic_stats.clone();
+        let tasks = self.tasks.clone();
+        self.start_remote_control().await;
+
+        // Ports of the share session; tunnel streams name the one they want
+        let mut share_ports: Vec<u16> = Vec::new();
+        let (tunnel_tx, mut tunnel_rx) = mpsc::channel::<(PeerId, TunnelRequest, libp2p::Stream)>(64);
+        
+        // Track auto-approve setting
+        let mut auto_approve = false;
+        
+        // Track secrets to share
+        let mut share_secrets: Option<EnvVault> = None;
+        
+        // Track connect session info
+        let mut connect_info: Option<(PeerId, u16, TcpListener)> = None;
+        // Listeners of further host ports, by the host port they forward to
+        let mut connect_forwards: Vec<(u16, TcpListener)> = Vec::new();
+        // ID of the revocable link we connected with, presented on every connection to the host
+        let mut connect_link_id: Option<String> = None;
+        // Link of the host, dialed again when the connection to it drops
+        let mut connect_link: Option<String> = None;
+        // Service name sent with every tunnel stream to the host, if we connected by name
+        let mut connect_service: Option<String> = None;
+        let mut reconnect: Option<Reconnect> = None;
+        // Connect command still looking for the host, and the links resolved for it
+        let mut connecting: Option<ConnectAttempt> = None;
+        let (resolved_tx, mut resolved_rx) = mpsc::channel::<(String, Result<String>)>(4);
+        let stream_control = Streams {
+            control: network.stream_control(),
+            protocols: network.protocols().clone(),
+        };
+
+        // Our own signed claims, presented to hosts we connect to
+        let attestation = self.config.attestation.clone().and_then(|claims| {
+            claims
+                .sign(network.identity().keypair())
+                .map_err(|e| warn!("Not presenting attestation: {}", e))
+                .ok()
+        });
+        
+        // Stats update timer - send stats every 100ms for smooth graph updates
+        let mut stats_interval = tokio::time::interval(tokio::time::Duration::from_millis(100));
+        stats_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+        if let Some(sampler) = self.resources.memory_sampler() {
+            self.tasks.spawn(sampler);
+        }
+
+        // Housekeeping timer - approval timeouts, token expiry
+        let mut housekeeping_interval = tokio::time::interval(Duration::from_secs(1));
+        housekeeping_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+        // Service health probes (host side)
+        let health_enabled = self.config.health.interval_secs > 0;
+        let mut health_interval = tokio::time::interval(Duration::from_secs(self.config.health.interval_secs.max(1)));
+        health_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        let (health_tx, mut health_rx) = mpsc::channel::<ServiceHealth>(4);
+
+        // Sleep shows up as a clock jump between housekeeping ticks
+        let mut wake = WakeDetector::new(Duration::from_secs(self.config.wake_recovery.jump_threshold_secs));
+        let (wake_tx, mut wake_rx) = mpsc::channel::<(PeerId, bool)>(16);
+
+        // Config file polling for hot reload
+        let mut config_interval = tokio::time::interval(tokio::time::Duration::from_secs(2));
+        config_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+        // Main event loop
+        while self.running {
+            tokio::select! {
+                // Periodic stats update
+                _ = stats_interval.tick() => {
+                    let (session_bytes_sent, session_bytes_received) = traffic_stats.session_bytes();
+                    let _ = event_tx.send(DaemonEvent::StatsUpdate {
+                        bytes_sent: traffic_stats.bytes_sent.load(Ordering::Relaxed),
+                        bytes_received: traffic_stats.bytes_received.load(Ordering::Relaxed),
+                        session_bytes_sent,
+                        session_bytes_received,
+                        active_connections: traffic_stats.active_connections.load(Ordering::Relaxed),
+                        live_tasks: tasks.live(),
+                        traffic_classes: self.connections.traffic_classes(),
+                        ports: self.connections.port_stats(),
+                        peers: self.connections.peer_stats(),
+                    }).await;
+                }
+                _ = housekeeping_interval.tick() => {
+                    self.connections.sample_rates(Instant::now());
+                    self.close_idle_connections().await;
+                    self.expire_pending_approvals();
+                    self.expire_pending_secrets();
+                    self.expire_guests(&mut network);
+                    self.secret_scopes.reload_if_changed();
+                    self.session_tokens.prune(unix_now());
+                    if self.links.reload_if_changed() {
+                        debug!("Reloaded links from {}", LINKS_FILE);
+                    }
+                    self.stripe_sets.prune();
+                    self.rate_limiters.prune();
+                    if let Some(overload) = self.resources.memory_overload()
+                        && self.resources.should_warn()
+                    {
+                        warn!("Resource limit reached: {}", overload);
+                        let _ = event_tx.send(DaemonEvent::ResourceWarning {
+                            message: format!("Refusing new connections: {}", overload),
+                        }).await;
+                    }
+                    let grace = Duration::from_secs(self.config.reconnect_grace_secs);
+                    self.recently_approved.retain(|_, dropped_at| dropped_at.elapsed() <= grace);
+                    self.write_status(&share_ports, connect_info.as_ref().map(|(_, port, _)| *port));
+                    if views.receiver_count() > 0 {
+                        let view = SessionView {
+                            link: self.link.clone(),
+                            snapshot: self.status_snapshot(&share_ports, connect_info.as_ref().map(|(_, port, _)| *port)),
+                            peers: Self::peer_records(&mut network, &self.peer_paths).await,
+                        };
+                        views.send_replace(Some(view));
+                    }
+
+                    if let Some(asleep) = wake.check()
+                        && self.config.wake_recovery.enabled
+                    {
+                        let peers = network.recover_after_wake();
+                        info!("Woke after {:?} asleep, checking {} peer(s)", asleep, peers.len());
+                        let _ = event_tx.send(DaemonEvent::WokeFromSleep {
+                            asleep_secs: asleep.as_secs(),
+                            peers: peers.len(),
+                        }).await;
+                        let wake_config = &self.config.wake_recovery;
+                        let timeout = Duration::from_millis(wake_config.ping_timeout_ms);
+                        for peer_id in peers {
+                            let control = stream_control.clone();
+                            let wake_tx = wake_tx.clone();
+                            let count = wake_config.ping_burst;
+                            tasks.spawn(async move {
+                                let alive = ping_burst(control.control, control.protocols, peer_id, count, timeout).await;
+                                let _ = wake_tx.send((peer_id, alive)).await;
+                            });
+                        }
+                        // A host that dropped during sleep is worth dialing right away
+                        if let Some(state) = reconnect.as_mut() {
+                            state.retry_now(Instant::now());
+                        }
+                    }
+                }
+                // Drop connections that died while asleep; the host is then re-dialed
+                Some((peer_id, alive)) = wake_rx.recv() => {
+                    if !alive && network.is_connected(&peer_id) {
+                        warn!("Connection to {} did not survive sleep, dropping it", peer_id);
+                        network.disconnect_peer(peer_id);
+                        let _ = event_tx.send(DaemonEvent::PeerLostInSleep { peer_id: peer_id.to_string() }).await;
+                    }
+                }
+                // Probe the shared service for the peers' health signal
+                _ = health_interval.tick(), if health_enabled => {
+                    // The first shared port stands for the service
+                    if let Some(port) = share_ports.first().copied() {
+                        let health_tx = health_tx.clone();
+                        let config = self.config.health.clone();
+                        let host = self.target_host(port).to_string();
+                        tasks.spawn(async move {
+                            let _ = health_tx.send(probe(&host, port, &config).await).await;
+                        });
+                    }
+                }
+                Some(health) = health_rx.recv() => {
+                    self.publish_health(health, auto_approve, &stream_control);
+                }
+                Some(event) = self.connection_rx.recv() => {
+                    let _ = event_tx.send(event.into()).await;
+                }
+                // Check the watched config file for changes
+                _ = config_interval.tick(), if self.config_watch.is_some() => {
+                    if let Some((path, last_modified)) = &mut self.config_watch {
+                        let modified = Self::config_modified(path);
+                        if modified != *last_modified {
+                            *last_modified = modified;
+                            self.reload_config().await;
+                        }
+                    }
+                }
+                // Handle commands
+                Some(command) = self.command_rx.recv() => {
+                    match command {
+                        DaemonCommand::Share { mut ports, secrets_path, auto_approve: auto_approve_flag, require_link, targets } => {
+                            info!("Share command received for port(s) {:?} (auto_approve={})", ports, auto_approve_flag);
+                            self.share_targets.clear();
+                            let policy = self.config.share_target_policy();
+                            for (host, port) in targets {
+                                let target = ExitRequest { host, port };
+                                if !policy.allows(&target) || ports.contains(&port) {
+                                    warn!("Not forwarding port {} to {}: not in share_targets or already shared", port, target);
+                                    let _ = event_tx.send(DaemonEvent::Error {
+                                        message: format!("Target {} is not allowed (add it to share_targets) or its port is already shared", target),
+                                    }).await;
+                                    continue;
+                                }
+                                info!("Forwarding port {} to {}", port, target);
+                                ports.push(port);
+                                self.share_targets.insert(port, target.host);
+                            }
+                            share_ports = ports;
+                            auto_approve = auto_approve_flag;
+                            self.require_link = require_link;
+                            traffic_stats.reset_session();
+                            if self.config.announce_services
+                                && let Err(e) = network.announce_services(shared_services(&self.config, &share_ports))
+                            {
+                                warn!("Not announcing shared services: {}", e);
+                            }
+                            
+                            // Load secrets if provided
+                            if let Some(path) = secrets_path {
+                                match EnvVault::from_file_for(&path, &self.config) {
+                                    Ok(vault) => {
+                                        info!("Loaded secrets from {} (version {})", path.display(), vault.version().short());
+                                        share_secrets = Some(vault);
+                                    }
+                                    Err(e) => {
+                                        error!("Failed to load secrets from {}: {}", path.display(), e);
+                                        let _ = event_tx.send(DaemonEvent::Error {
+                                            message: format!("Failed to load secrets: {}", e),
+                                        }).await;
+                                    }
+                                }
+                            }
+                        }
+                        DaemonCommand::Connect { link, port, local_port, bind_addr, service, forwards } => {
+                            info!("Connect command received for {} port {}", link, port);
+                            connect_service = service;
+                            traffic_stats.reset_session();
+                            let attempt = ConnectAttempt::new(link.clone(), port, local_port, bind_addr).with_forwards(forwards);
+                            if let Some(previous) = connecting.replace(attempt) {
+                                info!("Dropping the connect attempt to {} for the new one", previous.link);
+                            }
+                            // Resolved in a task, so a slow DNS lookup does not hold up the loop
+                            let resolving = network.resolve_link(&link);
+                            let resolved_tx = resolved_tx.clone();
+                            tasks.spawn(async move {
+                                let _ = resolved_tx.send((link, resolving.await)).await;
+                            });
+                        }
+                        DaemonCommand::CancelConnect => match connecting.take() {
+                            Some(attempt) => {
+                                info!("Cancelled connecting to {} after {} attempt(s)", attempt.link, attempt.attempts());
+                                let _ = event_tx.send(DaemonEvent::ConnectCancelled {
+                                    peer_id: attempt.peer_label(),
+                                    attempts: attempt.attempts(),
+                                }).await;
+                            }
+                            None => debug!("No connect attempt to cancel"),
+                        },
+                        DaemonCommand::ApproveConnection { peer_id } => {
+                            info!("Approving connection from {}", peer_id);
+                            match peer_id.parse::<PeerId>() {
+                                Ok(peer_id) => {
+                                    self.approve_peer(peer_id, true);
+                                    self.issue_session_token(peer_id, &stream_control);
+                                    self.offer_fastpath(peer_id, &share_ports, &stream_control);
+                                    self.sync_kv(peer_id, &stream_control, &kv_tx);
+                                }
+                                Err(e) => warn!("Ignoring approval for invalid peer ID {}: {}", peer_id, e),
+                            }
+                        }
+                        DaemonCommand::ApproveSecrets { peer_id, include_new } => {
+                            let pending = peer_id.parse::<PeerId>().ok()
+                                .and_then(|peer_id| self.pending_secrets.remove(&peer_id).map(|pending| (peer_id, pending)));
+                            if let Some((peer_id, pending)) = pending {
+                                let ScopedKeys { mut shared, new } = pending.scoped;
+                                let withheld = if include_new {
+                                    shared.extend(new);
+                                    Vec::new()
+                                } else {
+                                    new
+                                };
+                                self.share_secrets(peer_id, shared, withheld, pending.reply);
+                            }
+                        }
+                        DaemonCommand::QueueSecretsRequest { peer_id, save_path } => {
+                            info!("Queueing a secrets request for {} until it connects", peer_id);
+                            self.secrets_queue.queue(&peer_id, save_path.clone());
+                            let _ = event_tx.send(DaemonEvent::SecretsRequestQueued {
+                                peer_id,
+                                save_path: save_path.display().to_string(),
+                            }).await;
+                        }
+                        DaemonCommand::DenySecrets { peer_id } => {
+                            info!("Denying secrets request from {}", peer_id);
+                            if let Ok(peer_id) = peer_id.parse::<PeerId>() {
+                                self.pending_secrets.remove(&peer_id);
+                            }
+                        }
+                        DaemonCommand::DenyConnection { peer_id } => {
+                            info!("Denying connection from {}", peer_id);
+                            if let Ok(peer_id) = peer_id.parse::<PeerId>() {
+                                self.reject_pending(peer_id, "not approved by the host");
+                            }
+                        }
+                        DaemonCommand::RevokeSessionTokens { peer_id } => {
+                            let revoked = self.session_tokens.revoke(peer_id.as_deref());
+                            info!("Revoked {} session token(s) for {}", revoked, peer_id.as_deref().unwrap_or("all peers"));
+                        }
+                        DaemonCommand::ListConnections { reply } => {
+                            let _ = reply.send(self.connection_records());
+                        }
+                        DaemonCommand::CloseConnection { connection_id } => match self.connections.close(connection_id) {
+                            Some((peer_id, port)) => {
+                                info!("Closing connection #{} from {} on port {}", connection_id, peer_id, port);
+                                self.audit_log.record(&peer_id, "connection_closed", &format!("#{} on port {}", connection_id, port));
+                                let _ = self
+                                    .event_tx
+                                    .send(DaemonEvent::ConnectionTerminated { connection_id, peer_id, port })
+                                    .await;
+                            }
+                            None => {
+                                let _ = self
+                                    .event_tx
+                                    .send(DaemonEvent::Error { message: format!("No open connection #{}", connection_id) })
+                                    .await;
+                            }
+                        },
+                        DaemonCommand::ListPeers => {
+                            let peers = Self::peer_records(&mut network, &self.peer_paths).await;
+                            let _ = event_tx.send(DaemonEvent::PeerList { peers }).await;
+                        }
+                        DaemonCommand::Status { reply } => {
+                            let _ = reply.send(self.status_snapshot(&share_ports, connect_info.as_ref().map(|(_, port, _)| *port)));
+                        }
+                        DaemonCommand::ReplayRequest { request } => {
+                            // Replays go through our own listener so they are bridged and logged like any request
+                            match connect_info.as_ref().map(|(_, _, listener)| listener.local_addr()) {
+                                Some(Ok(mut addr)) => {
+                                    if addr.ip().is_unspecified() {
+                                        addr.set_ip(std::net::Ipv4Addr::LOCALHOST.into());
+                                    }
+                                    tasks.spawn(replay_request(addr, request));
+                                }
+                                _ => warn!("Ignoring replay request: no connect session"),
+                            }
+                        }
+                        DaemonCommand::KvSet { key, value } => {
+                            match self.kv.set(key, value) {
+                                Ok(entry) => {
+                                    let host = connect_info.as_ref().map(|(host, _, _)| *host);
+                                    let _ = event_tx.send(DaemonEvent::KvChanged {
+                                        key: entry.key.clone(),
+                                        value: entry.value.clone(),
+                                        peer_id: self.peer_id.clone(),
+                                    }).await;
+                                    self.publish_kv(vec![entry], None, host, auto_approve, &stream_control);
+                                }
+                                Err(e) => warn!("Not setting key: {}", e),
+                            }
+                        }
+                        DaemonCommand::KvDelete { key } => {
+                            match self.kv.delete(&key) {
+                                Ok(Some(entry)) => {
+                                    let host = connect_info.as_ref().map(|(host, _, _)| *host);
+                                    let _ = event_tx.send(DaemonEvent::KvChanged {
+                                        key,
+                                        value: None,
+                                        peer_id: self.peer_id.clone(),
+                                    }).await;
+                                    self.publish_kv(vec![entry], None, host, auto_approve, &stream_control);
+                                }
+                                Ok(None) => debug!("No key '{}' to delete", key),
+                                Err(e) => warn!("Not deleting key: {}", e),
+                            }
+                        }
+                        DaemonCommand::KvList { reply } => {
+                            let _ = reply.send(self.kv.values());
+                        }
+                        DaemonCommand::ResetStats => {
+                            info!("Session traffic counters reset");
+                            traffic_stats.reset_session();
+                        }
+                        DaemonCommand::Shutdown => {
+                            info!("Shutdown command received");
+                            self.running = false;
+                        }
+                        DaemonCommand::StopSession { .. } => {}
+                        DaemonCommand::ReloadConfig => {
+                            self.reload_config().await;
+                        }
+                    }
+                }
+
+                // Handle incoming streams (host side - share): read the requested port first
+                Some((peer_id, stream)) = incoming_streams.next() => {
+                    if !self.admit_stream(peer_id, RiftProtocol::Tunnel) {
+                        continue;
+                    }
+                    let tunnel_tx = tunnel_tx.clone();
+                    tasks.spawn(async move {
+                        match read_tunnel_request(stream).await {
+                            Ok((request, stream)) => {
+                                let _ = tunnel_tx.send((peer_id, request, stream)).await;
+                            }
+                            Err(e) => debug!("Failed to read tunnel request from {}: {}", peer_id, e),
+                        }
+                    });
+                }
+                Some((peer_id, request, stream)) = tunnel_rx.recv() => {
+                    let port = match self.requested_port(&peer_id, &request) {
+                        Ok(port) => port,
+                        Err(reason) => {
+                            self.reject_tunnel(peer_id, TunnelStreams::Single(stream), reason);
+                            continue;
+                        }
+                    };
+                    self.accept_tunnel(peer_id, TunnelStreams::Single(stream), port, &share_ports, auto_approve);
+                }
+
+                // Handle incoming striped streams: read each header, then group them
+                Some((peer_id, stream)) = incoming_stripe_streams.next() => {
+                    if !self.admit_stream(peer_id, RiftProtocol::Tunnel) {
+                        continue;
+                    }
+                    let stripe_tx = stripe_tx.clone();
+                    tasks.spawn(async move {
+                        let mut stream = stream.compat();
+                        match receive_secrets::<StripeHeader, _>(&mut stream).await {
+                            Ok(header) => {
+                                let _ = stripe_tx.send((peer_id, header, stream.into_inner())).await;
+                            }
+                            Err(e) => debug!("Failed to read stripe header from {}: {}", peer_id, e),
+                        }
+                    });
+                }
+                Some((peer_id, header, stream)) = stripe_rx.recv() => {
+                    if let Some(streams) = self.stripe_sets.add((peer_id, header.session), header, stream) {
+                        self.accept_tunnel(peer_id, TunnelStreams::Striped(streams), header.port, &share_ports, auto_approve);
+                    }
+                }
+
+                // Handle incoming echo streams (`rift ping` from a peer)
+                Some((peer_id, stream)) = incoming_echo_streams.next() => {
+                    if !self.admit_stream(peer_id, RiftProtocol::Echo) {
+                        continue;
+                    }
+                    tasks.spawn(async move {
+                        let (read, mut write) = tokio::io::split(stream.compat());
+                        match serve_echo(read, &mut write).await {
+                            Ok(bytes) => debug!("Echoed {} bytes to {}", bytes, peer_id),
+                            Err(e) => debug!("Echo stream from {} ended: {}", peer_id, e),
+                        }
+                    });
+                }
+
+                // Handle services queries (`rift connect --list` from a peer)
+                Some((peer_id, stream)) = incoming_services_streams.next() => {
+                    if !self.admit_stream(peer_id, RiftProtocol::Services) {
+                        continue;
+                    }
+                    // Nothing is listed while not sharing, or with announcements turned off
+                    let services = if self.config.announce_services {
+                        shared_services(&self.config, &share_ports)
+                    } else {
+                        Vec::new()
+                    };
+                    tasks.spawn(async move {
+                        if let Err(e) = serve_services(&mut stream.compat(), &services).await {
+                            debug!("Failed to answer services query from {}: {}", peer_id, e);
+                        }
+                    });
+                }
+
+                // Handle incoming control streams (session tokens and resumption)
+                Some((peer_id, stream)) = incoming_control_streams.next() => {
+                    if !self.admit_stream(peer_id, RiftProtocol::Control) {
+                        continue;
+                    }
+                    let control_tx = control_tx.clone();
+                    let attach_tx = attach_tx.clone();
+                    tasks.spawn(async move {
+                        let mut stream = stream.compat();
+                        match receive_secrets::<ControlMessage, _>(&mut stream).await {
+                            Ok(ControlMessage::Attach) => {
+                                let _ = attach_tx.send((peer_id, stream.into_inner())).await;
+                            }
+                            Ok(message) => {
+                                let _ = control_tx.send((peer_id, message)).await;
+                            }
+                            Err(e) => debug!("Failed to read control message from {}: {}", peer_id, e),
+                        }
+                    });
+                }
+
+                // Handle incoming key-value streams (replicated app state)
+                Some((peer_id, stream)) = incoming_kv_streams.next() => {
+                    if !self.admit_stream(peer_id, RiftProtocol::Kv) {
+                        continue;
+                    }
+                    let kv_tx = kv_tx.clone();
+                    tasks.spawn(async move {
+                        let mut stream = stream.compat();
+                        match receive_secrets::<KvMessage, _>(&mut stream).await {
+                            Ok(message) => {
+                                let _ = kv_tx.send((peer_id, message, Some(stream.into_inner()))).await;
+                            }
+                            Err(e) => debug!("Failed to read key-value message from {}: {}", peer_id, e),
+                        }
+                    });
+                }
+
+                Some((peer_id, message, stream)) = kv_rx.recv() => {
+                    let host = connect_info.as_ref().map(|(host, _, _)| *host);
+                    if !self.kv_trusted(&peer_id, host, auto_approve) {
+                        debug!("Ignoring key-value state from unapproved peer {}", peer_id);
+                        continue;
+                    }
+                    let entries = match message {
+                        KvMessage::Sync { entries } => {
+                            // Answer with our own state so both sides converge
+                            if let Some(stream) = stream {
+                                let reply = KvMessage::Update { entries: self.kv.entries() };
+                                tasks.spawn(async move {
+                                    let mut stream = stream.compat();
+                                    if let Err(e) = send_secrets(&mut stream, &reply).await {
+                                        debug!("Failed to answer key-value sync from {}: {}", peer_id, e);
+                                    }
+                                    let _ = tokio::io::AsyncWriteExt::shutdown(&mut stream).await;
+                                });
+                            }
+                            entries
+                        }
+                        KvMessage::Update { entries } => entries,
+                    };
+                    let changed = self.merge_kv(entries);
+                    self.publish_kv(changed, Some(peer_id), host, auto_approve, &stream_control);
+                }
+
+                Some((peer_id, stream)) = attach_rx.recv() => {
+                    if self.config.viewers.contains(&peer_id.to_string()) {
+                        info!("Viewer {} attached", peer_id);
+                        self.audit_log.record(&peer_id.to_string(), "viewer_attached", "read-only dashboard");
+                        let _ = event_tx.send(DaemonEvent::ViewerAttached { peer_id: peer_id.to_string() }).await;
+                        let views = views.subscribe();
+                        tasks.spawn(async move {
+                            let mut stream = stream.compat();
+                            if let Err(e) = serve_viewer(&mut stream, views).await {
+                                debug!("Viewer {} left: {}", peer_id, e);
+                            }
+                        });
+                    } else {
+                        self.audit_log.record(&peer_id.to_string(), "attach_refused", "not in viewers");
+                        tasks.spawn(async move {
+                            let mut stream = stream.compat();
+                            let refusal = ViewerUpdate::Refused { reason: "not allowed to view this daemon".to_string() };
+                            let _ = send_secrets(&mut stream, &refusal).await;
+                            let _ = tokio::io::AsyncWriteExt::shutdown(&mut stream).await;
+                        });
+                    }
+                }
+
+                Some((peer_id, message)) = control_rx.recv() => {
+                    match message {
+                        ControlMessage::Resume { token } => {
+                            if self.session_tokens.validate(&peer_id.to_string(), &token) {
+                                info!("Peer {} resumed an approved session", peer_id);
+                                self.approve_peer(peer_id, false);
+                                self.offer_fastpath(peer_id, &share_ports, &stream_control);
+                                self.sync_kv(peer_id, &stream_control, &kv_tx);
+                                let _ = event_tx.send(DaemonEvent::SessionResumed {
+                                    peer_id: peer_id.to_string(),
+                                }).await;
+                            } else {
+                                warn!("Rejected invalid or expired session token from {}", peer_id);
+                            }
+                        }
+                        ControlMessage::Redeem { link_id } => {
+                            self.redeem_link(peer_id, link_id);
+                        }
+                        ControlMessage::SessionToken { token, expires_in_secs } => {
+                            debug!("Received session token from {} (ttl {}s)", peer_id, expires_in_secs);
+                            let expires_at = Instant::now() + Duration::from_secs(expires_in_secs);
+                            self.resume_tokens.insert(peer_id, (token, expires_at));
+                        }
+                        ControlMessage::Attestation { attestation } => {
+                            let valid = attestation.verify(&peer_id);
+                            if valid {
+                                info!("Peer {} claims to be {}", peer_id, attestation.claims);
+                            } else {
+                                warn!("Peer {} sent an attestation with a bad signature", peer_id);
+                            }
+                            let _ = event_tx.send(DaemonEvent::PeerAttested {
+                                peer_id: peer_id.to_string(),
+                                claims: attestation.claims.to_string(),
+                                valid,
                             }).await;
-                            
-                            // Wait for approval (with timeout)
-                            match tokio::time::timeout(
-                                std::time::Duration::from_secs(30),
-                                approval_rx
-                            ).await {
-                                Ok(Ok(approved)) => approved,
-                                Ok(Err(_)) => {
-                                    warn!("Approval channel closed for {}", peer_id_str);
-                                    false
-                                }
-                                Err(_) => {
-                                    warn!("Approval timeout for {}", peer_id_str);
-                                    self.pending_approvals.remove(&peer_id_str);
-                                    false
+                        }
+                        ControlMessage::FastpathProbe { fingerprint } => {
+                            #[cfg(feature = "local-fastpath")]
+                            if fingerprint == wh_core::network::fastpath::machine_fingerprint() {
+                                debug!("Peer {} runs on this machine", peer_id);
+                                self.local_peers.insert(peer_id);
+                                if auto_approve || self.approved_peers.contains(&peer_id) {
+                                    self.offer_fastpath(peer_id, &share_ports, &stream_control);
                                 }
                             }
-                        };
-                        
-                        if approved {
-                            info!("Connection approved - bridging to localhost:{}", port);
-                            let stats = traffic_stats.clone();
-                            // Spawn a task to bridge this stream to localhost:port with traffic tracking
-                            tokio::spawn(async move {
-                                stats.active_connections.fetch_add(1, Ordering::Relaxed);
-                                match bridge_with_stats(stream, port, stats.clone()).await {
-                                    Ok((sent, recv)) => {
-                                        debug!("Stream from {} closed. Sent: {}, Recv: {}", peer_id, sent, recv);
-                                    }
-                                    Err(e) => {
-                                        warn!("Stream bridge ended: {}", e);
-                                    }
-                                }
-                                stats.active_connections.fetch_sub(1, Ordering::Relaxed);
-                            });
-                        } else {
-                            info!("Connection denied from {}", peer_id_str);
-                            drop(stream);
+                            #[cfg(not(feature = "local-fastpath"))]
+                            debug!("Ignoring fast path probe from {} ({} bytes)", peer_id, fingerprint.len());
+                        }
+                        ControlMessage::Health { health } => {
+                            if connect_info.as_ref().is_some_and(|(host, _, _)| *host == peer_id) {
+                                self.receive_health(health);
+                            }
+                        }
+                        // Handed to `attach_rx` along with its stream
+                        ControlMessage::Attach => {}
+                        ControlMessage::FastpathOffer { port } => {
+                            // Only take offers for the port we asked for, from the peer we connected to
+                            let expected = connect_info
+                                .as_ref()
+                                .is_some_and(|(host, remote_port, _)| *host == peer_id && *remote_port == port);
+                            // The fast path bypasses the stream bridge, so HTTP logging would miss it
+                            if cfg!(feature = "local-fastpath") && expected && self.access_log.is_none() {
+                                info!("Using loopback fast path to localhost:{}", port);
+                                self.fastpath_port = Some(port);
+                            }
                         }
-                    } else {
-                        warn!("Received stream but no share session active");
                     }
                 }
 
                 // Handle incoming secrets requests (host side - share with secrets)
                 Some((peer_id, stream)) = incoming_secrets_streams.next() => {
+                    if !self.admit_stream(peer_id, RiftProtocol::Secrets) {
+                        continue;
+                    }
                     if let Some(ref vault) = share_secrets {
                         info!("Incoming secrets request from {}", peer_id);
                         let vault = vault.clone();
-                        
-                        tokio::spawn(async move {
+                        let scoped = self.secret_scopes.resolve(&peer_id.to_string(), vault.secrets().keys());
+                        let (reply_tx, reply_rx) = oneshot::channel::<Vec<String>>();
+                        if scoped.new.is_empty() || auto_approve {
+                            self.share_secrets(peer_id, scoped.shared, scoped.new, reply_tx);
+                        } else {
+                            let _ = event_tx.send(DaemonEvent::SecretsApprovalRequest {
+                                peer_id: peer_id.to_string(),
+                                keys: scoped.shared.clone(),
+                                new_keys: scoped.new.clone(),
+                            }).await;
+                            self.pending_secrets.insert(peer_id, PendingSecrets {
+                                requested_at: Instant::now(),
+                                scoped,
+                                reply: reply_tx,
+                            });
+                        }
+
+                        tasks.spawn(async move {
                             let stream = stream.compat();
                             let (mut read, mut write) = tokio::io::split(stream);
                             
@@ -433,9 +2257,15 @@ impl DaemonServer {
                                     return;
                                 }
                             };
+
+                            // 2. Wait for the keys this peer gets (remembered scope or the host's answer)
+                            let Ok(keys) = reply_rx.await else {
+                                info!("Secrets request from {} refused", peer_id);
+                                return;
+                            };
                             
-                            // 2. Encrypt secrets for the requester's public key
-                            let response = match vault.encrypt_for_peer(&request.public_key) {
+                            // 3. Encrypt those the requester does not hold yet for its public key
+                            let response = match vault.scoped(keys.iter().map(String::as_str)).encrypt_changes_for_peer(&request.public_key, &request.known) {
                                 Ok(resp) => resp,
                                 Err(e) => {
                                     error!("Failed to encrypt secrets: {}", e);
@@ -443,13 +2273,18 @@ impl DaemonServer {
                                 }
                             };
                             
-                            // 3. Send SecretsResponse
+                            // 4. Send SecretsResponse
                             if let Err(e) = send_secrets(&mut write, &response).await {
                                 error!("Failed to send response: {}", e);
                                 return;
                             }
                             
-                            info!("Secrets sent to {}", peer_id);
+                            info!(
+                                "{} secrets sent to {} ({} unchanged)",
+                                keys.len().saturating_sub(response.unchanged.len()),
+                                peer_id,
+                                response.unchanged.len()
+                            );
                         });
                     } else {
                         warn!("Received secrets request but no secrets configured");
@@ -457,93 +2292,467 @@ impl DaemonServer {
                 }
 
                 // Handle incoming TCP connections (client side - connect)  
-                result = async {
-                    if let Some((_, _, ref listener)) = connect_info {
-                        listener.accept().await
+                (remote_port, result) = async {
+                    if let Some((_, port, ref listener)) = connect_info {
+                        let forwards = connect_forwards.iter().map(|(port, listener)| (*port, listener));
+                        accept_any(std::iter::once((port, listener)).chain(forwards)).await
                     } else {
                         // No listener, pend forever
                         std::future::pending().await
                     }
-                } => {
-                    if let Ok((tcp_stream, addr)) = result {
-                        if let Some((peer_id, _remote_port, _)) = &connect_info {
-                            info!("Incoming TCP connection from {} - opening stream to peer", addr);
-                            let peer_id = *peer_id;
-                            let mut control = stream_control.clone();
-                            let stats = traffic_stats.clone();
-                            
-                            tokio::spawn(async move {
-                                stats.active_connections.fetch_add(1, Ordering::Relaxed);
-                                match open_tunnel_stream(&mut control, peer_id).await {
-                                    Ok(stream) => {
-                                        // Convert futures AsyncRead/Write to tokio
-                                        let stream = stream.compat();
-                                        let (mut stream_read, mut stream_write) = tokio::io::split(stream);
-                                        let (mut tcp_read, mut tcp_write) = tcp_stream.into_split();
-                                        
-                                        // Bidirectional copy with stats tracking
-                                        let stats_clone = stats.clone();
-                                        tokio::select! {
-                                            _r = async {
-                                                let mut buf = [0u8; 8192];
-                                                let mut total = 0u64;
-                                                loop {
-                                                    match tokio::io::AsyncReadExt::read(&mut stream_read, &mut buf).await {
-                                                        Ok(0) => break,
-                                                        Ok(n) => {
-                                                            if let Err(e) = tokio::io::AsyncWriteExt::write_all(&mut tcp_write, &buf[..n]).await {
-                                                                debug!("Stream->TCP write error: {}", e);
-                                                                break;
-                                                            }
-                                                            total += n as u64;
-                                                            stats_clone.bytes_received.fetch_add(n as u64, Ordering::Relaxed);
+                }, if !self.accept_paused() && reconnect.is_none() => {
+                    let buffer_bytes = if self.config.striping.streams > 1 {
+                        striped_buffer_bytes(self.config.striping.streams.into(), self.config.striping.chunk_size)
+                    } else {
+                        BRIDGE_BUFFER_BYTES
+                    };
+                    // Dropping the accepted socket sheds the connection
+                    let accepted = match result {
+                        Ok(accepted) => self.admit_connection(buffer_bytes).map(|lease| (accepted, lease)),
+                        Err(_) => None,
+                    };
+                    if let Some(((tcp_stream, _), _)) = &accepted {
+                        apply_keepalive(tcp_stream, &self.config.socket_options);
+                    }
+                    // The fast path is only offered for the port of the link
+                    if let Some(port) = self.fastpath_port.filter(|port| *port == remote_port)
+                        && let Some(((tcp_stream, addr), lease)) = accepted
+                    {
+                        debug!("Incoming TCP connection from {} - using loopback fast path", addr);
+                        let stats = traffic_stats.clone();
+                        let host = connect_info.as_ref().map(|(peer_id, _, _)| peer_id.to_string()).unwrap_or_default();
+                        let conn = self.connections.open(host, "connect", port, ConnectionPath::Loopback).with_events(self.connection_tx.clone());
+                        tasks.spawn(async move {
+                            let _lease = lease;
+                            bridge_loopback(tcp_stream, port, stats, conn).await;
+                        });
+                    } else if let Some((peer_id, port, _)) = &connect_info
+                        && let Some(((tcp_stream, addr), lease)) = accepted
+                    {
+                        info!("Incoming TCP connection from {} - opening stream to peer port {}", addr, remote_port);
+                        let peer_id = *peer_id;
+                        // A service name stands for the port of the link only
+                        let service = connect_service.clone().filter(|_| remote_port == *port);
+                        let request = TunnelRequest::new(remote_port).with_service(service);
+                        let mut control = stream_control.clone();
+                        let stats = traffic_stats.clone();
+                        let accepted_at = Instant::now();
+                        let striping = self.config.striping.clone();
+                        let migration = self.config.migration.clone();
+                        let events = self.event_tx.clone();
+                        let conn = self.open_connection(peer_id, "connect", remote_port);
+                        let http = self.access_log.clone().map(|log| HttpTap {
+                            peer_id: peer_id.to_string(),
+                            log,
+                            observer: StdMutex::new(HttpObserver::capturing(self.config.history.inspect_body_bytes)),
+                            events: self.event_tx.clone(),
+                        });
+                        
+                        tasks.spawn(async move {
+                            let _lease = lease;
+                            stats.active_connections.fetch_add(1, Ordering::Relaxed);
+                            // Striped transfers are not followed by the HTTP observer
+                            if striping.streams > 1 && http.is_none() {
+                                match open_stripe_streams(&mut control.control, &control.protocols, peer_id, remote_port, striping.streams).await {
+                                    Ok(streams) => {
+                                        conn.run(bridge_striped(tcp_stream, streams, striping.chunk_size, stats.clone(), &conn, Some(accepted_at), None, None)).await;
+                                        debug!("Striped tunnel connection to {} closed", peer_id);
+                                        stats.active_connections.fetch_sub(1, Ordering::Relaxed);
+                                        return;
+                                    }
+                                    Err(e) => debug!("Striping unavailable ({}), using a single stream", e),
+                                }
+                            }
+                            match open_tunnel_stream_with(&mut control.control, &control.protocols, peer_id, &request).await {
+                                Ok(stream) if migration.enabled => {
+                                    let reopen = || {
+                                        let mut control = control.clone();
+                                        let request = request.clone();
+                                        async move { open_tunnel_stream_with(&mut control.control, &control.protocols, peer_id, &request).await.ok().map(|s| s.compat()) }
+                                    };
+                                    let mut first_byte = true;
+                                    conn.run(bridge_migrating(
+                                        tcp_stream,
+                                        stream.compat(),
+                                        reopen,
+                                        &migration,
+                                        |data| {
+                                            stats.bytes_sent.fetch_add(data.len() as u64, Ordering::Relaxed);
+                                            conn.sniff(data);
+                                            conn.record_sent(data.len() as u64);
+                                            if let Some(http) = &http {
+                                                http.request(data);
+                                            }
+                                        },
+                                        |data| {
+                                            if std::mem::take(&mut first_byte) {
+                                                stats.stream_open_latency.record(accepted_at.elapsed());
+                                            }
+                                            stats.bytes_received.fetch_add(data.len() as u64, Ordering::Relaxed);
+                                            conn.sniff(data);
+                                            conn.record_received(data.len() as u64);
+                                            if let Some(http) = &http {
+                                                http.response(data);
+                                            }
+                                        },
+                                        |replayed_bytes| {
+                                            info!("Moved a connection to a new stream to {} ({} bytes replayed)", peer_id, replayed_bytes);
+                                            let _ = events.try_send(DaemonEvent::ConnectionMigrated {
+                                                peer_id: peer_id.to_string(),
+                                                replayed_bytes,
+                                            });
+                                        },
+                                    ))
+                                    .await;
+                                    if let Some(http) = &http {
+                                        http.close();
+                                    }
+                                    debug!("Tunnel connection to {} closed", peer_id);
+                                }
+                                Ok(stream) => {
+                                    // Convert futures AsyncRead/Write to tokio
+                                    let stream = stream.compat();
+                                    let (mut stream_read, mut stream_write) = tokio::io::split(stream);
+                                    let (mut tcp_read, mut tcp_write) = tcp_stream.into_split();
+                                    
+                                    // Bidirectional copy with stats tracking
+                                    let stats_clone = stats.clone();
+                                    tokio::select! {
+                                        _r = async {
+                                            let mut buf = [0u8; 8192];
+                                            let mut total = 0u64;
+                                            loop {
+                                                match tokio::io::AsyncReadExt::read(&mut stream_read, &mut buf).await {
+                                                    Ok(0) => break,
+                                                    Ok(n) => {
+                                                        if total == 0 {
+                                                            stats_clone.stream_open_latency.record(accepted_at.elapsed());
                                                         }
-                                                        Err(e) => {
-                                                            debug!("Stream->TCP read error: {}", e);
+                                                        let data = conn.filter(Direction::Response, &buf[..n]);
+                                                        if let Err(e) = tokio::io::AsyncWriteExt::write_all(&mut tcp_write, &data).await {
+                                                            debug!("Stream->TCP write error: {}", e);
                                                             break;
                                                         }
+                                                        total += n as u64;
+                                                        stats_clone.bytes_received.fetch_add(data.len() as u64, Ordering::Relaxed);
+                                                        conn.sniff(&data);
+                                                        conn.record_received(data.len() as u64);
+                                                        if let Some(http) = &http {
+                                                            http.response(&data);
+                                                        }
+                                                    }
+                                                    Err(e) => {
+                                                        debug!("Stream->TCP read error: {}", e);
+                                                        break;
                                                     }
                                                 }
-                                                total
-                                            } => {}
-                                            _r = async {
-                                                let mut buf = [0u8; 8192];
-                                                let mut total = 0u64;
-                                                loop {
-                                                    match tokio::io::AsyncReadExt::read(&mut tcp_read, &mut buf).await {
-                                                        Ok(0) => break,
-                                                        Ok(n) => {
-                                                            if let Err(e) = tokio::io::AsyncWriteExt::write_all(&mut stream_write, &buf[..n]).await {
-                                                                debug!("TCP->Stream write error: {}", e);
-                                                                break;
-                                                            }
-                                                            total += n as u64;
-                                                            stats.bytes_sent.fetch_add(n as u64, Ordering::Relaxed);
-                                                        }
-                                                        Err(e) => {
-                                                            debug!("TCP->Stream read error: {}", e);
+                                            }
+                                            total
+                                        } => {}
+                                        _r = async {
+                                            let mut buf = [0u8; 8192];
+                                            let mut total = 0u64;
+                                            loop {
+                                                match tokio::io::AsyncReadExt::read(&mut tcp_read, &mut buf).await {
+                                                    Ok(0) => break,
+                                                    Ok(n) => {
+                                                        let data = conn.filter(Direction::Request, &buf[..n]);
+                                                        if let Err(e) = tokio::io::AsyncWriteExt::write_all(&mut stream_write, &data).await {
+                                                            debug!("TCP->Stream write error: {}", e);
                                                             break;
                                                         }
+                                                        total += data.len() as u64;
+                                                        stats.bytes_sent.fetch_add(data.len() as u64, Ordering::Relaxed);
+                                                        conn.sniff(&data);
+                                                        conn.record_sent(data.len() as u64);
+                                                        if let Some(http) = &http {
+                                                            http.request(&data);
+                                                        }
+                                                    }
+                                                    Err(e) => {
+                                                        debug!("TCP->Stream read error: {}", e);
+                                                        break;
                                                     }
                                                 }
-                                                total
-                                            } => {}
+                                            }
+                                            total
+                                        } => {}
+                                        _ = conn.closed() => debug!("Tunnel connection to {} closed on request", peer_id),
+                                    }
+                                    if let Some(http) = &http {
+                                        http.close();
+                                    }
+                                    debug!("Tunnel connection to {} closed", peer_id);
+                                }
+                                Err(RiftError::TunnelRejected(reason)) => {
+                                    warn!("{} refused the tunnel to port {}: {}", peer_id, remote_port, reason);
+                                    let _ = events.try_send(DaemonEvent::Error {
+                                        message: format!("Host refused the connection: {}", reason),
+                                    });
+                                }
+                                Err(e) => {
+                                    error!("Failed to open stream to peer: {}", e);
+                                }
+                            }
+                            stats.active_connections.fetch_sub(1, Ordering::Relaxed);
+                        });
+                    }
+                }
+
+                // The link of a connect command is resolved; results for a cancelled or replaced attempt are dropped
+                Some((link, resolved)) = resolved_rx.recv() => {
+                    if let Some(attempt) = connecting.as_mut().filter(|attempt| attempt.link == link) {
+                        let target = resolved.and_then(|resolved| {
+                            PeerIdentity::parse_rift_link(&resolved).map(|peer_id| (resolved, peer_id))
+                        });
+                        match target {
+                            Ok((resolved, peer_id)) => attempt.resolved(resolved, peer_id, Instant::now()),
+                            Err(e) => {
+                                error!("Failed to connect to {}: {}", link, e);
+                                let peer_id = PeerIdentity::parse_rift_link(&link)
+                                    .map(|peer_id| peer_id.to_string())
+                                    .unwrap_or_else(|_| link.clone());
+                                connecting = None;
+                                let _ = event_tx.send(DaemonEvent::TunnelFailed {
+                                    peer_id,
+                                    reason: e.to_string(),
+                                }).await;
+                            }
+                        }
+                    }
+                }
+
+                // Dial the host of a connect command; the swarm runs between attempts so discovery can find it
+                _ = async {
+                    match connecting.as_ref().and_then(ConnectAttempt::next_at) {
+                        Some(at) => tokio::time::sleep_until(at.into()).await,
+                        None => std::future::pending().await,
+                    }
+                } => {
+                    let policy = self.config.dial_retry.clone();
+                    let due = connecting.as_mut().and_then(|attempt| {
+                        let (link, peer_id) = attempt.target().map(|(link, peer_id)| (link.to_string(), peer_id))?;
+                        Some((link, peer_id, attempt.start_attempt(&policy, Instant::now())))
+                    });
+                    if let Some((link, peer_id, number)) = due {
+                        match network.connect(&link).await {
+                            Ok(peer_id) => {
+                                if let Some(attempt) = connecting.take() {
+                                    let ConnectAttempt { link, port, local_port, bind_addr, forwards, .. } = attempt;
+                                    info!("Connected to peer {}", peer_id);
+                                    // Present the link ID; its stream is opened ahead of any tunnel
+                                    connect_link_id = PeerIdentity::link_id(&link).map(str::to_string);
+                                    connect_link = Some(link.clone());
+                                    reconnect = None;
+                                    if let Some(link_id) = &connect_link_id {
+                                        let message = ControlMessage::Redeem { link_id: link_id.clone() };
+                                        let control = stream_control.clone();
+                                        tasks.spawn(async move {
+                                            if let Err(e) = send_control_message(control, peer_id, &message).await {
+                                                warn!("Failed to present link to {}: {}", peer_id, e);
+                                            }
+                                        });
+                                    }
+                                    // Start local TCP listener
+                                    let local = local_port.unwrap_or(port);
+                                    match bind_listener(format!("{}:{}", bind_addr, local), &self.config.socket_options).await {
+                                        Ok(listener) => {
+                                            info!("Local proxy listening on {}:{}", bind_addr, local);
+                                            connect_info = Some((peer_id, port, listener));
+                                            let _ = event_tx.send(DaemonEvent::TunnelEstablished {
+                                                peer_id: peer_id.to_string(),
+                                                port: local,
+                                            }).await;
+                                        }
+                                        Err(e) => {
+                                            error!("Failed to bind local port {}: {}", local, e);
+                                            let _ = event_tx.send(DaemonEvent::TunnelFailed {
+                                                peer_id: peer_id.to_string(),
+                                                reason: format!("failed to bind port {}: {}", local, e),
+                                            }).await;
                                         }
-                                        debug!("Tunnel connection to {} closed", peer_id);
                                     }
-                                    Err(e) => {
-                                        error!("Failed to open stream to peer: {}", e);
+                                    // Further ports share the connection; one that cannot be bound leaves the others up
+                                    connect_forwards.clear();
+                                    for (remote, local) in forwards {
+                                        match bind_listener(format!("{}:{}", bind_addr, local), &self.config.socket_options).await {
+                                            Ok(listener) => {
+                                                info!("Local proxy listening on {}:{} for port {}", bind_addr, local, remote);
+                                                connect_forwards.push((remote, listener));
+                                            }
+                                            Err(e) => {
+                                                error!("Failed to bind local port {}: {}", local, e);
+                                                let _ = event_tx.send(DaemonEvent::Error {
+                                                    message: format!("Not forwarding port {}: failed to bind port {}: {}", remote, local, e),
+                                                }).await;
+                                            }
+                                        }
                                     }
                                 }
-                                stats.active_connections.fetch_sub(1, Ordering::Relaxed);
-                            });
+                            }
+                            Err(e) if connecting.as_ref().is_some_and(|attempt| !attempt.exhausted(&policy)) => {
+                                if number == 1 {
+                                    info!("Waiting for peer discovery...");
+                                }
+                                let max_attempts = policy.attempts.max(1);
+                                debug!("Dial attempt {}/{} to {} failed: {}", number, max_attempts, peer_id, e);
+                                let _ = event_tx.send(DaemonEvent::DialRetrying {
+                                    peer_id: peer_id.to_string(),
+                                    attempt: number,
+                                    max_attempts,
+                                    discovery: network.discovery_mechanisms(),
+                                }).await;
+                            }
+                            Err(e) => {
+                                error!("Failed to connect to {}: {}", peer_id, e);
+                                connecting = None;
+                                let _ = event_tx.send(DaemonEvent::TunnelFailed {
+                                    peer_id: peer_id.to_string(),
+                                    reason: e.to_string(),
+                                }).await;
+                            }
+                        }
+                    }
+                }
+
+                // Re-dial a host whose connection dropped
+                _ = async {
+                    match reconnect.as_ref() {
+                        Some(state) => tokio::time::sleep_until(state.next_at().into()).await,
+                        None => std::future::pending().await,
+                    }
+                } => {
+                    let config = self.config.auto_reconnect.clone();
+                    if let Some(state) = reconnect.as_mut() {
+                        let peer_id = state.peer_id;
+                        match state.start_attempt(&config, Instant::now()) {
+                            Some(attempt) => {
+                                info!("Reconnecting to {} (attempt {})", peer_id, attempt);
+                                let dialed = match &connect_link {
+                                    Some(link) => network.connect(link).await.map(|_| ()),
+                                    None => network.dial_peer(peer_id).await,
+                                };
+                                if let Err(e) = dialed {
+                                    debug!("Reconnect attempt {} to {} failed: {}", attempt, peer_id, e);
+                                }
+                            }
+                            None => {
+                                warn!("Gave up reconnecting to {} after {} attempts", peer_id, state.attempts());
+                                let _ = event_tx.send(DaemonEvent::Error {
+                                    message: format!("Lost the connection to {} and could not reconnect", peer_id),
+                                }).await;
+                                reconnect = None;
+                            }
                         }
                     }
                 }
 
                 // Handle network events
                 Some(event) = network_rx.recv() => {
-                    Self::handle_network_event(&event_tx, event).await;
+                    match &event {
+                        NetworkEvent::PeerConnected { peer_id, path } => {
+                            self.peer_paths.insert(*peer_id, *path);
+                            if let Some(state) = reconnect.take_if(|state| state.peer_id == *peer_id) {
+                                let downtime = state.downtime(Instant::now());
+                                info!("Reconnected to {} after {:?}", peer_id, downtime);
+                                let _ = event_tx.send(DaemonEvent::TunnelReconnected {
+                                    peer_id: peer_id.to_string(),
+                                    attempts: state.attempts(),
+                                    downtime_ms: downtime.as_millis() as u64,
+                                }).await;
+                            }
+                            if connect_info.as_ref().is_some_and(|(host, _, _)| host == peer_id) {
+                                self.sync_kv(*peer_id, &stream_control, &kv_tx);
+                            }
+                            self.request_queued_secrets(*peer_id, &stream_control);
+                            if let Some(attestation) = &attestation
+                                && connect_info.as_ref().is_some_and(|(host, _, _)| host == peer_id)
+                            {
+                                let message = ControlMessage::Attestation { attestation: attestation.clone() };
+                                let control = stream_control.clone();
+                                let peer_id = *peer_id;
+                                tasks.spawn(async move {
+                                    if let Err(e) = send_control_message(control, peer_id, &message).await {
+                                        debug!("Failed to send attestation to {}: {}", peer_id, e);
+                                    }
+                                });
+                            }
+
+                            #[cfg(feature = "local-fastpath")]
+                            if connect_info.as_ref().is_some_and(|(host, _, _)| host == peer_id) {
+                                let message = ControlMessage::FastpathProbe {
+                                    fingerprint: wh_core::network::fastpath::machine_fingerprint(),
+                                };
+                                let control = stream_control.clone();
+                                let peer_id = *peer_id;
+                                tasks.spawn(async move {
+                                    let _ = send_control_message(control, peer_id, &message).await;
+                                });
+                            }
+
+                            // Present the link again after a reconnect
+                            if let Some(link_id) = &connect_link_id
+                                && connect_info.as_ref().is_some_and(|(host, _, _)| host == peer_id)
+                            {
+                                let message = ControlMessage::Redeem { link_id: link_id.clone() };
+                                let control = stream_control.clone();
+                                let peer_id = *peer_id;
+                                tasks.spawn(async move {
+                                    if let Err(e) = send_control_message(control, peer_id, &message).await {
+                                        debug!("Failed to present link to {}: {}", peer_id, e);
+                                    }
+                                });
+                            }
+
+                            // Present a still-valid session token so the host skips approval
+                            if let Some((token, expires_at)) = self.resume_tokens.get(peer_id)
+                                && *expires_at > Instant::now()
+                            {
+                                let message = ControlMessage::Resume { token: token.clone() };
+                                let control = stream_control.clone();
+                                let peer_id = *peer_id;
+                                tasks.spawn(async move {
+                                    if let Err(e) = send_control_message(control, peer_id, &message).await {
+                                        debug!("Failed to resume session with {}: {}", peer_id, e);
+                                    }
+                                });
+                            }
+                        }
+                        NetworkEvent::UsingRelay { peer_id, .. } => {
+                            self.peer_paths.insert(*peer_id, ConnectionPath::Relay);
+                        }
+                        NetworkEvent::PathUpgraded { peer_id, .. } => {
+                            self.peer_paths.insert(*peer_id, ConnectionPath::Direct);
+                        }
+                        NetworkEvent::PeerIdentified { peer_id, agent_version }
+                            if wh_core::network::is_ephemeral_agent(agent_version) =>
+                        {
+                            info!("Peer {} is using an ephemeral identity", peer_id);
+                            self.ephemeral_peers.insert(*peer_id);
+                        }
+                        NetworkEvent::PeerDisconnected { peer_id } => {
+                            // Approval lasts for the connection; reconnects need a token, the grace window or a new prompt
+                            if self.approved_peers.remove(peer_id) {
+                                self.recently_approved.insert(*peer_id, Instant::now());
+                            }
+                            self.approved_ports.retain(|(peer, _)| peer != peer_id);
+                            self.ephemeral_peers.remove(peer_id);
+                            self.link_grants.remove(peer_id);
+                            self.peer_paths.remove(peer_id);
+                            self.local_peers.remove(peer_id);
+                            if connect_info.as_ref().is_some_and(|(host, _, _)| host == peer_id) {
+                                self.fastpath_port = None;
+                                self.service_health = None;
+                                // Local clients wait in the backlog until the host is back
+                                if self.config.auto_reconnect.enabled && self.running {
+                                    info!("Connection to {} dropped, reconnecting", peer_id);
+                                    reconnect = Some(Reconnect::new(*peer_id, &self.config.auto_reconnect, Instant::now()));
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                    Self::handle_network_event(&event_tx, &mut network, event).await;
                 }
 
                 // Poll the swarm to drive progress
@@ -551,7 +2760,13 @@ impl DaemonServer {
             }
         }
 
-        // Cleanup
+        // Cleanup: stop every bridge and exchange before tearing down the network
+        let live = self.tasks.live();
+        if live > 0 {
+            info!("Cancelling {} running task(s)", live);
+        }
+        self.tasks.shutdown().await;
+        StatusSnapshot::remove(self.state.as_ref(), std::process::id());
         network.shutdown().await;
         let _ = self.event_tx.send(DaemonEvent::Shutdown).await;
         
@@ -559,7 +2774,7 @@ impl DaemonServer {
     }
 
     /// Handle a network event (static method for use in run loop)
-    async fn handle_network_event(event_tx: &mpsc::Sender<DaemonEvent>, event: NetworkEvent) {
+    async fn handle_network_event(event_tx: &EventSender<DaemonEvent>, network: &mut PeerNetwork, event: NetworkEvent) {
         match event {
             NetworkEvent::Listening { address } => {
                 let _ = event_tx
@@ -568,11 +2783,16 @@ impl DaemonServer {
                     })
                     .await;
             }
-            NetworkEvent::PeerConnected { peer_id } => {
-                info!("Peer connected: {}", peer_id);
+            NetworkEvent::PeerConnected { peer_id, .. } => {
+                let nickname = network.peer_nickname(&peer_id);
+                match &nickname {
+                    Some(nickname) => info!("Peer connected: {} ({})", peer_id, nickname),
+                    None => info!("Peer connected: {}", peer_id),
+                }
                 let _ = event_tx
                     .send(DaemonEvent::PeerConnected {
                         peer_id: peer_id.to_string(),
+                        nickname,
                     })
                     .await;
             }
@@ -584,9 +2804,95 @@ impl DaemonServer {
                     })
                     .await;
             }
+            NetworkEvent::PeerDiscovered { peer_id, addresses } => {
+                let _ = event_tx
+                    .send(DaemonEvent::PeerDiscovered {
+                        peer_id: peer_id.to_string(),
+                        addresses: addresses.iter().map(|a| a.to_string()).collect(),
+                    })
+                    .await;
+            }
+            NetworkEvent::PeerExpired { peer_id } => {
+                debug!("Peer {} left the local network", peer_id);
+            }
+            NetworkEvent::PeerIdentified { peer_id, agent_version } => {
+                debug!("Peer {} runs {}", peer_id, agent_version);
+                let services = wh_core::network::advertised_services(&agent_version);
+                if !services.is_empty() {
+                    let services: Vec<String> = services.iter().map(ToString::to_string).collect();
+                    debug!("Peer {} offers {}", peer_id, services.join(", "));
+                }
+                if let Some(capabilities) = PeerCapabilities::from_agent_version(&agent_version) {
+                    let _ = event_tx.send(DaemonEvent::PeerCapabilities {
+                        peer_id: peer_id.to_string(),
+                        capabilities,
+                    }).await;
+                }
+            }
             NetworkEvent::HolePunchSucceeded { peer_id } => {
                 info!("Hole punch succeeded with {}", peer_id);
             }
+            NetworkEvent::PathUpgraded { peer_id, relayed_rtt, direct_rtt } => {
+                let _ = event_tx.send(DaemonEvent::PathUpgraded {
+                    peer_id: peer_id.to_string(),
+                    relayed_ms: relayed_rtt.map(|rtt| rtt.as_millis() as u64),
+                    direct_ms: direct_rtt.as_millis() as u64,
+                }).await;
+            }
+            NetworkEvent::UsingRelay { peer_id, relay, rtt } => {
+                let _ = event_tx.send(DaemonEvent::UsingRelay {
+                    peer_id: peer_id.to_string(),
+                    relay: relay.to_string(),
+                    rtt_ms: rtt.map(|rtt| rtt.as_millis() as u64),
+                }).await;
+            }
+            NetworkEvent::RelayReservationAccepted { relay_peer_id, renewal } => {
+                if !renewal {
+                    let _ = event_tx.send(DaemonEvent::RelayReserved { relay_peer_id: relay_peer_id.to_string() }).await;
+                }
+            }
+            NetworkEvent::RelayReservationFailed { relay_peer_id, reason } => {
+                let _ = event_tx.send(DaemonEvent::RelayReservationEnded {
+                    relay_peer_id: relay_peer_id.to_string(),
+                    reason,
+                    lost: false,
+                }).await;
+            }
+            NetworkEvent::RelayReservationLost { relay_peer_id, reason } => {
+                let _ = event_tx.send(DaemonEvent::RelayReservationEnded {
+                    relay_peer_id: relay_peer_id.to_string(),
+                    reason,
+                    lost: true,
+                }).await;
+            }
+            NetworkEvent::RelaySelected { relay, rtt } => {
+                let _ = event_tx.send(DaemonEvent::RelaySelected {
+                    relay: relay.to_string(),
+                    rtt_ms: rtt.map(|rtt| rtt.as_millis() as u64),
+                }).await;
+            }
+            NetworkEvent::RelayCircuitOpened { relay_peer_id } => {
+                debug!("Circuit opened through relay {}", relay_peer_id);
+            }
+            NetworkEvent::RelayCircuitAccepted { src_peer_id } => {
+                debug!("Peer {} came in through a relay", src_peer_id);
+            }
+            NetworkEvent::PeerRtt { peer_id, rtt } => {
+                let _ = event_tx.send(DaemonEvent::PeerRtt {
+                    peer_id: peer_id.to_string(),
+                    last_ms: rtt.last.as_millis() as u64,
+                    average_ms: rtt.average.as_millis() as u64,
+                }).await;
+            }
+            NetworkEvent::NatTypeChanged { nat_type } => {
+                let _ = event_tx.send(DaemonEvent::NatDetected { nat_type }).await;
+            }
+            NetworkEvent::DialRetrying { .. } => {
+                // Only sent by `connect_with_retry`; the run loop schedules its own attempts
+            }
+            NetworkEvent::ServicesAnnounced { peer_id, services } => {
+                debug!("Peer {} shares {} services", peer_id, services.len());
+            }
             NetworkEvent::Error { message } => {
                 error!("Network error: {}", message);
                 let _ = event_tx.send(DaemonEvent::Error { message }).await;
@@ -595,42 +2901,396 @@ impl DaemonServer {
     }
 }
 
-/// Bridge a stream to a local TCP port with traffic stats tracking
+/// Port a tunnel asking for `requested` is bridged to, if it may be
+///
+/// A share of a single port takes every tunnel, so links without a port keep
+/// working; with several, the tunnel must name one of them.
+fn share_target(requested: u16, share_ports: &[u16]) -> Option<u16> {
+    match share_ports {
+        [port] => Some(*port),
+        ports => ports.contains(&requested).then_some(requested),
+    }
+}
+
+/// How a tunnel that passed the allowlists is let through
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Admission {
+    /// Bridge it right away
+    Bridge,
+    /// Bridge it if the peer reconnected within the grace window, else ask
+    GraceOrAsk,
+    /// Hold it until the user answers the approval prompt
+    Ask,
+}
+
+/// Decide how a tunnel to a port with the given approval policy is admitted
+///
+/// `approved` is whether the peer is approved for its connection (by the user,
+/// a session token, the grace window or `peer_access.allow`); `port_approved`
+/// whether the user approved this very port for it. Ports that require
+/// approval only go by the latter.
+fn admission(approval: PortApproval, auto_approve: bool, approved: bool, port_approved: bool) -> Admission {
+    match approval {
+        PortApproval::Require if port_approved => Admission::Bridge,
+        PortApproval::Require => Admission::Ask,
+        PortApproval::Auto => Admission::Bridge,
+        PortApproval::Session if auto_approve || approved => Admission::Bridge,
+        PortApproval::Session => Admission::GraceOrAsk,
+    }
+}
+
+/// Services announced for a share of `ports`: the ports (under their names) plus their SNI routes
+fn shared_services(config: &RiftConfig, ports: &[u16]) -> Vec<SharedService> {
+    let mut routes: Vec<SharedService> = config
+        .sni_routes
+        .iter()
+        .map(|(name, port)| SharedService {
+            name: Some(name.clone()),
+            port: *port,
+        })
+        .collect();
+    routes.sort_by(|a, b| a.name.cmp(&b.name));
+    let mut services = Vec::new();
+    for port in ports {
+        let mut names: Vec<&String> = config
+            .service_names
+            .iter()
+            .filter(|(_, named)| *named == port)
+            .map(|(name, _)| name)
+            .collect();
+        names.sort();
+        if names.is_empty() {
+            services.push(SharedService { name: None, port: *port });
+        }
+        services.extend(names.into_iter().map(|name| SharedService {
+            name: Some(name.clone()),
+            port: *port,
+        }));
+    }
+    services.extend(routes);
+    services
+}
+
+/// Send a single control message to a peer on a fresh control stream
+async fn send_control_message(
+    mut streams: Streams,
+    peer_id: PeerId,
+    message: &ControlMessage,
+) -> Result<()> {
+    let stream = streams
+        .control
+        .open_stream(peer_id, streams.protocols.control.clone())
+        .await
+        .map_err(|e| wh_core::RiftError::StreamError(format!("Failed to open control stream: {:?}", e)))?;
+
+    let mut stream = stream.compat();
+    send_secrets(&mut stream, message).await?;
+    tokio::io::AsyncWriteExt::shutdown(&mut stream).await?;
+    Ok(())
+}
+
+/// Send a key-value message on a new KV stream, returning the answer to a `Sync`
+async fn send_kv_message(
+    mut streams: Streams,
+    peer_id: PeerId,
+    message: &KvMessage,
+) -> Result<Option<KvMessage>> {
+    let stream = streams
+        .control
+        .open_stream(peer_id, streams.protocols.kv.clone())
+        .await
+        .map_err(|e| wh_core::RiftError::StreamError(format!("Failed to open key-value stream: {:?}", e)))?;
+
+    let mut stream = stream.compat();
+    send_secrets(&mut stream, message).await?;
+    let reply = match message {
+        KvMessage::Sync { .. } => Some(receive_secrets(&mut stream).await?),
+        KvMessage::Update { .. } => None,
+    };
+    tokio::io::AsyncWriteExt::shutdown(&mut stream).await?;
+    Ok(reply)
+}
+
+/// HTTP observer attached to one connect-side connection
+struct HttpTap {
+    peer_id: String,
+    log: AccessLog,
+    observer: StdMutex<HttpObserver>,
+    events: EventSender<DaemonEvent>,
+}
+
+impl HttpTap {
+    /// Bytes sent by the local client
+    fn request(&self, data: &[u8]) {
+        self.observer.lock().unwrap().on_request_bytes(data);
+    }
+
+    /// Bytes returned by the peer
+    fn response(&self, data: &[u8]) {
+        let mut observer = self.observer.lock().unwrap();
+        for exchange in observer.on_response_bytes(data) {
+            self.log.record(&self.peer_id, &exchange);
+            let _ = self.events.try_send(DaemonEvent::HttpRequest { exchange });
+        }
+        self.report_inspected(&mut observer);
+    }
+
+    /// The connection closed
+    fn close(&self) {
+        let mut observer = self.observer.lock().unwrap();
+        observer.on_close();
+        self.report_inspected(&mut observer);
+    }
+
+    fn report_inspected(&self, observer: &mut HttpObserver) {
+        for record in observer.take_inspected() {
+            let _ = self.events.try_send(DaemonEvent::HttpInspected { record });
+        }
+    }
+}
+
+/// Send a raw HTTP request to `addr` and wait for the full response
+async fn replay_request(addr: std::net::SocketAddr, request: Vec<u8>) {
+    let result = tokio::time::timeout(REPLAY_TIMEOUT, async {
+        let mut tcp = tokio::net::TcpStream::connect(addr).await?;
+        tokio::io::AsyncWriteExt::write_all(&mut tcp, &request).await?;
+
+        // Bodies are not needed here, only where the response ends
+        let mut observer = HttpObserver::capturing(0);
+        observer.on_request_bytes(&request);
+        let mut buf = [0u8; 8192];
+        loop {
+            let n = tokio::io::AsyncReadExt::read(&mut tcp, &mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            observer.on_response_bytes(&buf[..n]);
+            if !observer.take_inspected().is_empty() {
+                break;
+            }
+        }
+        Ok::<_, std::io::Error>(())
+    })
+    .await;
+
+    match result {
+        Ok(Ok(())) => debug!("Replayed request through {}", addr),
+        Ok(Err(e)) => warn!("Replaying request failed: {}", e),
+        Err(_) => warn!("Replayed request timed out"),
+    }
+}
+
+/// Connect a local client straight to the shared port on this machine
+async fn bridge_loopback(
+    mut tcp_stream: tokio::net::TcpStream,
+    port: u16,
+    stats: StdArc<TrafficStats>,
+    conn: ConnectionGuard,
+) {
+    stats.active_connections.fetch_add(1, Ordering::Relaxed);
+    match tokio::net::TcpStream::connect(("127.0.0.1", port)).await {
+        Ok(mut target) => {
+            sniff_loopback(&tcp_stream, &target, &conn).await;
+            match conn.run(tokio::io::copy_bidirectional(&mut tcp_stream, &mut target)).await {
+                Some(Ok((sent, received))) => {
+                    stats.bytes_sent.fetch_add(sent, Ordering::Relaxed);
+                    stats.bytes_received.fetch_add(received, Ordering::Relaxed);
+                    conn.record_sent(sent);
+                    conn.record_received(received);
+                }
+                Some(Err(e)) => debug!("Loopback bridge ended: {}", e),
+                None => debug!("Loopback connection to localhost:{} closed on request", port),
+            }
+        }
+        Err(e) => warn!("Fast path connect to localhost:{} failed: {}", port, e),
+    }
+    stats.active_connections.fetch_sub(1, Ordering::Relaxed);
+}
+
+/// Classify a loopback connection by peeking at whichever side speaks first
+///
+/// `copy_bidirectional` hides the bytes it relays, so they are looked at
+/// before the copy starts without being consumed.
+async fn sniff_loopback(client: &tokio::net::TcpStream, target: &tokio::net::TcpStream, conn: &ConnectionGuard) {
+    let mut client_sample = [0u8; CLASSIFY_SAMPLE_BYTES];
+    let mut target_sample = [0u8; CLASSIFY_SAMPLE_BYTES];
+    tokio::select! {
+        Ok(n) = client.peek(&mut client_sample) => conn.sniff(&client_sample[..n]),
+        Ok(n) = target.peek(&mut target_sample) => conn.sniff(&target_sample[..n]),
+        else => {}
+    }
+}
+
+/// Pump a TCP connection over a set of striped streams with traffic stats tracking
+///
+/// With `opened_at` set (connect side), the time to the first byte back is
+/// recorded as the stream open latency. With `guard` set (share side), the
+/// connection is cut once a request body exceeds the upload limit; with
+/// `limiter` set, both directions are throttled by it.
+#[allow(clippy::too_many_arguments)]
+async fn bridge_striped(
+    tcp: tokio::net::TcpStream,
+    streams: Vec<libp2p::Stream>,
+    chunk_size: usize,
+    stats: StdArc<TrafficStats>,
+    conn: &ConnectionGuard,
+    opened_at: Option<Instant>,
+    guard: Option<UploadGuard>,
+    limiter: Option<StdArc<RateLimiter>>,
+) -> (u64, u64) {
+    let (tcp_read, tcp_write) = tcp.into_split();
+    let mut tcp_read = Throttled::new(tcp_read, limiter.clone());
+    let tcp_write = Throttled::new(tcp_write, limiter);
+    let mut tcp_write: Box<dyn tokio::io::AsyncWrite + Unpin + Send> = match guard {
+        Some(guard) => Box::new(GuardedWriter::new(tcp_write, guard)),
+        None => Box::new(tcp_write),
+    };
+    let (mut readers, mut writers): (Vec<_>, Vec<_>) = streams
+        .into_iter()
+        .map(|stream| tokio::io::split(stream.compat()))
+        .unzip();
+
+    let stats_send = stats.clone();
+    let send = stripe_copy(&mut tcp_read, &mut writers, chunk_size, |chunk| {
+        stats_send.bytes_sent.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+        conn.sniff(chunk);
+        conn.record_sent(chunk.len() as u64);
+    });
+
+    let mut first = opened_at;
+    let recv = unstripe_copy(&mut readers, &mut tcp_write, |chunk| {
+        if let Some(opened_at) = first.take() {
+            stats.stream_open_latency.record(opened_at.elapsed());
+        }
+        stats.bytes_received.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+        conn.sniff(chunk);
+        conn.record_received(chunk.len() as u64);
+    });
+
+    let (sent, recv) = tokio::join!(send, recv);
+    (
+        sent.unwrap_or_else(|e| {
+            debug!("TCP->Stripe copy ended: {}", e);
+            0
+        }),
+        recv.unwrap_or_else(|e| {
+            debug!("Stripe->TCP copy ended: {}", e);
+            0
+        }),
+    )
+}
+
+/// Bridge a stream to the shared service with traffic stats tracking
+///
+/// With SNI routes configured (TLS passthrough), the ClientHello is peeked to
+/// pick the local port; unmatched server names fall back to `target`.
+/// With `limiter` set, both directions are throttled by it.
 async fn bridge_with_stats(
     stream: libp2p::Stream,
-    target_port: u16,
+    target: (&str, u16),
+    config: &RiftConfig,
+    pool: Option<&StdArc<TargetPool>>,
+    limiter: Option<StdArc<RateLimiter>>,
     stats: StdArc<TrafficStats>,
+    conn: &ConnectionGuard,
 ) -> wh_core::Result<(u64, u64)> {
-    use tokio::net::TcpStream;
     use wh_core::RiftError;
-    
-    let tcp = TcpStream::connect(format!("127.0.0.1:{}", target_port))
-        .await
-        .map_err(|e| RiftError::ProxyError(format!("Failed to connect to local port {}: {}", target_port, e)))?;
+    use wh_core::network::sni::{read_client_hello, route_sni};
 
     // Convert futures AsyncRead/Write to tokio AsyncRead/Write using compat
-    let stream = stream.compat();
-    
-    let (mut tcp_read, mut tcp_write) = tcp.into_split();
+    let mut stream = stream.compat();
+    let read_only = config.port_policy(target.1).is_some_and(|policy| policy.read_only);
+    let sni_routes = &config.sni_routes;
+    let retry = &config.target_retry;
+    // The route depends on the ClientHello, which the peer sends only once accepted
+    if !sni_routes.is_empty() {
+        send_secrets(&mut stream, &TunnelResponse::accepted(conn.id())).await?;
+    }
     let (mut stream_read, mut stream_write) = tokio::io::split(stream);
 
+    let ((host, port), prefix) = if sni_routes.is_empty() {
+        (target, Vec::new())
+    } else {
+        let (prefix, server_name) = read_client_hello(&mut stream_read).await?;
+        // SNI routes name ports on this machine
+        let routed = server_name
+            .as_deref()
+            .and_then(|name| route_sni(sni_routes, name))
+            .map(|port| (LOCALHOST, port))
+            .unwrap_or(target);
+        debug!("SNI {:?} routed to {}:{}", server_name, routed.0, routed.1);
+        (routed, prefix)
+    };
+
+    // Only connections to HTTP services on this machine are pooled
+    let connected = match pool {
+        Some(pool) if host == LOCALHOST && config.target_pool.pools(port) => pool.checkout(port, retry).await,
+        _ => connect_target(host, port, retry).await,
+    };
+    let tcp = match connected {
+        Ok(tcp) => tcp,
+        Err(e) => {
+            let reason = format!("Failed to connect to {}:{}: {}", host, port, e);
+            if sni_routes.is_empty() {
+                let _ = send_secrets(&mut stream_write, &TunnelResponse::rejected(reason.clone())).await;
+            }
+            return Err(RiftError::ProxyError(reason));
+        }
+    };
+    if sni_routes.is_empty() {
+        send_secrets(&mut stream_write, &TunnelResponse::accepted(conn.id())).await?;
+    }
+    stats.target_connect_latency.record(conn.elapsed());
+
+    let (tcp_read, tcp_write) = tcp.into_split();
+    let mut tcp_read = Throttled::new(tcp_read, limiter.clone());
+    let mut tcp_write = Throttled::new(tcp_write, limiter);
+
+    // Replay the bytes consumed while peeking
+    let prefix = conn.filter(Direction::Request, &prefix);
+    if !prefix.is_empty() && !read_only {
+        tokio::io::AsyncWriteExt::write_all(&mut tcp_write, &prefix).await?;
+        stats.bytes_received.fetch_add(prefix.len() as u64, Ordering::Relaxed);
+        conn.sniff(&prefix);
+        conn.record_received(prefix.len() as u64);
+    }
+
     let stats_send = stats.clone();
     let stats_recv = stats.clone();
+    let mut guard = (config.max_request_body_bytes > 0).then(|| UploadGuard::new(config.max_request_body_bytes));
+    let rejection = guard.as_ref().map(UploadGuard::rejection).unwrap_or_default();
+    let rejected = tokio_util::sync::CancellationToken::new();
+    let rejected_recv = rejected.clone();
 
     // Bidirectional copy with stats tracking
     let send_task = async move {
         let mut buf = [0u8; 8192];
         let mut total = 0u64;
         loop {
-            match tokio::io::AsyncReadExt::read(&mut tcp_read, &mut buf).await {
+            let read = tokio::select! {
+                biased;
+                _ = rejected.cancelled() => {
+                    // Answer for the local service, which never sees the rest of the request
+                    if let Err(e) = tokio::io::AsyncWriteExt::write_all(&mut stream_write, &rejection).await {
+                        debug!("Failed to send upload rejection: {}", e);
+                    }
+                    let _ = tokio::io::AsyncWriteExt::shutdown(&mut stream_write).await;
+                    break;
+                }
+                read = tokio::io::AsyncReadExt::read(&mut tcp_read, &mut buf) => read,
+            };
+            match read {
                 Ok(0) => break,
                 Ok(n) => {
-                    if let Err(e) = tokio::io::AsyncWriteExt::write_all(&mut stream_write, &buf[..n]).await {
+                    let data = conn.filter(Direction::Response, &buf[..n]);
+                    if let Err(e) = tokio::io::AsyncWriteExt::write_all(&mut stream_write, &data).await {
                         debug!("TCP->Stream write error: {}", e);
                         break;
                     }
-                    total += n as u64;
-                    stats_send.bytes_sent.fetch_add(n as u64, Ordering::Relaxed);
+                    total += data.len() as u64;
+                    stats_send.bytes_sent.fetch_add(data.len() as u64, Ordering::Relaxed);
+                    conn.sniff(&data);
+                    conn.record_sent(data.len() as u64);
                 }
                 Err(e) => {
                     debug!("TCP->Stream read error: {}", e);
@@ -647,13 +3307,25 @@ async fn bridge_with_stats(
         loop {
             match tokio::io::AsyncReadExt::read(&mut stream_read, &mut buf).await {
                 Ok(0) => break,
+                // What peers send to a read-only port never reaches the service
+                Ok(_) if read_only => continue,
                 Ok(n) => {
-                    if let Err(e) = tokio::io::AsyncWriteExt::write_all(&mut tcp_write, &buf[..n]).await {
+                    let data = conn.filter(Direction::Request, &buf[..n]);
+                    if let Some(guard) = &mut guard
+                        && !guard.check(&data)
+                    {
+                        warn!("Refusing upload over {} bytes", config.max_request_body_bytes);
+                        rejected_recv.cancel();
+                        break;
+                    }
+                    if let Err(e) = tokio::io::AsyncWriteExt::write_all(&mut tcp_write, &data).await {
                         debug!("Stream->TCP write error: {}", e);
                         break;
                     }
-                    total += n as u64;
-                    stats_recv.bytes_received.fetch_add(n as u64, Ordering::Relaxed);
+                    total += data.len() as u64;
+                    stats_recv.bytes_received.fetch_add(data.len() as u64, Ordering::Relaxed);
+                    conn.sniff(&data);
+                    conn.record_received(data.len() as u64);
                 }
                 Err(e) => {
                     debug!("Stream->TCP read error: {}", e);
@@ -667,3 +3339,89 @@ async fn bridge_with_stats(
     let (sent, recv) = tokio::join!(send_task, recv_task);
     Ok((sent, recv))
 }
+
+/// First outcome of the connect session to `peer_id` on a daemon event stream
+async fn tunnel_ready(mut events: EventStream<DaemonEvent>, peer_id: String) -> Result<u16> {
+    while let Some(event) = events.next().await {
+        match event {
+            DaemonEvent::TunnelEstablished { peer_id: id, port } if id == peer_id => return Ok(port),
+            DaemonEvent::TunnelFailed { peer_id: id, reason } if id == peer_id => {
+                return Err(RiftError::ConnectionFailed { peer_id, reason });
+            }
+            DaemonEvent::ConnectCancelled { peer_id: id, .. } if id == peer_id => {
+                return Err(RiftError::ConnectionFailed { peer_id, reason: "cancelled".to_string() });
+            }
+            DaemonEvent::Shutdown => break,
+            _ => {}
+        }
+    }
+    Err(RiftError::TunnelNotEstablished)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shared_services_name_ports() {
+        let config = RiftConfig::default()
+            .with_service_name("db", 5432)
+            .with_service_name("api", 3000)
+            .with_sni_route("app.localhost", 3443);
+        let services = shared_services(&config, &[5432, 8080]);
+        let listed: Vec<(Option<&str>, u16)> = services.iter().map(|s| (s.name.as_deref(), s.port)).collect();
+        assert_eq!(listed, vec![(Some("db"), 5432), (None, 8080), (Some("app.localhost"), 3443)]);
+    }
+
+    #[test]
+    fn test_share_target_picks_requested_port() {
+        assert_eq!(share_target(3000, &[8080]), Some(8080));
+        assert_eq!(share_target(5432, &[3000, 8080, 5432]), Some(5432));
+        assert_eq!(share_target(9999, &[3000, 8080]), None);
+        assert_eq!(share_target(3000, &[]), None);
+    }
+
+    #[test]
+    fn test_require_port_asks_approved_peer() {
+        // Approved on a session port, then opening a port that requires approval
+        assert_eq!(admission(PortApproval::Session, false, true, false), Admission::Bridge);
+        assert_eq!(admission(PortApproval::Require, true, true, false), Admission::Ask);
+        assert_eq!(admission(PortApproval::Require, true, true, true), Admission::Bridge);
+
+        assert_eq!(admission(PortApproval::Session, false, false, false), Admission::GraceOrAsk);
+        assert_eq!(admission(PortApproval::Auto, false, false, false), Admission::Bridge);
+    }
+
+    #[test]
+    fn test_session_counters_reset_independently() {
+        let stats = TrafficStats::default();
+        stats.bytes_sent.fetch_add(100, Ordering::Relaxed);
+        stats.bytes_received.fetch_add(40, Ordering::Relaxed);
+        assert_eq!(stats.session_bytes(), (100, 40));
+
+        stats.reset_session();
+        assert_eq!(stats.session_bytes(), (0, 0));
+
+        stats.bytes_sent.fetch_add(5, Ordering::Relaxed);
+        assert_eq!(stats.session_bytes(), (5, 0));
+        assert_eq!(stats.bytes_sent.load(Ordering::Relaxed), 105);
+        assert_eq!(stats.bytes_received.load(Ordering::Relaxed), 40);
+    }
+
+    #[tokio::test]
+    async fn test_tunnel_ready_waits_for_its_own_peer() {
+        let (events, _rx) = EventSender::channel(8);
+        let (host, other) = (PeerId::random().to_string(), PeerId::random().to_string());
+
+        let ready = tokio::spawn(tunnel_ready(events.subscribe(), host.clone()));
+        let failed = tokio::spawn(tunnel_ready(events.subscribe(), other.clone()));
+        let stopped = tokio::spawn(tunnel_ready(events.subscribe(), "unrelated".to_string()));
+        events.send(DaemonEvent::TunnelFailed { peer_id: other, reason: "no route".to_string() }).await.unwrap();
+        events.send(DaemonEvent::TunnelEstablished { peer_id: host, port: 5432 }).await.unwrap();
+        events.send(DaemonEvent::Shutdown).await.unwrap();
+
+        assert_eq!(ready.await.unwrap().unwrap(), 5432);
+        assert!(matches!(failed.await.unwrap(), Err(RiftError::ConnectionFailed { reason, .. }) if reason == "no route"));
+        assert!(matches!(stopped.await.unwrap(), Err(RiftError::TunnelNotEstablished)));
+    }
+}
@@ -2,7 +2,7 @@
 //!
 //! Manages sharing and connecting sessions with the stream-based tunnel.
 
-use wh_core::{EnvVault, Result, secrets::SecretsResponse};
+use wh_core::{EnvVault, Result, Spool, SpoolFile, secrets::SecretsResponse};
 use std::path::PathBuf;
 use std::sync::atomic::AtomicU64;
 use std::sync::Arc;
@@ -187,10 +187,10 @@ impl ConnectSession {
         }
     }
 
-    /// Write secrets to a temp file
-    pub fn write_secrets_to_file(&self) -> Result<Option<PathBuf>> {
+    /// Write secrets to a spool file, deleted when the returned guard is dropped
+    pub fn write_secrets_to_file(&self, spool: &Spool) -> Result<Option<SpoolFile>> {
         if self.secrets_received && !self.env_vault.is_empty() {
-            Ok(Some(self.env_vault.write_to_temp_file()?))
+            Ok(Some(self.env_vault.write_to_temp_file(spool)?))
         } else {
             Ok(None)
         }
@@ -0,0 +1,138 @@
+//! Local Listener Sockets
+//!
+//! Binds the listeners `rift connect` accepts local clients on with the
+//! `socket_options` from the config (SO_REUSEPORT, backlog) and turns on TCP
+//! keepalive for the connections they accept.
+
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+use socket2::{SockRef, TcpKeepalive};
+use tokio::net::{lookup_host, TcpListener, TcpSocket, TcpStream, ToSocketAddrs};
+use tracing::debug;
+use wh_core::SocketOptionsConfig;
+
+/// Bind a listener on the first address `addr` resolves to that accepts it
+pub async fn bind_listener(addr: impl ToSocketAddrs, options: &SocketOptionsConfig) -> io::Result<TcpListener> {
+    let mut last_error = None;
+    for addr in lookup_host(addr).await? {
+        match bind_one(addr, options) {
+            Ok(listener) => return Ok(listener),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "address resolved to nothing")))
+}
+
+/// Accept on whichever of `listeners` takes a connection first, with the key it was given under
+///
+/// Never completes without listeners.
+pub async fn accept_any<'a, K: Copy>(
+    listeners: impl IntoIterator<Item = (K, &'a TcpListener)>,
+) -> (K, io::Result<(TcpStream, SocketAddr)>) {
+    let accepts: Vec<_> = listeners
+        .into_iter()
+        .map(|(key, listener)| Box::pin(async move { (key, listener.accept().await) }))
+        .collect();
+    if accepts.is_empty() {
+        return std::future::pending().await;
+    }
+    futures::future::select_all(accepts).await.0
+}
+
+fn bind_one(addr: SocketAddr, options: &SocketOptionsConfig) -> io::Result<TcpListener> {
+    let socket = if addr.is_ipv4() { TcpSocket::new_v4()? } else { TcpSocket::new_v6()? };
+    // As `TcpListener::bind` does, so a restarted session can take its port back at once
+    #[cfg(unix)]
+    socket.set_reuseaddr(true)?;
+    if options.reuse_port {
+        set_reuse_port(&socket)?;
+    }
+    socket.bind(addr)?;
+    socket.listen(options.backlog.max(1))
+}
+
+#[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos", target_os = "cygwin"))))]
+fn set_reuse_port(socket: &TcpSocket) -> io::Result<()> {
+    socket.set_reuseport(true)
+}
+
+#[cfg(not(all(unix, not(any(target_os = "solaris", target_os = "illumos", target_os = "cygwin")))))]
+fn set_reuse_port(_socket: &TcpSocket) -> io::Result<()> {
+    tracing::warn!("socket_options.reuse_port is not supported on this platform");
+    Ok(())
+}
+
+/// Turn on TCP keepalive for an accepted connection, if configured
+pub fn apply_keepalive(stream: &TcpStream, options: &SocketOptionsConfig) {
+    let Some(keepalive) = keepalive(options) else {
+        return;
+    };
+    if let Err(e) = SockRef::from(stream).set_tcp_keepalive(&keepalive) {
+        debug!("Failed to set keepalive: {}", e);
+    }
+}
+
+/// The keepalive `options` ask for; None when it is off
+fn keepalive(options: &SocketOptionsConfig) -> Option<TcpKeepalive> {
+    if options.keepalive_secs == 0 {
+        return None;
+    }
+    #[allow(unused_mut)]
+    let mut keepalive = TcpKeepalive::new().with_time(Duration::from_secs(options.keepalive_secs));
+    #[cfg(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd",
+        target_os = "netbsd",
+        target_os = "windows",
+    ))]
+    {
+        if options.keepalive_interval_secs > 0 {
+            keepalive = keepalive.with_interval(Duration::from_secs(options.keepalive_interval_secs));
+        }
+        if options.keepalive_retries > 0 {
+            keepalive = keepalive.with_retries(options.keepalive_retries);
+        }
+    }
+    Some(keepalive)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_reuse_port_lets_two_listeners_share_a_port() {
+        let options = SocketOptionsConfig {
+            reuse_port: true,
+            keepalive_secs: 30,
+            keepalive_interval_secs: 5,
+            ..SocketOptionsConfig::default()
+        };
+        let first = bind_listener("127.0.0.1:0", &options).await.unwrap();
+        let addr = first.local_addr().unwrap();
+        #[cfg(target_os = "linux")]
+        assert!(bind_listener(addr, &options).await.is_ok());
+        assert!(bind_listener(addr, &SocketOptionsConfig::default()).await.is_err());
+
+        let client = TcpStream::connect(addr).await.unwrap();
+        let (accepted, _) = first.accept().await.unwrap();
+        apply_keepalive(&accepted, &options);
+        assert!(SockRef::from(&accepted).keepalive().unwrap());
+        drop(client);
+    }
+
+    #[tokio::test]
+    async fn test_accept_any_names_the_listener() {
+        let options = SocketOptionsConfig::default();
+        let first = bind_listener("127.0.0.1:0", &options).await.unwrap();
+        let second = bind_listener("127.0.0.1:0", &options).await.unwrap();
+        let _client = TcpStream::connect(second.local_addr().unwrap()).await.unwrap();
+        let (key, accepted) = accept_any([(3000u16, &first), (5432, &second)]).await;
+        assert_eq!(key, 5432);
+        assert!(accepted.is_ok());
+    }
+}
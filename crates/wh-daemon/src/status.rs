@@ -0,0 +1,151 @@
+//! Status Snapshots
+//!
+//! Each running daemon periodically writes a small JSON snapshot of its stats
+//! into the state store so `rift status` can report on it from another process.
+
+use serde::{Deserialize, Serialize};
+use wh_core::stats::{LatencySummary, TrafficBreakdown};
+use wh_core::{Result, StateStore};
+
+use crate::connections::{ConnectionRecord, PeerStats, PortStats};
+use crate::tokens::unix_now;
+
+/// Directory (in the state store) holding one snapshot per daemon process
+pub const STATUS_DIR: &str = "status";
+
+/// Snapshots older than this are considered left over from a dead daemon
+pub const STALE_AFTER_SECS: u64 = 10;
+
+/// Stats of one running daemon
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusSnapshot {
+    /// Process ID of the daemon
+    pub pid: u32,
+    /// "share", "connect" or "idle"
+    pub mode: String,
+    /// Local peer ID
+    pub peer_id: String,
+    /// Shared or forwarded port (the first one, when sharing several)
+    pub port: Option<u16>,
+    /// Every shared port
+    #[serde(default)]
+    pub shared_ports: Vec<u16>,
+    /// Total bytes sent
+    pub bytes_sent: u64,
+    /// Total bytes received
+    pub bytes_received: u64,
+    /// Bytes sent since the session started or the counters were reset
+    #[serde(default)]
+    pub session_bytes_sent: u64,
+    /// Bytes received since the session started or the counters were reset
+    #[serde(default)]
+    pub session_bytes_received: u64,
+    /// Currently open tunnel connections
+    pub active_connections: u64,
+    /// Spawned daemon tasks still running (bridges, exchanges)
+    #[serde(default)]
+    pub live_tasks: usize,
+    /// Open tunnelled connections
+    #[serde(default)]
+    pub connections: Vec<ConnectionRecord>,
+    /// Connections and bytes per kind of traffic
+    #[serde(default)]
+    pub traffic_classes: TrafficBreakdown,
+    /// Connections and bytes per port
+    #[serde(default)]
+    pub ports: Vec<PortStats>,
+    /// Connections, bytes and current rate per peer
+    #[serde(default)]
+    pub peer_traffic: Vec<PeerStats>,
+    /// Connect side: local TCP accept to first byte relayed from the host
+    pub stream_open: LatencySummary,
+    /// Host side: stream accept to target TCP established
+    pub target_connect: LatencySummary,
+    /// Unix timestamp (seconds) of this snapshot
+    pub updated_at: u64,
+}
+
+impl StatusSnapshot {
+    /// Name of a process's snapshot in the state store
+    pub fn name_for(pid: u32) -> String {
+        format!("{}/{}.json", STATUS_DIR, pid)
+    }
+
+    /// Write the snapshot for this process
+    pub fn write(&self, store: &dyn StateStore) -> Result<()> {
+        store.save(&Self::name_for(self.pid), &serde_json::to_string_pretty(self)?)
+    }
+
+    /// Remove the snapshot for a process
+    pub fn remove(store: &dyn StateStore, pid: u32) {
+        let _ = store.remove(&Self::name_for(pid));
+    }
+
+    /// Read the snapshots of all live daemons, deleting stale ones
+    ///
+    /// Snapshots are saved atomically, so one that does not parse is corrupt
+    /// rather than mid-write and is deleted too.
+    pub fn read_all(store: &dyn StateStore) -> Vec<StatusSnapshot> {
+        let Ok(names) = store.list(&format!("{}/", STATUS_DIR)) else {
+            return Vec::new();
+        };
+
+        let now = unix_now();
+        let mut snapshots: Vec<StatusSnapshot> = names
+            .iter()
+            .filter_map(|name| {
+                let snapshot = store
+                    .load(name)
+                    .ok()
+                    .flatten()
+                    .and_then(|contents| serde_json::from_str::<StatusSnapshot>(&contents).ok());
+                match snapshot {
+                    Some(s) if now.saturating_sub(s.updated_at) <= STALE_AFTER_SECS => Some(s),
+                    _ => {
+                        let _ = store.remove(name);
+                        None
+                    }
+                }
+            })
+            .collect();
+
+        snapshots.sort_by_key(|s| s.pid);
+        snapshots
+    }
+
+    /// Fill in the timestamp
+    pub fn touch(mut self) -> Self {
+        self.updated_at = unix_now();
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_all_skips_stale_snapshots() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = wh_core::FileStore::new(dir.path().to_path_buf());
+
+        let live = StatusSnapshot {
+            pid: 1,
+            mode: "share".to_string(),
+            ..Default::default()
+        }
+        .touch();
+        live.write(&store).unwrap();
+
+        let stale = StatusSnapshot {
+            pid: 2,
+            updated_at: 1,
+            ..Default::default()
+        };
+        stale.write(&store).unwrap();
+
+        let snapshots = StatusSnapshot::read_all(&store);
+        assert_eq!(snapshots, vec![live]);
+        assert!(!store.path(&StatusSnapshot::name_for(2)).exists());
+    }
+}
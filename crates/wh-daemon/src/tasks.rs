@@ -0,0 +1,79 @@
+//! Task Supervision
+//!
+//! Every task the daemon spawns (stream bridges, secrets exchanges, control
+//! messages) goes through a [`TaskSet`] so shutdown can cancel them and wait
+//! for them to finish instead of leaving them running detached.
+
+use std::future::Future;
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+
+/// Tracked, cancellable set of spawned tasks
+#[derive(Debug, Clone, Default)]
+pub struct TaskSet {
+    tracker: TaskTracker,
+    token: CancellationToken,
+}
+
+impl TaskSet {
+    /// Create an empty task set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Spawn a task that is dropped as soon as the set is shut down
+    pub fn spawn<F>(&self, future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let token = self.token.clone();
+        self.tracker.spawn(async move {
+            token.run_until_cancelled_owned(future).await;
+        });
+    }
+
+    /// Token cancelled on shutdown, for loops that want to stop gracefully
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    /// Number of tasks still running
+    pub fn live(&self) -> usize {
+        self.tracker.len()
+    }
+
+    /// Cancel every task and wait until all of them have finished
+    pub async fn shutdown(&self) {
+        self.token.cancel();
+        self.tracker.close();
+        self.tracker.wait().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_shutdown_cancels_and_waits() {
+        let tasks = TaskSet::new();
+        let (done_tx, mut done_rx) = tokio::sync::mpsc::channel::<()>(1);
+
+        for _ in 0..3 {
+            let done_tx = done_tx.clone();
+            tasks.spawn(async move {
+                std::future::pending::<()>().await;
+                let _ = done_tx.send(()).await;
+            });
+        }
+        drop(done_tx);
+        assert_eq!(tasks.live(), 3);
+
+        tasks.shutdown().await;
+        assert_eq!(tasks.live(), 0);
+        assert!(tasks.token().is_cancelled());
+
+        // Cancelled tasks never reached their send; all senders are gone
+        assert!(done_rx.recv().await.is_none());
+    }
+}
@@ -0,0 +1,217 @@
+//! Session Resumption Tokens
+//!
+//! After a peer is approved the host issues it a short-lived token. If the
+//! connection drops and the peer comes back within the TTL, presenting the
+//! token skips the approval prompt. Tokens are persisted in the state store.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::warn;
+use wh_core::crypto::constant_time_eq;
+use wh_core::StateStore;
+
+/// Name of the token list in the state store
+pub const TOKENS_FILE: &str = "session_tokens.json";
+
+/// A resumption token issued to a peer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionToken {
+    /// Opaque token value
+    pub token: String,
+    /// Peer the token was issued to
+    pub peer_id: String,
+    /// Unix timestamp (seconds) of issuance
+    pub issued_at: u64,
+    /// Unix timestamp (seconds) after which the token is invalid
+    pub expires_at: u64,
+    /// Whether the token was revoked before expiry
+    pub revoked: bool,
+}
+
+impl SessionToken {
+    /// Check whether the token can still be used
+    pub fn is_valid(&self, now: u64) -> bool {
+        !self.revoked && now < self.expires_at
+    }
+}
+
+/// Persistent store of issued session tokens
+#[derive(Debug, Default)]
+pub struct SessionTokenStore {
+    /// Where the tokens are kept (None = in-memory only)
+    store: Option<Arc<dyn StateStore>>,
+    /// Tokens keyed by token value
+    tokens: HashMap<String, SessionToken>,
+}
+
+impl SessionTokenStore {
+    /// Create an in-memory store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the tokens from a state store, starting empty if they are missing or unreadable
+    pub fn open(state: Arc<dyn StateStore>) -> Self {
+        let tokens = match state.load(TOKENS_FILE) {
+            Ok(Some(contents)) => serde_json::from_str::<Vec<SessionToken>>(&contents)
+                .map(|list| list.into_iter().map(|t| (t.token.clone(), t)).collect())
+                .unwrap_or_else(|e| {
+                    warn!("Ignoring unreadable token store {}: {}", state.location(TOKENS_FILE), e);
+                    HashMap::new()
+                }),
+            Ok(None) => HashMap::new(),
+            Err(e) => {
+                warn!("Failed to read session tokens {}: {}", state.location(TOKENS_FILE), e);
+                HashMap::new()
+            }
+        };
+
+        let mut store = Self {
+            store: Some(state),
+            tokens,
+        };
+        store.prune(unix_now());
+        store
+    }
+
+    /// Issue a new token for a peer
+    pub fn issue(&mut self, peer_id: &str, ttl_secs: u64) -> SessionToken {
+        let now = unix_now();
+        let token = SessionToken {
+            token: generate_token(),
+            peer_id: peer_id.to_string(),
+            issued_at: now,
+            expires_at: now + ttl_secs,
+            revoked: false,
+        };
+        self.tokens.insert(token.token.clone(), token.clone());
+        self.persist();
+        token
+    }
+
+    /// Validate a token presented by a peer
+    ///
+    /// The peer's tokens are compared in constant time rather than looked up,
+    /// so timing does not tell how much of a guess was right.
+    pub fn validate(&self, peer_id: &str, token: &str) -> bool {
+        let now = unix_now();
+        self.tokens.values().any(|t| {
+            t.peer_id == peer_id && constant_time_eq(t.token.as_bytes(), token.as_bytes()) && t.is_valid(now)
+        })
+    }
+
+    /// Revoke all tokens of a peer (or every token if `peer_id` is None)
+    ///
+    /// Returns the number of tokens revoked.
+    pub fn revoke(&mut self, peer_id: Option<&str>) -> usize {
+        let mut count = 0;
+        for token in self.tokens.values_mut() {
+            if !token.revoked && peer_id.is_none_or(|p| token.peer_id == p) {
+                token.revoked = true;
+                count += 1;
+            }
+        }
+        if count > 0 {
+            self.persist();
+        }
+        count
+    }
+
+    /// Drop expired tokens
+    pub fn prune(&mut self, now: u64) {
+        let before = self.tokens.len();
+        self.tokens.retain(|_, t| now < t.expires_at);
+        if self.tokens.len() != before {
+            self.persist();
+        }
+    }
+
+    /// Number of tokens currently held
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    /// Check if the store holds no tokens
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    fn persist(&self) {
+        let Some(store) = &self.store else {
+            return;
+        };
+
+        let list: Vec<&SessionToken> = self.tokens.values().collect();
+        let result = serde_json::to_string_pretty(&list)
+            .map_err(wh_core::RiftError::from)
+            .and_then(|json| store.save(TOKENS_FILE, &json));
+
+        if let Err(e) = result {
+            warn!("Failed to persist session tokens to {}: {}", store.location(TOKENS_FILE), e);
+        }
+    }
+}
+
+/// Current Unix time in seconds
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Generate a random 256-bit token, hex encoded
+pub(crate) fn generate_token() -> String {
+    let bytes: [u8; 32] = rand::random();
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_issue_and_validate() {
+        let mut store = SessionTokenStore::new();
+        let token = store.issue("peer-a", 60);
+
+        assert!(store.validate("peer-a", &token.token));
+        assert!(!store.validate("peer-b", &token.token));
+        assert!(!store.validate("peer-a", "not-a-token"));
+    }
+
+    #[test]
+    fn test_revoke_and_expiry() {
+        let mut store = SessionTokenStore::new();
+        let a = store.issue("peer-a", 60);
+        let b = store.issue("peer-b", 60);
+        let expired = store.issue("peer-c", 0);
+
+        assert_eq!(store.revoke(Some("peer-a")), 1);
+        assert!(!store.validate("peer-a", &a.token));
+        assert!(store.validate("peer-b", &b.token));
+        assert!(!store.validate("peer-c", &expired.token));
+
+        store.prune(unix_now());
+        assert_eq!(store.len(), 2);
+    }
+
+    #[test]
+    fn test_persisted_tokens_survive_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let state: Arc<dyn StateStore> = Arc::new(wh_core::FileStore::new(dir.path().to_path_buf()));
+
+        let token = SessionTokenStore::open(state.clone()).issue("peer-a", 60);
+        let reloaded = SessionTokenStore::open(state);
+
+        assert!(reloaded.validate("peer-a", &token.token));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(dir.path().join(TOKENS_FILE)).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+}
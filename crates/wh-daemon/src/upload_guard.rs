@@ -0,0 +1,157 @@
+//! Upload Size Guard
+//!
+//! Share-side limit on HTTP request bodies arriving through the tunnel, so a
+//! peer cannot push an arbitrarily large upload into the local service.
+//! Traffic that does not parse as HTTP is not limited.
+
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::AsyncWrite;
+
+use crate::http_log::{Body, MessageHead, MessageParser, MessageSink};
+
+/// Tracks request bodies on one connection against a size limit
+#[derive(Debug)]
+pub struct UploadGuard {
+    parser: MessageParser,
+    max_body: u64,
+    body: u64,
+    exceeded: bool,
+}
+
+struct GuardSink<'a> {
+    max_body: u64,
+    body: &'a mut u64,
+    exceeded: &'a mut bool,
+}
+
+impl MessageSink for GuardSink<'_> {
+    fn head(&mut self, head: MessageHead) -> Option<Body> {
+        let mut parts = head.start_line.split_whitespace();
+        let (_method, _path, version) = (parts.next()?, parts.next()?, parts.next()?);
+        if !version.starts_with("HTTP/1.") {
+            return None;
+        }
+        *self.body = 0;
+        let body = head.declared_body().unwrap_or(Body::Length(0));
+        // Refuse declared oversized bodies before any of them is forwarded
+        if matches!(body, Body::Length(len) if len > self.max_body) {
+            *self.exceeded = true;
+        }
+        Some(body)
+    }
+
+    fn body(&mut self, len: usize) {
+        *self.body += len as u64;
+        if *self.body > self.max_body {
+            *self.exceeded = true;
+        }
+    }
+}
+
+impl UploadGuard {
+    /// Create a guard allowing request bodies of at most `max_body` bytes
+    pub fn new(max_body: u64) -> Self {
+        Self {
+            parser: MessageParser::new(None),
+            max_body,
+            body: 0,
+            exceeded: false,
+        }
+    }
+
+    /// Observe bytes headed for the local service; `false` once the limit is exceeded
+    pub fn check(&mut self, data: &[u8]) -> bool {
+        if !self.exceeded {
+            let mut sink = GuardSink {
+                max_body: self.max_body,
+                body: &mut self.body,
+                exceeded: &mut self.exceeded,
+            };
+            self.parser.feed(data, &mut sink);
+        }
+        !self.exceeded
+    }
+
+    /// Response sent back to the peer when a request is refused
+    pub fn rejection(&self) -> Vec<u8> {
+        let body = format!("Request body exceeds the {} byte limit of this share\n", self.max_body);
+        format!(
+            "HTTP/1.1 413 Payload Too Large\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )
+        .into_bytes()
+    }
+}
+
+/// Writer failing once the bytes written through it exceed an [`UploadGuard`]
+///
+/// Used where no response can be sent back (striped transfers); the limit
+/// may be overshot by up to one write.
+pub struct GuardedWriter<W> {
+    inner: W,
+    guard: UploadGuard,
+}
+
+impl<W> GuardedWriter<W> {
+    /// Wrap `inner`, enforcing `guard`
+    pub fn new(inner: W, guard: UploadGuard) -> Self {
+        Self { inner, guard }
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for GuardedWriter<W> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        match Pin::new(&mut this.inner).poll_write(cx, buf) {
+            Poll::Ready(Ok(n)) if !this.guard.check(&buf[..n]) => Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("request body exceeds the {} byte limit", this.guard.max_body),
+            ))),
+            poll => poll,
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rejects_declared_and_streamed_oversized_bodies() {
+        let mut guard = UploadGuard::new(10);
+        assert!(guard.check(b"POST /a HTTP/1.1\r\nContent-Length: 10\r\n\r\n0123456789"));
+        assert!(guard.check(b"GET /b HTTP/1.1\r\n\r\n"));
+        assert!(!guard.check(b"POST /c HTTP/1.1\r\nContent-Length: 11\r\n\r\n"));
+
+        let mut guard = UploadGuard::new(10);
+        assert!(guard.check(b"POST /d HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n8\r\n01234567\r\n"));
+        assert!(!guard.check(b"8\r\n01234567\r\n"));
+        assert!(String::from_utf8(guard.rejection()).unwrap().starts_with("HTTP/1.1 413"));
+
+        // TLS and other non-HTTP traffic is not limited
+        let mut guard = UploadGuard::new(10);
+        assert!(guard.check(b"\x16\x03\x01\x02\x00 client hello\r\n\r\n"));
+        assert!(guard.check(&[0u8; 100]));
+    }
+
+    #[tokio::test]
+    async fn test_guarded_writer_fails_past_limit() {
+        use tokio::io::AsyncWriteExt;
+
+        let mut writer = GuardedWriter::new(Vec::new(), UploadGuard::new(4));
+        writer.write_all(b"PUT /f HTTP/1.1\r\nContent-Length: 4\r\n\r\nabcd").await.unwrap();
+        writer.write_all(b"PUT /g HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n").await.unwrap();
+        assert!(writer.write_all(b"5\r\nabcde\r\n").await.is_err());
+    }
+}
@@ -0,0 +1,14 @@
+//! Tunnel protocol messages decoded from peer bytes
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use wh_core::protocol::Message;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(message) = Message::from_bytes(data) {
+        // Anything we accept must survive a round trip
+        let bytes = message.to_bytes().expect("decoded message re-encodes");
+        Message::from_bytes(&bytes).expect("re-encoded message decodes");
+    }
+});
@@ -0,0 +1,15 @@
+//! `rift://<peer id>` links pasted by users or scanned from QR codes
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use wh_core::PeerIdentity;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(link) = std::str::from_utf8(data) {
+        if let Ok(peer_id) = PeerIdentity::parse_rift_link(link) {
+            let again = PeerIdentity::parse_rift_link(&format!("rift://{}", peer_id)).expect("canonical link parses");
+            assert_eq!(again, peer_id);
+        }
+    }
+});
@@ -0,0 +1,12 @@
+//! Length-prefixed secrets exchange frames read from a peer stream
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use wh_core::receive_secrets;
+use wh_core::secrets::{SecretsRequest, SecretsResponse};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = futures::executor::block_on(receive_secrets::<SecretsRequest, _>(&mut &data[..]));
+    let _ = futures::executor::block_on(receive_secrets::<SecretsResponse, _>(&mut &data[..]));
+});